@@ -0,0 +1,6 @@
+/* <stdarg.h> as alecc ships it. va_start, va_arg, va_copy and va_end are
+   built into the compiler. */
+#define __GNUC_VA_LIST 1
+
+typedef __builtin_va_list va_list;
+typedef __builtin_va_list __gnuc_va_list;
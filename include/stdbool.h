@@ -0,0 +1,5 @@
+/* <stdbool.h> as alecc ships it. bool is a keyword in every language mode
+   alecc supports. */
+#define true 1
+#define false 0
+#define __bool_true_false_are_defined 1
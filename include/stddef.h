@@ -0,0 +1,7 @@
+/* <stddef.h> as alecc ships it. The types come from macros the compiler
+   predefines for the target. */
+#define NULL ((void*)0)
+
+typedef __SIZE_TYPE__ size_t;
+typedef __PTRDIFF_TYPE__ ptrdiff_t;
+typedef __WCHAR_TYPE__ wchar_t;
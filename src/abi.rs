@@ -0,0 +1,110 @@
+use crate::layout::TypeLayout;
+use crate::parser::Type;
+use crate::targets::Target;
+
+/// The part of a struct or union passed by value that one register carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Piece {
+    pub offset: usize,
+    pub size: usize,
+    /// Carried in a floating-point register rather than an integer one
+    pub floating: bool,
+}
+
+/// How a struct or union is passed and returned by value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregatePassing {
+    /// Piece by piece in registers, as long as enough of them are left for
+    /// every piece; otherwise the whole value goes on the stack
+    Registers(Vec<Piece>),
+    /// In memory. As an argument, amd64 and i386 copy it to the stack and
+    /// arm64 passes the address of a copy the caller makes; as a result, the
+    /// caller passes the address to store it at (rdi on amd64, x8 on arm64,
+    /// and on i386 a first argument the callee pops)
+    Memory,
+}
+
+/// How the SysV (amd64 and i386) and AAPCS64 (arm64) ABIs pass a value of
+/// type `ty`, or `None` when it is not a struct or union. The i386 ABI puts
+/// every struct and union in memory, whatever its size.
+pub fn aggregate_passing(
+    target: Target,
+    layout: &TypeLayout,
+    ty: &Type,
+) -> Option<AggregatePassing> {
+    let ty = layout.resolve(ty);
+    if !matches!(ty, Type::Struct { .. } | Type::Union { .. }) {
+        return None;
+    }
+    let size = layout.size_of(&ty);
+    let scalars = layout.scalars(&ty);
+    Some(match target {
+        Target::I386 => AggregatePassing::Memory,
+        Target::Amd64 => amd64_passing(layout, size, &scalars),
+        Target::Arm64 => arm64_passing(layout, size, &scalars),
+    })
+}
+
+/// SysV classification: up to 16 bytes go in eightbytes, each an SSE one when
+/// it holds only `float`s and `double`s and an INTEGER one otherwise. Bigger
+/// aggregates, and ones with misaligned members, are MEMORY.
+fn amd64_passing(layout: &TypeLayout, size: usize, scalars: &[(usize, Type)]) -> AggregatePassing {
+    let misaligned = scalars
+        .iter()
+        .any(|(offset, ty)| offset % layout.align_of(ty) != 0);
+    if size > 16 || misaligned {
+        return AggregatePassing::Memory;
+    }
+    let pieces = (0..size.div_ceil(8))
+        .map(|index| {
+            let offset = index * 8;
+            let floating = scalars
+                .iter()
+                .filter(|(at, _)| (offset..offset + 8).contains(at))
+                .all(|(_, ty)| matches!(ty, Type::Float | Type::Double));
+            Piece {
+                offset,
+                size: (size - offset).min(8),
+                floating,
+            }
+        })
+        .collect();
+    AggregatePassing::Registers(pieces)
+}
+
+/// AAPCS64: a homogeneous floating-point aggregate (one to four members of the
+/// same floating type) has a register per member; anything else up to 16
+/// bytes goes in general registers, and bigger ones by reference.
+fn arm64_passing(layout: &TypeLayout, size: usize, scalars: &[(usize, Type)]) -> AggregatePassing {
+    if let Some((_, first)) = scalars.first() {
+        let member = layout.size_of(first);
+        let homogeneous = matches!(first, Type::Float | Type::Double)
+            && scalars.len() <= 4
+            && scalars.iter().enumerate().all(|(index, (offset, ty))| {
+                std::mem::discriminant(ty) == std::mem::discriminant(first)
+                    && *offset == index * member
+            })
+            && size == scalars.len() * member;
+        if homogeneous {
+            let pieces = (0..scalars.len())
+                .map(|index| Piece {
+                    offset: index * member,
+                    size: member,
+                    floating: true,
+                })
+                .collect();
+            return AggregatePassing::Registers(pieces);
+        }
+    }
+    if size > 16 {
+        return AggregatePassing::Memory;
+    }
+    let pieces = (0..size.div_ceil(8))
+        .map(|index| Piece {
+            offset: index * 8,
+            size: (size - index * 8).min(8),
+            floating: false,
+        })
+        .collect();
+    AggregatePassing::Registers(pieces)
+}
@@ -0,0 +1,1749 @@
+use crate::builtins::moves_stack_pointer;
+use crate::callgraph::{CallGraph, CallKind};
+use crate::cli::StackProtector;
+use crate::constant::integer_constant;
+use crate::layout::TypeLayout;
+use crate::lexer::Span;
+use crate::parser::{
+    is_null_pointer_constant, AssignmentOperator, BinaryOperator, Expression, ExpressionKind,
+    Function, Program, Statement, StatementKind, Type, UnaryOperator,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// A read of a local variable that no assignment reaches on some path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UninitializedUse {
+    pub function: String,
+    pub variable: String,
+    /// No path assigns the variable before this read; otherwise only some do
+    pub definitely: bool,
+    /// The read
+    pub span: Span,
+}
+
+/// Find reads of locals that may happen before any assignment, with a forward
+/// dataflow pass over the function body.
+///
+/// The analysis is conservative about what counts as initialization: taking a
+/// variable's address, and declaring arrays, aggregates or `va_list`s (which
+/// are filled in through pointers), count as initializing it. Each name is
+/// reported at most once.
+pub fn uninitialized_uses(function: &Function) -> Vec<UninitializedUse> {
+    let mut analysis = Analysis {
+        function: &function.name,
+        scopes: vec![HashMap::new()],
+        reported: Vec::new(),
+        uses: Vec::new(),
+        report: true,
+    };
+    let mut state = Some(Vec::new());
+    analysis.statement(&function.body, &mut state);
+    analysis.uses
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Init {
+    No,
+    Maybe,
+    Yes,
+}
+
+impl Init {
+    fn join(self, other: Init) -> Init {
+        if self == other {
+            self
+        } else {
+            Init::Maybe
+        }
+    }
+}
+
+/// Initialization of every declared local, by declaration index; `None` when
+/// the current point is unreachable (e.g. after `return`)
+type State = Option<Vec<Init>>;
+
+fn join(a: &State, b: &State) -> State {
+    match (a, b) {
+        (None, state) | (state, None) => state.clone(),
+        (Some(a), Some(b)) => {
+            // Variables declared on only one side are out of scope after the join
+            Some(a.iter().zip(b).map(|(a, b)| a.join(*b)).collect())
+        }
+    }
+}
+
+struct Analysis<'a> {
+    function: &'a str,
+    /// Declaration index of each visible local, innermost scope last
+    scopes: Vec<HashMap<String, usize>>,
+    /// Names already reported, so each variable warns once
+    reported: Vec<String>,
+    uses: Vec<UninitializedUse>,
+    /// Off while iterating loops to a fixed point, so uses are reported from the final pass only
+    report: bool,
+}
+
+impl Analysis<'_> {
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+    }
+
+    fn statement(&mut self, statement: &Statement, state: &mut State) {
+        match &statement.kind {
+            StatementKind::Expression(expr) => self.expression(expr, state),
+            StatementKind::Declaration {
+                name,
+                var_type,
+                initializer,
+                ..
+            } => {
+                if let Some(init) = initializer {
+                    self.expression(init, state);
+                }
+                let initialized = initializer.is_some() || filled_indirectly(var_type);
+                let Some(vars) = state else {
+                    return;
+                };
+                let index = vars.len();
+                vars.push(if initialized { Init::Yes } else { Init::No });
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), index);
+                }
+            }
+            StatementKind::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                let outer_len = state.as_ref().map(Vec::len);
+                for statement in statements {
+                    self.statement(statement, state);
+                }
+                self.scopes.pop();
+                if let (Some(vars), Some(len)) = (state.as_mut(), outer_len) {
+                    vars.truncate(len);
+                }
+            }
+            StatementKind::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                self.expression(condition, state);
+                let mut then_state = state.clone();
+                self.branch(then_stmt, &mut then_state);
+                let mut else_state = state.clone();
+                if let Some(else_stmt) = else_stmt {
+                    self.branch(else_stmt, &mut else_state);
+                }
+                *state = join(&then_state, &else_state);
+            }
+            StatementKind::While { condition, body } => {
+                self.scopes.push(HashMap::new());
+                let head = self.loop_head(state, |analysis, state| {
+                    analysis.expression(condition, state);
+                    analysis.branch(body, state);
+                });
+                // The condition runs once more than the body, and is where the loop exits
+                let mut exit = head;
+                self.expression(condition, &mut exit);
+                let mut body_state = exit.clone();
+                self.branch(body, &mut body_state);
+                self.scopes.pop();
+                *state = exit;
+            }
+            StatementKind::DoWhile { body, condition } => {
+                self.scopes.push(HashMap::new());
+                let head = self.loop_head(state, |analysis, state| {
+                    analysis.branch(body, state);
+                    analysis.expression(condition, state);
+                });
+                let mut exit = head;
+                self.branch(body, &mut exit);
+                self.expression(condition, &mut exit);
+                self.scopes.pop();
+                *state = exit;
+            }
+            StatementKind::For {
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                self.scopes.push(HashMap::new());
+                let outer_len = state.as_ref().map(Vec::len);
+                if let Some(init) = init {
+                    self.statement(init, state);
+                }
+                let head = self.loop_head(state, |analysis, state| {
+                    if let Some(condition) = condition {
+                        analysis.expression(condition, state);
+                    }
+                    analysis.branch(body, state);
+                    if let Some(increment) = increment {
+                        analysis.expression(increment, state);
+                    }
+                });
+                let mut exit = head;
+                if let Some(condition) = condition {
+                    self.expression(condition, &mut exit);
+                }
+                let mut body_state = exit.clone();
+                self.branch(body, &mut body_state);
+                if let Some(increment) = increment {
+                    self.expression(increment, &mut body_state);
+                }
+                self.scopes.pop();
+                if let (Some(vars), Some(len)) = (exit.as_mut(), outer_len) {
+                    vars.truncate(len);
+                }
+                *state = exit;
+            }
+            StatementKind::Switch { expression, cases } => {
+                self.expression(expression, state);
+                let entry = state.clone();
+                let mut exit = state.clone();
+                for (_, statements) in cases {
+                    let mut case_state = entry.clone();
+                    self.scopes.push(HashMap::new());
+                    for statement in statements {
+                        self.statement(statement, &mut case_state);
+                    }
+                    self.scopes.pop();
+                    exit = join(&exit, &case_state);
+                }
+                *state = exit;
+            }
+            StatementKind::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr, state);
+                }
+                *state = None;
+            }
+            StatementKind::Fallthrough => {}
+            StatementKind::ComputedGoto(address) => {
+                self.expression(address, state);
+                if let Some(vars) = state {
+                    vars.iter_mut().for_each(|init| *init = Init::Yes);
+                }
+            }
+            StatementKind::Break
+            | StatementKind::Continue
+            | StatementKind::Goto(_)
+            | StatementKind::Label(_) => {
+                // Unstructured jumps aren't modelled; assume every variable may be set
+                if let Some(vars) = state {
+                    vars.iter_mut().for_each(|init| *init = Init::Yes);
+                }
+            }
+        }
+    }
+
+    /// A sub-statement whose own declarations go out of scope when it ends
+    fn branch(&mut self, statement: &Statement, state: &mut State) {
+        let outer_len = state.as_ref().map(Vec::len);
+        self.scopes.push(HashMap::new());
+        self.statement(statement, state);
+        self.scopes.pop();
+        if let (Some(vars), Some(len)) = (state.as_mut(), outer_len) {
+            vars.truncate(len);
+        }
+    }
+
+    /// The state at the top of a loop: the entry state joined with the state
+    /// after each further iteration, until nothing changes
+    fn loop_head(&mut self, entry: &State, iteration: impl Fn(&mut Self, &mut State)) -> State {
+        let report = std::mem::replace(&mut self.report, false);
+        let mut head = entry.clone();
+        loop {
+            let mut state = head.clone();
+            iteration(self, &mut state);
+            let next = join(&head, &state);
+            if next == head {
+                break;
+            }
+            head = next;
+        }
+        self.report = report;
+        head
+    }
+
+    fn expression(&mut self, expr: &Expression, state: &mut State) {
+        match &expr.kind {
+            ExpressionKind::Identifier(name) => self.read(name, expr.span, state),
+            ExpressionKind::Binary {
+                left,
+                operator: BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr,
+                right,
+            } => {
+                // The right operand may not run
+                self.expression(left, state);
+                let mut right_state = state.clone();
+                self.expression(right, &mut right_state);
+                *state = join(state, &right_state);
+            }
+            ExpressionKind::Binary { left, right, .. } => {
+                self.expression(left, state);
+                self.expression(right, state);
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => match &operand.kind {
+                // Whatever the pointer is used for, assume it initializes the variable
+                ExpressionKind::Identifier(name) => self.write(name, state),
+                _ => self.expression(operand, state),
+            },
+            ExpressionKind::Unary { operand, .. } => self.expression(operand, state),
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => {
+                if !matches!(&function.kind, ExpressionKind::Identifier(_)) {
+                    self.expression(function, state);
+                }
+                for argument in arguments {
+                    self.expression(argument, state);
+                }
+            }
+            ExpressionKind::Member { object, .. } => self.expression(object, state),
+            ExpressionKind::Index { array, index } => {
+                self.expression(array, state);
+                self.expression(index, state);
+            }
+            // `(void)x` only says `x` is deliberately unused; it reads nothing
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } if is_void(target_type)
+                && matches!(expression.kind, ExpressionKind::Identifier(_)) => {}
+            ExpressionKind::Cast { expression, .. } => self.expression(expression, state),
+            ExpressionKind::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                self.expression(value, state);
+                match &target.kind {
+                    ExpressionKind::Identifier(name) => {
+                        if !matches!(operator, AssignmentOperator::Assign) {
+                            self.read(name, target.span, state);
+                        }
+                        self.write(name, state);
+                    }
+                    _ => self.expression(target, state),
+                }
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expression(condition, state);
+                let mut then_state = state.clone();
+                self.expression(then_expr, &mut then_state);
+                self.expression(else_expr, state);
+                *state = join(&then_state, state);
+            }
+            ExpressionKind::VaArg { list, .. } => self.expression(list, state),
+            ExpressionKind::IntegerLiteral(_)
+            | ExpressionKind::FloatLiteral(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::CharLiteral(_)
+            | ExpressionKind::BooleanLiteral(_)
+            | ExpressionKind::Sizeof(_)
+            | ExpressionKind::LabelAddress(_) => {}
+        }
+    }
+
+    fn read(&mut self, name: &str, span: Span, state: &State) {
+        let (Some(index), Some(vars)) = (self.lookup(name), state) else {
+            return;
+        };
+        let init = vars[index];
+        if init == Init::Yes || !self.report || self.reported.iter().any(|n| n == name) {
+            return;
+        }
+        self.reported.push(name.to_string());
+        self.uses.push(UninitializedUse {
+            function: self.function.to_string(),
+            variable: name.to_string(),
+            definitely: init == Init::No,
+            span,
+        });
+    }
+
+    fn write(&mut self, name: &str, state: &mut State) {
+        if let (Some(index), Some(vars)) = (self.lookup(name), state.as_mut()) {
+            vars[index] = Init::Yes;
+        }
+    }
+}
+
+/// Objects that are normally set up through a pointer rather than assigned
+fn filled_indirectly(ty: &Type) -> bool {
+    match ty {
+        Type::Array(..) | Type::Struct { .. } | Type::Union { .. } | Type::VaList => true,
+        Type::Typedef(_, aliased) | Type::Qualified(_, aliased) => filled_indirectly(aliased),
+        _ => false,
+    }
+}
+
+/// How a function declared `noreturn` can return anyway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoreturnViolation {
+    /// The body has a `return` statement
+    ReturnStatement,
+    /// Control can reach the end of the body
+    FallsOff,
+}
+
+/// Check the body of a `noreturn` function. A path ends at a call to one of the
+/// `noreturn` functions, to `exit` or `abort`, or in a loop with a constant true
+/// condition and no `break`.
+pub fn noreturn_violations(
+    function: &Function,
+    noreturn: &HashSet<String>,
+) -> Vec<NoreturnViolation> {
+    let mut violations = Vec::new();
+    if contains_return(&function.body) {
+        violations.push(NoreturnViolation::ReturnStatement);
+    }
+    if completes(&function.body, noreturn) {
+        violations.push(NoreturnViolation::FallsOff);
+    }
+    violations
+}
+
+/// The functions of `program` declared `noreturn`
+pub fn noreturn_functions(program: &Program) -> HashSet<String> {
+    program
+        .functions
+        .iter()
+        .filter(|f| f.attributes.noreturn)
+        .map(|f| f.name.clone())
+        .collect()
+}
+
+/// Whether `statement` calls one of the `noreturn` functions, or a C library
+/// function that never returns
+pub fn is_noreturn_call(statement: &Statement, noreturn: &HashSet<String>) -> bool {
+    matches!(
+        &statement.kind,
+        StatementKind::Expression(Expression {
+            kind: ExpressionKind::Call { function, .. },
+            ..
+        }) if matches!(
+            &function.kind,
+            ExpressionKind::Identifier(name) if is_noreturn_function(name, noreturn)
+        )
+    )
+}
+
+/// Whether `name` is one of the `noreturn` functions, or a C library function
+/// that never returns
+pub fn is_noreturn_function(name: &str, noreturn: &HashSet<String>) -> bool {
+    noreturn.contains(name)
+        || matches!(
+            name,
+            "exit"
+                | "abort"
+                | "_exit"
+                | "_Exit"
+                | "__builtin_abort"
+                | "__builtin_trap"
+                | "__builtin_unreachable"
+        )
+}
+
+/// Whether `name` is a function that may return more than once, like
+/// `setjmp`: a `longjmp` resumes its caller with the registers it had then
+pub fn returns_twice(name: &str) -> bool {
+    let name = name.trim_start_matches('_');
+    matches!(
+        name,
+        "setjmp" | "sigsetjmp" | "savectx" | "vfork" | "getcontext" | "builtin_setjmp"
+    )
+}
+
+/// The functions of `program` that call a function returning twice. Their
+/// locals must stay in memory, where a `longjmp` finds them as they were
+/// last stored.
+pub fn setjmp_callers(program: &Program) -> HashSet<String> {
+    CallGraph::build(program)
+        .calls()
+        .into_iter()
+        .filter(|call| call.kind == CallKind::Direct && returns_twice(&call.callee))
+        .map(|call| call.caller)
+        .collect()
+}
+
+/// Whether control can flow out of the end of `statement`
+pub fn completes(statement: &Statement, noreturn: &HashSet<String>) -> bool {
+    match &statement.kind {
+        StatementKind::Expression(_) => !is_noreturn_call(statement, noreturn),
+        StatementKind::Block(statements) => completes_all(statements, noreturn),
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            completes(then_stmt, noreturn)
+                || else_stmt
+                    .as_ref()
+                    .is_none_or(|else_stmt| completes(else_stmt, noreturn))
+        }
+        StatementKind::While { condition, body } | StatementKind::DoWhile { body, condition } => {
+            !is_always_true(Some(condition)) || breaks_out(body)
+        }
+        StatementKind::For {
+            condition, body, ..
+        } => !is_always_true(condition.as_ref()) || breaks_out(body),
+        // Without a default, a value matching no case skips the whole switch
+        StatementKind::Switch { cases, .. } => {
+            cases.iter().all(|(value, _)| value.is_some())
+                || cases
+                    .iter()
+                    .flat_map(|(_, statements)| statements)
+                    .any(breaks_out)
+                || cases
+                    .last()
+                    .is_none_or(|(_, statements)| completes_all(statements, noreturn))
+        }
+        StatementKind::Return(_)
+        | StatementKind::Break
+        | StatementKind::Continue
+        | StatementKind::Goto(_)
+        | StatementKind::ComputedGoto(_) => false,
+        _ => true,
+    }
+}
+
+/// Whether control can reach the end of a sequence of statements
+fn completes_all(statements: &[Statement], noreturn: &HashSet<String>) -> bool {
+    let mut reachable = true;
+    for statement in statements {
+        if matches!(&statement.kind, StatementKind::Label(_)) {
+            // A goto may jump here
+            reachable = true;
+        } else if reachable && !completes(statement, noreturn) {
+            reachable = false;
+        }
+    }
+    reachable
+}
+
+/// A loop condition that never ends the loop; a missing `for` condition counts
+fn is_always_true(condition: Option<&Expression>) -> bool {
+    match condition.map(|condition| &condition.kind) {
+        None => true,
+        Some(ExpressionKind::IntegerLiteral(value)) => *value != 0,
+        Some(ExpressionKind::CharLiteral(value)) => *value != '\0',
+        Some(ExpressionKind::BooleanLiteral(value)) => *value,
+        Some(_) => false,
+    }
+}
+
+/// Whether a loop body has a `break` that leaves that loop
+fn breaks_out(statement: &Statement) -> bool {
+    match &statement.kind {
+        StatementKind::Break => true,
+        StatementKind::Block(statements) => statements.iter().any(breaks_out),
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => breaks_out(then_stmt) || else_stmt.as_deref().is_some_and(breaks_out),
+        // A `break` in a nested loop or switch leaves that instead
+        _ => false,
+    }
+}
+
+fn contains_return(statement: &Statement) -> bool {
+    let mut returns = Vec::new();
+    return_statements(statement, &mut returns);
+    !returns.is_empty()
+}
+
+/// Every `return` in `statement`, in source order
+fn return_statements<'a>(statement: &'a Statement, returns: &mut Vec<&'a Statement>) {
+    match &statement.kind {
+        StatementKind::Return(_) => returns.push(statement),
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                return_statements(statement, returns);
+            }
+        }
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            return_statements(then_stmt, returns);
+            if let Some(else_stmt) = else_stmt {
+                return_statements(else_stmt, returns);
+            }
+        }
+        StatementKind::While { body, .. }
+        | StatementKind::DoWhile { body, .. }
+        | StatementKind::For { body, .. } => return_statements(body, returns),
+        StatementKind::Switch { cases, .. } => {
+            for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                return_statements(statement, returns);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The last statement of each non-empty `switch` case group from whose end
+/// control can run into the next group's label, unless it is `[[fallthrough]];`
+pub fn implicit_fallthroughs(function: &Function, noreturn: &HashSet<String>) -> Vec<Span> {
+    let mut found = Vec::new();
+    fallthroughs(&function.body, noreturn, &mut found);
+    found
+}
+
+fn fallthroughs(statement: &Statement, noreturn: &HashSet<String>, found: &mut Vec<Span>) {
+    match &statement.kind {
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                fallthroughs(statement, noreturn, found);
+            }
+        }
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            fallthroughs(then_stmt, noreturn, found);
+            if let Some(else_stmt) = else_stmt {
+                fallthroughs(else_stmt, noreturn, found);
+            }
+        }
+        StatementKind::While { body, .. }
+        | StatementKind::DoWhile { body, .. }
+        | StatementKind::For { body, .. } => fallthroughs(body, noreturn, found),
+        StatementKind::Switch { cases, .. } => {
+            for (index, (_, statements)) in cases.iter().enumerate() {
+                for statement in statements {
+                    fallthroughs(statement, noreturn, found);
+                }
+                let Some(last) = statements.last() else {
+                    continue;
+                };
+                if index + 1 < cases.len()
+                    && !matches!(last.kind, StatementKind::Fallthrough)
+                    && completes_all(statements, noreturn)
+                {
+                    found.push(last.span);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A statement no path reaches, found by [`unreachable_code`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachableCode {
+    pub span: Span,
+    /// The statement control can't continue past
+    pub after: Span,
+    /// What that statement is: `'return'`, or `call to 'exit'`
+    pub terminator: String,
+}
+
+/// Statements that follow a `return`, `break`, `continue`, `goto` or call to
+/// a `noreturn` function in the same block, the first of each run. A label
+/// makes code reachable again; a `break` after a terminator is the usual end
+/// of a `switch` case, and declarations without an initializer run no code.
+pub fn unreachable_code(function: &Function, noreturn: &HashSet<String>) -> Vec<UnreachableCode> {
+    let mut found = Vec::new();
+    unreachable_in(&function.body, noreturn, &mut found);
+    found
+}
+
+fn unreachable_in(
+    statement: &Statement,
+    noreturn: &HashSet<String>,
+    found: &mut Vec<UnreachableCode>,
+) {
+    match &statement.kind {
+        StatementKind::Block(statements) => unreachable_after(statements, noreturn, found),
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            unreachable_in(then_stmt, noreturn, found);
+            if let Some(else_stmt) = else_stmt {
+                unreachable_in(else_stmt, noreturn, found);
+            }
+        }
+        StatementKind::While { body, .. }
+        | StatementKind::DoWhile { body, .. }
+        | StatementKind::For { body, .. } => unreachable_in(body, noreturn, found),
+        StatementKind::Switch { cases, .. } => {
+            for (_, statements) in cases {
+                unreachable_after(statements, noreturn, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn unreachable_after(
+    statements: &[Statement],
+    noreturn: &HashSet<String>,
+    found: &mut Vec<UnreachableCode>,
+) {
+    // The statement control stopped at, until the first unreachable one is reported
+    let mut dead: Option<Option<(&Statement, String)>> = None;
+    for statement in statements {
+        if matches!(statement.kind, StatementKind::Label(_)) {
+            dead = None;
+        }
+        if let Some(terminator) = &mut dead {
+            let runs_code = !matches!(
+                statement.kind,
+                StatementKind::Break
+                    | StatementKind::Fallthrough
+                    | StatementKind::Declaration {
+                        initializer: None,
+                        ..
+                    }
+            );
+            if runs_code {
+                if let Some((after, what)) = terminator.take() {
+                    found.push(UnreachableCode {
+                        span: statement.span,
+                        after: after.span,
+                        terminator: what,
+                    });
+                }
+            }
+            continue;
+        }
+        unreachable_in(statement, noreturn, found);
+        if let Some(what) = terminator_of(statement, noreturn) {
+            dead = Some(Some((statement, what)));
+        }
+    }
+}
+
+/// How to name `statement` if control never continues past it
+fn terminator_of(statement: &Statement, noreturn: &HashSet<String>) -> Option<String> {
+    let keyword = match &statement.kind {
+        StatementKind::Return(_) => "return",
+        StatementKind::Break => "break",
+        StatementKind::Continue => "continue",
+        StatementKind::Goto(_) | StatementKind::ComputedGoto(_) => "goto",
+        StatementKind::Expression(Expression {
+            kind: ExpressionKind::Call { function, .. },
+            ..
+        }) if is_noreturn_call(statement, noreturn) => {
+            return match &function.kind {
+                ExpressionKind::Identifier(name) => Some(format!("call to '{}'", name)),
+                _ => None,
+            };
+        }
+        _ => return None,
+    };
+    Some(format!("'{}'", keyword))
+}
+
+/// A way a function's returns disagree with its return type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnProblem {
+    /// Control can reach the end of a non-void function
+    FallsOff,
+    /// `return value;` in a void function
+    ValueInVoid(Span),
+    /// `return;` in a non-void function
+    MissingValue(Span),
+}
+
+/// Check a function's returns against its return type. Paths end as for
+/// [`noreturn_violations`]; `main` may fall off the end, which returns 0.
+/// `return f();` of a void function `f` is allowed in a void function.
+pub fn return_problems(
+    function: &Function,
+    noreturn: &HashSet<String>,
+    void_functions: &HashSet<String>,
+) -> Vec<ReturnProblem> {
+    let returns_void = is_void(&function.return_type);
+    let mut statements = Vec::new();
+    return_statements(&function.body, &mut statements);
+
+    let mut problems = Vec::new();
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::Return(Some(value))
+                if returns_void && !is_void_expression(value, void_functions) =>
+            {
+                problems.push(ReturnProblem::ValueInVoid(statement.span));
+            }
+            StatementKind::Return(None) if !returns_void => {
+                problems.push(ReturnProblem::MissingValue(statement.span));
+            }
+            _ => {}
+        }
+    }
+    if !returns_void && function.name != "main" && completes(&function.body, noreturn) {
+        problems.push(ReturnProblem::FallsOff);
+    }
+    problems
+}
+
+/// `void`, possibly qualified; a typedef name may stand for a type the parser never saw
+fn is_void(ty: &Type) -> bool {
+    match ty {
+        Type::Void => true,
+        Type::Qualified(_, inner) => is_void(inner),
+        _ => false,
+    }
+}
+
+fn is_void_expression(expression: &Expression, void_functions: &HashSet<String>) -> bool {
+    match &expression.kind {
+        ExpressionKind::Cast { target_type, .. } => is_void(target_type),
+        ExpressionKind::Call { function, .. } => matches!(
+            &function.kind,
+            ExpressionKind::Identifier(name) if void_functions.contains(name)
+        ),
+        _ => false,
+    }
+}
+
+/// Which `--analyze` check found a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalyzerCheck {
+    /// A pointer dereferenced where it can only be null
+    NullDereference,
+    /// A function returning the address of one of its locals or parameters
+    ReturnLocalAddress,
+    /// A constant index outside a fixed-size local array
+    OutOfBounds,
+}
+
+impl AnalyzerCheck {
+    /// The warning option that controls the check, without `-W`
+    pub fn option(self) -> &'static str {
+        match self {
+            AnalyzerCheck::NullDereference => "analyzer-null-dereference",
+            AnalyzerCheck::ReturnLocalAddress => "analyzer-return-local-addr",
+            AnalyzerCheck::OutOfBounds => "analyzer-out-of-bounds",
+        }
+    }
+}
+
+/// A problem reported by `--analyze`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzerFinding {
+    pub check: AnalyzerCheck,
+    pub function: String,
+    pub message: String,
+}
+
+/// The opt-in checks of `--analyze`, within one function: dereferences of
+/// pointers that a comparison or assignment shows to be null, returning the
+/// address of a local, and constant indices past the bounds of a local array.
+///
+/// Only what is certain on every path is reported, so a pointer is known to be
+/// null after `if (p == NULL)`, but no longer once `&p` is taken.
+pub fn analyze(function: &Function) -> Vec<AnalyzerFinding> {
+    let mut analyzer = Analyzer {
+        function: &function.name,
+        scopes: vec![function.parameters.iter().cloned().collect()],
+        findings: Vec::new(),
+    };
+    let mut nulls = Some(BTreeSet::new());
+    analyzer.statement(&function.body, &mut nulls);
+    analyzer.findings
+}
+
+/// Locals known to hold a null pointer; `None` where the point is unreachable
+type Nulls = Option<BTreeSet<String>>;
+
+fn join_nulls(a: &Nulls, b: &Nulls) -> Nulls {
+    match (a, b) {
+        (None, nulls) | (nulls, None) => nulls.clone(),
+        (Some(a), Some(b)) => Some(a.intersection(b).cloned().collect()),
+    }
+}
+
+/// What a condition shows about pointers when it is true or false
+#[derive(Debug, Default)]
+struct NullFacts {
+    null: Vec<String>,
+    non_null: Vec<String>,
+}
+
+impl NullFacts {
+    fn apply(&self, nulls: &mut Nulls) {
+        if let Some(set) = nulls {
+            for name in &self.non_null {
+                set.remove(name);
+            }
+            set.extend(self.null.iter().cloned());
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    function: &'a str,
+    /// Types of the visible parameters and locals, innermost scope last
+    scopes: Vec<HashMap<String, Type>>,
+    findings: Vec<AnalyzerFinding>,
+}
+
+impl Analyzer<'_> {
+    fn lookup(&self, name: &str) -> Option<&Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn is_pointer(&self, name: &str) -> bool {
+        self.lookup(name)
+            .is_some_and(|ty| matches!(strip(ty), Type::Pointer(_)))
+    }
+
+    fn report(&mut self, check: AnalyzerCheck, message: String) {
+        let finding = AnalyzerFinding {
+            check,
+            function: self.function.to_string(),
+            message,
+        };
+        if !self.findings.contains(&finding) {
+            self.findings.push(finding);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, nulls: &mut Nulls) {
+        match &statement.kind {
+            StatementKind::Expression(expr) => self.expression(expr, nulls),
+            StatementKind::Declaration {
+                name,
+                var_type,
+                initializer,
+                ..
+            } => {
+                if let Some(init) = initializer {
+                    self.expression(init, nulls);
+                }
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), var_type.clone());
+                }
+                if let Some(set) = nulls {
+                    set.remove(name);
+                    if self.is_pointer(name)
+                        && initializer.as_ref().is_some_and(is_null_pointer_constant)
+                    {
+                        set.insert(name.clone());
+                    }
+                }
+            }
+            StatementKind::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                for statement in statements {
+                    if matches!(&statement.kind, StatementKind::Label(_)) && nulls.is_none() {
+                        // A goto may jump here from anywhere
+                        *nulls = Some(BTreeSet::new());
+                    }
+                    self.statement(statement, nulls);
+                }
+                self.leave_scope(nulls);
+            }
+            StatementKind::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                self.expression(condition, nulls);
+                let (when_true, when_false) = self.null_facts(condition);
+                let mut then_nulls = nulls.clone();
+                when_true.apply(&mut then_nulls);
+                self.branch(then_stmt, &mut then_nulls);
+                let mut else_nulls = nulls.clone();
+                when_false.apply(&mut else_nulls);
+                if let Some(else_stmt) = else_stmt {
+                    self.branch(else_stmt, &mut else_nulls);
+                }
+                *nulls = join_nulls(&then_nulls, &else_nulls);
+            }
+            StatementKind::While { condition, body } => {
+                self.forget_assigned(statement, nulls);
+                self.expression(condition, nulls);
+                let (when_true, when_false) = self.null_facts(condition);
+                let mut body_nulls = nulls.clone();
+                when_true.apply(&mut body_nulls);
+                self.branch(body, &mut body_nulls);
+                if !breaks_out(body) {
+                    when_false.apply(nulls);
+                }
+            }
+            StatementKind::DoWhile { body, condition } => {
+                self.forget_assigned(statement, nulls);
+                let mut body_nulls = nulls.clone();
+                self.branch(body, &mut body_nulls);
+                self.expression(condition, &mut body_nulls);
+                if !breaks_out(body) {
+                    self.null_facts(condition).1.apply(nulls);
+                }
+            }
+            StatementKind::For {
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.statement(init, nulls);
+                }
+                self.forget_assigned(statement, nulls);
+                let (when_true, when_false) = match condition {
+                    Some(condition) => {
+                        self.expression(condition, nulls);
+                        self.null_facts(condition)
+                    }
+                    None => Default::default(),
+                };
+                let mut body_nulls = nulls.clone();
+                when_true.apply(&mut body_nulls);
+                self.branch(body, &mut body_nulls);
+                if let Some(increment) = increment {
+                    self.expression(increment, &mut body_nulls);
+                }
+                if !breaks_out(body) {
+                    when_false.apply(nulls);
+                }
+                self.leave_scope(nulls);
+            }
+            StatementKind::Switch { expression, cases } => {
+                self.expression(expression, nulls);
+                self.forget_assigned(statement, nulls);
+                for (_, statements) in cases {
+                    let mut case_nulls = nulls.clone();
+                    self.scopes.push(HashMap::new());
+                    for statement in statements {
+                        self.statement(statement, &mut case_nulls);
+                    }
+                    self.leave_scope(&mut case_nulls);
+                }
+            }
+            StatementKind::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.check_returned_address(expr);
+                    self.expression(expr, nulls);
+                }
+                *nulls = None;
+            }
+            StatementKind::Break | StatementKind::Continue | StatementKind::Goto(_) => {
+                *nulls = None
+            }
+            StatementKind::ComputedGoto(address) => {
+                self.expression(address, nulls);
+                *nulls = None;
+            }
+            StatementKind::Label(_) | StatementKind::Fallthrough => {}
+        }
+    }
+
+    fn branch(&mut self, statement: &Statement, nulls: &mut Nulls) {
+        self.scopes.push(HashMap::new());
+        self.statement(statement, nulls);
+        self.leave_scope(nulls);
+    }
+
+    /// Pop a scope; what was known about its locals no longer applies
+    fn leave_scope(&mut self, nulls: &mut Nulls) {
+        if let (Some(scope), Some(set)) = (self.scopes.pop(), nulls.as_mut()) {
+            set.retain(|name| !scope.contains_key(name));
+        }
+    }
+
+    /// Before a loop or switch, forget pointers assigned anywhere inside it,
+    /// as the state at its top must hold for every iteration
+    fn forget_assigned(&mut self, statement: &Statement, nulls: &mut Nulls) {
+        if let Some(set) = nulls {
+            let mut assigned = BTreeSet::new();
+            assigned_in_statement(statement, &mut assigned);
+            set.retain(|name| !assigned.contains(name));
+        }
+    }
+
+    /// Which pointers are null or not when `condition` is true, and when false
+    fn null_facts(&self, condition: &Expression) -> (NullFacts, NullFacts) {
+        match &condition.kind {
+            ExpressionKind::Identifier(name) if self.is_pointer(name) => (
+                NullFacts {
+                    non_null: vec![name.clone()],
+                    ..Default::default()
+                },
+                NullFacts {
+                    null: vec![name.clone()],
+                    ..Default::default()
+                },
+            ),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::LogicalNot,
+                operand,
+            } => {
+                let (when_true, when_false) = self.null_facts(operand);
+                (when_false, when_true)
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: operator @ (BinaryOperator::Equal | BinaryOperator::NotEqual),
+                right,
+            } => {
+                let name = match (left.as_ref(), right.as_ref()) {
+                    (
+                        Expression {
+                            kind: ExpressionKind::Identifier(name),
+                            ..
+                        },
+                        other,
+                    )
+                    | (
+                        other,
+                        Expression {
+                            kind: ExpressionKind::Identifier(name),
+                            ..
+                        },
+                    ) if self.is_pointer(name) && is_null_pointer_constant(other) => name.clone(),
+                    _ => return Default::default(),
+                };
+                let is_null = NullFacts {
+                    null: vec![name.clone()],
+                    ..Default::default()
+                };
+                let is_not_null = NullFacts {
+                    non_null: vec![name],
+                    ..Default::default()
+                };
+                if matches!(operator, BinaryOperator::Equal) {
+                    (is_null, is_not_null)
+                } else {
+                    (is_not_null, is_null)
+                }
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: BinaryOperator::LogicalAnd,
+                right,
+            } => {
+                // Both operands held if it's true
+                let (mut when_true, _) = self.null_facts(left);
+                let (right_true, _) = self.null_facts(right);
+                when_true.null.extend(right_true.null);
+                when_true.non_null.extend(right_true.non_null);
+                (when_true, NullFacts::default())
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: BinaryOperator::LogicalOr,
+                right,
+            } => {
+                // Both operands failed if it's false
+                let (_, mut when_false) = self.null_facts(left);
+                let (_, right_false) = self.null_facts(right);
+                when_false.null.extend(right_false.null);
+                when_false.non_null.extend(right_false.non_null);
+                (NullFacts::default(), when_false)
+            }
+            _ => Default::default(),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression, nulls: &mut Nulls) {
+        match &expr.kind {
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => {
+                self.check_dereference(operand, nulls);
+                self.expression(operand, nulls);
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => {
+                match &operand.kind {
+                    // The pointer may be set through its address
+                    ExpressionKind::Identifier(name) => forget(nulls, name),
+                    // `&a[n]` points just past the end, which is allowed
+                    ExpressionKind::Index { array, index } => {
+                        self.check_index(array, index, true);
+                        self.check_dereference(array, nulls);
+                        self.expression(array, nulls);
+                        self.expression(index, nulls);
+                        return;
+                    }
+                    _ => {}
+                }
+                self.expression(operand, nulls);
+            }
+            ExpressionKind::Unary {
+                operator:
+                    UnaryOperator::PreIncrement
+                    | UnaryOperator::PreDecrement
+                    | UnaryOperator::PostIncrement
+                    | UnaryOperator::PostDecrement,
+                operand,
+            } => {
+                self.expression(operand, nulls);
+                if let ExpressionKind::Identifier(name) = &operand.kind {
+                    forget(nulls, name);
+                }
+            }
+            ExpressionKind::Unary { operand, .. } => self.expression(operand, nulls),
+            ExpressionKind::Index { array, index } => {
+                self.check_index(array, index, false);
+                self.check_dereference(array, nulls);
+                self.expression(array, nulls);
+                self.expression(index, nulls);
+            }
+            ExpressionKind::Member {
+                object, is_arrow, ..
+            } => {
+                if *is_arrow {
+                    self.check_dereference(object, nulls);
+                }
+                self.expression(object, nulls);
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: operator @ (BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr),
+                right,
+            } => {
+                self.expression(left, nulls);
+                // The right operand only runs when the left one didn't decide the result
+                let (when_true, when_false) = self.null_facts(left);
+                let mut right_nulls = nulls.clone();
+                if matches!(operator, BinaryOperator::LogicalAnd) {
+                    when_true.apply(&mut right_nulls);
+                } else {
+                    when_false.apply(&mut right_nulls);
+                }
+                self.expression(right, &mut right_nulls);
+                *nulls = join_nulls(nulls, &right_nulls);
+            }
+            ExpressionKind::Binary { left, right, .. } => {
+                self.expression(left, nulls);
+                self.expression(right, nulls);
+            }
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => {
+                self.expression(function, nulls);
+                for argument in arguments {
+                    self.expression(argument, nulls);
+                }
+            }
+            ExpressionKind::Cast { expression, .. } => self.expression(expression, nulls),
+            ExpressionKind::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                self.expression(value, nulls);
+                match &target.kind {
+                    ExpressionKind::Identifier(name) => {
+                        forget(nulls, name);
+                        if matches!(operator, AssignmentOperator::Assign)
+                            && self.is_pointer(name)
+                            && is_null_pointer_constant(value)
+                        {
+                            if let Some(set) = nulls {
+                                set.insert(name.clone());
+                            }
+                        }
+                    }
+                    _ => self.expression(target, nulls),
+                }
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expression(condition, nulls);
+                let (when_true, when_false) = self.null_facts(condition);
+                let mut then_nulls = nulls.clone();
+                when_true.apply(&mut then_nulls);
+                self.expression(then_expr, &mut then_nulls);
+                when_false.apply(nulls);
+                self.expression(else_expr, nulls);
+                *nulls = join_nulls(&then_nulls, nulls);
+            }
+            ExpressionKind::VaArg { list, .. } => self.expression(list, nulls),
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::IntegerLiteral(_)
+            | ExpressionKind::FloatLiteral(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::CharLiteral(_)
+            | ExpressionKind::BooleanLiteral(_)
+            | ExpressionKind::Sizeof(_)
+            | ExpressionKind::LabelAddress(_) => {}
+        }
+    }
+
+    fn check_dereference(&mut self, pointer: &Expression, nulls: &Nulls) {
+        if let (ExpressionKind::Identifier(name), Some(set)) = (&pointer.kind, nulls) {
+            if set.contains(name) {
+                self.report(
+                    AnalyzerCheck::NullDereference,
+                    format!(
+                        "dereference of NULL '{}' in function '{}' [-Wanalyzer-null-dereference]",
+                        name, self.function
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_index(&mut self, array: &Expression, index: &Expression, address_only: bool) {
+        let ExpressionKind::Identifier(name) = &array.kind else {
+            return;
+        };
+        let Some(Type::Array(_, Some(length))) = self.lookup(name).map(strip) else {
+            return;
+        };
+        let Some(index) = integer_constant(index) else {
+            return;
+        };
+        let length = *length as i64;
+        let limit = if address_only { length } else { length - 1 };
+        if index < 0 || index > limit {
+            self.report(
+                AnalyzerCheck::OutOfBounds,
+                format!(
+                    "array subscript {} is outside the bounds of '{}' with {} elements in function '{}' [-Wanalyzer-out-of-bounds]",
+                    index, name, length, self.function
+                ),
+            );
+        }
+    }
+
+    fn check_returned_address(&mut self, expr: &Expression) {
+        let local = match &expr.kind {
+            ExpressionKind::Cast { expression, .. } => {
+                return self.check_returned_address(expression)
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => object_name(operand, self),
+            // An array decays to the address of its first element
+            ExpressionKind::Identifier(name)
+                if self
+                    .lookup(name)
+                    .is_some_and(|ty| matches!(strip(ty), Type::Array(..))) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        };
+        if let Some(name) = local.filter(|name| self.lookup(name).is_some()) {
+            self.report(
+                AnalyzerCheck::ReturnLocalAddress,
+                format!(
+                    "function '{}' returns address of local variable '{}' [-Wanalyzer-return-local-addr]",
+                    self.function, name
+                ),
+            );
+        }
+    }
+}
+
+fn forget(nulls: &mut Nulls, name: &str) {
+    if let Some(set) = nulls {
+        set.remove(name);
+    }
+}
+
+/// The variable an lvalue is part of, unless it's reached through a pointer
+fn object_name<'a>(expr: &'a Expression, types: &Analyzer) -> Option<&'a String> {
+    match &expr.kind {
+        ExpressionKind::Identifier(name) => Some(name),
+        ExpressionKind::Member {
+            object,
+            is_arrow: false,
+            ..
+        } => object_name(object, types),
+        // Indexing a pointer leaves the variable
+        ExpressionKind::Index { array, .. } => object_name(array, types).filter(|name| {
+            types
+                .lookup(name)
+                .is_some_and(|ty| matches!(strip(ty), Type::Array(..)))
+        }),
+        _ => None,
+    }
+}
+
+fn strip(ty: &Type) -> &Type {
+    match ty {
+        Type::Typedef(_, aliased) | Type::Qualified(_, aliased) => strip(aliased),
+        ty => ty,
+    }
+}
+
+/// Whether `function` calls alloca or declares a variable length array, and
+/// so moves the stack pointer at run time
+pub fn allocates_on_stack(function: &Function) -> bool {
+    let mut allocates = false;
+    expressions_in(&function.body, &mut |expr| {
+        if let ExpressionKind::Call { function, .. } = &expr.kind {
+            if let ExpressionKind::Identifier(name) = &function.kind {
+                allocates |= moves_stack_pointer(name);
+            }
+        }
+    });
+    allocates
+}
+
+/// Whether `-fstack-protector` in `mode` puts a canary in `function`'s frame.
+/// As in GCC, the basic mode guards character arrays of 8 bytes or more and
+/// functions calling alloca, and the strong one any array and any local or
+/// parameter whose address is taken; arrays inside a local struct or union
+/// count too
+pub fn needs_stack_protector(
+    function: &Function,
+    mode: StackProtector,
+    layout: &TypeLayout,
+) -> bool {
+    let mut locals = Vec::new();
+    local_declarations(&function.body, &mut locals);
+    match mode {
+        StackProtector::Off => false,
+        StackProtector::All => true,
+        StackProtector::Basic => {
+            allocates_on_stack(function)
+                || locals.iter().any(|(_, ty)| {
+                    contains_array(ty, layout, &|element, length| {
+                        matches!(element, Type::Char | Type::UnsignedChar) && length >= 8
+                    })
+                })
+        }
+        StackProtector::Strong => {
+            if allocates_on_stack(function)
+                || locals
+                    .iter()
+                    .any(|(_, ty)| contains_array(ty, layout, &|_, _| true))
+            {
+                return true;
+            }
+            let mut addressed = false;
+            expressions_in(&function.body, &mut |expr| {
+                if let ExpressionKind::Unary {
+                    operator: UnaryOperator::AddressOf,
+                    operand,
+                } = &expr.kind
+                {
+                    if let ExpressionKind::Identifier(name) = &operand.kind {
+                        addressed |= locals.iter().any(|(local, _)| *local == name)
+                            || function.parameters.iter().any(|(param, _)| param == name);
+                    }
+                }
+            });
+            addressed
+        }
+    }
+}
+
+/// Whether `ty` is, or has a member that is, an array `accept` takes, given
+/// its resolved element type and length
+fn contains_array(ty: &Type, layout: &TypeLayout, accept: &dyn Fn(&Type, usize) -> bool) -> bool {
+    match layout.resolve(ty) {
+        Type::Array(element, length) => {
+            accept(&layout.resolve(&element), length.unwrap_or(0))
+                || contains_array(&element, layout, accept)
+        }
+        Type::Struct { fields, .. } | Type::Union { fields, .. } => fields
+            .iter()
+            .any(|(_, field)| contains_array(field, layout, accept)),
+        _ => false,
+    }
+}
+
+/// The name and type of every local declared in a statement
+fn local_declarations<'a>(statement: &'a Statement, locals: &mut Vec<(&'a String, &'a Type)>) {
+    match &statement.kind {
+        StatementKind::Declaration { name, var_type, .. } => locals.push((name, var_type)),
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                local_declarations(statement, locals);
+            }
+        }
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            local_declarations(then_stmt, locals);
+            if let Some(else_stmt) = else_stmt {
+                local_declarations(else_stmt, locals);
+            }
+        }
+        StatementKind::While { body, .. } | StatementKind::DoWhile { body, .. } => {
+            local_declarations(body, locals)
+        }
+        StatementKind::For { init, body, .. } => {
+            if let Some(init) = init {
+                local_declarations(init, locals);
+            }
+            local_declarations(body, locals);
+        }
+        StatementKind::Switch { cases, .. } => {
+            for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                local_declarations(statement, locals);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Apply `f` to every expression in `statement`, outermost first
+fn expressions_in(statement: &Statement, f: &mut impl FnMut(&Expression)) {
+    match &statement.kind {
+        StatementKind::Expression(expr)
+        | StatementKind::Return(Some(expr))
+        | StatementKind::ComputedGoto(expr) => subexpressions(expr, f),
+        StatementKind::Declaration {
+            initializer: Some(expr),
+            ..
+        } => subexpressions(expr, f),
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                expressions_in(statement, f);
+            }
+        }
+        StatementKind::If {
+            condition,
+            then_stmt,
+            else_stmt,
+        } => {
+            subexpressions(condition, f);
+            expressions_in(then_stmt, f);
+            if let Some(else_stmt) = else_stmt {
+                expressions_in(else_stmt, f);
+            }
+        }
+        StatementKind::While { condition, body } | StatementKind::DoWhile { body, condition } => {
+            subexpressions(condition, f);
+            expressions_in(body, f);
+        }
+        StatementKind::For {
+            init,
+            condition,
+            increment,
+            body,
+        } => {
+            if let Some(init) = init {
+                expressions_in(init, f);
+            }
+            for expr in condition.iter().chain(increment) {
+                subexpressions(expr, f);
+            }
+            expressions_in(body, f);
+        }
+        StatementKind::Switch { expression, cases } => {
+            subexpressions(expression, f);
+            for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                expressions_in(statement, f);
+            }
+        }
+        StatementKind::Declaration { .. }
+        | StatementKind::Return(None)
+        | StatementKind::Break
+        | StatementKind::Continue
+        | StatementKind::Goto(_)
+        | StatementKind::Label(_)
+        | StatementKind::Fallthrough => {}
+    }
+}
+
+/// Apply `f` to `expr` and everything inside it
+fn subexpressions(expr: &Expression, f: &mut impl FnMut(&Expression)) {
+    f(expr);
+    match &expr.kind {
+        ExpressionKind::Binary { left, right, .. }
+        | ExpressionKind::Index {
+            array: left,
+            index: right,
+        }
+        | ExpressionKind::Assignment {
+            target: left,
+            value: right,
+            ..
+        } => {
+            subexpressions(left, f);
+            subexpressions(right, f);
+        }
+        ExpressionKind::Call {
+            function,
+            arguments,
+        } => {
+            subexpressions(function, f);
+            for argument in arguments {
+                subexpressions(argument, f);
+            }
+        }
+        ExpressionKind::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            subexpressions(condition, f);
+            subexpressions(then_expr, f);
+            subexpressions(else_expr, f);
+        }
+        ExpressionKind::Unary { operand: inner, .. }
+        | ExpressionKind::Member { object: inner, .. }
+        | ExpressionKind::Cast {
+            expression: inner, ..
+        }
+        | ExpressionKind::VaArg { list: inner, .. } => subexpressions(inner, f),
+        ExpressionKind::Identifier(_)
+        | ExpressionKind::IntegerLiteral(_)
+        | ExpressionKind::FloatLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::CharLiteral(_)
+        | ExpressionKind::BooleanLiteral(_)
+        | ExpressionKind::Sizeof(_)
+        | ExpressionKind::LabelAddress(_) => {}
+    }
+}
+
+/// Names assigned, incremented or whose address is taken anywhere in a statement
+fn assigned_in_statement(statement: &Statement, assigned: &mut BTreeSet<String>) {
+    let mut expression = |expr: &Expression| assigned_in_expression(expr, assigned);
+    match &statement.kind {
+        StatementKind::Expression(expr) | StatementKind::ComputedGoto(expr) => expression(expr),
+        StatementKind::Declaration { initializer, .. } => {
+            if let Some(init) = initializer {
+                expression(init);
+            }
+        }
+        StatementKind::Return(expr) => {
+            if let Some(expr) = expr {
+                expression(expr);
+            }
+        }
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                assigned_in_statement(statement, assigned);
+            }
+        }
+        StatementKind::If {
+            condition,
+            then_stmt,
+            else_stmt,
+        } => {
+            expression(condition);
+            assigned_in_statement(then_stmt, assigned);
+            if let Some(else_stmt) = else_stmt {
+                assigned_in_statement(else_stmt, assigned);
+            }
+        }
+        StatementKind::While { condition, body } | StatementKind::DoWhile { body, condition } => {
+            expression(condition);
+            assigned_in_statement(body, assigned);
+        }
+        StatementKind::For {
+            init,
+            condition,
+            increment,
+            body,
+        } => {
+            for expr in condition.iter().chain(increment) {
+                assigned_in_expression(expr, assigned);
+            }
+            if let Some(init) = init {
+                assigned_in_statement(init, assigned);
+            }
+            assigned_in_statement(body, assigned);
+        }
+        StatementKind::Switch {
+            expression: expr,
+            cases,
+        } => {
+            expression(expr);
+            for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                assigned_in_statement(statement, assigned);
+            }
+        }
+        StatementKind::Break
+        | StatementKind::Continue
+        | StatementKind::Goto(_)
+        | StatementKind::Label(_)
+        | StatementKind::Fallthrough => {}
+    }
+}
+
+fn assigned_in_expression(expr: &Expression, assigned: &mut BTreeSet<String>) {
+    match &expr.kind {
+        ExpressionKind::Assignment { target, value, .. } => {
+            if let ExpressionKind::Identifier(name) = &target.kind {
+                assigned.insert(name.clone());
+            }
+            assigned_in_expression(target, assigned);
+            assigned_in_expression(value, assigned);
+        }
+        ExpressionKind::Unary { operator, operand } => {
+            if let (
+                UnaryOperator::AddressOf
+                | UnaryOperator::PreIncrement
+                | UnaryOperator::PreDecrement
+                | UnaryOperator::PostIncrement
+                | UnaryOperator::PostDecrement,
+                ExpressionKind::Identifier(name),
+            ) = (operator, &operand.kind)
+            {
+                assigned.insert(name.clone());
+            }
+            assigned_in_expression(operand, assigned);
+        }
+        ExpressionKind::Binary { left, right, .. } => {
+            assigned_in_expression(left, assigned);
+            assigned_in_expression(right, assigned);
+        }
+        ExpressionKind::Call {
+            function,
+            arguments,
+        } => {
+            assigned_in_expression(function, assigned);
+            for argument in arguments {
+                assigned_in_expression(argument, assigned);
+            }
+        }
+        ExpressionKind::Member { object, .. } => assigned_in_expression(object, assigned),
+        ExpressionKind::Index { array, index } => {
+            assigned_in_expression(array, assigned);
+            assigned_in_expression(index, assigned);
+        }
+        ExpressionKind::Cast { expression, .. } => assigned_in_expression(expression, assigned),
+        ExpressionKind::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            assigned_in_expression(condition, assigned);
+            assigned_in_expression(then_expr, assigned);
+            assigned_in_expression(else_expr, assigned);
+        }
+        ExpressionKind::VaArg { list, .. } => assigned_in_expression(list, assigned),
+        ExpressionKind::Identifier(_)
+        | ExpressionKind::IntegerLiteral(_)
+        | ExpressionKind::FloatLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::CharLiteral(_)
+        | ExpressionKind::BooleanLiteral(_)
+        | ExpressionKind::Sizeof(_)
+        | ExpressionKind::LabelAddress(_) => {}
+    }
+}
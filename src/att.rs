@@ -0,0 +1,380 @@
+/// The x86 assembly alecc generates, `.intel_syntax noprefix`, rewritten in
+/// AT&T syntax: operands source first, `%` before registers, `$` before
+/// immediates, memory as `disp(base,index,scale)` and the operand size in
+/// the mnemonic where no register gives it. Labels, directives and comments
+/// stay as they are.
+pub fn translate(intel: &str) -> String {
+    let mut att = String::with_capacity(intel.len() + intel.len() / 8);
+    for line in intel.lines() {
+        att.push_str(&translate_line(line));
+        att.push('\n');
+    }
+    att
+}
+
+fn translate_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    if trimmed == ".intel_syntax noprefix" {
+        return line.replace(".intel_syntax noprefix", ".att_syntax");
+    }
+    if trimmed.is_empty()
+        || trimmed.starts_with('.')
+        || trimmed.starts_with('#')
+        || trimmed.ends_with(':')
+    {
+        return line.to_string();
+    }
+    let indent = &line[..line.len() - trimmed.len()];
+    let (code, comment) = match trimmed.find(" #") {
+        Some(at) => (trimmed[..at].trim_end(), &trimmed[at..]),
+        None => (trimmed, ""),
+    };
+    format!("{}{}{}", indent, instruction(code), comment)
+}
+
+/// One instruction, with any `lock` or `rep` prefixes in front of it
+fn instruction(code: &str) -> String {
+    let (mnemonic, operands) = match code.split_once(' ') {
+        Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+        None => (code, ""),
+    };
+    if matches!(
+        mnemonic,
+        "lock" | "rep" | "repe" | "repz" | "repne" | "repnz"
+    ) {
+        return format!("{} {}", mnemonic, instruction(operands));
+    }
+    // Sign extensions of the accumulator have names of their own
+    let renamed = match mnemonic {
+        "cqo" => Some("cqto"),
+        "cdq" => Some("cltd"),
+        "cdqe" => Some("cltq"),
+        "cwde" => Some("cwtl"),
+        "cwd" => Some("cwtd"),
+        "cbw" => Some("cbtw"),
+        _ => None,
+    };
+    if let Some(renamed) = renamed {
+        return renamed.to_string();
+    }
+    if operands.is_empty() {
+        return mnemonic.to_string();
+    }
+
+    let operands: Vec<Operand> = split_operands(operands)
+        .into_iter()
+        .map(Operand::parse)
+        .collect();
+    let branch = mnemonic == "call" || mnemonic.starts_with('j');
+    let mnemonic = sized_mnemonic(mnemonic, &operands);
+    let translated: Vec<String> = operands
+        .iter()
+        .rev()
+        .map(|operand| match operand {
+            // Indirect branches take the target's address from a register or memory
+            Operand::Register(_) | Operand::Memory { .. } if branch => {
+                format!("*{}", operand.att())
+            }
+            _ => operand.att(),
+        })
+        .collect();
+    format!("{} {}", mnemonic, translated.join(", "))
+}
+
+/// The operands of an instruction, split at the commas outside brackets
+fn split_operands(operands: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (at, c) in operands.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(operands[start..at].trim());
+                start = at + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(operands[start..].trim());
+    split
+}
+
+enum Operand {
+    Register(String),
+    Immediate(String),
+    /// `size` is from its `BYTE PTR` and the like, in bytes
+    Memory {
+        size: Option<usize>,
+        segment: Option<String>,
+        address: String,
+    },
+    /// A label a branch goes to, or a symbol used as a memory operand
+    Symbol(String),
+}
+
+impl Operand {
+    fn parse(operand: &str) -> Operand {
+        let (size, rest) = match operand.split_once(" PTR ") {
+            Some((size, rest)) => (pointer_size(size), rest.trim()),
+            None => (None, operand),
+        };
+        if let Some(symbol) = rest.strip_prefix("OFFSET ") {
+            return Operand::Immediate(symbol.trim().to_string());
+        }
+        let (segment, rest) = match rest.split_once(':') {
+            Some((segment, rest)) if register_size(segment).is_some() => {
+                (Some(segment.to_string()), rest)
+            }
+            _ => (None, rest),
+        };
+        if let Some(address) = rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            return Operand::Memory {
+                size,
+                segment,
+                address: address.to_string(),
+            };
+        }
+        if size.is_some() || segment.is_some() {
+            return Operand::Memory {
+                size,
+                segment,
+                address: rest.to_string(),
+            };
+        }
+        if register_size(rest).is_some() {
+            Operand::Register(rest.to_string())
+        } else if rest.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+            Operand::Immediate(rest.to_string())
+        } else {
+            Operand::Symbol(rest.to_string())
+        }
+    }
+
+    /// The operand's size in bytes when it says
+    fn size(&self) -> Option<usize> {
+        match self {
+            Operand::Register(register) => register_size(register),
+            Operand::Memory { size, .. } => *size,
+            _ => None,
+        }
+    }
+
+    fn att(&self) -> String {
+        match self {
+            Operand::Register(register) => format!("%{}", register),
+            Operand::Immediate(value) => format!("${}", value),
+            Operand::Symbol(symbol) => symbol.clone(),
+            Operand::Memory {
+                segment, address, ..
+            } => {
+                let segment = segment
+                    .as_ref()
+                    .map(|segment| format!("%{}:", segment))
+                    .unwrap_or_default();
+                format!("{}{}", segment, att_address(address))
+            }
+        }
+    }
+}
+
+/// `base + index*scale + displacement`, its terms in any order, as
+/// `displacement(%base,%index,scale)`
+fn att_address(address: &str) -> String {
+    let mut base = None;
+    let mut index = None;
+    let mut offset: i64 = 0;
+    let mut symbols = String::new();
+    let mut term = String::new();
+    let mut negative = false;
+    let mut finish = |term: &str, negative: bool| {
+        let term = term.trim();
+        if term.is_empty() {
+            return;
+        }
+        if let Some((register, scale)) = term.split_once('*') {
+            index = Some((register.trim().to_string(), scale.trim().to_string()));
+        } else if register_size(term).is_some() {
+            if base.is_none() {
+                base = Some(term.to_string());
+            } else {
+                index = Some((term.to_string(), "1".to_string()));
+            }
+        } else if let Some(value) = parse_number(term) {
+            offset += if negative { -value } else { value };
+        } else {
+            if negative {
+                symbols.push('-');
+            } else if !symbols.is_empty() {
+                symbols.push('+');
+            }
+            symbols.push_str(term);
+        }
+    };
+    for c in address.chars() {
+        match c {
+            '+' | '-' if !term.trim().is_empty() => {
+                finish(&term, negative);
+                term.clear();
+                negative = c == '-';
+            }
+            // A sign of its own, as in `rbp + -8`
+            '-' => negative = !negative,
+            '+' => {}
+            c => term.push(c),
+        }
+    }
+    finish(&term, negative);
+
+    let displacement = match (symbols.is_empty(), offset) {
+        (true, 0) if base.is_some() || index.is_some() => String::new(),
+        (true, offset) => offset.to_string(),
+        (false, 0) => symbols,
+        (false, offset) if offset < 0 => format!("{}{}", symbols, offset),
+        (false, offset) => format!("{}+{}", symbols, offset),
+    };
+    match (base, index) {
+        (None, None) => displacement,
+        (Some(base), None) => format!("{}(%{})", displacement, base),
+        (base, Some((index, scale))) => format!(
+            "{}({},%{},{})",
+            displacement,
+            base.map(|base| format!("%{}", base)).unwrap_or_default(),
+            index,
+            scale
+        ),
+    }
+}
+
+fn parse_number(term: &str) -> Option<i64> {
+    match term.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => term.parse().ok(),
+    }
+}
+
+/// The AT&T mnemonic: size suffixes where an operand's size is only known
+/// from its `PTR`, and both sizes for the extending moves
+fn sized_mnemonic(mnemonic: &str, operands: &[Operand]) -> String {
+    let suffix = |size: Option<usize>| match size {
+        Some(1) => "b",
+        Some(2) => "w",
+        Some(4) => "l",
+        Some(8) => "q",
+        _ => "",
+    };
+    let memory_size = operands.iter().find_map(|operand| match operand {
+        Operand::Memory { size, .. } => *size,
+        _ => None,
+    });
+    match mnemonic {
+        "movsx" | "movzx" | "movsxd" => {
+            let extend = if mnemonic == "movzx" { "movz" } else { "movs" };
+            let source = operands.get(1).and_then(Operand::size);
+            let destination = operands.first().and_then(Operand::size);
+            format!("{}{}{}", extend, suffix(source), suffix(destination))
+        }
+        // x87 integer loads and stores name the integer's size: s, l or ll
+        _ if mnemonic.starts_with("fi") => {
+            let format = match memory_size {
+                Some(2) => "s",
+                Some(4) => "l",
+                Some(8) => "ll",
+                _ => "",
+            };
+            format!("{}{}", mnemonic, format)
+        }
+        // x87 loads and stores name the memory format: s, l or t
+        _ if mnemonic.starts_with('f') => {
+            let format = match memory_size {
+                Some(4) => "s",
+                Some(8) => "l",
+                Some(10) => "t",
+                _ => "",
+            };
+            format!("{}{}", mnemonic, format)
+        }
+        // Integer to floating conversions take an integer in memory of either size
+        "cvtsi2sd" | "cvtsi2ss" => format!("{}{}", mnemonic, suffix(memory_size)),
+        _ if is_vector(mnemonic) => mnemonic.to_string(),
+        _ if memory_size.is_some()
+            && !operands
+                .iter()
+                .any(|operand| matches!(operand, Operand::Register(_))) =>
+        {
+            format!("{}{}", mnemonic, suffix(memory_size))
+        }
+        _ => mnemonic.to_string(),
+    }
+}
+
+/// SSE instructions, whose operand sizes are part of the mnemonic already
+fn is_vector(mnemonic: &str) -> bool {
+    mnemonic.starts_with("cvt")
+        || mnemonic.starts_with("ucomis")
+        || mnemonic.starts_with("comis")
+        || mnemonic.starts_with("pshuf")
+        || mnemonic.ends_with("ss")
+        || mnemonic.ends_with("sd")
+        || mnemonic.ends_with("ps")
+        || mnemonic.ends_with("pd")
+        || matches!(mnemonic, "movd" | "movq")
+}
+
+fn pointer_size(size: &str) -> Option<usize> {
+    match size.trim() {
+        "BYTE" => Some(1),
+        "WORD" => Some(2),
+        "DWORD" => Some(4),
+        "QWORD" => Some(8),
+        "TBYTE" => Some(10),
+        "XMMWORD" => Some(16),
+        _ => None,
+    }
+}
+
+/// The width of an x86 register in bytes, or `None` when `name` isn't one
+fn register_size(name: &str) -> Option<usize> {
+    const BYTES: [&str; 20] = [
+        "al", "bl", "cl", "dl", "ah", "bh", "ch", "dh", "sil", "dil", "bpl", "spl", "r8b", "r9b",
+        "r10b", "r11b", "r12b", "r13b", "r14b", "r15b",
+    ];
+    const WORDS: [&str; 14] = [
+        "ax", "bx", "cx", "dx", "si", "di", "bp", "sp", "cs", "ds", "es", "fs", "gs", "ss",
+    ];
+    const DWORDS: [&str; 8] = ["eax", "ebx", "ecx", "edx", "esi", "edi", "ebp", "esp"];
+    const QWORDS: [&str; 9] = [
+        "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "rip",
+    ];
+    if BYTES.contains(&name) {
+        return Some(1);
+    }
+    if WORDS.contains(&name) {
+        return Some(2);
+    }
+    if DWORDS.contains(&name) {
+        return Some(4);
+    }
+    if QWORDS.contains(&name) {
+        return Some(8);
+    }
+    if let Some(number) = name.strip_prefix('r') {
+        let (number, size) = match number.as_bytes().last() {
+            Some(b'd') => (&number[..number.len() - 1], 4),
+            Some(b'w') => (&number[..number.len() - 1], 2),
+            _ => (number, 8),
+        };
+        if matches!(number.parse::<u8>(), Ok(8..=15)) {
+            return Some(size);
+        }
+    }
+    if let Some(number) = name.strip_prefix("xmm") {
+        if matches!(number.parse::<u8>(), Ok(0..=15)) {
+            return Some(16);
+        }
+    }
+    if name == "st" || name.starts_with("st(") {
+        return Some(10);
+    }
+    None
+}
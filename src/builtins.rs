@@ -0,0 +1,313 @@
+use crate::parser::{Qualifiers, Type};
+
+/// The GCC builtins alecc knows besides the `<stdarg.h>` ones. Libc headers
+/// and kernel code call them without declaring them, so the semantic pass
+/// declares them up front and the code generator expands each one inline or
+/// calls the library function of the same name.
+pub const BUILTINS: &[&str] = &[
+    "__builtin_memcpy",
+    "__builtin_memset",
+    "__builtin_expect",
+    "__builtin_unreachable",
+    "__builtin_trap",
+    "__builtin_popcount",
+    "__builtin_popcountl",
+    "__builtin_popcountll",
+    "__builtin_clz",
+    "__builtin_clzl",
+    "__builtin_clzll",
+    "__builtin_ctz",
+    "__builtin_ctzl",
+    "__builtin_ctzll",
+    "__builtin_bswap16",
+    "__builtin_bswap32",
+    "__builtin_bswap64",
+    "__builtin_memmove",
+    "__builtin_memcmp",
+    "__builtin_strlen",
+    "__builtin_strcmp",
+    "__builtin_strncmp",
+    "__builtin_strcpy",
+    "__builtin_strchr",
+    "__builtin_malloc",
+    "__builtin_free",
+    "__builtin_abort",
+    "__builtin_alloca",
+    "__builtin_stack_save",
+    "__builtin_stack_restore",
+];
+
+/// Whether `name` is one of the builtins in [`BUILTINS`]
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+}
+
+/// Whether a call to `name` moves the stack pointer at run time: alloca,
+/// under its own name too as `<alloca.h>` declares it without GCC, and the
+/// builtins variable length arrays are freed with
+pub fn moves_stack_pointer(name: &str) -> bool {
+    matches!(
+        name,
+        "__builtin_alloca" | "alloca" | "__builtin_stack_save" | "__builtin_stack_restore"
+    )
+}
+
+/// The library function a call to builtin `name` becomes, for the builtins
+/// that aren't expanded inline
+pub fn library_function(name: &str) -> Option<&str> {
+    match name.strip_prefix("__builtin_")? {
+        function @ ("memmove" | "memcmp" | "strlen" | "strcmp" | "strncmp" | "strcpy"
+        | "strchr" | "malloc" | "free" | "abort") => Some(function),
+        _ => None,
+    }
+}
+
+/// The prototype builtin `name` has, as GCC declares it
+pub fn signature(name: &str) -> Option<Type> {
+    let pointer = |ty: Type| Type::Pointer(Box::new(ty));
+    let constant = |ty: Type| {
+        Type::Qualified(
+            Qualifiers {
+                is_const: true,
+                ..Qualifiers::default()
+            },
+            Box::new(ty),
+        )
+    };
+    let size = Type::UnsignedLong;
+    let (return_type, parameters) = match name.strip_prefix("__builtin_")? {
+        "memcpy" | "memmove" => (
+            pointer(Type::Void),
+            vec![pointer(Type::Void), pointer(constant(Type::Void)), size],
+        ),
+        "memset" => (
+            pointer(Type::Void),
+            vec![pointer(Type::Void), Type::Int, size],
+        ),
+        "memcmp" => (
+            Type::Int,
+            vec![
+                pointer(constant(Type::Void)),
+                pointer(constant(Type::Void)),
+                size,
+            ],
+        ),
+        "expect" => (Type::Long, vec![Type::Long, Type::Long]),
+        "unreachable" | "trap" | "abort" => (Type::Void, vec![]),
+        "popcount" | "clz" | "ctz" => (Type::Int, vec![Type::UnsignedInt]),
+        "popcountl" | "popcountll" | "clzl" | "clzll" | "ctzl" | "ctzll" => {
+            (Type::Int, vec![Type::UnsignedLong])
+        }
+        "bswap16" => (Type::UnsignedShort, vec![Type::UnsignedShort]),
+        "bswap32" => (Type::UnsignedInt, vec![Type::UnsignedInt]),
+        "bswap64" => (Type::UnsignedLong, vec![Type::UnsignedLong]),
+        "strlen" => (size, vec![pointer(constant(Type::Char))]),
+        "strcmp" => (
+            Type::Int,
+            vec![pointer(constant(Type::Char)), pointer(constant(Type::Char))],
+        ),
+        "strncmp" => (
+            Type::Int,
+            vec![
+                pointer(constant(Type::Char)),
+                pointer(constant(Type::Char)),
+                size,
+            ],
+        ),
+        "strcpy" => (
+            pointer(Type::Char),
+            vec![pointer(Type::Char), pointer(constant(Type::Char))],
+        ),
+        "strchr" => (
+            pointer(Type::Char),
+            vec![pointer(constant(Type::Char)), Type::Int],
+        ),
+        "malloc" | "alloca" => (pointer(Type::Void), vec![size]),
+        "stack_save" => (pointer(Type::Void), vec![]),
+        "stack_restore" => (Type::Void, vec![pointer(Type::Void)]),
+        "free" => (Type::Void, vec![pointer(Type::Void)]),
+        _ => return None,
+    };
+    Some(Type::Function {
+        return_type: Box::new(return_type),
+        parameters,
+        variadic: false,
+    })
+}
+
+/// A memory order argument of the `__atomic` builtins, numbered as GCC's
+/// `__ATOMIC_*` macros are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryOrder {
+    Relaxed,
+    Consume,
+    Acquire,
+    Release,
+    AcqRel,
+    SeqCst,
+}
+
+impl MemoryOrder {
+    /// The order `value` names; anything else is taken as the strongest
+    pub fn from_value(value: i64) -> Self {
+        match value {
+            0 => MemoryOrder::Relaxed,
+            1 => MemoryOrder::Consume,
+            2 => MemoryOrder::Acquire,
+            3 => MemoryOrder::Release,
+            4 => MemoryOrder::AcqRel,
+            _ => MemoryOrder::SeqCst,
+        }
+    }
+
+    /// Whether later accesses can't move before the operation
+    pub fn acquires(self) -> bool {
+        matches!(
+            self,
+            MemoryOrder::Consume | MemoryOrder::Acquire | MemoryOrder::AcqRel | MemoryOrder::SeqCst
+        )
+    }
+
+    /// Whether earlier accesses can't move after the operation
+    pub fn releases(self) -> bool {
+        matches!(
+            self,
+            MemoryOrder::Release | MemoryOrder::AcqRel | MemoryOrder::SeqCst
+        )
+    }
+}
+
+/// The read-modify-write operations of `__atomic_fetch_*` and friends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomicOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Nand,
+}
+
+/// What an `__atomic_*` or `__sync_*` builtin does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomicOperation {
+    /// `(p, order)`, the value at `p`
+    Load,
+    /// `(p, value, order)`
+    Store,
+    /// `(p, value, order)`, the value before
+    Exchange,
+    /// `__atomic_compare_exchange_n(p, expected, desired, weak, success,
+    /// failure)`: whether `*p` was `*expected`, which gets `*p` if not
+    CompareExchange,
+    /// `__sync_*_compare_and_swap(p, old, new)`: whether `*p` was `old`, or
+    /// the value before
+    CompareAndSwap { returns_bool: bool },
+    /// `(p, value, order)`, the value before or, with `returns_new`, after
+    Fetch { op: AtomicOp, returns_new: bool },
+    /// `(order)`
+    Fence,
+}
+
+/// A call to one of the atomic builtins. Their operand type is whatever the
+/// first argument points to, so unlike [`BUILTINS`] they have no prototype
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Atomic {
+    pub operation: AtomicOperation,
+    /// The argument with the memory order, for the `__atomic` builtins
+    pub order_argument: Option<usize>,
+    /// The order without one, or when it isn't a constant
+    pub order: MemoryOrder,
+}
+
+/// The atomic builtin `name` is, if it is one
+pub fn atomic(name: &str) -> Option<Atomic> {
+    let op = |name: &str| match name {
+        "add" => Some(AtomicOp::Add),
+        "sub" => Some(AtomicOp::Sub),
+        "and" => Some(AtomicOp::And),
+        "or" => Some(AtomicOp::Or),
+        "xor" => Some(AtomicOp::Xor),
+        "nand" => Some(AtomicOp::Nand),
+        _ => None,
+    };
+    let (operation, order_argument, order) = if let Some(rest) = name.strip_prefix("__atomic_") {
+        let operation = match rest {
+            "load_n" => AtomicOperation::Load,
+            "store_n" => AtomicOperation::Store,
+            "exchange_n" => AtomicOperation::Exchange,
+            "compare_exchange_n" => AtomicOperation::CompareExchange,
+            "thread_fence" | "signal_fence" => AtomicOperation::Fence,
+            _ => match (rest.strip_prefix("fetch_"), rest.strip_suffix("_fetch")) {
+                (Some(fetched), _) => AtomicOperation::Fetch {
+                    op: op(fetched)?,
+                    returns_new: false,
+                },
+                (None, Some(fetched)) => AtomicOperation::Fetch {
+                    op: op(fetched)?,
+                    returns_new: true,
+                },
+                (None, None) => return None,
+            },
+        };
+        let order = match operation {
+            AtomicOperation::Fence => 0,
+            AtomicOperation::Load => 1,
+            AtomicOperation::CompareExchange => 4,
+            _ => 2,
+        };
+        (operation, Some(order), MemoryOrder::SeqCst)
+    } else {
+        let rest = name.strip_prefix("__sync_")?;
+        let (operation, order) = match rest {
+            "val_compare_and_swap" => (
+                AtomicOperation::CompareAndSwap {
+                    returns_bool: false,
+                },
+                MemoryOrder::SeqCst,
+            ),
+            "bool_compare_and_swap" => (
+                AtomicOperation::CompareAndSwap { returns_bool: true },
+                MemoryOrder::SeqCst,
+            ),
+            "lock_test_and_set" => (AtomicOperation::Exchange, MemoryOrder::Acquire),
+            "lock_release" => (AtomicOperation::Store, MemoryOrder::Release),
+            "synchronize" => (AtomicOperation::Fence, MemoryOrder::SeqCst),
+            _ => {
+                let operation = match (
+                    rest.strip_prefix("fetch_and_"),
+                    rest.strip_suffix("_and_fetch"),
+                ) {
+                    (Some(fetched), _) => AtomicOperation::Fetch {
+                        op: op(fetched)?,
+                        returns_new: false,
+                    },
+                    (None, Some(fetched)) => AtomicOperation::Fetch {
+                        op: op(fetched)?,
+                        returns_new: true,
+                    },
+                    (None, None) => return None,
+                };
+                (operation, MemoryOrder::SeqCst)
+            }
+        };
+        (operation, None, order)
+    };
+    Some(Atomic {
+        operation,
+        order_argument,
+        order,
+    })
+}
+
+impl Atomic {
+    /// The type of the call when the first argument points to `operand`
+    pub fn result_type(&self, operand: Option<Type>) -> Option<Type> {
+        match self.operation {
+            AtomicOperation::Store | AtomicOperation::Fence => Some(Type::Void),
+            AtomicOperation::CompareExchange
+            | AtomicOperation::CompareAndSwap { returns_bool: true } => Some(Type::Bool),
+            _ => operand,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::Result;
+use crate::language::LanguageOptions;
+use crate::lexer::Token;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Token lists kept at most; the cache is emptied when it would grow past this
+const MAX_CACHED_UNITS: usize = 256;
+
+#[derive(Debug)]
+struct CachedFile {
+    modified: Option<SystemTime>,
+    len: u64,
+    contents: Arc<String>,
+}
+
+#[derive(Debug, Default)]
+struct Caches {
+    headers: HashMap<PathBuf, CachedFile>,
+    tokens: HashMap<u64, (String, Vec<Token>)>,
+    header_hits: usize,
+    token_hits: usize,
+}
+
+/// Headers and token lists shared by the compilations of a long-running
+/// process (`--daemon`), so each job doesn't read and lex them again. A
+/// header is read again once its size or modification time changes.
+#[derive(Debug, Clone, Default)]
+pub struct CompileCache {
+    inner: Arc<Mutex<Caches>>,
+}
+
+impl CompileCache {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Contents of the header at `path`
+    pub async fn read_header(&self, path: &Path) -> std::io::Result<Arc<String>> {
+        let metadata = tokio::fs::metadata(path).await?;
+        let (modified, len) = (metadata.modified().ok(), metadata.len());
+        {
+            let mut caches = self.lock();
+            let cached = caches
+                .headers
+                .get(path)
+                .filter(|file| file.modified == modified && file.len == len)
+                .map(|file| file.contents.clone());
+            if let Some(contents) = cached {
+                caches.header_hits += 1;
+                return Ok(contents);
+            }
+        }
+
+        let contents = Arc::new(tokio::fs::read_to_string(path).await?);
+        self.lock().headers.insert(
+            path.to_path_buf(),
+            CachedFile {
+                modified,
+                len,
+                contents: contents.clone(),
+            },
+        );
+        Ok(contents)
+    }
+
+    /// The tokens of a preprocessed unit, running `lex` only for text not seen before
+    pub fn tokens(
+        &self,
+        source: &str,
+        language: LanguageOptions,
+        lex: impl FnOnce() -> Result<Vec<Token>>,
+    ) -> Result<Vec<Token>> {
+        let mut hasher = DefaultHasher::new();
+        (source, language).hash(&mut hasher);
+        let key = hasher.finish();
+        {
+            let mut caches = self.lock();
+            let cached = caches
+                .tokens
+                .get(&key)
+                .filter(|(text, _)| text == source)
+                .map(|(_, tokens)| tokens.clone());
+            if let Some(tokens) = cached {
+                caches.token_hits += 1;
+                return Ok(tokens);
+            }
+        }
+
+        let tokens = lex()?;
+        let mut caches = self.lock();
+        if caches.tokens.len() >= MAX_CACHED_UNITS {
+            caches.tokens.clear();
+        }
+        caches
+            .tokens
+            .insert(key, (source.to_string(), tokens.clone()));
+        Ok(tokens)
+    }
+
+    /// How many header reads and lexer runs the cache has saved
+    #[allow(dead_code)]
+    pub fn hits(&self) -> (usize, usize) {
+        let caches = self.lock();
+        (caches.header_hits, caches.token_hits)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Caches> {
+        // A panic while holding the lock leaves nothing half-updated worth refusing
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
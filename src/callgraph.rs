@@ -0,0 +1,507 @@
+use crate::parser::{
+    Expression, ExpressionKind, Function, Program, Statement, StatementKind, Type, UnaryOperator,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+/// How a caller reaches a callee
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum CallKind {
+    /// By name
+    Direct,
+    /// Through a function pointer
+    Indirect,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FunctionNode {
+    /// Whether a unit has its body; calls to the others go to libraries
+    pub defined: bool,
+    pub is_static: bool,
+    /// Used other than by calling it, so it may be called through a pointer
+    pub address_taken: bool,
+    /// Parameter count and whether it is variadic, when a prototype says
+    #[cfg_attr(feature = "serde", serde(skip))]
+    signature: Option<(usize, bool)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Call {
+    pub caller: String,
+    pub callee: String,
+    pub kind: CallKind,
+    /// Call sites in the caller
+    pub count: usize,
+}
+
+/// A call through a pointer, resolved once every unit has been added
+#[derive(Debug, Clone)]
+struct PointerCall {
+    caller: String,
+    /// The called expression, e.g. `ops->run`
+    pointer: String,
+    signature: Option<(usize, bool)>,
+}
+
+/// Which functions of a program call which. Static functions are named
+/// `file:name` when units are added with [`CallGraph::add_unit`], so those of
+/// different units stay apart.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    functions: BTreeMap<String, FunctionNode>,
+    direct: BTreeMap<(String, String), usize>,
+    pointer_calls: Vec<PointerCall>,
+}
+
+impl CallGraph {
+    /// The graph of a single translation unit
+    pub fn build(program: &Program) -> Self {
+        let mut graph = Self::default();
+        graph.add(program, None);
+        graph
+    }
+
+    /// Add the functions of the unit compiled from `file`
+    pub fn add_unit(&mut self, file: &str, program: &Program) {
+        self.add(program, Some(file));
+    }
+
+    pub fn functions(&self) -> &BTreeMap<String, FunctionNode> {
+        &self.functions
+    }
+
+    /// Every caller-callee pair, direct calls first. A call through a
+    /// pointer goes to each address-taken function whose parameters fit the
+    /// pointer's type; when none does, the callee is the pointer expression
+    /// in parentheses, e.g. `(*ops->run)`.
+    pub fn calls(&self) -> Vec<Call> {
+        let mut calls: Vec<Call> = self
+            .direct
+            .iter()
+            .map(|((caller, callee), &count)| Call {
+                caller: caller.clone(),
+                callee: callee.clone(),
+                kind: CallKind::Direct,
+                count,
+            })
+            .collect();
+
+        let mut indirect: BTreeMap<(&str, String), usize> = BTreeMap::new();
+        for call in &self.pointer_calls {
+            let targets: Vec<&String> = self
+                .functions
+                .iter()
+                .filter(|(_, node)| node.address_taken)
+                .filter(|(_, node)| match (call.signature, node.signature) {
+                    (Some(pointer), Some(function)) => pointer == function,
+                    _ => true,
+                })
+                .map(|(name, _)| name)
+                .collect();
+            if targets.is_empty() {
+                *indirect
+                    .entry((&call.caller, format!("(*{})", call.pointer)))
+                    .or_default() += 1;
+            }
+            for target in targets {
+                *indirect.entry((&call.caller, target.clone())).or_default() += 1;
+            }
+        }
+        calls.extend(indirect.into_iter().map(|((caller, callee), count)| Call {
+            caller: caller.to_string(),
+            callee,
+            kind: CallKind::Indirect,
+            count,
+        }));
+        calls
+    }
+
+    /// Functions that can run starting from `roots`
+    pub fn reachable<'a>(&self, roots: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+        let mut callees: HashMap<String, Vec<String>> = HashMap::new();
+        for call in self.calls() {
+            callees.entry(call.caller).or_default().push(call.callee);
+        }
+        let mut reached = HashSet::new();
+        let mut pending: Vec<String> = roots.into_iter().map(str::to_string).collect();
+        while let Some(name) = pending.pop() {
+            if reached.insert(name.clone()) {
+                pending.extend(callees.get(&name).into_iter().flatten().cloned());
+            }
+        }
+        reached
+    }
+
+    /// The graph in Graphviz's DOT language. Functions without a body are
+    /// dashed, as are calls through pointers; edges are labelled with the
+    /// number of call sites when there is more than one.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph callgraph {\n    node [shape=box];\n");
+        for (name, node) in &self.functions {
+            let mut attributes = Vec::new();
+            if !node.defined {
+                attributes.push("style=dashed");
+            }
+            if node.address_taken {
+                attributes.push("peripheries=2");
+            }
+            let _ = write!(dot, "    {}", quote(name));
+            if !attributes.is_empty() {
+                let _ = write!(dot, " [{}]", attributes.join(", "));
+            }
+            dot.push_str(";\n");
+        }
+        for call in self.calls() {
+            if !self.functions.contains_key(&call.callee) {
+                let _ = writeln!(dot, "    {} [shape=plaintext];", quote(&call.callee));
+            }
+            let mut attributes = Vec::new();
+            if call.kind == CallKind::Indirect {
+                attributes.push("style=dashed".to_string());
+            }
+            if call.count > 1 {
+                attributes.push(format!("label=\"{}\"", call.count));
+            }
+            let _ = write!(
+                dot,
+                "    {} -> {}",
+                quote(&call.caller),
+                quote(&call.callee)
+            );
+            if !attributes.is_empty() {
+                let _ = write!(dot, " [{}]", attributes.join(", "));
+            }
+            dot.push_str(";\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph as JSON: `functions` by name, then `calls`
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        #[derive(serde::Serialize)]
+        struct Graph<'a> {
+            functions: &'a BTreeMap<String, FunctionNode>,
+            calls: Vec<Call>,
+        }
+        serde_json::to_string_pretty(&Graph {
+            functions: &self.functions,
+            calls: self.calls(),
+        })
+    }
+
+    fn add(&mut self, program: &Program, file: Option<&str>) {
+        let statics: HashSet<&str> = program
+            .functions
+            .iter()
+            .filter(|function| function.is_static)
+            .map(|function| function.name.as_str())
+            .collect();
+        let key = |name: &str| match file {
+            Some(file) if statics.contains(name) => format!("{}:{}", file, name),
+            _ => name.to_string(),
+        };
+
+        for function in &program.functions {
+            let node = self.functions.entry(key(&function.name)).or_default();
+            node.defined |= has_body(function);
+            node.is_static |= function.is_static;
+            if function.has_prototype {
+                node.signature = Some((function.parameters.len(), function.is_variadic));
+            }
+        }
+
+        let globals: HashMap<&str, &Type> = program
+            .global_variables
+            .iter()
+            .map(|global| (global.name.as_str(), &global.var_type))
+            .collect();
+        let mut unit = Unit {
+            graph: self,
+            key: &key,
+            variables: HashMap::new(),
+            caller: None,
+        };
+        // Functions in initializers, such as tables of handlers, are address-taken
+        for initializer in program
+            .global_variables
+            .iter()
+            .filter_map(|global| global.initializer.as_ref())
+        {
+            unit.expression(initializer);
+        }
+        for function in program.functions.iter().filter(|f| has_body(f)) {
+            unit.variables = globals
+                .iter()
+                .map(|(&name, &ty)| (name.to_string(), ty.clone()))
+                .collect();
+            for (name, ty) in &function.parameters {
+                unit.variables.insert(name.clone(), ty.clone());
+            }
+            declarations(&function.body, &mut unit.variables);
+            unit.caller = Some(key(&function.name));
+            unit.statement(&function.body);
+        }
+    }
+}
+
+/// The walk over one unit's bodies
+struct Unit<'a, K: Fn(&str) -> String> {
+    graph: &'a mut CallGraph,
+    key: &'a K,
+    /// Objects in scope somewhere in the current function, whose names hide functions
+    variables: HashMap<String, Type>,
+    caller: Option<String>,
+}
+
+impl<K: Fn(&str) -> String> Unit<'_, K> {
+    fn statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Expression(expr)
+            | StatementKind::Return(Some(expr))
+            | StatementKind::ComputedGoto(expr) => self.expression(expr),
+            StatementKind::Declaration {
+                initializer: Some(expr),
+                ..
+            } => self.expression(expr),
+            StatementKind::Block(statements) => {
+                for statement in statements {
+                    self.statement(statement);
+                }
+            }
+            StatementKind::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                self.expression(condition);
+                self.statement(then_stmt);
+                if let Some(else_stmt) = else_stmt {
+                    self.statement(else_stmt);
+                }
+            }
+            StatementKind::While { condition, body }
+            | StatementKind::DoWhile { body, condition } => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            StatementKind::For {
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                for expr in [condition, increment].into_iter().flatten() {
+                    self.expression(expr);
+                }
+                self.statement(body);
+            }
+            StatementKind::Switch { expression, cases } => {
+                self.expression(expression);
+                for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                    self.statement(statement);
+                }
+            }
+            StatementKind::Declaration { .. }
+            | StatementKind::Return(None)
+            | StatementKind::Break
+            | StatementKind::Continue
+            | StatementKind::Goto(_)
+            | StatementKind::Label(_)
+            | StatementKind::Fallthrough => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match &expr.kind {
+            ExpressionKind::Identifier(name) if !self.variables.contains_key(name) => {
+                if let Some(node) = self.graph.functions.get_mut(&(self.key)(name)) {
+                    node.address_taken = true;
+                }
+            }
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => {
+                match self.function_name(function) {
+                    Some(name) => {
+                        if let Some(caller) = &self.caller {
+                            let callee = (self.key)(name);
+                            self.graph.functions.entry(callee.clone()).or_default();
+                            *self
+                                .graph
+                                .direct
+                                .entry((caller.clone(), callee))
+                                .or_default() += 1;
+                        }
+                    }
+                    None => {
+                        if let Some(caller) = &self.caller {
+                            self.graph.pointer_calls.push(PointerCall {
+                                caller: caller.clone(),
+                                pointer: describe(function),
+                                signature: self.signature(function),
+                            });
+                        }
+                        self.expression(function);
+                    }
+                }
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            ExpressionKind::Binary { left, right, .. }
+            | ExpressionKind::Index {
+                array: left,
+                index: right,
+            }
+            | ExpressionKind::Assignment {
+                target: left,
+                value: right,
+                ..
+            } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExpressionKind::Unary { operand, .. } => self.expression(operand),
+            ExpressionKind::Member { object, .. } => self.expression(object),
+            ExpressionKind::Cast { expression, .. } => self.expression(expression),
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expression(condition);
+                self.expression(then_expr);
+                self.expression(else_expr);
+            }
+            ExpressionKind::VaArg { list, .. } => self.expression(list),
+            ExpressionKind::IntegerLiteral(_)
+            | ExpressionKind::FloatLiteral(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::CharLiteral(_)
+            | ExpressionKind::BooleanLiteral(_)
+            | ExpressionKind::Identifier(_)
+            | ExpressionKind::Sizeof(_)
+            | ExpressionKind::LabelAddress(_) => {}
+        }
+    }
+
+    /// The function a call names, also through `*` and `&`: `(*f)(x)` calls `f`
+    fn function_name<'e>(&self, function: &'e Expression) -> Option<&'e str> {
+        match &function.kind {
+            ExpressionKind::Identifier(name) if !self.variables.contains_key(name) => Some(name),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference | UnaryOperator::AddressOf,
+                operand,
+            } => self.function_name(operand),
+            _ => None,
+        }
+    }
+
+    /// Parameter count and variadicness of the function a called pointer
+    /// points to, when its type is known here
+    fn signature(&self, function: &Expression) -> Option<(usize, bool)> {
+        let ty = match &function.kind {
+            ExpressionKind::Identifier(name) => self.variables.get(name)?,
+            ExpressionKind::Unary { operand, .. } => return self.signature(operand),
+            _ => return None,
+        };
+        let mut ty = ty;
+        loop {
+            ty = match ty.unqualified() {
+                Type::Typedef(_, aliased) => aliased,
+                Type::Pointer(pointee) => pointee,
+                Type::Function {
+                    parameters,
+                    variadic,
+                    ..
+                } => return Some((parameters.len(), *variadic)),
+                _ => return None,
+            };
+        }
+    }
+}
+
+fn has_body(function: &Function) -> bool {
+    !matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+}
+
+/// Every local declared anywhere in `statement`, with its type
+fn declarations(statement: &Statement, variables: &mut HashMap<String, Type>) {
+    match &statement.kind {
+        StatementKind::Declaration { name, var_type, .. } => {
+            variables.insert(name.clone(), var_type.clone());
+        }
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                declarations(statement, variables);
+            }
+        }
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            declarations(then_stmt, variables);
+            if let Some(else_stmt) = else_stmt {
+                declarations(else_stmt, variables);
+            }
+        }
+        StatementKind::While { body, .. } | StatementKind::DoWhile { body, .. } => {
+            declarations(body, variables)
+        }
+        StatementKind::For { init, body, .. } => {
+            if let Some(init) = init {
+                declarations(init, variables);
+            }
+            declarations(body, variables);
+        }
+        StatementKind::Switch { cases, .. } => {
+            for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                declarations(statement, variables);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Source-like text for a called expression that isn't a function name
+fn describe(expr: &Expression) -> String {
+    match &expr.kind {
+        ExpressionKind::Identifier(name) => name.clone(),
+        ExpressionKind::Member {
+            object,
+            member,
+            is_arrow,
+        } => format!(
+            "{}{}{}",
+            describe(object),
+            if *is_arrow { "->" } else { "." },
+            member
+        ),
+        ExpressionKind::Index { array, .. } => format!("{}[]", describe(array)),
+        ExpressionKind::Unary { operand, .. }
+        | ExpressionKind::Cast {
+            expression: operand,
+            ..
+        } => describe(operand),
+        _ => "...".to_string(),
+    }
+}
+
+/// A DOT identifier for `name`
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
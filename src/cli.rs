@@ -1,4 +1,4 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug, Clone)]
@@ -6,6 +6,9 @@ use std::path::PathBuf;
 #[command(about = "A high-performance C/C++ compiler with GCC compatibility")]
 #[command(version)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Input source files
     #[arg(value_name = "FILE")]
     pub input_files: Vec<PathBuf>,
@@ -38,7 +41,8 @@ pub struct Args {
     #[arg(short = 'g', long = "debug")]
     pub debug: bool,
 
-    /// Warning level
+    /// Warnings: `-Wall`, `-Wextra`, `-W<name>`, `-Wno-<name>`, `-Werror[=<name>]`;
+    /// `-Wl,<options>` passes comma-separated options to the linker instead
     #[arg(short = 'W', long = "warn")]
     pub warnings: Vec<String>,
 
@@ -54,6 +58,15 @@ pub struct Args {
     #[arg(short = 'l', long = "library")]
     pub libraries: Vec<String>,
 
+    /// Link calls to SYMBOL to `__wrap_SYMBOL`, leaving the original reachable
+    /// as `__real_SYMBOL` (`ld --wrap`)
+    #[arg(long = "wrap", value_name = "SYMBOL")]
+    pub wrap: Vec<String>,
+
+    /// Define SYMBOL as the address EXPRESSION gives at link time (`ld --defsym`)
+    #[arg(long = "defsym", value_name = "SYMBOL=EXPRESSION")]
+    pub defsym: Vec<String>,
+
     /// Define preprocessor macros
     #[arg(short = 'D', long = "define")]
     pub defines: Vec<String>,
@@ -74,10 +87,18 @@ pub struct Args {
     #[arg(long = "pic")]
     pub pic: bool,
 
-    /// Position independent executable
+    /// Position independent executable (the default on amd64 and arm64 when linking libc dynamically)
     #[arg(long = "pie")]
     pub pie: bool,
 
+    /// Link a position-dependent executable, with absolute addressing
+    #[arg(long = "no-pie", conflicts_with = "pie")]
+    pub no_pie: bool,
+
+    /// Don't record the alecc version and flags in each object's `.comment` section
+    #[arg(long = "no-ident")]
+    pub no_ident: bool,
+
     /// Static linking
     #[arg(long = "static")]
     pub static_link: bool,
@@ -86,6 +107,133 @@ pub struct Args {
     #[arg(long = "shared")]
     pub shared: bool,
 
+    /// Don't link libc; link alecc's raw-syscall runtime (read, write, exit) instead
+    #[arg(long = "nostdlib")]
+    pub nostdlib: bool,
+
+    /// Don't emit the `_start` entry stub; the program defines its own `_start`
+    #[arg(long = "nostartfiles")]
+    pub nostartfiles: bool,
+
+    /// Enter `main` through libc's crt1.o and `__libc_start_main` instead of
+    /// alecc's `_start`; the default when linking dynamically against libc
+    #[arg(long = "fuse-crt", overrides_with = "no_use_crt")]
+    pub use_crt: bool,
+
+    /// Emit alecc's `_start` even when linking against libc
+    #[arg(long = "fno-use-crt", overrides_with = "use_crt")]
+    pub no_use_crt: bool,
+
+    /// Never use the amd64 red zone below the stack pointer (for kernel and interrupt code)
+    #[arg(long = "mno-red-zone")]
+    pub no_red_zone: bool,
+
+    /// Address the frame from the stack pointer instead of keeping a frame
+    /// pointer; the default from -O1 on
+    #[arg(long = "fomit-frame-pointer", overrides_with = "no_omit_frame_pointer")]
+    pub omit_frame_pointer: bool,
+
+    /// Keep the frame pointer in every function, even with optimization
+    #[arg(long = "fno-omit-frame-pointer", overrides_with = "omit_frame_pointer")]
+    pub no_omit_frame_pointer: bool,
+
+    /// Guard functions with character arrays of 8 bytes or more against
+    /// stack smashing with a canary checked before they return
+    #[arg(
+        long = "fstack-protector",
+        overrides_with_all = ["stack_protector_strong", "stack_protector_all", "no_stack_protector"]
+    )]
+    pub stack_protector: bool,
+
+    /// Guard functions with any local array, or a local whose address is taken
+    #[arg(
+        long = "fstack-protector-strong",
+        overrides_with_all = ["stack_protector", "stack_protector_all", "no_stack_protector"]
+    )]
+    pub stack_protector_strong: bool,
+
+    /// Guard every function
+    #[arg(
+        long = "fstack-protector-all",
+        overrides_with_all = ["stack_protector", "stack_protector_strong", "no_stack_protector"]
+    )]
+    pub stack_protector_all: bool,
+
+    /// Guard no function (the default)
+    #[arg(
+        long = "fno-stack-protector",
+        overrides_with_all = ["stack_protector", "stack_protector_strong", "stack_protector_all"]
+    )]
+    pub no_stack_protector: bool,
+
+    /// Compute float and double with SSE2 on i386, which needs a Pentium 4 or
+    /// later; amd64 and arm64 always have vector floating point
+    #[arg(long = "msse2")]
+    pub sse2: bool,
+
+    /// Stack size in bytes recorded in the executable (`ld -z stack-size`)
+    #[arg(long = "stack-size", value_name = "BYTES")]
+    pub stack_size: Option<u64>,
+
+    /// Write each function's frame size to a `.su` file, like GCC's -fstack-usage
+    #[arg(long = "fstack-usage")]
+    pub stack_usage: bool,
+
+    /// Put each function in a section of its own, `.text.<name>`, so the
+    /// linker's --gc-sections can drop the ones nothing calls
+    #[arg(long = "ffunction-sections")]
+    pub function_sections: bool,
+
+    /// Put each global variable in a section of its own, such as
+    /// `.data.<name>` or `.bss.<name>`, for --gc-sections and linker scripts
+    #[arg(long = "fdata-sections")]
+    pub data_sections: bool,
+
+    /// Annotate the assembly with comments: the C source line each statement
+    /// comes from, and where each function keeps its parameters and locals
+    #[arg(long = "fverbose-asm")]
+    pub verbose_asm: bool,
+
+    /// Keep each file's assembly as a `.s` next to its object instead of
+    /// assembling from a temporary file
+    #[arg(long = "save-asm-with-object")]
+    pub save_asm: bool,
+
+    /// What a non-void function other than `main` returns when control
+    /// reaches its closing brace; `main` returns 0
+    #[arg(
+        long = "fmissing-return",
+        value_enum,
+        value_name = "MODE",
+        default_value = "unspecified"
+    )]
+    pub missing_return: MissingReturn,
+
+    /// Most macros one macro's expansion may nest, e.g. `A` expanding to `B`
+    /// expanding to `C` is 3; deeper expansions are an error
+    #[arg(long = "fmacro-depth", value_name = "N", default_value_t = 256)]
+    pub macro_depth: usize,
+
+    /// Merge all C sources into one unit before optimizing, so inlining and
+    /// dead code elimination work across files; only `main` stays exported
+    #[arg(long = "fwhole-program")]
+    pub whole_program: bool,
+
+    /// Run the static analyzer: null dereferences, returned local addresses and
+    /// constant out-of-bounds indices, reported as -Wanalyzer-* warnings
+    #[arg(long = "analyze", alias = "fanalyzer")]
+    pub analyze: bool,
+
+    /// Print a summary of each source file after compiling it: lines, tokens,
+    /// functions, globals and generated instructions
+    #[arg(long = "summary")]
+    pub summary: bool,
+
+    /// Prepend PREFIX to the internal labels and static functions of each
+    /// translation unit, so objects linked together stay distinguishable
+    #[arg(long = "symbol-prefix", value_name = "PREFIX")]
+    pub symbol_prefix: Option<String>,
+
     /// Thread model
     #[arg(long = "thread-model", default_value = "posix")]
     pub thread_model: String,
@@ -98,9 +246,288 @@ pub struct Args {
     #[arg(long = "sysroot")]
     pub sysroot: Option<PathBuf>,
 
+    /// Directory searched before PATH for the assembler and linker, such as
+    /// the `bin` directory of a cross binutils
+    #[arg(long = "toolchain", value_name = "DIR")]
+    pub toolchain: Option<PathBuf>,
+
     /// Additional compiler flags
     #[arg(long = "extra-flags")]
     pub extra_flags: Vec<String>,
+
+    /// Print the phases and commands that would run without executing them (like gcc -###)
+    #[arg(short = '#', long = "dry-run", overrides_with = "dry_run")]
+    pub dry_run: bool,
+
+    /// Print diagnostics as soon as they are reported instead of grouped by input file
+    #[arg(long = "stream-diagnostics")]
+    pub stream_diagnostics: bool,
+
+    /// Serve compile jobs on ADDRESS, a Unix socket path or HOST:PORT, instead
+    /// of compiling; requires the `daemon` feature
+    #[arg(long = "daemon", value_name = "ADDRESS")]
+    pub daemon: Option<PathBuf>,
+
+    /// Preprocess locally and have the daemon at HOST:PORT compile each unit
+    /// to an object; linking stays local. Requires the `daemon` feature
+    #[arg(long = "remote", value_name = "HOST:PORT", conflicts_with = "daemon")]
+    pub remote: Option<String>,
+
+    /// Emit an alternate output form instead of assembly/objects (e.g. `--emit=llvm-ir`)
+    #[arg(long = "emit", value_enum, value_name = "KIND")]
+    pub emit: Option<EmitKind>,
+
+    /// Write the checked AST of each input instead of compiling it; requires the `serde` feature
+    #[arg(
+        long = "emit-ast",
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with = "emit"
+    )]
+    pub emit_ast: Option<AstFormat>,
+
+    /// Also write the call graph of all inputs, to `<output>.callgraph.dot`
+    /// or `.json`; JSON requires the `serde` feature
+    #[arg(long = "emit-callgraph", value_enum, value_name = "FORMAT")]
+    pub emit_callgraph: Option<CallGraphFormat>,
+
+    /// Code generator to use; `cranelift` requires building with the `cranelift` feature
+    #[arg(
+        long = "backend",
+        value_enum,
+        value_name = "BACKEND",
+        default_value = "native"
+    )]
+    pub backend: Backend,
+
+    /// Assembler the generated assembly is written for (and assembled with)
+    #[arg(
+        long = "as-dialect",
+        value_enum,
+        value_name = "DIALECT",
+        default_value = "gnu"
+    )]
+    pub as_dialect: AsDialect,
+
+    /// Syntax of the x86 assembly written by -S and handed to the assembler
+    #[arg(
+        long = "masm",
+        value_enum,
+        value_name = "DIALECT",
+        default_value = "intel"
+    )]
+    pub asm_syntax: AsmSyntax,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Write API documentation from the `/** ... */` comments in C headers
+    Doc(DocArgs),
+    /// Reformat C sources to a consistent layout
+    Fmt(FmtArgs),
+    /// Print an assembly file with a note on what each line does
+    ExplainAsm(ExplainAsmArgs),
+    /// Re-run one entry of a compilation database with its recorded flags
+    Replay(ReplayArgs),
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct DocArgs {
+    /// Headers (or sources) to document, in the order they should appear
+    #[arg(value_name = "HEADER", required = true)]
+    pub headers: Vec<PathBuf>,
+
+    /// Output format
+    #[arg(
+        long = "format",
+        value_enum,
+        value_name = "FORMAT",
+        default_value = "markdown"
+    )]
+    pub format: DocFormat,
+
+    /// Write the documentation to FILE instead of standard output
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Title of the generated page
+    #[arg(long = "title", default_value = "API reference")]
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct ExplainAsmArgs {
+    /// Assembly file to explain, such as one written by `-S`
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    /// Target the assembly is for, when its directives don't say
+    #[arg(short = 't', long = "target", value_name = "TARGET")]
+    pub target: Option<String>,
+
+    /// Write the explained assembly here instead of to stdout
+    #[arg(short = 'o', value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct ReplayArgs {
+    /// Source file whose entry to re-run; may be omitted when there is only one
+    #[arg(value_name = "FILE", conflicts_with = "index")]
+    pub file: Option<PathBuf>,
+
+    /// The compile_commands.json to read, or the directory holding it
+    #[arg(
+        short = 'p',
+        long = "database",
+        value_name = "PATH",
+        default_value = "compile_commands.json"
+    )]
+    pub database: PathBuf,
+
+    /// Re-run the entry at this position instead (see --list)
+    #[arg(long = "index", value_name = "N")]
+    pub index: Option<usize>,
+
+    /// Optimization level to use instead of the recorded one
+    #[arg(short = 'O', value_name = "LEVEL")]
+    pub optimization: Option<String>,
+
+    /// Target to compile for instead of the recorded one
+    #[arg(short = 't', long = "target", value_name = "TARGET")]
+    pub target: Option<String>,
+
+    /// List the entries with their positions
+    #[arg(long = "list")]
+    pub list: bool,
+
+    /// Print the command instead of running it
+    #[arg(long = "print")]
+    pub print: bool,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct FmtArgs {
+    /// Files to format
+    #[arg(value_name = "FILE", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Rewrite the files instead of printing the result
+    #[arg(short = 'i', long = "in-place")]
+    pub in_place: bool,
+
+    /// Only list the files that are not formatted, failing if there are any
+    #[arg(long = "check", conflicts_with = "in_place")]
+    pub check: bool,
+
+    /// Columns per indentation level
+    #[arg(long = "indent-width", value_name = "N", default_value_t = 4)]
+    pub indent_width: usize,
+
+    /// Indent with tabs, one per level
+    #[arg(long = "use-tabs")]
+    pub use_tabs: bool,
+
+    /// Where opening braces go
+    #[arg(
+        long = "brace-style",
+        value_enum,
+        value_name = "STYLE",
+        default_value = "attach"
+    )]
+    pub brace_style: BraceStyle,
+
+    /// Longest run of blank lines kept
+    #[arg(long = "max-blank-lines", value_name = "N", default_value_t = 1)]
+    pub max_blank_lines: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BraceStyle {
+    /// On the line that opens the block
+    Attach,
+    /// On a line of their own for function bodies, attached elsewhere
+    Linux,
+    /// Always on a line of their own
+    Allman,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EmitKind {
+    /// Textual LLVM IR (.ll) for clang/llc
+    LlvmIr,
+    /// Alecc's own SSA IR (.ir) as text
+    Ir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AstFormat {
+    /// The `Program` as JSON, one file per input
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CallGraphFormat {
+    /// Graphviz's DOT language
+    Dot,
+    /// Functions by name and a list of calls, as JSON
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// Built-in assembly generator, assembled with the system `as`
+    Native,
+    /// Cranelift, writing object files directly
+    Cranelift,
+    /// Built-in assembly generator going through the SSA IR (see `--emit=ir`)
+    Ir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum MissingReturn {
+    /// Whatever the return register holds
+    #[default]
+    Unspecified,
+    /// Return 0
+    Zero,
+    /// Stop the program with an illegal instruction
+    Trap,
+}
+
+/// Which functions get a stack canary (`-fstack-protector` and its variants)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StackProtector {
+    #[default]
+    Off,
+    /// Functions with a character array of 8 bytes or more
+    Basic,
+    /// Functions with any array, or a local whose address is taken
+    Strong,
+    /// Every function
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AsDialect {
+    /// GNU as
+    Gnu,
+    /// LLVM's integrated assembler (clang, llvm-mc)
+    Llvm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AsmSyntax {
+    /// Destination first, no register prefixes (`.intel_syntax noprefix`)
+    Intel,
+    /// Source first, `%` registers and `$` immediates, as GNU tools print it
+    Att,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -1,20 +1,169 @@
+use crate::abi::{aggregate_passing, AggregatePassing, Piece};
+use crate::analysis::{allocates_on_stack, completes, needs_stack_protector, noreturn_functions};
+use crate::att;
+use crate::builtins::{
+    atomic, is_builtin, library_function, signature, Atomic, AtomicOp, AtomicOperation,
+    MemoryOrder, BUILTINS,
+};
+use crate::cli::{AsDialect, AsmSyntax, MissingReturn, StackProtector};
+use crate::constant::{floating_constant, ConstantEvaluator};
 use crate::error::{AleccError, Result};
+use crate::frame::FrameLayout;
+use crate::ir_codegen::IrAssembler;
+use crate::layout::TypeLayout;
+use crate::lower::LoweringContext;
 use crate::parser::{
-    BinaryOperator, Expression, Function, Program, Statement, Type, UnaryOperator,
+    AssignmentOperator, BinaryOperator, Expression, ExpressionKind, Function, GlobalStorage,
+    Program, Statement, StatementKind, Type, UnaryOperator,
 };
+use crate::runtime::I386_DIVISION_HELPERS;
 use crate::targets::Target;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Stand-in for the frame size in prologues, patched once the body has been
+/// generated and every local has been given a slot.
+const FRAME_SIZE_PLACEHOLDER: &str = "__alecc_frame_size__";
+
+/// Stand-in line for the `-fverbose-asm` map of a function's stack slots,
+/// which is only known once the frame pointer has been kept or dropped. A
+/// comment itself, so the passes over the function's text leave it alone.
+const SLOT_MAP_PLACEHOLDER: &str = "# __alecc_slot_map__";
+
+/// Fewest cases a switch dispatches through a jump table
+const JUMP_TABLE_MIN_CASES: usize = 4;
+/// Most table entries per case: sparser switches compare case by case
+const JUMP_TABLE_MAX_SPREAD: i128 = 3;
+
+/// Integer argument registers, in order
+const AMD64_ARGUMENT_REGISTERS: &[&str] = &["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const ARM64_ARGUMENT_REGISTERS: &[&str] = &["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"];
+
+/// Size of the amd64 register save area: six GP registers plus eight SSE registers.
+const AMD64_REG_SAVE_AREA_SIZE: i32 = 6 * 8 + 8 * 16;
+/// Size of the arm64 register save areas: x0-x7 plus q0-q7.
+const ARM64_REG_SAVE_AREA_SIZE: i32 = 8 * 8 + 8 * 16;
+
+/// Where an argument is passed: in the nth integer or floating-point
+/// argument register, or from the nth 8-byte stack slot on. A struct or union
+/// can be split across registers a piece at a time, or (on arm64) be a copy
+/// whose address is passed instead.
+#[derive(Debug, Clone)]
+enum ArgumentLocation {
+    Integer(usize),
+    Floating(usize),
+    Stack(usize),
+    /// Each piece with its integer or floating-point register
+    Split(Vec<(Piece, usize)>),
+    Indirect(Box<ArgumentLocation>),
+}
+
+/// What an argument asks of the calling convention
+#[derive(Debug, Clone)]
+enum ArgumentClass {
+    Integer,
+    /// `float` or `double`
+    Floating,
+    /// A struct or union of this many bytes
+    Aggregate(usize, AggregatePassing),
+}
+
+/// The type of the function a call calls
+#[derive(Debug, Clone)]
+struct CallSignature {
+    return_type: Option<Type>,
+    /// `None` without a prototype
+    parameters: Option<Vec<Type>>,
+    variadic: bool,
+}
+
+/// Where a call's arguments go, and what they take altogether
+#[derive(Debug, Clone, Default)]
+struct ArgumentLayout {
+    locations: Vec<ArgumentLocation>,
+    integers: usize,
+    floats: usize,
+    stack_slots: usize,
+}
+
+/// What a global variable starts out holding
+#[derive(Debug, Clone, PartialEq)]
+enum GlobalValue {
+    /// These bits, in the variable's size
+    Bits(i64),
+    /// The address of a global, a function or a string literal, plus a
+    /// number of bytes, which the linker works out
+    Address(String, i64),
+    /// The characters of a string literal, zero-padded to the array's size
+    Characters(String),
+}
+
+/// A loop or switch being generated: where `break` goes, and for loops
+/// where `continue` goes
+#[derive(Debug, Clone)]
+struct JumpContext {
+    break_label: String,
+    /// `None` for a switch, which `continue` passes through
+    continue_label: Option<String>,
+}
 
 pub struct CodeGenerator {
     target: Target,
     output: String,
     label_counter: usize,
+    label_prefix: String, // Makes local labels unique to the translation unit
     string_literals: HashMap<String, String>,
+    current_function: String,
     current_function_params: Vec<(String, i32)>, // (name, stack_offset)
-    epilogue_emitted: bool,
-    local_variables: HashMap<String, i32>, // (name, stack_offset)
-    stack_offset: i32,                     // Current stack offset for local variables
-    last_call_stack_cleanup: usize,        // Stack bytes to clean up after last call
+    local_variables: HashMap<String, i32>,       // (name, stack_offset)
+    frame: Option<FrameLayout>,                  // Slots of the current function's locals
+    declarations_seen: usize, // Locals of the current function given their slot so far
+    jump_contexts: Vec<JumpContext>, // Enclosing loops and switches, innermost last
+    last_call_stack_cleanup: usize, // Stack bytes to clean up after last call
+    local_types: HashMap<String, Type>, // Also holds the parameters' types
+    global_variables: HashMap<String, Type>,
+    return_types: HashMap<String, Type>,
+    parameter_types: HashMap<String, Vec<Type>>, // Of the functions with a prototype
+    variadic_functions: HashSet<String>,         // Declared with `...`
+    floating_return: Option<Type>, // The current function's, if it returns float or double
+    return_type: Option<Type>,     // The current function's
+    parameter_layout: ArgumentLayout, // Where the current function's arrive
+    aggregate_return: Option<(usize, AggregatePassing)>, // The current function's struct or union result
+    return_buffer: Option<i32>, // Slot of the address a struct or union result goes to
+    stack_depth: i64,           // Bytes the current function has pushed or reserved below its frame
+    stack_moves: Vec<(usize, i64)>, // Where in `output` each of those moves ends, and by how much
+    layout: TypeLayout,
+    is_variadic: bool,
+    frame_bottom_reserve: i32, // Bytes kept below all locals, e.g. the register save area
+    parameter_base: usize,     // Bytes below the frame pointer above the parameter slots
+    as_dialect: AsDialect,
+    syntax: AsmSyntax,
+    missing_return: MissingReturn,
+    emit_start: bool,
+    red_zone: bool,
+    omit_frame_pointer: bool,
+    stack_protector: StackProtector,
+    stack_guard_symbol: bool,
+    canary: Option<i32>, // Slot of the current function's stack canary, if it has one
+    alloca_bottom: Option<i32>, // Slot of where the temporaries end, once alloca can move them
+    sse2: bool,
+    uses_floating_point: bool, // Whether the current function computes with float or double
+    divides_wide: bool,        // Whether i386 code divides long long values, with helpers
+    binding: SymbolBinding,
+    defined_functions: HashSet<String>, // Functions with a body in this translation unit
+    wrapped_symbols: HashSet<String>,
+    noreturn_functions: HashSet<String>,
+    ident: Option<String>,
+    function_sections: bool, // Each function in its own `.text.<name>`
+    data_sections: bool,     // Each global in its own `.data.<name>`, `.bss.<name>`...
+    verbose_asm: bool,
+    source_listing: Option<(String, Vec<String>)>, // File name and lines for -fverbose-asm
+    annotated_line: usize, // Source line the last -fverbose-asm comment quoted
+    slots: Vec<(String, i32, usize)>, // The current function's: name, frame offset and size
+    stack_usage: Vec<(String, usize)>,
+    instruction_counts: Vec<(String, usize)>,
+    use_ir: bool,
+    optimize_ir: bool,
+    lowering: Option<LoweringContext>, // Set up by `generate` when going through the IR
 }
 
 impl CodeGenerator {
@@ -23,20 +172,220 @@ impl CodeGenerator {
             target,
             output: String::new(),
             label_counter: 0,
+            label_prefix: String::new(),
             string_literals: HashMap::new(),
+            current_function: String::new(),
             current_function_params: Vec::new(),
-            epilogue_emitted: false,
+            noreturn_functions: HashSet::new(),
             local_variables: HashMap::new(),
-            stack_offset: 0,
+            frame: None,
+            declarations_seen: 0,
+            jump_contexts: Vec::new(),
             last_call_stack_cleanup: 0,
+            local_types: HashMap::new(),
+            global_variables: HashMap::new(),
+            return_types: HashMap::new(),
+            parameter_types: HashMap::new(),
+            variadic_functions: HashSet::new(),
+            floating_return: None,
+            return_type: None,
+            parameter_layout: ArgumentLayout::default(),
+            aggregate_return: None,
+            return_buffer: None,
+            stack_depth: 0,
+            stack_moves: Vec::new(),
+            layout: TypeLayout::new(target),
+            is_variadic: false,
+            frame_bottom_reserve: 0,
+            parameter_base: 0,
+            as_dialect: AsDialect::Gnu,
+            syntax: AsmSyntax::Intel,
+            missing_return: MissingReturn::Unspecified,
+            emit_start: true,
+            red_zone: true,
+            omit_frame_pointer: false,
+            stack_protector: StackProtector::Off,
+            stack_guard_symbol: false,
+            canary: None,
+            alloca_bottom: None,
+            sse2: false,
+            uses_floating_point: false,
+            divides_wide: false,
+            binding: SymbolBinding::default(),
+            defined_functions: HashSet::new(),
+            wrapped_symbols: HashSet::new(),
+            ident: None,
+            function_sections: false,
+            data_sections: false,
+            verbose_asm: false,
+            source_listing: None,
+            annotated_line: 0,
+            slots: Vec::new(),
+            stack_usage: Vec::new(),
+            instruction_counts: Vec::new(),
+            use_ir: false,
+            optimize_ir: false,
+            lowering: None,
         }
     }
 
+    /// Frame size in bytes of every function generated so far, as `--fstack-usage` reports it
+    pub fn stack_usage(&self) -> &[(String, usize)] {
+        &self.stack_usage
+    }
+
+    /// Instructions emitted for every function generated so far, for `--summary`
+    pub fn instruction_counts(&self) -> &[(String, usize)] {
+        &self.instruction_counts
+    }
+
+    /// Whether leaf functions may keep their frame in the red zone instead of
+    /// moving the stack pointer (off for `--mno-red-zone`, e.g. kernel code)
+    pub fn with_red_zone(mut self, red_zone: bool) -> Self {
+        self.red_zone = red_zone;
+        self
+    }
+
+    /// Whether functions may do without a frame pointer (`-fomit-frame-pointer`).
+    /// Direct code moves the stack pointer as it evaluates, so only amd64
+    /// leaf functions in the red zone drop it there; IR code addresses any
+    /// frame from the stack pointer
+    pub fn with_frame_pointer_omission(mut self, omit: bool) -> Self {
+        self.omit_frame_pointer = omit;
+        self
+    }
+
+    /// Which functions get a canary between their locals and the saved frame
+    /// pointer, checked before they return (`-fstack-protector`)
+    pub fn with_stack_protector(mut self, stack_protector: StackProtector) -> Self {
+        self.stack_protector = stack_protector;
+        self
+    }
+
+    /// Whether the canary's reference value is the global `__stack_chk_guard`
+    /// rather than the word libc keeps in the thread control block on x86.
+    /// arm64 always uses the global
+    pub fn with_stack_guard_symbol(mut self, symbol: bool) -> Self {
+        self.stack_guard_symbol = symbol;
+        self
+    }
+
+    /// Whether i386 code may use SSE2, which float and double need there
+    pub fn with_sse2(mut self, sse2: bool) -> Self {
+        self.sse2 = sse2;
+        self
+    }
+
+    /// Whether the code goes into a position-independent executable: calls to
+    /// functions defined elsewhere then go through the PLT
+    pub fn with_position_independent(mut self, position_independent: bool) -> Self {
+        self.binding.position_independent = position_independent;
+        self
+    }
+
+    /// Whether the code goes into a shared object, whose exported functions and
+    /// variables another module may interpose: their addresses then come from
+    /// the GOT and calls to them go through the PLT
+    pub fn with_shared_object(mut self, shared_object: bool) -> Self {
+        self.binding.shared_object = shared_object;
+        self.binding.position_independent |= shared_object;
+        self
+    }
+
+    /// Functions the link wraps with `--wrap`. ld only redirects references
+    /// it has to resolve, so calls to one defined in this unit name
+    /// `__wrap_<name>` here already
+    pub fn with_wrapped_symbols(mut self, symbols: &[String]) -> Self {
+        self.wrapped_symbols = symbols.iter().cloned().collect();
+        self
+    }
+
+    /// Whether to generate functions through the SSA IR (`--backend=ir`).
+    /// Functions the IR can't express still take the direct path.
+    pub fn with_ir(mut self, use_ir: bool) -> Self {
+        self.use_ir = use_ir;
+        self
+    }
+
+    /// Whether to run the IR optimizations before emitting assembly from it
+    pub fn with_optimization(mut self, optimize: bool) -> Self {
+        self.optimize_ir = optimize;
+        self
+    }
+
+    /// Record `ident` in the object's `.comment` section
+    pub fn with_ident(mut self, ident: Option<&str>) -> Self {
+        self.ident = ident.map(str::to_string);
+        self
+    }
+
+    /// Whether each function goes in a section of its own, `.text.<name>`
+    /// (`-ffunction-sections`), so `ld --gc-sections` can drop unused ones.
+    /// A `section` attribute still wins
+    pub fn with_function_sections(mut self, function_sections: bool) -> Self {
+        self.function_sections = function_sections;
+        self
+    }
+
+    /// Whether each global variable goes in a section of its own
+    /// (`-fdata-sections`): `.data.<name>`, `.bss.<name>` and the like
+    pub fn with_data_sections(mut self, data_sections: bool) -> Self {
+        self.data_sections = data_sections;
+        self
+    }
+
+    /// Annotate the assembly with comments (`-fverbose-asm`): where each
+    /// function keeps its parameters and locals, and before each statement
+    /// the line of `source`, read from `file`, it comes from
+    pub fn with_verbose_asm(mut self, verbose: bool, source: Option<(&str, &str)>) -> Self {
+        self.verbose_asm = verbose;
+        self.source_listing = source
+            .map(|(file, text)| (file.to_string(), text.lines().map(str::to_string).collect()));
+        self
+    }
+
+    /// Whether to emit the `_start` stub for a translation unit defining `main`
+    /// (off for `--nostartfiles`, where the program brings its own entry point)
+    pub fn with_start(mut self, emit_start: bool) -> Self {
+        self.emit_start = emit_start;
+        self
+    }
+
+    /// Put `prefix` after the `.L` of every local label, e.g. `.Lmain_C0` for a
+    /// string literal, so labels from different files never look alike
+    pub fn with_label_prefix(mut self, prefix: &str) -> Self {
+        self.label_prefix = prefix.to_string();
+        self
+    }
+
+    /// Emit directives accepted by the given assembler
+    pub fn with_as_dialect(mut self, as_dialect: AsDialect) -> Self {
+        self.as_dialect = as_dialect;
+        self
+    }
+
+    /// Write x86 assembly in this syntax; arm64 has only one
+    pub fn with_syntax(mut self, syntax: AsmSyntax) -> Self {
+        self.syntax = syntax;
+        self
+    }
+
+    /// What a non-void function returns when control reaches its closing brace
+    pub fn with_missing_return(mut self, missing_return: MissingReturn) -> Self {
+        self.missing_return = missing_return;
+        self
+    }
+
     pub fn generate(&mut self, program: &Program) -> Result<String> {
         // First pass: collect all string literals
         for function in &program.functions {
             self.collect_string_literals_from_statement(&function.body)?;
         }
+        for global in &program.global_variables {
+            if let Some(initializer) = &global.initializer {
+                self.collect_string_literals_from_expression(initializer)?;
+            }
+        }
 
         self.emit_header();
 
@@ -46,16 +395,192 @@ impl CodeGenerator {
             let string_literals = self.string_literals.clone(); // Clone to avoid borrow issues
             for (content, label) in &string_literals {
                 self.emit_line(&format!("{}:", label));
-                self.emit_line(&format!("    .string \"{}\"", self.escape_string(content)));
+                // .asciz is the spelling both assemblers agree on for every target
+                let directive = match self.as_dialect {
+                    AsDialect::Gnu => ".string",
+                    AsDialect::Llvm => ".asciz",
+                };
+                self.emit_line(&format!(
+                    "    {} \"{}\"",
+                    directive,
+                    self.escape_string(content)
+                ));
             }
             self.emit_line("");
         }
 
-        // Generate global variables
-        if !program.global_variables.is_empty() {
-            self.emit_line(".section .data");
-            for (name, var_type, _initializer) in &program.global_variables {
-                self.emit_global_variable(name, var_type)?;
+        self.layout.record_aggregates(program);
+        self.global_variables = program
+            .global_variables
+            .iter()
+            .map(|global| (global.name.clone(), global.var_type.clone()))
+            .collect();
+        self.return_types = program
+            .functions
+            .iter()
+            .map(|function| (function.name.clone(), function.return_type.clone()))
+            .collect();
+        self.parameter_types = program
+            .functions
+            .iter()
+            .filter(|function| function.has_prototype)
+            .map(|function| {
+                let types = function.parameters.iter().map(|(_, ty)| ty.clone());
+                (function.name.clone(), types.collect())
+            })
+            .collect();
+        self.variadic_functions = program
+            .functions
+            .iter()
+            .filter(|function| function.is_variadic)
+            .map(|function| function.name.clone())
+            .collect();
+        // Calls to the builtins are checked and converted like any other
+        for &name in BUILTINS {
+            if let Some(Type::Function {
+                return_type,
+                parameters,
+                ..
+            }) = signature(name)
+            {
+                if !self.return_types.contains_key(name) {
+                    self.return_types.insert(name.to_string(), *return_type);
+                    self.parameter_types.insert(name.to_string(), parameters);
+                }
+            }
+        }
+        self.defined_functions = program
+            .functions
+            .iter()
+            .filter(|f| !is_prototype(f))
+            .map(|f| f.name.clone())
+            .collect();
+        self.noreturn_functions = noreturn_functions(program);
+        self.binding.defined = self.defined_functions.clone();
+        self.binding.defined.extend(
+            program
+                .global_variables
+                .iter()
+                .filter(|global| global.storage != Some(GlobalStorage::Extern))
+                .map(|global| global.name.clone()),
+        );
+        self.binding.local = program
+            .functions
+            .iter()
+            .filter(|function| function.is_static)
+            .map(|function| function.name.clone())
+            .chain(
+                program
+                    .global_variables
+                    .iter()
+                    .filter(|global| global.storage == Some(GlobalStorage::Static))
+                    .map(|global| global.name.clone()),
+            )
+            .collect();
+        self.binding.thread_local = program
+            .global_variables
+            .iter()
+            .filter(|global| global.thread_local)
+            .map(|global| global.name.clone())
+            .collect();
+        if self.use_ir {
+            self.lowering = Some(
+                LoweringContext::new(self.target, program)
+                    .with_label_prefix(&self.label_prefix)
+                    .with_missing_return(self.missing_return)
+                    .with_optimization(self.optimize_ir)
+                    .with_strings(self.string_literals.clone()),
+            );
+        }
+
+        // Generate global variables: those with a value in .data, the zeroed
+        // ones in .bss, which takes no room in the file. An initializer that
+        // doesn't fold stays in .data, to be reported as it is emitted
+        let mut defined = HashSet::new();
+        let definitions: Vec<_> = program
+            .global_variables
+            .iter()
+            .filter(|global| {
+                // An initializer wins over tentative definitions and `extern` declarations
+                global.storage != Some(GlobalStorage::Extern)
+                    && (global.initializer.is_some()
+                        || !program
+                            .global_variables
+                            .iter()
+                            .any(|other| other.name == global.name && other.initializer.is_some()))
+            })
+            .filter(|global| defined.insert(&global.name))
+            .collect();
+        let (thread_local, definitions): (Vec<_>, Vec<_>) = definitions
+            .into_iter()
+            .partition(|global| global.thread_local);
+        let (data, bss): (Vec<_>, Vec<_>) = definitions.into_iter().partition(|global| {
+            global.attributes.section.is_some()
+                || !self.starts_zeroed(&global.name, &global.var_type, global.initializer.as_ref())
+        });
+        // Thread-locals are the template every thread's copies start from
+        let (tdata, tbss): (Vec<_>, Vec<_>) = thread_local.into_iter().partition(|global| {
+            !self.starts_zeroed(&global.name, &global.var_type, global.initializer.as_ref())
+        });
+        let (progbits, nobits) = match self.target {
+            Target::Arm64 => ("%progbits", "%nobits"),
+            Target::I386 | Target::Amd64 => ("@progbits", "@nobits"),
+        };
+        let groups = [
+            (".data", "aw", progbits, data),
+            (".bss", "aw", nobits, bss),
+            (".tdata", "awT", progbits, tdata),
+            (".tbss", "awT", nobits, tbss),
+        ];
+        for (section_name, flags, kind, globals) in groups {
+            if globals.is_empty() {
+                continue;
+            }
+            // The assembler knows the flags of .data and .bss by name
+            let directive = match section_name {
+                ".data" | ".bss" => format!(".section {}", section_name),
+                _ => format!(".section {},\"{}\",{}", section_name, flags, kind),
+            };
+            self.emit_line(&directive);
+            for global in globals {
+                let name = &global.name;
+                let var_type = &global.var_type;
+                let section = global.attributes.section.as_ref();
+                if let Some(section) = section {
+                    self.emit_line(&self.push_section(section, "aw"));
+                } else if self.data_sections {
+                    self.emit_line(&format!(
+                        ".pushsection {}.{},\"{}\",{}",
+                        section_name, name, flags, kind
+                    ));
+                }
+                // Laid out at its type's alignment, as gcc does, unless _Alignas asks for more
+                let alignment = global
+                    .attributes
+                    .aligned
+                    .unwrap_or(0)
+                    .max(self.layout.align_of(var_type));
+                let kind = if global.thread_local {
+                    "tls_object"
+                } else {
+                    "object"
+                };
+                self.emit_line(&self.symbol_type(name, kind));
+                self.emit_line(&format!(
+                    ".size {}, {}",
+                    name,
+                    self.layout.size_of(var_type)
+                ));
+                if global.storage != Some(GlobalStorage::Static) {
+                    self.emit_line(&format!(".globl {}", name));
+                }
+                if alignment > 1 {
+                    self.emit_line(&format!("    .balign {}", alignment));
+                }
+                self.emit_global_variable(name, var_type, global.initializer.as_ref())?;
+                if section.is_some() || self.data_sections {
+                    self.emit_line(".popsection");
+                }
             }
             self.emit_line("");
         }
@@ -63,43 +588,304 @@ impl CodeGenerator {
         // Generate functions
         self.emit_line(".section .text");
         for function in &program.functions {
+            let section = function.attributes.section.clone().or_else(|| {
+                (self.function_sections && !is_prototype(function))
+                    .then(|| format!(".text.{}", function.name))
+            });
+            if let Some(section) = &section {
+                self.emit_line(&self.push_section(section, "ax"));
+            }
             self.generate_function(function)?;
+            if section.is_some() {
+                self.emit_line(".popsection");
+            }
+        }
+        if self.divides_wide {
+            self.emit_line("");
+            for line in I386_DIVISION_HELPERS.lines() {
+                self.emit_line(line);
+            }
+        }
+
+        // The startup code calls these before and after main. The linker
+        // sorts prioritized entries by their section's suffix, ahead of the rest
+        for (array, constructors) in [(".init_array", true), (".fini_array", false)] {
+            let mut sections: Vec<(Option<u16>, Vec<&str>)> = Vec::new();
+            for function in program.functions.iter().filter(|f| !is_prototype(f)) {
+                let (marked, priority) = if constructors {
+                    (
+                        function.attributes.constructor,
+                        function.attributes.constructor_priority,
+                    )
+                } else {
+                    (
+                        function.attributes.destructor,
+                        function.attributes.destructor_priority,
+                    )
+                };
+                if !marked {
+                    continue;
+                }
+                match sections.iter_mut().find(|(p, _)| *p == priority) {
+                    Some((_, names)) => names.push(&function.name),
+                    None => sections.push((priority, vec![&function.name])),
+                }
+            }
+            sections.sort_by_key(|(priority, _)| *priority);
+            let directive = match self.target.pointer_size() {
+                4 => ".long",
+                _ => ".quad",
+            };
+            for (priority, names) in sections {
+                let section = match priority {
+                    Some(priority) => format!("{}.{:05}", array, priority),
+                    None => array.to_string(),
+                };
+                self.emit_line("");
+                self.emit_line(&self.push_section(&section, "aw"));
+                self.emit_line(&format!("    .balign {}", self.target.pointer_size()));
+                for name in names {
+                    self.emit_line(&format!("    {} {}", directive, name));
+                }
+                self.emit_line(".popsection");
+            }
         }
 
-        // Generate _start entry point
-        self.generate_start_function()?;
+        // Generate _start entry point next to main, so only one object in a link has it
+        let main = program.functions.iter().find(|f| {
+            f.name == "main"
+                && !matches!(&f.body.kind, StatementKind::Block(statements) if statements.is_empty())
+        });
+        if let Some(main) = main.filter(|_| self.emit_start) {
+            let returns_void = matches!(self.layout.resolve(&main.return_type), Type::Void);
+            self.generate_start_function(returns_void)?;
+        }
+
+        if let Some(ident) = self.ident.clone() {
+            self.emit_line("");
+            self.emit_line(&format!("    .ident \"{}\"", self.escape_string(&ident)));
+        }
 
+        if self.syntax == AsmSyntax::Att && self.target != Target::Arm64 {
+            return Ok(att::translate(&self.output));
+        }
         Ok(self.output.clone())
     }
 
-    fn generate_start_function(&mut self) -> Result<()> {
+    /// The entry point: run the constructors, call `main(argc, argv, envp)`
+    /// with what the kernel left on the stack, run the destructors and exit
+    /// with main's result, or 0 when it returns `void`
+    fn generate_start_function(&mut self, main_returns_void: bool) -> Result<()> {
         self.emit_line("");
         self.emit_line(".globl _start");
+        self.emit_line(&self.symbol_type("_start", "function"));
         self.emit_line("_start:");
+        // The return address is undefined, which ends a backtrace here
+        self.emit_line("    .cfi_startproc");
+        let return_address = match self.target {
+            Target::I386 => 8,
+            Target::Amd64 => 16,
+            Target::Arm64 => 30,
+        };
+        self.emit_line(&format!("    .cfi_undefined {}", return_address));
+
+        match self.target {
+            Target::I386 => {
+                // argc is on top of the stack, then argv's pointers, a null and envp's
+                self.emit_line("    xor ebp, ebp");
+                self.emit_line("    mov eax, DWORD PTR [esp]");
+                self.emit_line("    lea ecx, [esp + 4]");
+                self.emit_line("    lea edx, [ecx + eax*4 + 4]");
+                // Outermost frame, stack aligned for the call
+                self.emit_line(&format!("    and esp, -{}", self.target.stack_alignment()));
+                // Generated code doesn't preserve registers, so the array cursor
+                // and main's result live in [esp] and [esp + 4], and main's
+                // arguments from [esp + 16]
+                self.emit_stack_reserve(32);
+                self.emit_line("    mov DWORD PTR [esp + 16], eax");
+                self.emit_line("    mov DWORD PTR [esp + 20], ecx");
+                self.emit_line("    mov DWORD PTR [esp + 24], edx");
+
+                // Constructors, in order
+                self.emit_line("    mov DWORD PTR [esp], OFFSET __init_array_start");
+                self.emit_line(".Lstart_init:");
+                self.emit_line("    mov eax, DWORD PTR [esp]");
+                self.emit_line("    cmp eax, OFFSET __init_array_end");
+                self.emit_line("    je .Lstart_main");
+                self.emit_line("    add DWORD PTR [esp], 4");
+                self.emit_line("    call DWORD PTR [eax]");
+                self.emit_line("    jmp .Lstart_init");
+                self.emit_line(".Lstart_main:");
+                for offset in [0, 4, 8] {
+                    self.emit_line(&format!("    mov eax, DWORD PTR [esp + {}]", offset + 16));
+                    self.emit_line(&format!("    mov DWORD PTR [esp + {}], eax", offset));
+                }
+                self.emit_line("    call main");
+                if main_returns_void {
+                    self.emit_line("    xor eax, eax");
+                }
+                self.emit_line("    mov DWORD PTR [esp + 4], eax");
+
+                // Destructors, in reverse
+                self.emit_line("    mov DWORD PTR [esp], OFFSET __fini_array_end");
+                self.emit_line(".Lstart_fini:");
+                self.emit_line("    mov eax, DWORD PTR [esp]");
+                self.emit_line("    cmp eax, OFFSET __fini_array_start");
+                self.emit_line("    je .Lstart_exit");
+                self.emit_line("    sub eax, 4");
+                self.emit_line("    mov DWORD PTR [esp], eax");
+                self.emit_line("    call DWORD PTR [eax]");
+                self.emit_line("    jmp .Lstart_fini");
+                self.emit_line(".Lstart_exit:");
 
-        // Set up stack and call main
-        self.emit_line("    push rbp");
-        self.emit_line("    mov rbp, rsp");
+                // Exit syscall with main's return value
+                self.emit_line("    mov ebx, DWORD PTR [esp + 4]"); // exit status = main's return value
+                self.emit_line("    mov eax, 1"); // sys_exit syscall number
+                self.emit_line("    int 0x80"); // invoke syscall
+            }
+            Target::Amd64 => {
+                // argc is on top of the stack, then argv's pointers, a null and envp's
+                self.emit_line("    xor ebp, ebp");
+                self.emit_line("    mov rdi, QWORD PTR [rsp]");
+                self.emit_line("    lea rsi, [rsp + 8]");
+                // Outermost frame, stack aligned for the call
+                self.emit_line(&format!("    and rsp, -{}", self.target.stack_alignment()));
+                // Generated code doesn't preserve registers, so the array cursor
+                // and main's result live in [rsp] and [rsp + 8], argc and argv
+                // in [rsp + 16] and [rsp + 24]
+                self.emit_stack_reserve(32);
+                self.emit_line("    mov QWORD PTR [rsp + 16], rdi");
+                self.emit_line("    mov QWORD PTR [rsp + 24], rsi");
+
+                // Constructors, in order
+                self.emit_line("    lea rax, [rip + __init_array_start]");
+                self.emit_line("    mov QWORD PTR [rsp], rax");
+                self.emit_line(".Lstart_init:");
+                self.emit_line("    mov rax, QWORD PTR [rsp]");
+                self.emit_line("    lea rcx, [rip + __init_array_end]");
+                self.emit_line("    cmp rax, rcx");
+                self.emit_line("    je .Lstart_main");
+                self.emit_line("    add QWORD PTR [rsp], 8");
+                self.emit_line("    call QWORD PTR [rax]");
+                self.emit_line("    jmp .Lstart_init");
+                self.emit_line(".Lstart_main:");
+                self.emit_line("    mov rdi, QWORD PTR [rsp + 16]");
+                self.emit_line("    mov rsi, QWORD PTR [rsp + 24]");
+                self.emit_line("    lea rdx, [rsi + rdi*8 + 8]");
+                self.emit_line("    call main");
+                if main_returns_void {
+                    self.emit_line("    xor eax, eax");
+                }
+                self.emit_line("    mov QWORD PTR [rsp + 8], rax");
+
+                // Destructors, in reverse
+                self.emit_line("    lea rax, [rip + __fini_array_end]");
+                self.emit_line("    mov QWORD PTR [rsp], rax");
+                self.emit_line(".Lstart_fini:");
+                self.emit_line("    mov rax, QWORD PTR [rsp]");
+                self.emit_line("    lea rcx, [rip + __fini_array_start]");
+                self.emit_line("    cmp rax, rcx");
+                self.emit_line("    je .Lstart_exit");
+                self.emit_line("    sub rax, 8");
+                self.emit_line("    mov QWORD PTR [rsp], rax");
+                self.emit_line("    call QWORD PTR [rax]");
+                self.emit_line("    jmp .Lstart_fini");
+                self.emit_line(".Lstart_exit:");
+
+                // Exit syscall with main's return value
+                self.emit_line("    mov rdi, QWORD PTR [rsp + 8]"); // exit status = main's return value
+                self.emit_line("    mov rax, 60"); // sys_exit syscall number
+                self.emit_line("    syscall"); // invoke syscall
+            }
+            Target::Arm64 => {
+                // Outermost frame; sp is already 16-byte aligned on entry, with
+                // argc on top, then argv's pointers, a null and envp's
+                self.emit_line("    mov x29, #0");
+                self.emit_line("    mov x30, #0");
+                self.emit_line("    ldr x0, [sp]");
+                self.emit_line("    add x1, sp, #8");
+                // Generated code doesn't preserve registers, so the array cursor
+                // and main's result live in [sp] and [sp, #8], argc and argv
+                // in [sp, #16] and [sp, #24]
+                self.emit_stack_reserve(32);
+                self.emit_line("    stp x0, x1, [sp, #16]");
 
-        // Reserve space for temporary operations (ensures proper stack alignment)
-        // 120 bytes = 15*8, so after rbp push (8 bytes), total is 128 bytes = multiple of 16
-        self.emit_line("    sub rsp, 120");
+                // Constructors, in order
+                self.emit_line("    adrp x9, __init_array_start");
+                self.emit_line("    add x9, x9, :lo12:__init_array_start");
+                self.emit_line(".Lstart_init:");
+                self.emit_line("    adrp x10, __init_array_end");
+                self.emit_line("    add x10, x10, :lo12:__init_array_end");
+                self.emit_line("    cmp x9, x10");
+                self.emit_line("    b.eq .Lstart_main");
+                self.emit_line("    ldr x10, [x9], #8");
+                self.emit_line("    str x9, [sp]");
+                self.emit_line("    blr x10");
+                self.emit_line("    ldr x9, [sp]");
+                self.emit_line("    b .Lstart_init");
+                self.emit_line(".Lstart_main:");
+                self.emit_line("    ldp x0, x1, [sp, #16]");
+                self.emit_line("    add x2, x1, x0, lsl #3");
+                self.emit_line("    add x2, x2, #8");
+                self.emit_line("    bl main");
+                if main_returns_void {
+                    self.emit_line("    mov x0, #0");
+                }
+                self.emit_line("    str x0, [sp, #8]");
 
-        // Call main function
-        self.emit_line("    call main");
+                // Destructors, in reverse
+                self.emit_line("    adrp x9, __fini_array_end");
+                self.emit_line("    add x9, x9, :lo12:__fini_array_end");
+                self.emit_line(".Lstart_fini:");
+                self.emit_line("    adrp x10, __fini_array_start");
+                self.emit_line("    add x10, x10, :lo12:__fini_array_start");
+                self.emit_line("    cmp x9, x10");
+                self.emit_line("    b.eq .Lstart_exit");
+                self.emit_line("    ldr x10, [x9, #-8]!");
+                self.emit_line("    str x9, [sp]");
+                self.emit_line("    blr x10");
+                self.emit_line("    ldr x9, [sp]");
+                self.emit_line("    b .Lstart_fini");
+                self.emit_line(".Lstart_exit:");
 
-        // Exit syscall with main's return value
-        self.emit_line("    mov rdi, rax"); // exit status = main's return value
-        self.emit_line("    mov rax, 60"); // sys_exit syscall number
-        self.emit_line("    syscall"); // invoke syscall
+                // Exit syscall with main's return value
+                self.emit_line("    ldr x0, [sp, #8]");
+                self.emit_line("    mov x8, #93"); // sys_exit syscall number
+                self.emit_line("    svc #0"); // invoke syscall
+            }
+        }
+        self.emit_function_end("_start");
 
         Ok(())
     }
 
+    /// Switch to a named section until the next `.popsection`
+    fn push_section(&self, name: &str, flags: &str) -> String {
+        // `@` starts a comment in arm64 assembly
+        let kind = match self.target {
+            Target::Arm64 => "%progbits",
+            Target::I386 | Target::Amd64 => "@progbits",
+        };
+        // Including the `.init_array.NNNNN` sections of prioritized constructors
+        let kind = match name.split('.').nth(1) {
+            Some(array @ ("init_array" | "fini_array")) => kind.replace("progbits", array),
+            _ => kind.to_string(),
+        };
+        format!(".pushsection {},\"{}\",{}", name, flags, kind)
+    }
+
     fn emit_header(&mut self) {
         match self.target {
             Target::I386 => {
-                self.emit_line(".arch i386");
+                match self.as_dialect {
+                    AsDialect::Gnu if self.sse2 => {
+                        self.emit_line(".arch i686");
+                        self.emit_line(".arch .sse2");
+                    }
+                    AsDialect::Gnu => self.emit_line(".arch i386"),
+                    // LLVM only takes the mode from the triple or .code32
+                    AsDialect::Llvm => self.emit_line(".code32"),
+                }
                 self.emit_line(".intel_syntax noprefix");
             }
             Target::Amd64 => {
@@ -114,10 +900,13 @@ impl CodeGenerator {
 
     fn generate_function(&mut self, function: &Function) -> Result<()> {
         // Check if function has a body (implementation) or is just a declaration
-        match &function.body {
-            Statement::Block(statements) if statements.is_empty() => {
-                // This is a forward declaration, generate an external reference
-                self.emit_line(&format!(".extern {}", function.name));
+        match &function.body.kind {
+            StatementKind::Block(statements) if statements.is_empty() => {
+                // This is a forward declaration, generate an external reference.
+                // LLVM treats undefined symbols as external and ignores .extern.
+                if self.as_dialect == AsDialect::Gnu {
+                    self.emit_line(&format!(".extern {}", function.name));
+                }
                 return Ok(());
             }
             _ => {
@@ -125,190 +914,824 @@ impl CodeGenerator {
             }
         }
 
-        self.emit_line(&format!(".globl {}", function.name));
+        let function_start = self.output.len();
+        // Calls to a wrapped function defined here go to its wrapper instead
+        let wrapper = linked_symbol(
+            &function.name,
+            &self.wrapped_symbols,
+            &self.defined_functions,
+        );
+        if wrapper != function.name
+            && !self.defined_functions.contains(&wrapper)
+            && self.as_dialect == AsDialect::Gnu
+        {
+            self.emit_line(&format!(".extern {}", wrapper));
+        }
+        if let Some(alignment) = function.attributes.aligned {
+            self.emit_line(&format!(".balign {}", alignment));
+        }
+        // Static functions stay local to the object file
+        if function.is_inline_definition() {
+            self.emit_line(&format!(".weak {}", function.name));
+        } else if !function.is_static {
+            self.emit_line(&format!(".globl {}", function.name));
+        }
+        self.emit_line(&self.symbol_type(&function.name, "function"));
         self.emit_line(&format!("{}:", function.name));
+        // Unwind information, so debuggers and profilers can walk through the frame
+        self.emit_line("    .cfi_startproc");
+
+        // The IR plans its own frames, which have no slot for a canary
+        let protected = needs_stack_protector(function, self.stack_protector, &self.layout);
+        if !protected && self.generate_function_from_ir(function, function_start) {
+            return Ok(());
+        }
+
+        self.floating_return = self.floating(&function.return_type);
+        self.return_type = Some(function.return_type.clone());
+        self.uses_floating_point = false;
+
+        self.current_function = function.name.clone();
 
         // Set up parameter tracking
         self.current_function_params.clear();
         self.local_variables.clear();
-        // Start local variables after parameters to avoid collision
-        self.stack_offset = -(function.parameters.len() as i32 * 8);
-        self.epilogue_emitted = false;
+        self.local_types.clear();
+        self.slots.clear();
+        self.annotated_line = 0;
+        self.stack_depth = 0;
+        self.stack_moves.clear();
+        self.is_variadic = function.is_variadic;
+        self.frame_bottom_reserve = 0;
+        // A struct or union returned in memory goes where the caller's hidden
+        // pointer says, which is kept in the first slot; i386 reads it from
+        // the first argument's
+        self.aggregate_return = self.aggregate(&function.return_type);
+        let hidden_pointer = matches!(self.aggregate_return, Some((_, AggregatePassing::Memory)));
+        self.return_buffer = hidden_pointer.then_some(match self.target {
+            Target::I386 => 8,
+            _ => -8,
+        });
+        let classes: Vec<ArgumentClass> = function
+            .parameters
+            .iter()
+            .map(|(_, ty)| self.argument_class(Some(ty), self.floating(ty).as_ref()))
+            .collect();
+        self.parameter_layout = self.argument_locations(&classes, hidden_pointer);
+
+        // Lay out all locals up front, below the parameter slots
+        let parameter_bytes = match self.target {
+            // Structs and unions are used where the caller put them
+            Target::I386 => function
+                .parameters
+                .iter()
+                .filter(|(_, ty)| self.aggregate(ty).is_none())
+                .map(|(_, ty)| self.i386_argument_size(ty))
+                .sum(),
+            _ => {
+                let slots: usize = function
+                    .parameters
+                    .iter()
+                    .zip(&self.parameter_layout.locations)
+                    .map(|((_, ty), location)| self.parameter_slot_bytes(ty, location))
+                    .sum();
+                slots + usize::from(hidden_pointer) * 8
+            }
+        };
+        // A canary goes right below the saved frame pointer, with the locals
+        // under it and the parameters at the bottom, as GCC lays them out:
+        // an array overflowing upwards reaches the canary before anything else
+        let word = self.target.pointer_size();
+        let frame = if protected {
+            FrameLayout::plan(self.target, word, &function.body)?
+        } else {
+            FrameLayout::plan(self.target, parameter_bytes, &function.body)?
+        };
+        self.canary = protected.then_some(-(word as i32));
+        self.parameter_base = if protected { frame.size() } else { 0 };
+        if self.target != Target::I386 {
+            if let Some(buffer) = self.return_buffer.as_mut() {
+                *buffer -= self.parameter_base as i32;
+            }
+        }
+        let mut locals_size = frame.size().max(self.parameter_base + parameter_bytes);
+        // alloca takes its blocks from below the temporaries a statement has
+        // pushed, which it moves down; where they end is kept below the locals
+        self.alloca_bottom = allocates_on_stack(function).then(|| {
+            locals_size = locals_size.next_multiple_of(word) + word;
+            -(locals_size as i32)
+        });
+        self.frame = Some(frame);
+        self.declarations_seen = 0;
+        self.jump_contexts.clear();
+
+        // Array parameters are pointers
+        for (name, param_type) in &function.parameters {
+            let param_type = match self.layout.resolve(param_type) {
+                Type::Array(element, _) => Type::Pointer(element),
+                _ => param_type.clone(),
+            };
+            self.local_types.insert(name.clone(), param_type);
+        }
 
         // Function prologue
         self.emit_function_prologue(&function.parameters)?;
+        if function.is_variadic {
+            self.emit_register_save_area()?;
+        }
+        if let Some(canary) = self.canary {
+            self.emit_canary_store(canary);
+        }
+        if let Some(bottom) = self.alloca_bottom {
+            match self.target {
+                Target::I386 => {
+                    self.emit_line(&format!("    mov DWORD PTR [ebp + {}], esp", bottom))
+                }
+                Target::Amd64 => self.emit_store_word("rsp", "rbp", bottom),
+                Target::Arm64 => {
+                    self.emit_line("    mov x9, sp");
+                    self.emit_store_word("x9", "x29", bottom);
+                }
+            }
+        }
+        let body_start = self.output.len();
+        if self.verbose_asm {
+            self.emit_line(SLOT_MAP_PLACEHOLDER);
+        }
+        for (name, offset) in self.current_function_params.clone() {
+            let size = self
+                .local_types
+                .get(&name)
+                .map_or(0, |ty| self.layout.size_of(ty));
+            self.slots.push((name, offset, size));
+        }
 
         // Function body
         self.generate_statement(&function.body)?;
+        if self.target == Target::I386 && self.uses_floating_point && !self.sse2 {
+            return Err(AleccError::CodegenError {
+                message: format!(
+                    "'{}' uses floating point, which i386 code only gets with --msse2",
+                    function.name
+                ),
+                span: None,
+            });
+        }
+
+        // Returns emit their own epilogue, and nothing comes back from a
+        // `noreturn` call, so only a body control can fall out of needs one
+        if completes(&function.body, &self.noreturn_functions) {
+            self.emit_missing_return(function)?;
+        }
+
+        // Now that every local has a slot, reserve the whole frame (16-byte aligned)
+        let frame_size = (locals_size + self.frame_bottom_reserve as usize).max(16);
+        let frame_size = frame_size.div_ceil(16) * 16;
+
+        // A leaf function whose frame fits in the red zone never needs to move rsp
+        let in_red_zone = self.red_zone
+            && !function.is_variadic
+            && frame_size <= self.target.red_zone_size()
+            && is_red_zone_leaf(&self.output[body_start..]);
+        if in_red_zone {
+            let allocation = format!("    sub rsp, {}\n", FRAME_SIZE_PLACEHOLDER);
+            if let Some(at) = self.output[function_start..].find(&allocation) {
+                let at = function_start + at;
+                self.output.replace_range(at..at + allocation.len(), "");
+                for (end, _) in &mut self.stack_moves {
+                    if *end > at {
+                        *end -= allocation.len();
+                    }
+                }
+            }
+        }
+        // Frame plus the saved frame pointer and return address; without the
+        // frame pointer, its word still pads the frame, except in the red zone
+        let mut saved_words = 2;
+        // Where the frame pointer would be, above the stack pointer, once it is omitted
+        let mut frame_base = None;
+        if self.omit_frame_pointer
+            && (!in_red_zone || frame_size + word <= self.target.red_zone_size())
+        {
+            let moves: Vec<_> = self
+                .stack_moves
+                .iter()
+                .map(|&(end, bytes)| (end - function_start, bytes))
+                .collect();
+            let omitted = without_frame_pointer(
+                &self.output[function_start..],
+                &moves,
+                self.target,
+                frame_size,
+            );
+            if let Some(text) = omitted {
+                self.output.truncate(function_start);
+                self.output.push_str(&text);
+                if in_red_zone {
+                    saved_words = 1;
+                }
+                frame_base = Some(if in_red_zone {
+                    -(word as i32)
+                } else {
+                    frame_size as i32
+                });
+            }
+        }
+        if self.verbose_asm {
+            let slots = self.slot_map(frame_base);
+            self.output = self
+                .output
+                .replace(&format!("{}\n", SLOT_MAP_PLACEHOLDER), &slots);
+        }
+        self.stack_usage
+            .push((function.name.clone(), frame_size + saved_words * word));
+
+        self.output = self
+            .output
+            .replace(FRAME_SIZE_PLACEHOLDER, &frame_size.to_string());
+        self.emit_function_end(&function.name);
 
-        // Function epilogue (always ensure we have a proper function ending)
-        // This handles cases where there might not be explicit returns in all paths
-        self.emit_function_epilogue()?;
+        // Instructions are indented; labels and directives are not
+        let instructions = self.output[function_start..]
+            .lines()
+            .filter(|line| line.starts_with("    ") && !line.trim().starts_with('.'))
+            .count();
+        self.instruction_counts
+            .push((function.name.clone(), instructions));
 
         self.emit_line("");
         Ok(())
     }
 
+    /// Generate the body of `function` through the IR when `--backend=ir`
+    /// asks for it and the IR can express it. Returns whether it did.
+    fn generate_function_from_ir(&mut self, function: &Function, function_start: usize) -> bool {
+        let Some(lowering) = self.lowering.as_mut() else {
+            return false;
+        };
+        let Ok(lowered) = lowering.lower_function(function) else {
+            return false;
+        };
+        let assembled = IrAssembler::new(self.target, &self.label_prefix, &self.defined_functions)
+            .with_binding(&self.binding)
+            .with_wrapped_symbols(&self.wrapped_symbols)
+            .with_frame_pointer_omission(self.omit_frame_pointer)
+            .assemble(&lowered);
+        let Ok(assembled) = assembled else {
+            return false;
+        };
+        self.output.push_str(&assembled.text);
+
+        // Literals the first pass didn't reach still need their bytes
+        let mut new_strings: Vec<(String, String)> = lowering
+            .strings()
+            .iter()
+            .filter(|(content, _)| !self.string_literals.contains_key(*content))
+            .map(|(content, label)| (content.clone(), label.clone()))
+            .collect();
+        if !new_strings.is_empty() {
+            new_strings.sort_by(|a, b| a.1.cmp(&b.1));
+            self.emit_line(".pushsection .rodata");
+            for (content, label) in new_strings {
+                self.emit_line(&format!("{}:", label));
+                self.emit_line(&format!("    .asciz \"{}\"", self.escape_string(&content)));
+                self.string_literals.insert(content, label);
+            }
+            self.emit_line(".popsection");
+        }
+        self.emit_function_end(&function.name);
+
+        // Plus the return address
+        self.stack_usage.push((
+            function.name.clone(),
+            assembled.frame_size + self.target.pointer_size(),
+        ));
+        let instructions = self.output[function_start..]
+            .lines()
+            .filter(|line| line.starts_with("    ") && !line.trim().starts_with('.'))
+            .count();
+        self.instruction_counts
+            .push((function.name.clone(), instructions));
+        self.emit_line("");
+        true
+    }
+
+    /// A comment, for `-fverbose-asm`, quoting source line `line` the way
+    /// GCC does (`# file.c:3:   x = 1;`). A statement on the line just
+    /// quoted doesn't repeat it
+    fn emit_source_line(&mut self, line: usize) {
+        if line == self.annotated_line {
+            return;
+        }
+        self.annotated_line = line;
+        let Some((file, lines)) = &self.source_listing else {
+            return;
+        };
+        let Some(text) = line.checked_sub(1).and_then(|index| lines.get(index)) else {
+            return;
+        };
+        let comment = format!("{} {}:{}: {}", self.comment_marker(), file, line, text);
+        self.emit_line(comment.trim_end());
+    }
+
+    /// The comments listing the current function's stack slots, for
+    /// `-fverbose-asm`: each parameter and local, and the canary, with the
+    /// address the code uses for it. Without a frame pointer, that is from
+    /// the stack pointer, `frame_base` below where the frame pointer would be
+    fn slot_map(&self, frame_base: Option<i32>) -> String {
+        let marker = self.comment_marker();
+        let mut slots: Vec<String> = self
+            .slots
+            .iter()
+            .map(|(name, offset, size)| {
+                format!(
+                    "{}   {}: {}, {} bytes",
+                    marker,
+                    name,
+                    self.slot_address(*offset, frame_base),
+                    size
+                )
+            })
+            .collect();
+        if let Some(canary) = self.canary {
+            slots.push(format!(
+                "{}   canary: {}",
+                marker,
+                self.slot_address(canary, frame_base)
+            ));
+        }
+        if slots.is_empty() {
+            return String::new();
+        }
+        let mut map = format!("{} stack slots:\n", marker);
+        for slot in slots {
+            map.push_str(&slot);
+            map.push('\n');
+        }
+        map
+    }
+
+    /// `offset` from the frame pointer, as an operand in the output's syntax;
+    /// from the stack pointer when the frame pointer is `frame_base` above it
+    fn slot_address(&self, offset: i32, frame_base: Option<i32>) -> String {
+        let (base, offset) = match (self.target, frame_base) {
+            (Target::Arm64, _) => return format!("[x29, #{}]", offset),
+            (Target::I386, None) => ("ebp", offset),
+            (Target::I386, Some(frame_base)) => ("esp", offset + frame_base),
+            (Target::Amd64, None) => ("rbp", offset),
+            (Target::Amd64, Some(frame_base)) => ("rsp", offset + frame_base),
+        };
+        let sign = if offset < 0 { '-' } else { '+' };
+        match self.syntax {
+            AsmSyntax::Att => format!("{}(%{})", offset, base),
+            AsmSyntax::Intel => format!("[{} {} {}]", base, sign, offset.unsigned_abs()),
+        }
+    }
+
+    /// What starts a comment in the target's assembly
+    fn comment_marker(&self) -> &'static str {
+        match self.target {
+            Target::Arm64 => "//",
+            Target::I386 | Target::Amd64 => "#",
+        }
+    }
+
+    /// Close a function's unwind information and give its symbol the size
+    /// of the code from its label on
+    fn emit_function_end(&mut self, name: &str) {
+        self.emit_line("    .cfi_endproc");
+        self.emit_line(&format!(".size {0}, .-{0}", name));
+    }
+
+    /// `.type` for symbol `name`: a `function`, an `object` or a `tls_object`
+    fn symbol_type(&self, name: &str, kind: &str) -> String {
+        // `@` starts a comment in arm64 assembly
+        let prefix = match self.target {
+            Target::Arm64 => '%',
+            Target::I386 | Target::Amd64 => '@',
+        };
+        format!(".type {}, {}{}", name, prefix, kind)
+    }
+
     fn emit_function_prologue(&mut self, parameters: &[(String, Type)]) -> Result<()> {
+        for line in frame_entry(self.target) {
+            self.emit_line(&format!("    {}", line));
+        }
         match self.target {
             Target::I386 => {
-                self.emit_line("    push ebp");
-                self.emit_line("    mov ebp, esp");
+                // Reserve space for parameters and locals
+                self.emit_line(&format!("    sub esp, {}", FRAME_SIZE_PLACEHOLDER));
 
-                // Reserve space for parameters only (no extra temporaries for now)
-                let stack_space = parameters.len() * 4;
-                if stack_space > 0 {
-                    self.emit_line(&format!("    sub esp, {}", stack_space));
-                }
-
-                // Store parameters from stack (i386 calling convention)
-                for (i, (name, _)) in parameters.iter().enumerate() {
-                    let param_offset = -(i as i32 + 1) * 4;
-                    let stack_offset = 8 + i as i32 * 4; // ebp + 8 + offset
-                    self.emit_line(&format!("    mov eax, DWORD PTR [ebp + {}]", stack_offset));
-                    self.emit_line(&format!("    mov DWORD PTR [ebp + {}], eax", param_offset));
-                    self.current_function_params
-                        .push((name.clone(), param_offset));
-                }
-            }
-            Target::Amd64 => {
-                self.emit_line("    push rbp");
-                self.emit_line("    mov rbp, rsp");
-
-                // Reserve space for parameters + ensure 16-byte alignment
-                let stack_space = parameters.len() * 8;
-                // Always reserve at least 8 bytes to maintain 16-byte alignment after rbp push
-                let min_space = if stack_space == 0 { 8 } else { stack_space };
-                let aligned_space = min_space.div_ceil(16) * 16; // Round up to 16-byte boundary
-                self.emit_line(&format!("    sub rsp, {}", aligned_space));
-
-                // Store parameters from registers (x86_64 calling convention)
-                let param_registers = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
-                for (i, (name, _)) in parameters.iter().enumerate() {
-                    let param_offset = -(i as i32 + 1) * 8;
-                    if i < param_registers.len() {
-                        // Parameter passed in register
+                // Store parameters from stack (i386 calling convention); the
+                // copies are laid out like the caller's, a double in two words.
+                // Structs and unions are used where the caller put them, after
+                // the hidden pointer to a struct or union result
+                let mut bytes = 4 * i32::from(self.return_buffer.is_some());
+                let mut copied = 0;
+                for (name, ty) in parameters {
+                    let size = self.i386_argument_size(ty) as i32;
+                    let stack_offset = 8 + bytes; // ebp + 8 + offset
+                    bytes += size;
+                    if self.aggregate(ty).is_some() {
+                        self.current_function_params
+                            .push((name.clone(), stack_offset));
+                        continue;
+                    }
+                    let param_offset = -(self.parameter_base as i32 + copied + size);
+                    for word in (0..size).step_by(4) {
                         self.emit_line(&format!(
-                            "    mov QWORD PTR [rbp + {}], {}",
-                            param_offset, param_registers[i]
+                            "    mov eax, DWORD PTR [ebp + {}]",
+                            stack_offset + word
+                        ));
+                        self.emit_line(&format!(
+                            "    mov DWORD PTR [ebp + {}], eax",
+                            param_offset + word
                         ));
-                    } else {
-                        // Parameter passed on stack
-                        let stack_offset = 16 + (i - param_registers.len()) as i32 * 8;
-                        self.emit_line(&format!("    mov rax, QWORD PTR [rbp + {}]", stack_offset));
-                        self.emit_line(&format!("    mov QWORD PTR [rbp + {}], rax", param_offset));
                     }
+                    copied += size;
                     self.current_function_params
                         .push((name.clone(), param_offset));
                 }
             }
+            Target::Amd64 => {
+                // Reserve space for parameters and locals (16-byte aligned)
+                self.emit_line(&format!("    sub rsp, {}", FRAME_SIZE_PLACEHOLDER));
+                self.emit_parameter_stores(parameters)?;
+            }
             Target::Arm64 => {
-                self.emit_line("    stp x29, x30, [sp, #-16]!");
-                self.emit_line("    mov x29, sp");
+                // Reserve space for parameters and locals (16-byte aligned)
+                self.emit_line(&format!("    sub sp, sp, #{}", FRAME_SIZE_PLACEHOLDER));
+                self.emit_parameter_stores(parameters)?;
+            }
+        }
+        Ok(())
+    }
 
-                let stack_space = parameters.len() * 8;
-                if stack_space > 0 {
-                    let aligned_space = (stack_space + 15) & !15; // 16-byte aligned
-                    self.emit_line(&format!("    sub sp, sp, #{}", aligned_space));
+    /// Store the parameters arriving in registers (x86_64 and ARM64 calling
+    /// conventions) in their slots below the frame pointer, a word each, or as
+    /// many words as a struct or union takes. Those on the stack are copied
+    /// too, except structs and unions, which are used where the caller put them.
+    fn emit_parameter_stores(&mut self, parameters: &[(String, Type)]) -> Result<()> {
+        let (frame, word_registers, hidden) = match self.target {
+            Target::Amd64 => ("rbp", AMD64_ARGUMENT_REGISTERS, "rdi"),
+            _ => ("x29", ARM64_ARGUMENT_REGISTERS, "x8"),
+        };
+        let mut bytes = self.parameter_base;
+        if let Some(offset) = self.return_buffer {
+            bytes = -offset as usize;
+            self.emit_store_word(hidden, frame, offset);
+        }
+        for (i, (name, ty)) in parameters.iter().enumerate() {
+            let location = self.parameter_layout.locations[i].clone();
+            let aggregate = self.aggregate(ty);
+            bytes += self.parameter_slot_bytes(ty, &location);
+            let mut param_offset = -(bytes as i32);
+            match location {
+                ArgumentLocation::Integer(register) => {
+                    self.emit_store_word(word_registers[register], frame, param_offset);
                 }
-
-                // Store parameters from registers (ARM64 calling convention)
-                for (i, (name, _)) in parameters.iter().enumerate() {
-                    let param_offset = -(i as i32 + 1) * 8;
-                    if i < 8 {
-                        // Parameter passed in register x0-x7
-                        self.emit_line(&format!("    str x{}, [x29, #{}]", i, param_offset));
-                    } else {
-                        // Parameter passed on stack
-                        let stack_offset = 16 + (i - 8) as i32 * 8;
-                        self.emit_line(&format!("    ldr x9, [x29, #{}]", stack_offset));
-                        self.emit_line(&format!("    str x9, [x29, #{}]", param_offset));
+                ArgumentLocation::Floating(register) => {
+                    let ty = self.layout.resolve(ty);
+                    self.emit_store_floating(&ty, register, frame, param_offset);
+                }
+                ArgumentLocation::Stack(slot) if aggregate.is_some() => {
+                    param_offset = 16 + slot as i32 * 8;
+                }
+                ArgumentLocation::Stack(slot) => {
+                    let stack_offset = 16 + slot as i32 * 8;
+                    let scratch = self.emit_load_word(frame, stack_offset);
+                    self.emit_store_word(scratch, frame, param_offset);
+                }
+                ArgumentLocation::Split(pieces) => {
+                    for (piece, register) in pieces {
+                        let offset = param_offset + piece.offset as i32;
+                        if piece.floating {
+                            let ty = piece_type(&piece);
+                            self.emit_store_floating(&ty, register, frame, offset);
+                        } else {
+                            self.emit_store_word(word_registers[register], frame, offset);
+                        }
                     }
-                    self.current_function_params
-                        .push((name.clone(), param_offset));
+                }
+                ArgumentLocation::Indirect(pointer) => {
+                    // arm64 passes the address of the caller's copy; this one is the callee's
+                    match *pointer {
+                        ArgumentLocation::Stack(slot) => {
+                            self.emit_line(&format!("    ldr x10, [x29, #{}]", 16 + slot * 8));
+                        }
+                        ArgumentLocation::Integer(register) => {
+                            self.emit_line(&format!("    mov x10, x{}", register));
+                        }
+                        _ => unreachable!("an address is passed like an integer"),
+                    }
+                    self.emit_line(&format!("    sub x11, x29, #{}", -param_offset));
+                    let (size, _) = aggregate.expect("only aggregates are passed indirectly");
+                    self.emit_copy(size);
                 }
             }
+            self.current_function_params
+                .push((name.clone(), param_offset));
         }
         Ok(())
     }
 
-    fn emit_function_epilogue(&mut self) -> Result<()> {
-        if self.epilogue_emitted {
-            return Ok(()); // Don't emit duplicate epilogues
+    /// Bytes of the slot a parameter of type `ty` arriving at `location` gets
+    fn parameter_slot_bytes(&self, ty: &Type, location: &ArgumentLocation) -> usize {
+        match (self.aggregate(ty), location) {
+            (Some(_), ArgumentLocation::Stack(_)) => 0,
+            (Some((size, _)), _) => size.next_multiple_of(8).max(8),
+            (None, _) => 8,
         }
+    }
 
+    /// Store 64-bit register `register` at `offset` from `base`
+    fn emit_store_word(&mut self, register: &str, base: &str, offset: i32) {
         match self.target {
-            Target::I386 => {
-                self.emit_line("    mov esp, ebp");
-                self.emit_line("    pop ebp");
-                self.emit_line("    ret");
-            }
-            Target::Amd64 => {
-                self.emit_line("    mov rsp, rbp");
-                self.emit_line("    pop rbp");
-                self.emit_line("    ret");
+            Target::Arm64 => {
+                self.emit_line(&format!("    str {}, [{}, #{}]", register, base, offset))
             }
+            _ => self.emit_line(&format!(
+                "    mov QWORD PTR [{} + {}], {}",
+                base, offset, register
+            )),
+        }
+    }
+
+    /// Load the word at `offset` from `base` into a scratch register, which is returned
+    fn emit_load_word(&mut self, base: &str, offset: i32) -> &'static str {
+        match self.target {
             Target::Arm64 => {
-                self.emit_line("    mov sp, x29");
-                self.emit_line("    ldp x29, x30, [sp], #16");
-                self.emit_line("    ret");
+                self.emit_line(&format!("    ldr x9, [{}, #{}]", base, offset));
+                "x9"
+            }
+            _ => {
+                self.emit_line(&format!("    mov rax, QWORD PTR [{} + {}]", base, offset));
+                "rax"
             }
         }
+    }
 
-        self.epilogue_emitted = true;
-        Ok(())
+    /// Store the `ty` in floating-point register `register` at `offset` from `base`
+    fn emit_store_floating(&mut self, ty: &Type, register: usize, base: &str, offset: i32) {
+        match self.target {
+            Target::Arm64 => self.emit_line(&format!(
+                "    str {}{}, [{}, #{}]",
+                fp_prefix(ty),
+                register,
+                base,
+                offset
+            )),
+            _ => {
+                let (_, mov, width) = floating_operand(ty);
+                self.emit_line(&format!(
+                    "    {} {} PTR [{} + {}], xmm{}",
+                    mov, width, base, offset, register
+                ));
+            }
+        }
     }
 
-    fn emit_function_epilogue_force(&mut self) -> Result<()> {
-        // Force emit epilogue regardless of epilogue_emitted flag
+    /// Spill the argument registers of a variadic function so `va_arg` can walk
+    /// them, laid out as the SysV (amd64) and AAPCS64 (arm64) ABIs describe. The
+    /// save area sits at the bottom of the frame, right where the prologue left
+    /// the stack pointer.
+    fn emit_register_save_area(&mut self) -> Result<()> {
         match self.target {
             Target::I386 => {
-                self.emit_line("    mov esp, ebp");
-                self.emit_line("    pop ebp");
-                self.emit_line("    ret");
+                // All arguments are already on the stack
             }
             Target::Amd64 => {
-                self.emit_line("    mov rsp, rbp");
-                self.emit_line("    pop rbp");
-                self.emit_line("    ret");
+                self.frame_bottom_reserve = AMD64_REG_SAVE_AREA_SIZE;
+
+                for (i, reg) in ["rdi", "rsi", "rdx", "rcx", "r8", "r9"].iter().enumerate() {
+                    self.emit_line(&format!("    mov QWORD PTR [rsp + {}], {}", i * 8, reg));
+                }
+
+                // al holds the number of vector registers used by the caller. The
+                // expression stack can leave rsp misaligned at call sites, so avoid movaps.
+                let skip_label = self.new_label("va_skip_sse");
+                self.emit_line("    test al, al");
+                self.emit_line(&format!("    je {}", skip_label));
+                for i in 0..8 {
+                    self.emit_line(&format!(
+                        "    movups XMMWORD PTR [rsp + {}], xmm{}",
+                        48 + i * 16,
+                        i
+                    ));
+                }
+                self.emit_line(&format!("{}:", skip_label));
             }
             Target::Arm64 => {
-                self.emit_line("    mov sp, x29");
-                self.emit_line("    ldp x29, x30, [sp], #16");
-                self.emit_line("    ret");
+                // q0-q7 at [sp, sp + 128), x0-x7 at [sp + 128, sp + 192)
+                self.frame_bottom_reserve = ARM64_REG_SAVE_AREA_SIZE;
+
+                for i in 0..8 {
+                    self.emit_line(&format!("    str q{}, [sp, #{}]", i, i * 16));
+                }
+                for i in 0..8 {
+                    self.emit_line(&format!("    str x{}, [sp, #{}]", i, 128 + i * 8));
+                }
             }
         }
-
-        self.epilogue_emitted = true;
         Ok(())
     }
 
-    fn generate_statement(&mut self, statement: &Statement) -> Result<()> {
-        match statement {
-            Statement::Expression(expr) => {
-                self.generate_expression(expr)?;
+    /// The end of a function body that control can reach. `main` returns 0
+    /// as C99 requires; other non-void functions do what `--fmissing-return` says.
+    fn emit_missing_return(&mut self, function: &Function) -> Result<()> {
+        let returns_value = !matches!(self.layout.resolve(&function.return_type), Type::Void);
+        let mode = match function.name.as_str() {
+            "main" => MissingReturn::Zero,
+            _ if returns_value => self.missing_return,
+            _ => MissingReturn::Unspecified,
+        };
+        // The caller of an i386 float function pops st(0) whatever happens
+        if self.target == Target::I386 && self.floating_return.is_some() {
+            match mode {
+                MissingReturn::Trap => {}
+                _ => self.emit_line("    fldz"),
             }
-            Statement::Declaration {
-                name,
-                var_type,
-                initializer,
-            } => {
-                // Calculate space needed based on type
-                let size = match var_type {
-                    Type::Array(_, Some(length)) => length * 8, // Assuming 8-byte elements
-                    Type::Array(_, None) => 80,                 // Default size for unsized arrays
-                    _ => 8,                                     // Default 8 bytes for simple types
-                };
+        }
+        match (mode, self.target) {
+            (MissingReturn::Unspecified, _) => {}
+            (MissingReturn::Zero, Target::I386 | Target::Amd64) => {
+                self.emit_line("    xor eax, eax");
+            }
+            (MissingReturn::Zero, Target::Arm64) => self.emit_line("    mov x0, #0"),
+            (MissingReturn::Trap, Target::I386 | Target::Amd64) => {
+                self.emit_line("    ud2");
+                return Ok(());
+            }
+            (MissingReturn::Trap, Target::Arm64) => {
+                self.emit_line("    brk #0x3e8");
+                return Ok(());
+            }
+        }
+        self.emit_function_epilogue()
+    }
+
+    fn emit_function_epilogue(&mut self) -> Result<()> {
+        if let Some(canary) = self.canary {
+            self.emit_canary_check(canary);
+        }
+        for line in frame_exit(self.target) {
+            // An i386 callee pops the hidden pointer to its struct or union result
+            match *line {
+                "ret" if self.target == Target::I386 && self.return_buffer.is_some() => {
+                    self.emit_line("    ret 4")
+                }
+                line => self.emit_line(&format!("    {}", line)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the stack guard's value into the canary slot. The scratch
+    /// register is cleared after, so the value doesn't linger where the
+    /// function body could leak it
+    fn emit_canary_store(&mut self, canary: i32) {
+        let guard = self.stack_guard_operand();
+        match self.target {
+            Target::I386 => {
+                self.emit_line(&format!("    mov ecx, {}", guard));
+                self.emit_line(&format!("    mov DWORD PTR [ebp + {}], ecx", canary));
+                self.emit_line("    xor ecx, ecx");
+            }
+            Target::Amd64 => {
+                self.emit_line(&format!("    mov rcx, {}", guard));
+                self.emit_line(&format!("    mov QWORD PTR [rbp + {}], rcx", canary));
+                self.emit_line("    xor ecx, ecx");
+            }
+            Target::Arm64 => {
+                self.emit_line(&format!("    ldr x16, {}", guard));
+                self.emit_line(&format!("    str x16, [x29, #{}]", canary));
+                self.emit_line("    mov x16, #0");
+            }
+        }
+    }
+
+    /// Compare the canary slot with the stack guard before returning, and
+    /// stop the program through `__stack_chk_fail` if something overwrote it.
+    /// Only scratch registers no result comes back in are used
+    fn emit_canary_check(&mut self, canary: i32) {
+        let intact = self.new_label("canary_intact");
+        let guard = self.stack_guard_operand();
+        match self.target {
+            Target::I386 => {
+                self.emit_line(&format!("    mov ecx, DWORD PTR [ebp + {}]", canary));
+                self.emit_line(&format!("    sub ecx, {}", guard));
+                self.emit_line(&format!("    je {}", intact));
+                self.emit_line("    call __stack_chk_fail");
+            }
+            Target::Amd64 => {
+                self.emit_line(&format!("    mov rcx, QWORD PTR [rbp + {}]", canary));
+                self.emit_line(&format!("    sub rcx, {}", guard));
+                self.emit_line(&format!("    je {}", intact));
+                if self.binding.through_got("__stack_chk_fail") {
+                    self.emit_line("    call __stack_chk_fail@PLT");
+                } else {
+                    self.emit_line("    call __stack_chk_fail");
+                }
+            }
+            Target::Arm64 => {
+                self.emit_line(&format!("    ldr x16, [x29, #{}]", canary));
+                self.emit_line(&format!("    ldr x17, {}", guard));
+                self.emit_line("    cmp x16, x17");
+                self.emit_line(&format!("    b.eq {}", intact));
+                self.emit_line("    bl __stack_chk_fail");
+            }
+        }
+        self.emit_line(&format!("{}:", intact));
+    }
+
+    /// The memory operand holding the stack guard's value, after loading its
+    /// address into r11 or x17 where that takes an instruction
+    fn stack_guard_operand(&mut self) -> String {
+        const GUARD: &str = "__stack_chk_guard";
+        match self.target {
+            // Where glibc keeps the guard in the thread control block
+            Target::I386 if !self.stack_guard_symbol => "DWORD PTR gs:20".to_string(),
+            Target::Amd64 if !self.stack_guard_symbol => "QWORD PTR fs:40".to_string(),
+            Target::I386 => format!("DWORD PTR [{}]", GUARD),
+            Target::Amd64 if !self.binding.through_got(GUARD) => {
+                format!("QWORD PTR [rip + {}]", GUARD)
+            }
+            Target::Amd64 => {
+                self.emit_symbol_address(GUARD, GUARD, "ecx", "r11", "x17");
+                "QWORD PTR [r11]".to_string()
+            }
+            Target::Arm64 => {
+                self.emit_symbol_address(GUARD, GUARD, "ecx", "r11", "x17");
+                "[x17]".to_string()
+            }
+        }
+    }
+
+    /// Errors are attributed to the innermost statement or expression with a location
+    fn generate_statement(&mut self, statement: &Statement) -> Result<()> {
+        if self.verbose_asm && !matches!(statement.kind, StatementKind::Block(_)) {
+            self.emit_source_line(statement.span.line);
+        }
+        self.generate_statement_unlocated(statement)
+            .map_err(|error| error.at(statement.span))
+    }
 
-                // Allocate space for variable/array
-                self.stack_offset -= size as i32;
-                let var_offset = self.stack_offset;
+    fn generate_statement_unlocated(&mut self, statement: &Statement) -> Result<()> {
+        match &statement.kind {
+            StatementKind::Expression(expr) => {
+                self.generate_expression(expr)?;
+            }
+            StatementKind::Declaration {
+                name,
+                var_type,
+                initializer,
+                ..
+            } => {
+                // The slot was assigned by the frame layout, in declaration order
+                let var_offset = self
+                    .frame
+                    .as_ref()
+                    .and_then(|frame| frame.offset(self.declarations_seen))
+                    .ok_or_else(|| AleccError::CodegenError {
+                        message: format!("no stack slot planned for local variable '{}'", name),
+                        span: None,
+                    })?;
+                self.declarations_seen += 1;
 
                 // Store variable name and offset for later reference
                 self.local_variables.insert(name.clone(), var_offset);
+                self.slots
+                    .push((name.clone(), var_offset, self.layout.size_of(var_type)));
+                self.local_types.insert(name.clone(), var_type.clone());
 
-                if let Some(init_expr) = initializer {
-                    self.generate_expression(init_expr)?;
+                if let (Some(init_expr), Some((size, _))) = (initializer, self.aggregate(var_type))
+                {
+                    let spilled = self.emit_aggregate_address(init_expr)?;
+                    match self.target {
+                        Target::Arm64 => {
+                            self.emit_line("    mov x10, x0");
+                            self.emit_line(&format!("    add x11, x29, #{}", var_offset));
+                        }
+                        Target::I386 => {
+                            self.emit_line("    mov ecx, eax");
+                            self.emit_line(&format!("    lea edx, [ebp + {}]", var_offset));
+                        }
+                        Target::Amd64 => {
+                            self.emit_line("    mov r11, rax");
+                            self.emit_line(&format!("    lea r10, [rbp + {}]", var_offset));
+                        }
+                    }
+                    self.emit_copy(size);
+                    self.emit_release(spilled);
+                } else if let Some(init_expr) = initializer {
+                    let floating = self.floating(var_type);
+                    match &floating {
+                        Some(ty) => {
+                            self.generate_converted(init_expr, floating.as_ref())?;
+                            self.emit_floating_bits(ty)?
+                        }
+                        None => self.generate_integer(init_expr, Some(var_type))?,
+                    }
                     // Store the value in the local variable slot
                     match self.target {
                         Target::Amd64 => {
@@ -322,6 +1745,14 @@ impl CodeGenerator {
                                 "    mov DWORD PTR [ebp + {}], eax",
                                 var_offset
                             ));
+                            if matches!(floating, Some(Type::Double))
+                                || self.wide_integer(var_type).is_some()
+                            {
+                                self.emit_line(&format!(
+                                    "    mov DWORD PTR [ebp + {}], edx",
+                                    var_offset + 4
+                                ));
+                            }
                         }
                         Target::Arm64 => {
                             self.emit_line(&format!("    str x0, [x29, #{}]", var_offset));
@@ -329,13 +1760,69 @@ impl CodeGenerator {
                     }
                 }
             }
-            Statement::Return(expr) => {
+            StatementKind::Return(Some(expr)) if self.aggregate_return.is_some() => {
+                let spilled = self.emit_aggregate_address(expr)?;
+                match self.target {
+                    Target::I386 => self.emit_line("    mov ecx, eax"),
+                    Target::Amd64 => self.emit_line("    mov r11, rax"),
+                    Target::Arm64 => self.emit_line("    mov x10, x0"),
+                }
+                match self.aggregate_return.clone() {
+                    Some((_, AggregatePassing::Registers(pieces))) => {
+                        self.emit_return_pieces(&pieces)
+                    }
+                    Some((size, AggregatePassing::Memory)) => {
+                        // Copied to where the caller asked; x86 also returns that address
+                        let buffer = self.return_buffer.expect("a hidden pointer was passed");
+                        match self.target {
+                            Target::Arm64 => {
+                                self.emit_line(&format!("    ldr x11, [x29, #{}]", buffer));
+                                self.emit_copy(size);
+                            }
+                            Target::I386 => {
+                                self.emit_line(&format!(
+                                    "    mov edx, DWORD PTR [ebp + {}]",
+                                    buffer
+                                ));
+                                self.emit_copy(size);
+                                self.emit_line("    mov eax, edx");
+                            }
+                            Target::Amd64 => {
+                                self.emit_line(&format!(
+                                    "    mov r10, QWORD PTR [rbp + {}]",
+                                    buffer
+                                ));
+                                self.emit_copy(size);
+                                self.emit_line("    mov rax, r10");
+                            }
+                        }
+                    }
+                    None => unreachable!("checked by the guard"),
+                }
+                self.emit_release(spilled);
+                self.emit_function_epilogue()?;
+            }
+            StatementKind::Return(expr) => {
                 if let Some(expr) = expr {
-                    self.generate_expression(expr)?;
+                    // float and double are returned in xmm0 (s0/d0), where they are kept
+                    let floating = self.floating_return.clone();
+                    if floating.is_none() {
+                        let returned = self.return_type.clone();
+                        self.generate_integer(expr, returned.as_ref())?;
+                    } else {
+                        self.generate_converted(expr, floating.as_ref())?;
+                    }
                     // Move result to return register
                     match self.target {
                         Target::I386 => {
-                            // Result should already be in eax
+                            // Result should already be in eax, or for cdecl
+                            // float and double go to the x87 stack
+                            if let Some(ty) = &floating {
+                                let (size, mov, width) = floating_operand(ty);
+                                self.emit_stack_reserve(size);
+                                self.emit_line(&format!("    {} {} PTR [esp], xmm0", mov, width));
+                                self.emit_line(&format!("    fld {} PTR [esp]", width));
+                            }
                         }
                         Target::Amd64 => {
                             // Result should already be in rax
@@ -345,15 +1832,20 @@ impl CodeGenerator {
                         }
                     }
                 }
-                // Force emit epilogue for each return statement
-                self.emit_function_epilogue_force()?;
+                // Every return leaves through its own epilogue
+                self.emit_function_epilogue()?;
             }
-            Statement::Block(statements) => {
+            StatementKind::Block(statements) => {
+                // Names declared in the block go out of scope (and their slots may be reused)
+                let outer_variables = self.local_variables.clone();
+                let outer_types = self.local_types.clone();
                 for stmt in statements {
                     self.generate_statement(stmt)?;
                 }
+                self.local_variables = outer_variables;
+                self.local_types = outer_types;
             }
-            Statement::If {
+            StatementKind::If {
                 condition,
                 then_stmt,
                 else_stmt,
@@ -361,7 +1853,7 @@ impl CodeGenerator {
                 let else_label = self.new_label("else");
                 let end_label = self.new_label("endif");
 
-                self.generate_expression(condition)?;
+                self.generate_condition(condition)?;
                 self.emit_conditional_jump(false, &else_label)?;
 
                 self.generate_statement(then_stmt)?;
@@ -369,32 +1861,39 @@ impl CodeGenerator {
 
                 self.emit_line(&format!("{}:", else_label));
                 if let Some(else_stmt) = else_stmt {
-                    // Reset epilogue flag for else branch in case it contains a return
-                    let saved_epilogue_state = self.epilogue_emitted;
-                    self.epilogue_emitted = false;
                     self.generate_statement(else_stmt)?;
-                    // If else branch didn't emit epilogue, restore the saved state
-                    if !self.epilogue_emitted {
-                        self.epilogue_emitted = saved_epilogue_state;
-                    }
                 }
 
                 self.emit_line(&format!("{}:", end_label));
             }
-            Statement::While { condition, body } => {
+            StatementKind::While { condition, body } => {
                 let loop_label = self.new_label("loop");
                 let end_label = self.new_label("endloop");
 
                 self.emit_line(&format!("{}:", loop_label));
-                self.generate_expression(condition)?;
+                self.generate_condition(condition)?;
                 self.emit_conditional_jump(false, &end_label)?;
 
-                self.generate_statement(body)?;
+                self.generate_loop_body(body, &end_label, &loop_label)?;
                 self.emit_jump(&loop_label)?;
 
                 self.emit_line(&format!("{}:", end_label));
             }
-            Statement::For {
+            StatementKind::DoWhile { body, condition } => {
+                let loop_label = self.new_label("do");
+                let condition_label = self.new_label("dowhile");
+                let end_label = self.new_label("enddo");
+
+                self.emit_line(&format!("{}:", loop_label));
+                self.generate_loop_body(body, &end_label, &condition_label)?;
+
+                self.emit_line(&format!("{}:", condition_label));
+                self.generate_condition(condition)?;
+                self.emit_conditional_jump(true, &loop_label)?;
+
+                self.emit_line(&format!("{}:", end_label));
+            }
+            StatementKind::For {
                 init,
                 condition,
                 increment,
@@ -406,20 +1905,22 @@ impl CodeGenerator {
                 }
 
                 let loop_label = self.new_label("forloop");
+                let next_label = self.new_label("fornext");
                 let end_label = self.new_label("endfor");
 
                 self.emit_line(&format!("{}:", loop_label));
 
                 // Generate condition check
                 if let Some(cond_expr) = condition {
-                    self.generate_expression(cond_expr)?;
+                    self.generate_condition(cond_expr)?;
                     self.emit_conditional_jump(false, &end_label)?;
                 }
 
-                // Generate body
-                self.generate_statement(body)?;
+                // Generate body; `continue` goes on with the increment
+                self.generate_loop_body(body, &end_label, &next_label)?;
 
                 // Generate increment
+                self.emit_line(&format!("{}:", next_label));
                 if let Some(inc_expr) = increment {
                     self.generate_expression(inc_expr)?;
                 }
@@ -427,37 +1928,316 @@ impl CodeGenerator {
                 self.emit_jump(&loop_label)?;
                 self.emit_line(&format!("{}:", end_label));
             }
+            StatementKind::Switch { expression, cases } => {
+                self.generate_switch(expression, cases)?;
+            }
+            StatementKind::Break => {
+                let target = self
+                    .jump_contexts
+                    .last()
+                    .map(|context| context.break_label.clone())
+                    .ok_or_else(|| AleccError::CodegenError {
+                        message: "break statement not within a loop or switch".to_string(),
+                        span: None,
+                    })?;
+                self.emit_jump(&target)?;
+            }
+            StatementKind::Continue => {
+                let target = self
+                    .jump_contexts
+                    .iter()
+                    .rev()
+                    .find_map(|context| context.continue_label.clone())
+                    .ok_or_else(|| AleccError::CodegenError {
+                        message: "continue statement not within a loop".to_string(),
+                        span: None,
+                    })?;
+                self.emit_jump(&target)?;
+            }
+            StatementKind::Goto(label) => {
+                let target = self.user_label(label);
+                self.emit_jump(&target)?;
+            }
+            StatementKind::ComputedGoto(address) => {
+                self.generate_expression(address)?;
+                match self.target {
+                    Target::I386 => self.emit_line("    jmp eax"),
+                    Target::Amd64 => self.emit_line("    jmp rax"),
+                    Target::Arm64 => self.emit_line("    br x0"),
+                }
+            }
+            StatementKind::Label(label) => {
+                let label = self.user_label(label);
+                self.emit_line(&format!("{}:", label));
+            }
+            StatementKind::Fallthrough => {}
+        }
+        Ok(())
+    }
+
+    /// Jump to the case matching the controlling expression, through a table
+    /// when the case values are dense enough and a chain of comparisons
+    /// otherwise, then generate the cases in order so control falls through
+    fn generate_switch(
+        &mut self,
+        expression: &Expression,
+        cases: &[(Option<Expression>, Vec<Statement>)],
+    ) -> Result<()> {
+        // The value is compared as its promoted type, as are the case values
+        let ty = self.expression_type(expression);
+        let natural = ty.as_ref().map_or(4, |ty| self.layout.size_of(ty));
+        let unsigned = natural >= 4 && self.zero_extended(ty.as_ref());
+        let size = natural.max(4);
+        self.generate_expression(expression)?;
+        self.emit_extension(ty.as_ref());
+
+        let labels: Vec<String> = cases.iter().map(|_| self.new_label("case")).collect();
+        let end_label = self.new_label("endswitch");
+        let evaluator = ConstantEvaluator::new().with_layout(&self.layout);
+        let mut values = Vec::new();
+        let mut default_label = end_label.clone();
+        for ((value, _), label) in cases.iter().zip(&labels) {
+            let Some(value) = value else {
+                default_label = label.clone();
+                continue;
+            };
+            let value = evaluator
+                .evaluate(value)
+                .map_err(|error| AleccError::CodegenError {
+                    message: error.to_string(),
+                    span: Some(error.span()),
+                })?;
+            values.push((promoted_value(value, size, unsigned), label.clone()));
+        }
+
+        let low = values.iter().map(|(value, _)| *value).min();
+        let high = values.iter().map(|(value, _)| *value).max();
+        match (low, high) {
+            (Some(low), Some(high))
+                if values.len() >= JUMP_TABLE_MIN_CASES
+                    && (high as i128 - low as i128 + 1)
+                        <= JUMP_TABLE_MAX_SPREAD * values.len() as i128 =>
+            {
+                self.emit_jump_table(&values, low, high, &default_label)?;
+            }
             _ => {
-                // Other statements not implemented yet
-                return Err(AleccError::CodegenError {
-                    message: "Statement type not implemented".to_string(),
-                });
+                self.emit_case_comparisons(&values)?;
+                self.emit_jump(&default_label)?;
             }
         }
+
+        // All the cases are one block, which `break` leaves
+        let outer_variables = self.local_variables.clone();
+        let outer_types = self.local_types.clone();
+        self.jump_contexts.push(JumpContext {
+            break_label: end_label.clone(),
+            continue_label: None,
+        });
+        let generated = cases
+            .iter()
+            .zip(&labels)
+            .try_for_each(|((_, statements), label)| {
+                self.emit_line(&format!("{}:", label));
+                statements
+                    .iter()
+                    .try_for_each(|statement| self.generate_statement(statement))
+            });
+        self.jump_contexts.pop();
+        generated?;
+        self.local_variables = outer_variables;
+        self.local_types = outer_types;
+        self.emit_line(&format!("{}:", end_label));
         Ok(())
     }
 
-    fn generate_expression(&mut self, expression: &Expression) -> Result<()> {
-        match expression {
-            Expression::IntegerLiteral(value) => match self.target {
+    /// Jump to the label of the case value in the result register equals, if any
+    fn emit_case_comparisons(&mut self, values: &[(i64, String)]) -> Result<()> {
+        if self.target == Target::Arm64 {
+            self.emit_line("    mov x9, x0");
+        }
+        for (value, label) in values {
+            match self.target {
                 Target::I386 => {
-                    self.emit_line(&format!("    mov eax, {}", value));
+                    self.emit_line(&format!("    cmp eax, {}", *value as i32));
+                    self.emit_line(&format!("    je {}", label));
                 }
                 Target::Amd64 => {
-                    self.emit_line(&format!("    mov rax, {}", value));
+                    if i32::try_from(*value).is_ok() {
+                        self.emit_line(&format!("    cmp rax, {}", value));
+                    } else {
+                        self.emit_line(&format!("    mov rcx, {}", value));
+                        self.emit_line("    cmp rax, rcx");
+                    }
+                    self.emit_line(&format!("    je {}", label));
                 }
                 Target::Arm64 => {
-                    self.emit_line(&format!("    mov x0, #{}", value));
+                    if (0..4096).contains(value) {
+                        self.emit_line(&format!("    cmp x9, #{}", value));
+                    } else {
+                        self.emit_arm64_constant(*value);
+                        self.emit_line("    cmp x9, x0");
+                    }
+                    self.emit_line(&format!("    b.eq {}", label));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Jump through a table of the case labels indexed by the value in the
+    /// result register less `low`, after one unsigned comparison sends values
+    /// outside `low..=high` to `default_label`. Entries are offsets from the
+    /// table, which needs no relocation in position-independent code; values
+    /// without a case go to `default_label` too
+    fn emit_jump_table(
+        &mut self,
+        values: &[(i64, String)],
+        low: i64,
+        high: i64,
+        default_label: &str,
+    ) -> Result<()> {
+        let table = self.new_label("switch_table");
+        let last = high.wrapping_sub(low);
+        match self.target {
+            Target::I386 => {
+                if low != 0 {
+                    self.emit_line(&format!("    sub eax, {}", low as i32));
+                }
+                self.emit_line(&format!("    cmp eax, {}", last));
+                self.emit_line(&format!("    ja {}", default_label));
+                self.emit_line(&format!("    mov ecx, DWORD PTR [{} + eax*4]", table));
+                self.emit_line("    jmp ecx");
+            }
+            Target::Amd64 => {
+                if i32::try_from(low).is_ok() {
+                    if low != 0 {
+                        self.emit_line(&format!("    sub rax, {}", low));
+                    }
+                } else {
+                    self.emit_line(&format!("    mov rcx, {}", low));
+                    self.emit_line("    sub rax, rcx");
+                }
+                self.emit_line(&format!("    cmp rax, {}", last));
+                self.emit_line(&format!("    ja {}", default_label));
+                self.emit_line(&format!("    lea rcx, [rip + {}]", table));
+                self.emit_line("    movsxd rax, DWORD PTR [rcx + rax*4]");
+                self.emit_line("    add rax, rcx");
+                self.emit_line("    jmp rax");
+            }
+            Target::Arm64 => {
+                self.emit_line("    mov x9, x0");
+                if low != 0 {
+                    self.emit_arm64_constant(low);
+                    self.emit_line("    sub x9, x9, x0");
+                }
+                self.emit_arm64_constant(last);
+                self.emit_line("    cmp x9, x0");
+                self.emit_line(&format!("    b.hi {}", default_label));
+                self.emit_line(&format!("    adrp x10, {}", table));
+                self.emit_line(&format!("    add x10, x10, :lo12:{}", table));
+                self.emit_line("    ldrsw x11, [x10, x9, lsl #2]");
+                self.emit_line("    add x10, x10, x11");
+                self.emit_line("    br x10");
+            }
+        }
+
+        self.emit_line(".pushsection .rodata");
+        self.emit_line(".balign 4");
+        self.emit_line(&format!("{}:", table));
+        for offset in 0..=last {
+            let value = low.wrapping_add(offset);
+            let target = values
+                .iter()
+                .find(|(case, _)| *case == value)
+                .map_or(default_label, |(_, label)| label.as_str());
+            let entry = match self.target {
+                Target::I386 => format!("    .long {}", target),
+                Target::Amd64 => format!("    .long {} - {}", target, table),
+                Target::Arm64 => format!("    .word {} - {}", target, table),
+            };
+            self.emit_line(&entry);
+        }
+        self.emit_line(".popsection");
+        Ok(())
+    }
+
+    /// Generate a loop's body, where `break` goes to `end` and `continue` to `next`
+    fn generate_loop_body(&mut self, body: &Statement, end: &str, next: &str) -> Result<()> {
+        self.jump_contexts.push(JumpContext {
+            break_label: end.to_string(),
+            continue_label: Some(next.to_string()),
+        });
+        let generated = self.generate_statement(body);
+        self.jump_contexts.pop();
+        generated
+    }
+
+    /// Every expression leaves the stack pointer where it found it: the
+    /// expression stack is only ever used for temporaries, so a discarded value
+    /// can't leave pushes behind. The emitter's count of pushes and pops is checked.
+    fn generate_expression(&mut self, expression: &Expression) -> Result<()> {
+        let depth = self.stack_depth;
+        self.generate_expression_unlocated(expression)
+            .map_err(|error| error.at(expression.span))?;
+
+        let effect = depth - self.stack_depth;
+        if effect != 0 {
+            return Err(AleccError::CodegenError {
+                message: format!(
+                    "internal error: expression moves the stack pointer by {} bytes",
+                    effect
+                ),
+                span: Some(expression.span),
+            });
+        }
+        Ok(())
+    }
+
+    fn generate_expression_unlocated(&mut self, expression: &Expression) -> Result<()> {
+        match &expression.kind {
+            ExpressionKind::FloatLiteral(value) => {
+                self.emit_floating_constant(*value, &Type::Double, 0)?;
+            }
+            ExpressionKind::IntegerLiteral(value) => match self.target {
+                Target::I386 => {
+                    self.emit_line(&format!("    mov eax, {}", *value as i32));
+                    if i32::try_from(*value).is_err() {
+                        // A `long long`, whose high half goes in edx
+                        self.emit_line(&format!("    mov edx, {}", (*value >> 32) as i32));
+                    }
+                }
+                Target::Amd64 => {
+                    self.emit_line(&format!("    mov rax, {}", value));
                 }
+                Target::Arm64 => self.emit_arm64_constant(*value),
             },
-            Expression::StringLiteral(value) => {
+            ExpressionKind::Sizeof(ty) => {
+                let size = self.layout.size_of(ty);
+                match self.target {
+                    Target::I386 => self.emit_line(&format!("    mov eax, {}", size)),
+                    Target::Amd64 => self.emit_line(&format!("    mov rax, {}", size)),
+                    Target::Arm64 => self.emit_line(&format!("    mov x0, #{}", size)),
+                }
+            }
+            ExpressionKind::LabelAddress(label) => {
+                let label = self.user_label(label);
+                match self.target {
+                    Target::I386 => self.emit_line(&format!("    mov eax, OFFSET {}", label)),
+                    Target::Amd64 => self.emit_line(&format!("    lea rax, [rip + {}]", label)),
+                    Target::Arm64 => {
+                        self.emit_line(&format!("    adrp x0, {}", label));
+                        self.emit_line(&format!("    add x0, x0, :lo12:{}", label));
+                    }
+                }
+            }
+            ExpressionKind::StringLiteral(value) => {
                 let label = self.get_string_literal_label(value);
                 match self.target {
                     Target::I386 => {
                         self.emit_line(&format!("    mov eax, OFFSET {}", label));
                     }
                     Target::Amd64 => {
-                        self.emit_line(&format!("    lea rax, [{}]", label));
+                        self.emit_line(&format!("    lea rax, [rip + {}]", label));
                     }
                     Target::Arm64 => {
                         self.emit_line(&format!("    adrp x0, {}", label));
@@ -465,329 +2245,164 @@ impl CodeGenerator {
                     }
                 }
             }
-            Expression::Identifier(name) => {
-                // Check if it's a function parameter first
-                if let Some((_, offset)) = self
-                    .current_function_params
-                    .iter()
-                    .find(|(param_name, _)| param_name == name)
-                {
-                    // Load parameter from stack
-                    match self.target {
-                        Target::I386 => {
-                            self.emit_line(&format!("    mov eax, DWORD PTR [ebp + {}]", offset));
-                        }
-                        Target::Amd64 => {
-                            self.emit_line(&format!("    mov rax, QWORD PTR [rbp + {}]", offset));
-                        }
-                        Target::Arm64 => {
-                            self.emit_line(&format!("    ldr x0, [x29, #{}]", offset));
-                        }
-                    }
-                } else if let Some(offset) = self.local_variables.get(name) {
-                    // Load local variable from stack
-                    match self.target {
-                        Target::I386 => {
-                            self.emit_line(&format!("    mov eax, DWORD PTR [ebp + {}]", offset));
-                        }
-                        Target::Amd64 => {
-                            self.emit_line(&format!("    mov rax, QWORD PTR [rbp + {}]", offset));
-                        }
-                        Target::Arm64 => {
-                            self.emit_line(&format!("    ldr x0, [x29, #{}]", offset));
-                        }
+            ExpressionKind::Identifier(name) => {
+                let ty = self.variable_type(name);
+                if ty.is_none() && self.return_types.contains_key(name) {
+                    // A function decays to its address
+                    return self.emit_lvalue_address(expression);
+                }
+                if matches!(ty, Some(Type::Array(..))) {
+                    // An array decays to the address of its first element
+                    return self.emit_lvalue_address(expression);
+                }
+
+                if let Some(offset) = self.frame_offset(name) {
+                    if self.target == Target::Amd64
+                        && self.local_variables.contains_key(name)
+                        && ty.as_ref().is_some_and(Self::is_va_list)
+                    {
+                        // va_list is an array type on amd64 and decays to a pointer
+                        self.emit_line(&format!("    lea rax, [rbp + {}]", offset));
+                        return Ok(());
                     }
+
+                    // Load parameter or local variable from stack
+                    let address = match self.target {
+                        Target::I386 => format!("[ebp + {}]", offset),
+                        Target::Amd64 => format!("[rbp + {}]", offset),
+                        Target::Arm64 => format!("[x29, #{}]", offset),
+                    };
+                    self.emit_load(ty.as_ref(), &address);
                 } else {
                     // Load global variable
-                    match self.target {
-                        Target::I386 => {
-                            self.emit_line(&format!("    mov eax, DWORD PTR [{}]", name));
-                        }
-                        Target::Amd64 => {
-                            self.emit_line(&format!("    mov rax, QWORD PTR [{}]", name));
-                        }
-                        Target::Arm64 => {
-                            self.emit_line(&format!("    adrp x1, {}", name));
-                            self.emit_line(&format!("    add x1, x1, :lo12:{}", name));
-                            self.emit_line("    ldr x0, [x1]");
-                        }
-                    }
+                    let address = self.global_operand(name);
+                    self.emit_load(ty.as_ref(), &address);
                 }
             }
-            Expression::Call {
+            ExpressionKind::Call {
                 function,
                 arguments,
             } => {
-                // Generate arguments and place in calling convention registers/stack
-                match self.target {
-                    Target::I386 => {
-                        // i386: push arguments in reverse order
-                        for arg in arguments.iter().rev() {
-                            self.generate_expression(arg)?;
-                            self.emit_line("    push eax");
+                if let ExpressionKind::Identifier(func_name) = &function.kind {
+                    match func_name.as_str() {
+                        "__builtin_va_start" | "va_start" => {
+                            return self.generate_va_start(arguments);
                         }
-                    }
-                    Target::Amd64 => {
-                        // x86_64: first 6 args in registers, rest on stack
-                        let param_registers = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
-
-                        // Ensure stack alignment before function call
-                        // Stack must be 16-byte aligned before 'call' instruction
-                        // Since 'call' pushes 8 bytes (return address), we need stack to be 8 bytes off 16-byte boundary
-                        let stack_args = if arguments.len() > param_registers.len() {
-                            arguments.len() - param_registers.len()
-                        } else {
-                            0
-                        };
-                        let mut stack_cleanup_size = 0;
-
-                        // Handle stack arguments if any
-                        if stack_args > 0 {
-                            let total_stack_bytes = stack_args * 8;
-                            // Ensure alignment: if total_stack_bytes is odd multiple of 8, add 8 bytes for alignment
-                            if (total_stack_bytes / 8) % 2 != 0 {
-                                self.emit_line("    sub rsp, 8  # Stack alignment");
-                                stack_cleanup_size += 8;
-                            }
-                            stack_cleanup_size += stack_args * 8;
+                        "__builtin_va_copy" | "va_copy" => {
+                            return self.generate_va_copy(arguments);
                         }
-                        // Note: No additional alignment for register-only calls since function prologue handles it
-
-                        // First, save any arguments that go on the stack (in reverse order)
-                        if arguments.len() > param_registers.len() {
-                            for arg in arguments.iter().skip(param_registers.len()).rev() {
-                                self.generate_expression(arg)?;
-                                self.emit_line("    push rax");
-                            }
+                        "__builtin_va_end" | "va_end" => {
+                            // Nothing to release on any supported ABI
+                            return Ok(());
                         }
-
-                        // Then handle register arguments in reverse order to avoid overwriting
-                        let reg_args: Vec<_> =
-                            arguments.iter().take(param_registers.len()).collect();
-                        for (i, arg) in reg_args.iter().enumerate().rev() {
-                            self.generate_expression(arg)?;
-                            self.emit_line(&format!("    mov {}, rax", param_registers[i]));
+                        "alloca" if !self.defined_functions.contains("alloca") => {
+                            return self.generate_builtin("__builtin_alloca", arguments);
                         }
-
-                        // Store cleanup size for later use
-                        self.last_call_stack_cleanup = stack_cleanup_size;
-                    }
-                    Target::Arm64 => {
-                        // ARM64: first 8 args in x0-x7, rest on stack
-                        // Save stack arguments first
-                        if arguments.len() > 8 {
-                            for arg in arguments.iter().skip(8).rev() {
-                                self.generate_expression(arg)?;
-                                self.emit_line("    str x0, [sp, #-16]!");
-                            }
+                        name if is_builtin(name) && library_function(name).is_none() => {
+                            return self.generate_builtin(name, arguments);
                         }
-
-                        // Then handle register arguments in reverse order
-                        let reg_args: Vec<_> = arguments.iter().take(8).collect();
-                        for (i, arg) in reg_args.iter().enumerate().rev() {
-                            self.generate_expression(arg)?;
-                            if i > 0 {
-                                self.emit_line(&format!("    mov x{}, x0", i));
+                        name if !self.return_types.contains_key(name) => {
+                            if let Some(atomic) = atomic(name) {
+                                return self.generate_atomic(name, atomic, arguments);
                             }
-                            // x0 already has the result for first argument
                         }
+                        _ => {}
                     }
                 }
 
-                if let Expression::Identifier(func_name) = function.as_ref() {
-                    self.emit_line(&format!("    call {}", func_name));
-                } else {
-                    return Err(AleccError::CodegenError {
-                        message: "Indirect function calls not implemented".to_string(),
-                    });
-                }
-
-                // Clean up stack for arguments that were pushed
-                match self.target {
-                    Target::I386 => {
-                        let stack_cleanup = arguments.len() * 4;
-                        if stack_cleanup > 0 {
-                            self.emit_line(&format!("    add esp, {}", stack_cleanup));
-                        }
-                    }
-                    Target::Amd64 => {
-                        // Clean up stack using stored cleanup size
-                        if self.last_call_stack_cleanup > 0 {
-                            self.emit_line(&format!(
-                                "    add rsp, {}",
-                                self.last_call_stack_cleanup
-                            ));
-                        }
-                    }
-                    Target::Arm64 => {
-                        // Clean up stack arguments (if any)
-                        let stack_args = if arguments.len() > 8 {
-                            arguments.len() - 8
-                        } else {
-                            0
-                        };
-                        if stack_args > 0 {
-                            self.emit_line(&format!("    add sp, sp, #{}", stack_args * 16));
-                        }
-                    }
+                let result = self.emit_call(function, arguments)?;
+                if result > 0 {
+                    // Only the first word of a struct or union result is kept
+                    self.emit_load_through(self.expression_type(expression).as_ref());
+                    self.emit_release(result);
                 }
             }
-            Expression::Binary {
+            ExpressionKind::Binary {
                 left,
                 operator,
                 right,
             } => {
+                let logical = matches!(
+                    operator,
+                    BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr
+                );
+                if !logical {
+                    let operands =
+                        common_floating(self.floating_type(left), self.floating_type(right));
+                    if let Some(ty) = operands {
+                        return self.generate_floating_binary(left, operator, right, &ty);
+                    }
+                    let wide = match operator {
+                        BinaryOperator::Equal
+                        | BinaryOperator::NotEqual
+                        | BinaryOperator::Less
+                        | BinaryOperator::LessEqual
+                        | BinaryOperator::Greater
+                        | BinaryOperator::GreaterEqual => {
+                            common_wide(self.wide_type(left), self.wide_type(right))
+                        }
+                        _ => self.wide_type(expression),
+                    };
+                    if let Some(ty) = wide {
+                        return self.generate_wide_binary(left, operator, right, &ty);
+                    }
+                }
+
                 // Generate binary operations
                 // First generate right operand and save it
-                self.generate_expression(right)?;
+                if logical {
+                    self.generate_condition(right)?;
+                } else {
+                    self.generate_expression(right)?;
+                }
                 match self.target {
                     Target::I386 => {
-                        self.emit_line("    push eax"); // Save right operand
+                        self.emit_push("eax"); // Save right operand
                     }
                     Target::Amd64 => {
-                        self.emit_line("    push rax"); // Save right operand
+                        self.emit_push("rax"); // Save right operand
                     }
                     Target::Arm64 => {
-                        self.emit_line("    str x0, [sp, #-16]!"); // Save right operand
+                        self.emit_push("x0"); // Save right operand
                     }
                 }
 
                 // Generate left operand
-                self.generate_expression(left)?;
+                if logical {
+                    self.generate_condition(left)?;
+                } else {
+                    self.generate_expression(left)?;
+                }
 
                 // Pop right operand and perform operation
                 match self.target {
-                    Target::I386 => {
-                        self.emit_line("    pop ebx"); // Right operand in ebx
-                        match operator {
-                            BinaryOperator::Add => self.emit_line("    add eax, ebx"),
-                            BinaryOperator::Subtract => self.emit_line("    sub eax, ebx"),
-                            BinaryOperator::Multiply => self.emit_line("    imul eax, ebx"),
-                            BinaryOperator::Divide => {
-                                self.emit_line("    cdq"); // Sign extend eax to edx:eax
-                                self.emit_line("    idiv ebx");
-                            }
-                            BinaryOperator::Modulo => {
-                                self.emit_line("    cdq"); // Sign extend eax to edx:eax
-                                self.emit_line("    idiv ebx");
-                                self.emit_line("    mov eax, edx"); // Remainder is in edx
-                            }
-                            _ => {
-                                return Err(AleccError::CodegenError {
-                                    message: format!(
-                                        "Binary operator {:?} not implemented for i386",
-                                        operator
-                                    ),
-                                });
-                            }
-                        }
+                    Target::I386 => self.emit_pop("ebx"),
+                    Target::Amd64 => self.emit_pop("rbx"),
+                    Target::Arm64 => self.emit_pop("x1"),
+                }
+                let unsigned = match operator {
+                    BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                        self.unsigned_operand(self.expression_type(left).as_ref())
                     }
-                    Target::Amd64 => {
-                        self.emit_line("    pop rbx"); // Right operand in rbx
-                        match operator {
-                            BinaryOperator::Add => self.emit_line("    add rax, rbx"),
-                            BinaryOperator::Subtract => self.emit_line("    sub rax, rbx"),
-                            BinaryOperator::Multiply => self.emit_line("    imul rax, rbx"),
-                            BinaryOperator::Divide => {
-                                self.emit_line("    cqo"); // Sign extend rax to rdx:rax
-                                self.emit_line("    idiv rbx");
-                            }
-                            BinaryOperator::Modulo => {
-                                self.emit_line("    cqo"); // Sign extend rax to rdx:rax
-                                self.emit_line("    idiv rbx");
-                                self.emit_line("    mov rax, rdx"); // Remainder is in rdx
-                            }
-                            // Comparison operators
-                            BinaryOperator::Equal => {
-                                self.emit_line("    cmp rax, rbx");
-                                self.emit_line("    sete al");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            BinaryOperator::NotEqual => {
-                                self.emit_line("    cmp rax, rbx");
-                                self.emit_line("    setne al");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            BinaryOperator::Less => {
-                                self.emit_line("    cmp rax, rbx");
-                                self.emit_line("    setl al");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            BinaryOperator::Greater => {
-                                self.emit_line("    cmp rax, rbx");
-                                self.emit_line("    setg al");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            BinaryOperator::LessEqual => {
-                                self.emit_line("    cmp rax, rbx");
-                                self.emit_line("    setle al");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            BinaryOperator::GreaterEqual => {
-                                self.emit_line("    cmp rax, rbx");
-                                self.emit_line("    setge al");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            // Logical operators
-                            BinaryOperator::LogicalAnd => {
-                                self.emit_line("    test rax, rax");
-                                self.emit_line("    setne al");
-                                self.emit_line("    test rbx, rbx");
-                                self.emit_line("    setne bl");
-                                self.emit_line("    and al, bl");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            BinaryOperator::LogicalOr => {
-                                self.emit_line("    test rax, rax");
-                                self.emit_line("    setne al");
-                                self.emit_line("    test rbx, rbx");
-                                self.emit_line("    setne bl");
-                                self.emit_line("    or al, bl");
-                                self.emit_line("    movzx rax, al");
-                            }
-                            // Bitwise operators
-                            BinaryOperator::BitwiseAnd => self.emit_line("    and rax, rbx"),
-                            BinaryOperator::BitwiseOr => self.emit_line("    or rax, rbx"),
-                            BinaryOperator::BitwiseXor => self.emit_line("    xor rax, rbx"),
-                            // Shift operators
-                            BinaryOperator::LeftShift => {
-                                self.emit_line("    mov rcx, rbx"); // Shift count in rcx
-                                self.emit_line("    shl rax, cl");
-                            }
-                            BinaryOperator::RightShift => {
-                                self.emit_line("    mov rcx, rbx"); // Shift count in rcx
-                                self.emit_line("    sar rax, cl"); // Arithmetic right shift
-                            }
-                        }
-                    }
-                    Target::Arm64 => {
-                        self.emit_line("    ldr x1, [sp], #16"); // Right operand in x1
-                        match operator {
-                            BinaryOperator::Add => self.emit_line("    add x0, x0, x1"),
-                            BinaryOperator::Subtract => self.emit_line("    sub x0, x0, x1"),
-                            BinaryOperator::Multiply => self.emit_line("    mul x0, x0, x1"),
-                            BinaryOperator::Divide => self.emit_line("    sdiv x0, x0, x1"),
-                            BinaryOperator::Modulo => {
-                                self.emit_line("    sdiv x2, x0, x1"); // x2 = x0 / x1
-                                self.emit_line("    msub x0, x2, x1, x0"); // x0 = x0 - (x2 * x1)
-                            }
-                            _ => {
-                                return Err(AleccError::CodegenError {
-                                    message: format!(
-                                        "Binary operator {:?} not implemented for arm64",
-                                        operator
-                                    ),
-                                });
-                            }
-                        }
-                    }
-                }
+                    _ => [left, right].iter().any(|operand| {
+                        self.unsigned_operand(self.expression_type(operand).as_ref())
+                    }),
+                };
+                self.emit_binary_operator(operator, unsigned)?;
             }
-            Expression::Unary { operator, operand } => {
+            ExpressionKind::Unary { operator, operand } => {
                 match operator {
                     UnaryOperator::Minus => {
                         self.generate_expression(operand)?;
+                        if let Some(ty) = self.floating_type(operand) {
+                            return self.emit_floating_negation(&ty);
+                        }
                         match self.target {
+                            Target::I386 if self.wide_type(operand).is_some() => {
+                                self.emit_line("    neg eax");
+                                self.emit_line("    adc edx, 0");
+                                self.emit_line("    neg edx");
+                            }
                             Target::I386 => {
                                 self.emit_line("    neg eax");
                             }
@@ -805,8 +2420,14 @@ impl CodeGenerator {
                     }
                     UnaryOperator::LogicalNot => {
                         self.generate_expression(operand)?;
+                        if let Some(ty) = self.floating_type(operand) {
+                            return self.emit_floating_truth(&ty, true);
+                        }
                         match self.target {
                             Target::I386 => {
+                                if self.wide_type(operand).is_some() {
+                                    self.emit_line("    or eax, edx");
+                                }
                                 self.emit_line("    test eax, eax");
                                 self.emit_line("    setz al");
                                 self.emit_line("    movzx eax, al");
@@ -827,6 +2448,9 @@ impl CodeGenerator {
                         match self.target {
                             Target::I386 => {
                                 self.emit_line("    not eax");
+                                if self.wide_type(operand).is_some() {
+                                    self.emit_line("    not edx");
+                                }
                             }
                             Target::Amd64 => {
                                 self.emit_line("    not rax");
@@ -836,430 +2460,3971 @@ impl CodeGenerator {
                             }
                         }
                     }
-                    UnaryOperator::PreIncrement => {
-                        // Load variable, increment, store back, and leave incremented value in register
-                        if let Expression::Identifier(name) = operand.as_ref() {
-                            if let Some(&offset) = self.local_variables.get(name) {
-                                match self.target {
-                                    Target::I386 => {
-                                        self.emit_line(&format!(
-                                            "    inc DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    mov eax, DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Amd64 => {
-                                        self.emit_line(&format!(
-                                            "    inc QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    mov rax, QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Arm64 => {
-                                        self.emit_line(&format!("    ldr x0, [x29, #{}]", offset));
-                                        self.emit_line("    add x0, x0, #1");
-                                        self.emit_line(&format!("    str x0, [x29, #{}]", offset));
-                                    }
-                                }
-                            } else {
-                                return Err(AleccError::CodegenError {
-                                    message: format!("Undefined variable: {}", name),
-                                });
-                            }
-                        } else {
-                            return Err(AleccError::CodegenError {
-                                message: "Pre-increment can only be applied to variables"
-                                    .to_string(),
-                            });
-                        }
+                    UnaryOperator::PreIncrement
+                    | UnaryOperator::PostIncrement
+                    | UnaryOperator::PreDecrement
+                    | UnaryOperator::PostDecrement => {
+                        self.emit_lvalue_address(operand)?;
+                        self.emit_increment(operator, self.expression_type(operand).as_ref())?;
                     }
-                    UnaryOperator::PostIncrement => {
-                        // Load variable, store incremented value, but leave original value in register
-                        if let Expression::Identifier(name) = operand.as_ref() {
-                            if let Some(&offset) = self.local_variables.get(name) {
-                                match self.target {
-                                    Target::I386 => {
-                                        self.emit_line(&format!(
-                                            "    mov eax, DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    inc DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Amd64 => {
-                                        self.emit_line(&format!(
-                                            "    mov rax, QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    inc QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Arm64 => {
-                                        self.emit_line(&format!("    ldr x0, [x29, #{}]", offset));
-                                        self.emit_line(&format!("    ldr x1, [x29, #{}]", offset));
-                                        self.emit_line("    add x1, x1, #1");
-                                        self.emit_line(&format!("    str x1, [x29, #{}]", offset));
-                                    }
-                                }
-                            } else {
-                                return Err(AleccError::CodegenError {
-                                    message: format!("Undefined variable: {}", name),
-                                });
-                            }
-                        } else {
-                            return Err(AleccError::CodegenError {
-                                message: "Post-increment can only be applied to variables"
-                                    .to_string(),
-                            });
-                        }
+                    UnaryOperator::AddressOf => {
+                        self.emit_lvalue_address(operand)?;
                     }
-                    UnaryOperator::PreDecrement => {
-                        // Similar to PreIncrement but with decrement
-                        if let Expression::Identifier(name) = operand.as_ref() {
-                            if let Some(&offset) = self.local_variables.get(name) {
-                                match self.target {
-                                    Target::I386 => {
-                                        self.emit_line(&format!(
-                                            "    dec DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    mov eax, DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Amd64 => {
-                                        self.emit_line(&format!(
-                                            "    dec QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    mov rax, QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Arm64 => {
-                                        self.emit_line(&format!("    ldr x0, [x29, #{}]", offset));
-                                        self.emit_line("    sub x0, x0, #1");
-                                        self.emit_line(&format!("    str x0, [x29, #{}]", offset));
-                                    }
-                                }
-                            } else {
-                                return Err(AleccError::CodegenError {
-                                    message: format!("Undefined variable: {}", name),
-                                });
-                            }
-                        } else {
-                            return Err(AleccError::CodegenError {
-                                message: "Pre-decrement can only be applied to variables"
-                                    .to_string(),
-                            });
+                    UnaryOperator::Dereference => {
+                        // Dereference a pointer (load value from address)
+                        self.generate_expression(operand)?; // Get the address
+                        self.emit_load_through(self.expression_type(expression).as_ref());
+                    }
+                }
+            }
+            ExpressionKind::Index { .. } | ExpressionKind::Member { .. } => {
+                let spilled = self.emit_object_address(expression)?;
+                self.emit_load_through(self.expression_type(expression).as_ref());
+                self.emit_release(spilled);
+            }
+            ExpressionKind::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                let aggregate = self
+                    .expression_type(target)
+                    .and_then(|ty| self.aggregate(&ty));
+                if matches!(operator, AssignmentOperator::Assign) && aggregate.is_some() {
+                    self.generate_aggregate_assignment(target, value)?;
+                } else if matches!(operator, AssignmentOperator::Assign) {
+                    let floating = self.floating_type(target);
+                    match &floating {
+                        Some(ty) => {
+                            self.generate_converted(value, floating.as_ref())?;
+                            // Stored as bits from the result register, which keeps them
+                            self.emit_floating_bits(ty)?;
+                            self.store_in_target(target)?;
+                            self.emit_bits_to_floating(ty, self.result_register(), 0)?;
+                        }
+                        None => {
+                            self.generate_integer(value, self.expression_type(target).as_ref())?;
+                            self.store_in_target(target)?;
                         }
                     }
-                    UnaryOperator::PostDecrement => {
-                        // Similar to PostIncrement but with decrement
-                        if let Expression::Identifier(name) = operand.as_ref() {
-                            if let Some(&offset) = self.local_variables.get(name) {
-                                match self.target {
-                                    Target::I386 => {
-                                        self.emit_line(&format!(
-                                            "    mov eax, DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    dec DWORD PTR [ebp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Amd64 => {
-                                        self.emit_line(&format!(
-                                            "    mov rax, QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                        self.emit_line(&format!(
-                                            "    dec QWORD PTR [rbp + {}]",
-                                            offset
-                                        ));
-                                    }
-                                    Target::Arm64 => {
-                                        self.emit_line(&format!("    ldr x0, [x29, #{}]", offset));
-                                        self.emit_line(&format!("    ldr x1, [x29, #{}]", offset));
-                                        self.emit_line("    sub x1, x1, #1");
-                                        self.emit_line(&format!("    str x1, [x29, #{}]", offset));
-                                    }
-                                }
-                            } else {
-                                return Err(AleccError::CodegenError {
-                                    message: format!("Undefined variable: {}", name),
-                                });
+                } else {
+                    self.generate_compound_assignment(target, operator, value)?;
+                }
+            }
+            ExpressionKind::VaArg { list, arg_type } => {
+                self.generate_va_arg(list, arg_type)?;
+            }
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } if self.wide_integer(target_type).is_some() => {
+                self.generate_wide(expression)?;
+            }
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } if self.floating(target_type).is_some()
+                || self.floating_type(expression).is_some() =>
+            {
+                let floating = self.floating(target_type);
+                self.generate_converted(expression, floating.as_ref())?;
+                if floating.is_none() {
+                    self.emit_integer_conversion(Some(target_type));
+                }
+            }
+            // Values are kept a whole register wide, so casts to a narrower
+            // integer truncate and extend again, and pointer casts are no-ops
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } if !matches!(
+                self.layout.resolve(target_type),
+                Type::Float | Type::Double | Type::Struct { .. } | Type::Union { .. }
+            ) =>
+            {
+                self.generate_integer(expression, Some(target_type))?;
+            }
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: "Expression type not implemented".to_string(),
+                    span: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Call `function` with `arguments`, leaving the result in the result
+    /// register (xmm0, s0 or d0 for a `float` or `double`). A struct or union
+    /// result goes to a temporary on the stack, whose address is left instead;
+    /// the bytes the temporary takes are returned, to be released once it has
+    /// been read, like `emit_object_address` does.
+    fn emit_call(&mut self, function: &Expression, arguments: &[Expression]) -> Result<usize> {
+        let direct = self.called_function(function).map(str::to_string);
+        let CallSignature {
+            return_type,
+            parameters,
+            variadic,
+        } = match &direct {
+            Some(name) => CallSignature {
+                return_type: self.return_types.get(name).cloned(),
+                parameters: self.parameter_types.get(name).cloned(),
+                // A function without a prototype may turn out to be variadic too
+                variadic: self.variadic_functions.contains(name)
+                    || !self.parameter_types.contains_key(name),
+            },
+            None => self
+                .expression_type(function)
+                .and_then(|ty| self.function_signature(&ty))
+                .ok_or_else(|| AleccError::CodegenError {
+                    message: "called object is not a function or function pointer".to_string(),
+                    span: None,
+                })?,
+        };
+        // The pointer a call through `*f` or `**f` makes is just `f`
+        let mut callee = function;
+        while let ExpressionKind::Unary {
+            operator: UnaryOperator::Dereference,
+            operand,
+        } = &callee.kind
+        {
+            callee = operand;
+        }
+
+        // What each argument is converted to: its parameter's type, or
+        // for an argument without one, double if it is a float
+        let conversions: Vec<Option<Type>> = arguments
+            .iter()
+            .enumerate()
+            .map(
+                |(i, arg)| match parameters.as_ref().and_then(|p| p.get(i)) {
+                    Some(parameter) => self.floating(parameter),
+                    None => self.floating_type(arg).map(|_| Type::Double),
+                },
+            )
+            .collect();
+        let parameter = |i: usize| parameters.as_ref().and_then(|p| p.get(i)).cloned();
+        // Which arguments are i386 `long long` values, pushed as two words
+        let wide: Vec<bool> = arguments
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match parameter(i) {
+                Some(ty) => self.wide_integer(&ty).is_some(),
+                None => self.wide_type(arg).is_some(),
+            })
+            .collect();
+        let classes: Vec<ArgumentClass> = arguments
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let ty = parameter(i).or_else(|| self.expression_type(arg));
+                self.argument_class(ty.as_ref(), conversions[i].as_ref())
+            })
+            .collect();
+        let returned = return_type.as_ref().and_then(|ty| self.aggregate(ty));
+        let hidden_pointer = matches!(returned, Some((_, AggregatePassing::Memory)));
+        let ArgumentLayout {
+            locations,
+            stack_slots: stack_args,
+            ..
+        } = self.argument_locations(&classes, hidden_pointer);
+        let in_registers = |floating: bool| {
+            let wanted: Vec<(usize, usize)> = locations
+                .iter()
+                .enumerate()
+                .filter_map(|(i, location)| match location {
+                    ArgumentLocation::Integer(register) if !floating => Some((i, *register)),
+                    ArgumentLocation::Floating(register) if floating => Some((i, *register)),
+                    _ => None,
+                })
+                .collect();
+            wanted
+        };
+        let integer_args = in_registers(false);
+        let floating_args = in_registers(true);
+        // Computing an argument can take calls, divisions or shifts, which
+        // clobber argument registers, so the ones that do wait on the stack
+        // and only plain values are computed right into their register
+        let (leaf_args, computed_args): (Vec<_>, Vec<_>) = integer_args
+            .iter()
+            .partition(|&&(i, _)| is_leaf(&arguments[i]));
+        let split_args: Vec<(usize, Vec<(Piece, usize)>)> = locations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, location)| match location {
+                ArgumentLocation::Split(pieces) => Some((i, pieces.clone())),
+                _ => None,
+            })
+            .collect();
+
+        // A struct or union result gets a temporary above everything the call
+        // puts on the stack, which keeps the stack pointer 16-byte aligned
+        let result_bytes = returned
+            .as_ref()
+            .map_or(0, |(size, _)| size.next_multiple_of(16).max(16));
+        if result_bytes > 0 {
+            match self.target {
+                Target::I386 => self.emit_stack_reserve(result_bytes),
+                Target::Amd64 => self.emit_stack_reserve(result_bytes),
+                Target::Arm64 => self.emit_stack_reserve(result_bytes),
+            }
+        }
+        // The bytes each argument takes on the i386 stack
+        let pushed: Vec<usize> = classes
+            .iter()
+            .zip(&conversions)
+            .zip(&wide)
+            .map(|((class, ty), &wide)| match (class, ty) {
+                (ArgumentClass::Aggregate(size, _), _) => size.next_multiple_of(4),
+                (_, Some(ty)) => floating_operand(ty).0,
+                _ if wide => 8,
+                _ => 4,
+            })
+            .collect();
+
+        // Generate arguments and place in calling convention registers/stack
+        let mut copy_bytes = 0;
+        match self.target {
+            Target::I386 => {
+                // i386: push arguments in reverse order, with structs and
+                // unions copied whole
+                for (i, arg) in arguments.iter().enumerate().rev() {
+                    if let ArgumentClass::Aggregate(size, _) = classes[i] {
+                        self.emit_stack_reserve(pushed[i]);
+                        self.emit_aggregate_to_stack(arg, size, 0)?;
+                        continue;
+                    }
+                    self.generate_argument(arg, conversions[i].as_ref(), parameter(i).as_ref())?;
+                    match &conversions[i] {
+                        Some(ty) => {
+                            let (size, mov, width) = floating_operand(ty);
+                            self.emit_stack_reserve(size);
+                            self.emit_line(&format!("    {} {} PTR [esp], xmm0", mov, width));
+                        }
+                        None => {
+                            if wide[i] {
+                                self.emit_push("edx");
                             }
-                        } else {
-                            return Err(AleccError::CodegenError {
-                                message: "Post-decrement can only be applied to variables"
-                                    .to_string(),
-                            });
+                            self.emit_push("eax");
                         }
                     }
-                    UnaryOperator::AddressOf => {
-                        // Get address of a variable
-                        if let Expression::Identifier(name) = operand.as_ref() {
-                            if let Some(&offset) = self.local_variables.get(name) {
-                                match self.target {
-                                    Target::I386 => {
-                                        self.emit_line(&format!("    lea eax, [ebp + {}]", offset));
-                                    }
-                                    Target::Amd64 => {
-                                        self.emit_line(&format!("    lea rax, [rbp + {}]", offset));
-                                    }
-                                    Target::Arm64 => {
-                                        self.emit_line(&format!("    add x0, x29, #{}", offset));
-                                    }
-                                }
-                            } else {
-                                return Err(AleccError::CodegenError {
-                                    message: format!("Undefined variable: {}", name),
-                                });
-                            }
+                }
+                // The address of the result's temporary, right above the
+                // arguments, is pushed last, for the callee to pop
+                if hidden_pointer {
+                    let bytes: usize = pushed.iter().sum();
+                    self.emit_line(&format!("    lea eax, [esp + {}]", bytes));
+                    self.emit_push("eax");
+                }
+                // With every argument on the stack, computing the callee
+                // can't disturb them
+                if direct.is_none() {
+                    self.generate_expression(callee)?;
+                }
+            }
+            Target::Amd64 => {
+                // x86_64: first 6 integer args in registers, 8 floating in xmm0-xmm7, rest on stack
+
+                // A variadic callee may save xmm registers with movaps, so
+                // rsp is realigned in case the expression stack has left it
+                // 8 bytes off. The old value is pushed twice, which leaves rsp
+                // aligned, and the second copy is restored after the call.
+                // Where alloca can move the stack under it, the padding is
+                // pushed instead of an address
+                let moving_stack = self.alloca_bottom.is_some();
+                if variadic {
+                    self.emit_line("    mov rax, rsp");
+                    self.emit_line("    and rsp, -16");
+                    if moving_stack {
+                        self.emit_line("    sub rax, rsp");
+                    }
+                    self.emit_push("rax");
+                    self.emit_push("rax");
+                }
+
+                // Ensure stack alignment before function call
+                // Stack must be 16-byte aligned before 'call' instruction
+                // Since 'call' pushes 8 bytes (return address), we need stack to be 8 bytes off 16-byte boundary
+                let mut stack_cleanup_size = 0;
+
+                // Handle stack arguments if any
+                if stack_args > 0 {
+                    let total_stack_bytes = stack_args * 8;
+                    // Ensure alignment: if total_stack_bytes is odd multiple of 8, add 8 bytes for alignment
+                    if (total_stack_bytes / 8) % 2 != 0 {
+                        self.emit_stack_reserve(8);
+                        stack_cleanup_size += 8;
+                    }
+                    stack_cleanup_size += stack_args * 8;
+                }
+                // Note: No additional alignment for register-only calls since function prologue handles it
+
+                // First, save any arguments that go on the stack (in reverse order)
+                for (i, arg) in arguments.iter().enumerate().rev() {
+                    if !matches!(locations[i], ArgumentLocation::Stack(_)) {
+                        continue;
+                    }
+                    if let ArgumentClass::Aggregate(size, _) = classes[i] {
+                        // A struct or union is copied into as many slots as it takes
+                        self.emit_stack_reserve(size.next_multiple_of(8));
+                        self.emit_aggregate_to_stack(arg, size, 0)?;
+                        continue;
+                    }
+                    self.generate_argument(arg, conversions[i].as_ref(), parameter(i).as_ref())?;
+                    if let Some(ty) = &conversions[i] {
+                        self.emit_floating_bits(ty)?;
+                    }
+                    self.emit_push("rax");
+                }
+
+                // Floating-point arguments wait on the stack while the
+                // others are computed, which can use xmm0 and xmm1
+                for &(i, _) in floating_args.iter().rev() {
+                    let ty = conversions[i].clone().expect("floating argument");
+                    self.generate_converted(&arguments[i], Some(&ty))?;
+                    self.emit_floating_bits(&ty)?;
+                    self.emit_push("rax");
+                }
+                // And so do the pieces of structs and unions
+                for (i, pieces) in split_args.iter().rev() {
+                    self.emit_stack_reserve(pieces.len() * 8);
+                    self.emit_pieces_to_stack(&arguments[*i], pieces)?;
+                }
+
+                // The callee is computed first and taken out last
+                if direct.is_none() {
+                    self.generate_expression(callee)?;
+                    self.emit_push("rax");
+                }
+                for &(i, _) in computed_args.iter().rev() {
+                    self.generate_argument(&arguments[i], None, parameter(i).as_ref())?;
+                    self.emit_push("rax");
+                }
+                for &(i, register) in &leaf_args {
+                    self.generate_argument(&arguments[i], None, parameter(i).as_ref())?;
+                    self.emit_line(&format!(
+                        "    mov {}, rax",
+                        AMD64_ARGUMENT_REGISTERS[register]
+                    ));
+                }
+                for &(_, register) in &computed_args {
+                    self.emit_pop(AMD64_ARGUMENT_REGISTERS[register]);
+                }
+                if direct.is_none() {
+                    self.emit_pop("r11");
+                }
+
+                for (_, pieces) in &split_args {
+                    for (k, (piece, register)) in pieces.iter().enumerate() {
+                        let address = format!("[rsp + {}]", k * 8);
+                        if piece.floating {
+                            let (_, mov, width) = floating_operand(&piece_type(piece));
+                            self.emit_line(&format!(
+                                "    {} xmm{}, {} PTR {}",
+                                mov, register, width, address
+                            ));
                         } else {
-                            return Err(AleccError::CodegenError {
-                                message: "Address-of can only be applied to variables".to_string(),
-                            });
+                            self.emit_line(&format!(
+                                "    mov {}, QWORD PTR {}",
+                                AMD64_ARGUMENT_REGISTERS[*register], address
+                            ));
                         }
                     }
-                    UnaryOperator::Dereference => {
-                        // Dereference a pointer (load value from address)
-                        self.generate_expression(operand)?; // Get the address
-                        match self.target {
-                            Target::I386 => {
-                                self.emit_line("    mov eax, DWORD PTR [eax]");
+                    self.emit_stack_release(pieces.len() * 8);
+                }
+
+                for &(i, register) in &floating_args {
+                    self.emit_pop("rax");
+                    let ty = conversions[i].clone().expect("floating argument");
+                    self.emit_bits_to_floating(&ty, "rax", register)?;
+                }
+
+                // The result's temporary sits right above the stack arguments,
+                // or where the realigned stack pointer was saved from
+                if hidden_pointer && variadic {
+                    self.emit_line(&format!(
+                        "    mov rdi, QWORD PTR [rsp + {}]",
+                        stack_cleanup_size
+                    ));
+                    if moving_stack {
+                        self.emit_line(&format!(
+                            "    lea rdi, [rsp + rdi + {}]",
+                            stack_cleanup_size + 16
+                        ));
+                    }
+                } else if hidden_pointer {
+                    self.emit_line(&format!("    lea rdi, [rsp + {}]", stack_cleanup_size));
+                }
+
+                // al tells a variadic callee how many vector registers carry
+                // arguments, an upper bound being enough
+                if variadic {
+                    let vector_registers = locations
+                        .iter()
+                        .map(|location| match location {
+                            ArgumentLocation::Floating(_) => 1,
+                            ArgumentLocation::Split(pieces) => {
+                                pieces.iter().filter(|(piece, _)| piece.floating).count()
                             }
-                            Target::Amd64 => {
-                                self.emit_line("    mov rax, QWORD PTR [rax]");
+                            _ => 0,
+                        })
+                        .sum::<usize>();
+                    self.emit_line(&format!("    mov eax, {}", vector_registers));
+                }
+
+                // Store cleanup size for later use
+                self.last_call_stack_cleanup = stack_cleanup_size;
+            }
+            Target::Arm64 => {
+                // Structs and unions passed by reference are copied first, to
+                // temporaries that last until the call returns
+                let mut copies = HashMap::new();
+                for (i, location) in locations.iter().enumerate() {
+                    if let (ArgumentLocation::Indirect(_), ArgumentClass::Aggregate(size, _)) =
+                        (location, &classes[i])
+                    {
+                        copies.insert(i, copy_bytes);
+                        copy_bytes += size.next_multiple_of(16);
+                    }
+                }
+                if copy_bytes > 0 {
+                    self.emit_stack_reserve(copy_bytes);
+                }
+                for (i, arg) in arguments.iter().enumerate() {
+                    if let (Some(&offset), ArgumentClass::Aggregate(size, _)) =
+                        (copies.get(&i), &classes[i])
+                    {
+                        self.emit_aggregate_to_stack(arg, *size, offset)?;
+                    }
+                }
+
+                // ARM64: first 8 integer args in x0-x7 and 8 floating in
+                // d0-d7 (s0-s7), the rest in 8-byte slots upwards from
+                // sp, where the callee finds them at x29 + 16. On Linux the
+                // variadic ones go the same way (unlike on Apple platforms),
+                // floats having become doubles, and nothing says how many
+                let stack_bytes = arm64_stack_argument_bytes(stack_args);
+                if stack_bytes > 0 {
+                    self.emit_stack_reserve(stack_bytes);
+                }
+                for (i, arg) in arguments.iter().enumerate() {
+                    // Evaluation is stack-neutral, so sp still points at the slots
+                    match (&locations[i], &classes[i]) {
+                        (ArgumentLocation::Stack(slot), ArgumentClass::Aggregate(size, _)) => {
+                            self.emit_aggregate_to_stack(arg, *size, slot * 8)?;
+                        }
+                        (ArgumentLocation::Stack(slot), _) => {
+                            self.generate_argument(
+                                arg,
+                                conversions[i].as_ref(),
+                                parameter(i).as_ref(),
+                            )?;
+                            if let Some(ty) = &conversions[i] {
+                                self.emit_floating_bits(ty)?;
                             }
-                            Target::Arm64 => {
-                                self.emit_line("    ldr x0, [x0]");
+                            self.emit_line(&format!("    str x0, [sp, #{}]", slot * 8));
+                        }
+                        (ArgumentLocation::Indirect(pointer), _) => {
+                            if let ArgumentLocation::Stack(slot) = **pointer {
+                                let copy = stack_bytes + copies[&i];
+                                self.emit_line(&format!("    add x0, sp, #{}", copy));
+                                self.emit_line(&format!("    str x0, [sp, #{}]", slot * 8));
                             }
                         }
+                        _ => {}
                     }
                 }
-            }
-            Expression::Index { array, index } => {
-                // Generate the array base address
-                if let Expression::Identifier(array_name) = array.as_ref() {
-                    if let Some(&base_offset) = self.local_variables.get(array_name) {
-                        // Generate the index expression
-                        self.generate_expression(index)?;
 
-                        // Calculate the array element address: base + index * element_size
-                        match self.target {
-                            Target::Amd64 => {
-                                // Multiply index by 8 (assuming int is 8 bytes for simplicity)
-                                self.emit_line("    imul rax, 8"); // Use imul instead of mul
-                                                                   // Add base address
-                                self.emit_line(&format!("    lea rbx, [rbp + {}]", base_offset));
-                                self.emit_line("    add rax, rbx");
-                                // Load the value at that address
-                                self.emit_line("    mov rax, QWORD PTR [rax]");
-                            }
-                            Target::I386 => {
-                                // Similar for 32-bit
-                                self.emit_line("    imul eax, 4"); // Use imul instead of mul
-                                self.emit_line(&format!("    lea ebx, [ebp + {}]", base_offset));
-                                self.emit_line("    add eax, ebx");
-                                self.emit_line("    mov eax, DWORD PTR [eax]");
-                            }
-                            Target::Arm64 => {
-                                // ARM64 implementation
-                                self.emit_line("    lsl x0, x0, #3"); // multiply by 8
-                                self.emit_line(&format!("    add x1, x29, #{}", base_offset));
-                                self.emit_line("    add x0, x0, x1");
-                                self.emit_line("    ldr x0, [x0]");
-                            }
+                for &(i, _) in floating_args.iter().rev() {
+                    let ty = conversions[i].clone().expect("floating argument");
+                    self.generate_converted(&arguments[i], Some(&ty))?;
+                    self.emit_floating_bits(&ty)?;
+                    self.emit_push("x0");
+                }
+                for (i, pieces) in split_args.iter().rev() {
+                    let bytes = (pieces.len() * 8).next_multiple_of(16);
+                    self.emit_stack_reserve(bytes);
+                    self.emit_pieces_to_stack(&arguments[*i], pieces)?;
+                }
+
+                if direct.is_none() {
+                    self.generate_expression(callee)?;
+                    self.emit_push("x0");
+                }
+                for &(i, _) in computed_args.iter().rev() {
+                    self.generate_argument(&arguments[i], None, parameter(i).as_ref())?;
+                    self.emit_push("x0");
+                }
+                // x0 is set last, as the others are computed in it
+                for &(i, register) in leaf_args.iter().rev() {
+                    self.generate_argument(&arguments[i], None, parameter(i).as_ref())?;
+                    if register > 0 {
+                        self.emit_line(&format!("    mov x{}, x0", register));
+                    }
+                }
+                for &(_, register) in &computed_args {
+                    self.emit_pop(&format!("x{}", register));
+                }
+                if direct.is_none() {
+                    self.emit_pop("x16");
+                }
+
+                for (_, pieces) in &split_args {
+                    for (k, (piece, register)) in pieces.iter().enumerate() {
+                        let prefix = if piece.floating {
+                            fp_prefix(&piece_type(piece))
+                        } else {
+                            'x'
+                        };
+                        self.emit_line(&format!(
+                            "    ldr {}{}, [sp, #{}]",
+                            prefix,
+                            register,
+                            k * 8
+                        ));
+                    }
+                    let bytes = (pieces.len() * 8).next_multiple_of(16);
+                    self.emit_stack_release(bytes);
+                }
+
+                for &(i, register) in &floating_args {
+                    self.emit_pop("x9");
+                    let ty = conversions[i].clone().expect("floating argument");
+                    self.emit_bits_to_floating(&ty, "x9", register)?;
+                }
+
+                for (i, location) in locations.iter().enumerate() {
+                    if let ArgumentLocation::Indirect(pointer) = location {
+                        if let ArgumentLocation::Integer(register) = **pointer {
+                            let copy = stack_bytes + copies[&i];
+                            self.emit_line(&format!("    add x{}, sp, #{}", register, copy));
                         }
-                    } else {
-                        return Err(AleccError::CodegenError {
-                            message: format!("Array '{}' not found", array_name),
-                        });
                     }
-                } else {
-                    return Err(AleccError::CodegenError {
-                        message: "Complex array expressions not yet supported".to_string(),
-                    });
                 }
+                if hidden_pointer {
+                    self.emit_line(&format!("    add x8, sp, #{}", stack_bytes + copy_bytes));
+                }
+            }
+        }
+
+        match &direct {
+            // The callee was left in eax, r11 or x16
+            None => match self.target {
+                Target::I386 => self.emit_line("    call eax"),
+                Target::Amd64 => self.emit_line("    call r11"),
+                Target::Arm64 => self.emit_line("    blr x16"),
+            },
+            Some(name) => {
+                // The builtins that aren't expanded inline call the library
+                let name = library_function(name).unwrap_or(name);
+                let func_name =
+                    &linked_symbol(name, &self.wrapped_symbols, &self.defined_functions);
+                match self.target {
+                    Target::I386 => {
+                        self.emit_line(&format!("    call {}", func_name));
+                    }
+                    Target::Amd64 if self.binding.through_got(name) => {
+                        self.emit_line(&format!("    call {}@PLT", func_name));
+                    }
+                    Target::Amd64 => {
+                        self.emit_line(&format!("    call {}", func_name));
+                    }
+                    Target::Arm64 => {
+                        self.emit_line(&format!("    bl {}", func_name));
+                    }
+                }
+            }
+        }
+
+        // Clean up stack for arguments that were pushed
+        match self.target {
+            Target::I386 => {
+                // A float result comes back in st(0), and is moved to
+                // xmm0 even when unused so the x87 stack stays empty
+                if let Some(ty) = return_type.as_ref().and_then(|ty| self.floating(ty)) {
+                    self.uses_floating_point = true;
+                    let (size, mov, width) = floating_operand(&ty);
+                    self.emit_stack_reserve(size);
+                    self.emit_line(&format!("    fstp {} PTR [esp]", width));
+                    self.emit_line(&format!("    {} xmm0, {} PTR [esp]", mov, width));
+                    self.emit_stack_release(size);
+                }
+                // The callee popped the hidden pointer; its word is taken
+                // back, as GCC does, to be freed with the arguments
+                let mut stack_cleanup: usize = pushed.iter().sum();
+                if hidden_pointer {
+                    self.record_stack_move(-4);
+                    self.emit_stack_reserve(4);
+                    stack_cleanup += 4;
+                }
+                if stack_cleanup > 0 {
+                    self.emit_stack_release(stack_cleanup);
+                }
+            }
+            Target::Amd64 => {
+                // Clean up stack using stored cleanup size
+                if self.last_call_stack_cleanup > 0 {
+                    self.emit_stack_release(self.last_call_stack_cleanup);
+                }
+                if variadic && self.alloca_bottom.is_some() {
+                    self.emit_stack_release(8);
+                    self.emit_pop("rcx");
+                    self.emit_line("    add rsp, rcx");
+                } else if variadic {
+                    self.emit_stack_release(8);
+                    self.emit_pop("rsp");
+                }
+            }
+            Target::Arm64 => {
+                let stack_bytes = arm64_stack_argument_bytes(stack_args) + copy_bytes;
+                if stack_bytes > 0 {
+                    self.emit_stack_release(stack_bytes);
+                }
+            }
+        }
+
+        if let Some((_, AggregatePassing::Registers(pieces))) = &returned {
+            self.emit_returned_pieces(pieces);
+        }
+        // Only the low bytes of a narrow result are defined
+        self.emit_extension(return_type.as_ref());
+        if result_bytes > 0 {
+            match self.target {
+                Target::I386 => self.emit_line("    mov eax, esp"),
+                Target::Amd64 => self.emit_line("    mov rax, rsp"),
+                Target::Arm64 => self.emit_line("    mov x0, sp"),
+            }
+        }
+        Ok(result_bytes)
+    }
+
+    /// Copy the struct or union `value` of `size` bytes to `offset` bytes
+    /// above the stack pointer
+    fn emit_aggregate_to_stack(
+        &mut self,
+        value: &Expression,
+        size: usize,
+        offset: usize,
+    ) -> Result<()> {
+        let spilled = self.emit_aggregate_address(value)?;
+        match self.target {
+            Target::I386 => {
+                self.emit_line("    mov ecx, eax");
+                self.emit_line(&format!("    lea edx, [esp + {}]", spilled + offset));
+            }
+            Target::Amd64 => {
+                self.emit_line("    mov r11, rax");
+                self.emit_line(&format!("    lea r10, [rsp + {}]", spilled + offset));
+            }
+            Target::Arm64 => {
+                self.emit_line("    mov x10, x0");
+                self.emit_line(&format!("    add x11, sp, #{}", spilled + offset));
+            }
+        }
+        self.emit_copy(size);
+        self.emit_release(spilled);
+        Ok(())
+    }
+
+    /// Put the pieces of the struct or union `value` in consecutive words from
+    /// the stack pointer up, to be loaded into their registers just before the call
+    fn emit_pieces_to_stack(
+        &mut self,
+        value: &Expression,
+        pieces: &[(Piece, usize)],
+    ) -> Result<()> {
+        let spilled = self.emit_aggregate_address(value)?;
+        match self.target {
+            Target::Arm64 => self.emit_line("    mov x10, x0"),
+            _ => self.emit_line("    mov r11, rax"),
+        }
+        for (k, (piece, _)) in pieces.iter().enumerate() {
+            self.emit_load_bytes(piece.offset, piece.size);
+            match self.target {
+                Target::Arm64 => self.emit_line(&format!("    str x0, [sp, #{}]", spilled + k * 8)),
+                _ => self.emit_line(&format!(
+                    "    mov QWORD PTR [rsp + {}], rax",
+                    spilled + k * 8
+                )),
+            }
+        }
+        self.emit_release(spilled);
+        Ok(())
+    }
+
+    /// Store a struct or union returned in registers to the temporary at the
+    /// stack pointer: the integer pieces come in rax and rdx (x0 and x1), the
+    /// floating ones in xmm0 and xmm1 (or up to four s or d registers)
+    fn emit_returned_pieces(&mut self, pieces: &[Piece]) {
+        let (mut integers, mut floats) = (0, 0);
+        for piece in pieces {
+            if piece.floating {
+                self.emit_store_floating(
+                    &piece_type(piece),
+                    floats,
+                    self.stack_pointer(),
+                    piece.offset as i32,
+                );
+                floats += 1;
+            } else {
+                let register = match self.target {
+                    Target::Arm64 => ["x0", "x1"][integers],
+                    _ => ["rax", "rdx"][integers],
+                };
+                self.emit_store_word(register, self.stack_pointer(), piece.offset as i32);
+                integers += 1;
+            }
+        }
+    }
+
+    /// Load a struct or union to be returned, whose address is in r11 (x10 on
+    /// arm64), into the registers `pieces` go in. The floating pieces are
+    /// loaded first, and the piece in the result register last.
+    fn emit_return_pieces(&mut self, pieces: &[Piece]) {
+        for (floats, piece) in pieces.iter().filter(|piece| piece.floating).enumerate() {
+            let ty = piece_type(piece);
+            match self.target {
+                Target::Arm64 => self.emit_line(&format!(
+                    "    ldr {}{}, [x10, #{}]",
+                    fp_prefix(&ty),
+                    floats,
+                    piece.offset
+                )),
+                _ => {
+                    let (_, mov, width) = floating_operand(&ty);
+                    self.emit_line(&format!(
+                        "    {} xmm{}, {} PTR [r11 + {}]",
+                        mov, floats, width, piece.offset
+                    ));
+                }
+            }
+        }
+        let integers: Vec<&Piece> = pieces.iter().filter(|piece| !piece.floating).collect();
+        for (index, piece) in integers.iter().enumerate().rev() {
+            self.emit_load_bytes(piece.offset, piece.size);
+            if index == 1 {
+                match self.target {
+                    Target::Arm64 => self.emit_line("    mov x1, x0"),
+                    _ => self.emit_line("    mov rdx, rax"),
+                }
+            }
+        }
+    }
+
+    fn stack_pointer(&self) -> &'static str {
+        match self.target {
+            Target::I386 => "esp",
+            Target::Amd64 => "rsp",
+            Target::Arm64 => "sp",
+        }
+    }
+
+    /// Load the `size` bytes at `offset` from the address in r11 (x10 on
+    /// arm64) into the result register, zero-extended. Sizes that aren't a
+    /// power of two are put together in pieces, through r10 (x12).
+    fn emit_load_bytes(&mut self, offset: usize, size: usize) {
+        for (index, (at, width)) in chunks(size, 8).into_iter().enumerate() {
+            let address = offset + at;
+            match self.target {
+                Target::Arm64 => {
+                    let (register, narrow) = if index == 0 {
+                        ("x0", "w0")
+                    } else {
+                        ("x12", "w12")
+                    };
+                    let line = match width {
+                        8 => format!("ldr {}, [x10, #{}]", register, address),
+                        4 => format!("ldr {}, [x10, #{}]", narrow, address),
+                        2 => format!("ldrh {}, [x10, #{}]", narrow, address),
+                        _ => format!("ldrb {}, [x10, #{}]", narrow, address),
+                    };
+                    self.emit_line(&format!("    {}", line));
+                    if index > 0 {
+                        self.emit_line(&format!("    orr x0, x0, x12, lsl #{}", at * 8));
+                    }
+                }
+                _ => {
+                    let (register, narrow) = if index == 0 {
+                        ("rax", "eax")
+                    } else {
+                        ("r10", "r10d")
+                    };
+                    let line = match width {
+                        8 => format!("mov {}, QWORD PTR [r11 + {}]", register, address),
+                        4 => format!("mov {}, DWORD PTR [r11 + {}]", narrow, address),
+                        2 => format!("movzx {}, WORD PTR [r11 + {}]", narrow, address),
+                        _ => format!("movzx {}, BYTE PTR [r11 + {}]", narrow, address),
+                    };
+                    self.emit_line(&format!("    {}", line));
+                    if index > 0 {
+                        self.emit_line(&format!("    shl r10, {}", at * 8));
+                        self.emit_line("    or rax, r10");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy `size` bytes from the address in r11 to the one in r10 (x10 to x11
+    /// on arm64, ecx to edx on i386), through rax (x12)
+    fn emit_copy(&mut self, size: usize) {
+        for (at, width) in chunks(size, self.target.pointer_size()) {
+            match self.target {
+                Target::Arm64 => {
+                    let (load, store, register) = match width {
+                        8 => ("ldr", "str", "x12"),
+                        4 => ("ldr", "str", "w12"),
+                        2 => ("ldrh", "strh", "w12"),
+                        _ => ("ldrb", "strb", "w12"),
+                    };
+                    self.emit_line(&format!("    {} {}, [x10, #{}]", load, register, at));
+                    self.emit_line(&format!("    {} {}, [x11, #{}]", store, register, at));
+                }
+                _ => {
+                    let (register, width) = match width {
+                        8 => ("rax", "QWORD"),
+                        4 => ("eax", "DWORD"),
+                        2 => ("ax", "WORD"),
+                        _ => ("al", "BYTE"),
+                    };
+                    let (source, destination) = match self.target {
+                        Target::I386 => ("ecx", "edx"),
+                        _ => ("r11", "r10"),
+                    };
+                    self.emit_line(&format!(
+                        "    mov {}, {} PTR [{} + {}]",
+                        register, width, source, at
+                    ));
+                    self.emit_line(&format!(
+                        "    mov {} PTR [{} + {}], {}",
+                        width, destination, at, register
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Leave the address of the struct or union `expr` evaluates to in the
+    /// result register: the object an lvalue designates, or the temporary a
+    /// call's result went to. Returns the bytes to release once it has been
+    /// read, like `emit_object_address`
+    fn emit_aggregate_address(&mut self, expr: &Expression) -> Result<usize> {
+        match &expr.kind {
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => self.emit_call(function, arguments),
+            ExpressionKind::Member { .. } => self.emit_object_address(expr),
+            ExpressionKind::Assignment {
+                target,
+                operator: AssignmentOperator::Assign,
+                value,
+            } => {
+                self.generate_aggregate_assignment(target, value)?;
+                self.emit_lvalue_address(target)?;
+                Ok(0)
+            }
+            _ if expr.is_lvalue() => {
+                self.emit_lvalue_address(expr)?;
+                Ok(0)
+            }
+            _ => Err(AleccError::CodegenError {
+                message: "struct or union value of this kind of expression is not supported"
+                    .to_string(),
+                span: Some(expr.span),
+            }),
+        }
+    }
+
+    /// Copy the struct or union `value` to the object `target` designates
+    fn generate_aggregate_assignment(
+        &mut self,
+        target: &Expression,
+        value: &Expression,
+    ) -> Result<()> {
+        let size = self
+            .expression_type(target)
+            .map_or(0, |ty| self.layout.size_of(&ty));
+        // The target's address waits on the stack while the value is computed
+        self.emit_lvalue_address(target)?;
+        match self.target {
+            Target::I386 => self.emit_push("eax"),
+            Target::Amd64 => self.emit_push("rax"),
+            Target::Arm64 => self.emit_push("x0"),
+        }
+        let spilled = self.emit_aggregate_address(value)?;
+        match self.target {
+            Target::I386 => {
+                self.emit_line("    mov ecx, eax");
+                self.emit_line(&format!("    mov edx, DWORD PTR [esp + {}]", spilled));
+            }
+            Target::Amd64 => {
+                self.emit_line("    mov r11, rax");
+                self.emit_line(&format!("    mov r10, QWORD PTR [rsp + {}]", spilled));
+            }
+            Target::Arm64 => {
+                self.emit_line("    mov x10, x0");
+                self.emit_line(&format!("    ldr x11, [sp, #{}]", spilled));
+            }
+        }
+        self.emit_copy(size);
+        self.emit_release(spilled);
+        match self.target {
+            Target::I386 => self.emit_stack_release(4),
+            Target::Amd64 => self.emit_stack_release(8),
+            Target::Arm64 => self.emit_stack_release(16),
+        }
+        Ok(())
+    }
+
+    /// Leave the address of the object an lvalue designates in the result register
+    fn emit_lvalue_address(&mut self, expr: &Expression) -> Result<()> {
+        self.emit_lvalue_address_unlocated(expr)
+            .map_err(|error| error.at(expr.span))
+    }
+
+    fn emit_lvalue_address_unlocated(&mut self, expr: &Expression) -> Result<()> {
+        match &expr.kind {
+            ExpressionKind::Identifier(name) => match (self.frame_offset(name), self.target) {
+                (Some(offset), Target::I386) => {
+                    self.emit_line(&format!("    lea eax, [ebp + {}]", offset))
+                }
+                (Some(offset), Target::Amd64) => {
+                    self.emit_line(&format!("    lea rax, [rbp + {}]", offset))
+                }
+                (Some(offset), Target::Arm64) => {
+                    self.emit_line(&format!("    add x0, x29, #{}", offset))
+                }
+                (None, _)
+                    if !self.global_variables.contains_key(name)
+                        && self.return_types.contains_key(name) =>
+                {
+                    self.emit_function_address(name)
+                }
+                (None, _) if !self.global_variables.contains_key(name) => {
+                    return Err(AleccError::CodegenError {
+                        message: format!("Undefined variable: {}", name),
+                        span: None,
+                    });
+                }
+                (None, _) => self.emit_symbol_address(name, name, "eax", "rax", "x0"),
+            },
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => {
+                // The pointer's value is the address
+                self.generate_expression(operand)?;
+            }
+            ExpressionKind::Index { array, index } => {
+                // a[i] is *(a + i), whichever operand is the pointer
+                let (base, offset) = match self.expression_type(array) {
+                    Some(ty) if self.pointee(&ty).is_some() => (array, index),
+                    _ => (index, array),
+                };
+                let element = self
+                    .expression_type(base)
+                    .and_then(|ty| self.pointee(&ty))
+                    .ok_or_else(|| AleccError::CodegenError {
+                        message: "subscripted value is not an array or pointer".to_string(),
+                        span: None,
+                    })?;
+                // Arithmetic on void pointers counts bytes
+                let size = self.layout.size_of(&element).max(1);
+
+                // Arrays decay to their address, so this is the base either way
+                self.generate_expression(base)?;
+                self.emit_element_address(offset, size)?;
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let aggregate = if *is_arrow {
+                    // The pointer's value is the struct's address
+                    self.generate_expression(object)?;
+                    self.expression_type(object)
+                        .and_then(|ty| self.pointee(&ty))
+                } else {
+                    self.emit_lvalue_address(object)?;
+                    self.expression_type(object)
+                };
+                self.emit_member_offset(aggregate, member)?;
+            }
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: "expression is not an lvalue".to_string(),
+                    span: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply `operator` to the left operand in the result register and the
+    /// right one beside it (`ebx`, `rbx` or `x1`), leaving the result in the
+    /// result register. `unsigned` picks unsigned division, comparison and
+    /// right shift.
+    fn emit_binary_operator(&mut self, operator: &BinaryOperator, unsigned: bool) -> Result<()> {
+        match self.target {
+            Target::I386 | Target::Amd64 => {
+                let (a, b, c, d) = match self.target {
+                    Target::I386 => ("eax", "ebx", "ecx", "edx"),
+                    _ => ("rax", "rbx", "rcx", "rdx"),
+                };
+                match operator {
+                    BinaryOperator::Add => self.emit_line(&format!("    add {}, {}", a, b)),
+                    BinaryOperator::Subtract => self.emit_line(&format!("    sub {}, {}", a, b)),
+                    BinaryOperator::Multiply => self.emit_line(&format!("    imul {}, {}", a, b)),
+                    BinaryOperator::Divide | BinaryOperator::Modulo => {
+                        if unsigned {
+                            self.emit_line("    xor edx, edx");
+                            self.emit_line(&format!("    div {}", b));
+                        } else {
+                            // Sign extend the dividend into edx:eax or rdx:rax
+                            let extend = if self.target == Target::I386 {
+                                "cdq"
+                            } else {
+                                "cqo"
+                            };
+                            self.emit_line(&format!("    {}", extend));
+                            self.emit_line(&format!("    idiv {}", b));
+                        }
+                        if matches!(operator, BinaryOperator::Modulo) {
+                            self.emit_line(&format!("    mov {}, {}", a, d)); // Remainder
+                        }
+                    }
+                    BinaryOperator::Equal
+                    | BinaryOperator::NotEqual
+                    | BinaryOperator::Less
+                    | BinaryOperator::Greater
+                    | BinaryOperator::LessEqual
+                    | BinaryOperator::GreaterEqual => {
+                        let (condition, _) = condition_codes(operator, unsigned);
+                        self.emit_line(&format!("    cmp {}, {}", a, b));
+                        self.emit_line(&format!("    set{} al", condition));
+                        self.emit_line(&format!("    movzx {}, al", a));
+                    }
+                    BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                        let combine = if matches!(operator, BinaryOperator::LogicalAnd) {
+                            "and"
+                        } else {
+                            "or"
+                        };
+                        self.emit_line(&format!("    test {}, {}", a, a));
+                        self.emit_line("    setne al");
+                        self.emit_line(&format!("    test {}, {}", b, b));
+                        self.emit_line("    setne bl");
+                        self.emit_line(&format!("    {} al, bl", combine));
+                        self.emit_line(&format!("    movzx {}, al", a));
+                    }
+                    BinaryOperator::BitwiseAnd => self.emit_line(&format!("    and {}, {}", a, b)),
+                    BinaryOperator::BitwiseOr => self.emit_line(&format!("    or {}, {}", a, b)),
+                    BinaryOperator::BitwiseXor => self.emit_line(&format!("    xor {}, {}", a, b)),
+                    BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                        let shift = match operator {
+                            BinaryOperator::LeftShift => "shl",
+                            _ if unsigned => "shr",
+                            _ => "sar",
+                        };
+                        self.emit_line(&format!("    mov {}, {}", c, b)); // Shift count in cl
+                        self.emit_line(&format!("    {} {}, cl", shift, a));
+                    }
+                }
+            }
+            Target::Arm64 => {
+                match operator {
+                    BinaryOperator::Add => self.emit_line("    add x0, x0, x1"),
+                    BinaryOperator::Subtract => self.emit_line("    sub x0, x0, x1"),
+                    BinaryOperator::Multiply => self.emit_line("    mul x0, x0, x1"),
+                    BinaryOperator::Divide | BinaryOperator::Modulo => {
+                        let divide = if unsigned { "udiv" } else { "sdiv" };
+                        if matches!(operator, BinaryOperator::Divide) {
+                            self.emit_line(&format!("    {} x0, x0, x1", divide));
+                        } else {
+                            self.emit_line(&format!("    {} x2, x0, x1", divide)); // x2 = x0 / x1
+                            self.emit_line("    msub x0, x2, x1, x0"); // x0 = x0 - (x2 * x1)
+                        }
+                    }
+                    BinaryOperator::Equal
+                    | BinaryOperator::NotEqual
+                    | BinaryOperator::Less
+                    | BinaryOperator::Greater
+                    | BinaryOperator::LessEqual
+                    | BinaryOperator::GreaterEqual => {
+                        let (_, condition) = condition_codes(operator, unsigned);
+                        self.emit_line("    cmp x0, x1");
+                        self.emit_line(&format!("    cset x0, {}", condition));
+                    }
+                    BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                        let combine = if matches!(operator, BinaryOperator::LogicalAnd) {
+                            "and"
+                        } else {
+                            "orr"
+                        };
+                        self.emit_line("    cmp x0, #0");
+                        self.emit_line("    cset x0, ne");
+                        self.emit_line("    cmp x1, #0");
+                        self.emit_line("    cset x1, ne");
+                        self.emit_line(&format!("    {} x0, x0, x1", combine));
+                    }
+                    BinaryOperator::BitwiseAnd => self.emit_line("    and x0, x0, x1"),
+                    BinaryOperator::BitwiseOr => self.emit_line("    orr x0, x0, x1"),
+                    BinaryOperator::BitwiseXor => self.emit_line("    eor x0, x0, x1"),
+                    BinaryOperator::LeftShift => self.emit_line("    lsl x0, x0, x1"),
+                    BinaryOperator::RightShift if unsigned => self.emit_line("    lsr x0, x0, x1"),
+                    BinaryOperator::RightShift => self.emit_line("    asr x0, x0, x1"),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an integer operand of type `ty` makes an operation unsigned:
+    /// unsigned types that don't promote to `int`, and pointers
+    fn unsigned_operand(&self, ty: Option<&Type>) -> bool {
+        ty.is_some_and(|ty| {
+            let ty = self.layout.resolve(ty);
+            (ty.is_unsigned() && self.layout.size_of(&ty) >= self.layout.size_of(&Type::Int))
+                || self.pointee(&ty).is_some()
+        })
+    }
+
+    /// `target op= value` on any lvalue: the address is computed once and kept
+    /// on the stack while `value` is evaluated, and the new value is left in
+    /// the result register. Pointers step by the size of what they point to.
+    fn generate_compound_assignment(
+        &mut self,
+        target: &Expression,
+        operator: &AssignmentOperator,
+        value: &Expression,
+    ) -> Result<()> {
+        let operator = operator
+            .binary_operator()
+            .expect("plain assignment is not compound");
+        let floating = self.floating_type(target);
+        if let Some(common) = common_floating(floating.clone(), self.floating_type(value)) {
+            return self.generate_floating_compound_assignment(
+                target, &operator, value, floating, &common,
+            );
+        }
+        let ty = self.expression_type(target);
+        let pointer = ty.as_ref().is_some_and(|ty| self.pointee(ty).is_some());
+        let wide = match operator {
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => self.wide_type(target),
+            _ => common_wide(self.wide_type(target), self.wide_type(value)),
+        };
+        if let Some(common) = wide.filter(|_| !pointer) {
+            return self.generate_wide_compound_assignment(target, &operator, value, &common);
+        }
+        let step = ty
+            .as_ref()
+            .and_then(|ty| match self.layout.resolve(ty) {
+                Type::Pointer(inner) => Some(self.layout.size_of(&inner).max(1)),
+                _ => None,
+            })
+            .filter(|&step| {
+                step > 1 && matches!(operator, BinaryOperator::Add | BinaryOperator::Subtract)
+            });
+
+        self.emit_lvalue_address(target)?;
+        match self.target {
+            Target::I386 => {
+                self.emit_push("eax");
+                self.emit_load(ty.as_ref(), "[eax]");
+                self.emit_push("eax");
+            }
+            Target::Amd64 => {
+                self.emit_push("rax");
+                self.emit_load(ty.as_ref(), "[rax]");
+                self.emit_push("rax");
+            }
+            Target::Arm64 => {
+                self.emit_push("x0");
+                self.emit_load(ty.as_ref(), "[x0]");
+                self.emit_push("x0");
+            }
+        }
+        self.generate_expression(value)?;
+
+        // The current value goes in the result register and `value` beside it
+        match self.target {
+            Target::I386 => {
+                if let Some(step) = step {
+                    self.emit_line(&format!("    imul eax, {}", step));
+                }
+                self.emit_line("    mov ebx, eax");
+                self.emit_pop("eax");
+            }
+            Target::Amd64 => {
+                if let Some(step) = step {
+                    self.emit_line(&format!("    imul rax, {}", step));
+                }
+                self.emit_line("    mov rbx, rax");
+                self.emit_pop("rax");
+            }
+            Target::Arm64 => {
+                if let Some(step) = step {
+                    self.emit_line(&format!("    mov x9, #{}", step));
+                    self.emit_line("    mul x0, x0, x9");
+                }
+                self.emit_line("    mov x1, x0");
+                self.emit_pop("x0");
+            }
+        }
+        let unsigned = match operator {
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                self.unsigned_operand(ty.as_ref())
+            }
+            _ => {
+                self.unsigned_operand(ty.as_ref())
+                    || self.unsigned_operand(self.expression_type(value).as_ref())
+            }
+        };
+        self.emit_binary_operator(&operator, unsigned)?;
+        self.emit_integer_conversion(ty.as_ref());
+        match self.target {
+            Target::I386 => {
+                self.emit_pop("ecx");
+                self.emit_store(ty.as_ref(), "[ecx]");
+            }
+            Target::Amd64 => {
+                self.emit_pop("r11");
+                self.emit_store(ty.as_ref(), "[r11]");
+            }
+            Target::Arm64 => {
+                self.emit_pop("x9");
+                self.emit_store(ty.as_ref(), "[x9]");
+            }
+        }
+        Ok(())
+    }
+
+    /// `++`/`--` on the `ty` whose address is in the result register, leaving the
+    /// new (prefix) or old (postfix) value there
+    fn emit_increment(&mut self, operator: &UnaryOperator, ty: Option<&Type>) -> Result<()> {
+        if let Some(floating) = ty.and_then(|ty| self.floating(ty)) {
+            return self.emit_floating_increment(operator, &floating);
+        }
+        if ty.and_then(|ty| self.wide_integer(ty)).is_some() {
+            return self.emit_wide_increment(operator, ty);
+        }
+        let (step, postfix) = match operator {
+            UnaryOperator::PreIncrement => ("inc", false),
+            UnaryOperator::PostIncrement => ("inc", true),
+            UnaryOperator::PreDecrement => ("dec", false),
+            UnaryOperator::PostDecrement => ("dec", true),
+            _ => unreachable!("not an increment operator"),
+        };
+        let width = match self.access_size(ty) {
+            1 => "BYTE",
+            2 => "WORD",
+            4 => "DWORD",
+            _ => "QWORD",
+        };
+        match self.target {
+            Target::I386 => {
+                self.emit_line("    mov ecx, eax");
+                if postfix {
+                    self.emit_load(ty, "[ecx]");
+                    self.emit_line(&format!("    {} {} PTR [ecx]", step, width));
+                } else {
+                    self.emit_line(&format!("    {} {} PTR [ecx]", step, width));
+                    self.emit_load(ty, "[ecx]");
+                }
+            }
+            Target::Amd64 => {
+                self.emit_line("    mov r11, rax");
+                if postfix {
+                    self.emit_load(ty, "[r11]");
+                    self.emit_line(&format!("    {} {} PTR [r11]", step, width));
+                } else {
+                    self.emit_line(&format!("    {} {} PTR [r11]", step, width));
+                    self.emit_load(ty, "[r11]");
+                }
+            }
+            Target::Arm64 => {
+                let (instruction, undo) = if step == "inc" {
+                    ("add", "sub")
+                } else {
+                    ("sub", "add")
+                };
+                self.emit_line("    mov x9, x0");
+                self.emit_load(ty, "[x9]");
+                self.emit_line(&format!("    {} x0, x0, #1", instruction));
+                self.emit_store(ty, "[x9]");
+                if postfix {
+                    self.emit_line(&format!("    {} x0, x0, #1", undo));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_va_list(var_type: &Type) -> bool {
+        match var_type {
+            Type::VaList => true,
+            Type::Typedef(_, aliased) | Type::Qualified(_, aliased) => Self::is_va_list(aliased),
+            _ => false,
+        }
+    }
+
+    /// Leave the address of the `va_list` object named by `list` in the result register.
+    fn emit_va_list_address(&mut self, list: &Expression) -> Result<()> {
+        if let ExpressionKind::Identifier(name) = &list.kind {
+            if let Some(&offset) = self.local_variables.get(name) {
+                match self.target {
+                    Target::I386 => self.emit_line(&format!("    lea eax, [ebp + {}]", offset)),
+                    Target::Amd64 => self.emit_line(&format!("    lea rax, [rbp + {}]", offset)),
+                    Target::Arm64 => self.emit_line(&format!("    add x0, x29, #{}", offset)),
+                }
+                return Ok(());
+            }
+
+            if let Some(&(_, offset)) = self
+                .current_function_params
+                .iter()
+                .find(|(param_name, _)| param_name == name)
+            {
+                match self.target {
+                    // A va_list parameter has decayed to a pointer to the caller's object
+                    Target::Amd64 => {
+                        self.emit_line(&format!("    mov rax, QWORD PTR [rbp + {}]", offset))
+                    }
+                    Target::I386 => self.emit_line(&format!("    lea eax, [ebp + {}]", offset)),
+                    Target::Arm64 => self.emit_line(&format!("    add x0, x29, #{}", offset)),
+                }
+                return Ok(());
+            }
+        }
+
+        if self.target == Target::Amd64 {
+            return self.generate_expression(list);
+        }
+
+        Err(AleccError::CodegenError {
+            message: "va_list operand must be a variable".to_string(),
+            span: None,
+        })
+    }
+
+    fn generate_va_start(&mut self, arguments: &[Expression]) -> Result<()> {
+        let list = arguments.first().ok_or_else(|| AleccError::CodegenError {
+            message: "va_start requires a va_list argument".to_string(),
+            span: None,
+        })?;
+        if !self.is_variadic {
+            return Err(AleccError::CodegenError {
+                message: "va_start used in a function without variable arguments".to_string(),
+                span: None,
+            });
+        }
+        let integers = self.parameter_layout.integers as i32;
+        let floats = self.parameter_layout.floats as i32;
+        let on_stack = self.parameter_layout.stack_slots as i32;
+
+        match self.target {
+            Target::I386 => {
+                // Variadic arguments follow the last named one on the caller's stack
+                self.emit_va_list_address(list)?;
+                // The parameter copies are laid out like the caller's pushes
+                let named_bytes = self
+                    .current_function_params
+                    .last()
+                    .map_or(0, |&(_, offset)| -offset);
+                self.emit_line(&format!("    lea ecx, [ebp + {}]", 8 + named_bytes));
+                self.emit_line("    mov DWORD PTR [eax], ecx");
+            }
+            Target::Amd64 => {
+                let gp_offset = integers * 8;
+                let fp_offset = 48 + floats * 16;
+                let overflow = 16 + on_stack * 8;
+
+                self.emit_va_list_address(list)?;
+                self.emit_line(&format!("    mov DWORD PTR [rax], {}", gp_offset));
+                self.emit_line(&format!("    mov DWORD PTR [rax + 4], {}", fp_offset));
+                self.emit_line(&format!("    lea r10, [rbp + {}]", overflow));
+                self.emit_line("    mov QWORD PTR [rax + 8], r10");
+                self.emit_line(&format!("    lea r10, [rbp - {}]", FRAME_SIZE_PLACEHOLDER));
+                self.emit_line("    mov QWORD PTR [rax + 16], r10");
+            }
+            Target::Arm64 => {
+                let gr_offs = -(8 - integers) * 8;
+                let vr_offs = -(8 - floats) * 16;
+                let stack = 16 + on_stack * 8;
+
+                self.emit_va_list_address(list)?;
+                self.emit_line(&format!("    add x10, x29, #{}", stack));
+                self.emit_line("    str x10, [x0]");
+                self.emit_line(&format!("    sub x11, x29, #{}", FRAME_SIZE_PLACEHOLDER));
+                self.emit_line("    add x10, x11, #192");
+                self.emit_line("    str x10, [x0, #8]");
+                self.emit_line("    add x10, x11, #128");
+                self.emit_line("    str x10, [x0, #16]");
+                self.emit_line(&format!("    mov w10, #{}", gr_offs));
+                self.emit_line("    str w10, [x0, #24]");
+                self.emit_line(&format!("    mov w10, #{}", vr_offs));
+                self.emit_line("    str w10, [x0, #28]");
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_va_copy(&mut self, arguments: &[Expression]) -> Result<()> {
+        if arguments.len() != 2 {
+            return Err(AleccError::CodegenError {
+                message: "va_copy requires two va_list arguments".to_string(),
+                span: None,
+            });
+        }
+
+        self.emit_va_list_address(&arguments[1])?;
+        match self.target {
+            Target::I386 => {
+                self.emit_line("    mov ecx, DWORD PTR [eax]");
+                self.emit_push("ecx");
+                self.emit_va_list_address(&arguments[0])?;
+                self.emit_pop("ecx");
+                self.emit_line("    mov DWORD PTR [eax], ecx");
+            }
+            Target::Amd64 => {
+                self.emit_push("rax");
+                self.emit_va_list_address(&arguments[0])?;
+                self.emit_pop("r10");
+                for offset in [0, 8, 16] {
+                    self.emit_line(&format!("    mov r11, QWORD PTR [r10 + {}]", offset));
+                    self.emit_line(&format!("    mov QWORD PTR [rax + {}], r11", offset));
+                }
+            }
+            Target::Arm64 => {
+                self.emit_push("x0");
+                self.emit_va_list_address(&arguments[0])?;
+                self.emit_pop("x10");
+                self.emit_line("    ldp x11, x12, [x10]");
+                self.emit_line("    stp x11, x12, [x0]");
+                self.emit_line("    ldp x11, x12, [x10, #16]");
+                self.emit_line("    stp x11, x12, [x0, #16]");
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand a call to one of the builtins that don't call the library:
+    /// byte copies and fills with `rep movsb`/`rep stosb`, or a loop of
+    /// 16-byte stores on arm64, and single instructions for the rest
+    fn generate_builtin(&mut self, name: &str, arguments: &[Expression]) -> Result<()> {
+        let parameters = self.parameter_types.get(name).cloned().unwrap_or_default();
+        if arguments.len() < parameters.len() {
+            return Err(AleccError::CodegenError {
+                message: format!("{} requires {} arguments", name, parameters.len()),
+                span: None,
+            });
+        }
+        let builtin = name.trim_start_matches("__builtin_");
+        if builtin == "expect" {
+            // The value is all that matters; the hint is dropped
+            return self.generate_argument(&arguments[0], None, parameters.first());
+        }
+        // Every argument but the last waits on the stack
+        for (i, parameter) in parameters.iter().enumerate() {
+            self.generate_argument(&arguments[i], None, Some(parameter))?;
+            if i + 1 < parameters.len() {
+                match self.target {
+                    Target::I386 => self.emit_push("eax"),
+                    Target::Amd64 => self.emit_push("rax"),
+                    Target::Arm64 => self.emit_push("x0"),
+                }
+            }
+        }
+
+        match (builtin, self.target) {
+            ("unreachable" | "trap", Target::I386 | Target::Amd64) => self.emit_line("    ud2"),
+            ("unreachable" | "trap", Target::Arm64) => self.emit_line("    brk #0x3e8"),
+            ("popcount" | "popcountl" | "popcountll", Target::I386) => {
+                // The 386 has neither popcnt nor bswap: the bits are summed
+                // in pairs, nibbles and bytes instead
+                self.emit_line("    mov ecx, eax");
+                self.emit_line("    shr ecx, 1");
+                self.emit_line("    and ecx, 0x55555555");
+                self.emit_line("    sub eax, ecx");
+                self.emit_line("    mov ecx, eax");
+                self.emit_line("    shr ecx, 2");
+                self.emit_line("    and eax, 0x33333333");
+                self.emit_line("    and ecx, 0x33333333");
+                self.emit_line("    add eax, ecx");
+                self.emit_line("    mov ecx, eax");
+                self.emit_line("    shr ecx, 4");
+                self.emit_line("    add eax, ecx");
+                self.emit_line("    and eax, 0x0f0f0f0f");
+                self.emit_line("    imul eax, eax, 0x01010101");
+                self.emit_line("    shr eax, 24");
+            }
+            ("popcount", Target::Amd64) => self.emit_line("    popcnt eax, eax"),
+            ("popcountl" | "popcountll", Target::Amd64) => self.emit_line("    popcnt rax, rax"),
+            ("popcount" | "popcountl" | "popcountll", Target::Arm64) => {
+                // arm64 only counts bits in vector registers
+                self.emit_line("    fmov d0, x0");
+                self.emit_line("    cnt v0.8b, v0.8b");
+                self.emit_line("    addv b0, v0.8b");
+                self.emit_line("    fmov w0, s0");
+            }
+            // Both are undefined for 0, as bsr and bsf leave it
+            ("clz", Target::I386 | Target::Amd64) | ("clzl" | "clzll", Target::I386) => {
+                self.emit_line("    bsr eax, eax");
+                self.emit_line("    xor eax, 31");
+            }
+            ("clzl" | "clzll", Target::Amd64) => {
+                self.emit_line("    bsr rax, rax");
+                self.emit_line("    xor eax, 63");
+            }
+            ("ctz", Target::I386 | Target::Amd64) | ("ctzl" | "ctzll", Target::I386) => {
+                self.emit_line("    bsf eax, eax")
+            }
+            ("ctzl" | "ctzll", Target::Amd64) => self.emit_line("    bsf rax, rax"),
+            ("clz", Target::Arm64) => self.emit_line("    clz w0, w0"),
+            ("clzl" | "clzll", Target::Arm64) => self.emit_line("    clz x0, x0"),
+            ("ctz", Target::Arm64) => {
+                self.emit_line("    rbit w0, w0");
+                self.emit_line("    clz w0, w0");
+            }
+            ("ctzl" | "ctzll", Target::Arm64) => {
+                self.emit_line("    rbit x0, x0");
+                self.emit_line("    clz x0, x0");
+            }
+            ("bswap16", Target::I386 | Target::Amd64) => self.emit_line("    rol ax, 8"),
+            ("bswap16", Target::Arm64) => self.emit_line("    rev16 w0, w0"),
+            ("bswap32", Target::I386) => {
+                self.emit_line("    xchg al, ah");
+                self.emit_line("    rol eax, 16");
+                self.emit_line("    xchg al, ah");
+            }
+            ("bswap32", Target::Amd64) => self.emit_line("    bswap eax"),
+            ("bswap32", Target::Arm64) => self.emit_line("    rev w0, w0"),
+            ("bswap64", Target::Amd64) => self.emit_line("    bswap rax"),
+            ("bswap64", Target::Arm64) => self.emit_line("    rev x0, x0"),
+            ("bswap64", Target::I386) => {
+                return Err(AleccError::CodegenError {
+                    message: "__builtin_bswap64 is not supported on i386".to_string(),
+                    span: None,
+                });
+            }
+            ("alloca", _) => self.emit_alloca()?,
+            ("stack_save", _) => {
+                let bottom = self.alloca_bottom_slot()?;
+                match self.target {
+                    Target::I386 => {
+                        self.emit_line(&format!("    mov eax, DWORD PTR [ebp + {}]", bottom))
+                    }
+                    Target::Amd64 => {
+                        self.emit_line(&format!("    mov rax, QWORD PTR [rbp + {}]", bottom))
+                    }
+                    Target::Arm64 => self.emit_line(&format!("    ldr x0, [x29, #{}]", bottom)),
+                }
+            }
+            ("stack_restore", _) => self.emit_stack_restore()?,
+            ("memcpy" | "memset", Target::I386) => {
+                // esi and edi belong to the caller
+                let fill = builtin == "memset";
+                self.emit_line("    mov ecx, eax");
+                self.emit_pop("eax");
+                self.emit_pop("edx");
+                self.emit_push("edi");
+                self.emit_line("    mov edi, edx");
+                if fill {
+                    self.emit_line("    rep stosb");
+                } else {
+                    self.emit_push("esi");
+                    self.emit_line("    mov esi, eax");
+                    self.emit_line("    rep movsb");
+                    self.emit_pop("esi");
+                }
+                self.emit_pop("edi");
+                self.emit_line("    mov eax, edx");
+            }
+            ("memcpy" | "memset", Target::Amd64) => {
+                self.emit_line("    mov rcx, rax");
+                if builtin == "memset" {
+                    self.emit_pop("rax");
+                    self.emit_pop("rdi");
+                    self.emit_line("    mov rdx, rdi");
+                    self.emit_line("    rep stosb");
+                } else {
+                    self.emit_pop("rsi");
+                    self.emit_pop("rdi");
+                    self.emit_line("    mov rdx, rdi");
+                    self.emit_line("    rep movsb");
+                }
+                self.emit_line("    mov rax, rdx");
+            }
+            ("memcpy" | "memset", Target::Arm64) => {
+                let fill = builtin == "memset";
+                let blocks = self.new_label("copy_blocks");
+                let bytes = self.new_label("copy_bytes");
+                let done = self.new_label("copy_done");
+                self.emit_line("    mov x2, x0");
+                self.emit_pop("x1");
+                self.emit_pop("x0");
+                if fill {
+                    self.emit_line("    dup v0.16b, w1");
+                }
+                self.emit_line("    mov x9, #0");
+                self.emit_line(&format!("{}:", blocks));
+                self.emit_line("    sub x10, x2, x9");
+                self.emit_line("    cmp x10, #16");
+                self.emit_line(&format!("    b.lo {}", bytes));
+                if !fill {
+                    self.emit_line("    ldr q0, [x1, x9]");
+                }
+                self.emit_line("    str q0, [x0, x9]");
+                self.emit_line("    add x9, x9, #16");
+                self.emit_line(&format!("    b {}", blocks));
+                self.emit_line(&format!("{}:", bytes));
+                self.emit_line("    cmp x9, x2");
+                self.emit_line(&format!("    b.hs {}", done));
+                if fill {
+                    self.emit_line("    strb w1, [x0, x9]");
+                } else {
+                    self.emit_line("    ldrb w10, [x1, x9]");
+                    self.emit_line("    strb w10, [x0, x9]");
+                }
+                self.emit_line("    add x9, x9, #1");
+                self.emit_line(&format!("    b {}", bytes));
+                self.emit_line(&format!("{}:", done));
+            }
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("{} is not supported", name),
+                    span: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The slot `alloca_bottom` names, which every function that moves the
+    /// stack pointer at run time has
+    fn alloca_bottom_slot(&self) -> Result<i32> {
+        self.alloca_bottom.ok_or_else(|| AleccError::CodegenError {
+            message: format!(
+                "no slot for the end of the temporaries in '{}'",
+                self.current_function
+            ),
+            span: None,
+        })
+    }
+
+    /// `__builtin_alloca` of the size in the result register, rounded up to
+    /// 16 bytes so the stack pointer stays aligned for calls. The temporaries
+    /// the statement has pushed so far, from the stack pointer up to the
+    /// bottom slot, are copied down by that much, and the block freed above
+    /// them is the allocation, whose address is the result. The locals stay
+    /// where the frame pointer finds them, and the epilogue frees the block
+    fn emit_alloca(&mut self) -> Result<()> {
+        let bottom = self.alloca_bottom_slot()?;
+        let copy = self.new_label("alloca_copy");
+        let done = self.new_label("alloca_done");
+        match self.target {
+            Target::Arm64 => {
+                self.emit_line("    add x0, x0, #15");
+                self.emit_line("    and x0, x0, #-16");
+                self.emit_line(&format!("    ldr x9, [x29, #{}]", bottom));
+                self.emit_line("    mov x10, sp");
+                self.emit_line("    sub sp, sp, x0");
+                self.emit_line(&format!("{}:", copy));
+                self.emit_line("    cmp x10, x9");
+                self.emit_line(&format!("    b.hs {}", done));
+                self.emit_line("    ldr x11, [x10]");
+                self.emit_line("    sub x12, x10, x0");
+                self.emit_line("    str x11, [x12]");
+                self.emit_line("    add x10, x10, #8");
+                self.emit_line(&format!("    b {}", copy));
+                self.emit_line(&format!("{}:", done));
+                self.emit_line("    sub x0, x9, x0");
+                self.emit_line(&format!("    str x0, [x29, #{}]", bottom));
+            }
+            _ => {
+                let (size, cursor, word, width, fp) = match self.target {
+                    Target::I386 => ("eax", "ecx", "edx", "DWORD", "ebp"),
+                    _ => ("rax", "rcx", "rdx", "QWORD", "rbp"),
+                };
+                let sp = self.stack_pointer();
+                let slot = format!("{} PTR [{} + {}]", width, fp, bottom);
+                self.emit_line(&format!("    add {}, 15", size));
+                self.emit_line(&format!("    and {}, -16", size));
+                self.emit_line(&format!("    mov {}, {}", cursor, sp));
+                self.emit_line(&format!("    sub {}, {}", sp, size));
+                self.emit_line(&format!("    neg {}", size));
+                self.emit_line(&format!("{}:", copy));
+                self.emit_line(&format!("    cmp {}, {}", cursor, slot));
+                self.emit_line(&format!("    jae {}", done));
+                self.emit_line(&format!("    mov {}, {} PTR [{}]", word, width, cursor));
+                self.emit_line(&format!(
+                    "    mov {} PTR [{} + {}], {}",
+                    width, cursor, size, word
+                ));
+                self.emit_line(&format!(
+                    "    add {}, {}",
+                    cursor,
+                    self.target.pointer_size()
+                ));
+                self.emit_line(&format!("    jmp {}", copy));
+                self.emit_line(&format!("{}:", done));
+                self.emit_line(&format!("    add {}, {}", size, slot));
+                self.emit_line(&format!("    mov {}, {}", slot, size));
+            }
+        }
+        Ok(())
+    }
+
+    /// `__builtin_stack_restore` to the address in the result register,
+    /// which `__builtin_stack_save` gave: everything allocated since is
+    /// freed by copying the temporaries back up to end there
+    fn emit_stack_restore(&mut self) -> Result<()> {
+        let bottom = self.alloca_bottom_slot()?;
+        let copy = self.new_label("restore_copy");
+        let done = self.new_label("restore_done");
+        match self.target {
+            Target::Arm64 => {
+                self.emit_line(&format!("    ldr x9, [x29, #{}]", bottom));
+                self.emit_line("    sub x0, x0, x9");
+                self.emit_line("    mov x10, sp");
+                self.emit_line(&format!("{}:", copy));
+                self.emit_line("    cmp x9, x10");
+                self.emit_line(&format!("    b.ls {}", done));
+                self.emit_line("    sub x9, x9, #8");
+                self.emit_line("    ldr x11, [x9]");
+                self.emit_line("    str x11, [x9, x0]");
+                self.emit_line(&format!("    b {}", copy));
+                self.emit_line(&format!("{}:", done));
+                self.emit_line("    add sp, sp, x0");
+                self.emit_line(&format!("    ldr x9, [x29, #{}]", bottom));
+                self.emit_line("    add x9, x9, x0");
+                self.emit_line(&format!("    str x9, [x29, #{}]", bottom));
+            }
+            _ => {
+                let (distance, cursor, word, width, fp) = match self.target {
+                    Target::I386 => ("eax", "ecx", "edx", "DWORD", "ebp"),
+                    _ => ("rax", "rcx", "rdx", "QWORD", "rbp"),
+                };
+                let sp = self.stack_pointer();
+                let slot = format!("{} PTR [{} + {}]", width, fp, bottom);
+                self.emit_line(&format!("    mov {}, {}", cursor, slot));
+                self.emit_line(&format!("    sub {}, {}", distance, cursor));
+                self.emit_line(&format!("{}:", copy));
+                self.emit_line(&format!("    cmp {}, {}", cursor, sp));
+                self.emit_line(&format!("    jbe {}", done));
+                self.emit_line(&format!(
+                    "    sub {}, {}",
+                    cursor,
+                    self.target.pointer_size()
+                ));
+                self.emit_line(&format!("    mov {}, {} PTR [{}]", word, width, cursor));
+                self.emit_line(&format!(
+                    "    mov {} PTR [{} + {}], {}",
+                    width, cursor, distance, word
+                ));
+                self.emit_line(&format!("    jmp {}", copy));
+                self.emit_line(&format!("{}:", done));
+                self.emit_line(&format!("    add {}, {}", sp, distance));
+                self.emit_line(&format!("    add {}, {}", slot, distance));
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand an `__atomic_*` or `__sync_*` builtin: `lock`ed instructions on
+    /// x86, with a `lock cmpxchg` loop for the bitwise operations, and a loop
+    /// of exclusive loads and stores on arm64. The memory order picks the
+    /// acquire and release forms; x86 only needs more for sequentially
+    /// consistent stores and fences
+    fn generate_atomic(
+        &mut self,
+        name: &str,
+        atomic: Atomic,
+        arguments: &[Expression],
+    ) -> Result<()> {
+        let error = |message: String| AleccError::CodegenError {
+            message,
+            span: arguments.first().map(|argument| argument.span),
+        };
+        // An order that isn't a constant is taken as the strongest
+        let order = atomic
+            .order_argument
+            .and_then(|index| arguments.get(index))
+            .map_or(atomic.order, |argument| {
+                ConstantEvaluator::new()
+                    .with_layout(&self.layout)
+                    .evaluate(argument)
+                    .map_or(MemoryOrder::SeqCst, MemoryOrder::from_value)
+            });
+
+        if atomic.operation == AtomicOperation::Fence {
+            let fence = match (self.target, order) {
+                // A signal handler runs on the same thread, so only the
+                // compiler mustn't reorder, and it doesn't
+                _ if name == "__atomic_signal_fence" => None,
+                (_, MemoryOrder::Relaxed) => None,
+                (Target::I386, MemoryOrder::SeqCst) => Some("lock or DWORD PTR [esp], 0"),
+                (Target::Amd64, MemoryOrder::SeqCst) => Some("mfence"),
+                (Target::I386 | Target::Amd64, _) => None,
+                (Target::Arm64, MemoryOrder::Consume | MemoryOrder::Acquire) => Some("dmb ishld"),
+                (Target::Arm64, _) => Some("dmb ish"),
+            };
+            if let Some(fence) = fence {
+                self.emit_line(&format!("    {}", fence));
+            }
+            return Ok(());
+        }
+
+        let operand = arguments
+            .first()
+            .and_then(|pointer| self.expression_type(pointer))
+            .and_then(|ty| self.pointee(&ty));
+        let (operand, size) = match operand {
+            Some(
+                ty @ (Type::Char
+                | Type::Bool
+                | Type::Short
+                | Type::Int
+                | Type::Long
+                | Type::LongLong
+                | Type::UnsignedChar
+                | Type::UnsignedShort
+                | Type::UnsignedInt
+                | Type::UnsignedLong
+                | Type::UnsignedLongLong
+                | Type::Enum { .. }
+                | Type::Pointer(_)),
+            ) if self.layout.size_of(&ty) <= self.target.pointer_size() => {
+                let size = self.layout.size_of(&ty);
+                (ty, size)
+            }
+            _ => {
+                return Err(error(format!(
+                    "the first argument of '{}' must point to an integer or pointer",
+                    name
+                )))
+            }
+        };
+
+        // The pointers come first, then the values, converted to the operand
+        let zero = Expression::new(ExpressionKind::IntegerLiteral(0), arguments[0].span);
+        let (count, pointers) = match atomic.operation {
+            AtomicOperation::Load => (1, 1),
+            AtomicOperation::CompareExchange => (3, 2),
+            AtomicOperation::CompareAndSwap { .. } => (3, 1),
+            _ => (2, 1),
+        };
+        let mut values: Vec<&Expression> = arguments.iter().take(count).collect();
+        // `__sync_lock_release(p)` stores 0
+        if name == "__sync_lock_release" {
+            values.push(&zero);
+        }
+        if values.len() < count {
+            return Err(error(format!("too few arguments to function '{}'", name)));
+        }
+        for (i, value) in values.iter().enumerate() {
+            let parameter = (i >= pointers).then_some(&operand);
+            self.generate_argument(value, None, parameter)?;
+            if i + 1 < values.len() {
+                match self.target {
+                    Target::I386 => self.emit_push("eax"),
+                    Target::Amd64 => self.emit_push("rax"),
+                    Target::Arm64 => self.emit_push("x0"),
+                }
+            }
+        }
+
+        match self.target {
+            Target::I386 | Target::Amd64 => self.emit_x86_atomic(atomic.operation, order, size),
+            Target::Arm64 => self.emit_arm64_atomic(atomic.operation, order, size),
+        }
+        match atomic.operation {
+            AtomicOperation::Store
+            | AtomicOperation::CompareExchange
+            | AtomicOperation::CompareAndSwap { returns_bool: true } => {}
+            _ => self.emit_extension(Some(&operand)),
+        }
+        Ok(())
+    }
+
+    /// The x86 half of `generate_atomic`, with the pointer in the result
+    /// register and the other operands pushed before it. Writes to memory
+    /// take the operand's size; everything else uses whole registers
+    fn emit_x86_atomic(&mut self, operation: AtomicOperation, order: MemoryOrder, size: usize) {
+        let amd64 = self.target == Target::Amd64;
+        let (a, c, d) = match amd64 {
+            true => ("rax", "rcx", "rdx"),
+            false => ("eax", "ecx", "edx"),
+        };
+        // The pointer, and for `__atomic_compare_exchange_n` where the
+        // expected value is
+        let (pointer, expected) = match amd64 {
+            true => ("r10", "r11"),
+            false => ("ebx", "edx"),
+        };
+        let width = match size {
+            1 => "BYTE",
+            2 => "WORD",
+            4 => "DWORD",
+            _ => "QWORD",
+        };
+        let sized = |register: &str| x86_register(register, size);
+        let memory = format!("{} PTR [{}]", width, pointer);
+        // xadd and cmpxchg came with the 486
+        let i386 = !amd64 && self.as_dialect == AsDialect::Gnu && !self.sse2;
+        if i386 {
+            self.emit_line(".arch i486");
+        }
+
+        match operation {
+            AtomicOperation::Load => {
+                self.emit_line(&format!("    mov {}, {}", pointer, a));
+                self.emit_line(&format!("    mov {}, {}", sized(a), memory));
+            }
+            AtomicOperation::Store => {
+                self.emit_line(&format!("    mov {}, {}", c, a));
+                self.emit_pop(pointer);
+                // A sequentially consistent store is an exchange, which
+                // orders like a fence
+                let instruction = match order {
+                    MemoryOrder::SeqCst => "xchg",
+                    _ => "mov",
+                };
+                self.emit_line(&format!("    {} {}, {}", instruction, memory, sized(c)));
+            }
+            AtomicOperation::Exchange => {
+                self.emit_line(&format!("    mov {}, {}", c, a));
+                self.emit_pop(pointer);
+                self.emit_line(&format!("    xchg {}, {}", memory, sized(c)));
+                self.emit_line(&format!("    mov {}, {}", a, c));
+            }
+            AtomicOperation::Fetch {
+                op: op @ (AtomicOp::Add | AtomicOp::Sub),
+                returns_new,
+            } => {
+                self.emit_line(&format!("    mov {}, {}", c, a));
+                self.emit_pop(pointer);
+                if op == AtomicOp::Sub {
+                    self.emit_line(&format!("    neg {}", c));
+                }
+                self.emit_line(&format!("    mov {}, {}", d, c));
+                self.emit_line(&format!("    lock xadd {}, {}", memory, sized(d)));
+                self.emit_line(&format!("    mov {}, {}", a, d));
+                if returns_new {
+                    self.emit_line(&format!("    add {}, {}", a, c));
+                }
+            }
+            AtomicOperation::Fetch { op, returns_new } => {
+                let retry = self.new_label("atomic_retry");
+                let instruction = match op {
+                    AtomicOp::Or => "or",
+                    AtomicOp::Xor => "xor",
+                    _ => "and",
+                };
+                self.emit_line(&format!("    mov {}, {}", c, a));
+                self.emit_pop(pointer);
+                self.emit_line(&format!("    mov {}, {}", sized(a), memory));
+                self.emit_line(&format!("{}:", retry));
+                self.emit_line(&format!("    mov {}, {}", d, a));
+                self.emit_line(&format!("    {} {}, {}", instruction, d, c));
+                if op == AtomicOp::Nand {
+                    self.emit_line(&format!("    not {}", d));
+                }
+                self.emit_line(&format!("    lock cmpxchg {}, {}", memory, sized(d)));
+                self.emit_line(&format!("    jnz {}", retry));
+                if returns_new {
+                    self.emit_line(&format!("    mov {}, {}", a, d));
+                }
+            }
+            AtomicOperation::CompareExchange => {
+                let done = self.new_label("atomic_done");
+                self.emit_line(&format!("    mov {}, {}", c, a));
+                self.emit_pop(expected);
+                self.emit_pop(pointer);
+                let expected = format!("{} PTR [{}]", width, expected);
+                self.emit_line(&format!("    mov {}, {}", sized(a), expected));
+                self.emit_line(&format!("    lock cmpxchg {}, {}", memory, sized(c)));
+                self.emit_line("    sete cl");
+                // What was there instead goes back to `*expected`
+                self.emit_line(&format!("    jz {}", done));
+                self.emit_line(&format!("    mov {}, {}", expected, sized(a)));
+                self.emit_line(&format!("{}:", done));
+                self.emit_line("    movzx eax, cl");
+            }
+            AtomicOperation::CompareAndSwap { returns_bool } => {
+                self.emit_line(&format!("    mov {}, {}", c, a));
+                self.emit_pop(a);
+                self.emit_pop(pointer);
+                self.emit_line(&format!("    lock cmpxchg {}, {}", memory, sized(c)));
+                if returns_bool {
+                    self.emit_line("    sete al");
+                    self.emit_line("    movzx eax, al");
+                }
+            }
+            AtomicOperation::Fence => {}
+        }
+        if i386 {
+            self.emit_line(".arch i386");
+        }
+    }
+
+    /// The arm64 half of `generate_atomic`: exclusive loads and stores, with
+    /// acquire and release semantics when the order asks for them
+    fn emit_arm64_atomic(&mut self, operation: AtomicOperation, order: MemoryOrder, size: usize) {
+        let register = |number: u32| match size {
+            8 => format!("x{}", number),
+            _ => format!("w{}", number),
+        };
+        let suffix = match size {
+            1 => "b",
+            2 => "h",
+            _ => "",
+        };
+        let load = match order.acquires() {
+            true => "ldaxr",
+            false => "ldxr",
+        };
+        let store = match order.releases() {
+            true => "stlxr",
+            false => "stxr",
+        };
+
+        match operation {
+            AtomicOperation::Load => {
+                let instruction = match order.acquires() {
+                    true => "ldar",
+                    false => "ldr",
+                };
+                self.emit_line(&format!(
+                    "    {}{} {}, [x0]",
+                    instruction,
+                    suffix,
+                    register(0)
+                ));
+            }
+            AtomicOperation::Store => {
+                let instruction = match order.releases() {
+                    true => "stlr",
+                    false => "str",
+                };
+                self.emit_pop("x9");
+                self.emit_line(&format!(
+                    "    {}{} {}, [x9]",
+                    instruction,
+                    suffix,
+                    register(0)
+                ));
+            }
+            AtomicOperation::Exchange | AtomicOperation::Fetch { .. } => {
+                let retry = self.new_label("atomic_retry");
+                self.emit_line("    mov x10, x0");
+                self.emit_pop("x9");
+                self.emit_line(&format!("{}:", retry));
+                self.emit_line(&format!("    {}{} {}, [x9]", load, suffix, register(12)));
+                let (new, result) = match operation {
+                    AtomicOperation::Fetch { op, returns_new } => {
+                        let instruction = match op {
+                            AtomicOp::Add => "add",
+                            AtomicOp::Sub => "sub",
+                            AtomicOp::Or => "orr",
+                            AtomicOp::Xor => "eor",
+                            AtomicOp::And | AtomicOp::Nand => "and",
+                        };
+                        self.emit_line(&format!(
+                            "    {} {}, {}, {}",
+                            instruction,
+                            register(13),
+                            register(12),
+                            register(10)
+                        ));
+                        if op == AtomicOp::Nand {
+                            self.emit_line(&format!("    mvn {}, {}", register(13), register(13)));
+                        }
+                        (13, if returns_new { 13 } else { 12 })
+                    }
+                    _ => (10, 12),
+                };
+                self.emit_line(&format!(
+                    "    {}{} w14, {}, [x9]",
+                    store,
+                    suffix,
+                    register(new)
+                ));
+                self.emit_line(&format!("    cbnz w14, {}", retry));
+                self.emit_line(&format!("    mov x0, x{}", result));
+            }
+            AtomicOperation::CompareExchange | AtomicOperation::CompareAndSwap { .. } => {
+                let retry = self.new_label("atomic_retry");
+                let failed = self.new_label("atomic_failed");
+                let done = self.new_label("atomic_done");
+                self.emit_line("    mov x10, x0");
+                if operation == AtomicOperation::CompareExchange {
+                    self.emit_pop("x11");
+                    self.emit_line(&format!("    ldr{} {}, [x11]", suffix, register(12)));
+                } else {
+                    // The exclusive load zero-extends what it compares with
+                    self.emit_pop("x12");
+                    match size {
+                        1 => self.emit_line("    uxtb w12, w12"),
+                        2 => self.emit_line("    uxth w12, w12"),
+                        _ => {}
+                    }
+                }
+                self.emit_pop("x9");
+                self.emit_line(&format!("{}:", retry));
+                self.emit_line(&format!("    {}{} {}, [x9]", load, suffix, register(13)));
+                self.emit_line(&format!("    cmp {}, {}", register(13), register(12)));
+                self.emit_line(&format!("    b.ne {}", failed));
+                self.emit_line(&format!(
+                    "    {}{} w14, {}, [x9]",
+                    store,
+                    suffix,
+                    register(10)
+                ));
+                self.emit_line(&format!("    cbnz w14, {}", retry));
+                self.emit_line(&format!("{}:", failed));
+                self.emit_line("    cset x15, eq");
+                self.emit_line(&format!("    b.eq {}", done));
+                self.emit_line("    clrex");
+                if operation == AtomicOperation::CompareExchange {
+                    self.emit_line(&format!("    str{} {}, [x11]", suffix, register(13)));
+                }
+                self.emit_line(&format!("{}:", done));
+                match operation {
+                    AtomicOperation::CompareAndSwap {
+                        returns_bool: false,
+                    } => self.emit_line("    mov x0, x13"),
+                    _ => self.emit_line("    mov x0, x15"),
+                }
+            }
+            AtomicOperation::Fence => {}
+        }
+    }
+
+    /// Fetch the next argument from a `va_list`: an integer or pointer into
+    /// the result register, a `double` into xmm0 (d0). Only scratch registers
+    /// that never carry call arguments are clobbered.
+    fn generate_va_arg(&mut self, list: &Expression, arg_type: &Type) -> Result<()> {
+        match self.floating(arg_type) {
+            Some(Type::Double) => return self.generate_va_arg_double(list),
+            Some(_) => {
+                return Err(AleccError::CodegenError {
+                    message: "va_arg can't fetch a 'float', which '...' promotes to 'double'"
+                        .to_string(),
+                    span: None,
+                })
+            }
+            None => {}
+        }
+
+        self.emit_va_list_address(list)?;
+        match self.target {
+            // A long long takes two slots
+            Target::I386 if self.wide_integer(arg_type).is_some() => {
+                self.emit_line("    mov ecx, eax");
+                self.emit_line("    mov eax, DWORD PTR [ecx]");
+                self.emit_line("    add DWORD PTR [ecx], 8");
+                self.emit_line("    mov edx, DWORD PTR [eax + 4]");
+                self.emit_line("    mov eax, DWORD PTR [eax]");
+            }
+            Target::I386 => {
+                self.emit_line("    mov ecx, eax");
+                self.emit_line("    mov edx, DWORD PTR [ecx]");
+                self.emit_line("    mov eax, DWORD PTR [edx]");
+                self.emit_line("    add edx, 4");
+                self.emit_line("    mov DWORD PTR [ecx], edx");
+            }
+            Target::Amd64 => {
+                let stack_label = self.new_label("va_stack");
+                let done_label = self.new_label("va_done");
+
+                self.emit_line("    mov r10, rax");
+                self.emit_line("    mov eax, DWORD PTR [r10]");
+                self.emit_line("    cmp eax, 48");
+                self.emit_line(&format!("    jae {}", stack_label));
+                self.emit_line("    mov r11, QWORD PTR [r10 + 16]");
+                self.emit_line("    add r11, rax");
+                self.emit_line("    add eax, 8");
+                self.emit_line("    mov DWORD PTR [r10], eax");
+                self.emit_line("    mov rax, QWORD PTR [r11]");
+                self.emit_line(&format!("    jmp {}", done_label));
+                self.emit_line(&format!("{}:", stack_label));
+                self.emit_line("    mov r11, QWORD PTR [r10 + 8]");
+                self.emit_line("    lea rax, [r11 + 8]");
+                self.emit_line("    mov QWORD PTR [r10 + 8], rax");
+                self.emit_line("    mov rax, QWORD PTR [r11]");
+                self.emit_line(&format!("{}:", done_label));
+            }
+            Target::Arm64 => {
+                let stack_label = self.new_label("va_stack");
+                let done_label = self.new_label("va_done");
+
+                self.emit_line("    mov x10, x0");
+                self.emit_line("    ldrsw x11, [x10, #24]");
+                self.emit_line(&format!("    tbz x11, #63, {}", stack_label));
+                self.emit_line("    add w12, w11, #8");
+                self.emit_line("    str w12, [x10, #24]");
+                self.emit_line("    ldr x13, [x10, #8]");
+                self.emit_line("    ldr x0, [x13, x11]");
+                self.emit_line(&format!("    b {}", done_label));
+                self.emit_line(&format!("{}:", stack_label));
+                self.emit_line("    ldr x13, [x10]");
+                self.emit_line("    ldr x0, [x13]");
+                self.emit_line("    add x13, x13, #8");
+                self.emit_line("    str x13, [x10]");
+                self.emit_line(&format!("{}:", done_label));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch the next `double` from a `va_list` into xmm0 (d0): from the
+    /// vector register save area while `fp_offset` (`__vr_offs`) says it has
+    /// one left, then from the overflow area, where it takes an 8-byte slot
+    fn generate_va_arg_double(&mut self, list: &Expression) -> Result<()> {
+        self.emit_va_list_address(list)?;
+        match self.target {
+            Target::I386 => {
+                self.emit_line("    mov ecx, eax");
+                self.emit_line("    mov edx, DWORD PTR [ecx]");
+                self.emit_line("    add DWORD PTR [ecx], 8");
+                self.emit_load(Some(&Type::Double), "[edx]");
+            }
+            Target::Amd64 => {
+                let stack_label = self.new_label("va_stack");
+                let done_label = self.new_label("va_done");
+
+                // The eight xmm slots end the save area, 16 bytes each
+                self.emit_line("    mov r10, rax");
+                self.emit_line("    mov eax, DWORD PTR [r10 + 4]");
+                self.emit_line(&format!("    cmp eax, {}", AMD64_REG_SAVE_AREA_SIZE));
+                self.emit_line(&format!("    jae {}", stack_label));
+                self.emit_line("    mov r11, QWORD PTR [r10 + 16]");
+                self.emit_line("    add r11, rax");
+                self.emit_line("    add eax, 16");
+                self.emit_line("    mov DWORD PTR [r10 + 4], eax");
+                self.emit_load(Some(&Type::Double), "[r11]");
+                self.emit_line(&format!("    jmp {}", done_label));
+                self.emit_line(&format!("{}:", stack_label));
+                self.emit_line("    mov r11, QWORD PTR [r10 + 8]");
+                self.emit_line("    lea rax, [r11 + 8]");
+                self.emit_line("    mov QWORD PTR [r10 + 8], rax");
+                self.emit_load(Some(&Type::Double), "[r11]");
+                self.emit_line(&format!("{}:", done_label));
+            }
+            Target::Arm64 => {
+                let stack_label = self.new_label("va_stack");
+                let done_label = self.new_label("va_done");
+
+                self.emit_line("    mov x10, x0");
+                self.emit_line("    ldrsw x11, [x10, #28]");
+                self.emit_line(&format!("    tbz x11, #63, {}", stack_label));
+                self.emit_line("    add w12, w11, #16");
+                self.emit_line("    str w12, [x10, #28]");
+                self.emit_line("    ldr x13, [x10, #16]");
+                self.emit_load(Some(&Type::Double), "[x13, x11]");
+                self.emit_line(&format!("    b {}", done_label));
+                self.emit_line(&format!("{}:", stack_label));
+                self.emit_line("    ldr x13, [x10]");
+                self.emit_load(Some(&Type::Double), "[x13]");
+                self.emit_line("    add x13, x13, #8");
+                self.emit_line("    str x13, [x10]");
+                self.emit_line(&format!("{}:", done_label));
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn push_argument(&mut self, _index: usize) -> Result<()> {
+        match self.target {
+            Target::I386 => {
+                self.emit_push("eax");
+            }
+            Target::Amd64 => {
+                // Use calling convention registers
+                self.emit_push("rax"); // Simplified
+            }
+            Target::Arm64 => {
+                // Use calling convention registers
+                self.emit_push("x0"); // Simplified
+            }
+        }
+        Ok(())
+    }
+
+    fn store_in_target(&mut self, target: &Expression) -> Result<()> {
+        // Store rax value into target
+        let ty = self.expression_type(target);
+        // An i386 double's or long long's high half is in edx
+        let wide = self.target == Target::I386
+            && (matches!(
+                ty.as_ref().and_then(|ty| self.floating(ty)),
+                Some(Type::Double)
+            ) || ty.as_ref().and_then(|ty| self.wide_integer(ty)).is_some());
+        if let ExpressionKind::Identifier(name) = &target.kind {
+            if let Some(offset) = self.frame_offset(name) {
+                // Scalar slots are a whole word, so the whole register goes in
+                match self.target {
+                    Target::Amd64 => {
+                        self.emit_line(&format!("    mov QWORD PTR [rbp + {}], rax", offset));
+                    }
+                    Target::I386 => {
+                        self.emit_line(&format!("    mov DWORD PTR [ebp + {}], eax", offset));
+                        if wide {
+                            self.emit_line(&format!(
+                                "    mov DWORD PTR [ebp + {}], edx",
+                                offset + 4
+                            ));
+                        }
+                    }
+                    Target::Arm64 => {
+                        self.emit_line(&format!("    str x0, [x29, #{}]", offset));
+                    }
+                }
+            } else {
+                // Global variable
+                let address = self.global_operand(name);
+                self.emit_store(ty.as_ref(), &address);
+            }
+        } else {
+            // Keep the value while the address is computed, then store through it
+            match self.target {
+                Target::Amd64 => {
+                    self.emit_push("rax");
+                    self.emit_lvalue_address(target)?;
+                    self.emit_line("    mov r11, rax");
+                    self.emit_pop("rax");
+                    self.emit_store(ty.as_ref(), "[r11]");
+                }
+                Target::I386 => {
+                    if wide {
+                        self.emit_push("edx");
+                    }
+                    self.emit_push("eax");
+                    self.emit_lvalue_address(target)?;
+                    self.emit_line("    mov ecx, eax");
+                    self.emit_pop("eax");
+                    if wide {
+                        self.emit_pop("edx");
+                    }
+                    self.emit_store(ty.as_ref(), "[ecx]");
+                }
+                Target::Arm64 => {
+                    self.emit_push("x0");
+                    self.emit_lvalue_address(target)?;
+                    self.emit_line("    mov x9, x0");
+                    self.emit_pop("x0");
+                    self.emit_store(ty.as_ref(), "[x9]");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Frame-pointer-relative slot of a parameter or local, looked up the way
+    /// identifier reads do. A local named like a parameter is declared in an
+    /// inner block and shadows it.
+    fn frame_offset(&self, name: &str) -> Option<i32> {
+        self.local_variables.get(name).copied().or_else(|| {
+            self.current_function_params
+                .iter()
+                .find(|(param_name, _)| param_name == name)
+                .map(|&(_, offset)| offset)
+        })
+    }
+
+    fn variable_type(&self, name: &str) -> Option<Type> {
+        self.local_types
+            .get(name)
+            .or_else(|| self.global_variables.get(name))
+            .map(|ty| self.layout.resolve(ty))
+    }
+
+    /// Type of `expr` before arrays decay, as far as the generator tracks it;
+    /// `None` means a plain word
+    fn expression_type(&self, expr: &Expression) -> Option<Type> {
+        let ty = match &expr.kind {
+            ExpressionKind::Identifier(name) => return self.variable_type(name),
+            ExpressionKind::StringLiteral(_) => Type::Pointer(Box::new(Type::Char)),
+            ExpressionKind::LabelAddress(_) => Type::Pointer(Box::new(Type::Void)),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => return self.pointee(&self.expression_type(operand)?),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => Type::Pointer(Box::new(self.expression_type(operand)?)),
+            ExpressionKind::Index { array, index } => {
+                return self
+                    .expression_type(array)
+                    .and_then(|ty| self.pointee(&ty))
+                    .or_else(|| self.pointee(&self.expression_type(index)?))
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let object = self.expression_type(object)?;
+                let aggregate = if *is_arrow {
+                    self.pointee(&object)?
+                } else {
+                    object
+                };
+                return self.layout.member(&aggregate, member).map(|(_, ty)| ty);
+            }
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => match self.called_function(function) {
+                Some(name) if atomic(name).is_some() && !self.return_types.contains_key(name) => {
+                    let operand = arguments
+                        .first()
+                        .and_then(|pointer| self.expression_type(pointer))
+                        .and_then(|ty| self.pointee(&ty));
+                    atomic(name)?.result_type(operand)?
+                }
+                Some(name) => self.return_types.get(name)?.clone(),
+                None => {
+                    self.function_signature(&self.expression_type(function)?)?
+                        .return_type?
+                }
+            },
+            ExpressionKind::Cast { target_type, .. } => target_type.clone(),
+            ExpressionKind::VaArg { arg_type, .. } => arg_type.clone(),
+            ExpressionKind::Assignment { target, .. } => return self.expression_type(target),
+            _ => return None,
+        };
+        Some(self.layout.resolve(&ty))
+    }
+
+    /// Put the address of function `name` in the result register
+    fn emit_function_address(&mut self, name: &str) {
+        let symbol = linked_symbol(name, &self.wrapped_symbols, &self.defined_functions);
+        self.emit_symbol_address(name, &symbol, "eax", "rax", "x0");
+    }
+
+    /// Put the address of the global `name`, which the object calls `symbol`,
+    /// in the register each target names. Position-independent code takes it
+    /// from the GOT, where the dynamic linker puts it, when the symbol may be
+    /// defined in another module
+    fn emit_symbol_address(
+        &mut self,
+        name: &str,
+        symbol: &str,
+        x86: &str,
+        amd64: &str,
+        arm64: &str,
+    ) {
+        if self.binding.thread_local.contains(name) {
+            self.emit_thread_local_address(name, x86, amd64, arm64);
+            return;
+        }
+        let through_got = self.binding.through_got(name);
+        match self.target {
+            Target::I386 => self.emit_line(&format!("    lea {}, [{}]", x86, symbol)),
+            Target::Amd64 if through_got => self.emit_line(&format!(
+                "    mov {}, QWORD PTR [rip + {}@GOTPCREL]",
+                amd64, symbol
+            )),
+            Target::Amd64 => self.emit_line(&format!("    lea {}, [rip + {}]", amd64, symbol)),
+            Target::Arm64 if through_got => {
+                self.emit_line(&format!("    adrp {}, :got:{}", arm64, symbol));
+                self.emit_line(&format!("    ldr {0}, [{0}, :got_lo12:{1}]", arm64, symbol));
+            }
+            Target::Arm64 => {
+                self.emit_line(&format!("    adrp {}, {}", arm64, symbol));
+                self.emit_line(&format!("    add {0}, {0}, :lo12:{1}", arm64, symbol));
+            }
+        }
+    }
+
+    /// The address of this thread's copy of thread-local `name`: the
+    /// thread pointer (fs or gs based on x86, tpidr_el0 on arm64) plus the
+    /// variable's offset from it
+    fn emit_thread_local_address(&mut self, name: &str, x86: &str, amd64: &str, arm64: &str) {
+        let local_exec = self.binding.local_exec(name);
+        match self.target {
+            Target::I386 => {
+                self.emit_line(&format!("    mov {}, DWORD PTR gs:0", x86));
+                if local_exec {
+                    self.emit_line(&format!("    lea {0}, [{0} + {1}@ntpoff]", x86, name));
+                } else {
+                    self.emit_line(&format!("    add {}, DWORD PTR [{}@indntpoff]", x86, name));
+                }
+            }
+            Target::Amd64 => {
+                self.emit_line(&format!("    mov {}, QWORD PTR fs:0", amd64));
+                if local_exec {
+                    self.emit_line(&format!("    lea {0}, [{0} + {1}@tpoff]", amd64, name));
+                } else {
+                    self.emit_line(&format!(
+                        "    add {}, QWORD PTR [rip + {}@gottpoff]",
+                        amd64, name
+                    ));
+                }
+            }
+            Target::Arm64 if local_exec => {
+                self.emit_line(&format!("    mrs {}, tpidr_el0", arm64));
+                self.emit_line(&format!(
+                    "    add {0}, {0}, #:tprel_hi12:{1}, lsl #12",
+                    arm64, name
+                ));
+                self.emit_line(&format!(
+                    "    add {0}, {0}, #:tprel_lo12_nc:{1}",
+                    arm64, name
+                ));
+            }
+            Target::Arm64 => {
+                // The offset is loaded into whichever of x16 and x17 isn't the result
+                let offset = if arm64 == "x17" { "x16" } else { "x17" };
+                self.emit_line(&format!("    adrp {}, :gottprel:{}", offset, name));
+                self.emit_line(&format!(
+                    "    ldr {0}, [{0}, :gottprel_lo12:{1}]",
+                    offset, name
+                ));
+                self.emit_line(&format!("    mrs {}, tpidr_el0", arm64));
+                self.emit_line(&format!("    add {0}, {0}, {1}", arm64, offset));
+            }
+        }
+    }
+
+    /// The memory operand of global variable `name`, after any instructions
+    /// that compute its address (into r11 or x17, which no argument uses, so
+    /// arguments already in their registers survive loading another; ecx
+    /// for an i386 thread-local)
+    fn global_operand(&mut self, name: &str) -> String {
+        let thread_local = self.binding.thread_local.contains(name);
+        match self.target {
+            Target::I386 if thread_local => {
+                self.emit_symbol_address(name, name, "ecx", "r11", "x17");
+                "[ecx]".to_string()
+            }
+            Target::I386 => format!("[{}]", name),
+            Target::Amd64 if !thread_local && !self.binding.through_got(name) => {
+                format!("[rip + {}]", name)
+            }
+            _ => {
+                self.emit_symbol_address(name, name, "eax", "r11", "x17");
+                match self.target {
+                    Target::Amd64 => "[r11]".to_string(),
+                    _ => "[x17]".to_string(),
+                }
+            }
+        }
+    }
+
+    /// The function a call names, through any `*` and `&` in front of it,
+    /// as long as no variable hides it; calls through pointers have none
+    fn called_function<'a>(&self, callee: &'a Expression) -> Option<&'a str> {
+        match &callee.kind {
+            ExpressionKind::Identifier(name) if self.variable_type(name).is_none() => Some(name),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference | UnaryOperator::AddressOf,
+                operand,
+            } => self.called_function(operand),
+            _ => None,
+        }
+    }
+
+    /// The signature of the function `ty` is, or points to
+    fn function_signature(&self, ty: &Type) -> Option<CallSignature> {
+        let function = match self.layout.resolve(ty) {
+            Type::Pointer(inner) => self.layout.resolve(&inner),
+            ty => ty,
+        };
+        let Type::Function {
+            return_type,
+            parameters,
+            variadic,
+        } = function
+        else {
+            return None;
+        };
+        // `int (*f)()` says nothing about the parameters
+        let prototyped = !parameters.is_empty() || variadic;
+        Some(CallSignature {
+            return_type: Some(*return_type),
+            parameters: prototyped.then_some(parameters),
+            variadic: variadic || !prototyped,
+        })
+    }
+
+    /// What an array or pointer type refers to
+    fn pointee(&self, ty: &Type) -> Option<Type> {
+        match self.layout.resolve(ty) {
+            Type::Pointer(inner) | Type::Array(inner, _) => Some(self.layout.resolve(&inner)),
+            _ => None,
+        }
+    }
+
+    /// Bytes moved when reading or writing a value of `ty`. Aggregates travel in
+    /// one register, so only their first word is moved
+    fn access_size(&self, ty: Option<&Type>) -> usize {
+        let word = self.target.pointer_size();
+        match ty.map(|ty| self.layout.resolve(ty)) {
+            Some(
+                ty @ (Type::Char
+                | Type::Bool
+                | Type::Short
+                | Type::Int
+                | Type::Long
+                | Type::LongLong
+                | Type::UnsignedChar
+                | Type::UnsignedShort
+                | Type::UnsignedInt
+                | Type::UnsignedLong
+                | Type::UnsignedLongLong
+                | Type::Float
+                | Type::Double
+                | Type::Pointer(_)),
+            ) => self.layout.size_of(&ty).min(word),
+            _ => word,
+        }
+    }
+
+    /// Load the value of type `ty` at the memory operand `address` into the
+    /// result register, sign-extending narrow integers (`_Bool` is zero-extended).
+    /// `float` and `double` go to xmm0 (s0/d0) instead, and an i386 `long long`
+    /// to edx:eax
+    fn emit_load(&mut self, ty: Option<&Type>, address: &str) {
+        if let Some(floating) = ty.and_then(|ty| self.floating(ty)) {
+            self.uses_floating_point = true;
+            let line = match (self.target, floating) {
+                (Target::Arm64, floating) => format!("ldr {}0, {}", fp_prefix(&floating), address),
+                (_, Type::Float) => format!("movss xmm0, DWORD PTR {}", address),
+                _ => format!("movsd xmm0, QWORD PTR {}", address),
+            };
+            self.emit_line(&format!("    {}", line));
+            return;
+        }
+        if ty.and_then(|ty| self.wide_integer(ty)).is_some() {
+            // The high half first, in case the address is in eax
+            self.emit_line(&format!("    mov edx, DWORD PTR {}", high_word(address)));
+            self.emit_line(&format!("    mov eax, DWORD PTR {}", address));
+            return;
+        }
+        let unsigned = self.zero_extended(ty);
+        let line = match (self.target, self.access_size(ty), unsigned) {
+            (Target::I386, 1, true) => format!("movzx eax, BYTE PTR {}", address),
+            (Target::I386, 1, false) => format!("movsx eax, BYTE PTR {}", address),
+            (Target::I386, 2, true) => format!("movzx eax, WORD PTR {}", address),
+            (Target::I386, 2, false) => format!("movsx eax, WORD PTR {}", address),
+            (Target::I386, _, _) => format!("mov eax, DWORD PTR {}", address),
+            (Target::Amd64, 1, true) => format!("movzx eax, BYTE PTR {}", address),
+            (Target::Amd64, 1, false) => format!("movsx rax, BYTE PTR {}", address),
+            (Target::Amd64, 2, true) => format!("movzx eax, WORD PTR {}", address),
+            (Target::Amd64, 2, false) => format!("movsx rax, WORD PTR {}", address),
+            (Target::Amd64, 4, true) => format!("mov eax, DWORD PTR {}", address),
+            (Target::Amd64, 4, false) => format!("movsxd rax, DWORD PTR {}", address),
+            (Target::Amd64, _, _) => format!("mov rax, QWORD PTR {}", address),
+            (Target::Arm64, 1, true) => format!("ldrb w0, {}", address),
+            (Target::Arm64, 1, false) => format!("ldrsb x0, {}", address),
+            (Target::Arm64, 2, true) => format!("ldrh w0, {}", address),
+            (Target::Arm64, 2, false) => format!("ldrsh x0, {}", address),
+            (Target::Arm64, 4, true) => format!("ldr w0, {}", address),
+            (Target::Arm64, 4, false) => format!("ldrsw x0, {}", address),
+            (Target::Arm64, _, _) => format!("ldr x0, {}", address),
+        };
+        self.emit_line(&format!("    {}", line));
+    }
+
+    /// Whether an integer `ty` narrower than a register is zero-extended to
+    /// it: `_Bool` and the `unsigned` types are, the others sign-extended
+    fn zero_extended(&self, ty: Option<&Type>) -> bool {
+        ty.map(|ty| self.layout.resolve(ty))
+            .is_some_and(|ty| matches!(ty, Type::Bool) || ty.is_unsigned())
+    }
+
+    /// Sign- or zero-extend the low bytes of the result register that make up
+    /// an integer `ty` to the whole register. Types as wide as a register, and
+    /// ones that aren't integers, are left alone
+    fn emit_extension(&mut self, ty: Option<&Type>) {
+        let Some(resolved) = ty.map(|ty| self.layout.resolve(ty)) else {
+            return;
+        };
+        if !matches!(
+            resolved,
+            Type::Char
+                | Type::Bool
+                | Type::Short
+                | Type::Int
+                | Type::Long
+                | Type::LongLong
+                | Type::UnsignedChar
+                | Type::UnsignedShort
+                | Type::UnsignedInt
+                | Type::UnsignedLong
+                | Type::UnsignedLongLong
+        ) {
+            return;
+        }
+        let size = self.layout.size_of(&resolved);
+        if size >= self.target.pointer_size() {
+            return;
+        }
+        let line = match (self.target, size, self.zero_extended(ty)) {
+            (Target::I386, 1, true) => "movzx eax, al",
+            (Target::I386, 1, false) => "movsx eax, al",
+            (Target::I386, _, true) => "movzx eax, ax",
+            (Target::I386, _, false) => "movsx eax, ax",
+            (Target::Amd64, 1, true) => "movzx eax, al",
+            (Target::Amd64, 1, false) => "movsx rax, al",
+            (Target::Amd64, 2, true) => "movzx eax, ax",
+            (Target::Amd64, 2, false) => "movsx rax, ax",
+            (Target::Amd64, _, true) => "mov eax, eax",
+            (Target::Amd64, _, false) => "movsxd rax, eax",
+            (Target::Arm64, 1, true) => "uxtb w0, w0",
+            (Target::Arm64, 1, false) => "sxtb x0, w0",
+            (Target::Arm64, 2, true) => "uxth w0, w0",
+            (Target::Arm64, 2, false) => "sxth x0, w0",
+            (Target::Arm64, _, true) => "mov w0, w0",
+            (Target::Arm64, _, false) => "sxtw x0, w0",
+        };
+        self.emit_line(&format!("    {}", line));
+    }
+
+    /// Convert the integer or pointer in the result register to `ty`, as a
+    /// cast, assignment or return does: a narrower integer keeps its low bytes,
+    /// extended again, and `_Bool` becomes whether the value is nonzero
+    fn emit_integer_conversion(&mut self, ty: Option<&Type>) {
+        if !matches!(ty.map(|ty| self.layout.resolve(ty)), Some(Type::Bool)) {
+            self.emit_extension(ty);
+            return;
+        }
+        match self.target {
+            Target::I386 => {
+                self.emit_line("    test eax, eax");
+                self.emit_line("    setne al");
+                self.emit_line("    movzx eax, al");
+            }
+            Target::Amd64 => {
+                self.emit_line("    test rax, rax");
+                self.emit_line("    setne al");
+                self.emit_line("    movzx eax, al");
+            }
+            Target::Arm64 => {
+                self.emit_line("    cmp x0, #0");
+                self.emit_line("    cset x0, ne");
+            }
+        }
+    }
+
+    /// Store the low bytes of the result register that make up a `ty` at `address`.
+    /// An i386 double or `long long` takes edx for its high half
+    fn emit_store(&mut self, ty: Option<&Type>, address: &str) {
+        let floating = ty.and_then(|ty| self.floating(ty));
+        let wide = ty.and_then(|ty| self.wide_integer(ty)).is_some();
+        if self.target == Target::I386 && (wide || matches!(floating, Some(Type::Double))) {
+            self.emit_line(&format!("    mov DWORD PTR {}, eax", address));
+            self.emit_line(&format!("    mov DWORD PTR {}, edx", high_word(address)));
+            return;
+        }
+        let line = match (self.target, self.access_size(ty)) {
+            (Target::I386, 1) => format!("mov BYTE PTR {}, al", address),
+            (Target::I386, 2) => format!("mov WORD PTR {}, ax", address),
+            (Target::I386, _) => format!("mov DWORD PTR {}, eax", address),
+            (Target::Amd64, 1) => format!("mov BYTE PTR {}, al", address),
+            (Target::Amd64, 2) => format!("mov WORD PTR {}, ax", address),
+            (Target::Amd64, 4) => format!("mov DWORD PTR {}, eax", address),
+            (Target::Amd64, _) => format!("mov QWORD PTR {}, rax", address),
+            (Target::Arm64, 1) => format!("strb w0, {}", address),
+            (Target::Arm64, 2) => format!("strh w0, {}", address),
+            (Target::Arm64, 4) => format!("str w0, {}", address),
+            (Target::Arm64, _) => format!("str x0, {}", address),
+        };
+        self.emit_line(&format!("    {}", line));
+    }
+
+    /// Replace the address in the result register with the `ty` stored there.
+    /// An array or function is left as its address, which is what it decays to
+    fn emit_load_through(&mut self, ty: Option<&Type>) {
+        if matches!(
+            ty.map(|ty| self.layout.resolve(ty)),
+            Some(Type::Array(..) | Type::Function { .. })
+        ) {
+            return;
+        }
+        let address = match self.target {
+            Target::I386 => "[eax]",
+            Target::Amd64 => "[rax]",
+            Target::Arm64 => "[x0]",
+        };
+        self.emit_load(ty, address);
+    }
+
+    /// Where arguments of these classes go: SysV and AAPCS64 number integer
+    /// and floating-point argument registers separately, and the arguments left
+    /// over take stack slots in order. A struct or union only goes in registers
+    /// when there are enough for all of it. `hidden_pointer` is set when the
+    /// caller passes the address of a struct or union result, which takes rdi
+    /// on amd64. On i386 everything is on the stack.
+    fn argument_locations(
+        &self,
+        classes: &[ArgumentClass],
+        hidden_pointer: bool,
+    ) -> ArgumentLayout {
+        let (integer_registers, floating_registers) = match self.target {
+            Target::I386 => (0, 0),
+            Target::Amd64 => (6, 8),
+            Target::Arm64 => (8, 8),
+        };
+        let mut integers = usize::from(hidden_pointer && self.target == Target::Amd64);
+        let (mut floats, mut slots) = (0, 0);
+        let on_stack = |slots: &mut usize, words: usize| {
+            *slots += words;
+            ArgumentLocation::Stack(*slots - words)
+        };
+        let locations = classes
+            .iter()
+            .map(|class| match class {
+                ArgumentClass::Floating if floats < floating_registers => {
+                    floats += 1;
+                    ArgumentLocation::Floating(floats - 1)
+                }
+                ArgumentClass::Integer if integers < integer_registers => {
+                    integers += 1;
+                    ArgumentLocation::Integer(integers - 1)
+                }
+                ArgumentClass::Integer | ArgumentClass::Floating => on_stack(&mut slots, 1),
+                ArgumentClass::Aggregate(size, AggregatePassing::Registers(pieces)) => {
+                    let wanted = |floating: bool| {
+                        pieces
+                            .iter()
+                            .filter(|piece| piece.floating == floating)
+                            .count()
+                    };
+                    if integers + wanted(false) <= integer_registers
+                        && floats + wanted(true) <= floating_registers
+                    {
+                        let split = pieces.iter().map(|&piece| {
+                            let count = if piece.floating {
+                                &mut floats
+                            } else {
+                                &mut integers
+                            };
+                            *count += 1;
+                            (piece, *count - 1)
+                        });
+                        return ArgumentLocation::Split(split.collect());
+                    }
+                    // AAPCS64 stops using the kind of register that ran out
+                    if self.target == Target::Arm64 {
+                        if wanted(true) > 0 {
+                            floats = floating_registers;
+                        } else {
+                            integers = integer_registers;
+                        }
+                    }
+                    on_stack(&mut slots, size.div_ceil(8))
+                }
+                ArgumentClass::Aggregate(_, AggregatePassing::Memory)
+                    if self.target == Target::Arm64 =>
+                {
+                    let pointer = if integers < integer_registers {
+                        integers += 1;
+                        ArgumentLocation::Integer(integers - 1)
+                    } else {
+                        on_stack(&mut slots, 1)
+                    };
+                    ArgumentLocation::Indirect(Box::new(pointer))
+                }
+                ArgumentClass::Aggregate(size, AggregatePassing::Memory) => {
+                    on_stack(&mut slots, size.div_ceil(8))
+                }
+            })
+            .collect();
+        ArgumentLayout {
+            locations,
+            integers,
+            floats,
+            stack_slots: slots,
+        }
+    }
+
+    /// How an argument of type `ty` is passed; `floating` is the `float` or
+    /// `double` it is converted to, if any
+    fn argument_class(&self, ty: Option<&Type>, floating: Option<&Type>) -> ArgumentClass {
+        match (floating, ty.and_then(|ty| self.aggregate(ty))) {
+            (Some(_), _) => ArgumentClass::Floating,
+            (None, Some((size, passing))) => ArgumentClass::Aggregate(size, passing),
+            (None, None) => ArgumentClass::Integer,
+        }
+    }
+
+    /// The size of `ty` and how it is passed by value, when it is a struct or
+    /// union this target passes by the ABI's rules
+    fn aggregate(&self, ty: &Type) -> Option<(usize, AggregatePassing)> {
+        let passing = aggregate_passing(self.target, &self.layout, ty)?;
+        Some((self.layout.size_of(ty), passing))
+    }
+
+    /// `ty` resolved, if it is `float` or `double`
+    fn floating(&self, ty: &Type) -> Option<Type> {
+        match self.layout.resolve(ty) {
+            ty @ (Type::Float | Type::Double) => Some(ty),
+            _ => None,
+        }
+    }
+
+    /// Bytes an argument of type `ty` takes on the i386 stack: two words for
+    /// a double or a `long long`, a struct or union rounded up to words, and
+    /// one for anything else
+    fn i386_argument_size(&self, ty: &Type) -> usize {
+        if self.wide_integer(ty).is_some() {
+            return 8;
+        }
+        if let Some((size, _)) = self.aggregate(ty) {
+            return size.next_multiple_of(4);
+        }
+        self.floating(ty).map_or(4, |ty| floating_operand(&ty).0)
+    }
+
+    /// The type of `expr` when it is `float` or `double`, whose values are
+    /// kept in xmm0 (s0 or d0 on arm64) instead of the result register
+    fn floating_type(&self, expr: &Expression) -> Option<Type> {
+        match &expr.kind {
+            ExpressionKind::FloatLiteral(_) => Some(Type::Double),
+            ExpressionKind::Binary {
+                left,
+                operator:
+                    BinaryOperator::Add
+                    | BinaryOperator::Subtract
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide,
+                right,
+            } => common_floating(self.floating_type(left), self.floating_type(right)),
+            ExpressionKind::Binary { .. } => None,
+            ExpressionKind::Unary {
+                operator:
+                    UnaryOperator::Minus
+                    | UnaryOperator::Plus
+                    | UnaryOperator::PreIncrement
+                    | UnaryOperator::PostIncrement
+                    | UnaryOperator::PreDecrement
+                    | UnaryOperator::PostDecrement,
+                operand,
+            } => self.floating_type(operand),
+            _ => self.floating(&self.expression_type(expr)?),
+        }
+    }
+
+    fn result_register(&self) -> &'static str {
+        match self.target {
+            Target::I386 => "eax",
+            Target::Amd64 => "rax",
+            Target::Arm64 => "x0",
+        }
+    }
+
+    /// Evaluate `expr` and convert its value to `to`, as assignments, returns
+    /// and argument passing do
+    fn generate_converted(&mut self, expr: &Expression, to: Option<&Type>) -> Result<()> {
+        self.generate_expression(expr)?;
+        if let (Some(wide), Some(to)) = (self.wide_type(expr), to) {
+            return self.emit_wide_to_floating(&wide, to);
+        }
+        let from = self.floating_type(expr);
+        self.emit_conversion(from.as_ref(), to)
+    }
+
+    /// Evaluate argument `arg`, converted to `conversion` if that is `float` or
+    /// `double` and otherwise to its integer `parameter` type, if it has one
+    fn generate_argument(
+        &mut self,
+        arg: &Expression,
+        conversion: Option<&Type>,
+        parameter: Option<&Type>,
+    ) -> Result<()> {
+        if conversion.is_none() && parameter.and_then(|ty| self.wide_integer(ty)).is_some() {
+            return self.generate_wide(arg);
+        }
+        self.generate_converted(arg, conversion)?;
+        // A constant that the parameter's type can hold is already converted
+        let in_range = match (&arg.kind, parameter) {
+            (ExpressionKind::IntegerLiteral(value), Some(ty)) => self.holds(ty, *value),
+            _ => false,
+        };
+        if conversion.is_none() && !in_range {
+            self.emit_integer_conversion(parameter);
+        }
+        Ok(())
+    }
+
+    /// Whether the integer type `ty` can represent `value`
+    fn holds(&self, ty: &Type, value: i64) -> bool {
+        let ty = self.layout.resolve(ty);
+        let bits = (self.layout.size_of(&ty) * 8) as u32;
+        match ty {
+            Type::Bool => value == 0 || value == 1,
+            ty if bits >= 64 => !ty.is_unsigned() || value >= 0,
+            ty if ty.is_unsigned() => (0..1i64 << bits).contains(&value),
+            _ => (-(1i64 << (bits - 1))..1i64 << (bits - 1)).contains(&value),
+        }
+    }
+
+    /// Evaluate `expr` for its truth: the result register is zero only when it is
+    fn generate_condition(&mut self, expr: &Expression) -> Result<()> {
+        self.generate_expression(expr)?;
+        if self.wide_type(expr).is_some() {
+            self.emit_line("    or eax, edx");
+        }
+        match self.floating_type(expr) {
+            Some(ty) => self.emit_floating_truth(&ty, false),
+            None => Ok(()),
+        }
+    }
+
+    /// Convert the value just computed from `from` to `to`, where `None` is an
+    /// integer in the result register and `float` or `double` a value in xmm0
+    fn emit_conversion(&mut self, from: Option<&Type>, to: Option<&Type>) -> Result<()> {
+        let line = match (self.target, from, to) {
+            (_, None, None) => return Ok(()),
+            (_, Some(from), Some(to)) if sse_suffix(from) == sse_suffix(to) => return Ok(()),
+            (Target::I386, None, Some(to)) => format!("cvtsi2{} xmm0, eax", sse_suffix(to)),
+            (Target::I386, Some(from), None) => {
+                format!("cvtt{}2si eax, xmm0", sse_suffix(from))
+            }
+            (Target::Amd64, None, Some(to)) => format!("cvtsi2{} xmm0, rax", sse_suffix(to)),
+            (Target::Amd64, Some(from), None) => {
+                format!("cvtt{}2si rax, xmm0", sse_suffix(from))
+            }
+            (Target::I386 | Target::Amd64, Some(from), Some(to)) => {
+                format!("cvt{}2{} xmm0, xmm0", sse_suffix(from), sse_suffix(to))
+            }
+            (Target::Arm64, None, Some(to)) => format!("scvtf {}0, x0", fp_prefix(to)),
+            (Target::Arm64, Some(from), None) => format!("fcvtzs x0, {}0", fp_prefix(from)),
+            (Target::Arm64, Some(from), Some(to)) => {
+                format!("fcvt {}0, {}0", fp_prefix(to), fp_prefix(from))
+            }
+        };
+        // Every floating value is loaded, converted or a constant first
+        self.uses_floating_point = true;
+        self.emit_line(&format!("    {}", line));
+        Ok(())
+    }
+
+    /// Copy the bits of the `ty` in xmm0 (s0/d0) to the result register, from
+    /// where it is pushed and stored like an integer of the same size. An
+    /// i386 double's high half goes to edx
+    fn emit_floating_bits(&mut self, ty: &Type) -> Result<()> {
+        let line = match (self.target, ty) {
+            (Target::I386, Type::Float) => "movd eax, xmm0",
+            (Target::I386, _) => {
+                self.emit_line("    movd eax, xmm0");
+                self.emit_line("    pshufd xmm7, xmm0, 1");
+                "movd edx, xmm7"
+            }
+            (Target::Amd64, Type::Float) => "movd eax, xmm0",
+            (Target::Amd64, _) => "movq rax, xmm0",
+            (Target::Arm64, Type::Float) => "fmov w0, s0",
+            (Target::Arm64, _) => "fmov x0, d0",
+        };
+        self.emit_line(&format!("    {}", line));
+        Ok(())
+    }
+
+    /// Move the bits of a `ty` from the 64-bit integer register `from` to
+    /// floating-point register `to`; on i386 from eax, and edx for the high
+    /// half of a double
+    fn emit_bits_to_floating(&mut self, ty: &Type, from: &str, to: usize) -> Result<()> {
+        let line = match (self.target, ty) {
+            (Target::I386, Type::Float) => format!("movd xmm{}, {}", to, from),
+            (Target::I386, _) => {
+                self.emit_line(&format!("    movd xmm{}, {}", to, from));
+                self.emit_line("    movd xmm7, edx");
+                format!("punpckldq xmm{}, xmm7", to)
+            }
+            (Target::Amd64, Type::Float) => {
+                // The low half of rax is eax, of r8 r8d
+                let low = match from.strip_prefix('r') {
+                    Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => {
+                        format!("{}d", from)
+                    }
+                    Some(rest) => format!("e{}", rest),
+                    None => from.to_string(),
+                };
+                format!("movd xmm{}, {}", to, low)
+            }
+            (Target::Amd64, _) => format!("movq xmm{}, {}", to, from),
+            (Target::Arm64, Type::Float) => format!("fmov s{}, w{}", to, &from[1..]),
+            (Target::Arm64, _) => format!("fmov d{}, {}", to, from),
+        };
+        self.emit_line(&format!("    {}", line));
+        Ok(())
+    }
+
+    /// Put the `ty` constant `value` in floating-point register `to`, going
+    /// through the result register
+    fn emit_floating_constant(&mut self, value: f64, ty: &Type, to: usize) -> Result<()> {
+        let bits = match ty {
+            Type::Float => (value as f32).to_bits() as u64,
+            _ => value.to_bits(),
+        };
+        self.uses_floating_point = true;
+        match self.target {
+            Target::I386 => {
+                self.emit_line(&format!("    mov eax, 0x{:x}", bits as u32));
+                if !matches!(ty, Type::Float) {
+                    self.emit_line(&format!("    mov edx, 0x{:x}", bits >> 32));
+                }
+            }
+            Target::Amd64 => match ty {
+                Type::Float => self.emit_line(&format!("    mov eax, 0x{:x}", bits)),
+                _ => self.emit_line(&format!("    mov rax, 0x{:x}", bits)),
+            },
+            Target::Arm64 => self.emit_arm64_constant(bits as i64),
+        }
+        self.emit_bits_to_floating(ty, self.result_register(), to)
+    }
+
+    /// Put `value` in x0: with `mov` when one `movz` or `movn` can make it,
+    /// otherwise 16 bits at a time
+    fn emit_arm64_constant(&mut self, value: i64) {
+        if (-0x10000..0x10000).contains(&value) {
+            self.emit_line(&format!("    mov x0, #{}", value));
+            return;
+        }
+        // movz sets the first nonzero 16 bits, movk each other one
+        let bits = value as u64;
+        let mut first = true;
+        for shift in (0..64).step_by(16) {
+            let chunk = (bits >> shift) & 0xffff;
+            if chunk == 0 {
+                continue;
+            }
+            let mnemonic = if first { "movz" } else { "movk" };
+            self.emit_line(&format!(
+                "    {} x0, #0x{:x}, lsl #{}",
+                mnemonic, chunk, shift
+            ));
+            first = false;
+        }
+    }
+
+    /// Copy floating-point register `from` to `to`
+    fn emit_floating_copy(&mut self, ty: &Type, from: usize, to: usize) -> Result<()> {
+        let line = match self.target {
+            Target::I386 | Target::Amd64 => format!("movaps xmm{}, xmm{}", to, from),
+            Target::Arm64 => format!("fmov {p}{}, {p}{}", to, from, p = fp_prefix(ty)),
+        };
+        self.emit_line(&format!("    {}", line));
+        Ok(())
+    }
+
+    /// Flip the sign of the `ty` in xmm0 (s0/d0), which also negates zero and NaN
+    fn emit_floating_negation(&mut self, ty: &Type) -> Result<()> {
+        match (self.target, ty) {
+            (Target::I386 | Target::Amd64, Type::Float) => {
+                self.emit_line("    movd eax, xmm0");
+                self.emit_line("    xor eax, 0x80000000");
+                self.emit_line("    movd xmm0, eax");
+            }
+            (Target::I386, _) => {
+                self.emit_floating_bits(ty)?;
+                self.emit_line("    xor edx, 0x80000000");
+                self.emit_bits_to_floating(ty, "eax", 0)?;
+            }
+            (Target::Amd64, _) => {
+                self.emit_line("    movq rax, xmm0");
+                self.emit_line("    btc rax, 63");
+                self.emit_line("    movq xmm0, rax");
+            }
+            (Target::Arm64, ty) => {
+                self.emit_line(&format!("    fneg {p}0, {p}0", p = fp_prefix(ty)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the `ty` in xmm0 (s0/d0) by 1 in the result register when it
+    /// is nonzero, NaN included, and by 0 otherwise; the other way round when
+    /// `negate`, for `!`
+    fn emit_floating_truth(&mut self, ty: &Type, negate: bool) -> Result<()> {
+        match self.target {
+            Target::I386 | Target::Amd64 => {
+                self.emit_line("    xorps xmm1, xmm1");
+                self.emit_line(&format!("    ucomi{} xmm0, xmm1", sse_suffix(ty)));
+                // Unordered sets the parity flag
+                if negate {
+                    self.emit_line("    sete al");
+                    self.emit_line("    setnp ah");
+                    self.emit_line("    and al, ah");
+                } else {
+                    self.emit_line("    setne al");
+                    self.emit_line("    setp ah");
+                    self.emit_line("    or al, ah");
+                }
+                self.emit_line(&format!("    movzx {}, al", self.result_register()));
+            }
+            Target::Arm64 => {
+                self.emit_line(&format!("    fcmp {}0, #0.0", fp_prefix(ty)));
+                let condition = if negate { "eq" } else { "ne" };
+                self.emit_line(&format!("    cset x0, {}", condition));
+            }
+        }
+        Ok(())
+    }
+
+    /// `left operator right` with both operands converted to `ty`: left in
+    /// xmm0 (s0/d0) and right in xmm1 (s1/d1)
+    fn generate_floating_binary(
+        &mut self,
+        left: &Expression,
+        operator: &BinaryOperator,
+        right: &Expression,
+        ty: &Type,
+    ) -> Result<()> {
+        self.generate_converted(right, Some(ty))?;
+        self.emit_floating_bits(ty)?;
+        let wide = !matches!(ty, Type::Float);
+        match self.target {
+            Target::I386 => {
+                if wide {
+                    self.emit_push("edx");
+                }
+                self.emit_push("eax");
+            }
+            Target::Amd64 => self.emit_push("rax"),
+            Target::Arm64 => self.emit_push("x0"),
+        }
+        self.generate_converted(left, Some(ty))?;
+        match self.target {
+            Target::I386 => {
+                self.emit_pop("eax");
+                if wide {
+                    self.emit_pop("edx");
+                }
+                self.emit_bits_to_floating(ty, "eax", 1)?;
+            }
+            Target::Amd64 => {
+                self.emit_pop("rbx");
+                self.emit_bits_to_floating(ty, "rbx", 1)?;
+            }
+            Target::Arm64 => {
+                self.emit_pop("x1");
+                self.emit_bits_to_floating(ty, "x1", 1)?;
+            }
+        }
+        self.emit_floating_operator(operator, ty)
+    }
+
+    /// xmm0 `operator` xmm1 (s0/d0 and s1/d1 on arm64) on two `ty` values.
+    /// Arithmetic leaves a `ty` in xmm0; comparisons leave 0 or 1 in the
+    /// result register, and only `!=` is true when an operand is NaN.
+    fn emit_floating_operator(&mut self, operator: &BinaryOperator, ty: &Type) -> Result<()> {
+        let arithmetic = match operator {
+            BinaryOperator::Add => Some("add"),
+            BinaryOperator::Subtract => Some("sub"),
+            BinaryOperator::Multiply => Some("mul"),
+            BinaryOperator::Divide => Some("div"),
+            _ => None,
+        };
+        match (self.target, arithmetic) {
+            (Target::I386 | Target::Amd64, Some(operation)) => {
+                self.emit_line(&format!("    {}{} xmm0, xmm1", operation, sse_suffix(ty)));
+            }
+            (Target::Arm64, Some(operation)) => {
+                self.emit_line(&format!(
+                    "    f{} {p}0, {p}0, {p}1",
+                    operation,
+                    p = fp_prefix(ty)
+                ));
+            }
+            (Target::I386 | Target::Amd64, None) => {
+                // Flags as for an unsigned comparison, all set when unordered:
+                // `<` and `<=` compare the other way round to be false then
+                let (swap, set) = match operator {
+                    BinaryOperator::Equal => (false, "sete"),
+                    BinaryOperator::NotEqual => (false, "setne"),
+                    BinaryOperator::Less => (true, "seta"),
+                    BinaryOperator::LessEqual => (true, "setae"),
+                    BinaryOperator::Greater => (false, "seta"),
+                    BinaryOperator::GreaterEqual => (false, "setae"),
+                    _ => return Err(floating_operands(operator)),
+                };
+                let (a, b) = if swap { (1, 0) } else { (0, 1) };
+                self.emit_line(&format!("    ucomi{} xmm{}, xmm{}", sse_suffix(ty), a, b));
+                self.emit_line(&format!("    {} al", set));
+                match operator {
+                    BinaryOperator::Equal => {
+                        self.emit_line("    setnp ah");
+                        self.emit_line("    and al, ah");
+                    }
+                    BinaryOperator::NotEqual => {
+                        self.emit_line("    setp ah");
+                        self.emit_line("    or al, ah");
+                    }
+                    _ => {}
+                }
+                self.emit_line(&format!("    movzx {}, al", self.result_register()));
+            }
+            (Target::Arm64, None) => {
+                // mi and ls are false when unordered, unlike lt and le
+                let condition = match operator {
+                    BinaryOperator::Equal => "eq",
+                    BinaryOperator::NotEqual => "ne",
+                    BinaryOperator::Less => "mi",
+                    BinaryOperator::LessEqual => "ls",
+                    BinaryOperator::Greater => "gt",
+                    BinaryOperator::GreaterEqual => "ge",
+                    _ => return Err(floating_operands(operator)),
+                };
+                self.emit_line(&format!("    fcmp {p}0, {p}1", p = fp_prefix(ty)));
+                self.emit_line(&format!("    cset x0, {}", condition));
+            }
+        }
+        Ok(())
+    }
+
+    /// `target op= value` when either side is `float` or `double`: computed
+    /// in `common`, then converted back to the target's type
+    fn generate_floating_compound_assignment(
+        &mut self,
+        target: &Expression,
+        operator: &BinaryOperator,
+        value: &Expression,
+        floating: Option<Type>,
+        common: &Type,
+    ) -> Result<()> {
+        let ty = self.expression_type(target);
+        self.emit_lvalue_address(target)?;
+        let address = match self.target {
+            Target::I386 => {
+                self.emit_push("eax");
+                "[eax]"
+            }
+            Target::Amd64 => {
+                self.emit_push("rax");
+                "[rax]"
+            }
+            Target::Arm64 => {
+                self.emit_push("x0");
+                "[x0]"
+            }
+        };
+        self.emit_load(ty.as_ref(), address);
+        let integer = ty.as_ref().and_then(|ty| self.wide_integer(ty));
+        match &integer {
+            Some(integer) => self.emit_wide_to_floating(integer, common)?,
+            None => self.emit_conversion(floating.as_ref(), Some(common))?,
+        }
+        self.emit_floating_bits(common)?;
+        let wide = !matches!(common, Type::Float);
+        match self.target {
+            Target::I386 => {
+                if wide {
+                    self.emit_push("edx");
+                }
+                self.emit_push("eax");
+            }
+            Target::Amd64 => self.emit_push("rax"),
+            _ => self.emit_push("x0"),
+        }
+        self.generate_converted(value, Some(common))?;
+        self.emit_floating_copy(common, 0, 1)?;
+        let result = self.result_register();
+        match self.target {
+            Target::I386 => {
+                self.emit_pop("eax");
+                if wide {
+                    self.emit_pop("edx");
+                }
+            }
+            Target::Amd64 => self.emit_pop("rax"),
+            _ => self.emit_pop("x0"),
+        }
+        self.emit_bits_to_floating(common, result, 0)?;
+        self.emit_floating_operator(operator, common)?;
+        match &integer {
+            Some(_) => self.emit_floating_to_wide(common)?,
+            None => self.emit_conversion(Some(common), floating.as_ref())?,
+        }
+        if let Some(floating) = &floating {
+            self.emit_floating_bits(floating)?;
+        }
+        match self.target {
+            Target::I386 => {
+                self.emit_pop("ecx");
+                self.emit_store(ty.as_ref(), "[ecx]");
+            }
+            Target::Amd64 => {
+                self.emit_pop("r11");
+                self.emit_store(ty.as_ref(), "[r11]");
+            }
+            _ => {
+                self.emit_pop("x9");
+                self.emit_store(ty.as_ref(), "[x9]");
+            }
+        }
+        Ok(())
+    }
+
+    /// `++`/`--` on the `ty` whose address is in the result register, adding
+    /// 1.0; the new (prefix) or old (postfix) value is left in xmm0 (s0/d0)
+    fn emit_floating_increment(&mut self, operator: &UnaryOperator, ty: &Type) -> Result<()> {
+        let (operation, postfix) = match operator {
+            UnaryOperator::PreIncrement => (BinaryOperator::Add, false),
+            UnaryOperator::PostIncrement => (BinaryOperator::Add, true),
+            UnaryOperator::PreDecrement => (BinaryOperator::Subtract, false),
+            UnaryOperator::PostDecrement => (BinaryOperator::Subtract, true),
+            _ => unreachable!("not an increment operator"),
+        };
+        let address = match self.target {
+            Target::I386 => {
+                self.emit_line("    mov ecx, eax");
+                "[ecx]"
+            }
+            Target::Amd64 => {
+                self.emit_line("    mov r11, rax");
+                "[r11]"
+            }
+            Target::Arm64 => {
+                self.emit_line("    mov x9, x0");
+                "[x9]"
+            }
+        };
+        self.emit_load(Some(ty), address);
+        self.emit_floating_constant(1.0, ty, 1)?;
+        // The old value waits in the third register
+        self.emit_floating_copy(ty, 0, 2)?;
+        self.emit_floating_operator(&operation, ty)?;
+        self.emit_floating_bits(ty)?;
+        self.emit_store(Some(ty), address);
+        if postfix {
+            self.emit_floating_copy(ty, 2, 0)?;
+        }
+        Ok(())
+    }
+
+    /// `ty` resolved, if it is a `long long` that i386 keeps in edx:eax
+    fn wide_integer(&self, ty: &Type) -> Option<Type> {
+        match self.layout.resolve(ty) {
+            ty @ (Type::LongLong | Type::UnsignedLongLong) if self.target == Target::I386 => {
+                Some(ty)
+            }
+            _ => None,
+        }
+    }
+
+    /// The type of `expr` when it is an i386 `long long`, whose high half is
+    /// kept in edx. Constants that don't fit an `int` are ones
+    fn wide_type(&self, expr: &Expression) -> Option<Type> {
+        if self.target != Target::I386 {
+            return None;
+        }
+        match &expr.kind {
+            ExpressionKind::IntegerLiteral(value) => {
+                i32::try_from(*value).is_err().then_some(Type::LongLong)
+            }
+            ExpressionKind::Binary {
+                left,
+                operator:
+                    BinaryOperator::Add
+                    | BinaryOperator::Subtract
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Modulo
+                    | BinaryOperator::BitwiseAnd
+                    | BinaryOperator::BitwiseOr
+                    | BinaryOperator::BitwiseXor,
+                right,
+            } => {
+                let pointer = [left, right].iter().any(|operand| {
+                    self.expression_type(operand)
+                        .is_some_and(|ty| self.pointee(&ty).is_some())
+                });
+                if pointer || self.floating_type(expr).is_some() {
+                    return None;
+                }
+                common_wide(self.wide_type(left), self.wide_type(right))
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: BinaryOperator::LeftShift | BinaryOperator::RightShift,
+                ..
+            } => self.wide_type(left),
+            ExpressionKind::Binary { .. } => None,
+            ExpressionKind::Unary {
+                operator:
+                    UnaryOperator::Minus
+                    | UnaryOperator::Plus
+                    | UnaryOperator::BitwiseNot
+                    | UnaryOperator::PreIncrement
+                    | UnaryOperator::PostIncrement
+                    | UnaryOperator::PreDecrement
+                    | UnaryOperator::PostDecrement,
+                operand,
+            } => self.wide_type(operand),
+            _ => self.wide_integer(&self.expression_type(expr)?),
+        }
+    }
+
+    /// Whether the integer value of `expr` has an unsigned type, so that
+    /// widening it fills the high half with zeros rather than its sign
+    fn unsigned_value(&self, expr: &Expression) -> bool {
+        match &expr.kind {
+            ExpressionKind::IntegerLiteral(_) => false,
+            ExpressionKind::Binary {
+                left,
+                operator: BinaryOperator::LeftShift | BinaryOperator::RightShift,
+                ..
+            } => self.unsigned_value(left),
+            ExpressionKind::Binary {
+                left,
+                operator:
+                    BinaryOperator::Add
+                    | BinaryOperator::Subtract
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Modulo
+                    | BinaryOperator::BitwiseAnd
+                    | BinaryOperator::BitwiseOr
+                    | BinaryOperator::BitwiseXor,
+                right,
+            } => self.unsigned_value(left) || self.unsigned_value(right),
+            ExpressionKind::Binary { .. } => false,
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Minus | UnaryOperator::Plus | UnaryOperator::BitwiseNot,
+                operand,
+            } => self.unsigned_value(operand),
+            _ => self.unsigned_operand(self.expression_type(expr).as_ref()),
+        }
+    }
+
+    /// Evaluate `expr` as an i386 `long long`, into edx:eax. Narrower
+    /// integers are sign- or zero-extended to the high half, and floating
+    /// values truncated
+    fn generate_wide(&mut self, expr: &Expression) -> Result<()> {
+        self.generate_expression(expr)?;
+        if self.wide_type(expr).is_some() {
+            return Ok(());
+        }
+        if let Some(floating) = self.floating_type(expr) {
+            return self.emit_floating_to_wide(&floating);
+        }
+        if self.unsigned_value(expr) {
+            self.emit_line("    xor edx, edx");
+        } else {
+            self.emit_line("    cdq");
+        }
+        Ok(())
+    }
+
+    /// Evaluate `expr` converted to the integer or pointer type `ty`, as
+    /// assignments, initializers and returns do. An i386 `long long` takes
+    /// edx:eax, and one converted to `_Bool` is true if either half is nonzero
+    fn generate_integer(&mut self, expr: &Expression, ty: Option<&Type>) -> Result<()> {
+        if ty.and_then(|ty| self.wide_integer(ty)).is_some() {
+            return self.generate_wide(expr);
+        }
+        let boolean = matches!(ty.map(|ty| self.layout.resolve(ty)), Some(Type::Bool));
+        if boolean && self.wide_type(expr).is_some() {
+            self.generate_condition(expr)?;
+        } else {
+            self.generate_converted(expr, None)?;
+        }
+        self.emit_integer_conversion(ty);
+        Ok(())
+    }
+
+    /// `left operator right` computed in the i386 `long long` type `ty`. The
+    /// left operand goes in edx:eax and the right one in ecx:ebx, or for a
+    /// shift just its count in ecx
+    fn generate_wide_binary(
+        &mut self,
+        left: &Expression,
+        operator: &BinaryOperator,
+        right: &Expression,
+        ty: &Type,
+    ) -> Result<()> {
+        let shift = matches!(
+            operator,
+            BinaryOperator::LeftShift | BinaryOperator::RightShift
+        );
+        if shift {
+            self.generate_expression(right)?;
+        } else {
+            self.generate_wide(right)?;
+            self.emit_push("edx");
+        }
+        self.emit_push("eax");
+        self.generate_wide(left)?;
+        self.emit_pop(if shift { "ecx" } else { "ebx" });
+        if !shift {
+            self.emit_pop("ecx");
+        }
+        self.emit_wide_operator(operator, ty)
+    }
+
+    /// edx:eax `operator` ecx:ebx on two `long long` values of type `ty`.
+    /// Arithmetic carries from the low half to the high one and leaves the
+    /// result in edx:eax; comparisons leave 0 or 1 in eax. Division calls the
+    /// helpers libgcc has for it, which this unit then brings along
+    fn emit_wide_operator(&mut self, operator: &BinaryOperator, ty: &Type) -> Result<()> {
+        let unsigned = ty.is_unsigned();
+        let lines: &[&str] = match operator {
+            BinaryOperator::Add => &["add eax, ebx", "adc edx, ecx"],
+            BinaryOperator::Subtract => &["sub eax, ebx", "sbb edx, ecx"],
+            BinaryOperator::BitwiseAnd => &["and eax, ebx", "and edx, ecx"],
+            BinaryOperator::BitwiseOr => &["or eax, ebx", "or edx, ecx"],
+            BinaryOperator::BitwiseXor => &["xor eax, ebx", "xor edx, ecx"],
+            // The high halves only meet the low ones: their product with
+            // each other is past the 64 bits kept
+            BinaryOperator::Multiply => &[
+                "imul edx, ebx",
+                "imul ecx, eax",
+                "add ecx, edx",
+                "mul ebx",
+                "add edx, ecx",
+            ],
+            BinaryOperator::Divide | BinaryOperator::Modulo => {
+                let helper = match (operator, unsigned) {
+                    (BinaryOperator::Divide, false) => "__divdi3",
+                    (BinaryOperator::Divide, true) => "__udivdi3",
+                    (_, false) => "__moddi3",
+                    (_, true) => "__umoddi3",
+                };
+                self.divides_wide = true;
+                for register in ["ecx", "ebx", "edx", "eax"] {
+                    self.emit_push(register);
+                }
+                self.emit_line(&format!("    call {}", helper));
+                self.emit_stack_release(16);
+                return Ok(());
+            }
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                // The double shifts take the count modulo 32, so past that
+                // one half moves into the other
+                let lines = match (operator, unsigned) {
+                    (BinaryOperator::LeftShift, _) => [
+                        "shld edx, eax, cl",
+                        "shl eax, cl",
+                        "mov edx, eax",
+                        "xor eax, eax",
+                    ],
+                    (_, true) => [
+                        "shrd eax, edx, cl",
+                        "shr edx, cl",
+                        "mov eax, edx",
+                        "xor edx, edx",
+                    ],
+                    (_, false) => [
+                        "shrd eax, edx, cl",
+                        "sar edx, cl",
+                        "mov eax, edx",
+                        "sar edx, 31",
+                    ],
+                };
+                let done = self.new_label("wide_shift");
+                self.emit_line(&format!("    {}", lines[0]));
+                self.emit_line(&format!("    {}", lines[1]));
+                self.emit_line("    test cl, 32");
+                self.emit_line(&format!("    je {}", done));
+                self.emit_line(&format!("    {}", lines[2]));
+                self.emit_line(&format!("    {}", lines[3]));
+                self.emit_line(&format!("{}:", done));
+                return Ok(());
+            }
+            BinaryOperator::Equal => &[
+                "xor eax, ebx",
+                "xor edx, ecx",
+                "or eax, edx",
+                "sete al",
+                "movzx eax, al",
+            ],
+            BinaryOperator::NotEqual => &[
+                "xor eax, ebx",
+                "xor edx, ecx",
+                "or eax, edx",
+                "setne al",
+                "movzx eax, al",
+            ],
+            // The flags of a subtraction that borrows across the halves
+            // order the whole values; `>` and `<=` subtract the other way
+            BinaryOperator::Less
+            | BinaryOperator::GreaterEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::LessEqual => {
+                let subtract = match operator {
+                    BinaryOperator::Less | BinaryOperator::GreaterEqual => {
+                        ["sub eax, ebx", "sbb edx, ecx"]
+                    }
+                    _ => ["sub ebx, eax", "sbb ecx, edx"],
+                };
+                let set = match (operator, unsigned) {
+                    (BinaryOperator::Less | BinaryOperator::Greater, false) => "setl",
+                    (BinaryOperator::Less | BinaryOperator::Greater, true) => "setb",
+                    (_, false) => "setge",
+                    (_, true) => "setae",
+                };
+                for line in subtract {
+                    self.emit_line(&format!("    {}", line));
+                }
+                self.emit_line(&format!("    {} al", set));
+                self.emit_line("    movzx eax, al");
+                return Ok(());
+            }
+            BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                unreachable!("logical operators take the truth of each operand")
+            }
+        };
+        for line in lines {
+            self.emit_line(&format!("    {}", line));
+        }
+        Ok(())
+    }
+
+    /// `target op= value` when either side is an i386 `long long`: computed
+    /// in `common`, then converted back to the target's type
+    fn generate_wide_compound_assignment(
+        &mut self,
+        target: &Expression,
+        operator: &BinaryOperator,
+        value: &Expression,
+        common: &Type,
+    ) -> Result<()> {
+        let ty = self.expression_type(target);
+        self.emit_lvalue_address(target)?;
+        self.emit_push("eax");
+        self.emit_load(ty.as_ref(), "[eax]");
+        if ty.as_ref().and_then(|ty| self.wide_integer(ty)).is_none() {
+            if self.unsigned_operand(ty.as_ref()) {
+                self.emit_line("    xor edx, edx");
+            } else {
+                self.emit_line("    cdq");
+            }
+        }
+        self.emit_push("edx");
+        self.emit_push("eax");
+        if matches!(
+            operator,
+            BinaryOperator::LeftShift | BinaryOperator::RightShift
+        ) {
+            self.generate_expression(value)?;
+            self.emit_line("    mov ecx, eax");
+        } else {
+            self.generate_wide(value)?;
+            self.emit_line("    mov ebx, eax");
+            self.emit_line("    mov ecx, edx");
+        }
+        self.emit_pop("eax");
+        self.emit_pop("edx");
+        self.emit_wide_operator(operator, common)?;
+        self.emit_integer_conversion(ty.as_ref());
+        self.emit_pop("ecx");
+        self.emit_store(ty.as_ref(), "[ecx]");
+        Ok(())
+    }
+
+    /// `++`/`--` on the i386 `long long` whose address is in eax, carrying
+    /// into the high half; the new (prefix) or old (postfix) value is left
+    /// in edx:eax
+    fn emit_wide_increment(&mut self, operator: &UnaryOperator, ty: Option<&Type>) -> Result<()> {
+        let (step, carry, postfix) = match operator {
+            UnaryOperator::PreIncrement => ("add", "adc", false),
+            UnaryOperator::PostIncrement => ("add", "adc", true),
+            UnaryOperator::PreDecrement => ("sub", "sbb", false),
+            UnaryOperator::PostDecrement => ("sub", "sbb", true),
+            _ => unreachable!("not an increment operator"),
+        };
+        self.emit_line("    mov ecx, eax");
+        if postfix {
+            self.emit_load(ty, "[ecx]");
+        }
+        self.emit_line(&format!("    {} DWORD PTR [ecx], 1", step));
+        self.emit_line(&format!("    {} DWORD PTR [ecx + 4], 0", carry));
+        if !postfix {
+            self.emit_load(ty, "[ecx]");
+        }
+        Ok(())
+    }
+
+    /// Convert the i386 `long long` `ty` in edx:eax to the `float` or `double`
+    /// `to` in xmm0. SSE only converts 32-bit integers there, but the x87
+    /// unit loads 64-bit ones; an unsigned value with the top bit set loads
+    /// as 2^64 less than it is
+    fn emit_wide_to_floating(&mut self, ty: &Type, to: &Type) -> Result<()> {
+        self.uses_floating_point = true;
+        let (_, mov, width) = floating_operand(to);
+        self.emit_push("edx");
+        self.emit_push("eax");
+        self.emit_line("    fild QWORD PTR [esp]");
+        if ty.is_unsigned() {
+            let done = self.new_label("wide_float");
+            self.emit_line("    test edx, edx");
+            self.emit_line(&format!("    jns {}", done));
+            // 2^64 as a float
+            self.emit_line(&format!("    mov DWORD PTR [esp], {}", 0x5f80_0000));
+            self.emit_line("    fadd DWORD PTR [esp]");
+            self.emit_line(&format!("{}:", done));
+        }
+        self.emit_line(&format!("    fstp {} PTR [esp]", width));
+        self.emit_line(&format!("    {} xmm0, {} PTR [esp]", mov, width));
+        self.emit_stack_release(8);
+        Ok(())
+    }
+
+    /// Truncate the `float` or `double` `from` in xmm0 to an i386 `long long`
+    /// in edx:eax, with an x87 store while its rounding is set to truncate
+    fn emit_floating_to_wide(&mut self, from: &Type) -> Result<()> {
+        let (_, mov, width) = floating_operand(from);
+        self.emit_stack_reserve(12);
+        self.emit_line(&format!("    {} {} PTR [esp], xmm0", mov, width));
+        self.emit_line(&format!("    fld {} PTR [esp]", width));
+        self.emit_line("    fnstcw WORD PTR [esp + 8]");
+        self.emit_line("    movzx eax, WORD PTR [esp + 8]");
+        self.emit_line("    or eax, 3072");
+        self.emit_line("    mov WORD PTR [esp + 10], ax");
+        self.emit_line("    fldcw WORD PTR [esp + 10]");
+        self.emit_line("    fistp QWORD PTR [esp]");
+        self.emit_line("    fldcw WORD PTR [esp + 8]");
+        self.emit_line("    mov eax, DWORD PTR [esp]");
+        self.emit_line("    mov edx, DWORD PTR [esp + 4]");
+        self.emit_stack_release(12);
+        Ok(())
+    }
+
+    /// Move the address in the result register from an aggregate of type
+    /// `aggregate` to its `member`
+    fn emit_member_offset(&mut self, aggregate: Option<Type>, member: &str) -> Result<()> {
+        let (offset, _) = aggregate
+            .and_then(|aggregate| self.layout.member(&aggregate, member))
+            .ok_or_else(|| AleccError::CodegenError {
+                message: format!("no member named '{}'", member),
+                span: None,
+            })?;
+        if offset > 0 {
+            match self.target {
+                Target::I386 => self.emit_line(&format!("    add eax, {}", offset)),
+                Target::Amd64 => self.emit_line(&format!("    add rax, {}", offset)),
+                Target::Arm64 => self.emit_line(&format!("    add x0, x0, #{}", offset)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `emit_lvalue_address`, but a struct returned by value at the root of
+    /// a `.` chain (`f().x`) is first spilled to the stack. Returns the bytes to
+    /// pop once the member has been read
+    fn emit_object_address(&mut self, expr: &Expression) -> Result<usize> {
+        // An array member of a returned struct decays into that struct's temporary
+        if let ExpressionKind::Index { array, index } = &expr.kind {
+            let element = self
+                .expression_type(array)
+                .filter(|ty| matches!(ty, Type::Array(..)))
+                .and_then(|ty| self.pointee(&ty));
+            if let Some(element) = element.filter(|_| !array.is_lvalue()) {
+                let spilled = self.emit_object_address(array)?;
+                self.emit_element_address(index, self.layout.size_of(&element).max(1))?;
+                return Ok(spilled);
             }
-            Expression::Assignment {
-                target,
-                operator,
-                value,
-            } => {
-                // Handle compound assignment operators
-                match operator {
-                    crate::parser::AssignmentOperator::Assign => {
-                        // Simple assignment: target = value
-                        self.generate_expression(value)?;
-                        self.store_in_target(target)?;
-                    }
-                    crate::parser::AssignmentOperator::PlusAssign => {
-                        // target += value  =>  target = target + value
-                        self.load_from_target(target)?; // Load current value
-                        self.emit_line("    push rax"); // Save current value
-                        self.generate_expression(value)?; // Generate RHS
-                        self.emit_line("    pop rbx"); // Restore current value
-                        self.emit_line("    add rax, rbx"); // target + value
-                        self.store_in_target(target)?; // Store result
-                    }
-                    crate::parser::AssignmentOperator::MinusAssign => {
-                        // target -= value  =>  target = target - value
-                        self.load_from_target(target)?;
-                        self.emit_line("    push rax");
-                        self.generate_expression(value)?;
-                        self.emit_line("    mov rbx, rax"); // RHS in rbx
-                        self.emit_line("    pop rax"); // Current value in rax
-                        self.emit_line("    sub rax, rbx"); // target - value
-                        self.store_in_target(target)?;
-                    }
-                    crate::parser::AssignmentOperator::MultiplyAssign => {
-                        // target *= value  =>  target = target * value
-                        self.load_from_target(target)?;
-                        self.emit_line("    push rax");
-                        self.generate_expression(value)?;
-                        self.emit_line("    pop rbx");
-                        self.emit_line("    imul rax, rbx"); // target * value
-                        self.store_in_target(target)?;
-                    }
-                    crate::parser::AssignmentOperator::DivideAssign => {
-                        // target /= value  =>  target = target / value
-                        self.load_from_target(target)?;
-                        self.emit_line("    push rax");
-                        self.generate_expression(value)?;
-                        self.emit_line("    mov rbx, rax"); // RHS in rbx
-                        self.emit_line("    pop rax"); // Current value in rax
-                        self.emit_line("    cqo"); // Sign extend for division
-                        self.emit_line("    idiv rbx"); // target / value
-                        self.store_in_target(target)?;
-                    }
-                    _ => {
-                        return Err(AleccError::CodegenError {
-                            message: "Assignment operator not implemented".to_string(),
-                        });
-                    }
-                }
+        }
+        let ExpressionKind::Member {
+            object,
+            member,
+            is_arrow: false,
+        } = &expr.kind
+        else {
+            self.emit_lvalue_address(expr)?;
+            return Ok(0);
+        };
+        if object.is_lvalue() {
+            self.emit_lvalue_address(expr)?;
+            return Ok(0);
+        }
+
+        let spilled = if matches!(
+            &object.kind,
+            ExpressionKind::Member {
+                is_arrow: false,
+                ..
             }
-            _ => {
+        ) {
+            self.emit_object_address(object)?
+        } else if self
+            .expression_type(object)
+            .is_some_and(|ty| self.aggregate(&ty).is_some())
+        {
+            self.emit_aggregate_address(object)?
+        } else {
+            let aggregate = self.expression_type(object);
+            let size = aggregate.as_ref().map_or(0, |ty| self.layout.size_of(ty));
+            if size > self.target.pointer_size() {
                 return Err(AleccError::CodegenError {
-                    message: "Expression type not implemented".to_string(),
+                    message: format!(
+                        "member '{}' of a struct of {} bytes returned by value does not fit in a register",
+                        member, size
+                    ),
+                    span: Some(expr.span),
                 });
             }
-        }
-        Ok(())
+            self.generate_expression(object)?;
+            match self.target {
+                Target::I386 => {
+                    self.emit_push("eax");
+                    self.emit_line("    mov eax, esp");
+                    4
+                }
+                Target::Amd64 => {
+                    self.emit_push("rax");
+                    self.emit_line("    mov rax, rsp");
+                    8
+                }
+                Target::Arm64 => {
+                    self.emit_push("x0");
+                    self.emit_line("    mov x0, sp");
+                    16
+                }
+            }
+        };
+        self.emit_member_offset(self.expression_type(object), member)?;
+        Ok(spilled)
     }
 
-    #[allow(dead_code)]
-    fn push_argument(&mut self, _index: usize) -> Result<()> {
+    /// Add `index` scaled by `size` to the address in the result register
+    fn emit_element_address(&mut self, index: &Expression, size: usize) -> Result<()> {
+        match self.target {
+            Target::I386 => self.emit_push("eax"),
+            Target::Amd64 => self.emit_push("rax"),
+            Target::Arm64 => self.emit_push("x0"),
+        }
+        self.generate_expression(index)?;
         match self.target {
             Target::I386 => {
-                self.emit_line("    push eax");
+                self.emit_line(&format!("    imul eax, {}", size));
+                self.emit_pop("ecx");
+                self.emit_line("    add eax, ecx");
             }
             Target::Amd64 => {
-                // Use calling convention registers
-                self.emit_line("    push rax"); // Simplified
+                self.emit_line(&format!("    imul rax, {}", size));
+                self.emit_pop("r11");
+                self.emit_line("    add rax, r11");
             }
             Target::Arm64 => {
-                // Use calling convention registers
-                self.emit_line("    str x0, [sp, #-16]!"); // Simplified
+                self.emit_line(&format!("    mov x9, #{}", size));
+                self.emit_line("    mul x0, x0, x9");
+                self.emit_pop("x9");
+                self.emit_line("    add x0, x0, x9");
             }
         }
         Ok(())
     }
 
-    fn load_from_target(&mut self, target: &Expression) -> Result<()> {
-        // Load the current value of target into rax
-        if let Expression::Identifier(name) = target {
-            if let Some(&offset) = self.local_variables.get(name) {
-                match self.target {
-                    Target::Amd64 => {
-                        self.emit_line(&format!("    mov rax, QWORD PTR [rbp + {}]", offset));
-                    }
-                    Target::I386 => {
-                        self.emit_line(&format!("    mov eax, DWORD PTR [ebp + {}]", offset));
-                    }
-                    Target::Arm64 => {
-                        self.emit_line(&format!("    ldr x0, [x29, #{}]", offset));
-                    }
-                }
-            } else {
-                // Global variable
-                match self.target {
-                    Target::Amd64 => {
-                        self.emit_line(&format!("    mov rax, QWORD PTR [{}]", name));
-                    }
-                    Target::I386 => {
-                        self.emit_line(&format!("    mov eax, DWORD PTR [{}]", name));
-                    }
-                    Target::Arm64 => {
-                        self.emit_line(&format!("    adrp x1, {}", name));
-                        self.emit_line(&format!("    add x1, x1, :lo12:{}", name));
-                        self.emit_line("    ldr x0, [x1]");
-                    }
-                }
-            }
-        } else {
-            return Err(AleccError::CodegenError {
-                message: "Complex assignment targets not supported for compound operators yet"
-                    .to_string(),
-            });
+    /// Drop what `emit_object_address` spilled, keeping the result register
+    fn emit_release(&mut self, bytes: usize) {
+        if bytes == 0 {
+            return;
         }
-        Ok(())
-    }
-
-    fn store_in_target(&mut self, target: &Expression) -> Result<()> {
-        // Store rax value into target
-        if let Expression::Identifier(name) = target {
-            if let Some(&offset) = self.local_variables.get(name) {
-                match self.target {
-                    Target::Amd64 => {
-                        self.emit_line(&format!("    mov QWORD PTR [rbp + {}], rax", offset));
-                    }
-                    Target::I386 => {
-                        self.emit_line(&format!("    mov DWORD PTR [ebp + {}], eax", offset));
-                    }
-                    Target::Arm64 => {
-                        self.emit_line(&format!("    str x0, [x29, #{}]", offset));
-                    }
-                }
-            } else {
-                // Global variable
-                match self.target {
-                    Target::Amd64 => {
-                        self.emit_line(&format!("    mov QWORD PTR [{}], rax", name));
-                    }
-                    Target::I386 => {
-                        self.emit_line(&format!("    mov DWORD PTR [{}], eax", name));
-                    }
-                    Target::Arm64 => {
-                        self.emit_line(&format!("    adrp x1, {}", name));
-                        self.emit_line(&format!("    add x1, x1, :lo12:{}", name));
-                        self.emit_line("    str x0, [x1]");
-                    }
-                }
-            }
-        } else {
-            return Err(AleccError::CodegenError {
-                message: "Complex assignment targets not supported for compound operators yet"
-                    .to_string(),
-            });
+        match self.target {
+            Target::I386 => self.emit_stack_release(bytes),
+            Target::Amd64 => self.emit_stack_release(bytes),
+            Target::Arm64 => self.emit_stack_release(bytes),
         }
-        Ok(())
     }
 
     fn emit_conditional_jump(&mut self, condition: bool, label: &str) -> Result<()> {
@@ -1290,29 +6455,226 @@ impl CodeGenerator {
         Ok(())
     }
 
-    fn emit_global_variable(&mut self, name: &str, var_type: &Type) -> Result<()> {
-        let size = self.get_type_size(var_type);
+    fn emit_global_variable(
+        &mut self,
+        name: &str,
+        var_type: &Type,
+        initializer: Option<&Expression>,
+    ) -> Result<()> {
+        let size = self.layout.size_of(var_type);
+        let value = self.global_value(name, var_type, initializer)?;
         self.emit_line(&format!("{}:", name));
-        match size {
-            1 => self.emit_line("    .byte 0"),
-            2 => self.emit_line("    .word 0"),
-            4 => self.emit_line("    .long 0"),
-            8 => self.emit_line("    .quad 0"),
-            _ => self.emit_line(&format!("    .zero {}", size)),
+        match (value, size) {
+            // Zeros may go in .bss, which holds no data
+            (GlobalValue::Bits(0), _) => self.emit_line(&format!("    .zero {}", size)),
+            (GlobalValue::Bits(value), 1) => self.emit_line(&format!("    .byte {}", value as i8)),
+            // .word is four bytes on arm64, .short is two everywhere
+            (GlobalValue::Bits(value), 2) => {
+                self.emit_line(&format!("    .short {}", value as i16))
+            }
+            (GlobalValue::Bits(value), 4) => self.emit_line(&format!("    .long {}", value as i32)),
+            (GlobalValue::Bits(value), 8) => self.emit_line(&format!("    .quad {}", value)),
+            (GlobalValue::Bits(_), _) => self.emit_line(&format!("    .zero {}", size)),
+            (GlobalValue::Address(symbol, offset), size) => {
+                let directive = if size == 4 { ".long" } else { ".quad" };
+                let address = match offset {
+                    0 => symbol,
+                    offset if offset < 0 => format!("{}{}", symbol, offset),
+                    offset => format!("{}+{}", symbol, offset),
+                };
+                self.emit_line(&format!("    {} {}", directive, address));
+            }
+            (GlobalValue::Characters(content), size) => {
+                self.emit_line(&format!("    .ascii \"{}\"", self.escape_string(&content)));
+                if size > content.len() {
+                    self.emit_line(&format!("    .zero {}", size - content.len()));
+                }
+            }
         }
         Ok(())
     }
 
-    fn get_type_size(&self, var_type: &Type) -> usize {
-        match var_type {
-            Type::Char => 1,
-            Type::Short => 2,
-            Type::Int => 4,
-            Type::Long => self.target.pointer_size(),
-            Type::Float => 4,
-            Type::Double => 8,
-            Type::Pointer(_) => self.target.pointer_size(),
-            _ => self.target.pointer_size(), // Default
+    /// Whether a global starts out all zeros, so it can go in .bss: it has
+    /// no initializer, or one that folds to zero
+    fn starts_zeroed(&self, name: &str, var_type: &Type, initializer: Option<&Expression>) -> bool {
+        matches!(
+            self.global_value(name, var_type, initializer),
+            Ok(GlobalValue::Bits(0))
+        )
+    }
+
+    /// What global `name` starts out holding: the constant its initializer
+    /// folds to, an address the linker fills in, or the characters of a
+    /// string literal. Anything else is an error, as it is for GCC
+    fn global_value(
+        &self,
+        name: &str,
+        var_type: &Type,
+        initializer: Option<&Expression>,
+    ) -> Result<GlobalValue> {
+        let Some(initializer) = initializer else {
+            return Ok(GlobalValue::Bits(0));
+        };
+        let constant = |initializer: &Expression| {
+            ConstantEvaluator::new()
+                .with_layout(&self.layout)
+                .evaluate(initializer)
+                .ok()
+        };
+        let ty = self.layout.resolve(var_type);
+        let word = self.target.pointer_size();
+        let value = match &ty {
+            Type::Bool => constant(initializer).map(|value| GlobalValue::Bits((value != 0) as i64)),
+            Type::Char
+            | Type::Short
+            | Type::Int
+            | Type::Long
+            | Type::LongLong
+            | Type::UnsignedChar
+            | Type::UnsignedShort
+            | Type::UnsignedInt
+            | Type::UnsignedLong
+            | Type::UnsignedLongLong
+            | Type::Pointer(_) => constant(initializer).map(GlobalValue::Bits).or_else(|| {
+                // An address only fits in an integer as wide as a pointer
+                (self.layout.size_of(&ty) == word)
+                    .then(|| self.address_constant(initializer))
+                    .flatten()
+                    .map(|(symbol, offset)| GlobalValue::Address(symbol, offset))
+            }),
+            Type::Float | Type::Double => floating_constant(initializer).map(|value| {
+                GlobalValue::Bits(match ty {
+                    Type::Float => (value as f32).to_bits() as i64,
+                    _ => value.to_bits() as i64,
+                })
+            }),
+            Type::Array(element, Some(length)) => {
+                match (&initializer.kind, self.layout.resolve(element)) {
+                    (ExpressionKind::StringLiteral(content), Type::Char | Type::UnsignedChar)
+                        if content.len() <= *length =>
+                    {
+                        Some(GlobalValue::Characters(content.clone()))
+                    }
+                    _ => {
+                        return Err(AleccError::CodegenError {
+                            message: format!("initializer for array '{}' is not supported", name),
+                            span: Some(initializer.span),
+                        })
+                    }
+                }
+            }
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("initializer for '{}' is not supported", name),
+                    span: Some(initializer.span),
+                })
+            }
+        };
+        value.ok_or_else(|| AleccError::CodegenError {
+            message: "initializer element is not constant".to_string(),
+            span: Some(initializer.span),
+        })
+    }
+
+    /// `initializer` as an address constant: where a global, a function or
+    /// a string literal is, plus a constant number of bytes
+    fn address_constant(&self, initializer: &Expression) -> Option<(String, i64)> {
+        match &initializer.kind {
+            ExpressionKind::StringLiteral(content) => {
+                Some((self.string_literals.get(content)?.clone(), 0))
+            }
+            // Arrays and functions stand for their address
+            ExpressionKind::Identifier(name) => match self.global_variables.get(name) {
+                Some(ty) if matches!(self.layout.resolve(ty), Type::Array(..)) => {
+                    self.object_address(initializer)
+                }
+                Some(_) => None,
+                None => self.object_address(initializer),
+            },
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => self.object_address(operand),
+            ExpressionKind::Cast { expression, .. } => self.address_constant(expression),
+            ExpressionKind::Binary {
+                left,
+                operator: operator @ (BinaryOperator::Add | BinaryOperator::Subtract),
+                right,
+            } => {
+                // Either operand of `+` can be the pointer
+                let (pointer, (symbol, offset), count) = match self.address_constant(left) {
+                    Some(address) => (left, address, right),
+                    None if matches!(operator, BinaryOperator::Add) => {
+                        (right, self.address_constant(right)?, left)
+                    }
+                    None => return None,
+                };
+                let count = ConstantEvaluator::new()
+                    .with_layout(&self.layout)
+                    .evaluate(count)
+                    .ok()?;
+                // Arithmetic on void pointers counts bytes
+                let size =
+                    self.expression_type(pointer)
+                        .and_then(|ty| self.pointee(&ty))
+                        .map_or(1, |ty| self.layout.size_of(&ty).max(1)) as i64;
+                let bytes = match operator {
+                    BinaryOperator::Subtract => -count * size,
+                    _ => count * size,
+                };
+                Some((symbol, offset + bytes))
+            }
+            _ => None,
+        }
+    }
+
+    /// The address of the object `lvalue` designates, when it is a global
+    /// or part of one, or of the function it names
+    fn object_address(&self, lvalue: &Expression) -> Option<(String, i64)> {
+        match &lvalue.kind {
+            // Each thread has its own copy, at no fixed address
+            ExpressionKind::Identifier(name) if self.binding.thread_local.contains(name) => None,
+            ExpressionKind::Identifier(name) if self.global_variables.contains_key(name) => {
+                Some((name.clone(), 0))
+            }
+            ExpressionKind::Identifier(name) if self.return_types.contains_key(name) => Some((
+                linked_symbol(name, &self.wrapped_symbols, &self.defined_functions),
+                0,
+            )),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => self.address_constant(operand),
+            ExpressionKind::Index { array, index } => {
+                let (symbol, offset) = self.address_constant(array)?;
+                let index = ConstantEvaluator::new()
+                    .with_layout(&self.layout)
+                    .evaluate(index)
+                    .ok()?;
+                let element = self.pointee(&self.expression_type(array)?)?;
+                let size = self.layout.size_of(&element) as i64;
+                Some((symbol, offset + index * size))
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let (symbol, offset) = if *is_arrow {
+                    self.address_constant(object)?
+                } else {
+                    self.object_address(object)?
+                };
+                let aggregate = self.expression_type(object)?;
+                let aggregate = if *is_arrow {
+                    self.pointee(&aggregate)?
+                } else {
+                    aggregate
+                };
+                let (member_offset, _) = self.layout.member(&aggregate, member)?;
+                Some((symbol, offset + member_offset as i64))
+            }
+            _ => None,
         }
     }
 
@@ -1320,15 +6682,22 @@ impl CodeGenerator {
         if let Some(label) = self.string_literals.get(content) {
             label.clone()
         } else {
-            let label = format!(".LC{}", self.string_literals.len());
+            let label = format!(".L{}C{}", self.label_prefix, self.string_literals.len());
             self.string_literals
                 .insert(content.to_string(), label.clone());
             label
         }
     }
 
+    /// The assembly label of a label in the source, `.L{prefix}{function}.{label}`.
+    /// The dot keeps it apart from the labels `new_label` makes and from
+    /// those of other functions.
+    fn user_label(&self, label: &str) -> String {
+        format!(".L{}{}.{}", self.label_prefix, self.current_function, label)
+    }
+
     fn new_label(&mut self, prefix: &str) -> String {
-        let label = format!(".L{}_{}", prefix, self.label_counter);
+        let label = format!(".L{}{}_{}", self.label_prefix, prefix, self.label_counter);
         self.label_counter += 1;
         label
     }
@@ -1338,6 +6707,63 @@ impl CodeGenerator {
         self.output.push('\n');
     }
 
+    /// Push `register`: a word on x86, 16 bytes on arm64, where sp stays aligned
+    fn emit_push(&mut self, register: &str) {
+        match self.target {
+            Target::Arm64 => {
+                self.emit_line(&format!("    str {}, [sp, #-16]!", register));
+                self.record_stack_move(16);
+            }
+            Target::I386 | Target::Amd64 => {
+                self.emit_line(&format!("    push {}", register));
+                self.record_stack_move(self.target.pointer_size() as i64);
+            }
+        }
+    }
+
+    /// Pop what `emit_push` pushed into `register`
+    fn emit_pop(&mut self, register: &str) {
+        match self.target {
+            Target::Arm64 => {
+                self.emit_line(&format!("    ldr {}, [sp], #16", register));
+                self.record_stack_move(-16);
+            }
+            Target::I386 | Target::Amd64 => {
+                self.emit_line(&format!("    pop {}", register));
+                self.record_stack_move(-(self.target.pointer_size() as i64));
+            }
+        }
+    }
+
+    /// Move the stack pointer down by `bytes`, for temporaries or arguments
+    fn emit_stack_reserve(&mut self, bytes: usize) {
+        let line = match self.target {
+            Target::I386 => format!("    sub esp, {}", bytes),
+            Target::Amd64 => format!("    sub rsp, {}", bytes),
+            Target::Arm64 => format!("    sub sp, sp, #{}", bytes),
+        };
+        self.emit_line(&line);
+        self.record_stack_move(bytes as i64);
+    }
+
+    /// Give back `bytes` that `emit_stack_reserve` or pushes took
+    fn emit_stack_release(&mut self, bytes: usize) {
+        let line = match self.target {
+            Target::I386 => format!("    add esp, {}", bytes),
+            Target::Amd64 => format!("    add rsp, {}", bytes),
+            Target::Arm64 => format!("    add sp, sp, #{}", bytes),
+        };
+        self.emit_line(&line);
+        self.record_stack_move(-(bytes as i64));
+    }
+
+    /// Count `bytes` the stack grew (shrank, when negative) by at the
+    /// instruction just emitted, or by a callee's `ret n` after a call
+    fn record_stack_move(&mut self, bytes: i64) {
+        self.stack_depth += bytes;
+        self.stack_moves.push((self.output.len(), bytes));
+    }
+
     fn escape_string(&self, s: &str) -> String {
         s.replace('\\', "\\\\")
             .replace('"', "\\\"")
@@ -1347,21 +6773,21 @@ impl CodeGenerator {
     }
 
     fn collect_string_literals_from_statement(&mut self, stmt: &Statement) -> Result<()> {
-        match stmt {
-            Statement::Expression(expr) => self.collect_string_literals_from_expression(expr),
-            Statement::Block(statements) => {
+        match &stmt.kind {
+            StatementKind::Expression(expr) => self.collect_string_literals_from_expression(expr),
+            StatementKind::Block(statements) => {
                 for stmt in statements {
                     self.collect_string_literals_from_statement(stmt)?;
                 }
                 Ok(())
             }
-            Statement::Return(expr) => {
+            StatementKind::Return(expr) => {
                 if let Some(expr) = expr {
                     self.collect_string_literals_from_expression(expr)?;
                 }
                 Ok(())
             }
-            Statement::If {
+            StatementKind::If {
                 condition,
                 then_stmt,
                 else_stmt,
@@ -1373,12 +6799,12 @@ impl CodeGenerator {
                 }
                 Ok(())
             }
-            Statement::While { condition, body } => {
+            StatementKind::While { condition, body } => {
                 self.collect_string_literals_from_expression(condition)?;
                 self.collect_string_literals_from_statement(body)?;
                 Ok(())
             }
-            Statement::For {
+            StatementKind::For {
                 init,
                 condition,
                 increment,
@@ -1396,7 +6822,7 @@ impl CodeGenerator {
                 self.collect_string_literals_from_statement(body)?;
                 Ok(())
             }
-            Statement::Declaration { initializer, .. } => {
+            StatementKind::Declaration { initializer, .. } => {
                 if let Some(expr) = initializer {
                     self.collect_string_literals_from_expression(expr)?;
                 }
@@ -1407,21 +6833,21 @@ impl CodeGenerator {
     }
 
     fn collect_string_literals_from_expression(&mut self, expr: &Expression) -> Result<()> {
-        match expr {
-            Expression::StringLiteral(value) => {
+        match &expr.kind {
+            ExpressionKind::StringLiteral(value) => {
                 self.get_string_literal_label(value);
                 Ok(())
             }
-            Expression::Binary { left, right, .. } => {
+            ExpressionKind::Binary { left, right, .. } => {
                 self.collect_string_literals_from_expression(left)?;
                 self.collect_string_literals_from_expression(right)?;
                 Ok(())
             }
-            Expression::Unary { operand, .. } => {
+            ExpressionKind::Unary { operand, .. } => {
                 self.collect_string_literals_from_expression(operand)?;
                 Ok(())
             }
-            Expression::Call {
+            ExpressionKind::Call {
                 function,
                 arguments,
             } => {
@@ -1431,12 +6857,483 @@ impl CodeGenerator {
                 }
                 Ok(())
             }
-            Expression::Assignment { target, value, .. } => {
+            ExpressionKind::Assignment { target, value, .. } => {
                 self.collect_string_literals_from_expression(target)?;
                 self.collect_string_literals_from_expression(value)?;
                 Ok(())
             }
+            ExpressionKind::Index { array, index } => {
+                self.collect_string_literals_from_expression(array)?;
+                self.collect_string_literals_from_expression(index)?;
+                Ok(())
+            }
+            ExpressionKind::Member { object, .. } => {
+                self.collect_string_literals_from_expression(object)?;
+                Ok(())
+            }
+            ExpressionKind::Cast { expression, .. } => {
+                self.collect_string_literals_from_expression(expression)
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.collect_string_literals_from_expression(condition)?;
+                self.collect_string_literals_from_expression(then_expr)?;
+                self.collect_string_literals_from_expression(else_expr)
+            }
             _ => Ok(()), // Other expression types don't contain string literals
         }
     }
 }
+
+/// Whether a function body (amd64) leaves the stack pointer alone: it makes no
+/// calls and never pushes, so nothing is written below rsp but its own locals.
+/// An empty body marks a declaration without a definition
+/// The type the usual arithmetic conversions give two operands, if either
+/// is `float` or `double`
+fn common_floating(left: Option<Type>, right: Option<Type>) -> Option<Type> {
+    match (left, right) {
+        (Some(Type::Double), _) | (_, Some(Type::Double)) => Some(Type::Double),
+        (Some(ty), _) | (_, Some(ty)) => Some(ty),
+        (None, None) => None,
+    }
+}
+
+/// The value of a floating initializer: a literal, possibly negated, or an integer
+/// The `long long` type i386 computes in when either operand is one
+fn common_wide(left: Option<Type>, right: Option<Type>) -> Option<Type> {
+    match (left, right) {
+        (None, None) => None,
+        (Some(Type::UnsignedLongLong), _) | (_, Some(Type::UnsignedLongLong)) => {
+            Some(Type::UnsignedLongLong)
+        }
+        _ => Some(Type::LongLong),
+    }
+}
+
+/// The memory operand four bytes above `address`, where the high half of an
+/// i386 double or `long long` is
+fn high_word(address: &str) -> String {
+    let inner = address.trim_end_matches(']');
+    let offset = inner
+        .rsplit_once(" + ")
+        .and_then(|(base, offset)| Some((base, offset.parse::<i64>().ok()?)));
+    match offset {
+        Some((base, offset)) => format!("{} + {}]", base, offset + 4),
+        None => format!("{} + 4]", inner),
+    }
+}
+
+/// The SSE suffix of scalar instructions on `ty`: `ss` for float, `sd` for double
+fn sse_suffix(ty: &Type) -> &'static str {
+    match ty {
+        Type::Float => "ss",
+        _ => "sd",
+    }
+}
+
+/// The arm64 name prefix of a floating-point register holding a `ty`
+/// The floating type a piece of a struct or union is moved as; a `double`
+/// also carries two `float`s
+fn piece_type(piece: &Piece) -> Type {
+    match piece.size {
+        4 => Type::Float,
+        _ => Type::Double,
+    }
+}
+
+/// `size` bytes as loads of 8, 4, 2 and 1 bytes, none wider than `widest`,
+/// with their offsets
+fn chunks(size: usize, widest: usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut at = 0;
+    while at < size {
+        let width = [8, 4, 2, 1]
+            .into_iter()
+            .find(|&width| width <= widest && width <= size - at)
+            .expect("1 always fits");
+        chunks.push((at, width));
+        at += width;
+    }
+    chunks
+}
+
+fn fp_prefix(ty: &Type) -> char {
+    match ty {
+        Type::Float => 's',
+        _ => 'd',
+    }
+}
+
+/// Which symbols position-independent code reaches through the GOT, and
+/// calls through the PLT
+#[derive(Debug, Clone, Default)]
+pub struct SymbolBinding {
+    /// Code for a PIE or a shared object, which can't use absolute addresses
+    pub position_independent: bool,
+    /// Code for a shared object, where another module may interpose any
+    /// symbol it exports
+    pub shared_object: bool,
+    /// Functions and variables the translation unit defines
+    pub defined: HashSet<String>,
+    /// Those with internal linkage, which nothing can interpose
+    pub local: HashSet<String>,
+    /// Variables each thread has its own copy of
+    pub thread_local: HashSet<String>,
+}
+
+impl SymbolBinding {
+    pub fn through_got(&self, symbol: &str) -> bool {
+        // Local labels (strings and the like) never leave the object
+        if symbol.starts_with(".L") || self.local.contains(symbol) {
+            return false;
+        }
+        self.shared_object || (self.position_independent && !self.defined.contains(symbol))
+    }
+
+    /// Whether thread-local `symbol` is at an offset from the thread pointer
+    /// the linker knows (the local-exec model), which only holds for one an
+    /// executable defines; the others take their offset from the GOT
+    pub fn local_exec(&self, symbol: &str) -> bool {
+        !self.shared_object && (self.defined.contains(symbol) || self.local.contains(symbol))
+    }
+}
+
+/// The prologue that saves the caller's frame pointer and points it at the
+/// new frame, with the unwind directives that follow the canonical frame
+/// address (the stack pointer before the call) through it. Registers are
+/// given by DWARF number
+pub fn frame_entry(target: Target) -> &'static [&'static str] {
+    match target {
+        Target::I386 => &[
+            "push ebp",
+            ".cfi_def_cfa_offset 8",
+            ".cfi_offset 5, -8",
+            "mov ebp, esp",
+            ".cfi_def_cfa_register 5",
+        ],
+        Target::Amd64 => &[
+            "push rbp",
+            ".cfi_def_cfa_offset 16",
+            ".cfi_offset 6, -16",
+            "mov rbp, rsp",
+            ".cfi_def_cfa_register 6",
+        ],
+        Target::Arm64 => &[
+            "stp x29, x30, [sp, #-16]!",
+            ".cfi_def_cfa_offset 16",
+            ".cfi_offset 29, -16",
+            ".cfi_offset 30, -8",
+            "mov x29, sp",
+            ".cfi_def_cfa_register 29",
+        ],
+    }
+}
+
+/// The epilogue undoing `frame_entry` and returning. Code after a return in
+/// the middle of a function still has the frame, so the unwind state from
+/// before it is restored after it
+pub fn frame_exit(target: Target) -> &'static [&'static str] {
+    match target {
+        Target::I386 => &[
+            ".cfi_remember_state",
+            "mov esp, ebp",
+            "pop ebp",
+            ".cfi_restore 5",
+            ".cfi_def_cfa 4, 4",
+            "ret",
+            ".cfi_restore_state",
+        ],
+        Target::Amd64 => &[
+            ".cfi_remember_state",
+            "mov rsp, rbp",
+            "pop rbp",
+            ".cfi_restore 6",
+            ".cfi_def_cfa 7, 8",
+            "ret",
+            ".cfi_restore_state",
+        ],
+        Target::Arm64 => &[
+            ".cfi_remember_state",
+            "mov sp, x29",
+            ".cfi_def_cfa 31, 16",
+            "ldp x29, x30, [sp], #16",
+            ".cfi_restore 30",
+            ".cfi_restore 29",
+            ".cfi_def_cfa_offset 0",
+            "ret",
+            ".cfi_restore_state",
+        ],
+    }
+}
+
+/// The symbol a call to `function` names: `__wrap_<function>` when the link
+/// wraps a function this unit defines, which ld would otherwise leave alone
+pub fn linked_symbol(
+    function: &str,
+    wrapped: &HashSet<String>,
+    defined: &HashSet<String>,
+) -> String {
+    if wrapped.contains(function) && defined.contains(function) {
+        format!("__wrap_{}", function)
+    } else {
+        function.to_string()
+    }
+}
+
+/// The x86 and arm64 condition codes under which the integer comparison
+/// `operator` holds
+fn condition_codes(operator: &BinaryOperator, unsigned: bool) -> (&'static str, &'static str) {
+    match (operator, unsigned) {
+        (BinaryOperator::Equal, _) => ("e", "eq"),
+        (BinaryOperator::NotEqual, _) => ("ne", "ne"),
+        (BinaryOperator::Less, false) => ("l", "lt"),
+        (BinaryOperator::Less, true) => ("b", "lo"),
+        (BinaryOperator::Greater, false) => ("g", "gt"),
+        (BinaryOperator::Greater, true) => ("a", "hi"),
+        (BinaryOperator::LessEqual, false) => ("le", "le"),
+        (BinaryOperator::LessEqual, true) => ("be", "ls"),
+        (BinaryOperator::GreaterEqual, false) => ("ge", "ge"),
+        (BinaryOperator::GreaterEqual, true) => ("ae", "hs"),
+        _ => unreachable!("{:?} is not a comparison", operator),
+    }
+}
+
+/// The bytes of a `ty` on the stack, and the SSE move and operand width
+/// that read and write it
+fn floating_operand(ty: &Type) -> (usize, &'static str, &'static str) {
+    match ty {
+        Type::Float => (4, "movss", "DWORD"),
+        _ => (8, "movsd", "QWORD"),
+    }
+}
+
+fn floating_operands(operator: &BinaryOperator) -> AleccError {
+    AleccError::CodegenError {
+        message: format!("invalid floating-point operands to {:?}", operator),
+        span: None,
+    }
+}
+
+/// Whether computing `expression` touches no register but the result one
+fn is_leaf(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::IntegerLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Identifier(_) => true,
+        ExpressionKind::Cast { expression, .. } => is_leaf(expression),
+        _ => false,
+    }
+}
+
+fn is_prototype(function: &Function) -> bool {
+    matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+}
+
+fn is_red_zone_leaf(body: &str) -> bool {
+    body.lines().all(|line| {
+        let line = line.trim();
+        // Directives, like the unwind information's, and -fverbose-asm
+        // comments do nothing at run time
+        if line.starts_with('.') || line.starts_with('#') {
+            return true;
+        }
+        !line.starts_with("call ")
+            && !line.starts_with("push ")
+            && (line == "mov rsp, rbp" || !line.contains("rsp"))
+    })
+}
+
+/// An x86 function, from its label on, addressing its frame from the stack
+/// pointer instead of the frame pointer, or `None` when that can't be shown
+/// right. The prologue reserves the word the frame pointer was saved in
+/// along with the frame, so the stack stays aligned as before, and each
+/// `[rbp ± n]` becomes an offset from rsp that counts what the code has
+/// pushed since, by the `moves` the emitter recorded: where in `function`
+/// each instruction that moved the stack pointer ends, and how many bytes
+/// it pushed. The pushes move the canonical frame address with them. A
+/// red-zone leaf reserves nothing and finds its frame below the return
+/// address. The depth must agree at every label and be zero at each return,
+/// and the stack pointer may only move by pushes, pops and constant amounts
+fn without_frame_pointer(
+    function: &str,
+    moves: &[(usize, i64)],
+    target: Target,
+    frame_size: usize,
+) -> Option<String> {
+    let (stack_pointer, frame_pointer) = match target {
+        Target::I386 => ("esp", "ebp"),
+        Target::Amd64 => ("rsp", "rbp"),
+        // Direct arm64 code computes x29-relative addresses too many ways
+        Target::Arm64 => return None,
+    };
+    let word = target.pointer_size() as i64;
+    let indented =
+        |lines: &[&str]| -> String { lines.iter().map(|line| format!("    {}\n", line)).collect() };
+    let entry = indented(frame_entry(target));
+    let entry_at = function.find(&entry)?;
+    let text = function.replacen(&entry, "", 1);
+    let moves: HashMap<usize, i64> = moves
+        .iter()
+        .map(|&(end, bytes)| {
+            let end = if end > entry_at {
+                end - entry.len()
+            } else {
+                end
+            };
+            (end, bytes)
+        })
+        .collect();
+
+    // Where rbp would be, above rsp once the prologue is done
+    let allocation = format!("    sub {}, {}", stack_pointer, FRAME_SIZE_PLACEHOLDER);
+    let reserved = if text.contains(&allocation) {
+        frame_size as i64
+    } else {
+        -word
+    };
+    let exit = indented(frame_exit(target));
+
+    let mut output = String::with_capacity(text.len());
+    let mut depth = 0;
+    let mut labels = HashMap::new();
+    let mut jumps = Vec::new();
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(exit.as_str()) {
+            if depth != 0 {
+                return None;
+            }
+            output.push_str("    .cfi_remember_state\n");
+            if reserved + word > 0 {
+                output.push_str(&format!("    add {}, {}\n", stack_pointer, reserved + word));
+                output.push_str(&format!("    .cfi_def_cfa_offset {}\n", word));
+            }
+            output.push_str("    ret\n    .cfi_restore_state\n");
+            rest = after;
+            continue;
+        }
+        let (line, after) = rest.split_once('\n').unwrap_or((rest, ""));
+        rest = after;
+        if line == allocation {
+            let frame = reserved + word;
+            output.push_str(&format!("    sub {}, {}\n", stack_pointer, frame));
+            output.push_str(&format!("    .cfi_def_cfa_offset {}\n", frame + word));
+            continue;
+        }
+        let line = line.replace(FRAME_SIZE_PLACEHOLDER, &frame_size.to_string());
+        let instruction = line.trim();
+        if !line.starts_with(' ') && instruction.ends_with(':') {
+            labels.insert(instruction.trim_end_matches(':').to_string(), depth);
+        }
+        if !line.starts_with("    ") || instruction.starts_with('.') {
+            output.push_str(&line);
+            output.push('\n');
+            continue;
+        }
+
+        let (mnemonic, operands) = instruction.split_once(' ').unwrap_or((instruction, ""));
+        if mnemonic.starts_with('j') && operands.starts_with(".L") {
+            jumps.push((operands.to_string(), depth));
+        }
+        if instruction.contains(stack_pointer) {
+            let moves_by_constant = matches!(mnemonic, "sub" | "add")
+                && operands
+                    .strip_prefix(stack_pointer)
+                    .and_then(|amount| amount.strip_prefix(", "))
+                    .and_then(|amount| amount.split_whitespace().next())
+                    .is_some_and(|amount| amount.parse::<i64>().is_ok());
+            if !moves_by_constant {
+                return None;
+            }
+        }
+
+        // Offsets are written `[rbp - 8]`, and `[rbp + -8]` when computed
+        let base = format!("[{} ", frame_pointer);
+        let mut line = line.as_str();
+        let mut rewritten = String::new();
+        while let Some(start) = line.find(&base) {
+            rewritten.push_str(&line[..start]);
+            let operand = &line[start + base.len()..];
+            let end = operand.find(']')?;
+            let (sign, offset) = operand[..end].split_once(' ')?;
+            let offset: i64 = offset.parse().ok()?;
+            let offset = match sign {
+                "+" => offset,
+                "-" => -offset,
+                _ => return None,
+            } + reserved
+                + depth;
+            let sign = if offset < 0 { '-' } else { '+' };
+            rewritten.push_str(&format!(
+                "[{} {} {}]",
+                stack_pointer,
+                sign,
+                offset.unsigned_abs()
+            ));
+            line = &operand[end + 1..];
+        }
+        rewritten.push_str(line);
+        if rewritten.contains(frame_pointer) {
+            return None;
+        }
+        output.push_str(&rewritten);
+        output.push('\n');
+
+        let pushed = moves.get(&(text.len() - rest.len())).copied().unwrap_or(0);
+        if pushed != 0 {
+            depth += pushed;
+            output.push_str(&format!("    .cfi_adjust_cfa_offset {}\n", pushed));
+        }
+    }
+
+    let consistent = jumps
+        .iter()
+        .all(|(label, depth)| labels.get(label).is_none_or(|at| at == depth));
+    consistent.then_some(output)
+}
+
+/// The part of x86 register `register`, named by its 32 or 64-bit name, that
+/// holds `size` bytes
+fn x86_register(register: &str, size: usize) -> String {
+    if let Some(number) = register
+        .strip_prefix('r')
+        .filter(|n| n.starts_with(char::is_numeric))
+    {
+        return match size {
+            1 => format!("r{}b", number),
+            2 => format!("r{}w", number),
+            4 => format!("r{}d", number),
+            _ => register.to_string(),
+        };
+    }
+    let base = &register[1..2];
+    match size {
+        1 => format!("{}l", base),
+        2 => format!("{}x", base),
+        4 => format!("e{}x", base),
+        _ => format!("r{}x", base),
+    }
+}
+
+/// A case value converted to the promoted type of the controlling expression,
+/// `size` bytes wide, then extended to 64 bits as the value in the register is
+fn promoted_value(value: i64, size: usize, unsigned: bool) -> i64 {
+    if size >= 8 {
+        return value;
+    }
+    let shift = 64 - size as u32 * 8;
+    if unsigned {
+        ((value as u64) << shift >> shift) as i64
+    } else {
+        (value << shift) >> shift
+    }
+}
+
+/// Stack reserved for the arguments of an arm64 call that found no register:
+/// 8-byte slots, as AAPCS64 lays them out, rounded so sp stays 16-byte aligned
+fn arm64_stack_argument_bytes(stack_arguments: usize) -> usize {
+    (stack_arguments * 8 + 15) & !15
+}
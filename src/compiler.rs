@@ -1,20 +1,161 @@
-use crate::cli::Args;
+use crate::analysis::{
+    analyze, implicit_fallthroughs, noreturn_functions, noreturn_violations, return_problems,
+    uninitialized_uses, unreachable_code, NoreturnViolation, ReturnProblem,
+};
+use crate::cache::CompileCache;
+use crate::callgraph::CallGraph;
+use crate::cli::{
+    Args, AsDialect, AstFormat, Backend, CallGraphFormat, EmitKind, MissingReturn, StackProtector,
+};
 use crate::codegen::CodeGenerator;
+#[cfg(feature = "cranelift")]
+use crate::cranelift_backend::CraneliftGenerator;
+use crate::diagnostics::{Diagnostic, DiagnosticBuffer, Severity};
 use crate::error::{AleccError, Result};
-use crate::lexer::Lexer;
-use crate::linker::Linker;
-use crate::optimizer::{OptimizationLevel, Optimizer};
-use crate::parser::Parser;
-use crate::targets::Target;
+use crate::headers::{builtin_header, header_declarations};
+use crate::host::{find_tool, under_sysroot, Host};
+use crate::language::LanguageOptions;
+use crate::lexer::{Lexer, TokenType, Trivia};
+use crate::linker::{format_command, Linker};
+use crate::llvm_ir::LlvmIrGenerator;
+use crate::lower::LoweringContext;
+use crate::merge::{internalize, merge_programs, WholeProgram};
+use crate::optimizer::{prefix_internal_symbols, OptimizationLevel, Optimizer};
+use crate::parser::{Parser, Program, StatementKind, Type, TypeWarning};
+use crate::runtime::runtime_assembly;
+use crate::sema::Sema;
+use crate::summary::{llvm_instruction_counts, UnitSummary};
+use crate::targets::{Target, TargetInfo};
+use crate::warnings::WarningOptions;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::fs;
 use tracing::{debug, info, warn};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Preprocess,
+    Lex,
+    Parse,
+    Sema,
+    Optimize,
+    Codegen,
+    /// Waiting for a `--remote` daemon to compile a unit
+    #[allow(dead_code)]
+    Remote,
+    Assemble,
+    Link,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::Preprocess => "preprocess",
+            Phase::Lex => "lex",
+            Phase::Parse => "parse",
+            Phase::Sema => "sema",
+            Phase::Optimize => "optimize",
+            Phase::Codegen => "codegen",
+            Phase::Remote => "remote",
+            Phase::Assemble => "assemble",
+            Phase::Link => "link",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PhaseTiming {
+    pub phase: Phase,
+    /// Input file the phase ran for, `None` for whole-program phases like linking
+    pub input: Option<PathBuf>,
+    pub duration: Duration,
+}
+
+/// Everything a successful `Compiler::compile` run produced, for library users
+/// that need more than the exit status.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOutput {
+    /// Object files left on disk (`-c`); temporaries removed after linking are not listed
+    pub objects: Vec<PathBuf>,
+    /// Assembly files written with `-S` or `--save-asm-with-object`
+    pub assembly: Vec<PathBuf>,
+    /// Preprocessed sources written with `-E`
+    pub preprocessed: Vec<PathBuf>,
+    /// LLVM IR files written with `--emit=llvm-ir`
+    pub llvm_ir: Vec<PathBuf>,
+    /// Alecc IR files written with `--emit=ir`
+    pub ir: Vec<PathBuf>,
+    /// ASTs written with `--emit-ast=json`
+    pub ast: Vec<PathBuf>,
+    /// The call graph written with `--emit-callgraph`
+    pub callgraph: Option<PathBuf>,
+    /// Stack usage reports written with `--fstack-usage`
+    pub stack_usage: Vec<PathBuf>,
+    /// Linked executable or shared library, if the link step ran
+    pub executable: Option<PathBuf>,
+    /// Per-file reports for `--summary`, in input order
+    pub summaries: Vec<UnitSummary>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub timings: Vec<PhaseTiming>,
+}
+
+impl CompileOutput {
+    #[allow(dead_code)]
+    pub fn total_time(&self, phase: Phase) -> Duration {
+        self.timings
+            .iter()
+            .filter(|t| t.phase == phase)
+            .map(|t| t.duration)
+            .sum()
+    }
+}
+
+/// A source file after semantic analysis, or what it was written to when
+/// the job ends before code generation (`-E`, `--remote`, `--emit-ast`)
+enum FrontEnd {
+    Done(PathBuf),
+    Checked {
+        program: Box<Program>,
+        summary: Option<UnitSummary>,
+        /// The source text, which `--fverbose-asm` quotes
+        source: String,
+    },
+}
+
+/// A checked unit that `--fwhole-program` keeps back for merging
+struct CheckedUnit {
+    input: PathBuf,
+    program: Program,
+    summary: Option<UnitSummary>,
+}
+
+/// A unit's `--summary`, waiting for codegen's instruction counts
+struct PendingSummary {
+    summary: UnitSummary,
+    /// The functions of the unit, when it was merged with others
+    functions: Option<HashSet<String>>,
+}
+
 pub struct Compiler {
     args: Args,
     target: Target,
+    language: LanguageOptions,
     temp_files: Vec<PathBuf>,
+    output: CompileOutput,
+    diagnostics: DiagnosticBuffer,
+    /// What `-W` says about each warning
+    warnings: WarningOptions,
+    /// Warnings of the current unit that `-Werror` made errors
+    warnings_as_errors: usize,
+    cache: Option<CompileCache>,
+    /// Calls of the units compiled so far, for `--emit-callgraph`
+    callgraph: CallGraph,
 }
 
 impl Compiler {
@@ -24,14 +165,186 @@ impl Compiler {
                 target: args.target.clone(),
             })?;
 
+        if args.backend == Backend::Cranelift {
+            if !cfg!(feature = "cranelift") {
+                return Err(AleccError::InvalidArgument {
+                    message: "alecc was built without the cranelift backend (enable the `cranelift` feature)".to_string(),
+                });
+            }
+            if args.assembly_only {
+                return Err(AleccError::InvalidArgument {
+                    message: "-S is not supported with --backend=cranelift".to_string(),
+                });
+            }
+            if args.save_asm {
+                return Err(AleccError::InvalidArgument {
+                    message: "--save-asm-with-object is not supported with --backend=cranelift, which writes objects directly".to_string(),
+                });
+            }
+            if stack_protector(&args) != StackProtector::Off {
+                return Err(AleccError::InvalidArgument {
+                    message: "-fstack-protector is not supported with --backend=cranelift"
+                        .to_string(),
+                });
+            }
+            if args.function_sections || args.data_sections {
+                return Err(AleccError::InvalidArgument {
+                    message: "-ffunction-sections and -fdata-sections are not supported with --backend=cranelift".to_string(),
+                });
+            }
+        }
+
+        if args.emit_ast.is_some() && !cfg!(feature = "serde") {
+            return Err(AleccError::InvalidArgument {
+                message: "alecc was built without AST serialization (enable the `serde` feature)"
+                    .to_string(),
+            });
+        }
+
+        if args.emit_callgraph == Some(CallGraphFormat::Json) && !cfg!(feature = "serde") {
+            return Err(AleccError::InvalidArgument {
+                message: "alecc was built without JSON call graphs (enable the `serde` feature)"
+                    .to_string(),
+            });
+        }
+
+        if let Some(prefix) = &args.symbol_prefix {
+            if !prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+            {
+                return Err(AleccError::InvalidArgument {
+                    message: format!(
+                        "invalid symbol prefix '{}': only letters, digits, '_' and '.' are allowed",
+                        prefix
+                    ),
+                });
+            }
+        }
+
+        for definition in &args.defsym {
+            let valid = matches!(
+                definition.split_once('='),
+                Some((symbol, expression)) if !symbol.is_empty() && !expression.is_empty()
+            );
+            if !valid {
+                return Err(AleccError::InvalidArgument {
+                    message: format!("--defsym expects SYMBOL=EXPRESSION, not '{}'", definition),
+                });
+            }
+        }
+
+        if args.pie {
+            if target == Target::I386 {
+                return Err(AleccError::InvalidArgument {
+                    message: "--pie is not supported for i386".to_string(),
+                });
+            }
+            if args.nostdlib || args.static_link {
+                return Err(AleccError::InvalidArgument {
+                    message: format!(
+                        "--pie needs the dynamic loader to relocate the executable, which {} leaves out",
+                        if args.nostdlib { "--nostdlib" } else { "--static" }
+                    ),
+                });
+            }
+        }
+
+        if args.use_crt && (args.nostdlib || args.static_link) {
+            return Err(AleccError::InvalidArgument {
+                message: format!(
+                    "--fuse-crt needs libc's startup files and dynamic loader, which {} leaves out",
+                    if args.nostdlib {
+                        "--nostdlib"
+                    } else {
+                        "--static"
+                    }
+                ),
+            });
+        }
+
+        // __stack_chk_fail comes from libc, or from alecc's runtime without it
+        let links = !args.compile_only && !args.assembly_only;
+        if links
+            && args.static_link
+            && !args.nostdlib
+            && stack_protector(&args) != StackProtector::Off
+        {
+            return Err(AleccError::InvalidArgument {
+                message: "-fstack-protector needs __stack_chk_fail from libc, which --static leaves out; add --nostdlib to link alecc's runtime".to_string(),
+            });
+        }
+
+        if args.remote.is_some() {
+            if !cfg!(all(feature = "daemon", unix)) {
+                return Err(AleccError::InvalidArgument {
+                    message:
+                        "alecc was built without remote compilation (enable the `daemon` feature)"
+                            .to_string(),
+                });
+            }
+            let local_only = [
+                (args.assembly_only, "-S"),
+                (args.emit.is_some(), "--emit"),
+                (args.emit_ast.is_some(), "--emit-ast"),
+                (args.emit_callgraph.is_some(), "--emit-callgraph"),
+                (args.whole_program, "--fwhole-program"),
+                (args.stack_usage, "--fstack-usage"),
+                (args.save_asm, "--save-asm-with-object"),
+                (args.summary, "--summary"),
+            ];
+            if let Some((_, flag)) = local_only.iter().find(|(set, _)| *set) {
+                return Err(AleccError::InvalidArgument {
+                    message: format!(
+                        "{} is not supported with --remote, which only returns objects",
+                        flag
+                    ),
+                });
+            }
+        }
+
+        let language = match &args.standard {
+            Some(name) => LanguageOptions::from_std(name)?,
+            None => LanguageOptions::default(),
+        };
+
+        let diagnostics = DiagnosticBuffer::new(args.stream_diagnostics);
+        let warnings = args
+            .warnings
+            .iter()
+            .filter(|option| !option.starts_with("l,"))
+            .cloned()
+            .collect::<Vec<_>>();
+        let warnings = WarningOptions::new(&warnings);
+
         Ok(Self {
+            warnings,
+            warnings_as_errors: 0,
             args,
             target,
+            language,
             temp_files: Vec::new(),
+            output: CompileOutput::default(),
+            diagnostics,
+            cache: None,
+            callgraph: CallGraph::default(),
         })
     }
 
-    pub async fn compile(&mut self) -> Result<()> {
+    /// Read headers and lex through `cache`, shared with other compilations
+    #[allow(dead_code)]
+    pub fn with_cache(mut self, cache: CompileCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Diagnostics of the last `compile`, also when it failed
+    #[allow(dead_code)]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.output.diagnostics
+    }
+
+    pub async fn compile(&mut self) -> Result<CompileOutput> {
         if self.args.input_files.is_empty() {
             return Err(AleccError::InvalidArgument {
                 message: "No input files specified".to_string(),
@@ -44,11 +357,42 @@ impl Compiler {
             self.target.as_str()
         );
 
+        if self.warning_enabled("unknown-warning-option") {
+            for option in self.warnings.unknown() {
+                let warning = Diagnostic::warning(format!("unknown warning option '{}'", option));
+                self.report_warning(0, "unknown-warning-option", warning);
+            }
+        }
+        let result = self.compile_inputs().await;
+
+        // Whatever happened, print diagnostics still waiting on earlier units
+        self.diagnostics.flush();
+        self.output.diagnostics = self.diagnostics.take_emitted();
+        result?;
+
+        for summary in &self.output.summaries {
+            eprintln!("{}", summary);
+        }
+
+        // Cleanup temporary files
+        self.cleanup().await?;
+
+        Ok(std::mem::take(&mut self.output))
+    }
+
+    async fn compile_inputs(&mut self) -> Result<()> {
         let mut object_files = Vec::new();
         let input_files = self.args.input_files.clone(); // Clone to avoid borrow issues
+        let link = !self.args.compile_only
+            && !self.args.assembly_only
+            && !self.args.preprocess_only
+            && !self.writes_alternate_output();
+        // Units kept back by --fwhole-program, and where their object goes in the link
+        let mut whole_program = Vec::new();
+        let mut whole_program_position = None;
 
         // Process each input file
-        for input_file in &input_files {
+        for (unit, input_file) in input_files.iter().enumerate() {
             debug!("Processing file: {}", input_file.display());
 
             let extension = input_file
@@ -56,16 +400,17 @@ impl Compiler {
                 .and_then(|ext| ext.to_str())
                 .unwrap_or("");
 
+            if !matches!(
+                extension,
+                "c" | "cpp" | "cxx" | "cc" | "C" | "s" | "S" | "o"
+            ) {
+                self.diagnostics.report(
+                    unit,
+                    Diagnostic::warning("unknown file extension, treating as C source")
+                        .with_file(input_file),
+                );
+            }
             match extension {
-                "c" | "cpp" | "cxx" | "cc" | "C" => {
-                    let obj_file = self.compile_source_file(input_file).await?;
-                    if !self.args.compile_only
-                        && !self.args.assembly_only
-                        && !self.args.preprocess_only
-                    {
-                        object_files.push(obj_file);
-                    }
-                }
                 "s" | "S" => {
                     let obj_file = self.assemble_file(input_file).await?;
                     if !self.args.compile_only && !self.args.assembly_only {
@@ -75,36 +420,140 @@ impl Compiler {
                 "o" => {
                     object_files.push(input_file.clone());
                 }
-                _ => {
-                    warn!(
-                        "Unknown file extension for {}, treating as C source",
-                        input_file.display()
-                    );
-                    let obj_file = self.compile_source_file(input_file).await?;
-                    if !self.args.compile_only
-                        && !self.args.assembly_only
-                        && !self.args.preprocess_only
-                    {
-                        object_files.push(obj_file);
+                _ => match self
+                    .compile_or_defer(unit, input_file, &mut whole_program)
+                    .await?
+                {
+                    Some(obj_file) if link => object_files.push(obj_file),
+                    Some(_) => {}
+                    None => {
+                        whole_program_position.get_or_insert(object_files.len());
                     }
-                }
+                },
+            }
+
+            self.diagnostics.finish_unit(unit);
+        }
+
+        if !whole_program.is_empty() {
+            let obj_file = self.compile_whole_program(whole_program).await?;
+            if link {
+                object_files.insert(whole_program_position.unwrap_or_default(), obj_file);
+            }
+        }
+
+        if let Some(format) = self.args.emit_callgraph {
+            if !self.args.preprocess_only {
+                self.write_callgraph(format).await?;
             }
         }
 
         // Link if not compile-only
-        if !self.args.compile_only && !self.args.assembly_only && !self.args.preprocess_only {
+        if link {
+            if self.args.nostdlib {
+                let runtime = self.build_runtime().await?;
+                object_files.push(runtime);
+            }
             self.link_files(object_files).await?;
         }
 
-        // Cleanup temporary files
-        self.cleanup().await?;
-
         Ok(())
     }
 
-    async fn compile_source_file(&mut self, input_file: &Path) -> Result<PathBuf> {
+    fn record_timing(&mut self, phase: Phase, input: Option<&Path>, start: Instant) {
+        self.output.timings.push(PhaseTiming {
+            phase,
+            input: input.map(Path::to_path_buf),
+            duration: start.elapsed(),
+        });
+    }
+
+    async fn compile_source_file(&mut self, unit: usize, input_file: &Path) -> Result<PathBuf> {
+        match self.front_end(unit, input_file).await? {
+            FrontEnd::Done(path) => Ok(path),
+            FrontEnd::Checked {
+                program,
+                summary,
+                source,
+            } => {
+                let summaries = summary.map(|summary| PendingSummary {
+                    summary,
+                    functions: None,
+                });
+                let summaries = summaries.into_iter().collect();
+                self.back_end(input_file, *program, summaries, Some(&source))
+                    .await
+            }
+        }
+    }
+
+    /// Compile a C source, or with `--fwhole-program` only check it and keep
+    /// it in `units`, to be compiled together with the others
+    async fn compile_or_defer(
+        &mut self,
+        unit: usize,
+        input_file: &Path,
+        units: &mut Vec<CheckedUnit>,
+    ) -> Result<Option<PathBuf>> {
+        if !self.args.whole_program || self.args.dry_run {
+            return self.compile_source_file(unit, input_file).await.map(Some);
+        }
+        match self.front_end(unit, input_file).await? {
+            FrontEnd::Done(path) => Ok(Some(path)),
+            FrontEnd::Checked {
+                program, summary, ..
+            } => {
+                units.push(CheckedUnit {
+                    input: input_file.to_path_buf(),
+                    program: *program,
+                    summary,
+                });
+                Ok(None)
+            }
+        }
+    }
+
+    /// Merge the units `--fwhole-program` kept back and compile them as one,
+    /// named after the first
+    async fn compile_whole_program(&mut self, units: Vec<CheckedUnit>) -> Result<PathBuf> {
+        let input_file = units[0].input.clone();
+        let mut programs = Vec::new();
+        let mut summaries = Vec::new();
+        for unit in units {
+            programs.push((unit.input.display().to_string(), unit.program));
+            summaries.push(unit.summary);
+        }
+
+        let WholeProgram {
+            mut program,
+            definitions,
+        } = merge_programs(programs)?;
+        // A shared library's functions are there to be called from outside it
+        if !self.args.shared {
+            internalize(&mut program);
+        }
+        let summaries = summaries
+            .into_iter()
+            .zip(definitions)
+            .filter_map(|(summary, functions)| {
+                Some(PendingSummary {
+                    summary: summary?,
+                    functions: Some(functions),
+                })
+            })
+            .collect();
+        // The merged program's lines come from several files, so none is quoted
+        self.back_end(&input_file, program, summaries, None).await
+    }
+
+    /// Read, preprocess, parse and check a source file, reporting its warnings
+    async fn front_end(&mut self, unit: usize, input_file: &Path) -> Result<FrontEnd> {
         info!("Compiling source file: {}", input_file.display());
 
+        if self.args.dry_run {
+            return self.plan_source_file(input_file).await.map(FrontEnd::Done);
+        }
+
         // Read source file
         let source =
             fs::read_to_string(input_file)
@@ -114,47 +563,255 @@ impl Compiler {
                 })?;
 
         // Preprocessing
-        let preprocessed = if self.args.preprocess_only {
+        let start = Instant::now();
+        let preprocessed = self.preprocess(&source, input_file).await?;
+        self.record_timing(Phase::Preprocess, Some(input_file), start);
+
+        if self.args.preprocess_only {
             let output_path = self.get_output_path(input_file, "i")?;
-            let preprocessed = self.preprocess(&source, input_file).await?;
-            fs::write(&output_path, preprocessed)
+            write_output(&output_path, preprocessed).await?;
+            self.output.preprocessed.push(output_path.clone());
+            return Ok(FrontEnd::Done(output_path));
+        }
+
+        if let Some(address) = self.args.remote.clone() {
+            return self
+                .compile_remotely(unit, input_file, &address, preprocessed)
                 .await
-                .map_err(AleccError::IoError)?;
-            return Ok(output_path);
-        } else {
-            self.preprocess(&source, input_file).await?
-        };
+                .map(FrontEnd::Done);
+        }
 
         // Lexical analysis
         debug!("Lexical analysis for {}", input_file.display());
-        let mut lexer = Lexer::new(preprocessed);
-        let tokens = lexer.tokenize()?;
+        let start = Instant::now();
+        let language = self.language;
+        let lex = || {
+            Lexer::new(preprocessed.clone())
+                .with_language(language)
+                .tokenize()
+        };
+        let tokens = match &self.cache {
+            Some(cache) => cache.tokens(&preprocessed, language, lex),
+            None => lex(),
+        }
+        .map_err(|error| error.in_file(input_file))?;
+        self.record_timing(Phase::Lex, Some(input_file), start);
+        let token_count = tokens
+            .iter()
+            .filter(|token| !matches!(token.token_type, TokenType::Newline | TokenType::Eof))
+            .count();
 
         // Parsing
         debug!("Parsing {}", input_file.display());
-        let mut parser = Parser::new(tokens);
-        let mut program = parser.parse()?;
+        let start = Instant::now();
+        let mut parser = Parser::new(tokens)
+            .with_target(self.target)
+            .with_language(self.language);
+        let mut program = match parser.parse() {
+            Ok(program) => program,
+            Err(error) => {
+                // Report what the unit got as far as before its error
+                self.warn_type_checks(unit, input_file, parser.warnings());
+                return Err(error.in_file(input_file));
+            }
+        };
+        self.record_timing(Phase::Parse, Some(input_file), start);
+
+        let start = Instant::now();
+        let mut sema = Sema::new().with_language(self.language);
+        if let Err(error) = sema.analyze(&mut program) {
+            self.warn_checked_unit(unit, input_file, &program, &parser, &sema);
+            return Err(error.in_file(input_file));
+        }
+        self.record_timing(Phase::Sema, Some(input_file), start);
+
+        // alecc's own startup code leaves the thread pointer unset, which only
+        // the dynamic loader sets up then
+        let links = !self.args.compile_only && !self.args.assembly_only && !self.args.shared;
+        if links
+            && (self.args.nostdlib || self.args.static_link)
+            && program
+                .global_variables
+                .iter()
+                .any(|global| global.thread_local)
+        {
+            return Err(AleccError::InvalidArgument {
+                message: format!(
+                    "thread-local variables need the dynamic loader to set up thread storage, which {} leaves out",
+                    if self.args.nostdlib { "--nostdlib" } else { "--static" }
+                ),
+            });
+        }
+
+        if self.args.emit_callgraph.is_some() {
+            self.callgraph
+                .add_unit(&input_file.display().to_string(), &program);
+        }
+
+        if self.args.emit_ast == Some(AstFormat::Json) {
+            return self
+                .write_ast(input_file, &program)
+                .await
+                .map(FrontEnd::Done);
+        }
+
+        self.warn_checked_unit(unit, input_file, &program, &parser, &sema);
+        self.check_warnings_as_errors()?;
+
+        let summary = self.args.summary.then(|| UnitSummary {
+            input: input_file.to_path_buf(),
+            lines: source.lines().count(),
+            tokens: token_count,
+            functions: program
+                .functions
+                .iter()
+                .filter(|f| !matches!(&f.body.kind, StatementKind::Block(s) if s.is_empty()))
+                .count(),
+            globals: program.global_variables.len(),
+            instructions: Vec::new(),
+        });
+        Ok(FrontEnd::Checked {
+            program: Box::new(program),
+            summary,
+            source,
+        })
+    }
+
+    /// Report the flow analysis warnings of a parsed unit and those its
+    /// parser and checker collected
+    fn warn_checked_unit(
+        &mut self,
+        unit: usize,
+        input_file: &Path,
+        program: &Program,
+        parser: &Parser,
+        sema: &Sema,
+    ) {
+        self.warn_uninitialized(unit, input_file, program);
+        self.warn_invalid_noreturn(unit, input_file, program);
+        self.warn_return_type(unit, input_file, program);
+        self.warn_unreachable_code(unit, input_file, program);
+        self.warn_implicit_fallthrough(unit, input_file, program);
+        if self.args.analyze {
+            self.warn_analyzer(unit, input_file, program);
+        }
+        self.warn_type_checks(unit, input_file, parser.warnings());
+        self.warn_type_checks(unit, input_file, sema.warnings());
+    }
 
+    /// Optimize a checked program and generate its code: an object, or the
+    /// assembly or IR the options ask for. `source` is the text of a single
+    /// input file, for `--fverbose-asm` to quote
+    async fn back_end(
+        &mut self,
+        input_file: &Path,
+        mut program: Program,
+        summaries: Vec<PendingSummary>,
+        source: Option<&str>,
+    ) -> Result<PathBuf> {
         // Optimization
+        let start = Instant::now();
         let opt_level = OptimizationLevel::from_string(&self.args.optimization);
         let mut optimizer = Optimizer::new(opt_level);
         optimizer.optimize(&mut program)?;
+        self.record_timing(Phase::Optimize, Some(input_file), start);
+
+        if let Some(prefix) = &self.args.symbol_prefix {
+            prefix_internal_symbols(&mut program, prefix);
+        }
+        let label_prefix = self.label_prefix(input_file);
+
+        if self.args.emit == Some(EmitKind::LlvmIr) {
+            debug!("LLVM IR generation for {}", input_file.display());
+            let start = Instant::now();
+            let mut generator = LlvmIrGenerator::new(self.target)
+                .with_label_prefix(&label_prefix)
+                .with_missing_return(self.args.missing_return)
+                .with_stack_protector(stack_protector(&self.args))
+                .with_ident(self.ident().as_deref());
+            let ir = generator.generate(&program)?;
+            self.record_timing(Phase::Codegen, Some(input_file), start);
+            self.keep_summary(summaries, llvm_instruction_counts(&ir));
+
+            let output_path = self.get_output_path(input_file, "ll")?;
+            write_output(&output_path, ir).await?;
+            self.output.llvm_ir.push(output_path.clone());
+            return Ok(output_path);
+        }
+
+        if self.args.emit == Some(EmitKind::Ir) {
+            debug!("IR generation for {}", input_file.display());
+            let start = Instant::now();
+            let module = LoweringContext::new(self.target, &program)
+                .with_label_prefix(&label_prefix)
+                .with_missing_return(self.args.missing_return)
+                .with_optimization(opt_level != OptimizationLevel::None)
+                .lower_program(&program);
+            self.record_timing(Phase::Codegen, Some(input_file), start);
+            let counts = module
+                .functions
+                .iter()
+                .map(|function| (function.name.clone(), function.instruction_count()))
+                .collect();
+            self.keep_summary(summaries, counts);
+
+            let output_path = self.get_output_path(input_file, "ir")?;
+            write_output(&output_path, module.to_string()).await?;
+            self.output.ir.push(output_path.clone());
+            return Ok(output_path);
+        }
+
+        #[cfg(feature = "cranelift")]
+        if self.args.backend == Backend::Cranelift {
+            return self
+                .compile_with_cranelift(input_file, &program, &label_prefix, summaries)
+                .await;
+        }
 
         // Code generation
         debug!("Code generation for {}", input_file.display());
-        let mut codegen = CodeGenerator::new(self.target);
+        let start = Instant::now();
+        let file_name = input_file.display().to_string();
+        let mut codegen = CodeGenerator::new(self.target)
+            .with_as_dialect(self.args.as_dialect)
+            .with_syntax(self.args.asm_syntax)
+            .with_label_prefix(&label_prefix)
+            .with_start(!self.args.nostartfiles && !self.uses_crt())
+            .with_missing_return(self.args.missing_return)
+            .with_red_zone(!self.args.no_red_zone)
+            .with_frame_pointer_omission(self.omit_frame_pointer())
+            .with_stack_protector(stack_protector(&self.args))
+            .with_stack_guard_symbol(self.args.nostdlib)
+            .with_sse2(self.args.sse2)
+            .with_position_independent(self.position_independent())
+            .with_shared_object((self.args.shared || self.args.pic) && self.target != Target::I386)
+            .with_ir(self.args.backend == Backend::Ir)
+            .with_optimization(opt_level != OptimizationLevel::None)
+            .with_ident(self.ident().as_deref())
+            .with_function_sections(self.args.function_sections)
+            .with_data_sections(self.args.data_sections)
+            .with_verbose_asm(
+                self.args.verbose_asm,
+                source.map(|source| (file_name.as_str(), source)),
+            )
+            .with_wrapped_symbols(&self.wrapped_symbols());
         let assembly = codegen.generate(&program)?;
+        self.record_timing(Phase::Codegen, Some(input_file), start);
+        self.keep_summary(summaries, codegen.instruction_counts().to_vec());
+
+        if self.args.stack_usage {
+            self.write_stack_usage(input_file, codegen.stack_usage())
+                .await?;
+        }
 
         if self.args.assembly_only {
             let output_path = self.get_output_path(input_file, "s")?;
-            fs::write(&output_path, assembly)
-                .await
-                .map_err(AleccError::IoError)?;
+            write_output(&output_path, assembly).await?;
+            self.output.assembly.push(output_path.clone());
             return Ok(output_path);
         }
 
-        // Write assembly to temporary file
-        let asm_path = self.create_temp_file("s")?;
+        let asm_path = self.assembly_path(input_file)?;
         fs::write(&asm_path, assembly)
             .await
             .map_err(AleccError::IoError)?;
@@ -165,12 +822,703 @@ impl Compiler {
         Ok(obj_path)
     }
 
+    /// Complete units' `--summary` with what codegen produced, to print once all units compiled
+    fn keep_summary(&mut self, summaries: Vec<PendingSummary>, instructions: Vec<(String, usize)>) {
+        for PendingSummary { summary, functions } in summaries {
+            let instructions = instructions
+                .iter()
+                .filter(|(name, _)| functions.as_ref().is_none_or(|f| f.contains(name)))
+                .cloned()
+                .collect();
+            self.output.summaries.push(UnitSummary {
+                instructions,
+                ..summary
+            });
+        }
+    }
+
+    /// `-Wuninitialized` and `-Wmaybe-uninitialized`, on unless turned off with `-Wno-...`
+    fn warn_uninitialized(&mut self, unit: usize, input_file: &Path, program: &Program) {
+        let definite = self.warning_enabled("uninitialized");
+        let maybe = self.warning_enabled("maybe-uninitialized");
+
+        for function in &program.functions {
+            for found in uninitialized_uses(function) {
+                let (option, message) = if found.definitely && definite {
+                    (
+                        "uninitialized",
+                        format!(
+                            "'{}' is used uninitialized in function '{}'",
+                            found.variable, found.function
+                        ),
+                    )
+                } else if !found.definitely && maybe {
+                    (
+                        "maybe-uninitialized",
+                        format!(
+                            "'{}' may be used uninitialized in function '{}'",
+                            found.variable, found.function
+                        ),
+                    )
+                } else {
+                    continue;
+                };
+                let warning = Diagnostic::warning(message)
+                    .with_file(input_file)
+                    .with_location(found.span.line, found.span.column);
+                self.report_warning(unit, option, warning);
+            }
+        }
+    }
+
+    /// `noreturn` functions that return anyway, which gcc always warns about;
+    /// `-Wno-invalid-noreturn` turns the warnings off as with clang
+    fn warn_invalid_noreturn(&mut self, unit: usize, input_file: &Path, program: &Program) {
+        if !self.warning_enabled("invalid-noreturn") {
+            return;
+        }
+        let noreturn = noreturn_functions(program);
+
+        for function in &program.functions {
+            // An empty body is a prototype
+            if !function.attributes.noreturn
+                || matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+            {
+                continue;
+            }
+            for violation in noreturn_violations(function, &noreturn) {
+                let message = match violation {
+                    NoreturnViolation::ReturnStatement => format!(
+                        "function '{}' declared 'noreturn' has a 'return' statement",
+                        function.name
+                    ),
+                    NoreturnViolation::FallsOff => {
+                        format!("'noreturn' function '{}' does return", function.name)
+                    }
+                };
+                self.report_warning(
+                    unit,
+                    "invalid-noreturn",
+                    Diagnostic::warning(message).with_file(input_file),
+                );
+            }
+        }
+    }
+
+    /// Returns that don't match the function's return type, and non-void
+    /// functions that can end without one; on by default, as in clang
+    fn warn_return_type(&mut self, unit: usize, input_file: &Path, program: &Program) {
+        if !self.warning_enabled("return-type") {
+            return;
+        }
+        let noreturn = noreturn_functions(program);
+        let void_functions: HashSet<String> = program
+            .functions
+            .iter()
+            .filter(|f| matches!(f.return_type, Type::Void))
+            .map(|f| f.name.clone())
+            .collect();
+
+        for function in &program.functions {
+            // -Winvalid-noreturn covers `noreturn` functions
+            if function.attributes.noreturn
+                || matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+            {
+                continue;
+            }
+            for problem in return_problems(function, &noreturn, &void_functions) {
+                let (message, span) = match problem {
+                    ReturnProblem::FallsOff => (
+                        format!(
+                            "non-void function '{}' does not return a value in all control paths",
+                            function.name
+                        ),
+                        function.end,
+                    ),
+                    ReturnProblem::ValueInVoid(span) => (
+                        format!(
+                            "void function '{}' should not return a value",
+                            function.name
+                        ),
+                        span,
+                    ),
+                    ReturnProblem::MissingValue(span) => (
+                        format!(
+                            "non-void function '{}' should return a value",
+                            function.name
+                        ),
+                        span,
+                    ),
+                };
+                let diagnostic = Diagnostic::warning(message)
+                    .with_file(input_file)
+                    .with_location(span.line, span.column);
+                self.report_warning(unit, "return-type", diagnostic);
+            }
+        }
+    }
+
+    /// `-Wimplicit-fallthrough`, part of `-Wextra`: a `switch` case that
+    /// runs into the next one without `[[fallthrough]];`
+    fn warn_implicit_fallthrough(&mut self, unit: usize, input_file: &Path, program: &Program) {
+        if !self.warning_enabled("implicit-fallthrough") {
+            return;
+        }
+        let noreturn = noreturn_functions(program);
+        for function in &program.functions {
+            for span in implicit_fallthroughs(function, &noreturn) {
+                let warning = Diagnostic::warning("this statement may fall through")
+                    .with_file(input_file)
+                    .with_location(span.line, span.column);
+                self.report_warning(unit, "implicit-fallthrough", warning);
+            }
+        }
+    }
+
+    /// `-Wunreachable-code`, opt-in as in clang, with a note at the statement
+    /// control stops at
+    fn warn_unreachable_code(&mut self, unit: usize, input_file: &Path, program: &Program) {
+        if !self.warning_enabled("unreachable-code") {
+            return;
+        }
+        let noreturn = noreturn_functions(program);
+        for function in &program.functions {
+            for found in unreachable_code(function, &noreturn) {
+                let warning = Diagnostic::warning("code will never be executed")
+                    .with_file(input_file)
+                    .with_location(found.span.line, found.span.column);
+                self.report_warning(unit, "unreachable-code", warning);
+                let note = Diagnostic::new(
+                    Severity::Note,
+                    format!("control does not continue past this {}", found.terminator),
+                )
+                .with_file(input_file)
+                .with_location(found.after.line, found.after.column);
+                self.diagnostics.report(unit, note);
+            }
+        }
+    }
+
+    /// Findings of `--analyze`, each check on unless turned off with `-Wno-analyzer-...`
+    fn warn_analyzer(&mut self, unit: usize, input_file: &Path, program: &Program) {
+        for function in &program.functions {
+            for finding in analyze(function) {
+                let option = finding.check.option();
+                if self.warning_enabled(option) {
+                    self.report_warning(
+                        unit,
+                        option,
+                        Diagnostic::warning(finding.message).with_file(input_file),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Warnings the parser and sema found, each reported when its option is in effect
+    fn warn_type_checks(&mut self, unit: usize, input_file: &Path, warnings: &[TypeWarning]) {
+        for warning in warnings {
+            if !self.warning_enabled(warning.option) {
+                continue;
+            }
+            let diagnostic = Diagnostic::warning(warning.message.clone())
+                .with_file(input_file)
+                .with_location(warning.line, warning.column);
+            self.report_warning(unit, warning.option, diagnostic);
+            if let Some((note, span)) = &warning.note {
+                let note = Diagnostic::new(Severity::Note, note.clone())
+                    .with_file(input_file)
+                    .with_location(span.line, span.column);
+                self.diagnostics.report(unit, note);
+            }
+        }
+    }
+
+    /// Whether `-W<name>` is in effect, after `-Wall`, `-Wextra` and `-Wno-<name>`
+    fn warning_enabled(&self, name: &str) -> bool {
+        self.warnings.enabled(name)
+    }
+
+    /// Report a warning `-W<option>` controls, as an error under `-Werror`
+    fn report_warning(&mut self, unit: usize, option: &str, diagnostic: Diagnostic) {
+        let mut diagnostic = diagnostic.with_option(option);
+        if self.warnings.is_error(option) {
+            diagnostic = diagnostic.into_error();
+            self.warnings_as_errors += 1;
+        }
+        self.diagnostics.report(unit, diagnostic);
+    }
+
+    /// Stop compiling a unit once `-Werror` has made one of its warnings an error
+    fn check_warnings_as_errors(&mut self) -> Result<()> {
+        if std::mem::take(&mut self.warnings_as_errors) == 0 {
+            return Ok(());
+        }
+        Err(AleccError::SemanticError {
+            message: if self.warnings.all_errors() {
+                "all warnings being treated as errors".to_string()
+            } else {
+                "some warnings being treated as errors".to_string()
+            },
+            span: None,
+        })
+    }
+
+    /// Write `<name>.su` in GCC's format: one `file:function<TAB>bytes<TAB>static`
+    /// line per function. The name follows `-o` for `-c`/`-S`, else the input.
+    async fn write_stack_usage(
+        &mut self,
+        input_file: &Path,
+        frames: &[(String, usize)],
+    ) -> Result<()> {
+        let su_path = self.side_file_path(input_file, "su")?;
+
+        let mut report = String::new();
+        for (function, bytes) in frames {
+            report.push_str(&format!(
+                "{}:{}\t{}\tstatic\n",
+                input_file.display(),
+                function,
+                bytes
+            ));
+        }
+        fs::write(&su_path, report)
+            .await
+            .map_err(AleccError::IoError)?;
+        self.output.stack_usage.push(su_path);
+        Ok(())
+    }
+
+    /// What goes after `.L` in the local labels of a translation unit: the
+    /// `--symbol-prefix`, or else the file name, so no two files share labels
+    fn label_prefix(&self, input_file: &Path) -> String {
+        if let Some(prefix) = &self.args.symbol_prefix {
+            return prefix.clone();
+        }
+        let stem = input_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let mut prefix: String = stem
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        prefix.push('_');
+        prefix
+    }
+
+    /// Whether the inputs are written out in another form (`--emit`,
+    /// `--emit-ast`) rather than compiled to objects and linked
+    fn writes_alternate_output(&self) -> bool {
+        self.args.emit.is_some() || self.args.emit_ast.is_some()
+    }
+
+    /// What every object records in `.comment`: the alecc version and the
+    /// flags that shape its code, or nothing with `--no-ident`
+    fn ident(&self) -> Option<String> {
+        if self.args.no_ident {
+            return None;
+        }
+        let mut flags = vec![
+            format!("--target={}", self.target.as_str()),
+            format!("-O{}", self.args.optimization),
+            format!("--std={}", self.language.name()),
+        ];
+        if self.args.debug {
+            flags.push("-g".to_string());
+        }
+        flags.push(
+            if self.position_independent() {
+                "--pie"
+            } else {
+                "--no-pie"
+            }
+            .to_string(),
+        );
+        Some(format!(
+            "alecc {} ({})",
+            env!("CARGO_PKG_VERSION"),
+            flags.join(" ")
+        ))
+    }
+
+    /// Whether code is generated for a position-independent executable, as
+    /// modern distributions link them; i386 code is always absolute
+    fn position_independent(&self) -> bool {
+        !self.args.no_pie && self.target != Target::I386
+    }
+
+    /// Whether libc's crt1.o enters `main`, which then gets no `_start` of its
+    /// own: by default whenever libc is linked dynamically
+    fn uses_crt(&self) -> bool {
+        !self.args.nostartfiles
+            && !self.args.no_use_crt
+            && !self.args.nostdlib
+            && !self.args.static_link
+    }
+
+    /// Whether functions do without a frame pointer: when asked to, or when
+    /// optimizing unless asked not to
+    fn omit_frame_pointer(&self) -> bool {
+        let optimizing =
+            OptimizationLevel::from_string(&self.args.optimization) != OptimizationLevel::None;
+        !self.args.no_omit_frame_pointer && (self.args.omit_frame_pointer || optimizing)
+    }
+
+    /// The symbols `--wrap` and `-Wl,--wrap` name, whose calls the link redirects
+    fn wrapped_symbols(&self) -> Vec<String> {
+        let mut symbols = self.args.wrap.clone();
+        let options = linker_options(&self.args.warnings);
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match option
+                .strip_prefix("--wrap=")
+                .or(option.strip_prefix("-wrap="))
+            {
+                Some(symbol) => symbols.push(symbol.to_string()),
+                None if option == "--wrap" || option == "-wrap" => {
+                    symbols.extend(options.next().cloned())
+                }
+                None => {}
+            }
+        }
+        symbols
+    }
+
+    /// Whether the link makes a PIE. Without libc's dynamic loader nothing would
+    /// apply the relocations, so `--nostdlib` and `--static` stay position-dependent.
+    fn links_pie(&self) -> bool {
+        self.position_independent()
+            && !self.args.nostdlib
+            && !self.args.static_link
+            && !self.args.shared
+            && !self.args.pic
+    }
+
+    /// Write `program` as JSON for tools that consume the AST (`--emit-ast=json`)
+    async fn write_ast(&mut self, input_file: &Path, program: &Program) -> Result<PathBuf> {
+        let json = ast_json(program)?;
+        let output_path = self.get_output_path(input_file, "ast.json")?;
+        write_output(&output_path, json).await?;
+        self.output.ast.push(output_path.clone());
+        Ok(output_path)
+    }
+
+    /// Write the call graph of every unit, named after `-o` or else the
+    /// only input (`callgraph.dot` for several)
+    async fn write_callgraph(&mut self, format: CallGraphFormat) -> Result<()> {
+        let extension = match format {
+            CallGraphFormat::Dot => "callgraph.dot",
+            CallGraphFormat::Json => "callgraph.json",
+        };
+        let path = match (&self.args.output, self.args.input_files.as_slice()) {
+            (Some(output), _) => output.with_extension(extension),
+            (None, [input]) => self.get_output_path(input, extension)?,
+            (None, _) => PathBuf::from(extension),
+        };
+        if self.args.dry_run {
+            eprintln!("# emit-callgraph -> {}", path.display());
+            return Ok(());
+        }
+
+        let text = match format {
+            CallGraphFormat::Dot => self.callgraph.to_dot(),
+            CallGraphFormat::Json => callgraph_json(&self.callgraph)?,
+        };
+        fs::write(&path, text).await.map_err(AleccError::IoError)?;
+        self.output.callgraph = Some(path);
+        Ok(())
+    }
+
+    /// Generate an object file in-process, skipping the assembler
+    #[cfg(feature = "cranelift")]
+    async fn compile_with_cranelift(
+        &mut self,
+        input_file: &Path,
+        program: &Program,
+        label_prefix: &str,
+        summaries: Vec<PendingSummary>,
+    ) -> Result<PathBuf> {
+        debug!("Cranelift code generation for {}", input_file.display());
+        let start = Instant::now();
+        let name = input_file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("alecc");
+        let optimize = !matches!(
+            OptimizationLevel::from_string(&self.args.optimization),
+            OptimizationLevel::None
+        );
+        let generator = CraneliftGenerator::new(
+            self.target,
+            optimize,
+            self.args.pic || self.position_independent(),
+            name,
+        )?
+        .with_label_prefix(label_prefix)
+        .with_start(!self.args.nostartfiles && !self.uses_crt())
+        .with_missing_return(self.args.missing_return)
+        .with_ident(self.ident().as_deref());
+        let object = generator.generate(program)?;
+        self.record_timing(Phase::Codegen, Some(input_file), start);
+        self.keep_summary(summaries, object.instruction_counts);
+        let object = object.bytes;
+
+        let obj_path = if self.args.compile_only {
+            self.get_output_path(input_file, "o")?
+        } else {
+            self.create_temp_file("o")?
+        };
+        fs::write(&obj_path, object)
+            .await
+            .map_err(AleccError::IoError)?;
+
+        if self.args.compile_only {
+            self.output.objects.push(obj_path.clone());
+        }
+        Ok(obj_path)
+    }
+
+    /// Ship a preprocessed unit to the `--remote` daemon and write the object it sends back
+    #[cfg(all(feature = "daemon", unix))]
+    async fn compile_remotely(
+        &mut self,
+        unit: usize,
+        input_file: &Path,
+        address: &str,
+        preprocessed: String,
+    ) -> Result<PathBuf> {
+        use crate::daemon::{compile_remote, Request, Source};
+
+        debug!(
+            "Remote compilation of {} on {}",
+            input_file.display(),
+            address
+        );
+        let start = Instant::now();
+        let request = Request {
+            args: self.remote_args(),
+            cwd: None,
+            source: Some(Source {
+                name: input_file
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                text: preprocessed,
+            }),
+        };
+        let response = compile_remote(address, &request).await?;
+        self.record_timing(Phase::Remote, Some(input_file), start);
+
+        for mut diagnostic in response.diagnostics.clone() {
+            if diagnostic.file.is_some() {
+                diagnostic.file = Some(input_file.to_path_buf());
+            }
+            self.diagnostics.report(unit, diagnostic);
+        }
+        if !response.success {
+            return Err(AleccError::CodegenError {
+                message: format!(
+                    "remote compilation on {} failed: {}",
+                    address,
+                    response.error.as_deref().unwrap_or("no reason given")
+                ),
+                span: None,
+            });
+        }
+        let object = response.object_bytes()?;
+
+        let obj_path = if self.args.compile_only {
+            self.get_output_path(input_file, "o")?
+        } else {
+            self.create_temp_file("o")?
+        };
+        fs::write(&obj_path, object)
+            .await
+            .map_err(AleccError::IoError)?;
+
+        if self.args.compile_only {
+            self.output.objects.push(obj_path.clone());
+        }
+        Ok(obj_path)
+    }
+
+    #[cfg(not(all(feature = "daemon", unix)))]
+    async fn compile_remotely(
+        &mut self,
+        _unit: usize,
+        _input_file: &Path,
+        _address: &str,
+        _preprocessed: String,
+    ) -> Result<PathBuf> {
+        unreachable!("Compiler::new rejects --remote without the daemon feature")
+    }
+
+    /// The flags that shape code generation, for the daemon compiling a
+    /// `--remote` unit; everything the preprocessor and linker use stays here
+    #[allow(dead_code)]
+    fn remote_args(&self) -> Vec<String> {
+        let mut args = vec![
+            format!("--target={}", self.target.as_str()),
+            format!("-O{}", self.args.optimization),
+        ];
+        if let Some(standard) = &self.args.standard {
+            args.push(format!("--std={}", standard));
+        }
+        let switches = [
+            (self.args.debug, "-g"),
+            (self.args.pic, "--pic"),
+            (self.args.no_pie, "--no-pie"),
+            (self.args.no_ident, "--no-ident"),
+            (self.args.nostartfiles, "--nostartfiles"),
+            // The daemon doesn't see --nostdlib or --static, which also keep `_start`
+            (!self.uses_crt(), "--fno-use-crt"),
+            (self.args.no_red_zone, "--mno-red-zone"),
+            (self.args.omit_frame_pointer, "--fomit-frame-pointer"),
+            (self.args.no_omit_frame_pointer, "--fno-omit-frame-pointer"),
+            (self.args.stack_protector, "--fstack-protector"),
+            (
+                self.args.stack_protector_strong,
+                "--fstack-protector-strong",
+            ),
+            (self.args.stack_protector_all, "--fstack-protector-all"),
+            (self.args.no_stack_protector, "--fno-stack-protector"),
+            (self.args.sse2, "--msse2"),
+            (self.args.function_sections, "--ffunction-sections"),
+            (self.args.data_sections, "--fdata-sections"),
+            (self.args.analyze, "--analyze"),
+            (
+                self.args.backend == Backend::Cranelift,
+                "--backend=cranelift",
+            ),
+            (self.args.backend == Backend::Ir, "--backend=ir"),
+            (self.args.as_dialect == AsDialect::Llvm, "--as-dialect=llvm"),
+        ];
+        args.extend(
+            switches
+                .iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, flag)| flag.to_string()),
+        );
+        if let Some(prefix) = &self.args.symbol_prefix {
+            args.push(format!("--symbol-prefix={}", prefix));
+        }
+        match self.args.missing_return {
+            MissingReturn::Unspecified => {}
+            MissingReturn::Zero => args.push("--fmissing-return=zero".to_string()),
+            MissingReturn::Trap => args.push("--fmissing-return=trap".to_string()),
+        }
+        args.extend(
+            self.wrapped_symbols()
+                .iter()
+                .map(|s| format!("--wrap={}", s)),
+        );
+        args.extend(
+            self.args
+                .warnings
+                .iter()
+                .filter(|w| !w.starts_with("l,"))
+                .map(|w| format!("-W{}", w)),
+        );
+        args
+    }
+
+    /// Print the internal phases `compile_source_file` would run for `--dry-run`,
+    /// then hand the planned assembly file to the (equally dry) assembler step.
+    async fn plan_source_file(&mut self, input_file: &Path) -> Result<PathBuf> {
+        let input = input_file.display();
+        eprintln!("# preprocess {}", input);
+        if self.args.preprocess_only {
+            let output_path = self.get_output_path(input_file, "i")?;
+            eprintln!("# write {}", output_path.display());
+            return Ok(output_path);
+        }
+
+        if let Some(address) = self.args.remote.clone() {
+            let obj_path = if self.args.compile_only {
+                self.get_output_path(input_file, "o")?
+            } else {
+                self.create_temp_file("o")?
+            };
+            eprintln!(
+                "# remote {} on {} -> {}",
+                input,
+                address,
+                obj_path.display()
+            );
+            return Ok(obj_path);
+        }
+
+        eprintln!("# lex {}", input);
+        eprintln!("# parse {}", input);
+        if self.args.emit_ast == Some(AstFormat::Json) {
+            let output_path = self.get_output_path(input_file, "ast.json")?;
+            eprintln!("# emit-ast {} -> {}", input, output_path.display());
+            return Ok(output_path);
+        }
+        eprintln!("# optimize {} (-O{})", input, self.args.optimization);
+
+        if self.args.emit == Some(EmitKind::LlvmIr) {
+            let output_path = self.get_output_path(input_file, "ll")?;
+            eprintln!("# emit-llvm {} -> {}", input, output_path.display());
+            return Ok(output_path);
+        }
+
+        if self.args.emit == Some(EmitKind::Ir) {
+            let output_path = self.get_output_path(input_file, "ir")?;
+            eprintln!("# emit-ir {} -> {}", input, output_path.display());
+            return Ok(output_path);
+        }
+
+        if self.args.backend == Backend::Cranelift {
+            let obj_path = if self.args.compile_only {
+                self.get_output_path(input_file, "o")?
+            } else {
+                self.create_temp_file("o")?
+            };
+            eprintln!(
+                "# cranelift {} -> {} (target {})",
+                input,
+                obj_path.display(),
+                self.target.as_str()
+            );
+            return Ok(obj_path);
+        }
+
+        let asm_path = if self.args.assembly_only {
+            self.get_output_path(input_file, "s")?
+        } else {
+            self.assembly_path(input_file)?
+        };
+        eprintln!(
+            "# codegen {} -> {} (target {})",
+            input,
+            asm_path.display(),
+            self.target.as_str()
+        );
+
+        if self.args.assembly_only {
+            return Ok(asm_path);
+        }
+
+        self.assemble_file(&asm_path).await
+    }
+
     async fn preprocess(&self, source: &str, input_file: &Path) -> Result<String> {
         debug!("Preprocessing {}", input_file.display());
 
         // Simple preprocessing - just handle basic #include and #define
         let mut preprocessed = String::new();
-        let mut defines = std::collections::HashMap::new();
+        let mut defines = HashMap::new();
+        for (name, value) in self
+            .language
+            .predefined_macros()
+            .into_iter()
+            .chain(self.target.predefined_macros())
+        {
+            defines.insert(name.to_string(), value.to_string());
+        }
+        defines.extend(TargetInfo::new(self.target).stdint_macros());
 
         // Add command-line defines
         for define in &self.args.defines {
@@ -182,32 +1530,96 @@ impl Compiler {
                 defines.insert(define.clone(), "1".to_string());
             }
         }
+        for undefine in &self.args.undefines {
+            defines.remove(undefine);
+        }
 
-        // Process source line by line
-        for line in source.lines() {
+        let file = string_literal(&input_file.display().to_string());
+        let mut assert = None;
+        let mut builtins_included = HashSet::new();
+        let expand = |text: &str, line: usize, defines: &HashMap<String, String>, assert| {
+            Expansion {
+                defines,
+                language: self.language,
+                file: &file,
+                assert,
+                depth_limit: self.args.macro_depth,
+            }
+            .expand_macros(text, line)
+        };
+
+        // Process source line by line; the lines between directives are expanded
+        // together, so a comment can span them
+        let mut text = String::new();
+        let mut text_line = 1;
+        for (index, line) in source.lines().enumerate() {
             let trimmed = line.trim();
+            if trimmed.starts_with('#') {
+                preprocessed.push_str(&expand(&text, text_line, &defines, assert)?);
+                text.clear();
+            }
 
             if trimmed.starts_with("#include") {
                 // Handle #include (simplified)
+                let before = preprocessed.len();
                 match self.extract_include_file(trimmed) {
                     Ok(include_file) => {
-                        match self.resolve_include_path(&include_file) {
-                            Ok(include_path) => {
-                                if include_path.exists() {
-                                    match fs::read_to_string(&include_path).await {
-                                        Ok(include_content) => {
-                                            // Simple include without recursive preprocessing to avoid recursion issues
-                                            preprocessed.push_str(&include_content);
-                                            preprocessed.push('\n');
-                                        }
-                                        Err(_) => {
-                                            // Skip file if can't read
+                        // The real definition is compiler-specific (gcc's own stddef.h)
+                        if defines_null(&include_file) {
+                            defines.insert("NULL".to_string(), "((void*)0)".to_string());
+                        }
+                        if include_file == "stdnoreturn.h" {
+                            defines.insert("noreturn".to_string(), "_Noreturn".to_string());
+                        }
+                        // The system's <assert.h> is all function-like macros
+                        if include_file == "assert.h" {
+                            assert = Some(if defines.contains_key("NDEBUG") {
+                                AssertMacro::Disabled
+                            } else {
+                                AssertMacro::Checked
+                            });
+                            preprocessed.push_str(ASSERT_H);
+                            preprocessed.push('\n');
+                        } else if include_file == "setjmp.h" {
+                            // glibc's declares the buffers with types the parser doesn't know
+                            defines.insert("sigsetjmp".to_string(), "__sigsetjmp".to_string());
+                            preprocessed.push_str(&setjmp_h(self.target));
+                            preprocessed.push('\n');
+                        } else if let Some(header) = self.builtin_header(&include_file) {
+                            // Its declarations go on one line, like ASSERT_H; a
+                            // second #include of it adds nothing
+                            if builtins_included.insert(include_file.clone()) {
+                                let declarations = header_declarations(header, &mut defines);
+                                let declarations =
+                                    expand(&declarations, index + 1, &defines, assert)?;
+                                preprocessed.push_str(&declarations.replace('\n', " "));
+                                preprocessed.push('\n');
+                            }
+                        } else {
+                            match self.resolve_include_path(&include_file) {
+                                Ok(include_path) => {
+                                    if include_path.exists() {
+                                        let contents = match &self.cache {
+                                            Some(cache) => cache.read_header(&include_path).await,
+                                            None => fs::read_to_string(&include_path)
+                                                .await
+                                                .map(Arc::new),
+                                        };
+                                        match contents {
+                                            Ok(include_content) => {
+                                                // Simple include without recursive preprocessing to avoid recursion issues
+                                                preprocessed.push_str(&include_content);
+                                                preprocessed.push('\n');
+                                            }
+                                            Err(_) => {
+                                                // Skip file if can't read
+                                            }
                                         }
                                     }
                                 }
-                            }
-                            Err(_) => {
-                                // Skip include if can't resolve path
+                                Err(_) => {
+                                    // Skip include if can't resolve path
+                                }
                             }
                         }
                     }
@@ -215,6 +1627,9 @@ impl Compiler {
                         // Skip malformed include
                     }
                 }
+                if preprocessed.len() == before {
+                    preprocessed.push('\n');
+                }
             } else if let Some(stripped) = trimmed.strip_prefix("#define") {
                 // Handle #define (simplified)
                 let parts: Vec<&str> = stripped.split_whitespace().collect();
@@ -227,16 +1642,19 @@ impl Compiler {
                     };
                     defines.insert(key, value);
                 }
+                // Keep line numbers in diagnostics in step with the source
+                preprocessed.push('\n');
             } else if !trimmed.starts_with('#') {
-                // Regular line - expand macros
-                let mut expanded_line = line.to_string();
-                for (key, value) in &defines {
-                    expanded_line = expanded_line.replace(key, value);
+                if text.is_empty() {
+                    text_line = index + 1;
                 }
-                preprocessed.push_str(&expanded_line);
+                text.push_str(line);
+                text.push('\n');
+            } else {
                 preprocessed.push('\n');
             }
         }
+        preprocessed.push_str(&expand(&text, text_line, &defines, assert)?);
 
         Ok(preprocessed)
     }
@@ -265,42 +1683,48 @@ impl Compiler {
         })
     }
 
-    fn resolve_include_path(&self, include_file: &str) -> Result<PathBuf> {
-        // Check current directory first
+    /// The header alecc ships as `include_file`, unless the current directory
+    /// or a `-I` directory has its own
+    fn builtin_header(&self, include_file: &str) -> Option<&'static str> {
+        builtin_header(include_file).filter(|_| self.user_include_path(include_file).is_none())
+    }
+
+    /// `include_file` in the current directory or a `-I` directory
+    fn user_include_path(&self, include_file: &str) -> Option<PathBuf> {
         let current_path = PathBuf::from(include_file);
         if current_path.exists() {
-            return Ok(current_path);
+            return Some(current_path);
         }
+        self.args
+            .include_dirs
+            .iter()
+            .map(|include_dir| include_dir.join(include_file))
+            .find(|path| path.exists())
+    }
 
-        // Check include directories
-        for include_dir in &self.args.include_dirs {
-            let path = include_dir.join(include_file);
-            if path.exists() {
-                return Ok(path);
-            }
+    fn resolve_include_path(&self, include_file: &str) -> Result<PathBuf> {
+        if let Some(path) = self.user_include_path(include_file) {
+            return Ok(path);
         }
 
-        // Check system include directories
-        let system_includes = match self.target {
-            Target::I386 => vec![
-                "/usr/include",
-                "/usr/local/include",
-                "/usr/include/i386-linux-gnu",
-            ],
-            Target::Amd64 => vec![
-                "/usr/include",
-                "/usr/local/include",
-                "/usr/include/x86_64-linux-gnu",
-            ],
-            Target::Arm64 => vec![
-                "/usr/include",
-                "/usr/local/include",
-                "/usr/include/aarch64-linux-gnu",
-            ],
+        // Check system include directories, which only a Linux host or a
+        // sysroot provides for the target
+        let sysroot = self.args.sysroot.as_deref();
+        if sysroot.is_none() && !Host::current().has_target_system_dirs() {
+            return Err(AleccError::FileNotFound {
+                path: include_file.to_string(),
+            });
+        }
+
+        let multiarch = match self.target {
+            Target::I386 => "/usr/include/i386-linux-gnu",
+            Target::Amd64 => "/usr/include/x86_64-linux-gnu",
+            Target::Arm64 => "/usr/include/aarch64-linux-gnu",
         };
+        let system_includes = ["/usr/include", "/usr/local/include", multiarch];
 
         for sys_dir in system_includes {
-            let path = Path::new(sys_dir).join(include_file);
+            let path = under_sysroot(sysroot, sys_dir).join(include_file);
             if path.exists() {
                 return Ok(path);
             }
@@ -320,46 +1744,86 @@ impl Compiler {
             self.create_temp_file("o")?
         };
 
-        let assembler = match self.target {
-            Target::I386 => "as",
-            Target::Amd64 => "as",
-            Target::Arm64 => "aarch64-linux-gnu-as",
-        };
+        let mut command = match self.args.as_dialect {
+            AsDialect::Gnu => {
+                let assembler = find_tool(
+                    &Host::current().tool_candidates("as", self.target),
+                    self.args.toolchain.as_deref(),
+                );
 
-        let mut command = Command::new(assembler);
+                let mut command = vec![assembler];
 
-        match self.target {
-            Target::I386 => {
-                command.args(["--32"]);
-            }
-            Target::Amd64 => {
-                command.args(["--64"]);
-            }
-            Target::Arm64 => {
-                // Default options for aarch64
+                match self.target {
+                    Target::I386 => {
+                        command.push("--32".to_string());
+                    }
+                    Target::Amd64 => {
+                        command.push("--64".to_string());
+                    }
+                    Target::Arm64 => {
+                        // Default options for aarch64
+                    }
+                }
+                command
             }
-        }
+            // llvm-mc handles every target, selected by triple
+            AsDialect::Llvm => vec![
+                find_tool(&["llvm-mc".to_string()], self.args.toolchain.as_deref()),
+                format!("--triple={}", self.target.triple()),
+                "--filetype=obj".to_string(),
+            ],
+        };
+
+        command.push("-o".to_string());
+        command.push(obj_path.to_string_lossy().to_string());
+        command.push(asm_file.to_string_lossy().to_string());
 
-        command.args([
-            "-o",
-            &obj_path.to_string_lossy(),
-            &asm_file.to_string_lossy(),
-        ]);
+        if self.args.dry_run {
+            eprintln!(" {}", format_command(&command));
+            return Ok(obj_path);
+        }
 
-        let output = command.output().map_err(|e| AleccError::CodegenError {
-            message: format!("Failed to execute assembler: {}", e),
-        })?;
+        let start = Instant::now();
+        let output = Command::new(&command[0])
+            .args(&command[1..])
+            .output()
+            .map_err(|e| AleccError::CodegenError {
+                message: format!("Failed to execute assembler: {}", e),
+                span: None,
+            })?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(AleccError::CodegenError {
                 message: format!("Assembly failed: {}", stderr),
+                span: None,
             });
         }
+        self.record_timing(Phase::Assemble, Some(asm_file), start);
+
+        if self.args.compile_only {
+            self.output.objects.push(obj_path.clone());
+        }
 
         Ok(obj_path)
     }
 
+    /// Assemble the raw-syscall runtime that replaces libc under `--nostdlib`
+    async fn build_runtime(&mut self) -> Result<PathBuf> {
+        let asm_path = self.create_temp_file("s")?;
+        if self.args.dry_run {
+            eprintln!("# runtime -> {}", asm_path.display());
+        } else {
+            fs::write(
+                &asm_path,
+                runtime_assembly(self.target, self.args.as_dialect),
+            )
+            .await
+            .map_err(AleccError::IoError)?;
+        }
+        self.assemble_file(&asm_path).await
+    }
+
     async fn link_files(&mut self, object_files: Vec<PathBuf>) -> Result<()> {
         info!("Linking {} object files", object_files.len());
 
@@ -373,7 +1837,7 @@ impl Compiler {
                 PathBuf::from("a.out")
             }
         });
-        linker.set_output_path(output_path);
+        linker.set_output_path(output_path.clone());
 
         // Add object files
         for obj in object_files {
@@ -390,21 +1854,42 @@ impl Compiler {
             linker.add_library(lib.clone());
         }
 
+        for symbol in &self.args.wrap {
+            linker.add_wrapped_symbol(symbol.clone());
+        }
+        for definition in &self.args.defsym {
+            let (symbol, expression) = definition.split_once('=').expect("checked in new");
+            linker.add_symbol_definition(symbol.to_string(), expression.to_string());
+        }
+        for option in linker_options(&self.args.warnings) {
+            linker.add_linker_arg(option);
+        }
+
         // Set linker options
         linker.set_static_link(self.args.static_link);
         linker.set_shared(self.args.shared);
+        linker.set_nostdlib(self.args.nostdlib);
+        linker.set_crt(self.uses_crt() && !self.args.shared);
+        linker.set_stack_size(self.args.stack_size);
         linker.set_pic(self.args.pic);
-        linker.set_pie(self.args.pie);
+        linker.set_pie(self.links_pie());
         linker.set_debug(self.args.debug);
         linker.set_lto(self.args.lto);
         linker.set_sysroot(self.args.sysroot.clone());
+        linker.set_toolchain(self.args.toolchain.clone());
+        linker.set_dry_run(self.args.dry_run);
 
         // Link
+        let start = Instant::now();
         if self.args.shared {
             linker.link_shared_library(None).await?;
         } else {
             linker.link().await?;
         }
+        if !self.args.dry_run {
+            self.record_timing(Phase::Link, None, start);
+            self.output.executable = Some(output_path);
+        }
 
         Ok(())
     }
@@ -426,11 +1911,42 @@ impl Compiler {
         }
     }
 
+    /// A file written alongside a unit's output: named after `-o` when that
+    /// names the unit's own output, after the source otherwise
+    fn side_file_path(&self, input_file: &Path, extension: &str) -> Result<PathBuf> {
+        match &self.args.output {
+            Some(output) if self.args.compile_only || self.args.assembly_only => {
+                Ok(output.with_extension(extension))
+            }
+            _ => {
+                let stem = input_file
+                    .file_stem()
+                    .ok_or_else(|| AleccError::InvalidArgument {
+                        message: "Invalid input file name".to_string(),
+                    })?;
+                Ok(PathBuf::from(stem).with_extension(extension))
+            }
+        }
+    }
+
+    /// Where the assembly to be assembled into an object goes: a temporary,
+    /// or the `.s` next to the object with `--save-asm-with-object`
+    fn assembly_path(&mut self, input_file: &Path) -> Result<PathBuf> {
+        if !self.args.save_asm {
+            return self.create_temp_file("s");
+        }
+        let path = self.side_file_path(input_file, "s")?;
+        self.output.assembly.push(path.clone());
+        Ok(path)
+    }
+
     fn create_temp_file(&mut self, extension: &str) -> Result<PathBuf> {
+        // Numbered per process, not per compiler: a daemon runs many compilers
+        static NEXT_TEMP_FILE: AtomicUsize = AtomicUsize::new(0);
         let temp_path = std::env::temp_dir().join(format!(
             "alecc_{}_{}.{}",
             std::process::id(),
-            self.temp_files.len(),
+            NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed),
             extension
         ));
         self.temp_files.push(temp_path.clone());
@@ -453,3 +1969,254 @@ impl Compiler {
         Ok(())
     }
 }
+
+/// How `assert` expands once <assert.h> has been included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AssertMacro {
+    Checked,
+    /// `NDEBUG` was defined when <assert.h> was included
+    Disabled,
+}
+
+/// The declaration <assert.h> provides; kept on one line so later lines keep their numbers
+const ASSERT_H: &str = "_Noreturn void __assert_fail(const char *assertion, const char *file, int line, const char *function);";
+
+/// The declarations <setjmp.h> provides, on one line like [`ASSERT_H`]. The
+/// buffers are as large as glibc's `struct __jmp_buf_tag` on the target.
+fn setjmp_h(target: Target) -> String {
+    let longs = match target {
+        Target::Amd64 => 25,
+        Target::I386 | Target::Arm64 => 39,
+    };
+    format!(
+        "typedef long jmp_buf[{longs}]; typedef long sigjmp_buf[{longs}]; \
+         int setjmp(jmp_buf env); int _setjmp(jmp_buf env); \
+         int __sigsetjmp(sigjmp_buf env, int savemask); \
+         _Noreturn void longjmp(jmp_buf env, int val); \
+         _Noreturn void _longjmp(jmp_buf env, int val); \
+         _Noreturn void siglongjmp(sigjmp_buf env, int val);"
+    )
+}
+
+/// The macros in effect, and what the predefined ones expand to
+struct Expansion<'a> {
+    defines: &'a HashMap<String, String>,
+    language: LanguageOptions,
+    /// `__FILE__`: the input file as a string literal
+    file: &'a str,
+    assert: Option<AssertMacro>,
+    /// `--fmacro-depth`: how many macros may be being expanded at once
+    depth_limit: usize,
+}
+
+impl Expansion<'_> {
+    /// Replace each name defined as a macro with its value, expanded in turn,
+    /// leaving comments and literals as they are. `line` is the source line
+    /// `text` starts on. Text that doesn't lex is kept as it is; compiling it
+    /// reports why.
+    fn expand_macros(&self, text: &str, line: usize) -> Result<String> {
+        Ok(self
+            .expand(text, line, &mut Vec::new())?
+            .unwrap_or_else(|| text.to_string()))
+    }
+
+    /// `active` holds the macros being expanded, which aren't expanded again
+    /// inside themselves, so `#define A A+1` stops after one step. A macro's
+    /// value has no newlines, so `__LINE__` in it is the line of the macro's use.
+    fn expand(
+        &self,
+        text: &str,
+        mut line: usize,
+        active: &mut Vec<String>,
+    ) -> Result<Option<String>> {
+        let mut lexer = Lexer::new(text.to_string())
+            .with_language(self.language)
+            .with_trivia();
+        if lexer.tokenize().is_err() {
+            return Ok(None);
+        }
+        let tokens = lexer.take_trivia();
+        let mut expanded = String::with_capacity(text.len());
+        let mut index = 0;
+        while let Some(token) = tokens.get(index) {
+            index += 1;
+            let leading = token.leading_text();
+            line += leading.matches('\n').count();
+            expanded.push_str(&leading);
+            // Keywords can be macro names too
+            let is_name = token
+                .text
+                .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+            if !is_name || active.contains(&token.text) {
+                line += token.text.matches('\n').count();
+                expanded.push_str(&token.text);
+                continue;
+            }
+            match token.text.as_str() {
+                "__LINE__" => expanded.push_str(&line.to_string()),
+                "__FILE__" => expanded.push_str(self.file),
+                "assert" if self.assert.is_some() => match macro_argument(&tokens[index..]) {
+                    Some((argument, used)) => {
+                        let invocation = written(&tokens[index..index + used]);
+                        index += used;
+                        expanded.push_str(&self.assertion(&written(argument), line, active)?);
+                        // An assertion over several lines leaves them in place
+                        let newlines = invocation.matches('\n').count();
+                        line += newlines;
+                        expanded.push_str(&"\n".repeat(newlines));
+                    }
+                    None => expanded.push_str(&token.text),
+                },
+                name => match self.defines.get(name) {
+                    Some(value) => {
+                        active.push(token.text.clone());
+                        if active.len() > self.depth_limit {
+                            return Err(AleccError::PreprocessorError {
+                                line,
+                                message: format!(
+                                    "macro expansion nested more than {} deep (--fmacro-depth): {}",
+                                    self.depth_limit,
+                                    active.join(" -> ")
+                                ),
+                            });
+                        }
+                        let value = self
+                            .expand(value, line, active)?
+                            .unwrap_or_else(|| value.clone());
+                        active.pop();
+                        expanded.push_str(&value);
+                    }
+                    None => expanded.push_str(&token.text),
+                },
+            }
+        }
+        Ok(Some(expanded))
+    }
+
+    /// `assert(condition)` as a statement: the parser has no `?:` to make
+    /// it an expression. The `else` keeps a following `else` for an outer `if`.
+    fn assertion(&self, condition: &str, line: usize, active: &mut Vec<String>) -> Result<String> {
+        if self.assert == Some(AssertMacro::Disabled) {
+            return Ok("((void)0)".to_string());
+        }
+        let written = condition.split_whitespace().collect::<Vec<_>>().join(" ");
+        let condition = self
+            .expand(condition, line, active)?
+            .unwrap_or_else(|| condition.to_string());
+        Ok(format!(
+            "if ({}) {{}} else __assert_fail({}, {}, {}, __func__)",
+            condition.replace('\n', " "),
+            string_literal(&written),
+            self.file,
+            line
+        ))
+    }
+}
+
+/// The tokens between the parentheses of a function-like macro's single
+/// argument, and how many tokens the parenthesized argument takes
+fn macro_argument(tokens: &[Trivia]) -> Option<(&[Trivia], usize)> {
+    if tokens.first()?.text != "(" {
+        return None;
+    }
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text.as_str() {
+            "(" => depth += 1,
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&tokens[1..index], index + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The tokens and their leading trivia, as written
+fn written(tokens: &[Trivia]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("{}{}", token.leading_text(), token.text))
+        .collect()
+}
+
+/// `text` as a C string literal
+fn string_literal(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Standard headers that define the `NULL` macro
+fn defines_null(header: &str) -> bool {
+    matches!(
+        header,
+        "stddef.h" | "stdio.h" | "stdlib.h" | "string.h" | "time.h" | "locale.h" | "wchar.h"
+    )
+}
+
+/// The options `-Wl,` passes to the linker, split at their commas
+/// Write a unit's text output, to stdout when `-o -` names it
+async fn write_output(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if path == Path::new("-") {
+        use tokio::io::AsyncWriteExt;
+        let mut stdout = tokio::io::stdout();
+        stdout
+            .write_all(contents.as_ref())
+            .await
+            .map_err(AleccError::IoError)?;
+        return stdout.flush().await.map_err(AleccError::IoError);
+    }
+    fs::write(path, contents).await.map_err(AleccError::IoError)
+}
+
+fn linker_options(warnings: &[String]) -> Vec<String> {
+    warnings
+        .iter()
+        .filter_map(|option| option.strip_prefix("l,"))
+        .flat_map(|options| options.split(','))
+        .filter(|option| !option.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Which functions get a stack canary; the flags override each other, so
+/// at most one is set
+fn stack_protector(args: &Args) -> StackProtector {
+    if args.stack_protector_all {
+        StackProtector::All
+    } else if args.stack_protector_strong {
+        StackProtector::Strong
+    } else if args.stack_protector {
+        StackProtector::Basic
+    } else {
+        StackProtector::Off
+    }
+}
+
+#[cfg(feature = "serde")]
+fn ast_json(program: &Program) -> Result<String> {
+    serde_json::to_string_pretty(program).map_err(|e| AleccError::CodegenError {
+        message: format!("cannot serialize the AST: {}", e),
+        span: None,
+    })
+}
+
+#[cfg(feature = "serde")]
+fn callgraph_json(graph: &CallGraph) -> Result<String> {
+    graph.to_json().map_err(|e| AleccError::CodegenError {
+        message: format!("cannot serialize the call graph: {}", e),
+        span: None,
+    })
+}
+
+#[cfg(not(feature = "serde"))]
+fn callgraph_json(_graph: &CallGraph) -> Result<String> {
+    unreachable!("Compiler::new rejects --emit-callgraph=json without the serde feature")
+}
+
+#[cfg(not(feature = "serde"))]
+fn ast_json(_program: &Program) -> Result<String> {
+    unreachable!("Compiler::new rejects --emit-ast without the serde feature")
+}
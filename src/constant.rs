@@ -0,0 +1,347 @@
+use crate::layout::TypeLayout;
+use crate::lexer::Span;
+use crate::parser::{BinaryOperator, Expression, ExpressionKind, Type, UnaryOperator};
+use std::fmt;
+
+/// Why an expression has no value at translation time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstantError {
+    /// Something only known at run time, such as a variable or a call
+    NotConstant(Span),
+    DivisionByZero(Span),
+    /// A shift by a negative count or by the width of the type or more
+    ShiftCount(Span),
+}
+
+impl ConstantError {
+    pub fn span(&self) -> Span {
+        match *self {
+            ConstantError::NotConstant(span)
+            | ConstantError::DivisionByZero(span)
+            | ConstantError::ShiftCount(span) => span,
+        }
+    }
+}
+
+impl fmt::Display for ConstantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConstantError::NotConstant(_) => "expression is not an integer constant expression",
+            ConstantError::DivisionByZero(_) => "division by zero in a constant expression",
+            ConstantError::ShiftCount(_) => "shift count out of range in a constant expression",
+        })
+    }
+}
+
+/// Looks up the value of an enumeration constant by name
+pub type Enumerators<'a> = dyn Fn(&str) -> Option<i64> + 'a;
+
+/// An evaluated constant with the width and signedness of its type, kept
+/// truncated to that type: sign-extended, or zero-extended when unsigned
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    value: i64,
+    bits: u32,
+    unsigned: bool,
+}
+
+impl Value {
+    fn int(value: i64) -> Self {
+        Self::of(value, 32, false)
+    }
+
+    /// `value` converted to the integer type of `bits` and `unsigned`
+    fn of(value: i64, bits: u32, unsigned: bool) -> Self {
+        let shift = 64 - bits;
+        let value = match (shift, unsigned) {
+            (0, _) => value,
+            (_, true) => ((value as u64) << shift >> shift) as i64,
+            (_, false) => value << shift >> shift,
+        };
+        Self {
+            value,
+            bits,
+            unsigned,
+        }
+    }
+
+    /// The type `a` and `b` are converted to by the usual arithmetic
+    /// conversions: the wider one, or the unsigned one of equal width
+    fn common(a: Value, b: Value) -> (u32, bool) {
+        match a.bits.cmp(&b.bits) {
+            std::cmp::Ordering::Greater => (a.bits, a.unsigned),
+            std::cmp::Ordering::Less => (b.bits, b.unsigned),
+            std::cmp::Ordering::Equal => (a.bits, a.unsigned || b.unsigned),
+        }
+    }
+
+    /// The result of an operator on `a` and `b` in their common type, where
+    /// arithmetic wraps at its width
+    fn arithmetic(a: Value, b: Value, value: i64) -> Self {
+        let (bits, unsigned) = Self::common(a, b);
+        Self::of(value, bits, unsigned)
+    }
+}
+
+/// Evaluates integer constant expressions: literals, enumeration constants,
+/// `sizeof`, casts to integer types, and the unary, binary and conditional
+/// operators applied to those. Arithmetic is done in the type the operands
+/// convert to, at least `int`, with the width of `long` taken from the layout.
+#[derive(Clone, Copy, Default)]
+pub struct ConstantEvaluator<'a> {
+    layout: Option<&'a TypeLayout>,
+    enumerators: Option<&'a Enumerators<'a>>,
+}
+
+impl<'a> ConstantEvaluator<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluate `sizeof` with the sizes of a target; without a layout it isn't constant
+    pub fn with_layout(mut self, layout: &'a TypeLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Look up the value of an identifier naming an enumeration constant
+    pub fn with_enumerators(mut self, enumerators: &'a Enumerators<'a>) -> Self {
+        self.enumerators = Some(enumerators);
+        self
+    }
+
+    pub fn evaluate(&self, expr: &Expression) -> Result<i64, ConstantError> {
+        self.value(expr).map(|value| value.value)
+    }
+
+    fn value(&self, expr: &Expression) -> Result<Value, ConstantError> {
+        let not_constant = ConstantError::NotConstant(expr.span);
+        Ok(match &expr.kind {
+            // A literal is an `int` if it fits, else a `long` (`long long` on i386)
+            ExpressionKind::IntegerLiteral(value) => match self.long_bits() {
+                _ if i32::try_from(*value).is_ok() => Value::int(*value),
+                bits if Value::of(*value, bits, false).value == *value => {
+                    Value::of(*value, bits, false)
+                }
+                _ => Value::of(*value, 64, false),
+            },
+            ExpressionKind::CharLiteral(c) => Value::int(*c as i64),
+            ExpressionKind::BooleanLiteral(b) => Value::int(*b as i64),
+            // size_t is `unsigned long`
+            ExpressionKind::Sizeof(ty) => Value::of(
+                self.layout.ok_or(not_constant)?.size_of(ty) as i64,
+                self.long_bits(),
+                true,
+            ),
+            ExpressionKind::Identifier(name) => Value::int(
+                self.enumerators
+                    .and_then(|lookup| lookup(name))
+                    .ok_or(not_constant)?,
+            ),
+            ExpressionKind::Unary { operator, operand } => {
+                let operand = self.value(operand)?;
+                let value = match operator {
+                    UnaryOperator::Plus => operand.value,
+                    UnaryOperator::Minus => operand.value.wrapping_neg(),
+                    UnaryOperator::BitwiseNot => !operand.value,
+                    UnaryOperator::LogicalNot => {
+                        return Ok(Value::int((operand.value == 0) as i64))
+                    }
+                    _ => return Err(not_constant),
+                };
+                Value::arithmetic(operand, operand, value)
+            }
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => return self.binary(expr.span, left, operator, right),
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                let (chosen, other) = if self.value(condition)?.value != 0 {
+                    (then_expr, else_expr)
+                } else {
+                    (else_expr, then_expr)
+                };
+                // The other operand only contributes its type to the result
+                let chosen = self.value(chosen)?;
+                let other = self.value(other).unwrap_or(chosen);
+                Value::arithmetic(chosen, other, chosen.value)
+            }
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } => {
+                let value = self.value(expression)?.value;
+                let ty = self.resolve(target_type);
+                let bits = match ty {
+                    Type::Bool => return Ok(Value::int((value != 0) as i64)),
+                    Type::Char | Type::UnsignedChar => 8,
+                    Type::Short | Type::UnsignedShort => 16,
+                    Type::Int | Type::UnsignedInt | Type::Enum { .. } => 32,
+                    Type::Long | Type::UnsignedLong => self.long_bits(),
+                    Type::LongLong | Type::UnsignedLongLong => 64,
+                    _ => return Err(not_constant),
+                };
+                let converted = Value::of(value, bits, ty.is_unsigned());
+                // Types narrower than `int` are promoted to it
+                if bits < 32 {
+                    Value::int(converted.value)
+                } else {
+                    converted
+                }
+            }
+            _ => return Err(not_constant),
+        })
+    }
+
+    fn binary(
+        &self,
+        span: Span,
+        left: &Expression,
+        operator: &BinaryOperator,
+        right: &Expression,
+    ) -> Result<Value, ConstantError> {
+        let a = self.value(left)?;
+        // Only the operand that decides is evaluated
+        match operator {
+            BinaryOperator::LogicalAnd if a.value == 0 => return Ok(Value::int(0)),
+            BinaryOperator::LogicalOr if a.value != 0 => return Ok(Value::int(1)),
+            _ => {}
+        }
+        let b = self.value(right)?;
+        let truth = |holds: bool| Ok(Value::int(holds as i64));
+        if let BinaryOperator::LeftShift | BinaryOperator::RightShift = operator {
+            // The result has the type of the left operand
+            if !(0..a.bits as i64).contains(&b.value) {
+                return Err(ConstantError::ShiftCount(span));
+            }
+            let value = match operator {
+                BinaryOperator::LeftShift => a.value.wrapping_shl(b.value as u32),
+                _ if a.unsigned => ((a.value as u64) >> b.value) as i64,
+                _ => a.value >> b.value,
+            };
+            return Ok(Value::of(value, a.bits, a.unsigned));
+        }
+
+        // Both operands are converted to their common type first
+        let (bits, unsigned) = Value::common(a, b);
+        let (x, y) = (
+            Value::of(a.value, bits, unsigned).value,
+            Value::of(b.value, bits, unsigned).value,
+        );
+        let ordering = if unsigned {
+            (x as u64).cmp(&(y as u64))
+        } else {
+            x.cmp(&y)
+        };
+        let value = match operator {
+            BinaryOperator::Add => x.wrapping_add(y),
+            BinaryOperator::Subtract => x.wrapping_sub(y),
+            BinaryOperator::Multiply => x.wrapping_mul(y),
+            BinaryOperator::Divide | BinaryOperator::Modulo if y == 0 => {
+                return Err(ConstantError::DivisionByZero(span))
+            }
+            BinaryOperator::Divide if unsigned => ((x as u64) / (y as u64)) as i64,
+            BinaryOperator::Modulo if unsigned => ((x as u64) % (y as u64)) as i64,
+            BinaryOperator::Divide => x.wrapping_div(y),
+            BinaryOperator::Modulo => x.wrapping_rem(y),
+            BinaryOperator::BitwiseAnd => x & y,
+            BinaryOperator::BitwiseOr => x | y,
+            BinaryOperator::BitwiseXor => x ^ y,
+            BinaryOperator::Equal => return truth(ordering.is_eq()),
+            BinaryOperator::NotEqual => return truth(ordering.is_ne()),
+            BinaryOperator::Less => return truth(ordering.is_lt()),
+            BinaryOperator::Greater => return truth(ordering.is_gt()),
+            BinaryOperator::LessEqual => return truth(ordering.is_le()),
+            BinaryOperator::GreaterEqual => return truth(ordering.is_ge()),
+            BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => return truth(b.value != 0),
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => unreachable!(),
+        };
+        Ok(Value::of(value, bits, unsigned))
+    }
+
+    /// Width of `long` on the target; 64 bits without a layout
+    fn long_bits(&self) -> u32 {
+        self.layout
+            .map_or(64, |layout| layout.size_of(&Type::Long) as u32 * 8)
+    }
+
+    /// Strip typedefs and qualifiers
+    fn resolve(&self, ty: &Type) -> Type {
+        match self.layout {
+            Some(layout) => layout.resolve(ty),
+            None => match ty {
+                Type::Typedef(_, inner) | Type::Qualified(_, inner) => self.resolve(inner),
+                ty => ty.clone(),
+            },
+        }
+    }
+}
+
+/// The value of an integer constant expression that needs neither the
+/// target's type sizes nor enumeration constants
+pub fn integer_constant(expr: &Expression) -> Option<i64> {
+    ConstantEvaluator::new().evaluate(expr).ok()
+}
+
+/// The value of a constant arithmetic expression, for a floating initializer
+pub fn floating_constant(initializer: &Expression) -> Option<f64> {
+    arithmetic_constant(initializer).map(|(value, _)| value)
+}
+
+/// The value of a constant arithmetic expression, and whether it is
+/// floating: integer operands divide and convert as integers do
+fn arithmetic_constant(expression: &Expression) -> Option<(f64, bool)> {
+    match &expression.kind {
+        ExpressionKind::FloatLiteral(value) => Some((*value, true)),
+        ExpressionKind::IntegerLiteral(value) => Some((*value as f64, false)),
+        ExpressionKind::Unary {
+            operator: UnaryOperator::Minus,
+            operand,
+        } => arithmetic_constant(operand).map(|(value, floating)| (-value, floating)),
+        ExpressionKind::Cast {
+            target_type,
+            expression,
+        } => {
+            let (value, _) = arithmetic_constant(expression)?;
+            match target_type {
+                Type::Float => Some((value as f32 as f64, true)),
+                Type::Double => Some((value, true)),
+                Type::Char
+                | Type::Short
+                | Type::Int
+                | Type::Long
+                | Type::LongLong
+                | Type::UnsignedChar
+                | Type::UnsignedShort
+                | Type::UnsignedInt
+                | Type::UnsignedLong
+                | Type::UnsignedLongLong => Some((value.trunc(), false)),
+                _ => None,
+            }
+        }
+        ExpressionKind::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let (left, left_floating) = arithmetic_constant(left)?;
+            let (right, right_floating) = arithmetic_constant(right)?;
+            let floating = left_floating || right_floating;
+            let value = match operator {
+                BinaryOperator::Add => left + right,
+                BinaryOperator::Subtract => left - right,
+                BinaryOperator::Multiply => left * right,
+                BinaryOperator::Divide if floating => left / right,
+                BinaryOperator::Divide if right != 0.0 => (left / right).trunc(),
+                _ => return None,
+            };
+            Some((value, floating))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,2315 @@
+use crate::analysis::{is_noreturn_call, noreturn_functions};
+use crate::builtins::{
+    atomic, is_builtin, library_function, moves_stack_pointer, signature, Atomic, AtomicOp,
+    AtomicOperation,
+};
+use crate::cli::MissingReturn;
+use crate::constant::ConstantEvaluator;
+use crate::error::{AleccError, Result};
+use crate::layout::TypeLayout;
+use crate::parser::{
+    AssignmentOperator, BinaryOperator, Expression, ExpressionKind, Function, GlobalStorage,
+    GlobalVariable, Program, Statement, StatementKind, Type, UnaryOperator,
+};
+use crate::targets::Target;
+use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{
+    types, AbiParam, AtomicRmwOp, Block, ExternalName, InstBuilder, MemFlags, Signature,
+    StackSlotData, StackSlotKind, TrapCode, UserExternalName, UserFuncName, Value,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::{FinalizedMachReloc, FinalizedRelocTarget};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch};
+use cranelift_module::{default_libcall_names, DataDescription, DataId, FuncId, Linkage, Module};
+use cranelift_object::object::SectionKind;
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use std::collections::{HashMap, HashSet};
+
+/// A lowered C value. Aggregates (structs, unions) are represented by their address.
+#[derive(Debug, Clone)]
+struct Operand {
+    value: Value,
+    ty: Type,
+}
+
+#[derive(Debug, Clone)]
+struct CSignature {
+    return_type: Type,
+    parameters: Vec<Type>,
+    variadic: bool,
+}
+
+fn cranelift_error(error: impl std::fmt::Display) -> AleccError {
+    AleccError::CodegenError {
+        message: format!("cranelift: {}", error),
+        span: None,
+    }
+}
+
+/// What `CraneliftGenerator::generate` made of a translation unit
+pub struct CraneliftObject {
+    /// Contents of the object file
+    pub bytes: Vec<u8>,
+    /// CLIF instructions of every function defined, after optimization
+    pub instruction_counts: Vec<(String, usize)>,
+}
+
+/// Alternative backend (`--backend=cranelift`, behind the `cranelift` cargo
+/// feature) that compiles a translation unit straight to an object file
+/// in-process instead of going through assembly text and `as`.
+pub struct CraneliftGenerator {
+    target: Target,
+    module: ObjectModule,
+    layout: TypeLayout,
+    signatures: HashMap<String, CSignature>,
+    functions: HashMap<String, FuncId>,
+    globals: HashMap<String, (DataId, Type)>,
+    strings: HashMap<String, DataId>,
+    noreturn: HashSet<String>,
+    label_prefix: String,
+    emit_start: bool,
+    missing_return: MissingReturn,
+    ident: Option<String>,
+}
+
+impl CraneliftGenerator {
+    pub fn new(target: Target, optimize: bool, pic: bool, name: &str) -> Result<Self> {
+        if target == Target::I386 {
+            return Err(AleccError::UnsupportedTarget {
+                target: "i386 (cranelift backend)".to_string(),
+            });
+        }
+
+        let mut flags = settings::builder();
+        flags
+            .set("opt_level", if optimize { "speed" } else { "none" })
+            .map_err(cranelift_error)?;
+        flags
+            .set("is_pic", if pic { "true" } else { "false" })
+            .map_err(cranelift_error)?;
+        let isa = cranelift_codegen::isa::lookup_by_name(target.triple())
+            .map_err(cranelift_error)?
+            .finish(settings::Flags::new(flags))
+            .map_err(cranelift_error)?;
+
+        let builder =
+            ObjectBuilder::new(isa, name, default_libcall_names()).map_err(cranelift_error)?;
+
+        Ok(Self {
+            target,
+            module: ObjectModule::new(builder),
+            layout: TypeLayout::new(target),
+            signatures: HashMap::new(),
+            functions: HashMap::new(),
+            globals: HashMap::new(),
+            strings: HashMap::new(),
+            noreturn: HashSet::new(),
+            label_prefix: String::new(),
+            emit_start: true,
+            missing_return: MissingReturn::Unspecified,
+            ident: None,
+        })
+    }
+
+    /// Whether to define `_start` when the program defines `main`
+    pub fn with_start(mut self, emit_start: bool) -> Self {
+        self.emit_start = emit_start;
+        self
+    }
+
+    /// Trap instead of returning 0 when control reaches the closing brace
+    /// of a non-void function other than `main`
+    pub fn with_missing_return(mut self, missing_return: MissingReturn) -> Self {
+        self.missing_return = missing_return;
+        self
+    }
+
+    /// Name string literal data `.L{prefix}C{n}`, like the assembly backend's labels
+    pub fn with_label_prefix(mut self, prefix: &str) -> Self {
+        self.label_prefix = prefix.to_string();
+        self
+    }
+
+    /// Record `ident` in the object's `.comment` section
+    pub fn with_ident(mut self, ident: Option<&str>) -> Self {
+        self.ident = ident.map(str::to_string);
+        self
+    }
+
+    /// Compile the program into an object file
+    pub fn generate(mut self, program: &Program) -> Result<CraneliftObject> {
+        self.layout.record_aggregates(program);
+        self.noreturn = noreturn_functions(program);
+
+        for function in &program.functions {
+            let signature = CSignature {
+                return_type: self.layout.resolve(&function.return_type),
+                parameters: function
+                    .parameters
+                    .iter()
+                    .map(|(_, ty)| parameter_type(&self.layout, ty))
+                    .collect(),
+                variadic: function.is_variadic,
+            };
+            let linkage = if is_declaration(function) {
+                Linkage::Import
+            } else if function.is_static {
+                Linkage::Local
+            } else if function.is_inline_definition() {
+                // Emitted as a weak symbol, so other units may define it too
+                Linkage::Preemptible
+            } else {
+                Linkage::Export
+            };
+            let clif_signature =
+                clif_signature(&self.module, &self.layout, self.target, &signature, &[])?;
+            let id = self
+                .module
+                .declare_function(&function.name, linkage, &clif_signature)
+                .map_err(cranelift_error)?;
+            self.functions.insert(function.name.clone(), id);
+            self.signatures.insert(function.name.clone(), signature);
+        }
+
+        for GlobalVariable {
+            name,
+            var_type,
+            initializer,
+            storage,
+            thread_local,
+            attributes,
+            ..
+        } in &program.global_variables
+        {
+            if *thread_local {
+                return Err(AleccError::CodegenError {
+                    message: format!(
+                        "thread-local variable '{}' is not supported by the cranelift backend",
+                        name
+                    ),
+                    span: None,
+                });
+            }
+            let alignment = attributes.aligned;
+            let section = attributes.section.as_deref();
+            let linkage = match storage {
+                Some(GlobalStorage::Extern) => Linkage::Import,
+                Some(GlobalStorage::Static) => Linkage::Local,
+                None => Linkage::Export,
+            };
+            self.define_global(
+                name,
+                var_type,
+                initializer.as_ref(),
+                alignment,
+                section,
+                linkage,
+            )?;
+        }
+
+        let mut instruction_counts = Vec::new();
+        for function in &program.functions {
+            if !is_declaration(function) {
+                let instructions = self.define_function(function)?;
+                instruction_counts.push((function.name.clone(), instructions));
+            }
+        }
+
+        let main = program
+            .functions
+            .iter()
+            .find(|f| f.name == "main" && !is_declaration(f));
+        if let Some(main) = main.filter(|_| self.emit_start) {
+            let returns_void = matches!(self.layout.resolve(&main.return_type), Type::Void);
+            self.define_start(returns_void)?;
+        }
+
+        let mut product = self.module.finish();
+        if let Some(ident) = &self.ident {
+            // Laid out like `as` does it: a leading NUL, then NUL-terminated strings
+            let comment = product.object.add_section(
+                Vec::new(),
+                b".comment".to_vec(),
+                SectionKind::OtherString,
+            );
+            let mut bytes = vec![0];
+            bytes.extend_from_slice(ident.as_bytes());
+            bytes.push(0);
+            product.object.append_section_data(comment, &bytes, 1);
+        }
+        Ok(CraneliftObject {
+            bytes: product.emit().map_err(cranelift_error)?,
+            instruction_counts,
+        })
+    }
+
+    fn define_global(
+        &mut self,
+        name: &str,
+        var_type: &Type,
+        initializer: Option<&Expression>,
+        alignment: Option<usize>,
+        section: Option<&str>,
+        linkage: Linkage,
+    ) -> Result<()> {
+        let ty = self.layout.resolve(var_type);
+        let size = self.layout.size_of(&ty);
+        let id = self
+            .module
+            .declare_data(name, linkage, true, false)
+            .map_err(cranelift_error)?;
+        self.globals.insert(name.to_string(), (id, ty.clone()));
+        if linkage == Linkage::Import {
+            return Ok(());
+        }
+
+        let mut data = DataDescription::new();
+        data.set_align(alignment.unwrap_or_else(|| self.layout.align_of(&ty)) as u64);
+        if let Some(section) = section {
+            // ELF has no segment names; only the section name is used
+            data.set_segment_section("", section);
+        }
+        match initializer {
+            // A custom section holds initialized data, so spell the zeros out
+            None if section.is_some() => data.define(vec![0u8; size].into_boxed_slice()),
+            None => data.define_zeroinit(size),
+            Some(Expression {
+                kind: ExpressionKind::StringLiteral(content),
+                ..
+            }) if is_pointer(&ty) => {
+                let string = string_data(
+                    &mut self.module,
+                    &mut self.strings,
+                    &self.label_prefix,
+                    content,
+                )?;
+                let value = self.module.declare_data_in_data(string, &mut data);
+                data.define(vec![0u8; size].into_boxed_slice());
+                data.write_data_addr(0, value, 0);
+            }
+            Some(initializer) => {
+                let bytes = constant_bytes(&self.layout, &ty, initializer).ok_or_else(|| {
+                    AleccError::CodegenError {
+                        message: format!("initializer of '{}' is not a constant", name),
+                        span: Some(initializer.span),
+                    }
+                })?;
+                data.define(bytes.into_boxed_slice());
+            }
+        }
+        self.module.define_data(id, &data).map_err(cranelift_error)
+    }
+
+    /// Define a function with a body, returning how many instructions it compiled to
+    fn define_function(&mut self, function: &Function) -> Result<usize> {
+        if function.is_variadic {
+            return Err(AleccError::CodegenError {
+                message: format!(
+                    "variadic function '{}' is not supported by the cranelift backend",
+                    function.name
+                ),
+                span: None,
+            });
+        }
+        let attributes = &function.attributes;
+        if let Some(attribute) = [
+            ("aligned", attributes.aligned.is_some()),
+            ("section", attributes.section.is_some()),
+            ("constructor", attributes.constructor),
+            ("destructor", attributes.destructor),
+        ]
+        .into_iter()
+        .find_map(|(name, set)| set.then_some(name))
+        {
+            return Err(AleccError::CodegenError {
+                message: format!(
+                    "'{}' attribute on function '{}' is not supported by the cranelift backend",
+                    attribute, function.name
+                ),
+                span: None,
+            });
+        }
+
+        let id = self.functions[&function.name];
+        let signature = self.signatures[&function.name].clone();
+        let mut context = self.module.make_context();
+        context.func.signature =
+            clif_signature(&self.module, &self.layout, self.target, &signature, &[])?;
+        context.func.name = UserFuncName::user(0, id.as_u32());
+
+        let mut builder_context = FunctionBuilderContext::new();
+        {
+            let builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+            let lowering = FunctionLowering {
+                builder,
+                target: self.target,
+                module: &mut self.module,
+                layout: &self.layout,
+                signatures: &self.signatures,
+                functions: &mut self.functions,
+                globals: &self.globals,
+                strings: &mut self.strings,
+                noreturn: &self.noreturn,
+                label_prefix: &self.label_prefix,
+                scopes: vec![HashMap::new()],
+                labels: HashMap::new(),
+                break_blocks: Vec::new(),
+                continue_blocks: Vec::new(),
+                return_type: signature.return_type.clone(),
+                trap_missing_return: self.missing_return == MissingReturn::Trap
+                    && function.name != "main",
+                terminated: false,
+            };
+            lowering.lower_function(function, &signature)?;
+        }
+
+        self.module
+            .define_function(id, &mut context)
+            .map_err(|e| AleccError::CodegenError {
+                message: format!("cranelift: in function '{}': {:?}", function.name, e),
+                span: None,
+            })?;
+        let layout = &context.func.layout;
+        Ok(layout
+            .blocks()
+            .map(|block| layout.block_insts(block).count())
+            .sum())
+    }
+
+    /// `_start` can't be written in CLIF (the stack is not call-aligned on
+    /// entry and exit needs a raw syscall), so emit the same stub the assembly
+    /// backend uses as machine code, with a relocation for the call to main.
+    fn define_start(&mut self, main_returns_void: bool) -> Result<()> {
+        let main = self.functions["main"];
+        let start = self
+            .module
+            .declare_function("_start", Linkage::Export, &self.module.make_signature())
+            .map_err(cranelift_error)?;
+
+        let mut func = cranelift_codegen::ir::Function::new();
+        let main_ref = func.declare_imported_user_function(UserExternalName::new(0, main.as_u32()));
+
+        let (code, reloc): (Vec<u8>, FinalizedMachReloc) = match self.target {
+            Target::Amd64 => (
+                [
+                    &[
+                        0x31, 0xED, // xor ebp, ebp
+                        0x48, 0x8B, 0x3C, 0x24, // mov rdi, [rsp] (argc)
+                        0x48, 0x8D, 0x74, 0x24, 0x08, // lea rsi, [rsp + 8] (argv)
+                        0x48, 0x8D, 0x54, 0xFE, 0x08, // lea rdx, [rsi + rdi*8 + 8] (envp)
+                        0x48, 0x83, 0xE4, 0xF0, // and rsp, -16
+                        0xE8, 0x00, 0x00, 0x00, 0x00, // call main
+                    ][..],
+                    if main_returns_void {
+                        &[0x31, 0xFF] // xor edi, edi
+                    } else {
+                        &[0x89, 0xC7] // mov edi, eax
+                    },
+                    &[
+                        0xB8, 0x3C, 0x00, 0x00, 0x00, // mov eax, 60 (exit)
+                        0x0F, 0x05, // syscall
+                    ],
+                ]
+                .concat(),
+                FinalizedMachReloc {
+                    offset: 21,
+                    kind: Reloc::X86CallPCRel4,
+                    target: FinalizedRelocTarget::ExternalName(ExternalName::User(main_ref)),
+                    addend: -4,
+                },
+            ),
+            Target::Arm64 => (
+                [
+                    0xD280001Du32, // mov x29, #0
+                    0xF94003E0,    // ldr x0, [sp] (argc)
+                    0x910023E1,    // add x1, sp, #8 (argv)
+                    0x8B000C22,    // add x2, x1, x0, lsl #3
+                    0x91002042,    // add x2, x2, #8 (envp)
+                    0x94000000,    // bl main
+                ]
+                .into_iter()
+                .chain(main_returns_void.then_some(0xD2800000)) // mov x0, #0
+                .chain([
+                    0xD2800BA8, // mov x8, #93 (exit)
+                    0xD4000001, // svc #0
+                ])
+                .flat_map(|word: u32| word.to_le_bytes())
+                .collect(),
+                FinalizedMachReloc {
+                    offset: 20,
+                    kind: Reloc::Arm64Call,
+                    target: FinalizedRelocTarget::ExternalName(ExternalName::User(main_ref)),
+                    addend: 0,
+                },
+            ),
+            Target::I386 => unreachable!("rejected in CraneliftGenerator::new"),
+        };
+
+        self.module
+            .define_function_bytes(start, &func, 16, &code, &[reloc])
+            .map_err(cranelift_error)
+    }
+}
+
+/// State for lowering the body of one function
+struct FunctionLowering<'a> {
+    builder: FunctionBuilder<'a>,
+    target: Target,
+    module: &'a mut ObjectModule,
+    layout: &'a TypeLayout,
+    signatures: &'a HashMap<String, CSignature>,
+    functions: &'a mut HashMap<String, FuncId>,
+    globals: &'a HashMap<String, (DataId, Type)>,
+    strings: &'a mut HashMap<String, DataId>,
+    noreturn: &'a HashSet<String>,
+    label_prefix: &'a str,
+    scopes: Vec<HashMap<String, (Value, Type)>>,
+    labels: HashMap<String, Block>,
+    break_blocks: Vec<Block>,
+    continue_blocks: Vec<Block>,
+    return_type: Type,
+    trap_missing_return: bool,
+    terminated: bool,
+}
+
+impl FunctionLowering<'_> {
+    fn lower_function(mut self, function: &Function, signature: &CSignature) -> Result<()> {
+        let entry = self.builder.create_block();
+        self.builder.append_block_params_for_function_params(entry);
+        self.builder.switch_to_block(entry);
+
+        let params = self.builder.block_params(entry).to_vec();
+        for ((name, _), (value, ty)) in function
+            .parameters
+            .iter()
+            .zip(params.into_iter().zip(&signature.parameters))
+        {
+            let address = self.declare_local(name, ty, None);
+            self.builder
+                .ins()
+                .store(MemFlags::trusted(), value, address, 0);
+        }
+
+        self.lower_statement(&function.body)?;
+
+        if !self.terminated {
+            // Falling off the end returns 0 (required for main, harmless elsewhere)
+            match scalar_type(self.layout, self.target, &self.return_type) {
+                Some(_) if self.trap_missing_return => {
+                    self.builder.ins().trap(TrapCode::unwrap_user(2));
+                }
+                Some(clif_type) => {
+                    let zero = self.zero(clif_type);
+                    self.builder.ins().return_(&[zero]);
+                }
+                None => {
+                    self.builder.ins().return_(&[]);
+                }
+            }
+        }
+
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+        Ok(())
+    }
+
+    /// An error takes the span of the deepest node it came from
+    fn lower_statement(&mut self, statement: &Statement) -> Result<()> {
+        self.lower_statement_unlocated(statement)
+            .map_err(|error| error.at(statement.span))
+    }
+
+    fn lower_statement_unlocated(&mut self, statement: &Statement) -> Result<()> {
+        match &statement.kind {
+            StatementKind::Expression(expr) => {
+                self.lower_expression(expr)?;
+                if is_noreturn_call(statement, self.noreturn) {
+                    self.builder.ins().trap(TrapCode::unwrap_user(1));
+                    self.terminated = true;
+                }
+            }
+            StatementKind::Declaration {
+                name,
+                var_type,
+                initializer,
+                alignment,
+                ..
+            } => {
+                let ty = self.layout.resolve(var_type);
+                if matches!(ty, Type::Void) {
+                    return Err(AleccError::CodegenError {
+                        message: format!("variable '{}' has incomplete type", name),
+                        span: None,
+                    });
+                }
+                if let Some(init) = initializer {
+                    let value = self.lower_expression(init)?;
+                    let address = self.declare_local(name, &ty, *alignment);
+                    self.store(value, address, &ty)?;
+                } else {
+                    self.declare_local(name, &ty, *alignment);
+                }
+            }
+            StatementKind::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                for stmt in statements {
+                    self.lower_statement(stmt)?;
+                }
+                self.scopes.pop();
+            }
+            StatementKind::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let end_block = self.builder.create_block();
+
+                let cond = self.lower_condition(condition)?;
+                let false_target = if else_stmt.is_some() {
+                    else_block
+                } else {
+                    end_block
+                };
+                self.builder
+                    .ins()
+                    .brif(cond, then_block, &[], false_target, &[]);
+                self.terminated = true;
+
+                self.start_block(then_block);
+                self.lower_statement(then_stmt)?;
+                self.jump(end_block);
+
+                if let Some(else_stmt) = else_stmt {
+                    self.start_block(else_block);
+                    self.lower_statement(else_stmt)?;
+                    self.jump(end_block);
+                }
+
+                self.start_block(end_block);
+            }
+            StatementKind::While { condition, body } => {
+                let cond_block = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let end_block = self.builder.create_block();
+
+                self.start_block(cond_block);
+                let cond = self.lower_condition(condition)?;
+                self.builder
+                    .ins()
+                    .brif(cond, body_block, &[], end_block, &[]);
+                self.terminated = true;
+
+                self.start_block(body_block);
+                self.lower_loop_body(body, end_block, cond_block)?;
+                self.jump(cond_block);
+
+                self.start_block(end_block);
+            }
+            StatementKind::DoWhile { body, condition } => {
+                let body_block = self.builder.create_block();
+                let cond_block = self.builder.create_block();
+                let end_block = self.builder.create_block();
+
+                self.start_block(body_block);
+                self.lower_loop_body(body, end_block, cond_block)?;
+
+                self.start_block(cond_block);
+                let cond = self.lower_condition(condition)?;
+                self.builder
+                    .ins()
+                    .brif(cond, body_block, &[], end_block, &[]);
+                self.terminated = true;
+
+                self.start_block(end_block);
+            }
+            StatementKind::For {
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                let cond_block = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let inc_block = self.builder.create_block();
+                let end_block = self.builder.create_block();
+
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.lower_statement(init)?;
+                }
+
+                self.start_block(cond_block);
+                if let Some(condition) = condition {
+                    let cond = self.lower_condition(condition)?;
+                    self.builder
+                        .ins()
+                        .brif(cond, body_block, &[], end_block, &[]);
+                    self.terminated = true;
+                }
+
+                self.start_block(body_block);
+                self.lower_loop_body(body, end_block, inc_block)?;
+
+                self.start_block(inc_block);
+                if let Some(increment) = increment {
+                    self.lower_expression(increment)?;
+                }
+                self.jump(cond_block);
+
+                self.start_block(end_block);
+                self.scopes.pop();
+            }
+            StatementKind::Switch { expression, cases } => {
+                let value = self.lower_expression(expression)?;
+                let ty = promote(self.layout, &value.ty);
+                let value = self.convert(value, &ty)?;
+                let bits = self.layout.size_of(&ty) * 8;
+
+                let end_block = self.builder.create_block();
+                let mut default_block = end_block;
+                let mut case_blocks = Vec::new();
+                let mut switch = Switch::new();
+                for (case_value, _) in cases {
+                    let block = self.builder.create_block();
+                    match case_value {
+                        Some(expr) => {
+                            let constant =
+                                integer_constant(self.layout, expr).ok_or_else(|| {
+                                    AleccError::CodegenError {
+                                        message: "case label is not an integer constant"
+                                            .to_string(),
+                                        span: None,
+                                    }
+                                })?;
+                            // Switch compares unsigned values of the operand's width
+                            switch.set_entry(constant as u64 as u128 & mask(bits), block);
+                        }
+                        None => default_block = block,
+                    }
+                    case_blocks.push(block);
+                }
+                switch.emit(&mut self.builder, value, default_block);
+                self.terminated = true;
+
+                self.break_blocks.push(end_block);
+                for ((_, statements), block) in cases.iter().zip(case_blocks) {
+                    // Falls through into the next case unless a break ends the block
+                    self.start_block(block);
+                    for stmt in statements {
+                        self.lower_statement(stmt)?;
+                    }
+                }
+                self.break_blocks.pop();
+
+                self.start_block(end_block);
+            }
+            StatementKind::Return(expr) => {
+                let return_type = self.return_type.clone();
+                let value = match expr {
+                    Some(expr) => Some(self.lower_expression(expr)?),
+                    None => None,
+                };
+                match scalar_type(self.layout, self.target, &return_type) {
+                    Some(clif_type) => {
+                        let value = match value {
+                            Some(value) => self.convert(value, &return_type)?,
+                            None => self.zero(clif_type),
+                        };
+                        self.builder.ins().return_(&[value]);
+                    }
+                    None => {
+                        self.builder.ins().return_(&[]);
+                    }
+                }
+                self.terminated = true;
+            }
+            StatementKind::Break => {
+                let block = *self
+                    .break_blocks
+                    .last()
+                    .ok_or_else(|| AleccError::CodegenError {
+                        message: "break statement not within a loop or switch".to_string(),
+                        span: None,
+                    })?;
+                self.jump(block);
+            }
+            StatementKind::Continue => {
+                let block =
+                    *self
+                        .continue_blocks
+                        .last()
+                        .ok_or_else(|| AleccError::CodegenError {
+                            message: "continue statement not within a loop".to_string(),
+                            span: None,
+                        })?;
+                self.jump(block);
+            }
+            StatementKind::Goto(label) => {
+                let block = self.label_block(label);
+                self.jump(block);
+            }
+            StatementKind::Label(label) => {
+                let block = self.label_block(label);
+                self.start_block(block);
+            }
+            StatementKind::ComputedGoto(_) => {
+                return Err(AleccError::CodegenError {
+                    message: "computed goto is not supported by the cranelift backend".to_string(),
+                    span: None,
+                })
+            }
+            StatementKind::Fallthrough => {}
+        }
+        Ok(())
+    }
+
+    fn lower_loop_body(
+        &mut self,
+        body: &Statement,
+        break_block: Block,
+        continue_block: Block,
+    ) -> Result<()> {
+        self.break_blocks.push(break_block);
+        self.continue_blocks.push(continue_block);
+        let result = self.lower_statement(body);
+        self.break_blocks.pop();
+        self.continue_blocks.pop();
+        result
+    }
+
+    fn lower_expression(&mut self, expression: &Expression) -> Result<Operand> {
+        self.lower_expression_unlocated(expression)
+            .map_err(|error| error.at(expression.span))
+    }
+
+    fn lower_expression_unlocated(&mut self, expression: &Expression) -> Result<Operand> {
+        match &expression.kind {
+            ExpressionKind::IntegerLiteral(value) => {
+                let ty = if i32::try_from(*value).is_ok() {
+                    Type::Int
+                } else {
+                    Type::Long
+                };
+                Ok(self.integer(*value, ty))
+            }
+            ExpressionKind::FloatLiteral(value) => Ok(Operand {
+                value: self.builder.ins().f64const(*value),
+                ty: Type::Double,
+            }),
+            ExpressionKind::CharLiteral(c) => Ok(self.integer(*c as i64, Type::Int)),
+            ExpressionKind::BooleanLiteral(b) => Ok(self.integer(*b as i64, Type::Int)),
+            ExpressionKind::StringLiteral(content) => {
+                let id = string_data(self.module, self.strings, self.label_prefix, content)?;
+                let value = self.data_address(id);
+                Ok(Operand {
+                    value,
+                    ty: Type::Pointer(Box::new(Type::Char)),
+                })
+            }
+            ExpressionKind::Identifier(name) => {
+                if self.lookup(name).is_none()
+                    && !self.globals.contains_key(name)
+                    && self.signatures.contains_key(name)
+                {
+                    return self.function_address(name);
+                }
+                let (address, ty) = self.lower_address(expression)?;
+                self.load(address, &ty)
+            }
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => match operator {
+                BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                    self.lower_logical(left, operator, right)
+                }
+                _ => {
+                    let lhs = self.lower_expression(left)?;
+                    let rhs = self.lower_expression(right)?;
+                    self.binary_values(operator, lhs, rhs)
+                }
+            },
+            ExpressionKind::Unary { operator, operand } => self.lower_unary(operator, operand),
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => self.lower_call(function, arguments),
+            ExpressionKind::Member { .. } | ExpressionKind::Index { .. } => {
+                let (address, ty) = self.lower_address(expression)?;
+                self.load(address, &ty)
+            }
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } => {
+                let value = self.lower_expression(expression)?;
+                let ty = self.layout.resolve(target_type);
+                if matches!(ty, Type::Void) {
+                    return Ok(self.void());
+                }
+                let value = self.convert(value, &ty)?;
+                Ok(Operand { value, ty })
+            }
+            ExpressionKind::Sizeof(ty) => {
+                let size = self.layout.size_of(ty) as i64;
+                Ok(self.integer(size, Type::Long))
+            }
+            ExpressionKind::Assignment {
+                target,
+                operator,
+                value,
+            } => self.lower_assignment(target, operator, value),
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => self.lower_conditional(condition, then_expr, else_expr),
+            ExpressionKind::VaArg { .. } => Err(AleccError::CodegenError {
+                message: "va_arg is not supported by the cranelift backend".to_string(),
+                span: None,
+            }),
+            ExpressionKind::LabelAddress(_) => Err(AleccError::CodegenError {
+                message: "label addresses are not supported by the cranelift backend".to_string(),
+                span: None,
+            }),
+        }
+    }
+
+    /// Compute the address of an lvalue, returning it with the type stored there
+    fn lower_address(&mut self, expression: &Expression) -> Result<(Value, Type)> {
+        self.lower_address_unlocated(expression)
+            .map_err(|error| error.at(expression.span))
+    }
+
+    fn lower_address_unlocated(&mut self, expression: &Expression) -> Result<(Value, Type)> {
+        match &expression.kind {
+            ExpressionKind::Identifier(name) => {
+                if let Some((address, ty)) = self.lookup(name) {
+                    Ok((address, ty))
+                } else if let Some((id, ty)) = self.globals.get(name).cloned() {
+                    Ok((self.data_address(id), ty))
+                } else {
+                    Err(AleccError::CodegenError {
+                        message: format!("use of undeclared identifier '{}'", name),
+                        span: None,
+                    })
+                }
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => {
+                let pointer = self.lower_expression(operand)?;
+                let pointee = pointee(self.layout, &pointer.ty)?;
+                Ok((pointer.value, pointee))
+            }
+            ExpressionKind::Index { array, index } => {
+                let base = self.lower_expression(array)?;
+                let index = self.lower_expression(index)?;
+                // a[i] is *(a + i), whichever operand is the pointer
+                let (base, index) = if is_integer(self.layout, &base.ty) {
+                    (index, base)
+                } else {
+                    (base, index)
+                };
+                let element = pointee(self.layout, &base.ty)?;
+                let address = self.element_address(base.value, &element, index, false)?;
+                Ok((address, element))
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let (base, aggregate) = if *is_arrow {
+                    let pointer = self.lower_expression(object)?;
+                    let pointee = pointee(self.layout, &pointer.ty)?;
+                    (pointer.value, pointee)
+                } else if object.is_lvalue() {
+                    self.lower_address(object)?
+                } else {
+                    // A struct returned by value (`f().x`) is already held by address
+                    let aggregate = self.lower_expression(object)?;
+                    (aggregate.value, aggregate.ty)
+                };
+                let (offset, ty) = self.layout.member(&aggregate, member).ok_or_else(|| {
+                    AleccError::CodegenError {
+                        message: format!("no member named '{}'", member),
+                        span: None,
+                    }
+                })?;
+                let address = self.builder.ins().iadd_imm(base, offset as i64);
+                Ok((address, ty))
+            }
+            _ => Err(AleccError::CodegenError {
+                message: "expression is not assignable".to_string(),
+                span: None,
+            }),
+        }
+    }
+
+    fn lower_logical(
+        &mut self,
+        left: &Expression,
+        operator: &BinaryOperator,
+        right: &Expression,
+    ) -> Result<Operand> {
+        let is_and = matches!(operator, BinaryOperator::LogicalAnd);
+        let rhs_block = self.builder.create_block();
+        let end_block = self.builder.create_block();
+        self.builder.append_block_param(end_block, types::I8);
+
+        let lhs = self.lower_condition(left)?;
+        let short_circuit = self.builder.ins().iconst(types::I8, !is_and as i64);
+        if is_and {
+            self.builder
+                .ins()
+                .brif(lhs, rhs_block, &[], end_block, &[short_circuit]);
+        } else {
+            self.builder
+                .ins()
+                .brif(lhs, end_block, &[short_circuit], rhs_block, &[]);
+        }
+        self.terminated = true;
+
+        self.start_block(rhs_block);
+        let rhs = self.lower_condition(right)?;
+        self.builder.ins().jump(end_block, &[rhs]);
+        self.terminated = true;
+
+        self.start_block(end_block);
+        let flag = self.builder.block_params(end_block)[0];
+        Ok(self.bool_to_int(flag))
+    }
+
+    fn binary_values(
+        &mut self,
+        operator: &BinaryOperator,
+        lhs: Operand,
+        rhs: Operand,
+    ) -> Result<Operand> {
+        let lhs_pointer = is_pointer(&self.layout.resolve(&lhs.ty));
+        let rhs_pointer = is_pointer(&self.layout.resolve(&rhs.ty));
+        // Pointer arithmetic
+        match operator {
+            BinaryOperator::Add | BinaryOperator::Subtract if lhs_pointer && !rhs_pointer => {
+                let element = pointee(self.layout, &lhs.ty)?;
+                let negate = matches!(operator, BinaryOperator::Subtract);
+                let value = self.element_address(lhs.value, &element, rhs, negate)?;
+                return Ok(Operand {
+                    value,
+                    ty: self.layout.resolve(&lhs.ty),
+                });
+            }
+            BinaryOperator::Add if rhs_pointer && !lhs_pointer => {
+                let element = pointee(self.layout, &rhs.ty)?;
+                let value = self.element_address(rhs.value, &element, lhs, false)?;
+                return Ok(Operand {
+                    value,
+                    ty: self.layout.resolve(&rhs.ty),
+                });
+            }
+            BinaryOperator::Subtract if lhs_pointer && rhs_pointer => {
+                let element = pointee(self.layout, &lhs.ty)?;
+                let size = self.layout.size_of(&element).max(1) as i64;
+                let bytes = self.builder.ins().isub(lhs.value, rhs.value);
+                let value = self.builder.ins().sdiv_imm(bytes, size);
+                return Ok(Operand {
+                    value,
+                    ty: Type::Long,
+                });
+            }
+            _ => {}
+        }
+
+        if let Some((int_cc, float_cc)) = comparison_codes(operator) {
+            if lhs_pointer || rhs_pointer {
+                let pointer = Type::Pointer(Box::new(Type::Void));
+                let l = self.convert(lhs, &pointer)?;
+                let r = self.convert(rhs, &pointer)?;
+                let flag = self.builder.ins().icmp(unsigned_cc(int_cc), l, r);
+                return Ok(self.bool_to_int(flag));
+            }
+
+            let ty = common_type(self.layout, &lhs.ty, &rhs.ty);
+            let l = self.convert(lhs, &ty)?;
+            let r = self.convert(rhs, &ty)?;
+            let flag = if is_floating(self.layout, &ty) {
+                self.builder.ins().fcmp(float_cc, l, r)
+            } else if ty.is_unsigned() {
+                self.builder.ins().icmp(unsigned_cc(int_cc), l, r)
+            } else {
+                self.builder.ins().icmp(int_cc, l, r)
+            };
+            return Ok(self.bool_to_int(flag));
+        }
+
+        // Shifts take the (promoted) type of the left operand
+        let ty = match operator {
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => promote(self.layout, &lhs.ty),
+            _ => common_type(self.layout, &lhs.ty, &rhs.ty),
+        };
+        let floating = is_floating(self.layout, &ty);
+        if !floating && !is_integer(self.layout, &ty) {
+            return Err(AleccError::CodegenError {
+                message: format!("invalid operands to binary {:?}", operator),
+                span: None,
+            });
+        }
+        let l = self.convert(lhs, &ty)?;
+        let r = self.convert(rhs, &ty)?;
+
+        let unsigned = ty.is_unsigned();
+        let ins = self.builder.ins();
+        let value = match (operator, floating) {
+            (BinaryOperator::Add, false) => ins.iadd(l, r),
+            (BinaryOperator::Add, true) => ins.fadd(l, r),
+            (BinaryOperator::Subtract, false) => ins.isub(l, r),
+            (BinaryOperator::Subtract, true) => ins.fsub(l, r),
+            (BinaryOperator::Multiply, false) => ins.imul(l, r),
+            (BinaryOperator::Multiply, true) => ins.fmul(l, r),
+            (BinaryOperator::Divide, false) if unsigned => ins.udiv(l, r),
+            (BinaryOperator::Divide, false) => ins.sdiv(l, r),
+            (BinaryOperator::Divide, true) => ins.fdiv(l, r),
+            (BinaryOperator::Modulo, false) if unsigned => ins.urem(l, r),
+            (BinaryOperator::Modulo, false) => ins.srem(l, r),
+            (BinaryOperator::BitwiseAnd, false) => ins.band(l, r),
+            (BinaryOperator::BitwiseOr, false) => ins.bor(l, r),
+            (BinaryOperator::BitwiseXor, false) => ins.bxor(l, r),
+            (BinaryOperator::LeftShift, false) => ins.ishl(l, r),
+            (BinaryOperator::RightShift, false) if unsigned => ins.ushr(l, r),
+            (BinaryOperator::RightShift, false) => ins.sshr(l, r),
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("invalid operands to binary {:?}", operator),
+                    span: None,
+                });
+            }
+        };
+        Ok(Operand { value, ty })
+    }
+
+    fn lower_unary(&mut self, operator: &UnaryOperator, operand: &Expression) -> Result<Operand> {
+        match operator {
+            UnaryOperator::Plus | UnaryOperator::Minus | UnaryOperator::BitwiseNot => {
+                let value = self.lower_expression(operand)?;
+                let ty = promote(self.layout, &value.ty);
+                let floating = is_floating(self.layout, &ty);
+                let value = self.convert(value, &ty)?;
+                let value = match operator {
+                    UnaryOperator::Plus => value,
+                    UnaryOperator::Minus if floating => self.builder.ins().fneg(value),
+                    UnaryOperator::Minus => self.builder.ins().ineg(value),
+                    _ if floating => {
+                        return Err(AleccError::CodegenError {
+                            message: "invalid operand to unary ~".to_string(),
+                            span: None,
+                        });
+                    }
+                    _ => self.builder.ins().bnot(value),
+                };
+                Ok(Operand { value, ty })
+            }
+            UnaryOperator::LogicalNot => {
+                let cond = self.lower_condition(operand)?;
+                let inverted = self.builder.ins().bxor_imm(cond, 1);
+                Ok(self.bool_to_int(inverted))
+            }
+            UnaryOperator::PreIncrement
+            | UnaryOperator::PreDecrement
+            | UnaryOperator::PostIncrement
+            | UnaryOperator::PostDecrement => {
+                let is_prefix = matches!(
+                    operator,
+                    UnaryOperator::PreIncrement | UnaryOperator::PreDecrement
+                );
+                let step = match operator {
+                    UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => {
+                        BinaryOperator::Add
+                    }
+                    _ => BinaryOperator::Subtract,
+                };
+
+                let (address, ty) = self.lower_address(operand)?;
+                let old = self.load(address, &ty)?;
+                let one = self.integer(1, Type::Int);
+                let new = self.binary_values(&step, old.clone(), one)?;
+                let stored = self.convert(new, &ty)?;
+                self.builder
+                    .ins()
+                    .store(MemFlags::new(), stored, address, 0);
+
+                if is_prefix {
+                    Ok(Operand { value: stored, ty })
+                } else {
+                    Ok(old)
+                }
+            }
+            UnaryOperator::AddressOf => {
+                if let ExpressionKind::Identifier(name) = &operand.kind {
+                    if self.lookup(name).is_none()
+                        && !self.globals.contains_key(name)
+                        && self.signatures.contains_key(name)
+                    {
+                        return self.function_address(name);
+                    }
+                }
+                let (address, ty) = self.lower_address(operand)?;
+                Ok(Operand {
+                    value: address,
+                    ty: Type::Pointer(Box::new(ty)),
+                })
+            }
+            UnaryOperator::Dereference => {
+                let pointer = self.lower_expression(operand)?;
+                let pointee = pointee(self.layout, &pointer.ty)?;
+                if matches!(pointee, Type::Function { .. }) {
+                    // *fp designates the function itself
+                    return Ok(pointer);
+                }
+                self.load(pointer.value, &pointee)
+            }
+        }
+    }
+
+    fn lower_call(&mut self, function: &Expression, arguments: &[Expression]) -> Result<Operand> {
+        let direct = match &function.kind {
+            ExpressionKind::Identifier(name)
+                if self.lookup(name).is_none() && !self.globals.contains_key(name) =>
+            {
+                Some(name.clone())
+            }
+            _ => None,
+        };
+        if direct.as_deref().is_some_and(moves_stack_pointer) {
+            return Err(AleccError::CodegenError {
+                message:
+                    "alloca and variable length arrays are not supported by the cranelift backend"
+                        .to_string(),
+                span: None,
+            });
+        }
+        if let Some(name) = direct.as_deref() {
+            if is_builtin(name) {
+                return self.lower_builtin(name, arguments);
+            }
+            if let Some(atomic) = atomic(name).filter(|_| !self.signatures.contains_key(name)) {
+                return self.lower_atomic(name, atomic, arguments);
+            }
+        }
+
+        let signature = match &direct {
+            Some(name) => self.signatures.get(name).cloned(),
+            None => None,
+        };
+        let (callee, signature) = match direct {
+            Some(name) => (None, signature.map(|s| (s, name))),
+            None => {
+                let value = self.lower_expression(function)?;
+                let signature = match pointee(self.layout, &value.ty) {
+                    Ok(Type::Function {
+                        return_type,
+                        parameters,
+                        variadic,
+                    }) => Some(CSignature {
+                        return_type: self.layout.resolve(&return_type),
+                        parameters: parameters
+                            .iter()
+                            .map(|ty| parameter_type(self.layout, ty))
+                            .collect(),
+                        variadic,
+                    }),
+                    _ => None,
+                };
+                (Some(value.value), signature.map(|s| (s, String::new())))
+            }
+        };
+
+        let (signature, name) = match signature {
+            Some((signature, name)) => (signature, name),
+            None => {
+                // No prototype: int return, arguments get the default promotions
+                let name = match &function.kind {
+                    ExpressionKind::Identifier(name) => name.clone(),
+                    _ => String::new(),
+                };
+                (
+                    CSignature {
+                        return_type: Type::Int,
+                        parameters: Vec::new(),
+                        variadic: true,
+                    },
+                    name,
+                )
+            }
+        };
+
+        let mut values = Vec::new();
+        let mut extra = Vec::new();
+        for (index, argument) in arguments.iter().enumerate() {
+            let value = self.lower_expression(argument)?;
+            let ty = match signature.parameters.get(index) {
+                Some(ty) => ty.clone(),
+                None => {
+                    let ty = match self.layout.resolve(&value.ty) {
+                        Type::Float => Type::Double,
+                        ty if is_integer(self.layout, &ty) => promote(self.layout, &ty),
+                        ty => ty,
+                    };
+                    // Cranelift can't set %al for SysV variadic calls, so a callee
+                    // like printf would not look at vector registers
+                    if self.target == Target::Amd64 && is_floating(self.layout, &ty) {
+                        return Err(AleccError::CodegenError {
+                            message: "floating-point variadic arguments are not supported by the cranelift backend on amd64".to_string(), span: None });
+                    }
+                    extra.push(ty.clone());
+                    ty
+                }
+            };
+            values.push(self.convert(value, &ty)?);
+        }
+
+        let clif_signature =
+            clif_signature(self.module, self.layout, self.target, &signature, &extra)?;
+        let call = match callee {
+            None if !signature.variadic && extra.is_empty() => {
+                let id = self.functions[&name];
+                let func_ref = self.module.declare_func_in_func(id, self.builder.func);
+                self.builder.ins().call(func_ref, &values)
+            }
+            callee => {
+                // Variadic and unprototyped callees get a call-site specific signature
+                let callee = match callee {
+                    Some(callee) => callee,
+                    None => {
+                        let id = self.import_function(&name, &signature)?;
+                        let func_ref = self.module.declare_func_in_func(id, self.builder.func);
+                        let pointer_type = self.pointer_type();
+                        self.builder.ins().func_addr(pointer_type, func_ref)
+                    }
+                };
+                let signature_ref = self.builder.import_signature(clif_signature);
+                self.builder
+                    .ins()
+                    .call_indirect(signature_ref, callee, &values)
+            }
+        };
+
+        if matches!(signature.return_type, Type::Void) {
+            return Ok(self.void());
+        }
+        let value = self.builder.inst_results(call)[0];
+        Ok(Operand {
+            value,
+            ty: signature.return_type,
+        })
+    }
+
+    /// GCC's builtins: the bit operations as Cranelift's `popcnt`, `clz`,
+    /// `ctz` and `bswap`, memcpy and memset as its calls to libc, and the rest
+    /// as calls to the library function of the same name
+    fn lower_builtin(&mut self, name: &str, arguments: &[Expression]) -> Result<Operand> {
+        let Some(Type::Function {
+            return_type,
+            parameters,
+            ..
+        }) = signature(name)
+        else {
+            return Err(AleccError::CodegenError {
+                message: format!("{} is not supported", name),
+                span: None,
+            });
+        };
+        if arguments.len() < parameters.len() {
+            return Err(AleccError::CodegenError {
+                message: format!("{} requires {} arguments", name, parameters.len()),
+                span: None,
+            });
+        }
+        let mut values = Vec::new();
+        for (argument, parameter) in arguments.iter().zip(&parameters) {
+            let value = self.lower_expression(argument)?;
+            values.push(self.convert(value, parameter)?);
+        }
+        let return_type = self.layout.resolve(&return_type);
+
+        if let Some(function) = library_function(name) {
+            let signature = CSignature {
+                return_type: return_type.clone(),
+                parameters: parameters
+                    .iter()
+                    .map(|ty| parameter_type(self.layout, ty))
+                    .collect(),
+                variadic: false,
+            };
+            let id = self.import_function(function, &signature)?;
+            let func_ref = self.module.declare_func_in_func(id, self.builder.func);
+            let call = self.builder.ins().call(func_ref, &values);
+            if matches!(return_type, Type::Void) {
+                return Ok(self.void());
+            }
+            let value = self.builder.inst_results(call)[0];
+            return Ok(Operand {
+                value,
+                ty: return_type,
+            });
+        }
+
+        let builtin = name.trim_start_matches("__builtin_");
+        let value = match builtin {
+            // The value is all that matters; the hint is dropped
+            "expect" => {
+                return Ok(Operand {
+                    value: values[0],
+                    ty: return_type,
+                })
+            }
+            "unreachable" | "trap" => {
+                // The rest of the expression lands in a block nothing reaches
+                self.builder.ins().trap(TrapCode::unwrap_user(1));
+                let block = self.builder.create_block();
+                self.builder.switch_to_block(block);
+                return Ok(self.void());
+            }
+            "memcpy" | "memset" => {
+                let config = self.module.target_config();
+                if builtin == "memcpy" {
+                    self.builder
+                        .call_memcpy(config, values[0], values[1], values[2]);
+                } else {
+                    let fill = self.builder.ins().ireduce(types::I8, values[1]);
+                    self.builder.call_memset(config, values[0], fill, values[2]);
+                }
+                return Ok(Operand {
+                    value: values[0],
+                    ty: return_type,
+                });
+            }
+            "popcount" | "popcountl" | "popcountll" => self.builder.ins().popcnt(values[0]),
+            "clz" | "clzl" | "clzll" => self.builder.ins().clz(values[0]),
+            "ctz" | "ctzl" | "ctzll" => self.builder.ins().ctz(values[0]),
+            "bswap16" | "bswap32" | "bswap64" => self.builder.ins().bswap(values[0]),
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("{} is not supported by the cranelift backend", name),
+                    span: None,
+                })
+            }
+        };
+        // The counts are narrowed to int
+        let value = self.convert(
+            Operand {
+                value,
+                ty: self.layout.resolve(&parameters[0]),
+            },
+            &return_type,
+        )?;
+        Ok(Operand {
+            value,
+            ty: return_type,
+        })
+    }
+
+    /// An `__atomic_*` or `__sync_*` builtin as `atomic_load`, `atomic_store`,
+    /// `atomic_rmw`, `atomic_cas` or `fence`. Cranelift's atomics are all
+    /// sequentially consistent, as strong as any memory order asked for
+    fn lower_atomic(
+        &mut self,
+        name: &str,
+        atomic: Atomic,
+        arguments: &[Expression],
+    ) -> Result<Operand> {
+        let error = |message: String| AleccError::CodegenError {
+            message,
+            span: arguments.first().map(|argument| argument.span),
+        };
+        if atomic.operation == AtomicOperation::Fence {
+            // A signal handler runs on the same thread, so only the compiler
+            // mustn't reorder, and it doesn't
+            if name != "__atomic_signal_fence" {
+                self.builder.ins().fence();
+            }
+            return Ok(self.void());
+        }
+
+        // The pointers come first, then the values
+        let zero = Expression::new(ExpressionKind::IntegerLiteral(0), arguments[0].span);
+        let (count, pointers) = match atomic.operation {
+            AtomicOperation::Load => (1, 1),
+            AtomicOperation::CompareExchange => (3, 2),
+            AtomicOperation::CompareAndSwap { .. } => (3, 1),
+            _ => (2, 1),
+        };
+        let mut values: Vec<&Expression> = arguments.iter().take(count).collect();
+        // `__sync_lock_release(p)` stores 0
+        if name == "__sync_lock_release" {
+            values.push(&zero);
+        }
+        if values.len() < count {
+            return Err(error(format!("too few arguments to function '{}'", name)));
+        }
+        let mut addresses = Vec::new();
+        for value in &values[..pointers] {
+            addresses.push(self.lower_expression(value)?);
+        }
+        let operand = match pointee(self.layout, &addresses[0].ty) {
+            Ok(ty)
+                if (is_integer(self.layout, &ty) || matches!(ty, Type::Pointer(_)))
+                    && self.layout.size_of(&ty) <= self.target.pointer_size() =>
+            {
+                ty
+            }
+            _ => {
+                return Err(error(format!(
+                    "the first argument of '{}' must point to an integer or pointer",
+                    name
+                )))
+            }
+        };
+        let mut operands = Vec::new();
+        for value in &values[pointers..] {
+            let value = self.lower_expression(value)?;
+            operands.push(self.convert(value, &operand)?);
+        }
+        let clif_type = scalar_type(self.layout, self.target, &operand)
+            .ok_or_else(|| error(format!("'{}' has no operand type", name)))?;
+        let address = addresses[0].value;
+        let flags = MemFlags::new();
+
+        let value = match atomic.operation {
+            AtomicOperation::Load => self.builder.ins().atomic_load(clif_type, flags, address),
+            AtomicOperation::Store => {
+                self.builder.ins().atomic_store(flags, operands[0], address);
+                return Ok(self.void());
+            }
+            AtomicOperation::Exchange => self.builder.ins().atomic_rmw(
+                clif_type,
+                flags,
+                AtomicRmwOp::Xchg,
+                address,
+                operands[0],
+            ),
+            AtomicOperation::Fetch { op, returns_new } => {
+                let rmw_op = match op {
+                    AtomicOp::Add => AtomicRmwOp::Add,
+                    AtomicOp::Sub => AtomicRmwOp::Sub,
+                    AtomicOp::And => AtomicRmwOp::And,
+                    AtomicOp::Or => AtomicRmwOp::Or,
+                    AtomicOp::Xor => AtomicRmwOp::Xor,
+                    AtomicOp::Nand => AtomicRmwOp::Nand,
+                };
+                let old =
+                    self.builder
+                        .ins()
+                        .atomic_rmw(clif_type, flags, rmw_op, address, operands[0]);
+                if returns_new {
+                    // The operation again, on the value it was applied to
+                    let ins = self.builder.ins();
+                    match op {
+                        AtomicOp::Add => ins.iadd(old, operands[0]),
+                        AtomicOp::Sub => ins.isub(old, operands[0]),
+                        AtomicOp::And => ins.band(old, operands[0]),
+                        AtomicOp::Or => ins.bor(old, operands[0]),
+                        AtomicOp::Xor => ins.bxor(old, operands[0]),
+                        AtomicOp::Nand => {
+                            let and = ins.band(old, operands[0]);
+                            self.builder.ins().bnot(and)
+                        }
+                    }
+                } else {
+                    old
+                }
+            }
+            AtomicOperation::CompareExchange | AtomicOperation::CompareAndSwap { .. } => {
+                let (expected, desired) = match atomic.operation {
+                    AtomicOperation::CompareExchange => (
+                        self.builder
+                            .ins()
+                            .load(clif_type, flags, addresses[1].value, 0),
+                        operands[0],
+                    ),
+                    _ => (operands[0], operands[1]),
+                };
+                let old = self
+                    .builder
+                    .ins()
+                    .atomic_cas(flags, address, expected, desired);
+                let swapped = self.builder.ins().icmp(IntCC::Equal, old, expected);
+                if atomic.operation == AtomicOperation::CompareExchange {
+                    // `*expected` gets the value found, if it wasn't that
+                    let failed = self.builder.create_block();
+                    let end = self.builder.create_block();
+                    self.builder.ins().brif(swapped, end, &[], failed, &[]);
+                    self.terminated = true;
+                    self.start_block(failed);
+                    self.builder.ins().store(flags, old, addresses[1].value, 0);
+                    self.start_block(end);
+                }
+                if atomic.operation
+                    == (AtomicOperation::CompareAndSwap {
+                        returns_bool: false,
+                    })
+                {
+                    old
+                } else {
+                    return Ok(Operand {
+                        value: swapped,
+                        ty: Type::Bool,
+                    });
+                }
+            }
+            AtomicOperation::Fence => unreachable!("fences return early"),
+        };
+        Ok(Operand { value, ty: operand })
+    }
+
+    fn import_function(&mut self, name: &str, signature: &CSignature) -> Result<FuncId> {
+        if let Some(&id) = self.functions.get(name) {
+            return Ok(id);
+        }
+        let clif_signature = clif_signature(self.module, self.layout, self.target, signature, &[])?;
+        let id = self
+            .module
+            .declare_function(name, Linkage::Import, &clif_signature)
+            .map_err(cranelift_error)?;
+        self.functions.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    fn lower_assignment(
+        &mut self,
+        target: &Expression,
+        operator: &AssignmentOperator,
+        value: &Expression,
+    ) -> Result<Operand> {
+        let (address, ty) = self.lower_address(target)?;
+
+        let operator = operator.binary_operator();
+
+        let rhs = self.lower_expression(value)?;
+        let result = match operator {
+            None => rhs,
+            Some(operator) => {
+                let current = self.load(address, &ty)?;
+                self.binary_values(&operator, current, rhs)?
+            }
+        };
+        self.store(result, address, &ty)
+    }
+
+    fn lower_conditional(
+        &mut self,
+        condition: &Expression,
+        then_expr: &Expression,
+        else_expr: &Expression,
+    ) -> Result<Operand> {
+        // Blocks must be filled before switching away, so the result type
+        // (and each arm's conversion to it) has to be known up front
+        let then_type = self.type_of(then_expr)?;
+        let else_type = self.type_of(else_expr)?;
+        let ty = if is_arithmetic(self.layout, &then_type) && is_arithmetic(self.layout, &else_type)
+        {
+            common_type(self.layout, &then_type, &else_type)
+        } else if is_pointer(&then_type) {
+            then_type
+        } else {
+            else_type
+        };
+        let result_type = scalar_type(self.layout, self.target, &ty);
+
+        let then_block = self.builder.create_block();
+        let else_block = self.builder.create_block();
+        let end_block = self.builder.create_block();
+        if let Some(clif_type) = result_type {
+            self.builder.append_block_param(end_block, clif_type);
+        }
+
+        let cond = self.lower_condition(condition)?;
+        self.builder
+            .ins()
+            .brif(cond, then_block, &[], else_block, &[]);
+        self.terminated = true;
+
+        for (block, expr) in [(then_block, then_expr), (else_block, else_expr)] {
+            self.start_block(block);
+            let value = self.lower_expression(expr)?;
+            let args = match result_type {
+                Some(_) => vec![self.convert(value, &ty)?],
+                None => Vec::new(),
+            };
+            self.builder.ins().jump(end_block, &args);
+            self.terminated = true;
+        }
+
+        self.start_block(end_block);
+        match result_type {
+            Some(_) => Ok(Operand {
+                value: self.builder.block_params(end_block)[0],
+                ty,
+            }),
+            None => Ok(self.void()),
+        }
+    }
+
+    /// Static type of an expression, without emitting any code
+    fn type_of(&self, expression: &Expression) -> Result<Type> {
+        let ty = match &expression.kind {
+            ExpressionKind::IntegerLiteral(value) => {
+                if i32::try_from(*value).is_ok() {
+                    Type::Int
+                } else {
+                    Type::Long
+                }
+            }
+            ExpressionKind::FloatLiteral(_) => Type::Double,
+            ExpressionKind::CharLiteral(_) | ExpressionKind::BooleanLiteral(_) => Type::Int,
+            ExpressionKind::StringLiteral(_) => Type::Pointer(Box::new(Type::Char)),
+            ExpressionKind::Identifier(name) => match self.lookup_type(name) {
+                Some(ty) => decay(self.layout, &ty),
+                None => match self.signatures.get(name) {
+                    Some(signature) => function_pointer(signature),
+                    None => {
+                        return Err(AleccError::CodegenError {
+                            message: format!("use of undeclared identifier '{}'", name),
+                            span: None,
+                        })
+                    }
+                },
+            },
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let (l, r) = (self.type_of(left)?, self.type_of(right)?);
+                match operator {
+                    _ if comparison_codes(operator).is_some() => Type::Int,
+                    BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => Type::Int,
+                    BinaryOperator::Subtract if is_pointer(&l) && is_pointer(&r) => Type::Long,
+                    BinaryOperator::Add | BinaryOperator::Subtract if is_pointer(&l) => l,
+                    BinaryOperator::Add if is_pointer(&r) => r,
+                    BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                        promote(self.layout, &l)
+                    }
+                    _ => common_type(self.layout, &l, &r),
+                }
+            }
+            ExpressionKind::Unary { operator, operand } => {
+                let ty = self.type_of(operand)?;
+                match operator {
+                    UnaryOperator::LogicalNot => Type::Int,
+                    UnaryOperator::Plus | UnaryOperator::Minus | UnaryOperator::BitwiseNot => {
+                        promote(self.layout, &ty)
+                    }
+                    UnaryOperator::AddressOf => match &operand.kind {
+                        ExpressionKind::Identifier(name) if self.lookup_type(name).is_none() => ty,
+                        _ => Type::Pointer(Box::new(self.object_type(operand)?)),
+                    },
+                    UnaryOperator::Dereference => {
+                        let pointee = pointee(self.layout, &ty)?;
+                        if matches!(pointee, Type::Function { .. }) {
+                            ty
+                        } else {
+                            decay(self.layout, &pointee)
+                        }
+                    }
+                    _ => self.object_type(operand)?,
+                }
+            }
+            ExpressionKind::Call { function, .. } => {
+                let signature = match &function.kind {
+                    ExpressionKind::Identifier(name) if self.lookup_type(name).is_none() => {
+                        self.signatures.get(name).map(|s| s.return_type.clone())
+                    }
+                    _ => match pointee(self.layout, &self.type_of(function)?) {
+                        Ok(Type::Function { return_type, .. }) => Some(*return_type),
+                        _ => None,
+                    },
+                };
+                signature.unwrap_or(Type::Int)
+            }
+            ExpressionKind::Member { .. } | ExpressionKind::Index { .. } => {
+                decay(self.layout, &self.object_type(expression)?)
+            }
+            ExpressionKind::Cast { target_type, .. } => target_type.clone(),
+            ExpressionKind::Sizeof(_) => Type::Long,
+            ExpressionKind::Assignment { target, .. } => self.object_type(target)?,
+            ExpressionKind::Conditional {
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                let (t, e) = (self.type_of(then_expr)?, self.type_of(else_expr)?);
+                if is_arithmetic(self.layout, &t) && is_arithmetic(self.layout, &e) {
+                    common_type(self.layout, &t, &e)
+                } else if is_pointer(&t) {
+                    t
+                } else {
+                    e
+                }
+            }
+            ExpressionKind::VaArg { arg_type, .. } => arg_type.clone(),
+            ExpressionKind::LabelAddress(_) => Type::Pointer(Box::new(Type::Void)),
+        };
+        Ok(self.layout.resolve(&ty))
+    }
+
+    /// Type of the object an lvalue designates (before array decay)
+    fn object_type(&self, expression: &Expression) -> Result<Type> {
+        match &expression.kind {
+            ExpressionKind::Identifier(name) => {
+                self.lookup_type(name)
+                    .ok_or_else(|| AleccError::CodegenError {
+                        message: format!("use of undeclared identifier '{}'", name),
+                        span: None,
+                    })
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => pointee(self.layout, &self.type_of(operand)?),
+            ExpressionKind::Index { array, index } => {
+                let base = self.type_of(array)?;
+                let base = if is_integer(self.layout, &base) {
+                    self.type_of(index)?
+                } else {
+                    base
+                };
+                pointee(self.layout, &base)
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let aggregate = if *is_arrow {
+                    pointee(self.layout, &self.type_of(object)?)?
+                } else if object.is_lvalue() {
+                    self.object_type(object)?
+                } else {
+                    self.type_of(object)?
+                };
+                self.layout
+                    .member(&aggregate, member)
+                    .map(|(_, ty)| ty)
+                    .ok_or_else(|| AleccError::CodegenError {
+                        message: format!("no member named '{}'", member),
+                        span: None,
+                    })
+            }
+            _ => Err(AleccError::CodegenError {
+                message: "expression is not assignable".to_string(),
+                span: None,
+            }),
+        }
+    }
+
+    /// Evaluate an expression as a branch condition (an `i8` that is 0 or 1)
+    fn lower_condition(&mut self, expression: &Expression) -> Result<Value> {
+        let value = self.lower_expression(expression)?;
+        self.truth_value(value)
+    }
+
+    fn truth_value(&mut self, operand: Operand) -> Result<Value> {
+        let ty = self.layout.resolve(&operand.ty);
+        if is_floating(self.layout, &ty) {
+            let zero = match scalar_type(self.layout, self.target, &ty) {
+                Some(types::F32) => self.builder.ins().f32const(0.0),
+                _ => self.builder.ins().f64const(0.0),
+            };
+            Ok(self
+                .builder
+                .ins()
+                .fcmp(FloatCC::NotEqual, operand.value, zero))
+        } else if is_integer(self.layout, &ty) || is_pointer(&ty) {
+            Ok(self
+                .builder
+                .ins()
+                .icmp_imm(IntCC::NotEqual, operand.value, 0))
+        } else {
+            Err(AleccError::CodegenError {
+                message: "scalar value required in condition".to_string(),
+                span: None,
+            })
+        }
+    }
+
+    fn bool_to_int(&mut self, flag: Value) -> Operand {
+        Operand {
+            value: self.builder.ins().uextend(types::I32, flag),
+            ty: Type::Int,
+        }
+    }
+
+    /// Load an object of type `ty`; arrays decay to a pointer to their first
+    /// element and aggregates stay addresses
+    fn load(&mut self, address: Value, ty: &Type) -> Result<Operand> {
+        let ty = self.layout.resolve(ty);
+        match &ty {
+            Type::Array(element, _) => Ok(Operand {
+                value: address,
+                ty: Type::Pointer(element.clone()),
+            }),
+            Type::Function { .. } => Ok(Operand {
+                value: address,
+                ty: Type::Pointer(Box::new(ty)),
+            }),
+            Type::Struct { .. } | Type::Union { .. } => Ok(Operand { value: address, ty }),
+            _ => {
+                let clif_type = scalar_type(self.layout, self.target, &ty).ok_or_else(|| {
+                    AleccError::CodegenError {
+                        message: "cannot load a value of incomplete type".to_string(),
+                        span: None,
+                    }
+                })?;
+                let value = self
+                    .builder
+                    .ins()
+                    .load(clif_type, MemFlags::new(), address, 0);
+                Ok(Operand { value, ty })
+            }
+        }
+    }
+
+    /// Store `value` into an object of type `ty`, returning the stored value
+    fn store(&mut self, value: Operand, address: Value, ty: &Type) -> Result<Operand> {
+        let ty = self.layout.resolve(ty);
+        if matches!(ty, Type::Struct { .. } | Type::Union { .. }) {
+            let size = self.layout.size_of(&ty) as u64;
+            let align = self.layout.align_of(&ty).min(u8::MAX as usize) as u8;
+            let config = self.module.target_config();
+            self.builder.emit_small_memory_copy(
+                config,
+                address,
+                value.value,
+                size,
+                align,
+                align,
+                true,
+                MemFlags::new(),
+            );
+            return Ok(Operand { value: address, ty });
+        }
+
+        let value = self.convert(value, &ty)?;
+        self.builder.ins().store(MemFlags::new(), value, address, 0);
+        Ok(Operand { value, ty })
+    }
+
+    fn element_address(
+        &mut self,
+        base: Value,
+        element: &Type,
+        index: Operand,
+        negate: bool,
+    ) -> Result<Value> {
+        if !is_integer(self.layout, &index.ty) {
+            return Err(AleccError::CodegenError {
+                message: "array subscript is not an integer".to_string(),
+                span: None,
+            });
+        }
+        // Arithmetic on void * steps in bytes, as GCC does
+        let size = match self.layout.resolve(element) {
+            Type::Void => 1,
+            ty => self.layout.size_of(&ty) as i64,
+        };
+        let index = self.convert(index, &Type::Long)?;
+        let offset = self.builder.ins().imul_imm(index, size);
+        Ok(if negate {
+            self.builder.ins().isub(base, offset)
+        } else {
+            self.builder.ins().iadd(base, offset)
+        })
+    }
+
+    /// Convert a value to type `to`
+    fn convert(&mut self, operand: Operand, to: &Type) -> Result<Value> {
+        let from = self.layout.resolve(&operand.ty);
+        let to = self.layout.resolve(to);
+
+        if matches!(to, Type::Bool) && !matches!(from, Type::Bool) {
+            return self.truth_value(operand);
+        }
+
+        let from_type = scalar_type(self.layout, self.target, &from);
+        let to_type = scalar_type(self.layout, self.target, &to);
+        let (from_type, to_type) = match (from_type, to_type) {
+            (Some(from_type), Some(to_type)) => (from_type, to_type),
+            (None, None) if matches!(from, Type::Struct { .. } | Type::Union { .. }) => {
+                return Ok(operand.value);
+            }
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("cannot convert {:?} to {:?}", from, to),
+                    span: None,
+                });
+            }
+        };
+        if from_type == to_type {
+            return Ok(operand.value);
+        }
+
+        let value = operand.value;
+        let ins = self.builder.ins();
+        Ok(match (from_type.is_int(), to_type.is_int()) {
+            (true, true) if from_type.bits() < to_type.bits() => {
+                // _Bool only ever holds 0 or 1
+                if matches!(from, Type::Bool) || from.is_unsigned() {
+                    ins.uextend(to_type, value)
+                } else {
+                    ins.sextend(to_type, value)
+                }
+            }
+            (true, true) => ins.ireduce(to_type, value),
+            (true, false) if from.is_unsigned() => ins.fcvt_from_uint(to_type, value),
+            (true, false) => ins.fcvt_from_sint(to_type, value),
+            (false, true) if to.is_unsigned() => ins.fcvt_to_uint_sat(to_type, value),
+            (false, true) => ins.fcvt_to_sint_sat(to_type, value),
+            (false, false) if from_type.bits() < to_type.bits() => ins.fpromote(to_type, value),
+            (false, false) => ins.fdemote(to_type, value),
+        })
+    }
+
+    fn integer(&mut self, value: i64, ty: Type) -> Operand {
+        let clif_type = scalar_type(self.layout, self.target, &ty).unwrap_or(types::I32);
+        let bits = clif_type.bits() as usize;
+        let value = self
+            .builder
+            .ins()
+            .iconst(clif_type, (value as u64 as u128 & mask(bits)) as i64);
+        Operand { value, ty }
+    }
+
+    fn zero(&mut self, clif_type: types::Type) -> Value {
+        match clif_type {
+            types::F32 => self.builder.ins().f32const(0.0),
+            types::F64 => self.builder.ins().f64const(0.0),
+            _ => self.builder.ins().iconst(clif_type, 0),
+        }
+    }
+
+    fn void(&mut self) -> Operand {
+        Operand {
+            value: self.builder.ins().iconst(types::I32, 0),
+            ty: Type::Void,
+        }
+    }
+
+    fn pointer_type(&self) -> types::Type {
+        self.module.target_config().pointer_type()
+    }
+
+    fn data_address(&mut self, id: DataId) -> Value {
+        let global = self.module.declare_data_in_func(id, self.builder.func);
+        let pointer_type = self.pointer_type();
+        self.builder.ins().symbol_value(pointer_type, global)
+    }
+
+    fn function_address(&mut self, name: &str) -> Result<Operand> {
+        let signature = self.signatures[name].clone();
+        let id = self.functions[name];
+        let func_ref = self.module.declare_func_in_func(id, self.builder.func);
+        let pointer_type = self.pointer_type();
+        Ok(Operand {
+            value: self.builder.ins().func_addr(pointer_type, func_ref),
+            ty: function_pointer(&signature),
+        })
+    }
+
+    fn declare_local(&mut self, name: &str, ty: &Type, alignment: Option<usize>) -> Value {
+        let size = self.layout.size_of(ty).max(1) as u32;
+        let align = alignment.unwrap_or_else(|| self.layout.align_of(ty)).max(1);
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            size,
+            align.trailing_zeros() as u8,
+        ));
+        let pointer_type = self.pointer_type();
+        let address = self.builder.ins().stack_addr(pointer_type, slot, 0);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), (address, ty.clone()));
+        }
+        address
+    }
+
+    fn lookup(&self, name: &str) -> Option<(Value, Type)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|(address, ty)| (*address, self.layout.resolve(ty)))
+    }
+
+    fn lookup_type(&self, name: &str) -> Option<Type> {
+        self.lookup(name)
+            .map(|(_, ty)| ty)
+            .or_else(|| self.globals.get(name).map(|(_, ty)| ty.clone()))
+    }
+
+    fn label_block(&mut self, label: &str) -> Block {
+        if let Some(&block) = self.labels.get(label) {
+            return block;
+        }
+        let block = self.builder.create_block();
+        self.labels.insert(label.to_string(), block);
+        block
+    }
+
+    fn jump(&mut self, block: Block) {
+        if !self.terminated {
+            self.builder.ins().jump(block, &[]);
+            self.terminated = true;
+        }
+    }
+
+    /// Continue in `block`, falling through from the current block if it is still open.
+    /// Code after return/break/goto lands in a fresh unreachable block.
+    fn start_block(&mut self, block: Block) {
+        self.jump(block);
+        self.builder.switch_to_block(block);
+        self.terminated = false;
+    }
+}
+
+/// Local NUL-terminated data for a string literal, shared between uses
+fn string_data(
+    module: &mut ObjectModule,
+    strings: &mut HashMap<String, DataId>,
+    label_prefix: &str,
+    content: &str,
+) -> Result<DataId> {
+    if let Some(&id) = strings.get(content) {
+        return Ok(id);
+    }
+    let name = format!(".L{}C{}", label_prefix, strings.len());
+    let id = module
+        .declare_data(&name, Linkage::Local, false, false)
+        .map_err(cranelift_error)?;
+    let mut data = DataDescription::new();
+    let mut bytes = content.as_bytes().to_vec();
+    bytes.push(0);
+    data.define(bytes.into_boxed_slice());
+    module.define_data(id, &data).map_err(cranelift_error)?;
+    strings.insert(content.to_string(), id);
+    Ok(id)
+}
+
+fn is_declaration(function: &Function) -> bool {
+    // Same convention as the assembly backend: an empty body is a prototype
+    matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+}
+
+fn clif_signature(
+    module: &ObjectModule,
+    layout: &TypeLayout,
+    target: Target,
+    signature: &CSignature,
+    extra: &[Type],
+) -> Result<Signature> {
+    let mut clif = module.make_signature();
+    for ty in signature.parameters.iter().chain(extra) {
+        let clif_type =
+            scalar_type(layout, target, ty).ok_or_else(|| AleccError::CodegenError {
+                message: "the cranelift backend cannot pass aggregates by value".to_string(),
+                span: None,
+            })?;
+        clif.params.push(AbiParam::new(clif_type));
+    }
+    if !matches!(layout.resolve(&signature.return_type), Type::Void) {
+        let clif_type = scalar_type(layout, target, &signature.return_type).ok_or_else(|| {
+            AleccError::CodegenError {
+                message: "the cranelift backend cannot return aggregates by value".to_string(),
+                span: None,
+            }
+        })?;
+        clif.returns.push(AbiParam::new(clif_type));
+    }
+    Ok(clif)
+}
+
+/// The Cranelift type holding a scalar C type; `None` for void and aggregates
+fn scalar_type(layout: &TypeLayout, target: Target, ty: &Type) -> Option<types::Type> {
+    let pointer = if target.pointer_size() == 8 {
+        types::I64
+    } else {
+        types::I32
+    };
+    match layout.resolve(ty) {
+        Type::Char | Type::UnsignedChar | Type::Bool => Some(types::I8),
+        Type::Short | Type::UnsignedShort => Some(types::I16),
+        Type::Int | Type::UnsignedInt => Some(types::I32),
+        Type::Long | Type::UnsignedLong => Some(pointer),
+        Type::LongLong | Type::UnsignedLongLong => Some(types::I64),
+        Type::Float => Some(types::F32),
+        Type::Double => Some(types::F64),
+        Type::Pointer(_) | Type::Array(..) | Type::Function { .. } | Type::VaList => Some(pointer),
+        _ => None,
+    }
+}
+
+/// Parameters of array type are adjusted to pointers
+fn parameter_type(layout: &TypeLayout, ty: &Type) -> Type {
+    match layout.resolve(ty) {
+        Type::Array(element, _) => Type::Pointer(element),
+        resolved => resolved,
+    }
+}
+
+fn function_pointer(signature: &CSignature) -> Type {
+    Type::Pointer(Box::new(Type::Function {
+        return_type: Box::new(signature.return_type.clone()),
+        parameters: signature.parameters.clone(),
+        variadic: signature.variadic,
+    }))
+}
+
+fn decay(layout: &TypeLayout, ty: &Type) -> Type {
+    match layout.resolve(ty) {
+        Type::Array(element, _) => Type::Pointer(element),
+        ty @ Type::Function { .. } => Type::Pointer(Box::new(ty)),
+        ty => ty,
+    }
+}
+
+fn pointee(layout: &TypeLayout, ty: &Type) -> Result<Type> {
+    match layout.resolve(ty) {
+        Type::Pointer(inner) | Type::Array(inner, _) => Ok(layout.resolve(&inner)),
+        _ => Err(AleccError::CodegenError {
+            message: "indirection requires pointer operand".to_string(),
+            span: None,
+        }),
+    }
+}
+
+fn is_integer(layout: &TypeLayout, ty: &Type) -> bool {
+    matches!(
+        layout.resolve(ty),
+        Type::Char
+            | Type::Short
+            | Type::Int
+            | Type::Long
+            | Type::LongLong
+            | Type::UnsignedChar
+            | Type::UnsignedShort
+            | Type::UnsignedInt
+            | Type::UnsignedLong
+            | Type::UnsignedLongLong
+            | Type::Bool
+    )
+}
+
+fn is_floating(layout: &TypeLayout, ty: &Type) -> bool {
+    matches!(layout.resolve(ty), Type::Float | Type::Double)
+}
+
+fn is_arithmetic(layout: &TypeLayout, ty: &Type) -> bool {
+    is_integer(layout, ty) || is_floating(layout, ty)
+}
+
+fn is_pointer(ty: &Type) -> bool {
+    matches!(ty, Type::Pointer(_) | Type::Array(..))
+}
+
+/// Integer promotion: anything narrower than int becomes int
+fn promote(layout: &TypeLayout, ty: &Type) -> Type {
+    let ty = layout.resolve(ty);
+    if is_integer(layout, &ty) && layout.size_of(&ty) < 4 {
+        Type::Int
+    } else {
+        ty
+    }
+}
+
+/// The usual arithmetic conversions
+fn common_type(layout: &TypeLayout, a: &Type, b: &Type) -> Type {
+    let (a, b) = (promote(layout, a), promote(layout, b));
+    if matches!(a, Type::Double) || matches!(b, Type::Double) {
+        Type::Double
+    } else if matches!(a, Type::Float) || matches!(b, Type::Float) {
+        Type::Float
+    } else if layout.size_of(&a) > layout.size_of(&b) {
+        a
+    } else if layout.size_of(&a) < layout.size_of(&b) || b.is_unsigned() {
+        b
+    } else {
+        a
+    }
+}
+
+fn comparison_codes(operator: &BinaryOperator) -> Option<(IntCC, FloatCC)> {
+    match operator {
+        BinaryOperator::Equal => Some((IntCC::Equal, FloatCC::Equal)),
+        BinaryOperator::NotEqual => Some((IntCC::NotEqual, FloatCC::NotEqual)),
+        BinaryOperator::Less => Some((IntCC::SignedLessThan, FloatCC::LessThan)),
+        BinaryOperator::Greater => Some((IntCC::SignedGreaterThan, FloatCC::GreaterThan)),
+        BinaryOperator::LessEqual => Some((IntCC::SignedLessThanOrEqual, FloatCC::LessThanOrEqual)),
+        BinaryOperator::GreaterEqual => {
+            Some((IntCC::SignedGreaterThanOrEqual, FloatCC::GreaterThanOrEqual))
+        }
+        _ => None,
+    }
+}
+
+fn unsigned_cc(cc: IntCC) -> IntCC {
+    match cc {
+        IntCC::SignedLessThan => IntCC::UnsignedLessThan,
+        IntCC::SignedGreaterThan => IntCC::UnsignedGreaterThan,
+        IntCC::SignedLessThanOrEqual => IntCC::UnsignedLessThanOrEqual,
+        IntCC::SignedGreaterThanOrEqual => IntCC::UnsignedGreaterThanOrEqual,
+        cc => cc,
+    }
+}
+
+fn mask(bits: usize) -> u128 {
+    if bits >= 64 {
+        u64::MAX as u128
+    } else {
+        (1u128 << bits) - 1
+    }
+}
+
+fn integer_constant(layout: &TypeLayout, expression: &Expression) -> Option<i64> {
+    ConstantEvaluator::new()
+        .with_layout(layout)
+        .evaluate(expression)
+        .ok()
+}
+
+/// Little-endian bytes of a constant scalar initializer
+fn constant_bytes(layout: &TypeLayout, ty: &Type, initializer: &Expression) -> Option<Vec<u8>> {
+    let size = layout.size_of(ty);
+    let float = match &initializer.kind {
+        ExpressionKind::FloatLiteral(value) => Some(*value),
+        ExpressionKind::Unary {
+            operator: UnaryOperator::Minus,
+            operand,
+        } => match &operand.kind {
+            ExpressionKind::FloatLiteral(value) => Some(-value),
+            _ => None,
+        },
+        _ => None,
+    };
+    let integer = integer_constant(layout, initializer);
+
+    match layout.resolve(ty) {
+        Type::Float => Some(
+            ((float.or(integer.map(|v| v as f64))?) as f32)
+                .to_le_bytes()
+                .to_vec(),
+        ),
+        Type::Double => Some(
+            (float.or(integer.map(|v| v as f64))?)
+                .to_le_bytes()
+                .to_vec(),
+        ),
+        ty if is_integer(layout, &ty) || (is_pointer(&ty) && integer == Some(0)) => {
+            Some(integer?.to_le_bytes()[..size].to_vec())
+        }
+        _ => None,
+    }
+}
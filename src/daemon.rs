@@ -0,0 +1,455 @@
+use crate::cache::CompileCache;
+use crate::cli::Args;
+use crate::compiler::{CompileOutput, Compiler};
+use crate::diagnostics::Diagnostic;
+use crate::error::{AleccError, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// One compile job: the command line after `alecc`, run in `cwd`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub args: Vec<String>,
+    /// Directory relative paths in `args` are resolved against; the daemon's own if absent
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    /// A preprocessed unit to compile to an object, sent back in the response;
+    /// `args` then holds only flags (this is what `--remote` sends)
+    #[serde(default)]
+    pub source: Option<Source>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Source {
+    /// File name of the original input, which internal labels are derived from
+    pub name: String,
+    pub text: String,
+}
+
+/// The outcome of a job. Artifact paths are absolute.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Response {
+    pub success: bool,
+    pub error: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub objects: Vec<PathBuf>,
+    pub assembly: Vec<PathBuf>,
+    pub preprocessed: Vec<PathBuf>,
+    pub llvm_ir: Vec<PathBuf>,
+    pub ir: Vec<PathBuf>,
+    pub ast: Vec<PathBuf>,
+    pub callgraph: Option<PathBuf>,
+    pub executable: Option<PathBuf>,
+    /// The object compiled from a request's `source`, in hex
+    pub object: Option<String>,
+}
+
+impl Response {
+    fn failed(error: impl ToString, diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            diagnostics,
+            ..Self::default()
+        }
+    }
+
+    fn succeeded(output: CompileOutput, cwd: &Path) -> Self {
+        let absolute = |paths: Vec<PathBuf>| paths.into_iter().map(|p| cwd.join(p)).collect();
+        Self {
+            success: true,
+            error: None,
+            diagnostics: output.diagnostics,
+            objects: absolute(output.objects),
+            assembly: absolute(output.assembly),
+            preprocessed: absolute(output.preprocessed),
+            llvm_ir: absolute(output.llvm_ir),
+            ir: absolute(output.ir),
+            ast: absolute(output.ast),
+            callgraph: output.callgraph.map(|p| cwd.join(p)),
+            executable: output.executable.map(|p| cwd.join(p)),
+            object: None,
+        }
+    }
+
+    /// The bytes of `object`
+    pub fn object_bytes(&self) -> Result<Vec<u8>> {
+        self.object
+            .as_deref()
+            .and_then(from_hex)
+            .ok_or_else(|| AleccError::CodegenError {
+                message: "the daemon sent back no object file".to_string(),
+                span: None,
+            })
+    }
+}
+
+/// Where a daemon listens: `HOST:PORT` for TCP, or `:PORT` for the loopback
+/// interface; anything else is a Unix socket path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl Endpoint {
+    pub fn parse(address: &str) -> Self {
+        match address.rsplit_once(':') {
+            Some((host, port))
+                if !address.contains('/')
+                    && !port.is_empty()
+                    && port.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                if host.is_empty() {
+                    Endpoint::Tcp(format!("127.0.0.1:{}", port))
+                } else {
+                    Endpoint::Tcp(address.to_string())
+                }
+            }
+            _ => Endpoint::Unix(PathBuf::from(address)),
+        }
+    }
+}
+
+/// The flags a job over TCP may carry, those `--remote` sends: they only
+/// shape code generation. Anyone who can connect may send a job, so the
+/// files it reads and writes, and the tools it runs, stay the daemon's
+const REMOTE_SWITCHES: &[&str] = &[
+    "-g",
+    "--pic",
+    "--no-pie",
+    "--no-ident",
+    "--nostartfiles",
+    "--fno-use-crt",
+    "--mno-red-zone",
+    "--fomit-frame-pointer",
+    "--fno-omit-frame-pointer",
+    "--fstack-protector",
+    "--fstack-protector-strong",
+    "--fstack-protector-all",
+    "--fno-stack-protector",
+    "--msse2",
+    "--ffunction-sections",
+    "--fdata-sections",
+    "--analyze",
+];
+
+/// The [`REMOTE_SWITCHES`] that take a value, given after `=` or, for `-O`
+/// and `-W`, right after the flag
+const REMOTE_OPTIONS: &[&str] = &[
+    "--target=",
+    "-O",
+    "--std=",
+    "--backend=",
+    "--as-dialect=",
+    "--fmissing-return=",
+    "--symbol-prefix=",
+    "--wrap=",
+    "-W",
+];
+
+/// Why a job that came over TCP can't run, if it can't: it must carry its
+/// source, and its flags must all be ones `--remote` sends. Their values
+/// are names, never paths or lists, which also keeps `-Wl,` out and
+/// anything but a symbol out of the assembly
+fn remote_job_refusal(request: &Request) -> Option<String> {
+    if request.source.is_none() {
+        return Some("a job over TCP must carry its source".to_string());
+    }
+    if request.cwd.is_some() {
+        return Some("a job over TCP can't name a working directory".to_string());
+    }
+    let is_name = |value: &str| {
+        !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '-' | '='))
+    };
+    request
+        .args
+        .iter()
+        .find(|arg| {
+            !REMOTE_SWITCHES.contains(&arg.as_str())
+                && !REMOTE_OPTIONS
+                    .iter()
+                    .any(|option| arg.strip_prefix(option).is_some_and(is_name))
+        })
+        .map(|arg| format!("'{}' is not accepted in a job over TCP", arg))
+}
+
+/// State shared by every connection: the caches, and the lock that runs one
+/// job at a time (jobs change the process's working directory).
+#[derive(Clone)]
+struct Daemon {
+    cache: CompileCache,
+    jobs: Arc<Mutex<()>>,
+    home: PathBuf,
+}
+
+/// Accept connections on `address` (see [`Endpoint`]) until the process is
+/// killed. Each line a client sends is a JSON [`Request`]; each gets one
+/// line of JSON [`Response`].
+pub async fn serve(address: &Path) -> Result<()> {
+    let daemon = Daemon {
+        cache: CompileCache::new(),
+        jobs: Arc::new(Mutex::new(())),
+        home: std::env::current_dir()?,
+    };
+
+    match Endpoint::parse(&address.to_string_lossy()) {
+        Endpoint::Unix(socket) => {
+            // A socket left behind by an earlier daemon would make bind fail.
+            // Anything else at the path, or a daemon still answering there, stays
+            if let Ok(metadata) = tokio::fs::symlink_metadata(&socket).await {
+                let refuse = |kind, reason: &str| {
+                    Err(AleccError::IoError(std::io::Error::new(
+                        kind,
+                        format!("cannot serve on {}: {}", socket.display(), reason),
+                    )))
+                };
+                if !metadata.file_type().is_socket() {
+                    return refuse(std::io::ErrorKind::AlreadyExists, "it is not a socket");
+                }
+                if UnixStream::connect(&socket).await.is_ok() {
+                    return refuse(
+                        std::io::ErrorKind::AddrInUse,
+                        "another daemon is serving on it",
+                    );
+                }
+                tokio::fs::remove_file(&socket).await?;
+            }
+            let listener = UnixListener::bind(&socket)?;
+            info!("Serving compile jobs on {}", socket.display());
+            loop {
+                let (stream, _) = listener.accept().await?;
+                daemon.spawn(stream, false);
+            }
+        }
+        Endpoint::Tcp(address) => {
+            let listener = TcpListener::bind(&address).await?;
+            let local = listener.local_addr()?;
+            info!("Serving compile jobs on {}", local);
+            if !local.ip().is_loopback() {
+                warn!(
+                    "{} is reachable from other hosts, and the daemon takes jobs from anyone who connects",
+                    local
+                );
+            }
+            loop {
+                let (stream, peer) = listener.accept().await?;
+                debug!("Connection from {}", peer);
+                daemon.spawn(stream, true);
+            }
+        }
+    }
+}
+
+/// Send `request` to the daemon at `address` and wait for its response
+pub async fn compile_remote(address: &str, request: &Request) -> Result<Response> {
+    let unreachable = |e: std::io::Error| {
+        AleccError::IoError(std::io::Error::new(
+            e.kind(),
+            format!("cannot reach the alecc daemon at {}: {}", address, e),
+        ))
+    };
+    match Endpoint::parse(address) {
+        Endpoint::Unix(socket) => {
+            let stream = UnixStream::connect(socket).await.map_err(unreachable)?;
+            exchange(stream, request).await
+        }
+        Endpoint::Tcp(address) => {
+            let stream = TcpStream::connect(address).await.map_err(unreachable)?;
+            exchange(stream, request).await
+        }
+    }
+}
+
+async fn exchange<S>(stream: S, request: &Request) -> Result<Response>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut line = serde_json::to_string(request).map_err(|e| AleccError::IoError(e.into()))?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let mut reply = String::new();
+    if BufReader::new(reader).read_line(&mut reply).await? == 0 {
+        return Err(AleccError::IoError(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "the alecc daemon closed the connection without answering",
+        )));
+    }
+    serde_json::from_str(&reply).map_err(|e| AleccError::IoError(e.into()))
+}
+
+impl Daemon {
+    /// Serve a connection; `remote` ones come over TCP, so from anyone
+    fn spawn<S>(&self, stream: S, remote: bool)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let daemon = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = daemon.connection(stream, remote).await {
+                warn!("Daemon connection closed: {}", e);
+            }
+        });
+    }
+
+    async fn connection<S>(&self, stream: S, remote: bool) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) if remote => match remote_job_refusal(&request) {
+                    Some(refusal) => Response::failed(refusal, Vec::new()),
+                    None => self.run(request).await,
+                },
+                Ok(request) => self.run(request).await,
+                Err(e) => Response::failed(format!("malformed request: {}", e), Vec::new()),
+            };
+            let mut text =
+                serde_json::to_string(&response).map_err(|e| AleccError::IoError(e.into()))?;
+            text.push('\n');
+            writer.write_all(text.as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    async fn run(&self, request: Request) -> Response {
+        let args = match parse_args(&request.args) {
+            Ok(args) => args,
+            Err(e) => return Response::failed(e, Vec::new()),
+        };
+        if args.daemon.is_some() || args.remote.is_some() {
+            return Response::failed(
+                "--daemon and --remote cannot be used in a compile job",
+                Vec::new(),
+            );
+        }
+
+        match request.source {
+            Some(source) => self.run_source(request.args, &args, source).await,
+            None => {
+                let cwd = match request.cwd {
+                    Some(cwd) => self.home.join(cwd),
+                    None => self.home.clone(),
+                };
+                self.compile(args, Some(&cwd)).await
+            }
+        }
+    }
+
+    /// Compile a unit sent inline to an object in a scratch directory, and send the object back
+    async fn run_source(&self, flags: Vec<String>, args: &Args, source: Source) -> Response {
+        if !args.input_files.is_empty() || args.output.is_some() {
+            return Response::failed(
+                "a job that carries its source cannot name input or output files",
+                Vec::new(),
+            );
+        }
+        let Some(name) = Path::new(&source.name).file_name() else {
+            return Response::failed(format!("invalid source name '{}'", source.name), Vec::new());
+        };
+        let workspace = match tempfile::tempdir() {
+            Ok(workspace) => workspace,
+            Err(e) => return Response::failed(e, Vec::new()),
+        };
+        let input = workspace.path().join(name);
+        let object = workspace.path().join("remote.o");
+        if let Err(e) = tokio::fs::write(&input, &source.text).await {
+            return Response::failed(e, Vec::new());
+        }
+
+        let mut command = flags;
+        command.extend(["-c".to_string(), "-o".to_string()]);
+        command.push(object.to_string_lossy().into_owned());
+        command.push(input.to_string_lossy().into_owned());
+        let args = match parse_args(&command) {
+            Ok(args) => args,
+            Err(e) => return Response::failed(e, Vec::new()),
+        };
+
+        // Every path is absolute, so the working directory can stay as it is
+        let mut response = self.compile(args, None).await;
+        // Diagnostics name the client's file, not the scratch copy
+        for diagnostic in &mut response.diagnostics {
+            if diagnostic.file.is_some() {
+                diagnostic.file = Some(PathBuf::from(name));
+            }
+        }
+        if response.success {
+            response.objects.clear();
+            match tokio::fs::read(&object).await {
+                Ok(bytes) => response.object = Some(to_hex(&bytes)),
+                Err(e) => return Response::failed(e, response.diagnostics),
+            }
+        }
+        response
+    }
+
+    /// Run one job, entering `cwd` first if given
+    async fn compile(&self, args: Args, cwd: Option<&Path>) -> Response {
+        let _job = self.jobs.lock().await;
+        if let Some(cwd) = cwd {
+            if let Err(e) = std::env::set_current_dir(cwd) {
+                return Response::failed(
+                    format!("cannot enter {}: {}", cwd.display(), e),
+                    Vec::new(),
+                );
+            }
+            info!("Compile job in {}: {:?}", cwd.display(), args.input_files);
+        } else {
+            info!("Compile job: {:?}", args.input_files);
+        }
+
+        let mut compiler = match Compiler::new(args) {
+            Ok(compiler) => compiler.with_cache(self.cache.clone()),
+            Err(e) => return Response::failed(e, Vec::new()),
+        };
+        let response = match compiler.compile().await {
+            Ok(output) => Response::succeeded(output, cwd.unwrap_or(Path::new(""))),
+            Err(e) => Response::failed(e, compiler.diagnostics().to_vec()),
+        };
+        let (headers, units) = self.cache.hits();
+        debug!(
+            "Cache hits so far: {} headers, {} token lists",
+            headers, units
+        );
+        response
+    }
+}
+
+/// Parse a job's command line, or say why it is not one
+fn parse_args(args: &[String]) -> std::result::Result<Args, String> {
+    Args::try_parse_from(std::iter::once("alecc").chain(args.iter().map(String::as_str)))
+        .map_err(|e| e.to_string().trim_end().to_string())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
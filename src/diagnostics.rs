@@ -0,0 +1,159 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    #[allow(dead_code)]
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A message reported to the user while compiling, independent of whether
+/// compilation as a whole succeeds.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    /// The `-W` option that controls a warning, e.g. `unused-variable`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub option: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            file: None,
+            line: 0,
+            column: 0,
+            option: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// A warning `-W<option>` controls, named in brackets after the message
+    pub fn with_option(mut self, option: impl Into<String>) -> Self {
+        let option = option.into();
+        if !self.message.ends_with(']') {
+            self.message = format!("{} [-W{}]", self.message, option);
+        }
+        self.option = Some(option);
+        self
+    }
+
+    /// Report the warning as an error, as `-Werror` does, naming the option as `-Werror=<option>`
+    pub fn into_error(mut self) -> Self {
+        self.severity = Severity::Error;
+        if let Some(start) = self.message.rfind(" [-W") {
+            self.message.replace_range(start..start + 4, " [-Werror=");
+        }
+        self
+    }
+
+    pub fn with_location(mut self, line: usize, column: usize) -> Self {
+        self.line = line;
+        self.column = column;
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}:", file.display())?;
+            if self.line > 0 {
+                write!(f, "{}:{}:", self.line, self.column)?;
+            }
+            write!(f, " ")?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Holds diagnostics per translation unit and prints them grouped in input
+/// order, so the output does not depend on which unit finishes first. In
+/// streaming mode every diagnostic is printed as soon as it is reported.
+#[derive(Debug, Default)]
+pub struct DiagnosticBuffer {
+    streaming: bool,
+    units: BTreeMap<usize, Vec<Diagnostic>>,
+    finished: BTreeSet<usize>,
+    next_unit: usize,
+    emitted: Vec<Diagnostic>,
+}
+
+impl DiagnosticBuffer {
+    pub fn new(streaming: bool) -> Self {
+        Self {
+            streaming,
+            ..Self::default()
+        }
+    }
+
+    /// Report a diagnostic for the unit at position `unit` of the input list
+    pub fn report(&mut self, unit: usize, diagnostic: Diagnostic) {
+        if self.streaming {
+            eprintln!("{}", diagnostic);
+            self.emitted.push(diagnostic);
+        } else {
+            self.units.entry(unit).or_default().push(diagnostic);
+        }
+    }
+
+    /// Mark `unit` as done and print every buffered unit whose predecessors are done too
+    pub fn finish_unit(&mut self, unit: usize) {
+        self.finished.insert(unit);
+        while self.finished.remove(&self.next_unit) {
+            self.emit_unit(self.next_unit);
+            self.next_unit += 1;
+        }
+    }
+
+    /// Print whatever is still buffered, in input order (used when compilation stops early)
+    pub fn flush(&mut self) {
+        let units: Vec<usize> = self.units.keys().copied().collect();
+        for unit in units {
+            self.emit_unit(unit);
+        }
+        self.finished.clear();
+    }
+
+    /// All diagnostics printed so far, in the order they were printed
+    pub fn take_emitted(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.emitted)
+    }
+
+    fn emit_unit(&mut self, unit: usize) {
+        for diagnostic in self.units.remove(&unit).unwrap_or_default() {
+            eprintln!("{}", diagnostic);
+            self.emitted.push(diagnostic);
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use crate::cli::{DocArgs, DocFormat};
+use crate::error::{AleccError, Result};
+use crate::lexer::{DocComment, Lexer, Span, Token, TokenType};
+use std::fmt::{self, Write as _};
+use std::path::PathBuf;
+
+/// What a documented declaration declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Function,
+    Variable,
+    /// A typedef, or a struct, union or enum definition
+    Type,
+    Macro,
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemKind::Function => write!(f, "function"),
+            ItemKind::Variable => write!(f, "variable"),
+            ItemKind::Type => write!(f, "type"),
+            ItemKind::Macro => write!(f, "macro"),
+        }
+    }
+}
+
+/// A declaration and the doc comment right before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocItem {
+    pub kind: ItemKind,
+    pub name: String,
+    /// The declaration as written, without a function body or the final `;`
+    pub declaration: String,
+    pub line: usize,
+    pub docs: Docs,
+}
+
+/// A doc comment split into prose and its `@param` and `@return` tags
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Docs {
+    pub paragraphs: Vec<String>,
+    pub params: Vec<(String, String)>,
+    pub returns: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeaderDocs {
+    pub path: PathBuf,
+    pub items: Vec<DocItem>,
+}
+
+/// `alecc doc`: document each header and write the page
+pub async fn run(args: &DocArgs) -> Result<()> {
+    let mut headers = Vec::new();
+    for path in &args.headers {
+        let source =
+            tokio::fs::read_to_string(path)
+                .await
+                .map_err(|_| AleccError::FileNotFound {
+                    path: path.to_string_lossy().to_string(),
+                })?;
+        headers.push(HeaderDocs {
+            path: path.clone(),
+            items: document(&source)?,
+        });
+    }
+
+    let page = match args.format {
+        DocFormat::Markdown => render_markdown(&args.title, &headers),
+        DocFormat::Html => render_html(&args.title, &headers),
+    };
+    match &args.output {
+        Some(output) => tokio::fs::write(output, page).await?,
+        None => print!("{}", page),
+    }
+    Ok(())
+}
+
+/// The documented declarations of one file, in source order. Only file-scope
+/// declarations and `#define`s count; `#ifdef __cplusplus` blocks are skipped
+/// and other preprocessor conditionals are read as if every branch were taken.
+pub fn document(source: &str) -> Result<Vec<DocItem>> {
+    let (code, mut declarations) = strip_directives(source);
+    let mut lexer = Lexer::new(code.clone()).with_doc_comments();
+    let tokens: Vec<Token> = lexer
+        .tokenize()?
+        .into_iter()
+        .filter(|token| !matches!(token.token_type, TokenType::Newline | TokenType::Eof))
+        .collect();
+    let comments = lexer.take_doc_comments();
+
+    let lines: Vec<Vec<char>> = code.lines().map(|line| line.chars().collect()).collect();
+    declarations.extend(
+        split_declarations(&tokens)
+            .into_iter()
+            .filter_map(|(tokens, last)| declaration(tokens, last, &lines)),
+    );
+    declarations.sort_by_key(|declaration| position(declaration.start));
+
+    let mut items = Vec::new();
+    let mut previous_end = (0, 0);
+    for declaration in declarations {
+        // The closest doc comment between the previous declaration and this one
+        let comment = comments.iter().rev().find(|comment| {
+            position(comment.end) <= position(declaration.start)
+                && position(comment.span) >= previous_end
+                && !comment.text.starts_with('<')
+        });
+        if let Some(comment) = comment {
+            items.push(DocItem {
+                kind: declaration.kind,
+                name: declaration.name,
+                declaration: declaration.text,
+                line: declaration.start.line,
+                docs: parse_docs(comment),
+            });
+        }
+        previous_end = position(declaration.end);
+    }
+    Ok(items)
+}
+
+#[derive(Debug)]
+struct Declaration {
+    kind: ItemKind,
+    name: String,
+    text: String,
+    start: Span,
+    end: Span,
+}
+
+fn position(span: Span) -> (usize, usize) {
+    (span.line, span.column)
+}
+
+/// Blank out preprocessor lines (and `__cplusplus`-only code) so the rest
+/// lexes as plain C, collecting each `#define` as a declaration
+fn strip_directives(source: &str) -> (String, Vec<Declaration>) {
+    let mut code = Vec::new();
+    let mut macros: Vec<Declaration> = Vec::new();
+    // One entry per open conditional: Some(skipping) for `__cplusplus` tests
+    let mut conditionals: Vec<Option<bool>> = Vec::new();
+    let mut continued_macro = None;
+    let mut continued = false;
+
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        if continued {
+            continued = line.ends_with('\\');
+            if let Some(declaration) = continued_macro.and_then(|i: usize| macros.get_mut(i)) {
+                declaration.text.push('\n');
+                declaration.text.push_str(line);
+                declaration.end = Span {
+                    line: number,
+                    column: line.chars().count() + 1,
+                };
+            }
+            if !continued {
+                continued_macro = None;
+            }
+            code.push("");
+            continue;
+        }
+
+        let skipping = conditionals.contains(&Some(true));
+        let Some(directive) = line.trim_start().strip_prefix('#') else {
+            code.push(if skipping { "" } else { line });
+            continue;
+        };
+        code.push("");
+        continued = line.ends_with('\\');
+
+        let directive = directive.trim_start();
+        let word: String = directive
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        let rest = directive[word.len()..].trim();
+        let cplusplus = rest
+            .trim_start_matches("defined")
+            .trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace())
+            == "__cplusplus";
+        match word.as_str() {
+            "ifdef" | "if" => conditionals.push(cplusplus.then_some(true)),
+            "ifndef" => conditionals.push(cplusplus.then_some(false)),
+            "else" | "elif" => {
+                if let Some(Some(skip)) = conditionals.last_mut() {
+                    *skip = !*skip;
+                }
+            }
+            "endif" => {
+                conditionals.pop();
+            }
+            "define" if !skipping => {
+                let name: String = rest
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                    .collect();
+                if !name.is_empty() {
+                    if continued {
+                        continued_macro = Some(macros.len());
+                    }
+                    macros.push(Declaration {
+                        kind: ItemKind::Macro,
+                        name,
+                        text: line.trim().to_string(),
+                        start: Span {
+                            line: number,
+                            column: 1,
+                        },
+                        end: Span {
+                            line: number,
+                            column: line.chars().count() + 1,
+                        },
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    (code.join("\n"), macros)
+}
+
+/// Cut the file-scope tokens into declarations, each ending before its `;`
+/// or, for a function definition, before the body. The last token each
+/// takes up (the `;` or the body's `}`) comes with it.
+fn split_declarations(tokens: &[Token]) -> Vec<(&[Token], &Token)> {
+    let mut declarations = Vec::new();
+    let mut start = 0;
+    while start < tokens.len() {
+        let mut depth = 0usize;
+        let mut assigned = false;
+        let mut end = tokens.len();
+        let mut next = tokens.len();
+        let mut i = start;
+        while i < tokens.len() {
+            match tokens[i].token_type {
+                TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket => depth = depth.saturating_sub(1),
+                TokenType::Assign if depth == 0 => assigned = true,
+                TokenType::LeftBrace if depth == 0 => {
+                    let close = matching_brace(tokens, i);
+                    let body =
+                        !assigned && i > start && tokens[i - 1].token_type == TokenType::RightParen;
+                    if body {
+                        end = i;
+                        next = close + 1;
+                        break;
+                    }
+                    i = close;
+                }
+                TokenType::Semicolon if depth == 0 => {
+                    end = i;
+                    next = i + 1;
+                    break;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        if end > start {
+            declarations.push((&tokens[start..end], &tokens[next - 1]));
+        }
+        start = next;
+    }
+    declarations
+}
+
+fn matching_brace(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.token_type {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len() - 1
+}
+
+fn declaration(tokens: &[Token], taken: &Token, lines: &[Vec<char>]) -> Option<Declaration> {
+    let first = tokens.first()?;
+    let start = first.span();
+    let text = source_text(lines, start, end_of(tokens.last()?));
+
+    let (kind, name) = match declarator(tokens) {
+        Some(_) if tokens.iter().any(|t| t.token_type == TokenType::Typedef) => {
+            (ItemKind::Type, declarator_name(tokens)?)
+        }
+        Some(i) if tokens.get(i + 1).map(|t| &t.token_type) == Some(&TokenType::LeftParen) => {
+            (ItemKind::Function, declarator_name(tokens)?)
+        }
+        Some(_) => (ItemKind::Variable, declarator_name(tokens)?),
+        // `struct tag { ... }` with no declarator defines just the tag
+        None => match (&first.token_type, tokens.get(1).map(|t| &t.token_type)) {
+            (TokenType::Struct, Some(TokenType::Identifier(tag))) => {
+                (ItemKind::Type, format!("struct {}", tag))
+            }
+            (TokenType::Union, Some(TokenType::Identifier(tag))) => {
+                (ItemKind::Type, format!("union {}", tag))
+            }
+            (TokenType::Enum, Some(TokenType::Identifier(tag))) => {
+                (ItemKind::Type, format!("enum {}", tag))
+            }
+            _ => return None,
+        },
+    };
+    Some(Declaration {
+        kind,
+        name,
+        text,
+        start,
+        end: end_of(taken),
+    })
+}
+
+fn end_of(token: &Token) -> Span {
+    Span {
+        line: token.line,
+        column: token.column + token.length,
+    }
+}
+
+fn declarator_name(tokens: &[Token]) -> Option<String> {
+    match &tokens[declarator(tokens)?].token_type {
+        TokenType::Identifier(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Index of the declared name: the first identifier outside braces and
+/// brackets that isn't a tag or a type name, which is to say one followed
+/// by something a declarator can be followed by
+fn declarator(tokens: &[Token]) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i].token_type {
+            TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+            TokenType::RightBrace | TokenType::RightBracket => depth = depth.saturating_sub(1),
+            TokenType::Identifier(name) if depth == 0 => {
+                if name.starts_with("__attribute") || name.starts_with("__asm") || name == "asm" {
+                    i = skip_parenthesized(tokens, i + 1);
+                    continue;
+                }
+                let tag = i > 0
+                    && matches!(
+                        tokens[i - 1].token_type,
+                        TokenType::Struct | TokenType::Union | TokenType::Enum
+                    );
+                let declarator_ends = matches!(
+                    tokens.get(i + 1).map(|t| &t.token_type),
+                    None | Some(
+                        TokenType::LeftParen
+                            | TokenType::RightParen
+                            | TokenType::LeftBracket
+                            | TokenType::Assign
+                            | TokenType::Comma
+                    )
+                );
+                if !tag && declarator_ends {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn skip_parenthesized(tokens: &[Token], open: usize) -> usize {
+    if tokens.get(open).map(|t| &t.token_type) != Some(&TokenType::LeftParen) {
+        return open;
+    }
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.token_type {
+            TokenType::LeftParen => depth += 1,
+            TokenType::RightParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// The text from `start` up to (not including) `end`; columns count characters
+fn source_text(lines: &[Vec<char>], start: Span, end: Span) -> String {
+    let mut text = String::new();
+    for line in start.line..=end.line {
+        let chars = lines.get(line - 1).map(Vec::as_slice).unwrap_or_default();
+        let from = if line == start.line {
+            start.column - 1
+        } else {
+            0
+        };
+        let to = if line == end.line {
+            (end.column - 1).min(chars.len())
+        } else {
+            chars.len()
+        };
+        if line != start.line {
+            text.push('\n');
+        }
+        text.extend(chars.get(from..to).unwrap_or_default());
+    }
+    text
+}
+
+fn parse_docs(comment: &DocComment) -> Docs {
+    enum Target {
+        Paragraph,
+        Param(usize),
+        Returns,
+    }
+
+    let mut docs = Docs::default();
+    let mut paragraph = String::new();
+    let mut target = Target::Paragraph;
+    for line in comment.text.lines() {
+        let line = line.trim();
+        let line = line.strip_prefix('*').unwrap_or(line).trim();
+        if line.is_empty() {
+            if !paragraph.is_empty() {
+                docs.paragraphs.push(std::mem::take(&mut paragraph));
+            }
+            target = Target::Paragraph;
+            continue;
+        }
+
+        if let Some(rest) = tag(line, "param") {
+            // `@param[in] name`
+            let rest = match rest.strip_prefix('[') {
+                Some(direction) => direction.split_once(']').map_or("", |(_, r)| r).trim(),
+                None => rest,
+            };
+            let (name, description) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            docs.params
+                .push((name.to_string(), description.trim().to_string()));
+            target = Target::Param(docs.params.len() - 1);
+            continue;
+        }
+        if let Some(rest) = tag(line, "returns").or_else(|| tag(line, "return")) {
+            docs.returns = Some(rest.to_string());
+            target = Target::Returns;
+            continue;
+        }
+
+        let line = tag(line, "brief").unwrap_or(line);
+        let text = match target {
+            Target::Paragraph => &mut paragraph,
+            Target::Param(i) => &mut docs.params[i].1,
+            Target::Returns => docs.returns.get_or_insert_with(String::new),
+        };
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(line);
+    }
+    if !paragraph.is_empty() {
+        docs.paragraphs.push(paragraph);
+    }
+    docs
+}
+
+/// The rest of `line` if it starts with the tag `@name` (or `\name`)
+fn tag<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line
+        .strip_prefix('@')
+        .or_else(|| line.strip_prefix('\\'))?
+        .strip_prefix(name)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) || rest.starts_with('[') {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+/// How a declaration is shown: as written, with the `;` it stands for
+fn shown(item: &DocItem) -> String {
+    match item.kind {
+        ItemKind::Macro => item.declaration.clone(),
+        _ => format!("{};", item.declaration),
+    }
+}
+
+pub fn render_markdown(title: &str, headers: &[HeaderDocs]) -> String {
+    let mut out = format!("# {}\n", title);
+    for header in headers {
+        let _ = write!(out, "\n## `{}`\n", header.path.display());
+        if header.items.is_empty() {
+            out.push_str("\nNo documented declarations.\n");
+        }
+        for item in &header.items {
+            let _ = write!(
+                out,
+                "\n### `{}` ({})\n\n```c\n{}\n```\n",
+                item.name,
+                item.kind,
+                shown(item)
+            );
+            for paragraph in &item.docs.paragraphs {
+                let _ = write!(out, "\n{}\n", paragraph);
+            }
+            if !item.docs.params.is_empty() {
+                out.push_str("\n**Parameters**\n\n");
+                for (name, description) in &item.docs.params {
+                    let _ = writeln!(out, "- `{}`: {}", name, description);
+                }
+            }
+            if let Some(returns) = &item.docs.returns {
+                let _ = write!(out, "\n**Returns:** {}\n", returns);
+            }
+        }
+    }
+    out
+}
+
+pub fn render_html(title: &str, headers: &[HeaderDocs]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape_html(title)
+    );
+    for header in headers {
+        let _ = writeln!(
+            out,
+            "<h2><code>{}</code></h2>",
+            escape_html(&header.path.display().to_string())
+        );
+        if header.items.is_empty() {
+            out.push_str("<p>No documented declarations.</p>\n");
+        }
+        for item in &header.items {
+            let _ = writeln!(
+                out,
+                "<section class=\"{}\" id=\"{}\">\n<h3><code>{}</code> ({})</h3>\n<pre><code>{}</code></pre>",
+                item.kind,
+                item.name.replace(' ', "-"),
+                escape_html(&item.name),
+                item.kind,
+                escape_html(&shown(item))
+            );
+            for paragraph in &item.docs.paragraphs {
+                let _ = writeln!(out, "<p>{}</p>", escape_html(paragraph));
+            }
+            if !item.docs.params.is_empty() {
+                out.push_str("<h4>Parameters</h4>\n<ul>\n");
+                for (name, description) in &item.docs.params {
+                    let _ = writeln!(
+                        out,
+                        "<li><code>{}</code>: {}</li>",
+                        escape_html(name),
+                        escape_html(description)
+                    );
+                }
+                out.push_str("</ul>\n");
+            }
+            if let Some(returns) = &item.docs.returns {
+                let _ = writeln!(
+                    out,
+                    "<p><strong>Returns:</strong> {}</p>",
+                    escape_html(returns)
+                );
+            }
+            out.push_str("</section>\n");
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
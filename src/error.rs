@@ -1,3 +1,5 @@
+use crate::lexer::Span;
+use std::path::Path;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,6 +11,9 @@ pub enum AleccError {
         message: String,
     },
 
+    #[error("Preprocessor error at line {line}: {message}")]
+    PreprocessorError { line: usize, message: String },
+
     #[error("Parse error at line {line}, column {column}: {message}")]
     ParseError {
         line: usize,
@@ -16,12 +21,19 @@ pub enum AleccError {
         message: String,
     },
 
-    #[allow(dead_code)]
-    #[error("Semantic error: {message}")]
-    SemanticError { message: String },
+    #[error("Semantic error{}: {message}", located(.span))]
+    SemanticError {
+        message: String,
+        /// The construct the error is about, when known
+        span: Option<Span>,
+    },
 
-    #[error("Code generation error: {message}")]
-    CodegenError { message: String },
+    #[error("Code generation error{}: {message}", located(.span))]
+    CodegenError {
+        message: String,
+        /// The construct the error is about, when known
+        span: Option<Span>,
+    },
 
     #[error("Linker error: {message}")]
     LinkerError { message: String },
@@ -41,6 +53,52 @@ pub enum AleccError {
     #[allow(dead_code)]
     #[error("Internal compiler error: {message}")]
     InternalError { message: String },
+
+    #[error("{path}: {error}")]
+    InFile {
+        path: String,
+        /// The error itself, whose line and column are in `path`
+        #[source]
+        error: Box<AleccError>,
+    },
+}
+
+impl AleccError {
+    /// Attribute a semantic or code generation error to `span` unless it
+    /// already names a (more precise) place
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            AleccError::SemanticError {
+                message,
+                span: None,
+            } => AleccError::SemanticError {
+                message,
+                span: Some(span),
+            },
+            AleccError::CodegenError {
+                message,
+                span: None,
+            } => AleccError::CodegenError {
+                message,
+                span: Some(span),
+            },
+            error => error,
+        }
+    }
+}
+
+impl AleccError {
+    /// Name the source file an error with a line and column comes from
+    pub fn in_file(self, path: &Path) -> Self {
+        AleccError::InFile {
+            path: path.display().to_string(),
+            error: Box::new(self),
+        }
+    }
+}
+
+fn located(span: &Option<Span>) -> String {
+    span.map(|span| format!(" at {}", span)).unwrap_or_default()
 }
 
 pub type Result<T> = std::result::Result<T, AleccError>;
@@ -0,0 +1,718 @@
+use crate::cli::ExplainAsmArgs;
+use crate::error::{AleccError, Result};
+use crate::targets::{RegisterSet, Target};
+
+pub async fn run(args: &ExplainAsmArgs) -> Result<()> {
+    let source =
+        tokio::fs::read_to_string(&args.file)
+            .await
+            .map_err(|_| AleccError::FileNotFound {
+                path: args.file.to_string_lossy().to_string(),
+            })?;
+    let target = match &args.target {
+        Some(name) => Target::from_string(name).ok_or_else(|| AleccError::UnsupportedTarget {
+            target: name.clone(),
+        })?,
+        None => detect_target(&source),
+    };
+    let explained = explain(&source, target);
+    match &args.output {
+        Some(output) => tokio::fs::write(output, explained).await?,
+        None => print!("{}", explained),
+    }
+    Ok(())
+}
+
+/// The target an assembly file is for, from the directives alecc puts at its
+/// top; files without them are taken to be for the host
+pub fn detect_target(source: &str) -> Target {
+    for line in source.lines().map(str::trim) {
+        if line.starts_with(".arch armv8") {
+            return Target::Arm64;
+        }
+        if line == ".arch i386" || line == ".arch i686" || line == ".code32" {
+            return Target::I386;
+        }
+    }
+    if source.contains(".intel_syntax") {
+        Target::Amd64
+    } else {
+        Target::native()
+    }
+}
+
+/// `source` with a comment after each instruction and label saying what it
+/// does: the role the calling convention gives its registers, and how it
+/// moves the stack pointer. Stack effects add up from each function's label,
+/// so every line also shows how far the stack pointer is below the return
+/// address. The result assembles like the original.
+pub fn explain(source: &str, target: Target) -> String {
+    let explainer = Explainer {
+        target,
+        registers: target.register_names(),
+    };
+    let comment = match target {
+        Target::Arm64 => "//",
+        Target::I386 | Target::Amd64 => "#",
+    };
+    let mut output = String::with_capacity(source.len() * 2);
+    let mut globals: Vec<String> = Vec::new();
+    let mut in_text = true;
+    let mut sections: Vec<bool> = Vec::new();
+    // Bytes the stack pointer is below the caller's, once a function's label
+    // is seen, and what it was when the frame pointer was set from it
+    let mut depth: Option<i64> = None;
+    let mut frame: Option<i64> = None;
+    for line in source.lines() {
+        let trimmed = line.trim();
+        let code = trimmed
+            .split_once(comment)
+            .map_or(trimmed, |(code, _)| code.trim_end());
+        let note = if let Some(label) = code.strip_suffix(':') {
+            if label.starts_with('.') && !globals.iter().any(|global| global == label) {
+                Some("jump target".to_string())
+            } else if !in_text {
+                Some(format!("the data of {}", label))
+            } else {
+                // The process entry point is jumped to, with no return address
+                depth = Some(match target {
+                    Target::I386 | Target::Amd64 if label != "_start" => explainer.word(),
+                    _ => 0,
+                });
+                frame = None;
+                Some(format!(
+                    "entry of {}: arguments as the {} convention passes them",
+                    label,
+                    explainer.convention()
+                ))
+            }
+        } else if code.starts_with('.') {
+            if let Some(names) = code.strip_prefix(".globl") {
+                globals.extend(names.split(',').map(|name| name.trim().to_string()));
+            }
+            let (directive, operands) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+            match directive {
+                ".text" => in_text = true,
+                ".data" | ".bss" => in_text = false,
+                ".section" => in_text = operands.trim().starts_with(".text"),
+                ".pushsection" => {
+                    sections.push(in_text);
+                    in_text = operands.trim().starts_with(".text");
+                }
+                ".popsection" => in_text = sections.pop().unwrap_or(true),
+                _ => {}
+            }
+            explain_directive(code)
+        } else if code.is_empty() {
+            None
+        } else {
+            let (mnemonic, operands) = split_instruction(code);
+            let explanation = explainer.instruction(&mnemonic, &operands);
+            match explainer.frame_move(&mnemonic, &operands) {
+                Some(FrameMove::Set) => frame = depth,
+                Some(FrameMove::Restore) => depth = frame,
+                Some(FrameMove::Unknown) => depth = None,
+                None => {}
+            }
+            let effect = explainer.stack_effect(&mnemonic, &operands);
+            match (explanation, effect, depth.as_mut()) {
+                (Some(explanation), Some(effect), Some(depth)) => {
+                    *depth += effect;
+                    Some(format!(
+                        "{}; {} {}",
+                        explanation,
+                        explainer.registers.stack_pointer(),
+                        describe_depth(*depth)
+                    ))
+                }
+                (explanation, _, _) => explanation,
+            }
+        };
+        match note {
+            Some(note) if !code.is_empty() && code.len() == trimmed.len() => {
+                output.push_str(&format!("{:<44} {} {}\n", line, comment, note))
+            }
+            _ => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+    output
+}
+
+fn describe_depth(depth: i64) -> String {
+    if depth == 0 {
+        "back where the caller had it".to_string()
+    } else {
+        format!("now {} bytes below the caller's", depth)
+    }
+}
+
+/// An instruction that sets the stack pointer in a way its stack effect
+/// can't say
+enum FrameMove {
+    /// The frame pointer takes the stack pointer's value
+    Set,
+    /// The stack pointer goes back to the frame pointer, as `leave` does
+    Restore,
+    /// The stack pointer is realigned or computed
+    Unknown,
+}
+
+fn explain_directive(code: &str) -> Option<String> {
+    let (directive, operands) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let operands = operands.trim();
+    let note = match directive {
+        ".intel_syntax" => "Intel operand order: destination first".to_string(),
+        ".arch" => format!("assemble for {}", operands),
+        ".code32" => "assemble 32-bit code".to_string(),
+        ".text" => "what follows is code".to_string(),
+        ".data" => "what follows is writable data".to_string(),
+        ".bss" => "what follows is zero-filled data".to_string(),
+        ".section" | ".pushsection" => format!("switch to section {}", operands),
+        ".popsection" => "back to the previous section".to_string(),
+        ".globl" | ".global" => format!("{} is visible to other object files", operands),
+        ".weak" => format!("{} may be overridden by another definition", operands),
+        ".extern" => format!("{} is defined elsewhere", operands),
+        ".type" => match operands.split_once(',') {
+            Some((name, kind)) => match kind.trim().trim_start_matches(['@', '%']) {
+                "function" => format!("{} is a function", name),
+                "tls_object" => format!("{} is a thread-local variable", name),
+                _ => format!("{} is a variable", name),
+            },
+            None => return None,
+        },
+        ".size" => match operands.split_once(',') {
+            Some((name, size)) if size.trim().starts_with(".-") => {
+                format!("{} takes the bytes from its label to here", name)
+            }
+            Some((name, size)) => format!("{} takes {} bytes", name, size.trim()),
+            None => return None,
+        },
+        ".ident" => "the compiler's name, kept in the .comment section".to_string(),
+        ".string" | ".asciz" => "a NUL-terminated string".to_string(),
+        ".byte" => "1-byte values".to_string(),
+        ".short" | ".word" | ".hword" => "2-byte values".to_string(),
+        ".long" | ".int" => "4-byte values".to_string(),
+        ".quad" | ".xword" => "8-byte values".to_string(),
+        ".zero" | ".skip" | ".space" => format!("{} zero bytes", operands),
+        ".align" | ".balign" | ".p2align" => "pad to an alignment boundary".to_string(),
+        ".cfi_startproc" => "start of the function's unwind information".to_string(),
+        ".cfi_endproc" => "end of the function's unwind information".to_string(),
+        ".cfi_def_cfa_offset" | ".cfi_def_cfa_register" | ".cfi_def_cfa" => {
+            "where the caller's stack pointer is, for unwinding".to_string()
+        }
+        ".cfi_offset" => "where the caller's value of a register is saved".to_string(),
+        ".cfi_restore" => "the register has the caller's value again".to_string(),
+        ".cfi_remember_state" | ".cfi_restore_state" => {
+            "unwind information kept across a return".to_string()
+        }
+        ".cfi_undefined" => "no caller: backtraces end here".to_string(),
+        _ => return None,
+    };
+    Some(note)
+}
+
+/// The mnemonic, lowercased, and the operands, split at the commas outside brackets
+fn split_instruction(code: &str) -> (String, Vec<String>) {
+    let (mnemonic, rest) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in rest.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    (mnemonic.to_lowercase(), operands)
+}
+
+struct Explainer {
+    target: Target,
+    registers: RegisterSet,
+}
+
+impl Explainer {
+    fn convention(&self) -> &'static str {
+        self.target.calling_convention().name()
+    }
+
+    fn word(&self) -> i64 {
+        self.target.pointer_size() as i64
+    }
+
+    /// The full-width name of `register`, if it is one: `eax` is `rax` on
+    /// amd64, `w0` is `x0` on arm64
+    fn canonical(&self, register: &str) -> Option<String> {
+        let register = register.to_lowercase();
+        let all = self.registers.general_purpose_registers();
+        let extra: &[&str] = match self.target {
+            Target::I386 => &["esp", "ebp"],
+            Target::Amd64 => &["rsp", "rbp"],
+            Target::Arm64 => &["sp", "x29", "x30", "xzr"],
+        };
+        let known = |name: &str| all.contains(&name) || extra.contains(&name);
+        if known(&register) {
+            return Some(register);
+        }
+        let widened = match self.target {
+            Target::Arm64 => match register.as_str() {
+                "wsp" => "sp".to_string(),
+                "wzr" => "xzr".to_string(),
+                _ => register.replacen('w', "x", 1),
+            },
+            Target::Amd64 => {
+                let numbered = register
+                    .strip_prefix('r')
+                    .map(|rest| rest.trim_end_matches(['d', 'w', 'b']))
+                    .filter(|number| number.parse::<u8>().is_ok());
+                match numbered {
+                    Some(number) => format!("r{}", number),
+                    None => format!("r{}", x86_base(&register)?),
+                }
+            }
+            Target::I386 => format!("e{}", x86_base(&register)?),
+        };
+        known(&widened).then_some(widened)
+    }
+
+    /// What the calling convention uses `register` for
+    fn role(&self, register: &str) -> Option<String> {
+        let name = self.canonical(register)?;
+        let set = &self.registers;
+        let mut roles = Vec::new();
+        if name == set.stack_pointer() {
+            roles.push("stack pointer".to_string());
+        } else if name == set.frame_pointer() {
+            roles.push("frame pointer".to_string());
+        } else if name == "x30" {
+            roles.push("link register: the return address".to_string());
+        } else if name == "xzr" {
+            roles.push("always zero".to_string());
+        } else if name == "x8" {
+            roles.push("where to put a returned struct".to_string());
+        } else if name == "x16" || name == "x17" {
+            roles.push("scratch for the linker's veneers".to_string());
+        }
+        if let Some(index) = set.parameter_registers().iter().position(|r| *r == name) {
+            roles.push(format!("argument {}", index + 1));
+        }
+        if name == set.return_register() {
+            roles.push("return value".to_string());
+        }
+        if roles.is_empty() {
+            if set.callee_saved_registers().contains(&name.as_str()) {
+                roles.push("callee-saved".to_string());
+            } else if set.caller_saved_registers().contains(&name.as_str()) {
+                roles.push("scratch".to_string());
+            }
+        }
+        (!roles.is_empty()).then(|| roles.join(", "))
+    }
+
+    /// `operand` in words: registers with their role, memory with its size
+    fn describe(&self, operand: &str) -> String {
+        if let Some(role) = self.role(operand) {
+            return format!("{} ({})", operand, role);
+        }
+        if let Some(symbol) = operand.strip_prefix("OFFSET ") {
+            return format!("the address of {}", symbol);
+        }
+        if let Some((size, address)) = operand.split_once(" PTR ") {
+            let bytes = match size {
+                "BYTE" => 1,
+                "WORD" => 2,
+                "DWORD" => 4,
+                _ => 8,
+            };
+            return match self.stack_argument(address) {
+                Some(index) => format!(
+                    "the {}-byte value at {} (argument {}, passed on the stack)",
+                    bytes, address, index
+                ),
+                None => format!("the {}-byte value at {}", bytes, address),
+            };
+        }
+        operand.trim_start_matches('#').to_string()
+    }
+
+    /// Which argument `[ebp + 8]`-style addresses above the saved frame
+    /// pointer and return address hold, counting from 1
+    fn stack_argument(&self, address: &str) -> Option<usize> {
+        let inner = address.strip_prefix('[')?.strip_suffix(']')?;
+        let (base, offset) = inner.split_once('+')?;
+        if base.trim() != self.registers.frame_pointer() {
+            return None;
+        }
+        let offset: i64 = offset.trim().parse().ok()?;
+        let first = 2 * self.word();
+        if offset < first || (offset - first) % self.word() != 0 {
+            return None;
+        }
+        let in_registers = self.registers.parameter_registers().len();
+        Some(in_registers + ((offset - first) / self.word()) as usize + 1)
+    }
+
+    fn operand(&self, operands: &[String], index: usize) -> String {
+        operands
+            .get(index)
+            .map(|operand| self.describe(operand))
+            .unwrap_or_default()
+    }
+
+    fn is_stack_pointer(&self, operand: Option<&String>) -> bool {
+        operand
+            .and_then(|operand| self.canonical(operand))
+            .as_deref()
+            == Some(self.registers.stack_pointer())
+    }
+
+    fn immediate(operand: Option<&String>) -> Option<i64> {
+        operand?.trim_start_matches('#').parse().ok()
+    }
+
+    fn frame_move(&self, mnemonic: &str, operands: &[String]) -> Option<FrameMove> {
+        let is = |index: usize, register: &str| {
+            operands
+                .get(index)
+                .and_then(|operand| self.canonical(operand))
+                .as_deref()
+                == Some(register)
+        };
+        let sp = self.registers.stack_pointer();
+        let fp = self.registers.frame_pointer();
+        match mnemonic {
+            "leave" => Some(FrameMove::Restore),
+            "mov" if is(0, fp) && is(1, sp) => Some(FrameMove::Set),
+            "mov" if is(0, sp) && is(1, fp) => Some(FrameMove::Restore),
+            "and" | "mov" | "lea" if is(0, sp) => Some(FrameMove::Unknown),
+            _ => None,
+        }
+    }
+
+    /// How many bytes the instruction moves the stack pointer down (up when
+    /// negative), if it moves it at all
+    fn stack_effect(&self, mnemonic: &str, operands: &[String]) -> Option<i64> {
+        let word = self.word();
+        match self.target {
+            Target::I386 | Target::Amd64 => match mnemonic {
+                "push" => Some(word),
+                "pop" => Some(-word),
+                "sub" if self.is_stack_pointer(operands.first()) => {
+                    Self::immediate(operands.get(1))
+                }
+                "add" if self.is_stack_pointer(operands.first()) => {
+                    Self::immediate(operands.get(1)).map(|n| -n)
+                }
+                _ => None,
+            },
+            Target::Arm64 => match mnemonic {
+                "sub" if self.is_stack_pointer(operands.first()) => {
+                    Self::immediate(operands.get(2))
+                }
+                "add" if self.is_stack_pointer(operands.first()) => {
+                    Self::immediate(operands.get(2)).map(|n| -n)
+                }
+                // Pre-index `[sp, #-16]!` and post-index `[sp], #16`
+                "stp" | "ldp" | "str" | "ldr" => {
+                    let address = operands.iter().find(|o| o.starts_with('['))?;
+                    if !address.starts_with("[sp") {
+                        return None;
+                    }
+                    if address.ends_with('!') {
+                        let offset = address
+                            .trim_end_matches('!')
+                            .trim_end_matches(']')
+                            .split_once('#')?
+                            .1;
+                        offset.parse::<i64>().ok().map(|n| -n)
+                    } else if address == "[sp]" {
+                        Self::immediate(operands.last()).map(|n| -n)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+        }
+    }
+
+    fn instruction(&self, mnemonic: &str, operands: &[String]) -> Option<String> {
+        match self.target {
+            Target::I386 | Target::Amd64 => self.x86(mnemonic, operands),
+            Target::Arm64 => self.arm64(mnemonic, operands),
+        }
+    }
+
+    fn x86(&self, mnemonic: &str, operands: &[String]) -> Option<String> {
+        let a = self.operand(operands, 0);
+        let b = self.operand(operands, 1);
+        let set = &self.registers;
+        let note = match mnemonic {
+            "push" => format!("push {} onto the stack", a),
+            "pop" => format!("pop the top of the stack into {}", a),
+            "mov" => format!("{} = {}", a, b),
+            "lea" => format!(
+                "{} = the address {}",
+                a,
+                operands.get(1).map(String::as_str).unwrap_or_default()
+            ),
+            "movzx" => format!("{} = {}, zero-extended", a, b),
+            "movsx" | "movsxd" => format!("{} = {}, sign-extended", a, b),
+            "sub" if self.is_stack_pointer(operands.first()) => {
+                format!("reserve {} bytes of stack for the frame", b)
+            }
+            "add" if self.is_stack_pointer(operands.first()) => {
+                format!("release {} bytes of stack", b)
+            }
+            "xor" if operands.first() == operands.get(1) => format!("{} = 0", a),
+            "add" => format!("{} += {}", a, b),
+            "sub" => format!("{} -= {}", a, b),
+            "imul" => format!("{} *= {}", a, b),
+            "and" => format!("{} &= {}", a, b),
+            "or" => format!("{} |= {}", a, b),
+            "xor" => format!("{} ^= {}", a, b),
+            "shl" | "sal" => format!("{} <<= {}", a, b),
+            "shr" => format!("{} >>= {}, shifting in zeros", a, b),
+            "sar" => format!("{} >>= {}, keeping the sign", a, b),
+            "neg" => format!("{} = -{}", a, a),
+            "not" => format!("{} = ~{}", a, a),
+            "inc" => format!("{} += 1", a),
+            "dec" => format!("{} -= 1", a),
+            "cmp" => format!("compare {} with {}, setting the flags", a, b),
+            "test" if operands.first() == operands.get(1) => {
+                format!("set the flags from whether {} is zero", a)
+            }
+            "test" => format!("set the flags from {} & {}", a, b),
+            "cqo" => "sign-extend rax into rdx:rax for a division".to_string(),
+            "cdq" => "sign-extend eax into edx:eax for a division".to_string(),
+            "idiv" | "div" => {
+                let (quotient, remainder) = match self.target {
+                    Target::I386 => ("eax", "edx"),
+                    _ => ("rax", "rdx"),
+                };
+                format!(
+                    "divide by {}{}: quotient in {}, remainder in {}",
+                    a,
+                    if mnemonic == "div" { ", unsigned" } else { "" },
+                    quotient,
+                    remainder
+                )
+            }
+            "jmp" => format!("jump to {}", a),
+            "call" => {
+                let arguments = match set.parameter_registers() {
+                    [] => "arguments on the stack".to_string(),
+                    registers => format!("arguments in {}", registers.join(", ")),
+                };
+                let through = if a.ends_with("@PLT") {
+                    ", through the PLT"
+                } else {
+                    ""
+                };
+                format!(
+                    "call {}{}: {}, result in {}; pushes the return address",
+                    a.trim_end_matches("@PLT"),
+                    through,
+                    arguments,
+                    set.return_register()
+                )
+            }
+            "ret" => format!(
+                "return to the caller with the result in {}; pops the return address",
+                set.return_register()
+            ),
+            "leave" => format!(
+                "{} = {}, then pop {}",
+                set.stack_pointer(),
+                set.frame_pointer(),
+                set.frame_pointer()
+            ),
+            "syscall" => {
+                "system call: number in rax, arguments in rdi, rsi, rdx, r10, r8, r9".to_string()
+            }
+            "int" if a == "0x80" => {
+                "system call: number in eax, arguments in ebx, ecx, edx, esi, edi".to_string()
+            }
+            "ud2" => "trap: an invalid instruction that stops the program".to_string(),
+            "nop" => "do nothing".to_string(),
+            _ => {
+                if let Some(condition) = mnemonic.strip_prefix("set") {
+                    format!("{} = 1 if {}, else 0", a, x86_condition(condition)?)
+                } else if let Some(condition) = mnemonic.strip_prefix("cmov") {
+                    format!("{} = {} if {}", a, b, x86_condition(condition)?)
+                } else if let Some(condition) = mnemonic.strip_prefix('j') {
+                    format!("jump to {} if {}", a, x86_condition(condition)?)
+                } else {
+                    return None;
+                }
+            }
+        };
+        Some(note)
+    }
+
+    fn arm64(&self, mnemonic: &str, operands: &[String]) -> Option<String> {
+        let a = self.operand(operands, 0);
+        let b = self.operand(operands, 1);
+        let c = self.operand(operands, 2);
+        let address = || {
+            operands
+                .iter()
+                .find(|operand| operand.starts_with('['))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let size = |mnemonic: &str, register: &str| match mnemonic.chars().last() {
+            Some('b') => 1,
+            Some('h') => 2,
+            _ if register.starts_with('w') => 4,
+            _ => 8,
+        };
+        let note = match mnemonic {
+            "stp" => format!("store {} and {} at {}", a, b, address()),
+            "ldp" => format!("load {} and {} from {}", a, b, address()),
+            "str" | "strb" | "strh" => format!(
+                "store the low {} bytes of {} at {}",
+                size(mnemonic, &operands[0]),
+                a,
+                address()
+            ),
+            "ldr" | "ldrb" | "ldrh" if operands.get(1).is_some_and(|o| o.starts_with('=')) => {
+                format!("{} = {}", a, operands[1].trim_start_matches('='))
+            }
+            "ldr" | "ldrb" | "ldrh" | "ldrsb" | "ldrsh" | "ldrsw" => format!(
+                "{} = the {}-byte value at {}",
+                a,
+                size(mnemonic.trim_start_matches("ldrs"), &operands[0]),
+                address()
+            ),
+            "mov" => format!("{} = {}", a, b),
+            "movz" => format!("{} = {} {}", a, b, c),
+            "movk" => format!("set 16 bits of {} to {}, {}", a, b, c),
+            "mvn" => format!("{} = ~{}", a, b),
+            "neg" => format!("{} = -{}", a, b),
+            "sub" if self.is_stack_pointer(operands.first()) => {
+                format!("reserve {} bytes of stack for the frame", c)
+            }
+            "add" if self.is_stack_pointer(operands.first()) => {
+                format!("release {} bytes of stack", c)
+            }
+            "add" if c.starts_with(":lo12:") => {
+                format!("{} = {} + the low 12 bits of {}", a, b, &c[6..])
+            }
+            "add" => format!("{} = {} + {}", a, b, c),
+            "sub" => format!("{} = {} - {}", a, b, c),
+            "mul" => format!("{} = {} * {}", a, b, c),
+            "sdiv" => format!("{} = {} / {}", a, b, c),
+            "udiv" => format!("{} = {} / {}, unsigned", a, b, c),
+            "msub" => format!("{} = {} - {} * {}", a, self.operand(operands, 3), b, c),
+            "and" => format!("{} = {} & {}", a, b, c),
+            "orr" => format!("{} = {} | {}", a, b, c),
+            "eor" => format!("{} = {} ^ {}", a, b, c),
+            "lsl" => format!("{} = {} << {}", a, b, c),
+            "lsr" => format!("{} = {} >> {}, shifting in zeros", a, b, c),
+            "asr" => format!("{} = {} >> {}, keeping the sign", a, b, c),
+            "sxtb" | "sxth" | "sxtw" => format!("{} = {}, sign-extended", a, b),
+            "uxtb" | "uxth" => format!("{} = {}, zero-extended", a, b),
+            "cmp" => format!("compare {} with {}, setting the flags", a, b),
+            "cset" => format!(
+                "{} = 1 if {}, else 0",
+                a,
+                arm64_condition(operands.get(1)?)?
+            ),
+            "adrp" => format!("{} = the 4 KiB page holding {}", a, b),
+            "b" => format!("jump to {}", a),
+            "bl" => format!(
+                "call {}: arguments in {}, result in {}; the return address goes in x30",
+                a,
+                self.registers.parameter_registers().join(", "),
+                self.registers.return_register()
+            ),
+            "blr" => format!("call the function whose address is in {}", a),
+            "br" => format!("jump to the address in {}", a),
+            "cbz" => format!("jump to {} if {} is zero", b, a),
+            "cbnz" => format!("jump to {} if {} is not zero", b, a),
+            "ret" => format!(
+                "return to the address in x30 with the result in {}",
+                self.registers.return_register()
+            ),
+            "svc" => "system call: number in x8, arguments in x0-x5".to_string(),
+            "brk" => "trap: stop the program in the debugger".to_string(),
+            "nop" => "do nothing".to_string(),
+            _ => {
+                let condition = mnemonic.strip_prefix("b.")?;
+                format!("jump to {} if {}", a, arm64_condition(condition)?)
+            }
+        };
+        Some(note)
+    }
+}
+
+/// The letters an x86 register's name shares across its widths: `a` for
+/// `al`, `ax` and `eax`, `si` for `sil`, `si` and `esi`
+fn x86_base(register: &str) -> Option<&'static str> {
+    let base = match register {
+        "al" | "ah" | "ax" | "eax" => "ax",
+        "bl" | "bh" | "bx" | "ebx" => "bx",
+        "cl" | "ch" | "cx" | "ecx" => "cx",
+        "dl" | "dh" | "dx" | "edx" => "dx",
+        "sil" | "si" | "esi" => "si",
+        "dil" | "di" | "edi" => "di",
+        "spl" | "sp" | "esp" => "sp",
+        "bpl" | "bp" | "ebp" => "bp",
+        _ => return None,
+    };
+    Some(base)
+}
+
+fn x86_condition(suffix: &str) -> Option<&'static str> {
+    let condition = match suffix {
+        "e" => "equal",
+        "z" => "zero",
+        "ne" => "not equal",
+        "nz" => "not zero",
+        "l" => "less (signed)",
+        "le" => "less or equal (signed)",
+        "g" => "greater (signed)",
+        "ge" => "greater or equal (signed)",
+        "b" => "below (unsigned)",
+        "be" => "below or equal (unsigned)",
+        "a" => "above (unsigned)",
+        "ae" => "above or equal (unsigned)",
+        "s" => "negative",
+        "ns" => "not negative",
+        _ => return None,
+    };
+    Some(condition)
+}
+
+fn arm64_condition(condition: &str) -> Option<&'static str> {
+    let condition = match condition {
+        "eq" => "equal",
+        "ne" => "not equal",
+        "lt" => "less (signed)",
+        "le" => "less or equal (signed)",
+        "gt" => "greater (signed)",
+        "ge" => "greater or equal (signed)",
+        "lo" | "cc" => "below (unsigned)",
+        "ls" => "below or equal (unsigned)",
+        "hi" => "above (unsigned)",
+        "hs" | "cs" => "above or equal (unsigned)",
+        "mi" => "negative",
+        "pl" => "not negative",
+        _ => return None,
+    };
+    Some(condition)
+}
@@ -0,0 +1,737 @@
+use crate::cli::{BraceStyle, FmtArgs};
+use crate::error::{AleccError, Result};
+use crate::lexer::{Lexer, TokenType, Trivia};
+use std::collections::HashSet;
+
+/// The layout `alecc fmt` produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub indent_width: usize,
+    pub use_tabs: bool,
+    pub brace_style: BraceStyle,
+    pub max_blank_lines: usize,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            use_tabs: false,
+            brace_style: BraceStyle::Attach,
+            max_blank_lines: 1,
+        }
+    }
+}
+
+impl From<&FmtArgs> for Style {
+    fn from(args: &FmtArgs) -> Self {
+        Self {
+            indent_width: args.indent_width,
+            use_tabs: args.use_tabs,
+            brace_style: args.brace_style,
+            max_blank_lines: args.max_blank_lines,
+        }
+    }
+}
+
+/// `alecc fmt`: print, rewrite or check each file. Returns whether every
+/// file was already formatted.
+pub async fn run(args: &FmtArgs) -> Result<bool> {
+    let style = Style::from(args);
+    let mut formatted = true;
+    for path in &args.files {
+        let source =
+            tokio::fs::read_to_string(path)
+                .await
+                .map_err(|_| AleccError::FileNotFound {
+                    path: path.to_string_lossy().to_string(),
+                })?;
+        let output = format_source(&source, &style)?;
+        if args.check {
+            if output != source {
+                println!("{}", path.display());
+                formatted = false;
+            }
+        } else if args.in_place {
+            if output != source {
+                tokio::fs::write(path, output).await?;
+            }
+        } else {
+            print!("{}", output);
+        }
+    }
+    Ok(formatted)
+}
+
+/// Reformat a C file. Line breaks are kept as written except around braces,
+/// which `style` places; spacing and indentation are recomputed from the
+/// tokens, and comments and preprocessor lines are kept as they are. Long
+/// lines are not wrapped.
+pub fn format_source(source: &str, style: &Style) -> Result<String> {
+    let mut lexer = Lexer::new(source.to_string()).with_trivia();
+    let tokens = lexer.tokenize()?;
+    let trivia = lexer.take_trivia();
+    let types: Vec<TokenType> = tokens.into_iter().map(|token| token.token_type).collect();
+
+    let (toks, lines) = split_lines(&types, &trivia);
+    let analysis = analyze(&toks);
+    let lines = place_braces(lines, &toks, &analysis, style.brace_style);
+    Ok(render(&lines, &toks, &analysis, style))
+}
+
+/// A token outside preprocessor lines
+#[derive(Debug)]
+struct Tok {
+    kind: TokenType,
+    text: String,
+}
+
+impl Tok {
+    fn is(&self, text: &str) -> bool {
+        self.text == text
+    }
+
+    /// Identifiers, keywords and literals
+    fn is_word(&self) -> bool {
+        self.text
+            .starts_with(|c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '"' | '\''))
+    }
+
+    fn is_identifier(&self) -> bool {
+        matches!(self.kind, TokenType::Identifier(_))
+    }
+
+    fn is_keyword(&self) -> bool {
+        self.is_word()
+            && !self.is_identifier()
+            && !matches!(
+                self.kind,
+                TokenType::IntegerLiteral(_)
+                    | TokenType::SuffixedIntegerLiteral(..)
+                    | TokenType::FloatLiteral(_)
+                    | TokenType::StringLiteral(_)
+                    | TokenType::CharLiteral(_)
+            )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Token(usize),
+    Comment(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Line {
+    items: Vec<Item>,
+    /// Blank lines before this one in the source
+    blank_before: usize,
+    /// The text of a preprocessor line, which is kept as written
+    directive: Option<String>,
+}
+
+impl Line {
+    fn tokens(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.items.iter().filter_map(|item| match item {
+            Item::Token(index) => Some(*index),
+            Item::Comment(_) => None,
+        })
+    }
+
+    /// The last token, if nothing but tokens follow it
+    fn ends_with_token(&self) -> Option<usize> {
+        match self.items.last() {
+            Some(Item::Token(index)) if self.directive.is_none() => Some(*index),
+            _ => None,
+        }
+    }
+}
+
+/// Group the tokens into source lines, setting preprocessor lines aside
+fn split_lines(types: &[TokenType], trivia: &[Trivia]) -> (Vec<Tok>, Vec<Line>) {
+    let mut toks = Vec::new();
+    let mut lines = Vec::new();
+    let mut current = Line::default();
+    let mut blank = 0;
+
+    let mut i = 0;
+    while i < types.len() {
+        if current.items.is_empty() && types[i] == TokenType::Hash {
+            let mut text = format!(
+                "{}{}",
+                trivia[i].leading_text().trim_start(),
+                trivia[i].text
+            );
+            i += 1;
+            while !matches!(types[i], TokenType::Newline | TokenType::Eof) {
+                text.push_str(&trivia[i].leading_text());
+                text.push_str(&trivia[i].text);
+                i += 1;
+            }
+            text.push_str(&trivia[i].leading_text());
+            lines.push(Line {
+                blank_before: std::mem::take(&mut blank),
+                directive: Some(text.trim_end().to_string()),
+                ..Line::default()
+            });
+            if types[i] == TokenType::Eof {
+                break;
+            }
+            i += 1;
+            continue;
+        }
+
+        current.items.extend(
+            trivia[i]
+                .comments()
+                .map(|comment| Item::Comment(comment.text.trim_end().to_string())),
+        );
+        match &types[i] {
+            TokenType::Newline | TokenType::Eof => {
+                if current.items.is_empty() {
+                    blank += 1;
+                } else {
+                    current.blank_before = std::mem::take(&mut blank);
+                    lines.push(std::mem::take(&mut current));
+                }
+            }
+            kind => {
+                current.items.push(Item::Token(toks.len()));
+                toks.push(Tok {
+                    kind: kind.clone(),
+                    text: trivia[i].text.clone(),
+                });
+            }
+        }
+        i += 1;
+    }
+    (toks, lines)
+}
+
+/// What a pair of braces encloses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BraceKind {
+    Function,
+    Block,
+    Switch,
+    /// A struct or union body
+    Record,
+    Enum,
+    /// An initializer or compound literal
+    Aggregate,
+}
+
+/// How a token binds to its neighbours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Plain,
+    /// A unary operator, or the `*` of a declarator: nothing after it
+    Prefix,
+    /// `x++`: nothing before it
+    Postfix,
+    /// A binary, assignment or conditional operator: spaces around it
+    Binary,
+    /// The colon of a label
+    Label,
+    /// The `)` of a cast: nothing after it
+    CastClose,
+}
+
+struct Analysis {
+    roles: Vec<Role>,
+    /// The kind of the pair each brace belongs to
+    braces: Vec<Option<BraceKind>>,
+    /// Parentheses open before each token
+    paren_depth: Vec<usize>,
+    /// `)` tokens that close the condition of if, for, while or switch
+    control: Vec<bool>,
+}
+
+fn analyze(toks: &[Tok]) -> Analysis {
+    let typedefs = typedef_names(toks);
+    let is_type = |i: usize| is_type_word(toks, i, &typedefs);
+
+    let mut analysis = Analysis {
+        roles: vec![Role::Plain; toks.len()],
+        braces: vec![None; toks.len()],
+        paren_depth: vec![0; toks.len()],
+        control: vec![false; toks.len()],
+    };
+    let mut parens: Vec<usize> = Vec::new();
+    let mut opener: Vec<Option<usize>> = vec![None; toks.len()];
+    let mut braces: Vec<BraceKind> = Vec::new();
+    let mut statement = 0;
+    let mut conditionals = 0;
+
+    for (i, tok) in toks.iter().enumerate() {
+        analysis.paren_depth[i] = parens.len();
+        let prev = i.checked_sub(1).map(|p| &toks[p]);
+        let prev_role = i.checked_sub(1).map_or(Role::Plain, |p| analysis.roles[p]);
+        match tok.text.as_str() {
+            "(" => parens.push(i),
+            ")" => {
+                if let Some(open) = parens.pop() {
+                    opener[i] = Some(open);
+                    let before = open.checked_sub(1).map(|b| &toks[b]);
+                    if before.is_some_and(|b| {
+                        matches!(
+                            b.kind,
+                            TokenType::If | TokenType::While | TokenType::For | TokenType::Switch
+                        )
+                    }) {
+                        analysis.control[i] = true;
+                    } else if is_cast(toks, open, i, &is_type) {
+                        analysis.roles[i] = Role::CastClose;
+                    }
+                }
+            }
+            "{" => {
+                let kind = if prev.is_some_and(|p| p.is("="))
+                    || prev_role == Role::CastClose
+                    || (braces.last() == Some(&BraceKind::Aggregate)
+                        && prev.is_some_and(|p| p.is(",") || p.is("{")))
+                {
+                    BraceKind::Aggregate
+                } else if let Some(keyword) = tag_keyword(toks, i) {
+                    keyword
+                } else if prev.is_some_and(|p| p.is(")"))
+                    && opener[i - 1]
+                        .and_then(|open| open.checked_sub(1))
+                        .is_some_and(|b| toks[b].kind == TokenType::Switch)
+                {
+                    BraceKind::Switch
+                } else if braces.is_empty()
+                    && parens.is_empty()
+                    && prev.is_some_and(|p| p.is(")") || p.is(";"))
+                {
+                    BraceKind::Function
+                } else {
+                    BraceKind::Block
+                };
+                braces.push(kind);
+                analysis.braces[i] = Some(kind);
+                statement = i + 1;
+            }
+            "}" => {
+                analysis.braces[i] = braces.pop();
+                statement = i + 1;
+            }
+            ";" if parens.is_empty() => statement = i + 1,
+            "?" => {
+                conditionals += 1;
+                analysis.roles[i] = Role::Binary;
+            }
+            ":" => {
+                let in_code = matches!(
+                    braces.last(),
+                    Some(BraceKind::Function | BraceKind::Block | BraceKind::Switch)
+                );
+                analysis.roles[i] = if conditionals > 0 {
+                    conditionals -= 1;
+                    Role::Binary
+                } else if parens.is_empty()
+                    && in_code
+                    && (matches!(toks[statement].kind, TokenType::Case | TokenType::Default)
+                        || (i == statement + 1 && toks[statement].is_identifier()))
+                {
+                    statement = i + 1;
+                    Role::Label
+                } else {
+                    Role::Binary
+                };
+            }
+            "*" | "&" | "+" | "-" => {
+                let unary = match prev {
+                    None => true,
+                    Some(p) if p.is(")") => prev_role == Role::CastClose || analysis.control[i - 1],
+                    Some(p) if p.is("]") => false,
+                    Some(p) if p.is_keyword() => true,
+                    Some(p) if p.is_identifier() => is_type(i - 1),
+                    Some(p) => !p.is_word(),
+                };
+                analysis.roles[i] = if unary { Role::Prefix } else { Role::Binary };
+            }
+            "++" | "--" => {
+                let operand = prev
+                    .is_some_and(|p| (p.is_word() && !p.is_keyword()) || p.is(")") || p.is("]"));
+                analysis.roles[i] = if operand { Role::Postfix } else { Role::Prefix };
+            }
+            "!" | "~" => analysis.roles[i] = Role::Prefix,
+            text if is_binary_operator(text) => analysis.roles[i] = Role::Binary,
+            _ => {}
+        }
+    }
+    analysis
+}
+
+fn is_binary_operator(text: &str) -> bool {
+    matches!(
+        text,
+        "/" | "%"
+            | "="
+            | "+="
+            | "-="
+            | "*="
+            | "/="
+            | "%="
+            | "&="
+            | "|="
+            | "^="
+            | "<<="
+            | ">>="
+            | "=="
+            | "!="
+            | "<"
+            | ">"
+            | "<="
+            | ">="
+            | "&&"
+            | "||"
+            | "|"
+            | "^"
+            | "<<"
+            | ">>"
+    )
+}
+
+/// `struct name {`, `union {` or `enum name {`
+fn tag_keyword(toks: &[Tok], brace: usize) -> Option<BraceKind> {
+    let keyword = match brace.checked_sub(1).map(|p| &toks[p]) {
+        Some(p) if p.is_identifier() => brace.checked_sub(2).map(|p| &toks[p])?,
+        Some(p) => p,
+        None => return None,
+    };
+    match keyword.kind {
+        TokenType::Struct | TokenType::Union => Some(BraceKind::Record),
+        TokenType::Enum => Some(BraceKind::Enum),
+        _ => None,
+    }
+}
+
+/// Whether the parenthesized tokens from `open` to `close` are a type name
+/// used as a cast, such as `(char *)` or `(size_t)`
+fn is_cast(toks: &[Tok], open: usize, close: usize, is_type: &impl Fn(usize) -> bool) -> bool {
+    let called = open.checked_sub(1).is_some_and(|b| {
+        let before = &toks[b];
+        (before.is_word() && !matches!(before.kind, TokenType::Return | TokenType::Case))
+            || before.is(")")
+            || before.is("]")
+    });
+    let operand = toks
+        .get(close + 1)
+        .is_some_and(|next| !matches!(next.text.as_str(), ")" | ";" | "," | "]" | "="));
+    !called
+        && operand
+        && close > open + 1
+        && is_type(open + 1)
+        && (open + 1..close).all(|i| is_type(i) || toks[i].is_identifier() || toks[i].is("*"))
+}
+
+/// Type keywords and qualifiers, struct tags and names that look like types
+fn is_type_word(toks: &[Tok], i: usize, typedefs: &HashSet<&str>) -> bool {
+    let tok = &toks[i];
+    match &tok.kind {
+        TokenType::Char
+        | TokenType::Short
+        | TokenType::Int
+        | TokenType::Long
+        | TokenType::Float
+        | TokenType::Double
+        | TokenType::Signed
+        | TokenType::Unsigned
+        | TokenType::Void
+        | TokenType::Bool
+        | TokenType::Const
+        | TokenType::Volatile
+        | TokenType::Restrict
+        | TokenType::Struct
+        | TokenType::Union
+        | TokenType::Enum => true,
+        TokenType::Identifier(name) => {
+            typedefs.contains(name.as_str())
+                || name.ends_with("_t")
+                || name == "FILE"
+                || i.checked_sub(1).is_some_and(|p| {
+                    matches!(
+                        toks[p].kind,
+                        TokenType::Struct | TokenType::Union | TokenType::Enum
+                    )
+                })
+        }
+        _ => false,
+    }
+}
+
+/// The names the file's typedefs declare
+fn typedef_names(toks: &[Tok]) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    let mut i = 0;
+    while i < toks.len() {
+        if toks[i].kind != TokenType::Typedef {
+            i += 1;
+            continue;
+        }
+        let (mut braces, mut parens) = (0usize, 0usize);
+        while i < toks.len() {
+            match toks[i].text.as_str() {
+                "{" => braces += 1,
+                "}" => braces = braces.saturating_sub(1),
+                "(" => parens += 1,
+                ")" => parens = parens.saturating_sub(1),
+                ";" if braces == 0 => break,
+                _ => {}
+            }
+            let declared = braces == 0
+                && toks[i].is_identifier()
+                && toks
+                    .get(i + 1)
+                    .is_some_and(|next| matches!(next.text.as_str(), ";" | "," | "[" | ")"))
+                && (parens == 0 || (parens == 1 && toks[i - 1].is("*")));
+            if declared {
+                names.insert(toks[i].text.as_str());
+            }
+            i += 1;
+        }
+    }
+    names
+}
+
+/// Move opening braces (and `else` after a closing one) onto or off the
+/// line before, as `style` wants
+fn place_braces(
+    lines: Vec<Line>,
+    toks: &[Tok],
+    analysis: &Analysis,
+    style: BraceStyle,
+) -> Vec<Line> {
+    let block = |index: usize| {
+        (toks[index].is("{") || toks[index].is("}"))
+            && analysis.braces[index].is_some_and(|kind| kind != BraceKind::Aggregate)
+    };
+    let own_line = |index: usize| match style {
+        BraceStyle::Attach => false,
+        BraceStyle::Linux => {
+            toks[index].is("{") && analysis.braces[index] == Some(BraceKind::Function)
+        }
+        BraceStyle::Allman => {
+            block(index)
+                || (toks[index].kind == TokenType::Else && index > 0 && toks[index - 1].is("}"))
+        }
+    };
+    // Allman blocks hold nothing but a trailing comment on the line of their `{`
+    let ends_line =
+        |index: usize| style == BraceStyle::Allman && block(index) && toks[index].is("{");
+
+    let mut out: Vec<Line> = Vec::new();
+    for line in lines {
+        let mut pieces = vec![Line {
+            blank_before: line.blank_before,
+            directive: line.directive,
+            ..Line::default()
+        }];
+        let mut previous = None;
+        for item in line.items {
+            let split = match &item {
+                Item::Token(index) => own_line(*index) || previous.is_some_and(&ends_line),
+                Item::Comment(_) => false,
+            };
+            if split && pieces.last().is_some_and(|piece| !piece.items.is_empty()) {
+                pieces.push(Line::default());
+            }
+            previous = match &item {
+                Item::Token(index) => Some(*index),
+                Item::Comment(_) => None,
+            };
+            pieces.last_mut().unwrap().items.push(item);
+        }
+
+        for piece in pieces {
+            // A block's `{`, or an `else`, joins the line before when that
+            // ends in a token it can follow
+            let previous = out.last().and_then(Line::ends_with_token);
+            let attach = match (piece.items.first(), previous) {
+                (Some(&Item::Token(first)), Some(last)) if !own_line(first) => {
+                    if toks[first].is("{") {
+                        block(first)
+                            && !matches!(toks[last].text.as_str(), ";" | "{" | "}")
+                            && analysis.roles[last] != Role::Label
+                    } else {
+                        toks[first].kind == TokenType::Else && toks[last].is("}")
+                    }
+                }
+                _ => false,
+            };
+            if attach {
+                out.last_mut().unwrap().items.extend(piece.items);
+            } else {
+                out.push(piece);
+            }
+        }
+    }
+    out
+}
+
+fn render(lines: &[Line], toks: &[Tok], analysis: &Analysis, style: &Style) -> String {
+    let indent = |level: usize| {
+        if style.use_tabs {
+            "\t".repeat(level)
+        } else {
+            " ".repeat(level * style.indent_width)
+        }
+    };
+
+    let mut output = String::new();
+    // Open braces, with the indentation of the line each one is on
+    let mut braces: Vec<(BraceKind, usize)> = Vec::new();
+    // Levels owed to the bodies of `if`, `else` and loops without braces
+    let mut pending = 0;
+    let mut continued = false;
+    // The level of the previous line if it ended in a label, for a block after it
+    let mut label_level = None;
+
+    for line in lines {
+        if !output.is_empty() {
+            output.push_str(&"\n".repeat(line.blank_before.min(style.max_blank_lines)));
+        }
+        if let Some(directive) = &line.directive {
+            output.push_str(directive);
+            output.push('\n');
+            continue;
+        }
+
+        let inner = braces.last().map_or(0, |&(_, level)| level + 1);
+        let Some(first) = line.tokens().next() else {
+            // A line of comments is indented like the code after it
+            output.push_str(&indent(inner + pending));
+            output.push_str(&join(line, toks, analysis));
+            output.push('\n');
+            continue;
+        };
+
+        let level = if toks[first].is("}") {
+            braces.last().map_or(0, |&(_, level)| level)
+        } else if block_opener(toks, analysis, first) {
+            label_level.unwrap_or(inner + pending.saturating_sub(1))
+        } else {
+            // Case labels line up with their switch, and other labels stand out
+            let label = (matches!(toks[first].kind, TokenType::Case | TokenType::Default)
+                && braces
+                    .last()
+                    .is_some_and(|&(kind, _)| kind == BraceKind::Switch))
+                || toks
+                    .get(first + 1)
+                    .is_some_and(|_| analysis.roles[first + 1] == Role::Label);
+            let continuation = analysis.paren_depth[first] > 0 || continued;
+            (inner + pending + usize::from(continuation)).saturating_sub(usize::from(label))
+        };
+        output.push_str(&indent(level));
+        output.push_str(&join(line, toks, analysis));
+        output.push('\n');
+
+        for index in line.tokens() {
+            match toks[index].text.as_str() {
+                "{" => {
+                    braces.push((analysis.braces[index].unwrap_or(BraceKind::Block), level));
+                    pending = 0;
+                }
+                "}" => {
+                    braces.pop();
+                    pending = 0;
+                }
+                _ => {}
+            }
+        }
+
+        let last = line.tokens().next_back().unwrap_or(first);
+        let tok = &toks[last];
+        label_level = (analysis.roles[last] == Role::Label).then_some(level);
+        if tok.is(";") && analysis.paren_depth[last] == 0 {
+            pending = 0;
+        } else if analysis.control[last] || matches!(tok.kind, TokenType::Else | TokenType::Do) {
+            pending += 1;
+        }
+        continued = analysis.roles[last] == Role::Binary
+            || (tok.is(",")
+                && analysis.paren_depth[last] == 0
+                && !matches!(
+                    braces.last(),
+                    Some((BraceKind::Aggregate | BraceKind::Enum, _))
+                ));
+    }
+    output
+}
+
+/// Whether the token is a `{` that starts a block of statements or declarations
+fn block_opener(toks: &[Tok], analysis: &Analysis, index: usize) -> bool {
+    toks[index].is("{") && analysis.braces[index] != Some(BraceKind::Aggregate)
+}
+
+/// The items of a line with the spacing between them
+fn join(line: &Line, toks: &[Tok], analysis: &Analysis) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&Item> = None;
+    for item in &line.items {
+        let space = match (previous, item) {
+            (None, _) => false,
+            (Some(Item::Token(a)), Item::Token(b)) => space_between(toks, analysis, *a, *b),
+            _ => true,
+        };
+        if space {
+            text.push(' ');
+        }
+        match item {
+            Item::Token(index) => text.push_str(&toks[*index].text),
+            Item::Comment(comment) => text.push_str(comment),
+        }
+        previous = Some(item);
+    }
+    text
+}
+
+fn space_between(toks: &[Tok], analysis: &Analysis, a: usize, b: usize) -> bool {
+    let (left, right) = (&toks[a], &toks[b]);
+    let (left_role, right_role) = (analysis.roles[a], analysis.roles[b]);
+    let aggregate = |index: usize| analysis.braces[index] == Some(BraceKind::Aggregate);
+    let called = (left.is_identifier()
+        || matches!(
+            left.kind,
+            TokenType::Sizeof
+                | TokenType::Alignof
+                | TokenType::Alignas
+                | TokenType::Generic
+                | TokenType::StaticAssert
+        ))
+        || left.is(")")
+        || left.is("]");
+
+    if matches!(right.text.as_str(), ")" | "]" | "," | ";")
+        || right_role == Role::Postfix
+        || right_role == Role::Label
+        || matches!(left_role, Role::Prefix | Role::CastClose)
+        || left.is("(")
+        || left.is("[")
+        || left.is(".")
+        || left.is("->")
+    {
+        return false;
+    }
+    if left.is("{") {
+        return !aggregate(a) && !right.is("}");
+    }
+    if right.is("}") {
+        return !aggregate(b);
+    }
+    if right.is(".") || right.is("->") {
+        return !(left.is_word() || left.is(")") || left.is("]"));
+    }
+    if left_role == Role::Binary || right_role == Role::Binary {
+        return true;
+    }
+    if right.is("(") {
+        return !called;
+    }
+    if right.is("[") {
+        return !(left.is_word() || left.is(")") || left.is("]"));
+    }
+    true
+}
@@ -0,0 +1,141 @@
+use crate::parser::Type;
+
+/// Which family a format string belongs to: `printf` takes values, `scanf` pointers to store through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatStyle {
+    Printf,
+    Scanf,
+}
+
+/// The position of the format string among the arguments of a standard
+/// function that takes one, and its style
+pub fn format_function(name: &str) -> Option<(usize, FormatStyle)> {
+    Some(match name {
+        "printf" => (0, FormatStyle::Printf),
+        "fprintf" | "sprintf" | "dprintf" => (1, FormatStyle::Printf),
+        "snprintf" => (2, FormatStyle::Printf),
+        "scanf" => (0, FormatStyle::Scanf),
+        "fscanf" | "sscanf" => (1, FormatStyle::Scanf),
+        _ => return None,
+    })
+}
+
+/// One argument a format string consumes
+#[derive(Debug, Clone)]
+pub struct Directive {
+    /// The conversion specification as written, e.g. `%-5ld`
+    pub text: String,
+    /// Type of the argument after default promotions; `None` when any type the
+    /// compiler knows of could be right (e.g. `long double`)
+    pub argument: Option<Type>,
+}
+
+/// The arguments a format string consumes, in order, or a description of the
+/// first malformed specification
+pub fn directives(format: &str, style: FormatStyle) -> Result<Vec<Directive>, String> {
+    let mut directives = Vec::new();
+    let mut chars = format.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        let mut stars = 0;
+        let mut suppressed = false;
+        let mut length = String::new();
+        let conversion = loop {
+            let Some((_, c)) = chars.next() else {
+                return Err("spurious trailing '%' in format".to_string());
+            };
+            match c {
+                '*' if style == FormatStyle::Scanf => suppressed = true,
+                '*' => stars += 1,
+                '-' | '+' | ' ' | '#' | '0'..='9' | '.' | '\'' => {}
+                'h' | 'l' | 'j' | 'z' | 't' | 'L' | 'q' => length.push(c),
+                c => break c,
+            }
+        };
+        let end = chars.peek().map_or(format.len(), |&(i, _)| i);
+        let text = &format[start..end];
+        if conversion == '%' {
+            continue;
+        }
+        if conversion == '[' {
+            // Skip the scanset: a leading `]` (after an optional `^`) belongs to it
+            if matches!(chars.peek(), Some((_, '^'))) {
+                chars.next();
+            }
+            if matches!(chars.peek(), Some((_, ']'))) {
+                chars.next();
+            }
+            chars.by_ref().find(|&(_, c)| c == ']');
+        }
+
+        // `*` takes the field width or precision from an int argument
+        for _ in 0..stars {
+            directives.push(Directive {
+                text: text.to_string(),
+                argument: Some(Type::Int),
+            });
+        }
+        let value = value_type(conversion, &length, style).ok_or_else(|| {
+            format!(
+                "unknown conversion type character '{}' in format",
+                conversion
+            )
+        })?;
+        if suppressed {
+            continue;
+        }
+        let argument = match style {
+            FormatStyle::Printf => value,
+            FormatStyle::Scanf => value.map(|ty| Type::Pointer(Box::new(ty))),
+        };
+        directives.push(Directive {
+            text: text.to_string(),
+            argument,
+        });
+    }
+
+    Ok(directives)
+}
+
+/// Type a conversion prints, or for `scanf` stores; `Some(None)` when unchecked
+fn value_type(conversion: char, length: &str, style: FormatStyle) -> Option<Option<Type>> {
+    // The integer type stored through a pointer; printf arguments are promoted
+    let stored = match length {
+        "hh" => Type::Char,
+        "h" => Type::Short,
+        "" => Type::Int,
+        "ll" | "q" => Type::LongLong,
+        _ => Type::Long,
+    };
+    let ty = match conversion {
+        'd' | 'i' => match (style, stored) {
+            (FormatStyle::Printf, Type::Char | Type::Short) => Type::Int,
+            (_, stored) => stored,
+        },
+        // The unsigned type of the same rank; it promotes to int in a printf call
+        'u' | 'o' | 'x' | 'X' => match (style, stored) {
+            (FormatStyle::Printf, Type::Char | Type::Short) => Type::Int,
+            (_, Type::Char) => Type::UnsignedChar,
+            (_, Type::Short) => Type::UnsignedShort,
+            (_, Type::Int) => Type::UnsignedInt,
+            (_, Type::LongLong) => Type::UnsignedLongLong,
+            _ => Type::UnsignedLong,
+        },
+        'n' if style == FormatStyle::Printf => Type::Pointer(Box::new(stored)),
+        'n' => stored,
+        'c' if style == FormatStyle::Printf => Type::Int,
+        'c' | 's' | '[' if style == FormatStyle::Scanf => Type::Char,
+        's' => Type::Pointer(Box::new(Type::Char)),
+        'p' => Type::Pointer(Box::new(Type::Void)),
+        'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => match (length, style) {
+            ("L", _) => return Some(None),
+            ("", FormatStyle::Scanf) => Type::Float,
+            _ => Type::Double,
+        },
+        _ => return None,
+    };
+    Some(Some(ty))
+}
@@ -0,0 +1,161 @@
+use crate::error::{AleccError, Result};
+use crate::layout::TypeLayout;
+use crate::parser::{Statement, StatementKind, Type};
+use crate::targets::Target;
+
+/// Stack slots of one function's locals, planned before its body is generated.
+///
+/// Locals are laid out scope by scope below the parameter slots: a nested scope
+/// starts where the locals of its enclosing scope end, and sibling scopes start
+/// at the same place, since at most one of them is live at a time.
+#[derive(Debug, Clone)]
+pub struct FrameLayout {
+    /// Frame-pointer-relative offset of every declaration, in the order the body declares them
+    offsets: Vec<i32>,
+    /// Bytes below the frame pointer taken by parameters and the deepest nest of live locals
+    size: usize,
+}
+
+impl FrameLayout {
+    /// Plan the locals of `body` below `parameter_bytes` of parameter slots
+    pub fn plan(target: Target, parameter_bytes: usize, body: &Statement) -> Result<Self> {
+        let mut planner = Planner {
+            target,
+            layout: TypeLayout::new(target),
+            offsets: Vec::new(),
+            deepest: parameter_bytes,
+        };
+        planner.plan_statement(body, parameter_bytes)?;
+        Ok(Self {
+            offsets: planner.offsets,
+            size: planner.deepest,
+        })
+    }
+
+    /// Offset of the `index`th declaration of the body
+    pub fn offset(&self, index: usize) -> Option<i32> {
+        self.offsets.get(index).copied()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Alignment the frame pointer is known to have, and so the strictest one a slot can get.
+    /// i386 code can be entered with a stack aligned to only 4 bytes.
+    pub fn frame_alignment(target: Target) -> usize {
+        match target {
+            Target::I386 => 4,
+            Target::Amd64 | Target::Arm64 => target.stack_alignment(),
+        }
+    }
+}
+
+struct Planner {
+    target: Target,
+    layout: TypeLayout,
+    offsets: Vec<i32>,
+    deepest: usize,
+}
+
+impl Planner {
+    /// Assign slots to the declarations in `statement`, whose scope has `top` bytes in
+    /// use; returns the bytes in use by that scope afterwards
+    fn plan_statement(&mut self, statement: &Statement, top: usize) -> Result<usize> {
+        match &statement.kind {
+            StatementKind::Declaration {
+                name,
+                var_type,
+                alignment,
+                ..
+            } => {
+                let (size, natural) = self.slot_size_and_alignment(var_type);
+                let frame_alignment = FrameLayout::frame_alignment(self.target);
+                let align = match *alignment {
+                    Some(alignment) if alignment > frame_alignment => {
+                        return Err(AleccError::CodegenError {
+                            message: format!(
+                                "_Alignas({}) on local variable '{}' exceeds the {}-byte frame alignment",
+                                alignment, name, frame_alignment
+                            ),
+                            span: Some(statement.span),
+                        });
+                    }
+                    Some(alignment) => alignment.max(natural),
+                    None => natural,
+                }
+                .min(frame_alignment);
+
+                // The slot spans [fp - top, fp - top + size), so `top` is what gets aligned
+                let top = (top + size).next_multiple_of(align);
+                self.offsets.push(-(top as i32));
+                self.deepest = self.deepest.max(top);
+                Ok(top)
+            }
+            StatementKind::Block(statements) => {
+                let mut inner = top;
+                for statement in statements {
+                    inner = self.plan_statement(statement, inner)?;
+                }
+                Ok(top)
+            }
+            StatementKind::If {
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                self.plan_statement(then_stmt, top)?;
+                if let Some(else_stmt) = else_stmt {
+                    self.plan_statement(else_stmt, top)?;
+                }
+                Ok(top)
+            }
+            StatementKind::While { body, .. } | StatementKind::DoWhile { body, .. } => {
+                self.plan_statement(body, top)?;
+                Ok(top)
+            }
+            StatementKind::For { init, body, .. } => {
+                let inner = match init {
+                    Some(init) => self.plan_statement(init, top)?,
+                    None => top,
+                };
+                self.plan_statement(body, inner)?;
+                Ok(top)
+            }
+            // All the cases are one block
+            StatementKind::Switch { cases, .. } => {
+                let mut inner = top;
+                for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                    inner = self.plan_statement(statement, inner)?;
+                }
+                Ok(top)
+            }
+            _ => Ok(top),
+        }
+    }
+
+    /// The backend writes scalar variables a whole register at a time, so no
+    /// slot is smaller than a word; array elements take just their own size
+    fn slot_size_and_alignment(&self, var_type: &Type) -> (usize, usize) {
+        let word = self.target.pointer_size();
+        match self.layout.resolve(var_type) {
+            Type::Array(element, length) => {
+                let size = self.layout.size_of(&element) * length.unwrap_or(10);
+                let align = self.layout.align_of(&element);
+                (size.next_multiple_of(word).max(word), align.max(word))
+            }
+            ty => {
+                let size = self.layout.size_of(&ty).next_multiple_of(word);
+                // Scalars like double are aligned to their size even where the
+                // ABI relaxes that inside structs
+                let align = match ty {
+                    Type::Struct { .. } | Type::Union { .. } | Type::VaList => {
+                        self.layout.align_of(&ty)
+                    }
+                    _ => self.layout.size_of(&ty),
+                };
+                (size.max(word), align.max(word))
+            }
+        }
+    }
+}
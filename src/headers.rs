@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+/// The headers compiled into alecc, from the repository's `include/`
+/// directory. An `#include` finds them after the `-I` directories and before
+/// the system's, so programs using only these build without any libc headers.
+const BUILTIN_HEADERS: &[(&str, &str)] = &[
+    ("limits.h", include_str!("../include/limits.h")),
+    ("stdarg.h", include_str!("../include/stdarg.h")),
+    ("stdbool.h", include_str!("../include/stdbool.h")),
+    ("stddef.h", include_str!("../include/stddef.h")),
+    ("stdint.h", include_str!("../include/stdint.h")),
+];
+
+/// The text of the builtin header `name`, if alecc ships one
+pub fn builtin_header(name: &str) -> Option<&'static str> {
+    BUILTIN_HEADERS
+        .iter()
+        .find(|(header, _)| *header == name)
+        .map(|(_, text)| *text)
+}
+
+/// Apply a builtin header's `#define`s to `defines` and return the rest of
+/// its text. Besides `#define`, the headers only use `#ifdef`, `#ifndef`,
+/// `#else` and `#endif`, which are evaluated here.
+pub fn header_declarations(header: &str, defines: &mut HashMap<String, String>) -> String {
+    let mut declarations = String::new();
+    // Whether each enclosing conditional's current branch is taken
+    let mut taken: Vec<bool> = Vec::new();
+    for line in header.lines() {
+        let trimmed = line.trim();
+        let enclosing = taken.iter().all(|&branch| branch);
+        let Some(directive) = trimmed.strip_prefix('#') else {
+            if enclosing {
+                declarations.push_str(line);
+                declarations.push('\n');
+            }
+            continue;
+        };
+        let mut words = directive.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("ifdef"), Some(name)) => taken.push(defines.contains_key(name)),
+            (Some("ifndef"), Some(name)) => taken.push(!defines.contains_key(name)),
+            (Some("else"), _) => {
+                if let Some(branch) = taken.last_mut() {
+                    *branch = !*branch;
+                }
+            }
+            (Some("endif"), _) => {
+                taken.pop();
+            }
+            (Some("define"), Some(name)) if enclosing => {
+                let value = words.collect::<Vec<_>>().join(" ");
+                let value = if value.is_empty() {
+                    "1".to_string()
+                } else {
+                    value
+                };
+                defines.insert(name.to_string(), value);
+            }
+            _ => {}
+        }
+    }
+    declarations
+}
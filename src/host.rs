@@ -0,0 +1,89 @@
+use crate::targets::Target;
+use std::path::{Path, PathBuf};
+
+/// The platform alecc itself runs on, as opposed to the one it compiles for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Host {
+    Linux,
+    MacOs,
+    Windows,
+    Other,
+}
+
+impl Host {
+    pub fn current() -> Self {
+        if cfg!(target_os = "linux") {
+            Host::Linux
+        } else if cfg!(target_os = "macos") {
+            Host::MacOs
+        } else if cfg!(windows) {
+            Host::Windows
+        } else {
+            Host::Other
+        }
+    }
+
+    /// Whether `/usr/include` and `/usr/lib` hold the target's headers and
+    /// libraries, so they can be searched without a `--sysroot`
+    pub fn has_target_system_dirs(&self) -> bool {
+        *self == Host::Linux
+    }
+
+    /// Names to try for a binutils `tool` (`as`, `ld`, `ar`) that handles
+    /// `target`, most preferred first
+    pub fn tool_candidates(&self, tool: &str, target: Target) -> Vec<String> {
+        let cross = format!("{}-{}", target.gnu_prefix(), tool);
+        let native_arch = match target {
+            Target::I386 | Target::Amd64 => {
+                cfg!(any(target_arch = "x86", target_arch = "x86_64"))
+            }
+            Target::Arm64 => cfg!(target_arch = "aarch64"),
+        };
+
+        let mut candidates = Vec::new();
+        if *self == Host::Linux && native_arch {
+            // The host's own binutils, which take --32/--64 for either x86 flavour
+            candidates.push(tool.to_string());
+            candidates.push(cross);
+        } else {
+            // Anywhere else the unprefixed tools produce Mach-O or PE objects
+            candidates.push(cross);
+        }
+        // LLVM's drop-ins handle every target from any host
+        match tool {
+            "ld" => candidates.push("ld.lld".to_string()),
+            "ar" => candidates.push("llvm-ar".to_string()),
+            _ => {}
+        }
+        candidates
+    }
+}
+
+/// Locate the first of `candidates` in `toolchain`, then on `PATH`. When none
+/// is found the first candidate is returned, so the failure to run it names
+/// the preferred tool.
+pub fn find_tool(candidates: &[String], toolchain: Option<&Path>) -> String {
+    if let Some(dir) = toolchain {
+        for name in candidates {
+            let path = dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+            if path.is_file() {
+                return path.to_string_lossy().to_string();
+            }
+        }
+    }
+
+    candidates
+        .iter()
+        .find(|name| which::which(name.as_str()).is_ok())
+        .or_else(|| candidates.first())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// `dir` as seen from the target filesystem rooted at `sysroot`
+pub fn under_sysroot(sysroot: Option<&Path>, dir: &str) -> PathBuf {
+    match sysroot {
+        Some(root) => root.join(dir.trim_start_matches('/')),
+        None => PathBuf::from(dir),
+    }
+}
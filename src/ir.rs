@@ -0,0 +1,887 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Width of a value in a virtual register or in memory. Pointers are a
+/// target word wide: `I32` on i386, `I64` elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrType {
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+impl IrType {
+    pub fn size(self) -> usize {
+        match self {
+            IrType::I8 => 1,
+            IrType::I16 => 2,
+            IrType::I32 => 4,
+            IrType::I64 => 8,
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        self.size() as u32 * 8
+    }
+
+    /// The low bits of `value` that make up this type, sign- or zero-extended
+    /// back to 64 bits
+    pub fn wrap(self, value: i64, signed: bool) -> i64 {
+        let shift = 64 - self.bits();
+        if signed {
+            value.wrapping_shl(shift).wrapping_shr(shift)
+        } else {
+            ((value as u64).wrapping_shl(shift).wrapping_shr(shift)) as i64
+        }
+    }
+}
+
+impl fmt::Display for IrType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "i{}", self.bits())
+    }
+}
+
+/// A virtual register; each one is assigned by exactly one instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VReg(pub usize);
+
+impl fmt::Display for VReg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId(pub usize);
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+/// A stack slot holding a local variable or a temporary whose address is taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlotId(pub usize);
+
+impl fmt::Display for SlotId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Reg(VReg),
+    Imm(i64),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => reg.fmt(f),
+            Operand::Imm(value) => value.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+    SRem,
+    URem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    LShr,
+    AShr,
+}
+
+impl BinaryOp {
+    fn name(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::SDiv => "sdiv",
+            BinaryOp::UDiv => "udiv",
+            BinaryOp::SRem => "srem",
+            BinaryOp::URem => "urem",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+            BinaryOp::Xor => "xor",
+            BinaryOp::Shl => "shl",
+            BinaryOp::LShr => "lshr",
+            BinaryOp::AShr => "ashr",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    /// Bitwise complement
+    Not,
+}
+
+/// How `Compare` relates its operands; the `U` forms compare them as unsigned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Eq,
+    Ne,
+    SLt,
+    SLe,
+    SGt,
+    SGe,
+    ULt,
+    ULe,
+    UGt,
+    UGe,
+}
+
+impl Condition {
+    fn name(self) -> &'static str {
+        match self {
+            Condition::Eq => "eq",
+            Condition::Ne => "ne",
+            Condition::SLt => "slt",
+            Condition::SLe => "sle",
+            Condition::SGt => "sgt",
+            Condition::SGe => "sge",
+            Condition::ULt => "ult",
+            Condition::ULe => "ule",
+            Condition::UGt => "ugt",
+            Condition::UGe => "uge",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Callee {
+    Direct(String),
+    /// Through a function pointer
+    Indirect(Operand),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Binary {
+        dest: VReg,
+        op: BinaryOp,
+        ty: IrType,
+        left: Operand,
+        right: Operand,
+    },
+    Unary {
+        dest: VReg,
+        op: UnaryOp,
+        ty: IrType,
+        operand: Operand,
+    },
+    /// An `I32` that is 1 when `left condition right` holds and 0 otherwise
+    Compare {
+        dest: VReg,
+        condition: Condition,
+        ty: IrType,
+        left: Operand,
+        right: Operand,
+    },
+    /// Truncate `value` to a narrower type, or sign- or zero-extend it to a wider one
+    Convert {
+        dest: VReg,
+        from: IrType,
+        to: IrType,
+        signed: bool,
+        value: Operand,
+    },
+    Copy {
+        dest: VReg,
+        ty: IrType,
+        value: Operand,
+    },
+    SlotAddress {
+        dest: VReg,
+        slot: SlotId,
+    },
+    /// The address of a global variable or string literal
+    SymbolAddress {
+        dest: VReg,
+        symbol: String,
+    },
+    FunctionAddress {
+        dest: VReg,
+        function: String,
+    },
+    Load {
+        dest: VReg,
+        ty: IrType,
+        address: Operand,
+        volatile: bool,
+    },
+    Store {
+        ty: IrType,
+        value: Operand,
+        address: Operand,
+        volatile: bool,
+    },
+    /// Arguments are passed a whole word wide; `dest` gets the low bits of the result
+    Call {
+        dest: Option<(VReg, IrType)>,
+        callee: Callee,
+        arguments: Vec<(IrType, Operand)>,
+        variadic: bool,
+    },
+}
+
+impl Instruction {
+    /// The register the instruction assigns, if any
+    pub fn dest(&self) -> Option<VReg> {
+        match self {
+            Instruction::Binary { dest, .. }
+            | Instruction::Unary { dest, .. }
+            | Instruction::Compare { dest, .. }
+            | Instruction::Convert { dest, .. }
+            | Instruction::Copy { dest, .. }
+            | Instruction::SlotAddress { dest, .. }
+            | Instruction::SymbolAddress { dest, .. }
+            | Instruction::FunctionAddress { dest, .. }
+            | Instruction::Load { dest, .. } => Some(*dest),
+            Instruction::Call { dest, .. } => dest.map(|(dest, _)| dest),
+            Instruction::Store { .. } => None,
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Operand> {
+        let mut operands = Vec::new();
+        self.visit_operands(|operand| operands.push(*operand));
+        operands
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Binary { left, right, .. } | Instruction::Compare { left, right, .. } => {
+                vec![left, right]
+            }
+            Instruction::Unary { operand, .. } => vec![operand],
+            Instruction::Convert { value, .. } | Instruction::Copy { value, .. } => vec![value],
+            Instruction::Load { address, .. } => vec![address],
+            Instruction::Store { value, address, .. } => vec![value, address],
+            Instruction::Call {
+                callee, arguments, ..
+            } => {
+                let mut operands: Vec<&mut Operand> =
+                    arguments.iter_mut().map(|(_, argument)| argument).collect();
+                if let Callee::Indirect(target) = callee {
+                    operands.push(target);
+                }
+                operands
+            }
+            Instruction::SlotAddress { .. }
+            | Instruction::SymbolAddress { .. }
+            | Instruction::FunctionAddress { .. } => Vec::new(),
+        }
+    }
+
+    fn visit_operands(&self, mut visit: impl FnMut(&Operand)) {
+        match self {
+            Instruction::Binary { left, right, .. } | Instruction::Compare { left, right, .. } => {
+                visit(left);
+                visit(right);
+            }
+            Instruction::Unary { operand, .. } => visit(operand),
+            Instruction::Convert { value, .. } | Instruction::Copy { value, .. } => visit(value),
+            Instruction::Load { address, .. } => visit(address),
+            Instruction::Store { value, address, .. } => {
+                visit(value);
+                visit(address);
+            }
+            Instruction::Call {
+                callee, arguments, ..
+            } => {
+                arguments.iter().for_each(|(_, argument)| visit(argument));
+                if let Callee::Indirect(target) = callee {
+                    visit(target);
+                }
+            }
+            Instruction::SlotAddress { .. }
+            | Instruction::SymbolAddress { .. }
+            | Instruction::FunctionAddress { .. } => {}
+        }
+    }
+
+    /// Whether removing the instruction could change what the program does
+    /// even when nothing uses its result
+    pub fn has_side_effects(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Store { .. }
+                | Instruction::Call { .. }
+                | Instruction::Load { volatile: true, .. }
+        )
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let volatile = |volatile: bool| if volatile { "volatile " } else { "" };
+        match self {
+            Instruction::Binary {
+                dest,
+                op,
+                ty,
+                left,
+                right,
+            } => write!(f, "{} = {} {} {}, {}", dest, op.name(), ty, left, right),
+            Instruction::Unary {
+                dest,
+                op,
+                ty,
+                operand,
+            } => {
+                let name = match op {
+                    UnaryOp::Neg => "neg",
+                    UnaryOp::Not => "not",
+                };
+                write!(f, "{} = {} {} {}", dest, name, ty, operand)
+            }
+            Instruction::Compare {
+                dest,
+                condition,
+                ty,
+                left,
+                right,
+            } => write!(
+                f,
+                "{} = cmp {} {} {}, {}",
+                dest,
+                condition.name(),
+                ty,
+                left,
+                right
+            ),
+            Instruction::Convert {
+                dest,
+                from,
+                to,
+                signed,
+                value,
+            } => {
+                let name = match (to.size() < from.size(), signed) {
+                    (true, _) => "trunc",
+                    (false, true) => "sext",
+                    (false, false) => "zext",
+                };
+                write!(f, "{} = {} {} {} to {}", dest, name, from, value, to)
+            }
+            Instruction::Copy { dest, ty, value } => write!(f, "{} = copy {} {}", dest, ty, value),
+            Instruction::SlotAddress { dest, slot } => write!(f, "{} = slot {}", dest, slot),
+            Instruction::SymbolAddress { dest, symbol } => {
+                write!(f, "{} = symbol @{}", dest, symbol)
+            }
+            Instruction::FunctionAddress { dest, function } => {
+                write!(f, "{} = function @{}", dest, function)
+            }
+            Instruction::Load {
+                dest,
+                ty,
+                address,
+                volatile: is_volatile,
+            } => write!(
+                f,
+                "{} = load {}{}, {}",
+                dest,
+                volatile(*is_volatile),
+                ty,
+                address
+            ),
+            Instruction::Store {
+                ty,
+                value,
+                address,
+                volatile: is_volatile,
+            } => write!(
+                f,
+                "store {}{} {}, {}",
+                volatile(*is_volatile),
+                ty,
+                value,
+                address
+            ),
+            Instruction::Call {
+                dest,
+                callee,
+                arguments,
+                variadic,
+            } => {
+                match dest {
+                    Some((dest, ty)) => write!(f, "{} = call {} ", dest, ty)?,
+                    None => write!(f, "call void ")?,
+                }
+                match callee {
+                    Callee::Direct(name) => write!(f, "@{}(", name)?,
+                    Callee::Indirect(target) => write!(f, "{}(", target)?,
+                }
+                for (index, (ty, argument)) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} {}", ty, argument)?;
+                }
+                if *variadic {
+                    write!(f, "{}...", if arguments.is_empty() { "" } else { ", " })?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// How control leaves a basic block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// To `if_true` when `condition`, of type `ty`, isn't zero
+    Branch {
+        condition: Operand,
+        ty: IrType,
+        if_true: BlockId,
+        if_false: BlockId,
+    },
+    /// Return values are a whole word wide
+    Return(Option<(IrType, Operand)>),
+    /// Control never gets here, e.g. after a call to a `noreturn` function
+    Unreachable,
+    /// Stop the program, e.g. at the end of a function under `--fmissing-return=trap`
+    Trap,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                if_true, if_false, ..
+            } => vec![*if_true, *if_false],
+            Terminator::Return(_) | Terminator::Unreachable | Terminator::Trap => Vec::new(),
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Operand> {
+        match self {
+            Terminator::Branch { condition, .. } => vec![*condition],
+            Terminator::Return(Some((_, value))) => vec![*value],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Terminator::Branch { condition, .. } => vec![condition],
+            Terminator::Return(Some((_, value))) => vec![value],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jmp {}", target),
+            Terminator::Branch {
+                condition,
+                ty,
+                if_true,
+                if_false,
+            } => write!(f, "br {} {}, {}, {}", ty, condition, if_true, if_false),
+            Terminator::Return(Some((ty, value))) => write!(f, "ret {} {}", ty, value),
+            Terminator::Return(None) => write!(f, "ret void"),
+            Terminator::Unreachable => write!(f, "unreachable"),
+            Terminator::Trap => write!(f, "trap"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub id: BlockId,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub size: usize,
+    pub align: usize,
+}
+
+/// A function in SSA form. Variables live in stack slots, so registers are
+/// only ever assigned once and no phi nodes are needed. The first block is
+/// the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrFunction {
+    pub name: String,
+    pub parameters: Vec<(VReg, IrType)>,
+    /// `None` for a function returning `void`
+    pub return_type: Option<IrType>,
+    pub slots: Vec<Slot>,
+    pub blocks: Vec<BasicBlock>,
+    /// The type of every virtual register, by number
+    pub types: Vec<IrType>,
+}
+
+impl IrFunction {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            parameters: Vec::new(),
+            return_type: None,
+            slots: Vec::new(),
+            blocks: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    pub fn new_vreg(&mut self, ty: IrType) -> VReg {
+        self.types.push(ty);
+        VReg(self.types.len() - 1)
+    }
+
+    pub fn new_slot(&mut self, size: usize, align: usize) -> SlotId {
+        self.slots.push(Slot { size, align });
+        SlotId(self.slots.len() - 1)
+    }
+
+    pub fn block(&self, id: BlockId) -> Option<&BasicBlock> {
+        self.blocks.iter().find(|block| block.id == id)
+    }
+
+    /// Instructions and terminators, as `--summary` counts them
+    pub fn instruction_count(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| block.instructions.len() + 1)
+            .sum()
+    }
+}
+
+impl fmt::Display for IrFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let return_type = self
+            .return_type
+            .map_or("void".to_string(), |ty| ty.to_string());
+        write!(f, "function {} @{}(", return_type, self.name)?;
+        for (index, (reg, ty)) in self.parameters.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {}", ty, reg)?;
+        }
+        writeln!(f, ") {{")?;
+        for (index, slot) in self.slots.iter().enumerate() {
+            writeln!(
+                f,
+                "  {} = slot {}, align {}",
+                SlotId(index),
+                slot.size,
+                slot.align
+            )?;
+        }
+        for block in &self.blocks {
+            writeln!(f, "{}:", block.id)?;
+            for instruction in &block.instructions {
+                writeln!(f, "  {}", instruction)?;
+            }
+            writeln!(f, "  {}", block.terminator)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// The IR of a translation unit
+#[derive(Debug, Clone, Default)]
+pub struct IrModule {
+    pub functions: Vec<IrFunction>,
+    /// String literals by label, in label order
+    pub strings: Vec<(String, String)>,
+    /// Functions that could not be lowered, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+impl fmt::Display for IrModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (label, content) in &self.strings {
+            writeln!(f, "@{} = string {:?}", label, content)?;
+        }
+        // A blank line before each function, but none at the top
+        let mut separate = !self.strings.is_empty();
+        for function in &self.functions {
+            if separate {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+            separate = true;
+        }
+        if separate && !self.skipped.is_empty() {
+            writeln!(f)?;
+        }
+        for (name, reason) in &self.skipped {
+            writeln!(f, "; @{} is not in IR: {}", name, reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run the IR optimizations until none of them changes anything: constant
+/// and copy propagation with folding, branches on constants turned into
+/// jumps, and removal of unreachable blocks and of unused instructions
+pub fn optimize(function: &mut IrFunction) {
+    loop {
+        let mut changed = propagate_constants(function);
+        changed |= simplify_branches(function);
+        changed |= remove_unreachable_blocks(function);
+        changed |= eliminate_dead_code(function);
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// Replace uses of registers that only copy a constant or another register,
+/// and fold instructions whose operands are all constants
+fn propagate_constants(function: &mut IrFunction) -> bool {
+    let mut changed = false;
+    loop {
+        let mut values: HashMap<VReg, Operand> = HashMap::new();
+        for block in &mut function.blocks {
+            for instruction in &mut block.instructions {
+                if let Some(folded) = fold(instruction) {
+                    let dest = instruction
+                        .dest()
+                        .expect("folded instructions assign a register");
+                    let ty = function.types[dest.0];
+                    *instruction = Instruction::Copy {
+                        dest,
+                        ty,
+                        value: Operand::Imm(folded),
+                    };
+                }
+                if let Instruction::Copy { dest, value, .. } = instruction {
+                    values.insert(*dest, *value);
+                }
+            }
+        }
+        if values.is_empty() {
+            return changed;
+        }
+
+        let resolve = |operand: &mut Operand| -> bool {
+            let mut replaced = false;
+            while let Operand::Reg(reg) = operand {
+                match values.get(reg) {
+                    Some(value) if value != operand => {
+                        *operand = *value;
+                        replaced = true;
+                    }
+                    _ => break,
+                }
+            }
+            replaced
+        };
+        let mut replaced = false;
+        for block in &mut function.blocks {
+            for instruction in &mut block.instructions {
+                // A copy stays as the definition of its register until it is unused
+                if matches!(instruction, Instruction::Copy { .. }) {
+                    continue;
+                }
+                for operand in instruction.operands_mut() {
+                    replaced |= resolve(operand);
+                }
+            }
+            for operand in block.terminator.operands_mut() {
+                replaced |= resolve(operand);
+            }
+        }
+        if !replaced {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// The value of an instruction whose operands are all constants
+fn fold(instruction: &Instruction) -> Option<i64> {
+    match *instruction {
+        Instruction::Binary {
+            op,
+            ty,
+            left: Operand::Imm(left),
+            right: Operand::Imm(right),
+            ..
+        } => fold_binary(op, ty, left, right),
+        Instruction::Unary {
+            op,
+            ty,
+            operand: Operand::Imm(value),
+            ..
+        } => Some(ty.wrap(
+            match op {
+                UnaryOp::Neg => value.wrapping_neg(),
+                UnaryOp::Not => !value,
+            },
+            true,
+        )),
+        Instruction::Compare {
+            condition,
+            ty,
+            left: Operand::Imm(left),
+            right: Operand::Imm(right),
+            ..
+        } => Some(fold_compare(condition, ty, left, right) as i64),
+        Instruction::Convert {
+            from,
+            to,
+            signed,
+            value: Operand::Imm(value),
+            ..
+        } => Some(fold_convert(from, to, signed, value)),
+        _ => None,
+    }
+}
+
+/// `value` of type `from` converted to `to`
+pub fn fold_convert(from: IrType, to: IrType, signed: bool, value: i64) -> i64 {
+    to.wrap(from.wrap(value, signed), true)
+}
+
+/// `left op right` at the width of `ty`; `None` when it has no defined
+/// value, such as a division by zero
+pub fn fold_binary(op: BinaryOp, ty: IrType, left: i64, right: i64) -> Option<i64> {
+    let (a, b) = (ty.wrap(left, true), ty.wrap(right, true));
+    let (ua, ub) = (ty.wrap(left, false) as u64, ty.wrap(right, false) as u64);
+    let value = match op {
+        BinaryOp::Add => a.wrapping_add(b),
+        BinaryOp::Sub => a.wrapping_sub(b),
+        BinaryOp::Mul => a.wrapping_mul(b),
+        BinaryOp::SDiv | BinaryOp::UDiv | BinaryOp::SRem | BinaryOp::URem if b == 0 => return None,
+        BinaryOp::SDiv => a.wrapping_div(b),
+        BinaryOp::UDiv => (ua / ub) as i64,
+        BinaryOp::SRem => a.wrapping_rem(b),
+        BinaryOp::URem => (ua % ub) as i64,
+        BinaryOp::And => a & b,
+        BinaryOp::Or => a | b,
+        BinaryOp::Xor => a ^ b,
+        BinaryOp::Shl | BinaryOp::LShr | BinaryOp::AShr if !(0..ty.bits() as i64).contains(&b) => {
+            return None
+        }
+        BinaryOp::Shl => a.wrapping_shl(b as u32),
+        BinaryOp::LShr => (ua >> b) as i64,
+        BinaryOp::AShr => a >> b,
+    };
+    Some(ty.wrap(value, true))
+}
+
+fn fold_compare(condition: Condition, ty: IrType, left: i64, right: i64) -> bool {
+    let (a, b) = (ty.wrap(left, true), ty.wrap(right, true));
+    let (ua, ub) = (ty.wrap(left, false) as u64, ty.wrap(right, false) as u64);
+    match condition {
+        Condition::Eq => a == b,
+        Condition::Ne => a != b,
+        Condition::SLt => a < b,
+        Condition::SLe => a <= b,
+        Condition::SGt => a > b,
+        Condition::SGe => a >= b,
+        Condition::ULt => ua < ub,
+        Condition::ULe => ua <= ub,
+        Condition::UGt => ua > ub,
+        Condition::UGe => ua >= ub,
+    }
+}
+
+/// Turn branches whose condition is known, or whose targets are the same, into jumps
+fn simplify_branches(function: &mut IrFunction) -> bool {
+    let mut changed = false;
+    for block in &mut function.blocks {
+        if let Terminator::Branch {
+            condition,
+            ty,
+            if_true,
+            if_false,
+        } = block.terminator
+        {
+            let target = match condition {
+                Operand::Imm(value) if ty.wrap(value, true) != 0 => if_true,
+                Operand::Imm(_) => if_false,
+                Operand::Reg(_) if if_true == if_false => if_true,
+                Operand::Reg(_) => continue,
+            };
+            block.terminator = Terminator::Jump(target);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Drop the blocks no path from the entry reaches
+pub fn remove_unreachable_blocks(function: &mut IrFunction) -> bool {
+    let Some(entry) = function.blocks.first().map(|block| block.id) else {
+        return false;
+    };
+    let mut reached = HashSet::from([entry]);
+    let mut pending = vec![entry];
+    while let Some(id) = pending.pop() {
+        let Some(block) = function.block(id) else {
+            continue;
+        };
+        for successor in block.terminator.successors() {
+            if reached.insert(successor) {
+                pending.push(successor);
+            }
+        }
+    }
+    let before = function.blocks.len();
+    function.blocks.retain(|block| reached.contains(&block.id));
+    function.blocks.len() != before
+}
+
+/// Drop instructions without side effects whose result nothing uses
+fn eliminate_dead_code(function: &mut IrFunction) -> bool {
+    let mut changed = false;
+    loop {
+        let mut used = HashSet::new();
+        for block in &mut function.blocks {
+            for instruction in &block.instructions {
+                for operand in instruction.operands() {
+                    if let Operand::Reg(reg) = operand {
+                        used.insert(reg);
+                    }
+                }
+            }
+            for operand in block.terminator.operands_mut() {
+                if let Operand::Reg(reg) = operand {
+                    used.insert(*reg);
+                }
+            }
+        }
+        let mut removed = false;
+        for block in &mut function.blocks {
+            let before = block.instructions.len();
+            block.instructions.retain(|instruction| {
+                instruction.has_side_effects()
+                    || instruction.dest().is_none_or(|dest| used.contains(&dest))
+            });
+            removed |= block.instructions.len() != before;
+        }
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
@@ -0,0 +1,1293 @@
+use crate::codegen::{frame_entry, frame_exit, linked_symbol, SymbolBinding};
+use crate::error::{AleccError, Result};
+use crate::ir::{
+    BinaryOp, BlockId, Callee, Condition, Instruction, IrFunction, IrType, Operand, Terminator,
+    UnaryOp, VReg,
+};
+use crate::regalloc::{allocate, Allocation};
+use crate::targets::Target;
+use std::collections::HashSet;
+
+/// The x86 registers the emitter uses, by the name of their 8-, 16-, 32-
+/// and 64-bit forms
+const X86_REGISTERS: &[[&str; 4]] = &[
+    ["al", "ax", "eax", "rax"],
+    ["cl", "cx", "ecx", "rcx"],
+    ["dl", "dx", "edx", "rdx"],
+    ["dil", "di", "edi", "rdi"],
+    ["sil", "si", "esi", "rsi"],
+    ["r8b", "r8w", "r8d", "r8"],
+    ["r9b", "r9w", "r9d", "r9"],
+    ["r11b", "r11w", "r11d", "r11"],
+    ["bl", "bx", "ebx", "rbx"],
+    ["r10b", "r10w", "r10d", "r10"],
+    ["r12b", "r12w", "r12d", "r12"],
+    ["r13b", "r13w", "r13d", "r13"],
+    ["r14b", "r14w", "r14d", "r14"],
+    ["r15b", "r15w", "r15d", "r15"],
+];
+
+const AMD64_ARGUMENT_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+
+/// Lowers IR functions to assembly for the same targets, ABI and prologue
+/// as `CodeGenerator`, so IR and AST-generated functions call each other
+/// freely. Virtual registers live in the machine registers the linear-scan
+/// allocator gives them, or in a frame slot once those run out; an
+/// instruction loads its operands into scratch registers and moves its
+/// result back.
+pub struct IrAssembler<'a> {
+    target: Target,
+    label_prefix: &'a str,
+    /// Which symbols are reached through the PLT or GOT; none without one
+    binding: Option<&'a SymbolBinding>,
+    /// Functions with a body in the translation unit
+    defined_functions: &'a HashSet<String>,
+    /// Functions whose calls go to `__wrap_<name>` while they are defined here
+    wrapped_symbols: Option<&'a HashSet<String>>,
+    omit_frame_pointer: bool,
+}
+
+/// The assembly of a function, from its label on, and the bytes it takes on
+/// the stack besides the return address
+pub struct AssembledFunction {
+    pub text: String,
+    pub frame_size: usize,
+}
+
+impl<'a> IrAssembler<'a> {
+    pub fn new(
+        target: Target,
+        label_prefix: &'a str,
+        defined_functions: &'a HashSet<String>,
+    ) -> Self {
+        Self {
+            target,
+            label_prefix,
+            binding: None,
+            defined_functions,
+            wrapped_symbols: None,
+            omit_frame_pointer: false,
+        }
+    }
+
+    pub fn with_wrapped_symbols(mut self, wrapped_symbols: &'a HashSet<String>) -> Self {
+        self.wrapped_symbols = Some(wrapped_symbols);
+        self
+    }
+
+    pub fn with_binding(mut self, binding: &'a SymbolBinding) -> Self {
+        self.binding = Some(binding);
+        self
+    }
+
+    /// Whether to address the frame from the stack pointer and leave the
+    /// frame pointer alone (`-fomit-frame-pointer`). On arm64 only leaf
+    /// functions drop it, as the others save the link register with it
+    pub fn with_frame_pointer_omission(mut self, omit: bool) -> Self {
+        self.omit_frame_pointer = omit;
+        self
+    }
+
+    /// The body of `function`, from the prologue to the last block
+    pub fn assemble(&self, function: &IrFunction) -> Result<AssembledFunction> {
+        if self.target == Target::I386 && function.types.contains(&IrType::I64) {
+            return Err(AleccError::CodegenError {
+                message: format!(
+                    "64-bit values in '{}' are not supported on i386",
+                    function.name
+                ),
+                span: None,
+            });
+        }
+        let allocation = allocate(function, self.target);
+        let mut frame = Frame::plan(self.target, function, &allocation);
+        let leaf = !function
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .any(|instruction| matches!(instruction, Instruction::Call { .. }));
+        if self.omit_frame_pointer && (leaf || self.target != Target::Arm64) {
+            frame = frame.without_frame_pointer(self.target, leaf);
+        }
+        let mut emitter = Emitter {
+            assembler: self,
+            function,
+            text: String::new(),
+            frame,
+            allocation,
+            pushed: 0,
+        };
+        emitter.prologue();
+        for (index, block) in function.blocks.iter().enumerate() {
+            // The entry block is entered from the prologue
+            if index > 0 {
+                let label = emitter.block_label(block.id);
+                emitter.text.push_str(&format!("{}:\n", label));
+            }
+            for instruction in &block.instructions {
+                emitter.instruction(instruction);
+            }
+            let next = function.blocks.get(index + 1).map(|block| block.id);
+            emitter.terminator(&block.terminator, next);
+        }
+        // The saved frame pointer, and on arm64 the link register with it
+        let word = self.target.pointer_size();
+        let saved = match (emitter.frame.frame_pointer, self.target) {
+            (false, _) => 0,
+            (true, Target::Arm64) => 2 * word,
+            (true, Target::I386 | Target::Amd64) => word,
+        };
+        Ok(AssembledFunction {
+            text: emitter.text,
+            frame_size: emitter.frame.size + saved,
+        })
+    }
+}
+
+/// A place in the frame: `offset` bytes from the frame pointer, or from the
+/// stack pointer on arm64, where the frame is addressed upwards, and
+/// wherever the frame pointer is omitted
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    from_stack_pointer: bool,
+    offset: i64,
+}
+
+impl Location {
+    fn frame(offset: i64) -> Self {
+        Self {
+            from_stack_pointer: false,
+            offset,
+        }
+    }
+
+    fn stack(offset: i64) -> Self {
+        Self {
+            from_stack_pointer: true,
+            offset,
+        }
+    }
+}
+
+/// Where a function keeps its stack slots, the homes of the registers it
+/// spills and the callee-saved registers it uses
+struct Frame {
+    slots: Vec<Location>,
+    homes: Vec<Option<Location>>,
+    saves: Vec<(&'static str, Location)>,
+    /// Bytes reserved below the saved frame pointer, a multiple of 16, or
+    /// below the return address without one
+    size: usize,
+    /// Whether the prologue saves the frame pointer and points it at the frame
+    frame_pointer: bool,
+}
+
+impl Frame {
+    fn plan(target: Target, function: &IrFunction, allocation: &Allocation) -> Self {
+        let word = target.pointer_size();
+        let mut homes = vec![None; function.types.len()];
+
+        // Arguments the caller passed on the stack stay where they are
+        let register_arguments = match target {
+            Target::I386 => 0,
+            Target::Amd64 => AMD64_ARGUMENT_REGISTERS.len(),
+            Target::Arm64 => 8,
+        };
+        let first_stack_argument = match target {
+            Target::I386 => 8,
+            Target::Amd64 | Target::Arm64 => 16,
+        };
+        for (index, (reg, _)) in function.parameters.iter().enumerate() {
+            if let Some(stack_index) = index.checked_sub(register_arguments) {
+                homes[reg.0] = Some(Location::frame(
+                    (first_stack_argument + stack_index * word) as i64,
+                ));
+            }
+        }
+
+        let mut defined: Vec<VReg> = function
+            .parameters
+            .iter()
+            .map(|(reg, _)| *reg)
+            .filter(|reg| homes[reg.0].is_none())
+            .collect();
+        for block in &function.blocks {
+            defined.extend(block.instructions.iter().filter_map(Instruction::dest));
+        }
+        defined.retain(|reg| allocation.register(*reg).is_none());
+
+        match target {
+            Target::I386 | Target::Amd64 => {
+                // Below the frame pointer, growing downwards
+                let frame_alignment = match target {
+                    Target::I386 => 4,
+                    _ => 16,
+                };
+                let mut used = 0usize;
+                let slots = function
+                    .slots
+                    .iter()
+                    .map(|slot| {
+                        let align = slot.align.clamp(1, frame_alignment);
+                        used = (used + slot.size).div_ceil(align) * align;
+                        Location::frame(-(used as i64))
+                    })
+                    .collect();
+                for reg in defined {
+                    used = (used + word).div_ceil(word) * word;
+                    homes[reg.0] = Some(Location::frame(-(used as i64)));
+                }
+                let saves = allocation
+                    .callee_saved
+                    .iter()
+                    .map(|register| {
+                        used = (used + word).div_ceil(word) * word;
+                        (*register, Location::frame(-(used as i64)))
+                    })
+                    .collect();
+                Self {
+                    slots,
+                    homes,
+                    saves,
+                    size: used.div_ceil(16) * 16,
+                    frame_pointer: true,
+                }
+            }
+            Target::Arm64 => {
+                // Above the outgoing argument area at the bottom of the frame
+                let outgoing = function
+                    .blocks
+                    .iter()
+                    .flat_map(|block| &block.instructions)
+                    .map(|instruction| match instruction {
+                        Instruction::Call { arguments, .. } => {
+                            arguments.len().saturating_sub(8) * 8
+                        }
+                        _ => 0,
+                    })
+                    .max()
+                    .unwrap_or(0);
+                let mut used = outgoing.div_ceil(16) * 16;
+                let slots = function
+                    .slots
+                    .iter()
+                    .map(|slot| {
+                        let align = slot.align.clamp(1, 16);
+                        let offset = used.div_ceil(align) * align;
+                        used = offset + slot.size;
+                        Location::stack(offset as i64)
+                    })
+                    .collect();
+                let mut next = || {
+                    let offset = used.div_ceil(8) * 8;
+                    used = offset + 8;
+                    Location::stack(offset as i64)
+                };
+                for reg in defined {
+                    homes[reg.0] = Some(next());
+                }
+                let saves = allocation
+                    .callee_saved
+                    .iter()
+                    .map(|register| (*register, next()))
+                    .collect();
+                Self {
+                    slots,
+                    homes,
+                    saves,
+                    size: used.div_ceil(16) * 16,
+                    frame_pointer: true,
+                }
+            }
+        }
+    }
+
+    /// The same frame with every place addressed from the stack pointer,
+    /// for a prologue that neither saves nor sets the frame pointer. On x86
+    /// the frame shrinks by the word the saved frame pointer took, keeping
+    /// the stack pointer aligned as before at calls; a leaf only needs room
+    /// for its slots
+    fn without_frame_pointer(mut self, target: Target, leaf: bool) -> Self {
+        let word = target.pointer_size();
+        let saved = match target {
+            Target::I386 | Target::Amd64 => {
+                let used = self
+                    .slots
+                    .iter()
+                    .chain(self.homes.iter().flatten())
+                    .chain(self.saves.iter().map(|(_, location)| location))
+                    .filter(|location| !location.from_stack_pointer)
+                    .map(|location| (-location.offset).max(0) as usize)
+                    .max()
+                    .unwrap_or(0);
+                self.size = if leaf {
+                    used.div_ceil(word) * word
+                } else {
+                    (used + word).div_ceil(16) * 16 - word
+                };
+                word
+            }
+            // The x29 and x30 pair
+            Target::Arm64 => 16,
+        };
+        let size = self.size as i64;
+        let rebase = |location: &mut Location| {
+            if !location.from_stack_pointer {
+                let offset = if location.offset < 0 {
+                    size + location.offset
+                } else {
+                    size + location.offset - saved as i64
+                };
+                *location = Location::stack(offset);
+            }
+        };
+        self.slots.iter_mut().for_each(rebase);
+        self.homes.iter_mut().flatten().for_each(rebase);
+        self.saves
+            .iter_mut()
+            .for_each(|(_, location)| rebase(location));
+        self.frame_pointer = false;
+        self
+    }
+}
+
+struct Emitter<'a, 'b> {
+    assembler: &'b IrAssembler<'a>,
+    function: &'b IrFunction,
+    text: String,
+    frame: Frame,
+    allocation: Allocation,
+    /// Bytes pushed below the frame for the call being set up, which the
+    /// offsets from the stack pointer make up for
+    pushed: i64,
+}
+
+impl Emitter<'_, '_> {
+    fn line(&mut self, line: &str) {
+        self.text.push_str("    ");
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+
+    fn target(&self) -> Target {
+        self.assembler.target
+    }
+
+    fn word(&self) -> IrType {
+        match self.target().pointer_size() {
+            4 => IrType::I32,
+            _ => IrType::I64,
+        }
+    }
+
+    fn block_label(&self, block: BlockId) -> String {
+        format!(
+            ".L{}{}_bb{}",
+            self.assembler.label_prefix, self.function.name, block.0
+        )
+    }
+
+    fn home(&self, reg: VReg) -> Location {
+        self.frame.homes[reg.0].expect("every register used is assigned somewhere")
+    }
+
+    /// The symbol a direct call to `function` names
+    fn call_symbol(&self, function: &str) -> String {
+        match self.assembler.wrapped_symbols {
+            Some(wrapped) => linked_symbol(function, wrapped, self.assembler.defined_functions),
+            None => function.to_string(),
+        }
+    }
+
+    /// Whether the call to `function`, or the address of a symbol, must go
+    /// through the PLT or GOT
+    fn is_external(&self, symbol: &str) -> bool {
+        self.assembler
+            .binding
+            .is_some_and(|binding| binding.through_got(symbol))
+    }
+
+    fn prologue(&mut self) {
+        let size = self.frame.size;
+        if self.frame.frame_pointer {
+            for line in frame_entry(self.target()) {
+                self.line(line);
+            }
+        } else if size > 0 {
+            self.allocate_frame(size);
+            // The canonical frame address stays the stack pointer on entry
+            let return_address = match self.target() {
+                Target::Arm64 => 0,
+                Target::I386 | Target::Amd64 => self.target().pointer_size(),
+            };
+            self.line(&format!(".cfi_def_cfa_offset {}", size + return_address));
+        }
+        match self.target() {
+            Target::I386 => {
+                if self.frame.frame_pointer {
+                    self.allocate_frame(size);
+                }
+                self.save_registers();
+            }
+            Target::Amd64 => {
+                if self.frame.frame_pointer {
+                    self.allocate_frame(size);
+                }
+                self.save_registers();
+                let parameters = self.function.parameters.clone();
+                for ((reg, _), register) in parameters.iter().zip(AMD64_ARGUMENT_REGISTERS) {
+                    self.store_home(*reg, register);
+                }
+            }
+            Target::Arm64 => {
+                if self.frame.frame_pointer {
+                    self.allocate_frame(size);
+                }
+                self.save_registers();
+                let parameters = self.function.parameters.clone();
+                for (index, (reg, _)) in parameters.iter().enumerate().take(8) {
+                    self.store_home(*reg, &format!("x{}", index));
+                }
+            }
+        }
+    }
+
+    /// Move the stack pointer down `size` bytes, past the frame
+    fn allocate_frame(&mut self, size: usize) {
+        match self.target() {
+            Target::I386 => self.line(&format!("sub esp, {}", size)),
+            Target::Amd64 => self.line(&format!("sub rsp, {}", size)),
+            Target::Arm64 if size <= 4095 => self.line(&format!("sub sp, sp, #{}", size)),
+            Target::Arm64 => {
+                self.arm64_immediate("x16", size as i64, IrType::I64);
+                self.line("sub sp, sp, x16");
+            }
+        }
+    }
+
+    /// Keep the callee-saved registers the allocator handed out in the frame,
+    /// and tell the unwinder where, relative to the canonical frame address:
+    /// the frame pointer plus the two words it and the return address take,
+    /// or the stack pointer above the frame and those two words on arm64.
+    /// Without a frame pointer, the stack pointer above the frame and the
+    /// return address, if the call pushed it
+    fn save_registers(&mut self) {
+        let word = self.target().pointer_size();
+        let size = self.frame.size as i64;
+        for (register, location) in self.frame.saves.clone() {
+            let memory = self.memory(location, word);
+            let from_cfa = match (self.frame.frame_pointer, self.target()) {
+                (true, Target::I386 | Target::Amd64) => location.offset - 2 * word as i64,
+                (true, Target::Arm64) => location.offset - size - 16,
+                (false, Target::I386 | Target::Amd64) => location.offset - size - word as i64,
+                (false, Target::Arm64) => location.offset - size,
+            };
+            match self.target() {
+                Target::I386 | Target::Amd64 => self.line(&format!("mov {}, {}", memory, register)),
+                Target::Arm64 => self.line(&format!("str {}, {}", register, memory)),
+            }
+            if let Some(number) = self.target().register_names().dwarf_number(register) {
+                self.line(&format!(".cfi_offset {}, {}", number, from_cfa));
+            }
+        }
+    }
+
+    fn epilogue(&mut self) {
+        let word = self.target().pointer_size();
+        for (register, location) in self.frame.saves.clone() {
+            let memory = self.memory(location, word);
+            match self.target() {
+                Target::I386 | Target::Amd64 => self.line(&format!("mov {}, {}", register, memory)),
+                Target::Arm64 => self.line(&format!("ldr {}, {}", register, memory)),
+            }
+        }
+        if self.frame.frame_pointer {
+            for line in frame_exit(self.target()) {
+                self.line(line);
+            }
+            return;
+        }
+        // Like `frame_exit`, the code after a return still has the frame
+        let size = self.frame.size;
+        self.line(".cfi_remember_state");
+        if size > 0 {
+            match self.target() {
+                Target::I386 => self.line(&format!("add esp, {}", size)),
+                Target::Amd64 => self.line(&format!("add rsp, {}", size)),
+                Target::Arm64 if size <= 4095 => self.line(&format!("add sp, sp, #{}", size)),
+                Target::Arm64 => {
+                    self.arm64_immediate("x16", size as i64, IrType::I64);
+                    self.line("add sp, sp, x16");
+                }
+            }
+            let return_address = match self.target() {
+                Target::Arm64 => 0,
+                Target::I386 | Target::Amd64 => self.target().pointer_size(),
+            };
+            self.line(&format!(".cfi_def_cfa_offset {}", return_address));
+        }
+        self.line("ret");
+        self.line(".cfi_restore_state");
+    }
+
+    /// Note that the stack pointer moved down `bytes` (up, when negative)
+    /// while a call's arguments are pushed, for the places addressed from it
+    /// and for the unwinder when it follows the stack pointer
+    fn stack_moved(&mut self, bytes: i64) {
+        self.pushed += bytes;
+        if !self.frame.frame_pointer {
+            self.line(&format!(".cfi_adjust_cfa_offset {}", bytes));
+        }
+    }
+
+    // Moving values
+
+    /// A memory operand for `size` bytes at `location`
+    fn memory(&mut self, location: Location, size: usize) -> String {
+        match self.target() {
+            Target::I386 | Target::Amd64 => {
+                let (base, offset) = match (location.from_stack_pointer, self.target()) {
+                    (false, Target::I386) => ("ebp", location.offset),
+                    (false, _) => ("rbp", location.offset),
+                    (true, Target::I386) => ("esp", location.offset + self.pushed),
+                    (true, _) => ("rsp", location.offset + self.pushed),
+                };
+                let sign = if offset < 0 { '-' } else { '+' };
+                format!(
+                    "{} PTR [{} {} {}]",
+                    x86_size(size),
+                    base,
+                    sign,
+                    offset.unsigned_abs()
+                )
+            }
+            Target::Arm64 => {
+                let base = if location.from_stack_pointer {
+                    "sp"
+                } else {
+                    "x29"
+                };
+                let offset = location.offset;
+                let scaled =
+                    offset >= 0 && offset % size as i64 == 0 && offset / (size as i64) < 4096;
+                if scaled || (-256..256).contains(&offset) {
+                    format!("[{}, #{}]", base, offset)
+                } else {
+                    self.arm64_immediate("x16", offset, IrType::I64);
+                    format!("[{}, x16]", base)
+                }
+            }
+        }
+    }
+
+    /// Load `operand`, of type `ty`, into `register`, named by its widest
+    /// form; narrow values are zero-extended
+    fn load(&mut self, register: &str, operand: Operand, ty: IrType) {
+        match self.target() {
+            Target::I386 | Target::Amd64 => {
+                let wide = ty == IrType::I64;
+                let destination = x86_register(register, if wide { 8 } else { 4 });
+                match operand {
+                    Operand::Imm(value) => {
+                        let value = ty.wrap(value, true);
+                        self.line(&format!("mov {}, {}", destination, value));
+                    }
+                    Operand::Reg(reg) => match self.allocation.register(reg) {
+                        Some(home) => {
+                            let source = x86_register(home, if wide { 8 } else { 4 });
+                            self.line(&format!("mov {}, {}", destination, source));
+                            if matches!(ty, IrType::I8 | IrType::I16) {
+                                let narrow = x86_register(register, ty.size());
+                                self.line(&format!("movzx {}, {}", destination, narrow));
+                            }
+                        }
+                        None => {
+                            let source = self.memory(self.home(reg), ty.size());
+                            let mnemonic = match ty {
+                                IrType::I8 | IrType::I16 => "movzx",
+                                IrType::I32 | IrType::I64 => "mov",
+                            };
+                            self.line(&format!("{} {}, {}", mnemonic, destination, source));
+                        }
+                    },
+                }
+            }
+            Target::Arm64 => {
+                let destination = arm64_register(register, ty);
+                match operand {
+                    Operand::Imm(value) => self.arm64_immediate(&destination, value, ty),
+                    Operand::Reg(reg) => match self.allocation.register(reg) {
+                        Some(home) => {
+                            let source = arm64_register(home, ty);
+                            self.line(&format!("mov {}, {}", destination, source));
+                            match ty {
+                                IrType::I8 => self.line(&format!("uxtb {0}, {0}", destination)),
+                                IrType::I16 => self.line(&format!("uxth {0}, {0}", destination)),
+                                IrType::I32 | IrType::I64 => {}
+                            }
+                        }
+                        None => {
+                            let source = self.memory(self.home(reg), ty.size());
+                            let mnemonic = match ty {
+                                IrType::I8 => "ldrb",
+                                IrType::I16 => "ldrh",
+                                IrType::I32 | IrType::I64 => "ldr",
+                            };
+                            self.line(&format!("{} {}, {}", mnemonic, destination, source));
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    /// Move the whole of `register` to where `reg` lives
+    fn store_home(&mut self, reg: VReg, register: &str) {
+        let word = self.target().pointer_size();
+        if let Some(home) = self.allocation.register(reg) {
+            match self.target() {
+                Target::I386 | Target::Amd64 => {
+                    let source = x86_register(register, word);
+                    self.line(&format!("mov {}, {}", x86_register(home, word), source));
+                }
+                Target::Arm64 => self.line(&format!("mov {}, {}", home, register)),
+            }
+            return;
+        }
+        let destination = self.memory(self.home(reg), word);
+        match self.target() {
+            Target::I386 | Target::Amd64 => {
+                let source = x86_register(register, word);
+                self.line(&format!("mov {}, {}", destination, source));
+            }
+            Target::Arm64 => self.line(&format!("str {}, {}", register, destination)),
+        }
+    }
+
+    /// Put `value` in the arm64 `register` with `mov`, or `movz` and `movk` a
+    /// half-word at a time when it is too wide for one
+    fn arm64_immediate(&mut self, register: &str, value: i64, ty: IrType) {
+        let value = ty.wrap(value, true);
+        if (-65536..65536).contains(&value) {
+            self.line(&format!("mov {}, #{}", register, value));
+            return;
+        }
+        let bits = value as u64 & u64::MAX >> (64 - ty.bits().max(32));
+        let mut first = true;
+        for shift in (0..ty.bits().max(32)).step_by(16) {
+            let half = (bits >> shift) & 0xffff;
+            if half == 0 && !(first && shift + 16 >= ty.bits().max(32)) {
+                continue;
+            }
+            let mnemonic = if first { "movz" } else { "movk" };
+            self.line(&format!(
+                "{} {}, #{}, lsl #{}",
+                mnemonic, register, half, shift
+            ));
+            first = false;
+        }
+    }
+
+    fn address_of(&mut self, register: &str, location: Location) {
+        match self.target() {
+            Target::I386 | Target::Amd64 => {
+                let memory = self.memory(location, 1);
+                let address = &memory[memory.find('[').unwrap_or(0)..];
+                let destination = x86_register(register, self.target().pointer_size());
+                self.line(&format!("lea {}, {}", destination, address));
+            }
+            Target::Arm64 => {
+                let base = if location.from_stack_pointer {
+                    "sp"
+                } else {
+                    "x29"
+                };
+                if (0..4096).contains(&location.offset) {
+                    self.line(&format!("add {}, {}, #{}", register, base, location.offset));
+                } else {
+                    self.arm64_immediate("x16", location.offset, IrType::I64);
+                    self.line(&format!("add {}, {}, x16", register, base));
+                }
+            }
+        }
+    }
+
+    fn symbol_address(&mut self, register: &str, symbol: &str, through_got: bool) {
+        match self.target() {
+            Target::I386 => self.line(&format!("mov {}, OFFSET {}", register, symbol)),
+            Target::Amd64 if through_got => self.line(&format!(
+                "mov {}, QWORD PTR [rip + {}@GOTPCREL]",
+                register, symbol
+            )),
+            Target::Amd64 => self.line(&format!("lea {}, [rip + {}]", register, symbol)),
+            Target::Arm64 if through_got => {
+                self.line(&format!("adrp {}, :got:{}", register, symbol));
+                self.line(&format!(
+                    "ldr {}, [{}, :got_lo12:{}]",
+                    register, register, symbol
+                ));
+            }
+            Target::Arm64 => {
+                self.line(&format!("adrp {}, {}", register, symbol));
+                self.line(&format!("add {}, {}, :lo12:{}", register, register, symbol));
+            }
+        }
+    }
+
+    // Instructions
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        match self.target() {
+            Target::I386 | Target::Amd64 => self.x86_instruction(instruction),
+            Target::Arm64 => self.arm64_instruction(instruction),
+        }
+    }
+
+    fn x86_instruction(&mut self, instruction: &Instruction) {
+        let (a, c) = match self.target() {
+            Target::I386 => ("eax", "ecx"),
+            _ => ("rax", "rcx"),
+        };
+        let word = self.word();
+        match instruction {
+            Instruction::Binary {
+                dest,
+                op,
+                ty,
+                left,
+                right,
+            } => {
+                self.load(a, *left, *ty);
+                self.load(c, *right, *ty);
+                let size = if *ty == IrType::I64 { 8 } else { 4 };
+                let (ra, rc) = (x86_register(a, size), x86_register(c, size));
+                let mut result = a;
+                match op {
+                    BinaryOp::SDiv | BinaryOp::SRem => {
+                        self.line(if size == 8 { "cqo" } else { "cdq" });
+                        self.line(&format!("idiv {}", rc));
+                    }
+                    BinaryOp::UDiv | BinaryOp::URem => {
+                        self.line("xor edx, edx");
+                        self.line(&format!("div {}", rc));
+                    }
+                    BinaryOp::Shl | BinaryOp::LShr | BinaryOp::AShr => {
+                        let mnemonic = match op {
+                            BinaryOp::Shl => "shl",
+                            BinaryOp::LShr => "shr",
+                            _ => "sar",
+                        };
+                        self.line(&format!("{} {}, cl", mnemonic, ra));
+                    }
+                    _ => {
+                        let mnemonic = match op {
+                            BinaryOp::Add => "add",
+                            BinaryOp::Sub => "sub",
+                            BinaryOp::Mul => "imul",
+                            BinaryOp::And => "and",
+                            BinaryOp::Or => "or",
+                            _ => "xor",
+                        };
+                        self.line(&format!("{} {}, {}", mnemonic, ra, rc));
+                    }
+                }
+                if matches!(op, BinaryOp::SRem | BinaryOp::URem) {
+                    result = x86_register("rdx", self.target().pointer_size());
+                }
+                self.store_home(*dest, result);
+            }
+            Instruction::Unary {
+                dest,
+                op,
+                ty,
+                operand,
+            } => {
+                self.load(a, *operand, *ty);
+                let register = x86_register(a, if *ty == IrType::I64 { 8 } else { 4 });
+                let mnemonic = match op {
+                    UnaryOp::Neg => "neg",
+                    UnaryOp::Not => "not",
+                };
+                self.line(&format!("{} {}", mnemonic, register));
+                self.store_home(*dest, a);
+            }
+            Instruction::Compare {
+                dest,
+                condition,
+                ty,
+                left,
+                right,
+            } => {
+                self.load(a, *left, *ty);
+                self.load(c, *right, *ty);
+                let size = if *ty == IrType::I64 { 8 } else { 4 };
+                self.line(&format!(
+                    "cmp {}, {}",
+                    x86_register(a, size),
+                    x86_register(c, size)
+                ));
+                self.line(&format!("set{} al", x86_condition(*condition)));
+                self.line("movzx eax, al");
+                self.store_home(*dest, a);
+            }
+            Instruction::Convert {
+                dest,
+                from,
+                to,
+                signed,
+                value,
+            } => {
+                match value {
+                    Operand::Reg(_) if to.size() > from.size() && *signed => {
+                        self.load(a, *value, *from);
+                        let mnemonic = match from {
+                            IrType::I32 => "movsxd",
+                            _ => "movsx",
+                        };
+                        let size = if *to == IrType::I64 { 8 } else { 4 };
+                        self.line(&format!(
+                            "{} {}, {}",
+                            mnemonic,
+                            x86_register(a, size),
+                            x86_register(a, from.size())
+                        ));
+                    }
+                    // Narrow loads zero-extend, and truncating only reads the low bytes
+                    Operand::Reg(_) if to.size() > from.size() => self.load(a, *value, *from),
+                    Operand::Reg(_) => self.load(a, *value, *to),
+                    Operand::Imm(_) => self.load(a, *value, *to),
+                }
+                self.store_home(*dest, a);
+            }
+            Instruction::Copy { dest, ty, value } => {
+                self.load(a, *value, *ty);
+                self.store_home(*dest, a);
+            }
+            Instruction::SlotAddress { dest, slot } => {
+                self.address_of(a, self.frame.slots[slot.0]);
+                self.store_home(*dest, a);
+            }
+            Instruction::SymbolAddress { dest, symbol } => {
+                let external = self.is_external(symbol);
+                self.symbol_address(a, symbol, external);
+                self.store_home(*dest, a);
+            }
+            Instruction::FunctionAddress { dest, function } => {
+                let external = self.is_external(function);
+                self.symbol_address(a, function, external);
+                self.store_home(*dest, a);
+            }
+            Instruction::Load {
+                dest, ty, address, ..
+            } => {
+                self.load(c, *address, word);
+                let source = format!("{} PTR [{}]", x86_size(ty.size()), c);
+                match ty {
+                    IrType::I8 | IrType::I16 => self.line(&format!("movzx eax, {}", source)),
+                    IrType::I32 => self.line(&format!("mov eax, {}", source)),
+                    IrType::I64 => self.line(&format!("mov rax, {}", source)),
+                }
+                self.store_home(*dest, a);
+            }
+            Instruction::Store {
+                ty, value, address, ..
+            } => {
+                self.load(c, *address, word);
+                self.load(a, *value, *ty);
+                self.line(&format!(
+                    "mov {} PTR [{}], {}",
+                    x86_size(ty.size()),
+                    c,
+                    x86_register(a, ty.size())
+                ));
+            }
+            Instruction::Call {
+                dest,
+                callee,
+                arguments,
+                variadic,
+            } => {
+                let target = match callee {
+                    Callee::Direct(name) => {
+                        let name = self.call_symbol(name);
+                        if self.target() == Target::Amd64 && self.is_external(&name) {
+                            format!("{}@PLT", name)
+                        } else {
+                            name
+                        }
+                    }
+                    Callee::Indirect(_) => {
+                        x86_register("r11", self.target().pointer_size()).to_string()
+                    }
+                };
+                let target = match (callee, self.target()) {
+                    (Callee::Indirect(_), Target::I386) => "ecx".to_string(),
+                    _ => target,
+                };
+                let stack_bytes = match self.target() {
+                    Target::I386 => {
+                        for (_, argument) in arguments.iter().rev() {
+                            self.load("eax", *argument, IrType::I32);
+                            self.line("push eax");
+                            self.stack_moved(4);
+                        }
+                        arguments.len() * 4
+                    }
+                    _ => {
+                        let on_stack = arguments
+                            .len()
+                            .saturating_sub(AMD64_ARGUMENT_REGISTERS.len());
+                        // Keep rsp 16-byte aligned at the call
+                        let padding = if on_stack % 2 == 1 { 8 } else { 0 };
+                        if padding > 0 {
+                            self.line("sub rsp, 8");
+                            self.stack_moved(8);
+                        }
+                        for (_, argument) in
+                            arguments.iter().skip(AMD64_ARGUMENT_REGISTERS.len()).rev()
+                        {
+                            self.load("rax", *argument, IrType::I64);
+                            self.line("push rax");
+                            self.stack_moved(8);
+                        }
+                        for ((_, argument), register) in
+                            arguments.iter().zip(AMD64_ARGUMENT_REGISTERS)
+                        {
+                            self.load(register, *argument, IrType::I64);
+                        }
+                        on_stack * 8 + padding
+                    }
+                };
+                if let Callee::Indirect(function) = callee {
+                    self.load(&target, *function, word);
+                }
+                // al holds the number of vector registers a variadic callee gets
+                if *variadic && self.target() == Target::Amd64 {
+                    self.line("mov eax, 0");
+                }
+                self.line(&format!("call {}", target));
+                if stack_bytes > 0 {
+                    let stack_pointer = match self.target() {
+                        Target::I386 => "esp",
+                        _ => "rsp",
+                    };
+                    self.line(&format!("add {}, {}", stack_pointer, stack_bytes));
+                    self.stack_moved(-(stack_bytes as i64));
+                }
+                if let Some((dest, _)) = dest {
+                    self.store_home(*dest, a);
+                }
+            }
+        }
+    }
+
+    fn arm64_instruction(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::Binary {
+                dest,
+                op,
+                ty,
+                left,
+                right,
+            } => {
+                self.load("x9", *left, *ty);
+                self.load("x10", *right, *ty);
+                let (a, b) = (arm64_register("x9", *ty), arm64_register("x10", *ty));
+                match op {
+                    BinaryOp::SRem | BinaryOp::URem => {
+                        let quotient = arm64_register("x11", *ty);
+                        let divide = match op {
+                            BinaryOp::SRem => "sdiv",
+                            _ => "udiv",
+                        };
+                        self.line(&format!("{} {}, {}, {}", divide, quotient, a, b));
+                        self.line(&format!("msub {}, {}, {}, {}", a, quotient, b, a));
+                    }
+                    _ => {
+                        let mnemonic = match op {
+                            BinaryOp::Add => "add",
+                            BinaryOp::Sub => "sub",
+                            BinaryOp::Mul => "mul",
+                            BinaryOp::SDiv => "sdiv",
+                            BinaryOp::UDiv => "udiv",
+                            BinaryOp::And => "and",
+                            BinaryOp::Or => "orr",
+                            BinaryOp::Xor => "eor",
+                            BinaryOp::Shl => "lsl",
+                            BinaryOp::LShr => "lsr",
+                            _ => "asr",
+                        };
+                        self.line(&format!("{} {}, {}, {}", mnemonic, a, a, b));
+                    }
+                }
+                self.store_home(*dest, "x9");
+            }
+            Instruction::Unary {
+                dest,
+                op,
+                ty,
+                operand,
+            } => {
+                self.load("x9", *operand, *ty);
+                let register = arm64_register("x9", *ty);
+                let mnemonic = match op {
+                    UnaryOp::Neg => "neg",
+                    UnaryOp::Not => "mvn",
+                };
+                self.line(&format!("{} {}, {}", mnemonic, register, register));
+                self.store_home(*dest, "x9");
+            }
+            Instruction::Compare {
+                dest,
+                condition,
+                ty,
+                left,
+                right,
+            } => {
+                self.load("x9", *left, *ty);
+                self.load("x10", *right, *ty);
+                self.line(&format!(
+                    "cmp {}, {}",
+                    arm64_register("x9", *ty),
+                    arm64_register("x10", *ty)
+                ));
+                self.line(&format!("cset w9, {}", arm64_condition(*condition)));
+                self.store_home(*dest, "x9");
+            }
+            Instruction::Convert {
+                dest,
+                from,
+                to,
+                signed,
+                value,
+            } => {
+                match value {
+                    Operand::Reg(_) if to.size() > from.size() => {
+                        self.load("x9", *value, *from);
+                        if *signed {
+                            let mnemonic = match from {
+                                IrType::I8 => "sxtb",
+                                IrType::I16 => "sxth",
+                                _ => "sxtw",
+                            };
+                            self.line(&format!("{} {}, w9", mnemonic, arm64_register("x9", *to)));
+                        }
+                    }
+                    _ => self.load("x9", *value, *to),
+                }
+                self.store_home(*dest, "x9");
+            }
+            Instruction::Copy { dest, ty, value } => {
+                self.load("x9", *value, *ty);
+                self.store_home(*dest, "x9");
+            }
+            Instruction::SlotAddress { dest, slot } => {
+                self.address_of("x9", self.frame.slots[slot.0]);
+                self.store_home(*dest, "x9");
+            }
+            Instruction::SymbolAddress { dest, symbol } => {
+                let external = self.is_external(symbol);
+                self.symbol_address("x9", symbol, external);
+                self.store_home(*dest, "x9");
+            }
+            Instruction::FunctionAddress { dest, function } => {
+                let external = self.is_external(function);
+                self.symbol_address("x9", function, external);
+                self.store_home(*dest, "x9");
+            }
+            Instruction::Load {
+                dest, ty, address, ..
+            } => {
+                self.load("x10", *address, IrType::I64);
+                let mnemonic = match ty {
+                    IrType::I8 => "ldrb",
+                    IrType::I16 => "ldrh",
+                    IrType::I32 | IrType::I64 => "ldr",
+                };
+                self.line(&format!(
+                    "{} {}, [x10]",
+                    mnemonic,
+                    arm64_register("x9", *ty)
+                ));
+                self.store_home(*dest, "x9");
+            }
+            Instruction::Store {
+                ty, value, address, ..
+            } => {
+                self.load("x10", *address, IrType::I64);
+                self.load("x9", *value, *ty);
+                let mnemonic = match ty {
+                    IrType::I8 => "strb",
+                    IrType::I16 => "strh",
+                    IrType::I32 | IrType::I64 => "str",
+                };
+                self.line(&format!(
+                    "{} {}, [x10]",
+                    mnemonic,
+                    arm64_register("x9", *ty)
+                ));
+            }
+            Instruction::Call {
+                dest,
+                callee,
+                arguments,
+                ..
+            } => {
+                // The outgoing argument area is at the bottom of the frame
+                for (index, (_, argument)) in arguments.iter().enumerate().skip(8) {
+                    self.load("x9", *argument, IrType::I64);
+                    self.line(&format!("str x9, [sp, #{}]", (index - 8) * 8));
+                }
+                for (index, (_, argument)) in arguments.iter().enumerate().take(8) {
+                    self.load(&format!("x{}", index), *argument, IrType::I64);
+                }
+                match callee {
+                    Callee::Direct(name) => {
+                        let name = self.call_symbol(name);
+                        self.line(&format!("bl {}", name));
+                    }
+                    Callee::Indirect(function) => {
+                        self.load("x17", *function, IrType::I64);
+                        self.line("blr x17");
+                    }
+                }
+                if let Some((dest, _)) = dest {
+                    self.store_home(*dest, "x0");
+                }
+            }
+        }
+    }
+
+    fn terminator(&mut self, terminator: &Terminator, next: Option<BlockId>) {
+        match terminator {
+            Terminator::Jump(target) => {
+                if Some(*target) != next {
+                    let label = self.block_label(*target);
+                    match self.target() {
+                        Target::I386 | Target::Amd64 => self.line(&format!("jmp {}", label)),
+                        Target::Arm64 => self.line(&format!("b {}", label)),
+                    }
+                }
+            }
+            Terminator::Branch {
+                condition,
+                ty,
+                if_true,
+                if_false,
+            } => {
+                let (true_label, false_label) =
+                    (self.block_label(*if_true), self.block_label(*if_false));
+                // Branch on the condition to whichever block doesn't follow
+                let (branch, taken, other) = if Some(*if_true) == next {
+                    (false, false_label, None)
+                } else if Some(*if_false) == next {
+                    (true, true_label, None)
+                } else {
+                    (true, true_label, Some(false_label))
+                };
+                match self.target() {
+                    Target::I386 | Target::Amd64 => {
+                        let a = x86_register("rax", self.target().pointer_size());
+                        self.load(a, *condition, *ty);
+                        let register = x86_register(a, if *ty == IrType::I64 { 8 } else { 4 });
+                        self.line(&format!("test {}, {}", register, register));
+                        self.line(&format!("{} {}", if branch { "jne" } else { "je" }, taken));
+                        if let Some(other) = other {
+                            self.line(&format!("jmp {}", other));
+                        }
+                    }
+                    Target::Arm64 => {
+                        self.load("x9", *condition, *ty);
+                        let register = arm64_register("x9", *ty);
+                        self.line(&format!(
+                            "{} {}, {}",
+                            if branch { "cbnz" } else { "cbz" },
+                            register,
+                            taken
+                        ));
+                        if let Some(other) = other {
+                            self.line(&format!("b {}", other));
+                        }
+                    }
+                }
+            }
+            Terminator::Return(value) => {
+                if let Some((ty, value)) = value {
+                    let register = match self.target() {
+                        Target::I386 => "eax",
+                        Target::Amd64 => "rax",
+                        Target::Arm64 => "x0",
+                    };
+                    self.load(register, *value, *ty);
+                }
+                self.epilogue();
+            }
+            Terminator::Unreachable => {}
+            Terminator::Trap => match self.target() {
+                Target::I386 | Target::Amd64 => self.line("ud2"),
+                Target::Arm64 => self.line("brk #0x3e8"),
+            },
+        }
+    }
+}
+
+/// The form of an x86 register, given by any of its names, that is `size` bytes wide
+fn x86_register(register: &str, size: usize) -> &'static str {
+    let forms = X86_REGISTERS
+        .iter()
+        .find(|forms| forms.contains(&register))
+        .expect("a register the emitter uses");
+    match size {
+        1 => forms[0],
+        2 => forms[1],
+        4 => forms[2],
+        _ => forms[3],
+    }
+}
+
+fn x86_size(size: usize) -> &'static str {
+    match size {
+        1 => "BYTE",
+        2 => "WORD",
+        4 => "DWORD",
+        _ => "QWORD",
+    }
+}
+
+fn x86_condition(condition: Condition) -> &'static str {
+    match condition {
+        Condition::Eq => "e",
+        Condition::Ne => "ne",
+        Condition::SLt => "l",
+        Condition::SLe => "le",
+        Condition::SGt => "g",
+        Condition::SGe => "ge",
+        Condition::ULt => "b",
+        Condition::ULe => "be",
+        Condition::UGt => "a",
+        Condition::UGe => "ae",
+    }
+}
+
+/// The `w` form of an arm64 `x` register for values of 32 bits or less
+fn arm64_register(register: &str, ty: IrType) -> String {
+    match ty {
+        IrType::I64 => register.to_string(),
+        _ => register.replacen('x', "w", 1),
+    }
+}
+
+fn arm64_condition(condition: Condition) -> &'static str {
+    match condition {
+        Condition::Eq => "eq",
+        Condition::Ne => "ne",
+        Condition::SLt => "lt",
+        Condition::SLe => "le",
+        Condition::SGt => "gt",
+        Condition::SGe => "ge",
+        Condition::ULt => "lo",
+        Condition::ULe => "ls",
+        Condition::UGt => "hi",
+        Condition::UGe => "hs",
+    }
+}
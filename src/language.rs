@@ -0,0 +1,135 @@
+use crate::error::{AleccError, Result};
+
+/// Revisions of ISO C, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Standard {
+    C89,
+    C99,
+    C11,
+    C17,
+    C23,
+}
+
+/// The dialect a translation unit is written in, chosen with `--std`. It
+/// decides which keywords the lexer knows, what the parser accepts and which
+/// macros are predefined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LanguageOptions {
+    pub standard: Standard,
+    /// `gnu*` dialects: GNU extensions on top of the ISO revision
+    pub gnu: bool,
+}
+
+impl Default for LanguageOptions {
+    /// gcc's default, `gnu17`
+    fn default() -> Self {
+        Self {
+            standard: Standard::C17,
+            gnu: true,
+        }
+    }
+}
+
+impl LanguageOptions {
+    /// Parse a `--std` value, accepting the names gcc does
+    pub fn from_std(name: &str) -> Result<Self> {
+        let (gnu, revision) = if let Some(revision) = name.strip_prefix("gnu") {
+            (true, revision)
+        } else if let Some(revision) = name.strip_prefix("iso9899:") {
+            (false, revision)
+        } else if name == "ansi" {
+            (false, "89")
+        } else {
+            (false, name.strip_prefix('c').unwrap_or(name))
+        };
+
+        let standard = match revision {
+            "89" | "90" | "1990" | "199409" => Standard::C89,
+            "99" | "9x" | "1999" => Standard::C99,
+            "11" | "1x" | "2011" => Standard::C11,
+            "17" | "18" | "2017" | "2018" => Standard::C17,
+            "23" | "2x" | "2024" => Standard::C23,
+            _ => {
+                return Err(AleccError::InvalidArgument {
+                    message: format!("unrecognized C standard '{}' in --std", name),
+                })
+            }
+        };
+        Ok(Self { standard, gnu })
+    }
+
+    /// Canonical `--std` spelling, e.g. `gnu17` or `c99`
+    pub fn name(&self) -> String {
+        let revision = match self.standard {
+            Standard::C89 => "89",
+            Standard::C99 => "99",
+            Standard::C11 => "11",
+            Standard::C17 => "17",
+            Standard::C23 => "23",
+        };
+        format!("{}{}", if self.gnu { "gnu" } else { "c" }, revision)
+    }
+
+    /// `//` comments, new in C99
+    pub fn line_comments(&self) -> bool {
+        self.standard >= Standard::C99 || self.gnu
+    }
+
+    /// `inline`, `restrict` and `_Bool`, new in C99
+    pub fn c99_keywords(&self) -> bool {
+        self.standard >= Standard::C99 || self.gnu
+    }
+
+    /// `alignas` and `alignof` without the underscore, new in C23
+    pub fn c23_keywords(&self) -> bool {
+        self.standard >= Standard::C23
+    }
+
+    /// `()` in a declaration leaves the parameters unspecified, until C23 made it `(void)`
+    pub fn unprototyped_functions(&self) -> bool {
+        self.standard < Standard::C23
+    }
+
+    /// Calls to undeclared functions, which C99 removed; GNU dialects still
+    /// accept them with a warning
+    pub fn implicit_function_declarations(&self) -> bool {
+        self.standard < Standard::C99 || self.gnu
+    }
+
+    /// Array sizes that aren't constant, new in C99
+    pub fn variable_length_arrays(&self) -> bool {
+        self.standard >= Standard::C99 || self.gnu
+    }
+
+    /// Value of `__STDC_VERSION__`, which C89 doesn't define
+    pub fn stdc_version(&self) -> Option<&'static str> {
+        match self.standard {
+            Standard::C89 => None,
+            Standard::C99 => Some("199901L"),
+            Standard::C11 => Some("201112L"),
+            Standard::C17 => Some("201710L"),
+            Standard::C23 => Some("202311L"),
+        }
+    }
+
+    /// Macros every translation unit starts with
+    pub fn predefined_macros(&self) -> Vec<(&'static str, &'static str)> {
+        let mut macros = vec![
+            ("__STDC__", "1"),
+            ("__STDC_HOSTED__", "1"),
+            ("__ATOMIC_RELAXED", "0"),
+            ("__ATOMIC_CONSUME", "1"),
+            ("__ATOMIC_ACQUIRE", "2"),
+            ("__ATOMIC_RELEASE", "3"),
+            ("__ATOMIC_ACQ_REL", "4"),
+            ("__ATOMIC_SEQ_CST", "5"),
+        ];
+        if let Some(version) = self.stdc_version() {
+            macros.push(("__STDC_VERSION__", version));
+        }
+        if !self.gnu {
+            macros.push(("__STRICT_ANSI__", "1"));
+        }
+        macros
+    }
+}
@@ -0,0 +1,189 @@
+use crate::parser::{Program, Type};
+use crate::targets::Target;
+use std::collections::HashMap;
+
+/// Sizes, alignments and member offsets of C types on a target, shared by
+/// the backends that lay out memory themselves.
+#[derive(Debug, Clone)]
+pub struct TypeLayout {
+    target: Target,
+    aggregates: HashMap<String, Type>,
+}
+
+impl TypeLayout {
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            aggregates: HashMap::new(),
+        }
+    }
+
+    /// Remember the struct/union definitions of a program, so that a later
+    /// `struct tag` without a body can be resolved to them
+    pub fn record_aggregates(&mut self, program: &Program) {
+        for ty in program.type_definitions.values() {
+            self.record_aggregate(ty);
+        }
+        for global in &program.global_variables {
+            self.record_aggregate(&global.var_type);
+        }
+    }
+
+    /// Remember the struct/union definitions a type contains
+    pub fn record_aggregate(&mut self, ty: &Type) {
+        match ty {
+            Type::Struct { name, fields, .. } | Type::Union { name, fields, .. }
+                if !fields.is_empty() =>
+            {
+                self.aggregates.insert(name.clone(), ty.clone());
+                for (_, field) in fields {
+                    self.record_aggregate(field);
+                }
+            }
+            Type::Pointer(inner)
+            | Type::Array(inner, _)
+            | Type::Typedef(_, inner)
+            | Type::Qualified(_, inner) => self.record_aggregate(inner),
+            _ => {}
+        }
+    }
+
+    /// Strip typedefs; enums are plain ints, and `struct tag` without a body
+    /// refers to the recorded definition
+    pub fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Typedef(_, inner) | Type::Qualified(_, inner) => self.resolve(inner),
+            Type::Enum { .. } => Type::Int,
+            Type::Struct { name, fields, .. } | Type::Union { name, fields, .. }
+                if fields.is_empty() =>
+            {
+                self.aggregates
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| ty.clone())
+            }
+            _ => ty.clone(),
+        }
+    }
+
+    pub fn size_of(&self, ty: &Type) -> usize {
+        match self.resolve(ty) {
+            Type::Void | Type::Char | Type::UnsignedChar | Type::Bool => 1,
+            Type::Short | Type::UnsignedShort => 2,
+            Type::Int | Type::UnsignedInt | Type::Float => 4,
+            Type::Double | Type::LongLong | Type::UnsignedLongLong => 8,
+            Type::Long | Type::UnsignedLong | Type::Pointer(_) | Type::Function { .. } => {
+                self.target.pointer_size()
+            }
+            Type::Array(element, size) => self.size_of(&element) * size.unwrap_or(0),
+            Type::Struct { fields, packed, .. } => {
+                let mut offset = 0usize;
+                for (_, field) in &fields {
+                    let align = self.member_align(field, packed);
+                    offset = offset.div_ceil(align) * align + self.size_of(field);
+                }
+                let align = self.align_of(ty);
+                offset.div_ceil(align) * align
+            }
+            Type::Union { fields, .. } => {
+                let size = fields
+                    .iter()
+                    .map(|(_, field)| self.size_of(field))
+                    .max()
+                    .unwrap_or(0);
+                let align = self.align_of(ty);
+                size.div_ceil(align) * align
+            }
+            Type::VaList => match self.target {
+                Target::I386 => 4,
+                Target::Amd64 => 24,
+                Target::Arm64 => 32,
+            },
+            Type::Enum { .. } | Type::Typedef(..) | Type::Qualified(..) => {
+                unreachable!("resolved above")
+            }
+        }
+    }
+
+    pub fn align_of(&self, ty: &Type) -> usize {
+        match self.resolve(ty) {
+            Type::Array(element, _) => self.align_of(&element),
+            Type::Struct { packed: true, .. } | Type::Union { packed: true, .. } => 1,
+            Type::Struct { fields, .. } | Type::Union { fields, .. } => fields
+                .iter()
+                .map(|(_, field)| self.align_of(field))
+                .max()
+                .unwrap_or(1),
+            Type::VaList => self.target.pointer_size(),
+            // i386 aligns 8-byte scalars to 4 inside structs
+            other => self.size_of(&other).min(self.target.alignment()).max(1),
+        }
+    }
+
+    /// Alignment of a member within its struct; a packed struct doesn't pad
+    fn member_align(&self, member: &Type, packed: bool) -> usize {
+        if packed {
+            1
+        } else {
+            self.align_of(member)
+        }
+    }
+
+    /// The scalar members of `ty`, with their byte offsets, after flattening
+    /// nested structs, unions and arrays; a scalar is just itself
+    pub fn scalars(&self, ty: &Type) -> Vec<(usize, Type)> {
+        let mut scalars = Vec::new();
+        self.collect_scalars(ty, 0, &mut scalars);
+        scalars
+    }
+
+    fn collect_scalars(&self, ty: &Type, offset: usize, scalars: &mut Vec<(usize, Type)>) {
+        match self.resolve(ty) {
+            Type::Struct { fields, packed, .. } => {
+                let mut member = 0usize;
+                for (_, field) in &fields {
+                    let align = self.member_align(field, packed);
+                    member = member.div_ceil(align) * align;
+                    self.collect_scalars(field, offset + member, scalars);
+                    member += self.size_of(field);
+                }
+            }
+            Type::Union { fields, .. } => {
+                for (_, field) in &fields {
+                    self.collect_scalars(field, offset, scalars);
+                }
+            }
+            Type::Array(element, length) => {
+                let size = self.size_of(&element);
+                for index in 0..length.unwrap_or(0) {
+                    self.collect_scalars(&element, offset + index * size, scalars);
+                }
+            }
+            scalar => scalars.push((offset, scalar)),
+        }
+    }
+
+    /// Byte offset and type of `member` within a struct or union
+    #[allow(dead_code)]
+    pub fn member(&self, aggregate: &Type, member: &str) -> Option<(usize, Type)> {
+        match self.resolve(aggregate) {
+            Type::Struct { fields, packed, .. } => {
+                let mut offset = 0usize;
+                for (name, field) in &fields {
+                    let align = self.member_align(field, packed);
+                    offset = offset.div_ceil(align) * align;
+                    if name == member {
+                        return Some((offset, self.resolve(field)));
+                    }
+                    offset += self.size_of(field);
+                }
+                None
+            }
+            Type::Union { fields, .. } => fields
+                .iter()
+                .find(|(name, _)| name == member)
+                .map(|(_, field)| (0, self.resolve(field))),
+            _ => None,
+        }
+    }
+}
@@ -1,9 +1,12 @@
+use crate::language::LanguageOptions;
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Literals
     IntegerLiteral(i64),
+    /// An integer literal with a `u` or `l` suffix, or too big for a `long`
+    SuffixedIntegerLiteral(u64, IntegerSuffix),
     FloatLiteral(f64),
     StringLiteral(String),
     CharLiteral(char),
@@ -43,7 +46,14 @@ pub enum TokenType {
     Unsigned,
     Void,
     Volatile,
+    Restrict,
     While,
+    Generic,      // _Generic
+    Alignof,      // _Alignof
+    Alignas,      // _Alignas
+    Noreturn,     // _Noreturn
+    ThreadLocal,  // _Thread_local
+    StaticAssert, // _Static_assert
 
     // C++ Keywords
     Bool,
@@ -128,6 +138,20 @@ pub enum TokenType {
     Newline,
 }
 
+/// Where a token or AST node starts in the preprocessed source; line 0 when unknown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
@@ -138,6 +162,13 @@ pub struct Token {
 }
 
 impl Token {
+    pub fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
     pub fn new(token_type: TokenType, line: usize, column: usize, length: usize) -> Self {
         Self {
             token_type,
@@ -148,10 +179,34 @@ impl Token {
     }
 }
 
+/// The `u` and `l` suffixes of an integer literal (`ll` counts as `l`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IntegerSuffix {
+    pub unsigned: bool,
+    pub long: bool,
+    /// `LL`, which also sets `long`
+    pub long_long: bool,
+}
+
+impl fmt::Display for IntegerSuffix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.unsigned {
+            f.write_str("U")?;
+        }
+        if self.long_long {
+            f.write_str("LL")?;
+        } else if self.long {
+            f.write_str("L")?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TokenType::IntegerLiteral(n) => write!(f, "{}", n),
+            TokenType::SuffixedIntegerLiteral(n, suffix) => write!(f, "{}{}", n, suffix),
             TokenType::FloatLiteral(n) => write!(f, "{}", n),
             TokenType::StringLiteral(s) => write!(f, "\"{}\"", s),
             TokenType::CharLiteral(c) => write!(f, "'{}'", c),
@@ -161,11 +216,73 @@ impl fmt::Display for TokenType {
     }
 }
 
+/// A `/** ... */` comment, kept by [`Lexer::with_doc_comments`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocComment {
+    /// Everything between `/**` and `*/`
+    pub text: String,
+    /// Where the comment starts
+    pub span: Span,
+    /// The position just after the closing `*/`
+    pub end: Span,
+}
+
+/// What a piece of trivia is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// Spaces, tabs and carriage returns
+    Whitespace,
+    /// A backslash right before a newline, which joins the two lines
+    LineSplice,
+    LineComment,
+    BlockComment,
+}
+
+/// Source text the parser never sees, as written
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriviaPiece {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// A token as written and the trivia before it, kept by
+/// [`Lexer::with_trivia`]. Newlines are tokens of their own, so `leading`
+/// only spans more than one line through a block comment or a line splice.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trivia {
+    pub leading: Vec<TriviaPiece>,
+    pub text: String,
+}
+
+impl Trivia {
+    /// The leading trivia as written
+    pub fn leading_text(&self) -> String {
+        self.leading
+            .iter()
+            .map(|piece| piece.text.as_str())
+            .collect()
+    }
+
+    /// The comments among the leading trivia
+    pub fn comments(&self) -> impl Iterator<Item = &TriviaPiece> {
+        self.leading.iter().filter(|piece| {
+            matches!(
+                piece.kind,
+                TriviaKind::LineComment | TriviaKind::BlockComment
+            )
+        })
+    }
+}
+
 pub struct Lexer {
     input: String,
     position: usize,
     line: usize,
     column: usize,
+    language: LanguageOptions,
+    doc_comments: Option<Vec<DocComment>>,
+    trivia: Option<Vec<Trivia>>,
 }
 
 impl Lexer {
@@ -175,14 +292,48 @@ impl Lexer {
             position: 0,
             line: 1,
             column: 1,
+            language: LanguageOptions::default(),
+            doc_comments: None,
+            trivia: None,
         }
     }
 
+    /// Keep `/** ... */` comments instead of discarding them, for [`Lexer::take_doc_comments`]
+    pub fn with_doc_comments(mut self) -> Self {
+        self.doc_comments = Some(Vec::new());
+        self
+    }
+
+    /// The doc comments seen so far, in source order
+    pub fn take_doc_comments(&mut self) -> Vec<DocComment> {
+        self.doc_comments
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Keep the text of every token and what precedes it, for [`Lexer::take_trivia`]
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = Some(Vec::new());
+        self
+    }
+
+    /// One entry per token returned by [`Lexer::tokenize`], the end of file included
+    pub fn take_trivia(&mut self) -> Vec<Trivia> {
+        self.trivia.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn with_language(mut self, language: LanguageOptions) -> Self {
+        self.language = language;
+        self
+    }
+
     pub fn tokenize(&mut self) -> crate::error::Result<Vec<Token>> {
         let mut tokens = Vec::new();
+        let mut leading = Vec::new();
 
         while !self.is_at_end() {
-            self.skip_whitespace();
+            self.skip_whitespace(&mut leading);
 
             if self.is_at_end() {
                 break;
@@ -196,16 +347,58 @@ impl Lexer {
                 Ok(Some(token_type)) => {
                     let length = self.position - start_position;
                     tokens.push(Token::new(token_type, start_line, start_column, length));
+                    if self.trivia.is_some() {
+                        let text = self.text(start_position, self.position);
+                        if let Some(trivia) = &mut self.trivia {
+                            trivia.push(Trivia {
+                                leading: std::mem::take(&mut leading),
+                                text,
+                            });
+                        }
+                    }
+                }
+                // A comment
+                Ok(None) => {
+                    let start = Span {
+                        line: start_line,
+                        column: start_column,
+                    };
+                    let kind = if self.text(start_position, start_position + 2) == "//" {
+                        TriviaKind::LineComment
+                    } else {
+                        TriviaKind::BlockComment
+                    };
+                    self.keep(&mut leading, kind, start_position, start);
                 }
-                Ok(None) => {} // Skip whitespace/comments
                 Err(e) => return Err(e),
             }
         }
 
         tokens.push(Token::new(TokenType::Eof, self.line, self.column, 0));
+        if let Some(trivia) = &mut self.trivia {
+            trivia.push(Trivia {
+                leading,
+                text: String::new(),
+            });
+        }
         Ok(tokens)
     }
 
+    /// Add the text from `start` to here to the trivia of the next token
+    fn keep(&self, leading: &mut Vec<TriviaPiece>, kind: TriviaKind, start: usize, span: Span) {
+        if self.trivia.is_some() && start < self.position {
+            leading.push(TriviaPiece {
+                kind,
+                text: self.text(start, self.position),
+                span,
+            });
+        }
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.input.chars().skip(start).take(end - start).collect()
+    }
+
     fn scan_token(&mut self) -> crate::error::Result<Option<TokenType>> {
         let c = self.advance();
 
@@ -241,10 +434,34 @@ impl Lexer {
                 if self.match_char('=') {
                     Ok(Some(TokenType::DivideAssign))
                 } else if self.match_char('/') {
+                    if !self.language.line_comments() {
+                        return Err(crate::error::AleccError::LexError {
+                            line: self.line,
+                            column: self.column - 2,
+                            message: "C++ style comments are not allowed in ISO C90".to_string(),
+                        });
+                    }
                     self.skip_line_comment();
                     Ok(None)
                 } else if self.match_char('*') {
-                    self.skip_block_comment()?;
+                    let span = Span {
+                        line: self.line,
+                        column: self.column - 2,
+                    };
+                    let text = self.skip_block_comment()?;
+                    if let Some(comments) = &mut self.doc_comments {
+                        // `/**/` is empty, and `/***` usually a separator line
+                        if let Some(text) = text.strip_prefix('*').filter(|t| !t.starts_with('*')) {
+                            comments.push(DocComment {
+                                text: text.to_string(),
+                                span,
+                                end: Span {
+                                    line: self.line,
+                                    column: self.column,
+                                },
+                            });
+                        }
+                    }
                     Ok(None)
                 } else {
                     Ok(Some(TokenType::Divide))
@@ -415,11 +632,26 @@ impl Lexer {
         self.position >= self.input.len()
     }
 
-    fn skip_whitespace(&mut self) {
+    fn skip_whitespace(&mut self, leading: &mut Vec<TriviaPiece>) {
         while !self.is_at_end() {
+            let start = self.position;
+            let span = Span {
+                line: self.line,
+                column: self.column,
+            };
             match self.current_char() {
                 ' ' | '\r' | '\t' => {
+                    while matches!(self.current_char(), ' ' | '\r' | '\t') {
+                        self.advance();
+                    }
+                    self.keep(leading, TriviaKind::Whitespace, start, span);
+                }
+                '\\' if self.peek() == '\n' => {
+                    self.advance();
                     self.advance();
+                    self.line += 1;
+                    self.column = 1;
+                    self.keep(leading, TriviaKind::LineSplice, start, span);
                 }
                 _ => break,
             }
@@ -432,18 +664,21 @@ impl Lexer {
         }
     }
 
-    fn skip_block_comment(&mut self) -> crate::error::Result<()> {
+    /// Skip to the end of a block comment, returning its text
+    fn skip_block_comment(&mut self) -> crate::error::Result<String> {
+        let mut text = String::new();
         while !self.is_at_end() {
             if self.current_char() == '*' && self.peek() == '/' {
                 self.advance(); // consume '*'
                 self.advance(); // consume '/'
-                return Ok(());
+                return Ok(text);
             }
-            if self.current_char() == '\n' {
+            let c = self.advance();
+            if c == '\n' {
                 self.line += 1;
                 self.column = 1;
             }
-            self.advance();
+            text.push(c);
         }
 
         Err(crate::error::AleccError::LexError {
@@ -557,7 +792,42 @@ impl Lexer {
             }
         }
 
-        let text = &self.input[start..self.position];
+        // An exponent, as in 1e9 or 2.5E-3
+        let signed = matches!(self.peek(), '+' | '-')
+            && self
+                .input
+                .chars()
+                .nth(self.position + 2)
+                .is_some_and(|c| c.is_ascii_digit());
+        if matches!(self.current_char(), 'e' | 'E') && (self.peek().is_ascii_digit() || signed) {
+            is_float = true;
+            self.advance(); // consume 'e'
+            if signed {
+                self.advance();
+            }
+            while !self.is_at_end() && self.current_char().is_ascii_digit() {
+                self.advance();
+            }
+        }
+
+        // Floating literals are all evaluated as double, whatever their suffix
+        let end = self.position;
+        let mut suffix = IntegerSuffix::default();
+        if is_float {
+            if matches!(self.current_char(), 'f' | 'F' | 'l' | 'L') {
+                self.advance();
+            }
+        } else {
+            while !self.is_at_end() && matches!(self.current_char(), 'u' | 'U' | 'l' | 'L') {
+                match self.current_char() {
+                    'u' | 'U' => suffix.unsigned = true,
+                    _ if suffix.long => suffix.long_long = true,
+                    _ => suffix.long = true,
+                }
+                self.advance();
+            }
+        }
+        let text = &self.input[start..end];
 
         if is_float {
             match text.parse::<f64>() {
@@ -569,8 +839,13 @@ impl Lexer {
                 }),
             }
         } else {
-            match text.parse::<i64>() {
-                Ok(value) => Ok(Some(TokenType::IntegerLiteral(value))),
+            match text.parse::<u64>() {
+                Ok(value) => match i64::try_from(value) {
+                    Ok(value) if suffix == IntegerSuffix::default() => {
+                        Ok(Some(TokenType::IntegerLiteral(value)))
+                    }
+                    _ => Ok(Some(TokenType::SuffixedIntegerLiteral(value, suffix))),
+                },
                 Err(_) => Err(crate::error::AleccError::LexError {
                     line: self.line,
                     column: self.column,
@@ -625,7 +900,22 @@ impl Lexer {
             "unsigned" => TokenType::Unsigned,
             "void" => TokenType::Void,
             "volatile" => TokenType::Volatile,
+            "restrict" if self.language.c99_keywords() => TokenType::Restrict,
+            "__restrict" | "__restrict__" => TokenType::Restrict,
+            "_Bool" if self.language.c99_keywords() => TokenType::Bool,
             "while" => TokenType::While,
+            "_Generic" => TokenType::Generic,
+            "_Alignof" => TokenType::Alignof,
+            "_Alignas" => TokenType::Alignas,
+            // `__noreturn` is how BSD and Linux headers spell it
+            "_Noreturn" | "__noreturn" | "__noreturn__" => TokenType::Noreturn,
+            "_Static_assert" => TokenType::StaticAssert,
+            // `__thread` is the GNU spelling
+            "_Thread_local" | "__thread" => TokenType::ThreadLocal,
+            "alignof" if self.language.c23_keywords() => TokenType::Alignof,
+            "alignas" if self.language.c23_keywords() => TokenType::Alignas,
+            "static_assert" if self.language.c23_keywords() => TokenType::StaticAssert,
+            "thread_local" if self.language.c23_keywords() => TokenType::ThreadLocal,
             // C++ keywords
             "bool" => TokenType::Bool,
             "class" => TokenType::Class,
@@ -633,7 +923,8 @@ impl Lexer {
             "export" => TokenType::Export,
             "false" => TokenType::False,
             "friend" => TokenType::Friend,
-            "inline" => TokenType::Inline,
+            "inline" if self.language.c99_keywords() => TokenType::Inline,
+            "__inline" | "__inline__" => TokenType::Inline,
             "mutable" => TokenType::Mutable,
             "namespace" => TokenType::Namespace,
             "new" => TokenType::New,
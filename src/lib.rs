@@ -1,9 +1,41 @@
+pub mod abi;
+pub mod analysis;
+pub mod att;
+pub mod builtins;
+pub mod cache;
+pub mod callgraph;
 pub mod cli;
 pub mod codegen;
 pub mod compiler;
+pub mod constant;
+#[cfg(feature = "cranelift")]
+pub mod cranelift_backend;
+#[cfg(all(feature = "daemon", unix))]
+pub mod daemon;
+pub mod diagnostics;
+pub mod doc;
 pub mod error;
+pub mod explain;
+pub mod fmt;
+pub mod format;
+pub mod frame;
+pub mod headers;
+pub mod host;
+pub mod ir;
+pub mod ir_codegen;
+pub mod language;
+pub mod layout;
 pub mod lexer;
 pub mod linker;
+pub mod llvm_ir;
+pub mod lower;
+pub mod merge;
 pub mod optimizer;
 pub mod parser;
+pub mod regalloc;
+pub mod replay;
+pub mod runtime;
+pub mod sema;
+pub mod summary;
 pub mod targets;
+pub mod warnings;
@@ -1,4 +1,5 @@
 use crate::error::{AleccError, Result};
+use crate::host::{find_tool, under_sysroot, Host};
 use crate::targets::Target;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -11,11 +12,29 @@ pub struct Linker {
     libraries: Vec<String>,
     static_link: bool,
     shared: bool,
+    nostdlib: bool,
+    /// Link libc's startup files, which define `_start`
+    crt: bool,
+    stack_size: Option<u64>,
     pic: bool,
     pie: bool,
     sysroot: Option<PathBuf>,
+    toolchain: Option<PathBuf>,
     debug: bool,
     lto: bool,
+    dry_run: bool,
+    wrapped_symbols: Vec<String>,
+    symbol_definitions: Vec<(String, String)>,
+    extra_args: Vec<String>,
+}
+
+/// Render a command line the way `gcc -###` does: every argument quoted.
+pub fn format_command(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|arg| format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 impl Linker {
@@ -28,11 +47,19 @@ impl Linker {
             libraries: Vec::new(),
             static_link: false,
             shared: false,
+            nostdlib: false,
+            crt: false,
+            stack_size: None,
             pic: false,
             pie: false,
             sysroot: None,
+            toolchain: None,
             debug: false,
             lto: false,
+            dry_run: false,
+            wrapped_symbols: Vec::new(),
+            symbol_definitions: Vec::new(),
+            extra_args: Vec::new(),
         }
     }
 
@@ -60,6 +87,18 @@ impl Linker {
         self.shared = shared;
     }
 
+    pub fn set_nostdlib(&mut self, nostdlib: bool) {
+        self.nostdlib = nostdlib;
+    }
+
+    pub fn set_crt(&mut self, crt: bool) {
+        self.crt = crt;
+    }
+
+    pub fn set_stack_size(&mut self, stack_size: Option<u64>) {
+        self.stack_size = stack_size;
+    }
+
     pub fn set_pic(&mut self, pic: bool) {
         self.pic = pic;
     }
@@ -72,6 +111,18 @@ impl Linker {
         self.sysroot = sysroot;
     }
 
+    pub fn set_toolchain(&mut self, toolchain: Option<PathBuf>) {
+        self.toolchain = toolchain;
+    }
+
+    /// The host program that provides binutils `tool` for the target
+    fn tool(&self, tool: &str) -> String {
+        find_tool(
+            &Host::current().tool_candidates(tool, self.target),
+            self.toolchain.as_deref(),
+        )
+    }
+
     pub fn set_debug(&mut self, debug: bool) {
         self.debug = debug;
     }
@@ -80,6 +131,26 @@ impl Linker {
         self.lto = lto;
     }
 
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Send undefined references to `symbol` to `__wrap_<symbol>`, and those
+    /// to `__real_<symbol>` to the original (`ld --wrap`)
+    pub fn add_wrapped_symbol(&mut self, symbol: String) {
+        self.wrapped_symbols.push(symbol);
+    }
+
+    /// Define `symbol` as the address `expression` evaluates to (`ld --defsym`)
+    pub fn add_symbol_definition(&mut self, symbol: String, expression: String) {
+        self.symbol_definitions.push((symbol, expression));
+    }
+
+    /// Pass `arg` to ld as is, as `-Wl,` does
+    pub fn add_linker_arg(&mut self, arg: String) {
+        self.extra_args.push(arg);
+    }
+
     pub async fn link(&self) -> Result<()> {
         if self.object_files.is_empty() {
             return Err(AleccError::LinkerError {
@@ -89,6 +160,11 @@ impl Linker {
 
         let linker_command = self.build_linker_command()?;
 
+        if self.dry_run {
+            eprintln!(" {}", format_command(&linker_command));
+            return Ok(());
+        }
+
         let output = Command::new(&linker_command[0])
             .args(&linker_command[1..])
             .output()
@@ -109,14 +185,7 @@ impl Linker {
     fn build_linker_command(&self) -> Result<Vec<String>> {
         let mut command = Vec::new();
 
-        // Choose linker based on target
-        let linker = match self.target {
-            Target::I386 => "ld",
-            Target::Amd64 => "ld",
-            Target::Arm64 => "aarch64-linux-gnu-ld",
-        };
-
-        command.push(linker.to_string());
+        command.push(self.tool("ld"));
 
         // Target-specific flags
         match self.target {
@@ -134,6 +203,10 @@ impl Linker {
             }
         }
 
+        // An index of the unwind tables, which unwinders find through the
+        // PT_GNU_EH_FRAME header
+        command.push("--eh-frame-hdr".to_string());
+
         // Output file
         command.push("-o".to_string());
         command.push(self.output_path.to_string_lossy().to_string());
@@ -164,6 +237,12 @@ impl Linker {
             command.push("-shared".to_string());
         }
 
+        // Stack size for the PT_GNU_STACK header
+        if let Some(stack_size) = self.stack_size {
+            command.push("-z".to_string());
+            command.push(format!("stack-size={}", stack_size));
+        }
+
         // Debug information
         if self.debug {
             command.push("-g".to_string());
@@ -174,8 +253,16 @@ impl Linker {
             command.push("--lto-O3".to_string());
         }
 
-        // Dynamic linker
-        if !self.static_link && !self.shared {
+        // Symbol wrapping and definitions
+        for symbol in &self.wrapped_symbols {
+            command.push(format!("--wrap={}", symbol));
+        }
+        for (symbol, expression) in &self.symbol_definitions {
+            command.push(format!("--defsym={}={}", symbol, expression));
+        }
+
+        // Dynamic linker (a --nostdlib executable has nothing to load)
+        if !self.static_link && !self.shared && !self.nostdlib {
             let dynamic_linker = match self.target {
                 Target::I386 => "/lib/ld-linux.so.2",
                 Target::Amd64 => "/lib64/ld-linux-x86-64.so.2",
@@ -185,10 +272,13 @@ impl Linker {
             command.push(dynamic_linker.to_string());
         }
 
-        // Standard library paths and startup files
-        if !self.static_link && !self.shared {
-            self.add_standard_startup_files(&mut command)?;
-        }
+        // libc's startup files, whose _start enters main through __libc_start_main
+        let (startup_files, end_files) = if self.crt {
+            self.startup_files()
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        command.extend(startup_files);
 
         // Library search paths
         for path in &self.library_paths {
@@ -197,7 +287,12 @@ impl Linker {
         }
 
         // Add standard library paths
-        self.add_standard_library_paths(&mut command)?;
+        if !self.nostdlib {
+            self.add_standard_library_paths(&mut command)?;
+        }
+
+        // Options passed through with -Wl, in command-line order
+        command.extend(self.extra_args.iter().cloned());
 
         // Object files
         for obj in &self.object_files {
@@ -211,20 +306,57 @@ impl Linker {
         }
 
         // Standard libraries
-        if !self.static_link {
+        if !self.static_link && !self.nostdlib {
             command.push("-lc".to_string());
         }
+        command.extend(end_files);
 
         Ok(command)
     }
 
-    fn add_standard_startup_files(&self, _command: &mut [String]) -> Result<()> {
-        // Skip startup files when we have our own _start
-        // This prevents conflicts with our custom _start implementation
-        Ok(())
+    /// The startup files that go before the program (crt1.o, crti.o and
+    /// crtbegin.o) and after it (crtend.o and crtn.o). gcc's crtbegin.o and
+    /// crtend.o, which define `__dso_handle` for `atexit`, are only linked
+    /// when the host gcc builds for the target. A file that isn't found is
+    /// passed by name, as gcc does, for ld to report.
+    fn startup_files(&self) -> (Vec<String>, Vec<String>) {
+        let dirs = self.system_library_dirs();
+        let find = |name: &str| {
+            dirs.iter()
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|| name.to_string())
+        };
+        let entry = if self.pie { "Scrt1.o" } else { "crt1.o" };
+        let mut startup = vec![find(entry), find("crti.o")];
+        let mut end = Vec::new();
+
+        let gcc_lib = match self.sysroot {
+            None if self.target == Target::native() => self.get_gcc_lib_path(),
+            _ => None,
+        };
+        if let Some(gcc_lib) = gcc_lib {
+            let suffix = if self.pie { "S" } else { "" };
+            let begin = Path::new(&gcc_lib).join(format!("crtbegin{}.o", suffix));
+            let finish = Path::new(&gcc_lib).join(format!("crtend{}.o", suffix));
+            if begin.is_file() && finish.is_file() {
+                startup.push(begin.to_string_lossy().to_string());
+                end.push(finish.to_string_lossy().to_string());
+            }
+        }
+        end.push(find("crtn.o"));
+        (startup, end)
     }
 
-    fn add_standard_library_paths(&self, command: &mut Vec<String>) -> Result<()> {
+    /// The directories of the target's libc, under the sysroot if there is one
+    fn system_library_dirs(&self) -> Vec<PathBuf> {
+        // Without a sysroot only a Linux host has the target's libraries
+        let sysroot = self.sysroot.as_deref();
+        if sysroot.is_none() && !Host::current().has_target_system_dirs() {
+            return Vec::new();
+        }
+
         let lib_paths = match self.target {
             Target::I386 => vec![
                 "/usr/lib/i386-linux-gnu",
@@ -241,44 +373,48 @@ impl Linker {
             Target::Arm64 => vec!["/usr/lib/aarch64-linux-gnu", "/lib/aarch64-linux-gnu"],
         };
 
-        for path in lib_paths {
+        lib_paths
+            .into_iter()
+            .map(|path| under_sysroot(sysroot, path))
+            .collect()
+    }
+
+    fn add_standard_library_paths(&self, command: &mut Vec<String>) -> Result<()> {
+        let sysroot = self.sysroot.as_deref();
+        if sysroot.is_none() && !Host::current().has_target_system_dirs() {
+            return Ok(());
+        }
+        for path in self.system_library_dirs() {
             command.push("-L".to_string());
-            command.push(path.to_string());
+            command.push(path.to_string_lossy().to_string());
         }
 
-        // Add GCC library path
-        let gcc_lib = self.get_gcc_lib_path()?;
-        command.push("-L".to_string());
-        command.push(gcc_lib);
+        // The host gcc's libgcc only matches the target's when there is no sysroot
+        if sysroot.is_none() {
+            if let Some(gcc_lib) = self.get_gcc_lib_path() {
+                command.push("-L".to_string());
+                command.push(gcc_lib);
+            }
+        }
 
         Ok(())
     }
 
-    fn get_gcc_lib_path(&self) -> Result<String> {
-        // Try to find GCC library path
+    /// Directory of the host gcc's libgcc, if a gcc is installed
+    fn get_gcc_lib_path(&self) -> Option<String> {
         let output = Command::new("gcc")
             .args(["-print-libgcc-file-name"])
             .output()
-            .map_err(|e| AleccError::LinkerError {
-                message: format!("Failed to find GCC library path: {}", e),
-            })?;
+            .ok()?;
 
         if !output.status.success() {
-            return Err(AleccError::LinkerError {
-                message: "Failed to determine GCC library path".to_string(),
-            });
+            return None;
         }
 
         let libgcc_path = String::from_utf8_lossy(&output.stdout);
-        let libgcc_path = libgcc_path.trim();
-
-        if let Some(parent) = Path::new(libgcc_path).parent() {
-            Ok(parent.to_string_lossy().to_string())
-        } else {
-            Err(AleccError::LinkerError {
-                message: "Invalid GCC library path".to_string(),
-            })
-        }
+        Path::new(libgcc_path.trim())
+            .parent()
+            .map(|parent| parent.to_string_lossy().to_string())
     }
 
     pub async fn link_shared_library(&self, soname: Option<&str>) -> Result<()> {
@@ -297,6 +433,11 @@ impl Linker {
             command.push(soname.to_string());
         }
 
+        if self.dry_run {
+            eprintln!(" {}", format_command(&command));
+            return Ok(());
+        }
+
         let output = Command::new(&command[0])
             .args(&command[1..])
             .output()
@@ -317,13 +458,18 @@ impl Linker {
     #[allow(dead_code)]
     pub async fn link_static_library(&self) -> Result<()> {
         // Use ar to create static library
-        let mut command = vec!["ar".to_string(), "rcs".to_string()];
+        let mut command = vec![self.tool("ar"), "rcs".to_string()];
         command.push(self.output_path.to_string_lossy().to_string());
 
         for obj in &self.object_files {
             command.push(obj.to_string_lossy().to_string());
         }
 
+        if self.dry_run {
+            eprintln!(" {}", format_command(&command));
+            return Ok(());
+        }
+
         let output = Command::new(&command[0])
             .args(&command[1..])
             .output()
@@ -0,0 +1,2659 @@
+use crate::analysis::{is_noreturn_call, returns_twice, setjmp_callers};
+use crate::builtins::{
+    atomic, is_builtin, library_function, signature, Atomic, AtomicOp, AtomicOperation, MemoryOrder,
+};
+use crate::cli::{MissingReturn, StackProtector};
+use crate::constant::{floating_constant, ConstantEvaluator};
+use crate::error::{AleccError, Result};
+use crate::layout::TypeLayout;
+use crate::parser::{
+    AssignmentOperator, BinaryOperator, Expression, ExpressionKind, Function, GlobalStorage,
+    GlobalVariable, Program, Statement, StatementKind, Type, UnaryOperator,
+};
+use crate::targets::Target;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// An SSA operand (or constant) together with the C type it holds
+#[derive(Debug, Clone)]
+struct Value {
+    operand: String,
+    ty: Type,
+}
+
+/// An lvalue: its address, the type stored there, and whether accessing it is volatile
+#[derive(Debug, Clone)]
+struct Place {
+    address: String,
+    ty: Type,
+    volatile: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Signature {
+    return_type: Type,
+    parameters: Vec<Type>,
+    variadic: bool,
+}
+
+/// Lowers the AST to textual LLVM IR for `--emit=llvm-ir`, so programs can be
+/// optimized and compiled by clang/llc instead of the built-in backend. No LLVM
+/// libraries are involved; the output uses opaque pointers (LLVM 15+).
+pub struct LlvmIrGenerator {
+    target: Target,
+    string_literals: HashMap<String, String>,
+    string_definitions: Vec<String>,
+    /// Goes into the names of string constants, so modules linked with `llvm-link` keep them apart
+    label_prefix: String,
+    signatures: HashMap<String, Signature>,
+    /// Functions declared `__attribute__((noreturn))` or `_Noreturn`
+    noreturn_functions: HashSet<String>,
+    /// Functions that call `setjmp` or another function that returns twice
+    setjmp_callers: HashSet<String>,
+    defined_functions: HashSet<String>,
+    referenced_functions: BTreeSet<String>,
+    intrinsics: BTreeMap<&'static str, &'static str>,
+    missing_return: MissingReturn,
+    globals: HashMap<String, Type>,
+    /// Globals each thread has its own copy of, at no constant address
+    thread_locals: BTreeSet<String>,
+    layout: TypeLayout,
+    // Per-function state
+    scopes: Vec<HashMap<String, (String, Type)>>,
+    allocas: Vec<String>,
+    body: Vec<String>,
+    value_counter: usize,
+    label_counter: usize,
+    current_block: String,
+    block_terminated: bool,
+    return_type: Type,
+    /// Every load and store is volatile, so that locals live across a
+    /// `setjmp` aren't promoted to registers a `longjmp` wouldn't restore
+    memory_only: bool,
+    break_labels: Vec<String>,
+    continue_labels: Vec<String>,
+    function_name: String,
+    /// Labels whose address `&&label` took, where an `indirectbr` may go
+    address_taken_labels: Vec<String>,
+    ident: Option<String>,
+    stack_protector: StackProtector,
+}
+
+/// Stands for the destinations of an `indirectbr` until the whole function
+/// has been generated and every label whose address is taken is known
+const INDIRECT_TARGETS_PLACEHOLDER: &str = "__alecc_indirect_targets__";
+
+impl LlvmIrGenerator {
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            string_literals: HashMap::new(),
+            string_definitions: Vec::new(),
+            label_prefix: String::new(),
+            signatures: HashMap::new(),
+            noreturn_functions: HashSet::new(),
+            setjmp_callers: HashSet::new(),
+            defined_functions: HashSet::new(),
+            referenced_functions: BTreeSet::new(),
+            intrinsics: BTreeMap::new(),
+            missing_return: MissingReturn::Unspecified,
+            globals: HashMap::new(),
+            thread_locals: BTreeSet::new(),
+            layout: TypeLayout::new(target),
+            scopes: Vec::new(),
+            allocas: Vec::new(),
+            body: Vec::new(),
+            value_counter: 0,
+            label_counter: 0,
+            current_block: String::new(),
+            block_terminated: false,
+            return_type: Type::Void,
+            memory_only: false,
+            break_labels: Vec::new(),
+            continue_labels: Vec::new(),
+            function_name: String::new(),
+            address_taken_labels: Vec::new(),
+            ident: None,
+            stack_protector: StackProtector::Off,
+        }
+    }
+
+    /// Name string constants `@.str.{prefix}{n}`
+    pub fn with_label_prefix(mut self, prefix: &str) -> Self {
+        self.label_prefix = prefix.to_string();
+        self
+    }
+
+    /// Record `ident` as the module's `llvm.ident`, which ends up in `.comment`
+    pub fn with_ident(mut self, ident: Option<&str>) -> Self {
+        self.ident = ident.map(str::to_string);
+        self
+    }
+
+    /// Trap instead of returning 0 when control reaches the closing brace
+    /// of a non-void function other than `main`
+    pub fn with_missing_return(mut self, missing_return: MissingReturn) -> Self {
+        self.missing_return = missing_return;
+        self
+    }
+
+    /// Mark functions `ssp`, `sspstrong` or `sspreq`, for LLVM to pick the
+    /// ones that get a stack canary as alecc would
+    pub fn with_stack_protector(mut self, stack_protector: StackProtector) -> Self {
+        self.stack_protector = stack_protector;
+        self
+    }
+
+    pub fn generate(&mut self, program: &Program) -> Result<String> {
+        self.layout.record_aggregates(program);
+        self.setjmp_callers = setjmp_callers(program);
+        for function in &program.functions {
+            self.signatures.insert(
+                function.name.clone(),
+                Signature {
+                    return_type: function.return_type.clone(),
+                    parameters: function
+                        .parameters
+                        .iter()
+                        .map(|(_, ty)| self.parameter_type(ty))
+                        .collect(),
+                    variadic: function.is_variadic,
+                },
+            );
+            if function.attributes.noreturn {
+                self.noreturn_functions.insert(function.name.clone());
+            }
+            if !Self::is_declaration(function) {
+                self.defined_functions.insert(function.name.clone());
+            }
+        }
+
+        let mut globals = Vec::new();
+        for GlobalVariable {
+            name,
+            var_type,
+            initializer,
+            storage,
+            thread_local,
+            attributes,
+            ..
+        } in &program.global_variables
+        {
+            self.globals.insert(name.clone(), var_type.clone());
+            if *thread_local {
+                self.thread_locals.insert(name.clone());
+            }
+            let thread_local = if *thread_local { "thread_local " } else { "" };
+            let mut global = match storage {
+                Some(GlobalStorage::Extern) => {
+                    globals.push(format!(
+                        "@{} = external {}global {}",
+                        name,
+                        thread_local,
+                        self.llvm_type(var_type)
+                    ));
+                    continue;
+                }
+                Some(GlobalStorage::Static) => {
+                    format!("@{} = internal {}global", name, thread_local)
+                }
+                None => format!("@{} = {}global", name, thread_local),
+            };
+            let init = self.constant_initializer(var_type, initializer.as_ref())?;
+            global.push_str(&format!(" {} {}", self.llvm_type(var_type), init));
+            if let Some(section) = &attributes.section {
+                global.push_str(&format!(", section \"{}\"", section));
+            }
+            if let Some(alignment) = attributes.aligned {
+                global.push_str(&format!(", align {}", alignment));
+            }
+            globals.push(global);
+        }
+
+        let mut functions = Vec::new();
+        for function in &program.functions {
+            if !Self::is_declaration(function) {
+                functions.push(self.generate_function(function)?);
+            }
+        }
+
+        // Constructors and destructors, run by the C runtime around main
+        for (array, constructors) in [("llvm.global_ctors", true), ("llvm.global_dtors", false)] {
+            let entries: Vec<String> = program
+                .functions
+                .iter()
+                .filter(|f| !Self::is_declaration(f))
+                .filter_map(|f| {
+                    let (marked, priority) = if constructors {
+                        (f.attributes.constructor, f.attributes.constructor_priority)
+                    } else {
+                        (f.attributes.destructor, f.attributes.destructor_priority)
+                    };
+                    // Entries without a priority run last, as 65535
+                    marked.then(|| {
+                        format!(
+                            "{{ i32, ptr, ptr }} {{ i32 {}, ptr @{}, ptr null }}",
+                            priority.unwrap_or(u16::MAX),
+                            f.name
+                        )
+                    })
+                })
+                .collect();
+            if !entries.is_empty() {
+                globals.push(format!(
+                    "@{} = appending global [{} x {{ i32, ptr, ptr }}] [{}]",
+                    array,
+                    entries.len(),
+                    entries.join(", ")
+                ));
+            }
+        }
+
+        let mut output = String::new();
+        output.push_str(&format!("target triple = \"{}\"\n\n", self.target.triple()));
+        for definition in &self.string_definitions {
+            output.push_str(definition);
+            output.push('\n');
+        }
+        if !self.string_definitions.is_empty() {
+            output.push('\n');
+        }
+        for global in &globals {
+            output.push_str(global);
+            output.push('\n');
+        }
+        if !globals.is_empty() {
+            output.push('\n');
+        }
+        for function in &functions {
+            output.push_str(function);
+            output.push('\n');
+        }
+
+        // Functions used but not defined in this translation unit
+        for name in &self.referenced_functions {
+            if self.defined_functions.contains(name) {
+                continue;
+            }
+            let mut declaration = match self.signatures.get(name) {
+                Some(signature) => format!(
+                    "declare {} @{}({})",
+                    self.llvm_type(&signature.return_type),
+                    name,
+                    self.parameter_list(signature)
+                ),
+                None => format!("declare i32 @{}(...)", name),
+            };
+            if self.noreturn_functions.contains(name) {
+                declaration.push_str(" noreturn");
+            }
+            if returns_twice(name) {
+                declaration.push_str(" returns_twice");
+            }
+            output.push_str(&declaration);
+            output.push('\n');
+        }
+        for declaration in self.intrinsics.values() {
+            output.push_str(declaration);
+            output.push('\n');
+        }
+
+        if let Some(ident) = &self.ident {
+            output.push_str(&format!(
+                "\n!llvm.ident = !{{!0}}\n!0 = !{{!\"{}\"}}\n",
+                escape_bytes(ident)
+            ));
+        }
+
+        Ok(output)
+    }
+
+    fn is_declaration(function: &Function) -> bool {
+        // Same convention as the assembly backend: an empty body is a prototype
+        matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+    }
+
+    /// Linkage prefix of a function definition
+    fn linkage(function: &Function) -> &'static str {
+        if function.is_static {
+            "internal "
+        } else if function.is_inline_definition() {
+            "linkonce_odr "
+        } else {
+            ""
+        }
+    }
+
+    fn generate_function(&mut self, function: &Function) -> Result<String> {
+        self.scopes = vec![HashMap::new()];
+        self.allocas.clear();
+        self.body.clear();
+        self.value_counter = 0;
+        self.current_block = "entry".to_string();
+        self.block_terminated = false;
+        self.return_type = self.resolve(&function.return_type);
+        self.memory_only = self.setjmp_callers.contains(&function.name);
+        self.break_labels.clear();
+        self.continue_labels.clear();
+        self.function_name = function.name.clone();
+        self.address_taken_labels.clear();
+
+        let mut parameters = Vec::new();
+        for (index, (name, ty)) in function.parameters.iter().enumerate() {
+            let ty = self.parameter_type(ty);
+            let llvm_type = self.llvm_type(&ty);
+            parameters.push(format!("{} %arg{}", llvm_type, index));
+
+            let slot = self.declare_local(name, &ty, None);
+            self.emit(&format!("store {} %arg{}, ptr {}", llvm_type, index, slot));
+        }
+        if function.is_variadic {
+            parameters.push("...".to_string());
+        }
+
+        self.generate_statement(&function.body)?;
+
+        if !self.block_terminated {
+            // Falling off the end of main returns 0; elsewhere the value is unspecified
+            let return_type = self.return_type.clone();
+            if matches!(return_type, Type::Void) {
+                self.emit("ret void");
+            } else if self.missing_return == MissingReturn::Trap && function.name != "main" {
+                self.intrinsics.insert(
+                    "llvm.trap",
+                    "declare void @llvm.trap() cold noreturn nounwind",
+                );
+                self.emit("call void @llvm.trap()");
+                self.emit("unreachable");
+            } else {
+                let zero = self.zero_value(&return_type);
+                self.emit(&format!("ret {} {}", self.llvm_type(&return_type), zero));
+            }
+        }
+
+        let targets: Vec<String> = self
+            .address_taken_labels
+            .iter()
+            .map(|label| format!("label %label.{}", label))
+            .collect();
+        let targets = targets.join(", ");
+        for line in &mut self.body {
+            if line.contains(INDIRECT_TARGETS_PLACEHOLDER) {
+                *line = line.replace(INDIRECT_TARGETS_PLACEHOLDER, &targets);
+            }
+        }
+
+        let mut attributes = String::new();
+        if function.attributes.noreturn {
+            attributes.push_str(" noreturn");
+        }
+        match self.stack_protector {
+            StackProtector::Off => {}
+            StackProtector::Basic => attributes.push_str(" ssp"),
+            StackProtector::Strong => attributes.push_str(" sspstrong"),
+            StackProtector::All => attributes.push_str(" sspreq"),
+        }
+        if let Some(section) = &function.attributes.section {
+            attributes.push_str(&format!(" section \"{}\"", section));
+        }
+        if let Some(alignment) = function.attributes.aligned {
+            attributes.push_str(&format!(" align {}", alignment));
+        }
+        let mut text = format!(
+            "define {}{} @{}({}){} {{\nentry:\n",
+            Self::linkage(function),
+            self.llvm_type(&function.return_type),
+            function.name,
+            parameters.join(", "),
+            attributes
+        );
+        for line in self.allocas.iter().chain(self.body.iter()) {
+            if line.ends_with(':') {
+                text.push_str(line);
+            } else {
+                text.push_str("  ");
+                text.push_str(line);
+            }
+            text.push('\n');
+        }
+        text.push_str("}\n");
+        Ok(text)
+    }
+
+    /// Failures inside the statement report where its innermost failing node was parsed
+    fn generate_statement(&mut self, statement: &Statement) -> Result<()> {
+        self.generate_statement_unlocated(statement)
+            .map_err(|error| error.at(statement.span))
+    }
+
+    fn generate_statement_unlocated(&mut self, statement: &Statement) -> Result<()> {
+        match &statement.kind {
+            StatementKind::Expression(expr) => {
+                self.generate_expression(expr)?;
+                // __builtin_trap and __builtin_unreachable end the block themselves
+                if is_noreturn_call(statement, &self.noreturn_functions) && !self.block_terminated {
+                    self.emit_terminator("unreachable");
+                }
+            }
+            StatementKind::Declaration {
+                name,
+                var_type,
+                initializer,
+                alignment,
+                ..
+            } => {
+                let ty = self.resolve(var_type);
+                if matches!(ty, Type::Void) {
+                    return Err(AleccError::CodegenError {
+                        message: format!("variable '{}' has incomplete type", name),
+                        span: None,
+                    });
+                }
+                let slot = self.declare_local(name, var_type, *alignment);
+                if let Some(init) = initializer {
+                    let value = self.generate_expression(init)?;
+                    let operand = self.convert(value, &ty)?;
+                    let volatile = self.is_volatile(var_type);
+                    self.store(&operand, &ty, &slot, volatile);
+                }
+            }
+            StatementKind::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                for stmt in statements {
+                    self.generate_statement(stmt)?;
+                }
+                self.scopes.pop();
+            }
+            StatementKind::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                let then_label = self.new_label("if.then");
+                let else_label = self.new_label("if.else");
+                let end_label = self.new_label("if.end");
+
+                let cond = self.generate_condition(condition)?;
+                let false_target = if else_stmt.is_some() {
+                    &else_label
+                } else {
+                    &end_label
+                };
+                self.emit_terminator(&format!(
+                    "br i1 {}, label %{}, label %{}",
+                    cond, then_label, false_target
+                ));
+
+                self.start_block(&then_label);
+                self.generate_statement(then_stmt)?;
+                self.emit_branch(&end_label);
+
+                if let Some(else_stmt) = else_stmt {
+                    self.start_block(&else_label);
+                    self.generate_statement(else_stmt)?;
+                    self.emit_branch(&end_label);
+                }
+
+                self.start_block(&end_label);
+            }
+            StatementKind::While { condition, body } => {
+                let cond_label = self.new_label("while.cond");
+                let body_label = self.new_label("while.body");
+                let end_label = self.new_label("while.end");
+
+                self.start_block(&cond_label);
+                let cond = self.generate_condition(condition)?;
+                self.emit_terminator(&format!(
+                    "br i1 {}, label %{}, label %{}",
+                    cond, body_label, end_label
+                ));
+
+                self.start_block(&body_label);
+                self.break_labels.push(end_label.clone());
+                self.continue_labels.push(cond_label.clone());
+                self.generate_statement(body)?;
+                self.break_labels.pop();
+                self.continue_labels.pop();
+                self.emit_branch(&cond_label);
+
+                self.start_block(&end_label);
+            }
+            StatementKind::DoWhile { body, condition } => {
+                let body_label = self.new_label("do.body");
+                let cond_label = self.new_label("do.cond");
+                let end_label = self.new_label("do.end");
+
+                self.start_block(&body_label);
+                self.break_labels.push(end_label.clone());
+                self.continue_labels.push(cond_label.clone());
+                self.generate_statement(body)?;
+                self.break_labels.pop();
+                self.continue_labels.pop();
+
+                self.start_block(&cond_label);
+                let cond = self.generate_condition(condition)?;
+                self.emit_terminator(&format!(
+                    "br i1 {}, label %{}, label %{}",
+                    cond, body_label, end_label
+                ));
+
+                self.start_block(&end_label);
+            }
+            StatementKind::For {
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                let cond_label = self.new_label("for.cond");
+                let body_label = self.new_label("for.body");
+                let inc_label = self.new_label("for.inc");
+                let end_label = self.new_label("for.end");
+
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.generate_statement(init)?;
+                }
+
+                self.start_block(&cond_label);
+                if let Some(condition) = condition {
+                    let cond = self.generate_condition(condition)?;
+                    self.emit_terminator(&format!(
+                        "br i1 {}, label %{}, label %{}",
+                        cond, body_label, end_label
+                    ));
+                }
+
+                self.start_block(&body_label);
+                self.break_labels.push(end_label.clone());
+                self.continue_labels.push(inc_label.clone());
+                self.generate_statement(body)?;
+                self.break_labels.pop();
+                self.continue_labels.pop();
+
+                self.start_block(&inc_label);
+                if let Some(increment) = increment {
+                    self.generate_expression(increment)?;
+                }
+                self.emit_branch(&cond_label);
+
+                self.start_block(&end_label);
+                self.scopes.pop();
+            }
+            StatementKind::Switch { expression, cases } => {
+                let value = self.generate_expression(expression)?;
+                let ty = self.promote(&value.ty);
+                let operand = self.convert(value, &ty)?;
+                let llvm_type = self.llvm_type(&ty);
+
+                let end_label = self.new_label("sw.end");
+                let mut default_label = end_label.clone();
+                let mut case_labels = Vec::new();
+                let mut table = Vec::new();
+                for (case_value, _) in cases {
+                    let label = self.new_label("sw.case");
+                    match case_value {
+                        Some(expr) => {
+                            let constant = self.integer_constant(expr).ok_or_else(|| {
+                                AleccError::CodegenError {
+                                    message: "case label is not an integer constant".to_string(),
+                                    span: None,
+                                }
+                            })?;
+                            table.push(format!("{} {}, label %{}", llvm_type, constant, label));
+                        }
+                        None => default_label = label.clone(),
+                    }
+                    case_labels.push(label);
+                }
+
+                self.emit_terminator(&format!(
+                    "switch {} {}, label %{} [ {} ]",
+                    llvm_type,
+                    operand,
+                    default_label,
+                    table.join(" ")
+                ));
+
+                self.break_labels.push(end_label.clone());
+                for ((_, statements), label) in cases.iter().zip(&case_labels) {
+                    // Falls through into the next case unless a break ends the block
+                    self.start_block(label);
+                    for stmt in statements {
+                        self.generate_statement(stmt)?;
+                    }
+                }
+                self.break_labels.pop();
+
+                self.start_block(&end_label);
+            }
+            StatementKind::Return(expr) => {
+                let return_type = self.return_type.clone();
+                match expr {
+                    Some(expr) if !matches!(return_type, Type::Void) => {
+                        let value = self.generate_expression(expr)?;
+                        let operand = self.convert(value, &return_type)?;
+                        self.emit_terminator(&format!(
+                            "ret {} {}",
+                            self.llvm_type(&return_type),
+                            operand
+                        ));
+                    }
+                    Some(expr) => {
+                        self.generate_expression(expr)?;
+                        self.emit_terminator("ret void");
+                    }
+                    None if matches!(return_type, Type::Void) => {
+                        self.emit_terminator("ret void");
+                    }
+                    None => {
+                        let zero = self.zero_value(&return_type);
+                        self.emit_terminator(&format!(
+                            "ret {} {}",
+                            self.llvm_type(&return_type),
+                            zero
+                        ));
+                    }
+                }
+            }
+            StatementKind::Break => {
+                let label =
+                    self.break_labels
+                        .last()
+                        .cloned()
+                        .ok_or_else(|| AleccError::CodegenError {
+                            message: "break statement not within a loop or switch".to_string(),
+                            span: None,
+                        })?;
+                self.emit_terminator(&format!("br label %{}", label));
+            }
+            StatementKind::Continue => {
+                let label = self.continue_labels.last().cloned().ok_or_else(|| {
+                    AleccError::CodegenError {
+                        message: "continue statement not within a loop".to_string(),
+                        span: None,
+                    }
+                })?;
+                self.emit_terminator(&format!("br label %{}", label));
+            }
+            StatementKind::Goto(label) => {
+                self.emit_terminator(&format!("br label %label.{}", label));
+            }
+            StatementKind::Label(label) => {
+                self.start_block(&format!("label.{}", label));
+            }
+            StatementKind::ComputedGoto(address) => {
+                let value = self.generate_expression(address)?;
+                let address = self.convert(value, &Type::Pointer(Box::new(Type::Void)))?;
+                self.emit_terminator(&format!(
+                    "indirectbr ptr {}, [{}]",
+                    address, INDIRECT_TARGETS_PLACEHOLDER
+                ));
+            }
+            StatementKind::Fallthrough => {}
+        }
+        Ok(())
+    }
+
+    fn generate_expression(&mut self, expression: &Expression) -> Result<Value> {
+        self.generate_expression_unlocated(expression)
+            .map_err(|error| error.at(expression.span))
+    }
+
+    fn generate_expression_unlocated(&mut self, expression: &Expression) -> Result<Value> {
+        match &expression.kind {
+            ExpressionKind::IntegerLiteral(value) => {
+                let ty = if i32::try_from(*value).is_ok() {
+                    Type::Int
+                } else if self.target.pointer_size() == 8 {
+                    Type::Long
+                } else {
+                    Type::LongLong
+                };
+                Ok(Value {
+                    operand: value.to_string(),
+                    ty,
+                })
+            }
+            ExpressionKind::FloatLiteral(value) => Ok(Value {
+                operand: Self::double_constant(*value),
+                ty: Type::Double,
+            }),
+            ExpressionKind::CharLiteral(c) => Ok(Value {
+                operand: (*c as u32).to_string(),
+                ty: Type::Int,
+            }),
+            ExpressionKind::BooleanLiteral(b) => Ok(Value {
+                operand: (*b as i32).to_string(),
+                ty: Type::Int,
+            }),
+            ExpressionKind::StringLiteral(content) => Ok(Value {
+                operand: self.string_constant(content),
+                ty: Type::Pointer(Box::new(Type::Char)),
+            }),
+            ExpressionKind::Identifier(name) => {
+                if self.lookup(name).is_none() && self.signatures.contains_key(name) {
+                    return Ok(self.function_value(name));
+                }
+                let place = self.generate_place(expression)?;
+                self.load_place(place)
+            }
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => match operator {
+                BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                    self.generate_logical(left, operator, right)
+                }
+                _ => {
+                    let lhs = self.generate_expression(left)?;
+                    let rhs = self.generate_expression(right)?;
+                    self.binary_values(operator, lhs, rhs)
+                }
+            },
+            ExpressionKind::Unary { operator, operand } => self.generate_unary(operator, operand),
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => self.generate_call(function, arguments),
+            ExpressionKind::Member { .. } | ExpressionKind::Index { .. } => {
+                let place = self.generate_place(expression)?;
+                self.load_place(place)
+            }
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } => {
+                let value = self.generate_expression(expression)?;
+                let ty = self.resolve(target_type);
+                if matches!(ty, Type::Void) {
+                    return Ok(Self::void_value());
+                }
+                let operand = self.convert(value, &ty)?;
+                Ok(Value { operand, ty })
+            }
+            ExpressionKind::Sizeof(ty) => Ok(Value {
+                operand: self.size_of(ty).to_string(),
+                ty: Type::Long,
+            }),
+            ExpressionKind::Assignment {
+                target,
+                operator,
+                value,
+            } => self.generate_assignment(target, operator, value),
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => self.generate_conditional(condition, then_expr, else_expr),
+            ExpressionKind::LabelAddress(label) => {
+                if !self.address_taken_labels.contains(label) {
+                    self.address_taken_labels.push(label.clone());
+                }
+                Ok(Value {
+                    operand: format!("blockaddress(@{}, %label.{})", self.function_name, label),
+                    ty: Type::Pointer(Box::new(Type::Void)),
+                })
+            }
+            ExpressionKind::VaArg { list, arg_type } => {
+                let address = self.va_list_address(list)?;
+                let ty = self.resolve(arg_type);
+                let result = self.new_value();
+                self.emit(&format!(
+                    "{} = va_arg ptr {}, {}",
+                    result,
+                    address,
+                    self.llvm_type(&ty)
+                ));
+                Ok(Value {
+                    operand: result,
+                    ty,
+                })
+            }
+        }
+    }
+
+    /// Compute the address of an lvalue
+    fn generate_place(&mut self, expression: &Expression) -> Result<Place> {
+        self.generate_place_unlocated(expression)
+            .map_err(|error| error.at(expression.span))
+    }
+
+    fn generate_place_unlocated(&mut self, expression: &Expression) -> Result<Place> {
+        match &expression.kind {
+            ExpressionKind::Identifier(name) => {
+                if let Some((slot, ty)) = self.declared(name) {
+                    Ok(self.place(slot, &ty, false))
+                } else if let Some(ty) = self.globals.get(name) {
+                    Ok(self.place(format!("@{}", name), ty, false))
+                } else {
+                    Err(AleccError::CodegenError {
+                        message: format!("use of undeclared identifier '{}'", name),
+                        span: None,
+                    })
+                }
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => {
+                let pointer = self.generate_expression(operand)?;
+                let pointee = self.pointee(&pointer.ty)?;
+                let volatile = self.points_to_volatile(&pointer.ty);
+                Ok(self.place(pointer.operand, &pointee, volatile))
+            }
+            ExpressionKind::Index { array, index } => {
+                let base = self.generate_expression(array)?;
+                let index = self.generate_expression(index)?;
+                // a[i] is *(a + i), whichever operand is the pointer
+                let (base, index) = if self.is_integer(&base.ty) {
+                    (index, base)
+                } else {
+                    (base, index)
+                };
+                let element = self.pointee(&base.ty)?;
+                let volatile = self.points_to_volatile(&base.ty);
+                let address = self.element_address(&base.operand, &element, index)?;
+                Ok(self.place(address, &element, volatile))
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let (base, aggregate, volatile) = if *is_arrow {
+                    let pointer = self.generate_expression(object)?;
+                    let pointee = self.pointee(&pointer.ty)?;
+                    let volatile = self.points_to_volatile(&pointer.ty);
+                    (pointer.operand, pointee, volatile)
+                } else if object.is_lvalue() {
+                    let object = self.generate_place(object)?;
+                    (object.address, object.ty, object.volatile)
+                } else {
+                    // A struct returned by value (`f().x`) gets a temporary to address
+                    let value = self.generate_expression(object)?;
+                    let slot = self.new_value();
+                    self.allocas
+                        .push(format!("{} = alloca {}", slot, self.llvm_type(&value.ty)));
+                    self.store(&value.operand, &value.ty, &slot, false);
+                    (slot, value.ty, false)
+                };
+
+                match self.resolve(&aggregate) {
+                    Type::Struct { fields, .. } => {
+                        let index = fields
+                            .iter()
+                            .position(|(name, _)| name == member)
+                            .ok_or_else(|| AleccError::CodegenError {
+                                message: format!("no member named '{}'", member),
+                                span: None,
+                            })?;
+                        let field_type = fields[index].1.clone();
+                        let address = self.new_value();
+                        self.emit(&format!(
+                            "{} = getelementptr inbounds {}, ptr {}, i32 0, i32 {}",
+                            address,
+                            self.llvm_type(&aggregate),
+                            base,
+                            index
+                        ));
+                        Ok(self.place(address, &field_type, volatile))
+                    }
+                    Type::Union { fields, .. } => {
+                        let field_type = fields
+                            .iter()
+                            .find(|(name, _)| name == member)
+                            .map(|(_, ty)| ty.clone())
+                            .ok_or_else(|| AleccError::CodegenError {
+                                message: format!("no member named '{}'", member),
+                                span: None,
+                            })?;
+                        // Every union member starts at offset 0
+                        Ok(self.place(base, &field_type, volatile))
+                    }
+                    _ => Err(AleccError::CodegenError {
+                        message: format!("member reference '{}' on a non-aggregate", member),
+                        span: None,
+                    }),
+                }
+            }
+            _ => Err(AleccError::CodegenError {
+                message: "expression is not assignable".to_string(),
+                span: None,
+            }),
+        }
+    }
+
+    fn generate_logical(
+        &mut self,
+        left: &Expression,
+        operator: &BinaryOperator,
+        right: &Expression,
+    ) -> Result<Value> {
+        let is_and = matches!(operator, BinaryOperator::LogicalAnd);
+        let rhs_label = self.new_label(if is_and { "land.rhs" } else { "lor.rhs" });
+        let end_label = self.new_label(if is_and { "land.end" } else { "lor.end" });
+
+        let lhs = self.generate_condition(left)?;
+        let lhs_block = self.current_block.clone();
+        if is_and {
+            self.emit_terminator(&format!(
+                "br i1 {}, label %{}, label %{}",
+                lhs, rhs_label, end_label
+            ));
+        } else {
+            self.emit_terminator(&format!(
+                "br i1 {}, label %{}, label %{}",
+                lhs, end_label, rhs_label
+            ));
+        }
+
+        self.start_block(&rhs_label);
+        let rhs = self.generate_condition(right)?;
+        let rhs_block = self.current_block.clone();
+        self.emit_branch(&end_label);
+
+        self.start_block(&end_label);
+        let phi = self.new_value();
+        self.emit(&format!(
+            "{} = phi i1 [ {}, %{} ], [ {}, %{} ]",
+            phi,
+            if is_and { "false" } else { "true" },
+            lhs_block,
+            rhs,
+            rhs_block
+        ));
+        let result = self.new_value();
+        self.emit(&format!("{} = zext i1 {} to i32", result, phi));
+        Ok(Value {
+            operand: result,
+            ty: Type::Int,
+        })
+    }
+
+    fn binary_values(
+        &mut self,
+        operator: &BinaryOperator,
+        lhs: Value,
+        rhs: Value,
+    ) -> Result<Value> {
+        let lhs_pointer = self.is_pointer(&lhs.ty);
+        let rhs_pointer = self.is_pointer(&rhs.ty);
+
+        // Pointer arithmetic
+        match operator {
+            BinaryOperator::Add if lhs_pointer && !rhs_pointer => {
+                return self.pointer_offset(lhs, rhs, false);
+            }
+            BinaryOperator::Add if rhs_pointer && !lhs_pointer => {
+                return self.pointer_offset(rhs, lhs, false);
+            }
+            BinaryOperator::Subtract if lhs_pointer && !rhs_pointer => {
+                return self.pointer_offset(lhs, rhs, true);
+            }
+            BinaryOperator::Subtract if lhs_pointer && rhs_pointer => {
+                let element = self.pointee(&lhs.ty)?;
+                let size = self.size_of(&element).max(1);
+                let int_type = self.llvm_type(&Type::Long);
+                let (l, r) = (self.new_value(), self.new_value());
+                self.emit(&format!(
+                    "{} = ptrtoint ptr {} to {}",
+                    l, lhs.operand, int_type
+                ));
+                self.emit(&format!(
+                    "{} = ptrtoint ptr {} to {}",
+                    r, rhs.operand, int_type
+                ));
+                let bytes = self.new_value();
+                self.emit(&format!("{} = sub {} {}, {}", bytes, int_type, l, r));
+                let result = self.new_value();
+                self.emit(&format!(
+                    "{} = sdiv exact {} {}, {}",
+                    result, int_type, bytes, size
+                ));
+                return Ok(Value {
+                    operand: result,
+                    ty: Type::Long,
+                });
+            }
+            _ => {}
+        }
+
+        if let Some(predicate) = Self::comparison_predicate(operator) {
+            if lhs_pointer || rhs_pointer {
+                let pointer_type = Type::Pointer(Box::new(Type::Void));
+                let l = self.convert(lhs, &pointer_type)?;
+                let r = self.convert(rhs, &pointer_type)?;
+                let flag = self.new_value();
+                self.emit(&format!(
+                    "{} = icmp {} ptr {}, {}",
+                    flag,
+                    Self::unsigned_predicate(predicate),
+                    l,
+                    r
+                ));
+                return self.bool_to_int(flag);
+            }
+
+            let ty = self.common_type(&lhs.ty, &rhs.ty);
+            let llvm_type = self.llvm_type(&ty);
+            let l = self.convert(lhs, &ty)?;
+            let r = self.convert(rhs, &ty)?;
+            let flag = self.new_value();
+            if self.is_floating(&ty) {
+                self.emit(&format!(
+                    "{} = fcmp {} {} {}, {}",
+                    flag,
+                    Self::float_predicate(predicate),
+                    llvm_type,
+                    l,
+                    r
+                ));
+            } else {
+                let predicate = if ty.is_unsigned() {
+                    Self::unsigned_predicate(predicate)
+                } else {
+                    predicate
+                };
+                self.emit(&format!(
+                    "{} = icmp {} {} {}, {}",
+                    flag, predicate, llvm_type, l, r
+                ));
+            }
+            return self.bool_to_int(flag);
+        }
+
+        // Shifts take the (promoted) type of the left operand
+        let ty = match operator {
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => self.promote(&lhs.ty),
+            _ => self.common_type(&lhs.ty, &rhs.ty),
+        };
+        let floating = self.is_floating(&ty);
+        let unsigned = ty.is_unsigned();
+        let instruction = match (operator, floating) {
+            (BinaryOperator::Add, false) => "add",
+            (BinaryOperator::Add, true) => "fadd",
+            (BinaryOperator::Subtract, false) => "sub",
+            (BinaryOperator::Subtract, true) => "fsub",
+            (BinaryOperator::Multiply, false) => "mul",
+            (BinaryOperator::Multiply, true) => "fmul",
+            (BinaryOperator::Divide, false) if unsigned => "udiv",
+            (BinaryOperator::Divide, false) => "sdiv",
+            (BinaryOperator::Divide, true) => "fdiv",
+            (BinaryOperator::Modulo, false) if unsigned => "urem",
+            (BinaryOperator::Modulo, false) => "srem",
+            (BinaryOperator::BitwiseAnd, false) => "and",
+            (BinaryOperator::BitwiseOr, false) => "or",
+            (BinaryOperator::BitwiseXor, false) => "xor",
+            (BinaryOperator::LeftShift, false) => "shl",
+            (BinaryOperator::RightShift, false) if unsigned => "lshr",
+            (BinaryOperator::RightShift, false) => "ashr",
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("invalid operands to binary {:?}", operator),
+                    span: None,
+                });
+            }
+        };
+
+        let llvm_type = self.llvm_type(&ty);
+        let l = self.convert(lhs, &ty)?;
+        let r = self.convert(rhs, &ty)?;
+        let result = self.new_value();
+        self.emit(&format!(
+            "{} = {} {} {}, {}",
+            result, instruction, llvm_type, l, r
+        ));
+        Ok(Value {
+            operand: result,
+            ty,
+        })
+    }
+
+    fn generate_unary(&mut self, operator: &UnaryOperator, operand: &Expression) -> Result<Value> {
+        match operator {
+            UnaryOperator::Plus => {
+                let value = self.generate_expression(operand)?;
+                let ty = self.promote(&value.ty);
+                let operand = self.convert(value, &ty)?;
+                Ok(Value { operand, ty })
+            }
+            UnaryOperator::Minus => {
+                let value = self.generate_expression(operand)?;
+                let ty = self.promote(&value.ty);
+                let llvm_type = self.llvm_type(&ty);
+                let operand = self.convert(value, &ty)?;
+                let result = self.new_value();
+                if self.is_floating(&ty) {
+                    self.emit(&format!("{} = fneg {} {}", result, llvm_type, operand));
+                } else {
+                    self.emit(&format!("{} = sub {} 0, {}", result, llvm_type, operand));
+                }
+                Ok(Value {
+                    operand: result,
+                    ty,
+                })
+            }
+            UnaryOperator::LogicalNot => {
+                let cond = self.generate_condition(operand)?;
+                let inverted = self.new_value();
+                self.emit(&format!("{} = xor i1 {}, true", inverted, cond));
+                self.bool_to_int(inverted)
+            }
+            UnaryOperator::BitwiseNot => {
+                let value = self.generate_expression(operand)?;
+                let ty = self.promote(&value.ty);
+                let llvm_type = self.llvm_type(&ty);
+                let operand = self.convert(value, &ty)?;
+                let result = self.new_value();
+                self.emit(&format!("{} = xor {} {}, -1", result, llvm_type, operand));
+                Ok(Value {
+                    operand: result,
+                    ty,
+                })
+            }
+            UnaryOperator::PreIncrement
+            | UnaryOperator::PreDecrement
+            | UnaryOperator::PostIncrement
+            | UnaryOperator::PostDecrement => {
+                let is_prefix = matches!(
+                    operator,
+                    UnaryOperator::PreIncrement | UnaryOperator::PreDecrement
+                );
+                let step = match operator {
+                    UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => {
+                        BinaryOperator::Add
+                    }
+                    _ => BinaryOperator::Subtract,
+                };
+
+                let place = self.generate_place(operand)?;
+                let ty = place.ty.clone();
+                let old = self.load_place(place.clone())?;
+                let one = Value {
+                    operand: "1".to_string(),
+                    ty: Type::Int,
+                };
+                let new = self.binary_values(&step, old.clone(), one)?;
+                let stored = self.convert(new, &ty)?;
+                self.store(&stored, &ty, &place.address, place.volatile);
+
+                if is_prefix {
+                    Ok(Value {
+                        operand: stored,
+                        ty,
+                    })
+                } else {
+                    Ok(old)
+                }
+            }
+            UnaryOperator::AddressOf => {
+                if let ExpressionKind::Identifier(name) = &operand.kind {
+                    if self.lookup(name).is_none() && self.signatures.contains_key(name) {
+                        return Ok(self.function_value(name));
+                    }
+                }
+                let place = self.generate_place(operand)?;
+                Ok(Value {
+                    operand: place.address,
+                    ty: Type::Pointer(Box::new(place.ty)),
+                })
+            }
+            UnaryOperator::Dereference => {
+                let pointer = self.generate_expression(operand)?;
+                let pointee = self.pointee(&pointer.ty)?;
+                if matches!(pointee, Type::Function { .. }) {
+                    // *fp designates the function itself
+                    return Ok(pointer);
+                }
+                let volatile = self.points_to_volatile(&pointer.ty);
+                self.load(pointer.operand, &pointee, volatile)
+            }
+        }
+    }
+
+    fn generate_call(&mut self, function: &Expression, arguments: &[Expression]) -> Result<Value> {
+        if let ExpressionKind::Identifier(name) = &function.kind {
+            match name.as_str() {
+                "__builtin_va_start" | "va_start" => {
+                    return self.generate_va_intrinsic("llvm.va_start", arguments, 1);
+                }
+                "__builtin_va_end" | "va_end" => {
+                    return self.generate_va_intrinsic("llvm.va_end", arguments, 1);
+                }
+                "__builtin_va_copy" | "va_copy" => {
+                    return self.generate_va_intrinsic("llvm.va_copy", arguments, 2);
+                }
+                "__builtin_alloca"
+                | "alloca"
+                | "__builtin_stack_save"
+                | "__builtin_stack_restore" => {
+                    return self.generate_stack_allocation(name, arguments);
+                }
+                name if is_builtin(name) => return self.generate_builtin(name, arguments),
+                name if !self.signatures.contains_key(name) && atomic(name).is_some() => {
+                    let atomic = atomic(name).expect("checked above");
+                    return self.generate_atomic(name, atomic, arguments);
+                }
+                _ => {}
+            }
+        }
+
+        // Direct calls use the declared prototype; anything else is called through a pointer
+        let (callee, signature) = match &function.kind {
+            ExpressionKind::Identifier(name)
+                if self.lookup(name).is_none() && !self.globals.contains_key(name) =>
+            {
+                self.referenced_functions.insert(name.clone());
+                (format!("@{}", name), self.signatures.get(name).cloned())
+            }
+            _ => {
+                let value = self.generate_expression(function)?;
+                let signature = match self.pointee(&value.ty) {
+                    Ok(Type::Function {
+                        return_type,
+                        parameters,
+                        variadic,
+                    }) => Some(Signature {
+                        return_type: *return_type,
+                        parameters,
+                        variadic,
+                    }),
+                    _ => None,
+                };
+                (value.operand, signature)
+            }
+        };
+
+        let mut operands = Vec::new();
+        for (index, argument) in arguments.iter().enumerate() {
+            let value = self.generate_expression(argument)?;
+            let parameter = signature
+                .as_ref()
+                .and_then(|s| s.parameters.get(index))
+                .map(|ty| self.resolve(ty));
+            // Arguments without a prototype get the default argument promotions
+            let ty = match parameter {
+                Some(ty) => ty,
+                None if matches!(self.resolve(&value.ty), Type::Float) => Type::Double,
+                None if self.is_integer(&value.ty) => self.promote(&value.ty),
+                None => self.resolve(&value.ty),
+            };
+            let llvm_type = self.llvm_type(&ty);
+            let operand = self.convert(value, &ty)?;
+            operands.push(format!("{} {}", llvm_type, operand));
+        }
+
+        let (return_type, function_type) = match &signature {
+            Some(signature) => {
+                let return_type = self.resolve(&signature.return_type);
+                let function_type = format!(
+                    "{} ({})",
+                    self.llvm_type(&return_type),
+                    self.parameter_list(signature)
+                );
+                (return_type, function_type)
+            }
+            None => (Type::Int, "i32 (...)".to_string()),
+        };
+
+        if matches!(return_type, Type::Void) {
+            self.emit(&format!(
+                "call {} {}({})",
+                function_type,
+                callee,
+                operands.join(", ")
+            ));
+            return Ok(Self::void_value());
+        }
+
+        let result = self.new_value();
+        self.emit(&format!(
+            "{} = call {} {}({})",
+            result,
+            function_type,
+            callee,
+            operands.join(", ")
+        ));
+        Ok(Value {
+            operand: result,
+            ty: return_type,
+        })
+    }
+
+    /// GCC's builtins: the bit operations as the `llvm.ctpop`, `llvm.ctlz`,
+    /// `llvm.cttz` and `llvm.bswap` intrinsics, memcpy and memset as
+    /// `llvm.memcpy` and `llvm.memset`, and the rest as calls to the library
+    /// function of the same name
+    fn generate_builtin(&mut self, name: &str, arguments: &[Expression]) -> Result<Value> {
+        let Some(Type::Function {
+            return_type,
+            parameters,
+            ..
+        }) = signature(name)
+        else {
+            return Err(AleccError::CodegenError {
+                message: format!("{} is not supported", name),
+                span: None,
+            });
+        };
+        if arguments.len() < parameters.len() {
+            return Err(AleccError::CodegenError {
+                message: format!("{} requires {} arguments", name, parameters.len()),
+                span: None,
+            });
+        }
+        let mut operands = Vec::new();
+        for (argument, parameter) in arguments.iter().zip(&parameters) {
+            let value = self.generate_expression(argument)?;
+            operands.push(self.convert(value, parameter)?);
+        }
+
+        if let Some(function) = library_function(name) {
+            let signature = Signature {
+                return_type: *return_type,
+                parameters,
+                variadic: false,
+            };
+            let return_type = self.resolve(&signature.return_type);
+            let call = format!(
+                "call {} @{}({})",
+                self.llvm_type(&return_type),
+                function,
+                signature
+                    .parameters
+                    .iter()
+                    .zip(&operands)
+                    .map(|(ty, operand)| format!("{} {}", self.llvm_type(ty), operand))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            // A prototype from a header wins over the builtin's
+            self.signatures
+                .entry(function.to_string())
+                .or_insert(signature);
+            self.referenced_functions.insert(function.to_string());
+            if matches!(return_type, Type::Void) {
+                self.emit(&call);
+                return Ok(Self::void_value());
+            }
+            let result = self.new_value();
+            self.emit(&format!("{} = {}", result, call));
+            return Ok(Value {
+                operand: result,
+                ty: return_type,
+            });
+        }
+
+        let builtin = name.trim_start_matches("__builtin_");
+        match builtin {
+            // The value is all that matters; the hint is dropped
+            "expect" => {
+                return Ok(Value {
+                    operand: operands.swap_remove(0),
+                    ty: Type::Long,
+                })
+            }
+            "unreachable" | "trap" => {
+                if builtin == "trap" {
+                    self.intrinsics.insert(
+                        "llvm.trap",
+                        "declare void @llvm.trap() cold noreturn nounwind",
+                    );
+                    self.emit("call void @llvm.trap()");
+                }
+                self.emit_terminator("unreachable");
+                return Ok(Self::void_value());
+            }
+            "memcpy" => {
+                self.intrinsics.insert(
+                    "llvm.memcpy.p0.p0.i64",
+                    "declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)",
+                );
+                self.emit(&format!(
+                    "call void @llvm.memcpy.p0.p0.i64(ptr {}, ptr {}, i64 {}, i1 false)",
+                    operands[0], operands[1], operands[2]
+                ));
+                return Ok(Value {
+                    operand: operands.swap_remove(0),
+                    ty: *return_type,
+                });
+            }
+            "memset" => {
+                self.intrinsics.insert(
+                    "llvm.memset.p0.i64",
+                    "declare void @llvm.memset.p0.i64(ptr, i8, i64, i1)",
+                );
+                let fill = self.convert(
+                    Value {
+                        operand: operands[1].clone(),
+                        ty: Type::Int,
+                    },
+                    &Type::UnsignedChar,
+                )?;
+                self.emit(&format!(
+                    "call void @llvm.memset.p0.i64(ptr {}, i8 {}, i64 {}, i1 false)",
+                    operands[0], fill, operands[2]
+                ));
+                return Ok(Value {
+                    operand: operands.swap_remove(0),
+                    ty: *return_type,
+                });
+            }
+            _ => {}
+        }
+
+        // The rest work on their one operand's width, and the counts are
+        // then narrowed to int
+        let operand_type = self.resolve(&parameters[0]);
+        let width = self.llvm_type(&operand_type);
+        let operation = builtin.trim_end_matches('l');
+        let (intrinsic, declaration) = match (operation, width.as_str()) {
+            ("popcount", "i32") => ("llvm.ctpop.i32", "declare i32 @llvm.ctpop.i32(i32)"),
+            ("popcount", "i64") => ("llvm.ctpop.i64", "declare i64 @llvm.ctpop.i64(i64)"),
+            ("clz", "i32") => ("llvm.ctlz.i32", "declare i32 @llvm.ctlz.i32(i32, i1)"),
+            ("clz", "i64") => ("llvm.ctlz.i64", "declare i64 @llvm.ctlz.i64(i64, i1)"),
+            ("ctz", "i32") => ("llvm.cttz.i32", "declare i32 @llvm.cttz.i32(i32, i1)"),
+            ("ctz", "i64") => ("llvm.cttz.i64", "declare i64 @llvm.cttz.i64(i64, i1)"),
+            ("bswap16", "i16") => ("llvm.bswap.i16", "declare i16 @llvm.bswap.i16(i16)"),
+            ("bswap32", "i32") => ("llvm.bswap.i32", "declare i32 @llvm.bswap.i32(i32)"),
+            ("bswap64", "i64") => ("llvm.bswap.i64", "declare i64 @llvm.bswap.i64(i64)"),
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("{} is not supported by the LLVM backend", name),
+                    span: None,
+                })
+            }
+        };
+        self.intrinsics.insert(intrinsic, declaration);
+        // Like GCC's, a count of the zero bits of 0 is undefined
+        let zero_is_poison = if matches!(operation, "clz" | "ctz") {
+            ", i1 true"
+        } else {
+            ""
+        };
+        let result = self.new_value();
+        self.emit(&format!(
+            "{} = call {} @{}({} {}{})",
+            result, width, intrinsic, width, operands[0], zero_is_poison
+        ));
+        let operand = self.convert(
+            Value {
+                operand: result,
+                ty: operand_type,
+            },
+            &return_type,
+        )?;
+        Ok(Value {
+            operand,
+            ty: *return_type,
+        })
+    }
+
+    /// An `__atomic_*` or `__sync_*` builtin as a `load atomic`, `store
+    /// atomic`, `atomicrmw`, `cmpxchg` or `fence` with the memory order given.
+    /// Pointer operands are accessed as integers of the same size
+    fn generate_atomic(
+        &mut self,
+        name: &str,
+        atomic: Atomic,
+        arguments: &[Expression],
+    ) -> Result<Value> {
+        let error = |message: String| AleccError::CodegenError {
+            message,
+            span: arguments.first().map(|argument| argument.span),
+        };
+        // An order that isn't a constant is taken as the strongest
+        let order = atomic
+            .order_argument
+            .and_then(|index| arguments.get(index))
+            .map_or(atomic.order, |argument| {
+                self.integer_constant(argument)
+                    .map_or(MemoryOrder::SeqCst, MemoryOrder::from_value)
+            });
+
+        if atomic.operation == AtomicOperation::Fence {
+            // A relaxed fence orders nothing
+            if order != MemoryOrder::Relaxed {
+                let scope = if name == "__atomic_signal_fence" {
+                    "syncscope(\"singlethread\") "
+                } else {
+                    ""
+                };
+                self.emit(&format!("fence {}{}", scope, ordering(order)));
+            }
+            return Ok(Self::void_value());
+        }
+
+        // The pointers come first, then the values
+        let zero = Expression::new(ExpressionKind::IntegerLiteral(0), arguments[0].span);
+        let (count, pointers) = match atomic.operation {
+            AtomicOperation::Load => (1, 1),
+            AtomicOperation::CompareExchange => (3, 2),
+            AtomicOperation::CompareAndSwap { .. } => (3, 1),
+            _ => (2, 1),
+        };
+        let mut values: Vec<&Expression> = arguments.iter().take(count).collect();
+        // `__sync_lock_release(p)` stores 0
+        if name == "__sync_lock_release" {
+            values.push(&zero);
+        }
+        if values.len() < count {
+            return Err(error(format!("too few arguments to function '{}'", name)));
+        }
+        let mut addresses = Vec::new();
+        for value in &values[..pointers] {
+            addresses.push(self.generate_expression(value)?);
+        }
+        let operand = match self.pointee(&addresses[0].ty) {
+            Ok(ty)
+                if (self.is_integer(&ty) || matches!(ty, Type::Pointer(_)))
+                    && self.size_of(&ty) <= self.target.pointer_size() =>
+            {
+                ty
+            }
+            _ => {
+                return Err(error(format!(
+                    "the first argument of '{}' must point to an integer or pointer",
+                    name
+                )))
+            }
+        };
+        let integer = if matches!(operand, Type::Pointer(_)) {
+            Type::UnsignedLong
+        } else {
+            operand.clone()
+        };
+        let mut operands = Vec::new();
+        for value in &values[pointers..] {
+            let value = self.generate_expression(value)?;
+            operands.push(self.convert(value, &integer)?);
+        }
+        let address = addresses[0].operand.clone();
+        let ty = self.llvm_type(&integer);
+        let align = self.size_of(&integer);
+
+        let old = self.new_value();
+        let result = match atomic.operation {
+            AtomicOperation::Load => {
+                self.emit(&format!(
+                    "{} = load atomic {}, ptr {} {}, align {}",
+                    old,
+                    ty,
+                    address,
+                    load_ordering(order),
+                    align
+                ));
+                old
+            }
+            AtomicOperation::Store => {
+                self.emit(&format!(
+                    "store atomic {} {}, ptr {} {}, align {}",
+                    ty,
+                    operands[0],
+                    address,
+                    store_ordering(order),
+                    align
+                ));
+                return Ok(Self::void_value());
+            }
+            AtomicOperation::Exchange => {
+                self.emit(&format!(
+                    "{} = atomicrmw xchg ptr {}, {} {} {}",
+                    old,
+                    address,
+                    ty,
+                    operands[0],
+                    ordering(order)
+                ));
+                old
+            }
+            AtomicOperation::Fetch { op, returns_new } => {
+                let (operation, instruction) = match op {
+                    AtomicOp::Add => ("add", "add"),
+                    AtomicOp::Sub => ("sub", "sub"),
+                    AtomicOp::And => ("and", "and"),
+                    AtomicOp::Or => ("or", "or"),
+                    AtomicOp::Xor => ("xor", "xor"),
+                    AtomicOp::Nand => ("nand", "and"),
+                };
+                self.emit(&format!(
+                    "{} = atomicrmw {} ptr {}, {} {} {}",
+                    old,
+                    operation,
+                    address,
+                    ty,
+                    operands[0],
+                    ordering(order)
+                ));
+                if returns_new {
+                    // The operation again, on the value it was applied to
+                    let new = self.new_value();
+                    self.emit(&format!(
+                        "{} = {} {} {}, {}",
+                        new, instruction, ty, old, operands[0]
+                    ));
+                    if op == AtomicOp::Nand {
+                        let inverted = self.new_value();
+                        self.emit(&format!("{} = xor {} {}, -1", inverted, ty, new));
+                        inverted
+                    } else {
+                        new
+                    }
+                } else {
+                    old
+                }
+            }
+            AtomicOperation::CompareExchange | AtomicOperation::CompareAndSwap { .. } => {
+                let (expected, desired) = match atomic.operation {
+                    AtomicOperation::CompareExchange => {
+                        let expected = self.new_value();
+                        self.emit(&format!(
+                            "{} = load {}, ptr {}",
+                            expected, ty, addresses[1].operand
+                        ));
+                        (expected, operands[0].clone())
+                    }
+                    _ => (operands[0].clone(), operands[1].clone()),
+                };
+                let pair = self.new_value();
+                self.emit(&format!(
+                    "{} = cmpxchg ptr {}, {} {}, {} {} {} {}",
+                    pair,
+                    address,
+                    ty,
+                    expected,
+                    ty,
+                    desired,
+                    ordering(order),
+                    load_ordering(order)
+                ));
+                let swapped = self.new_value();
+                self.emit(&format!(
+                    "{} = extractvalue {{ {}, i1 }} {}, 0",
+                    old, ty, pair
+                ));
+                self.emit(&format!(
+                    "{} = extractvalue {{ {}, i1 }} {}, 1",
+                    swapped, ty, pair
+                ));
+                if atomic.operation == AtomicOperation::CompareExchange {
+                    // `*expected` gets the value found, if it wasn't that
+                    let failed = self.new_label("cmpxchg.fail");
+                    let end = self.new_label("cmpxchg.end");
+                    self.emit_terminator(&format!(
+                        "br i1 {}, label %{}, label %{}",
+                        swapped, end, failed
+                    ));
+                    self.start_block(&failed);
+                    self.store(&old, &integer, &addresses[1].operand, false);
+                    self.start_block(&end);
+                }
+                if atomic.operation
+                    == (AtomicOperation::CompareAndSwap {
+                        returns_bool: false,
+                    })
+                {
+                    old
+                } else {
+                    let result = self.new_value();
+                    self.emit(&format!("{} = zext i1 {} to i8", result, swapped));
+                    return Ok(Value {
+                        operand: result,
+                        ty: Type::Bool,
+                    });
+                }
+            }
+            AtomicOperation::Fence => unreachable!("fences return early"),
+        };
+        let result = self.convert(
+            Value {
+                operand: result,
+                ty: integer,
+            },
+            &operand,
+        )?;
+        Ok(Value {
+            operand: result,
+            ty: operand,
+        })
+    }
+
+    /// alloca as a dynamic `alloca` in the body, which LLVM frees on return,
+    /// and the builtins freeing variable length arrays as `llvm.stacksave`
+    /// and `llvm.stackrestore`
+    fn generate_stack_allocation(&mut self, name: &str, arguments: &[Expression]) -> Result<Value> {
+        let operand = match (name, arguments) {
+            ("__builtin_stack_save", _) => None,
+            (_, [argument]) => Some(self.generate_expression(argument)?),
+            _ => {
+                return Err(AleccError::CodegenError {
+                    message: format!("{} requires one argument", name),
+                    span: None,
+                })
+            }
+        };
+        let void_pointer = Type::Pointer(Box::new(Type::Void));
+        match (name, operand) {
+            ("__builtin_stack_restore", Some(saved)) => {
+                self.intrinsics
+                    .insert("llvm.stackrestore", "declare void @llvm.stackrestore(ptr)");
+                let saved = self.convert(saved, &void_pointer)?;
+                self.emit(&format!("call void @llvm.stackrestore(ptr {})", saved));
+                Ok(Self::void_value())
+            }
+            (_, Some(size)) => {
+                let size = self.convert(size, &Type::UnsignedLong)?;
+                let result = self.new_value();
+                self.emit(&format!("{} = alloca i8, i64 {}, align 16", result, size));
+                Ok(Value {
+                    operand: result,
+                    ty: void_pointer,
+                })
+            }
+            (_, None) => {
+                self.intrinsics
+                    .insert("llvm.stacksave", "declare ptr @llvm.stacksave()");
+                let result = self.new_value();
+                self.emit(&format!("{} = call ptr @llvm.stacksave()", result));
+                Ok(Value {
+                    operand: result,
+                    ty: void_pointer,
+                })
+            }
+        }
+    }
+
+    fn generate_va_intrinsic(
+        &mut self,
+        intrinsic: &'static str,
+        arguments: &[Expression],
+        arity: usize,
+    ) -> Result<Value> {
+        if arguments.len() < arity {
+            return Err(AleccError::CodegenError {
+                message: format!("{} requires {} va_list argument(s)", intrinsic, arity),
+                span: None,
+            });
+        }
+
+        let declaration = match intrinsic {
+            "llvm.va_copy" => "declare void @llvm.va_copy(ptr, ptr)",
+            "llvm.va_start" => "declare void @llvm.va_start(ptr)",
+            _ => "declare void @llvm.va_end(ptr)",
+        };
+        self.intrinsics.insert(intrinsic, declaration);
+
+        let mut operands = Vec::new();
+        for argument in &arguments[..arity] {
+            let address = self.va_list_address(argument)?;
+            operands.push(format!("ptr {}", address));
+        }
+        self.emit(&format!(
+            "call void @{}({})",
+            intrinsic,
+            operands.join(", ")
+        ));
+        Ok(Self::void_value())
+    }
+
+    /// Address of the va_list object an expression designates. On amd64 a
+    /// va_list parameter is really a pointer, since the array type decays.
+    fn va_list_address(&mut self, list: &Expression) -> Result<String> {
+        let place = self.generate_place(list)?;
+        match place.ty {
+            Type::Pointer(_) => Ok(self.load_place(place)?.operand),
+            _ => Ok(place.address),
+        }
+    }
+
+    fn generate_assignment(
+        &mut self,
+        target: &Expression,
+        operator: &AssignmentOperator,
+        value: &Expression,
+    ) -> Result<Value> {
+        let place = self.generate_place(target)?;
+        let ty = place.ty.clone();
+
+        let operator = operator.binary_operator();
+
+        let rhs = self.generate_expression(value)?;
+        let result = match operator {
+            None => rhs,
+            Some(operator) => {
+                let current = self.load_place(place.clone())?;
+                self.binary_values(&operator, current, rhs)?
+            }
+        };
+
+        let operand = self.convert(result, &ty)?;
+        self.store(&operand, &ty, &place.address, place.volatile);
+        Ok(Value { operand, ty })
+    }
+
+    fn generate_conditional(
+        &mut self,
+        condition: &Expression,
+        then_expr: &Expression,
+        else_expr: &Expression,
+    ) -> Result<Value> {
+        let then_label = self.new_label("cond.true");
+        let else_label = self.new_label("cond.false");
+        let end_label = self.new_label("cond.end");
+
+        let cond = self.generate_condition(condition)?;
+        self.emit_terminator(&format!(
+            "br i1 {}, label %{}, label %{}",
+            cond, then_label, else_label
+        ));
+
+        // Each arm is generated into its own buffer: the result type, and so the
+        // conversion at the end of each arm, is only known once both are done
+        let saved = std::mem::take(&mut self.body);
+        self.start_block(&then_label);
+        let then_value = self.generate_expression(then_expr)?;
+        let then_body = std::mem::take(&mut self.body);
+        let then_block = self.current_block.clone();
+
+        self.block_terminated = true;
+        self.start_block(&else_label);
+        let else_value = self.generate_expression(else_expr)?;
+        let else_body = std::mem::replace(&mut self.body, saved);
+        let else_block = self.current_block.clone();
+
+        let ty = if self.is_arithmetic(&then_value.ty) && self.is_arithmetic(&else_value.ty) {
+            self.common_type(&then_value.ty, &else_value.ty)
+        } else if self.is_pointer(&then_value.ty) {
+            self.resolve(&then_value.ty)
+        } else {
+            self.resolve(&else_value.ty)
+        };
+
+        self.body.extend(then_body);
+        self.current_block = then_block;
+        self.block_terminated = false;
+        let then_operand = if matches!(ty, Type::Void) {
+            String::new()
+        } else {
+            self.convert(then_value, &ty)?
+        };
+        let then_block = self.current_block.clone();
+        self.emit_branch(&end_label);
+
+        self.body.extend(else_body);
+        self.current_block = else_block;
+        self.block_terminated = false;
+        let else_operand = if matches!(ty, Type::Void) {
+            String::new()
+        } else {
+            self.convert(else_value, &ty)?
+        };
+        let else_block = self.current_block.clone();
+        self.emit_branch(&end_label);
+
+        self.start_block(&end_label);
+        if matches!(ty, Type::Void) {
+            return Ok(Self::void_value());
+        }
+        let result = self.new_value();
+        self.emit(&format!(
+            "{} = phi {} [ {}, %{} ], [ {}, %{} ]",
+            result,
+            self.llvm_type(&ty),
+            then_operand,
+            then_block,
+            else_operand,
+            else_block
+        ));
+        Ok(Value {
+            operand: result,
+            ty,
+        })
+    }
+
+    /// Evaluate an expression as a branch condition (`i1`)
+    fn generate_condition(&mut self, expression: &Expression) -> Result<String> {
+        let value = self.generate_expression(expression)?;
+        self.truth_value(value)
+    }
+
+    fn truth_value(&mut self, value: Value) -> Result<String> {
+        let ty = self.resolve(&value.ty);
+        let result = self.new_value();
+        if self.is_floating(&ty) {
+            self.emit(&format!(
+                "{} = fcmp une {} {}, 0.0",
+                result,
+                self.llvm_type(&ty),
+                value.operand
+            ));
+        } else if self.is_pointer(&ty) {
+            self.emit(&format!("{} = icmp ne ptr {}, null", result, value.operand));
+        } else if self.is_integer(&ty) {
+            self.emit(&format!(
+                "{} = icmp ne {} {}, 0",
+                result,
+                self.llvm_type(&ty),
+                value.operand
+            ));
+        } else {
+            return Err(AleccError::CodegenError {
+                message: "scalar value required in condition".to_string(),
+                span: None,
+            });
+        }
+        Ok(result)
+    }
+
+    fn bool_to_int(&mut self, flag: String) -> Result<Value> {
+        let result = self.new_value();
+        self.emit(&format!("{} = zext i1 {} to i32", result, flag));
+        Ok(Value {
+            operand: result,
+            ty: Type::Int,
+        })
+    }
+
+    /// An lvalue of declared type `ty`, volatile if its type or its container is
+    fn place(&self, address: String, ty: &Type, volatile: bool) -> Place {
+        Place {
+            address,
+            ty: self.resolve(ty),
+            volatile: volatile || self.is_volatile(ty),
+        }
+    }
+
+    fn load_place(&mut self, place: Place) -> Result<Value> {
+        self.load(place.address, &place.ty, place.volatile)
+    }
+
+    /// Load an object of type `ty`; arrays decay to a pointer to their first element
+    fn load(&mut self, address: String, ty: &Type, volatile: bool) -> Result<Value> {
+        let ty = self.resolve(ty);
+        match ty {
+            Type::Array(element, _) => Ok(Value {
+                operand: address,
+                ty: Type::Pointer(element),
+            }),
+            Type::VaList if self.target == Target::Amd64 => Ok(Value {
+                operand: address,
+                ty: Type::Pointer(Box::new(Type::VaList)),
+            }),
+            Type::Function { .. } => Ok(Value {
+                operand: address,
+                ty: Type::Pointer(Box::new(ty)),
+            }),
+            _ => {
+                let result = self.new_value();
+                self.emit(&format!(
+                    "{} = load {}{}, ptr {}",
+                    result,
+                    if volatile { "volatile " } else { "" },
+                    self.llvm_type(&ty),
+                    address
+                ));
+                Ok(Value {
+                    operand: result,
+                    ty,
+                })
+            }
+        }
+    }
+
+    fn pointer_offset(&mut self, pointer: Value, index: Value, negate: bool) -> Result<Value> {
+        let element = self.pointee(&pointer.ty)?;
+        let index = if negate {
+            let zero = Value {
+                operand: "0".to_string(),
+                ty: Type::Int,
+            };
+            self.binary_values(&BinaryOperator::Subtract, zero, index)?
+        } else {
+            index
+        };
+        let operand = self.element_address(&pointer.operand, &element, index)?;
+        Ok(Value {
+            operand,
+            ty: self.resolve(&pointer.ty),
+        })
+    }
+
+    fn element_address(&mut self, base: &str, element: &Type, index: Value) -> Result<String> {
+        if !self.is_integer(&index.ty) {
+            return Err(AleccError::CodegenError {
+                message: "array subscript is not an integer".to_string(),
+                span: None,
+            });
+        }
+        // Arithmetic on void * steps in bytes, as GCC does
+        let element_type = match self.resolve(element) {
+            Type::Void => "i8".to_string(),
+            ty => self.llvm_type(&ty),
+        };
+        let index_type = Type::Long;
+        let index_llvm = self.llvm_type(&index_type);
+        let index = self.convert(index, &index_type)?;
+        let address = self.new_value();
+        self.emit(&format!(
+            "{} = getelementptr inbounds {}, ptr {}, {} {}",
+            address, element_type, base, index_llvm, index
+        ));
+        Ok(address)
+    }
+
+    /// Convert a value to `to`, returning the resulting operand
+    fn convert(&mut self, value: Value, to: &Type) -> Result<String> {
+        let from = self.resolve(&value.ty);
+        let to = self.resolve(to);
+        let from_llvm = self.llvm_type(&from);
+        let to_llvm = self.llvm_type(&to);
+
+        if matches!(to, Type::Bool) && !matches!(from, Type::Bool) {
+            let flag = self.truth_value(value)?;
+            let result = self.new_value();
+            self.emit(&format!("{} = zext i1 {} to i8", result, flag));
+            return Ok(result);
+        }
+        if from_llvm == to_llvm {
+            return Ok(value.operand);
+        }
+
+        let instruction = if self.is_integer(&from) && self.is_integer(&to) {
+            let (from_bits, to_bits) = (self.integer_bits(&from), self.integer_bits(&to));
+            if from_bits < to_bits {
+                // _Bool only ever holds 0 or 1
+                if matches!(from, Type::Bool) || from.is_unsigned() {
+                    "zext"
+                } else {
+                    "sext"
+                }
+            } else {
+                "trunc"
+            }
+        } else if self.is_integer(&from) && self.is_floating(&to) {
+            if from.is_unsigned() {
+                "uitofp"
+            } else {
+                "sitofp"
+            }
+        } else if self.is_floating(&from) && self.is_integer(&to) {
+            if to.is_unsigned() {
+                "fptoui"
+            } else {
+                "fptosi"
+            }
+        } else if self.is_floating(&from) && self.is_floating(&to) {
+            if self.size_of(&from) < self.size_of(&to) {
+                "fpext"
+            } else {
+                "fptrunc"
+            }
+        } else if self.is_pointer(&from) && self.is_integer(&to) {
+            "ptrtoint"
+        } else if self.is_integer(&from) && self.is_pointer(&to) {
+            if value.operand == "0" {
+                return Ok("null".to_string());
+            }
+            "inttoptr"
+        } else {
+            return Err(AleccError::CodegenError {
+                message: format!("cannot convert {:?} to {:?}", from, to),
+                span: None,
+            });
+        };
+
+        let result = self.new_value();
+        self.emit(&format!(
+            "{} = {} {} {} to {}",
+            result, instruction, from_llvm, value.operand, to_llvm
+        ));
+        Ok(result)
+    }
+
+    fn comparison_predicate(operator: &BinaryOperator) -> Option<&'static str> {
+        match operator {
+            BinaryOperator::Equal => Some("eq"),
+            BinaryOperator::NotEqual => Some("ne"),
+            BinaryOperator::Less => Some("slt"),
+            BinaryOperator::Greater => Some("sgt"),
+            BinaryOperator::LessEqual => Some("sle"),
+            BinaryOperator::GreaterEqual => Some("sge"),
+            _ => None,
+        }
+    }
+
+    fn unsigned_predicate(predicate: &str) -> &'static str {
+        match predicate {
+            "eq" => "eq",
+            "ne" => "ne",
+            "slt" => "ult",
+            "sgt" => "ugt",
+            "sle" => "ule",
+            _ => "uge",
+        }
+    }
+
+    fn float_predicate(predicate: &str) -> &'static str {
+        match predicate {
+            "eq" => "oeq",
+            "ne" => "une",
+            "slt" => "olt",
+            "sgt" => "ogt",
+            "sle" => "ole",
+            _ => "oge",
+        }
+    }
+
+    /// Integer promotion: anything narrower than int becomes int
+    fn promote(&self, ty: &Type) -> Type {
+        let ty = self.resolve(ty);
+        if self.is_integer(&ty) && self.integer_bits(&ty) < 32 {
+            Type::Int
+        } else {
+            ty
+        }
+    }
+
+    /// The usual arithmetic conversions
+    fn common_type(&self, a: &Type, b: &Type) -> Type {
+        let (a, b) = (self.promote(a), self.promote(b));
+        if matches!(a, Type::Double) || matches!(b, Type::Double) {
+            Type::Double
+        } else if matches!(a, Type::Float) || matches!(b, Type::Float) {
+            Type::Float
+        } else if self.integer_bits(&a) > self.integer_bits(&b) {
+            a
+        } else if self.integer_bits(&a) < self.integer_bits(&b) || b.is_unsigned() {
+            b
+        } else {
+            a
+        }
+    }
+
+    fn is_integer(&self, ty: &Type) -> bool {
+        matches!(
+            self.resolve(ty),
+            Type::Char
+                | Type::Short
+                | Type::Int
+                | Type::Long
+                | Type::LongLong
+                | Type::UnsignedChar
+                | Type::UnsignedShort
+                | Type::UnsignedInt
+                | Type::UnsignedLong
+                | Type::UnsignedLongLong
+                | Type::Bool
+        )
+    }
+
+    fn is_floating(&self, ty: &Type) -> bool {
+        matches!(self.resolve(ty), Type::Float | Type::Double)
+    }
+
+    fn is_arithmetic(&self, ty: &Type) -> bool {
+        self.is_integer(ty) || self.is_floating(ty)
+    }
+
+    fn is_pointer(&self, ty: &Type) -> bool {
+        matches!(self.resolve(ty), Type::Pointer(_) | Type::Array(_, _))
+    }
+
+    fn integer_bits(&self, ty: &Type) -> usize {
+        self.size_of(ty) * 8
+    }
+
+    fn store(&mut self, operand: &str, ty: &Type, address: &str, volatile: bool) {
+        self.emit(&format!(
+            "store {}{} {}, ptr {}",
+            if volatile { "volatile " } else { "" },
+            self.llvm_type(ty),
+            operand,
+            address
+        ));
+    }
+
+    /// Whether accesses to an object declared with type `ty` must be volatile
+    fn is_volatile(&self, ty: &Type) -> bool {
+        self.memory_only || ty.qualifiers().is_volatile
+    }
+
+    /// Whether a pointer (or array) of type `ty` designates volatile objects
+    fn points_to_volatile(&self, ty: &Type) -> bool {
+        match self.resolve(ty) {
+            Type::Pointer(inner) | Type::Array(inner, _) => self.is_volatile(&inner),
+            _ => self.memory_only,
+        }
+    }
+
+    fn pointee(&self, ty: &Type) -> Result<Type> {
+        match self.resolve(ty) {
+            Type::Pointer(inner) | Type::Array(inner, _) => Ok(self.resolve(&inner)),
+            _ => Err(AleccError::CodegenError {
+                message: "indirection requires pointer operand".to_string(),
+                span: None,
+            }),
+        }
+    }
+
+    fn resolve(&self, ty: &Type) -> Type {
+        self.layout.resolve(ty)
+    }
+
+    /// Parameters of array (and, on amd64, va_list) type are adjusted to pointers
+    fn parameter_type(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Type::Array(element, _) => Type::Pointer(element),
+            Type::VaList if self.target == Target::Amd64 => Type::Pointer(Box::new(Type::VaList)),
+            resolved => resolved,
+        }
+    }
+
+    fn parameter_list(&self, signature: &Signature) -> String {
+        let mut parameters: Vec<String> = signature
+            .parameters
+            .iter()
+            .map(|ty| self.llvm_type(ty))
+            .collect();
+        if signature.variadic {
+            parameters.push("...".to_string());
+        }
+        parameters.join(", ")
+    }
+
+    fn llvm_type(&self, ty: &Type) -> String {
+        match self.resolve(ty) {
+            Type::Void => "void".to_string(),
+            Type::Char | Type::UnsignedChar | Type::Bool => "i8".to_string(),
+            Type::Short | Type::UnsignedShort => "i16".to_string(),
+            Type::Int | Type::UnsignedInt => "i32".to_string(),
+            Type::Long | Type::UnsignedLong => format!("i{}", self.target.pointer_size() * 8),
+            Type::LongLong | Type::UnsignedLongLong => "i64".to_string(),
+            Type::Float => "float".to_string(),
+            Type::Double => "double".to_string(),
+            Type::Pointer(_) | Type::Function { .. } => "ptr".to_string(),
+            Type::Array(element, size) => {
+                format!("[{} x {}]", size.unwrap_or(0), self.llvm_type(&element))
+            }
+            Type::Struct { fields, packed, .. } => {
+                let fields: Vec<String> = fields.iter().map(|(_, ty)| self.llvm_type(ty)).collect();
+                if packed {
+                    format!("<{{ {} }}>", fields.join(", "))
+                } else {
+                    format!("{{ {} }}", fields.join(", "))
+                }
+            }
+            Type::Union { fields, .. } => {
+                // Most strictly aligned member first, padded out to the full size
+                let size = self.size_of(ty);
+                match fields.iter().max_by_key(|(_, ty)| self.align_of(ty)) {
+                    Some((_, member)) => {
+                        let padding = size - self.size_of(member);
+                        if padding > 0 {
+                            format!("{{ {}, [{} x i8] }}", self.llvm_type(member), padding)
+                        } else {
+                            format!("{{ {} }}", self.llvm_type(member))
+                        }
+                    }
+                    None => "{}".to_string(),
+                }
+            }
+            Type::VaList => match self.target {
+                Target::I386 => "ptr".to_string(),
+                Target::Amd64 => "[1 x { i32, i32, ptr, ptr }]".to_string(),
+                Target::Arm64 => "{ ptr, ptr, ptr, i32, i32 }".to_string(),
+            },
+            Type::Enum { .. } | Type::Typedef(..) | Type::Qualified(..) => {
+                unreachable!("resolved above")
+            }
+        }
+    }
+
+    fn size_of(&self, ty: &Type) -> usize {
+        self.layout.size_of(ty)
+    }
+
+    fn align_of(&self, ty: &Type) -> usize {
+        self.layout.align_of(ty)
+    }
+
+    fn zero_value(&self, ty: &Type) -> String {
+        match self.resolve(ty) {
+            Type::Float | Type::Double => "0.0".to_string(),
+            Type::Pointer(_) | Type::Function { .. } => "null".to_string(),
+            Type::Array(..) | Type::Struct { .. } | Type::Union { .. } | Type::VaList => {
+                "zeroinitializer".to_string()
+            }
+            _ => "0".to_string(),
+        }
+    }
+
+    /// Initializer of a global: a constant scalar, or the address of a global,
+    /// function or string literal plus a constant offset
+    fn constant_initializer(
+        &mut self,
+        ty: &Type,
+        initializer: Option<&Expression>,
+    ) -> Result<String> {
+        let initializer = match initializer {
+            Some(initializer) => initializer,
+            None => return Ok(self.zero_value(ty)),
+        };
+
+        if let Some(value) = self.integer_constant(initializer) {
+            if self.is_floating(ty) {
+                return Ok(Self::double_constant(value as f64));
+            }
+            if self.is_pointer(ty) && value == 0 {
+                return Ok("null".to_string());
+            }
+            if self.is_integer(ty) {
+                return Ok(value.to_string());
+            }
+        }
+        if let Some(value) = floating_constant(initializer) {
+            if matches!(self.resolve(ty), Type::Float) {
+                return Ok(Self::double_constant(value as f32 as f64));
+            }
+            if self.is_floating(ty) {
+                return Ok(Self::double_constant(value));
+            }
+        }
+        // A char array holds the string's characters, padded with zeros
+        if let (ExpressionKind::StringLiteral(content), Type::Array(element, Some(length))) =
+            (&initializer.kind, self.resolve(ty))
+        {
+            if self.size_of(&element) == 1 && content.len() <= length {
+                let padding = "\\00".repeat(length - content.len());
+                return Ok(format!("c\"{}{}\"", escape_bytes(content), padding));
+            }
+        }
+
+        let pointer_sized = self.is_integer(ty) && self.size_of(ty) == self.size_of(&Type::Long);
+        if self.is_pointer(ty) || pointer_sized {
+            if let Some((symbol, offset, _)) = self.address_constant(initializer) {
+                let address = match offset {
+                    0 => symbol,
+                    _ => format!("getelementptr (i8, ptr {}, i64 {})", symbol, offset),
+                };
+                return Ok(match pointer_sized {
+                    true => format!("ptrtoint (ptr {} to {})", address, self.llvm_type(ty)),
+                    false => address,
+                });
+            }
+        }
+
+        Err(AleccError::CodegenError {
+            message: "global initializer is not a constant".to_string(),
+            span: Some(initializer.span),
+        })
+    }
+
+    /// The symbol and byte offset of an address constant, with the type of
+    /// the expression giving it
+    fn address_constant(&mut self, initializer: &Expression) -> Option<(String, i64, Type)> {
+        match &initializer.kind {
+            ExpressionKind::StringLiteral(content) => Some((
+                self.string_constant(content),
+                0,
+                Type::Array(Box::new(Type::Char), Some(content.len() + 1)),
+            )),
+            // Arrays and functions stand for their address
+            ExpressionKind::Identifier(name) => match self.globals.get(name) {
+                Some(ty) if matches!(self.resolve(ty), Type::Array(..)) => {
+                    self.object_address(initializer)
+                }
+                Some(_) => None,
+                None => self.object_address(initializer),
+            },
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => {
+                let (symbol, offset, ty) = self.object_address(operand)?;
+                Some((symbol, offset, Type::Pointer(Box::new(ty))))
+            }
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } => {
+                let (symbol, offset, _) = self.address_constant(expression)?;
+                Some((symbol, offset, target_type.clone()))
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: operator @ (BinaryOperator::Add | BinaryOperator::Subtract),
+                right,
+            } => {
+                // Either operand of `+` can be the pointer
+                let ((symbol, offset, ty), count) = match self.address_constant(left) {
+                    Some(address) => (address, right),
+                    None if matches!(operator, BinaryOperator::Add) => {
+                        (self.address_constant(right)?, left)
+                    }
+                    None => return None,
+                };
+                let count = self.integer_constant(count)?;
+                // Arithmetic on void pointers counts bytes
+                let size = self
+                    .pointee(&ty)
+                    .map_or(1, |pointee| self.size_of(&pointee).max(1))
+                    as i64;
+                let bytes = match operator {
+                    BinaryOperator::Subtract => -count * size,
+                    _ => count * size,
+                };
+                Some((symbol, offset + bytes, ty))
+            }
+            _ => None,
+        }
+    }
+
+    /// The symbol and byte offset of the object `lvalue` designates, when it
+    /// is a global or part of one, or of the function it names, with its type
+    fn object_address(&mut self, lvalue: &Expression) -> Option<(String, i64, Type)> {
+        match &lvalue.kind {
+            ExpressionKind::Identifier(name) if self.thread_locals.contains(name) => None,
+            ExpressionKind::Identifier(name) if self.globals.contains_key(name) => {
+                Some((format!("@{}", name), 0, self.globals[name].clone()))
+            }
+            ExpressionKind::Identifier(name) if self.signatures.contains_key(name) => {
+                let function = self.function_value(name);
+                let ty = self.pointee(&function.ty).ok()?;
+                Some((function.operand, 0, ty))
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => {
+                let (symbol, offset, ty) = self.address_constant(operand)?;
+                Some((symbol, offset, self.pointee(&ty).ok()?))
+            }
+            ExpressionKind::Index { array, index } => {
+                let (symbol, offset, ty) = self.address_constant(array)?;
+                let index = self.integer_constant(index)?;
+                let element = self.pointee(&ty).ok()?;
+                let size = self.size_of(&element) as i64;
+                Some((symbol, offset + index * size, element))
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let (symbol, offset, aggregate) = if *is_arrow {
+                    let (symbol, offset, ty) = self.address_constant(object)?;
+                    (symbol, offset, self.pointee(&ty).ok()?)
+                } else {
+                    self.object_address(object)?
+                };
+                let (member_offset, ty) = self.layout.member(&aggregate, member)?;
+                Some((symbol, offset + member_offset as i64, ty))
+            }
+            _ => None,
+        }
+    }
+
+    fn integer_constant(&self, expression: &Expression) -> Option<i64> {
+        ConstantEvaluator::new()
+            .with_layout(&self.layout)
+            .evaluate(expression)
+            .ok()
+    }
+
+    /// LLVM only accepts exactly representable decimal floats, so always use hex
+    fn double_constant(value: f64) -> String {
+        format!("0x{:016X}", value.to_bits())
+    }
+
+    fn string_constant(&mut self, content: &str) -> String {
+        if let Some(name) = self.string_literals.get(content) {
+            return name.clone();
+        }
+
+        let name = format!("@.str.{}{}", self.label_prefix, self.string_literals.len());
+        let escaped = escape_bytes(content);
+        self.string_definitions.push(format!(
+            "{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"",
+            name,
+            content.len() + 1,
+            escaped
+        ));
+        self.string_literals
+            .insert(content.to_string(), name.clone());
+        name
+    }
+
+    fn function_value(&mut self, name: &str) -> Value {
+        self.referenced_functions.insert(name.to_string());
+        let signature = &self.signatures[name];
+        Value {
+            operand: format!("@{}", name),
+            ty: Type::Pointer(Box::new(Type::Function {
+                return_type: Box::new(signature.return_type.clone()),
+                parameters: signature.parameters.clone(),
+                variadic: signature.variadic,
+            })),
+        }
+    }
+
+    fn void_value() -> Value {
+        Value {
+            operand: String::new(),
+            ty: Type::Void,
+        }
+    }
+
+    fn declare_local(&mut self, name: &str, ty: &Type, alignment: Option<usize>) -> String {
+        let slot = format!("%{}.addr{}", name, self.value_counter);
+        self.value_counter += 1;
+        let mut alloca = format!("{} = alloca {}", slot, self.llvm_type(ty));
+        if let Some(alignment) = alignment {
+            alloca.push_str(&format!(", align {}", alignment));
+        }
+        self.allocas.push(alloca);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), (slot.clone(), ty.clone()));
+        }
+        slot
+    }
+
+    fn lookup(&self, name: &str) -> Option<(String, Type)> {
+        self.declared(name)
+            .map(|(slot, ty)| (slot, self.resolve(&ty)))
+    }
+
+    /// The slot of a local and its type as declared, qualifiers included
+    fn declared(&self, name: &str) -> Option<(String, Type)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn new_value(&mut self) -> String {
+        let value = format!("%t{}", self.value_counter);
+        self.value_counter += 1;
+        value
+    }
+
+    fn new_label(&mut self, prefix: &str) -> String {
+        let label = format!("{}{}", prefix, self.label_counter);
+        self.label_counter += 1;
+        label
+    }
+
+    fn emit(&mut self, instruction: &str) {
+        if self.block_terminated {
+            // Code after return/break/goto: give it a (dead) block of its own
+            let label = self.new_label("dead");
+            self.body.push(format!("{}:", label));
+            self.current_block = label;
+            self.block_terminated = false;
+        }
+        self.body.push(instruction.to_string());
+    }
+
+    fn emit_terminator(&mut self, instruction: &str) {
+        self.emit(instruction);
+        self.block_terminated = true;
+    }
+
+    fn emit_branch(&mut self, label: &str) {
+        if !self.block_terminated {
+            self.emit_terminator(&format!("br label %{}", label));
+        }
+    }
+
+    fn start_block(&mut self, label: &str) {
+        // Fall through from the previous block
+        self.emit_branch(label);
+        self.body.push(format!("{}:", label));
+        self.current_block = label.to_string();
+        self.block_terminated = false;
+    }
+}
+
+/// The LLVM ordering of a read-modify-write or fence with `order`
+fn ordering(order: MemoryOrder) -> &'static str {
+    match order {
+        MemoryOrder::Relaxed => "monotonic",
+        MemoryOrder::Consume | MemoryOrder::Acquire => "acquire",
+        MemoryOrder::Release => "release",
+        MemoryOrder::AcqRel => "acq_rel",
+        MemoryOrder::SeqCst => "seq_cst",
+    }
+}
+
+/// The ordering of a load with `order`, which can't release
+fn load_ordering(order: MemoryOrder) -> &'static str {
+    match order {
+        MemoryOrder::SeqCst => "seq_cst",
+        order if order.acquires() => "acquire",
+        _ => "monotonic",
+    }
+}
+
+/// The ordering of a store with `order`, which can't acquire
+fn store_ordering(order: MemoryOrder) -> &'static str {
+    match order {
+        MemoryOrder::SeqCst => "seq_cst",
+        order if order.releases() => "release",
+        _ => "monotonic",
+    }
+}
+
+/// `content` as the body of an LLVM `c"..."` or metadata string
+fn escape_bytes(content: &str) -> String {
+    let mut escaped = String::new();
+    for byte in content.bytes() {
+        if (byte.is_ascii_graphic() && byte != b'"' && byte != b'\\') || byte == b' ' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("\\{:02X}", byte));
+        }
+    }
+    escaped
+}
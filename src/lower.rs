@@ -0,0 +1,1452 @@
+use crate::analysis::{is_noreturn_function, noreturn_functions};
+use crate::builtins::{atomic, is_builtin, moves_stack_pointer};
+use crate::cli::MissingReturn;
+use crate::constant::ConstantEvaluator;
+use crate::error::{AleccError, Result};
+use crate::ir::{
+    fold_convert, optimize, remove_unreachable_blocks, BinaryOp, BlockId, Callee, Condition,
+    Instruction, IrFunction, IrModule, IrType, Operand, SlotId, Terminator, UnaryOp, VReg,
+};
+use crate::layout::TypeLayout;
+use crate::parser::{
+    AssignmentOperator, BinaryOperator, Expression, ExpressionKind, Function, Program, Statement,
+    StatementKind, Type, UnaryOperator,
+};
+use crate::targets::Target;
+use std::collections::{HashMap, HashSet};
+
+/// A function's type as its declarations give it
+#[derive(Debug, Clone)]
+struct Signature {
+    return_type: Type,
+    /// `None` when no declaration has a prototype
+    parameters: Option<Vec<Type>>,
+    variadic: bool,
+}
+
+/// What lowering a function to IR needs to know about the rest of its
+/// program: the globals and functions it may refer to, and the labels of
+/// string literals.
+#[derive(Debug, Clone)]
+pub struct LoweringContext {
+    target: Target,
+    layout: TypeLayout,
+    globals: HashMap<String, Type>,
+    /// Globals whose address depends on the thread, which the IR has no way to say
+    thread_locals: HashSet<String>,
+    functions: HashMap<String, Signature>,
+    noreturn: HashSet<String>,
+    missing_return: MissingReturn,
+    optimize: bool,
+    label_prefix: String,
+    /// Labels of the string literals, by content
+    strings: HashMap<String, String>,
+}
+
+impl LoweringContext {
+    pub fn new(target: Target, program: &Program) -> Self {
+        let mut layout = TypeLayout::new(target);
+        layout.record_aggregates(program);
+        let mut functions: HashMap<String, Signature> = HashMap::new();
+        for function in &program.functions {
+            let signature = Signature {
+                return_type: function.return_type.clone(),
+                parameters: function.has_prototype.then(|| {
+                    function
+                        .parameters
+                        .iter()
+                        .map(|(_, ty)| ty.clone())
+                        .collect()
+                }),
+                variadic: function.is_variadic,
+            };
+            // A prototype says more than `int f()`
+            match functions.get(&function.name) {
+                Some(known) if known.parameters.is_some() => {}
+                _ => {
+                    functions.insert(function.name.clone(), signature);
+                }
+            }
+        }
+        Self {
+            target,
+            layout,
+            globals: program
+                .global_variables
+                .iter()
+                .map(|global| (global.name.clone(), global.var_type.clone()))
+                .collect(),
+            thread_locals: program
+                .global_variables
+                .iter()
+                .filter(|global| global.thread_local)
+                .map(|global| global.name.clone())
+                .collect(),
+            functions,
+            noreturn: noreturn_functions(program),
+            missing_return: MissingReturn::Unspecified,
+            optimize: false,
+            label_prefix: String::new(),
+            strings: HashMap::new(),
+        }
+    }
+
+    /// What a non-void function returns when control reaches its closing brace
+    pub fn with_missing_return(mut self, missing_return: MissingReturn) -> Self {
+        self.missing_return = missing_return;
+        self
+    }
+
+    /// Run the IR optimizations on every function lowered
+    pub fn with_optimization(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    /// Put `prefix` after the `.L` of the labels given to string literals
+    pub fn with_label_prefix(mut self, prefix: &str) -> Self {
+        self.label_prefix = prefix.to_string();
+        self
+    }
+
+    /// Refer to string literals by the labels the assembly already gives them
+    pub fn with_strings(mut self, strings: HashMap<String, String>) -> Self {
+        self.strings = strings;
+        self
+    }
+
+    /// Lower every function `program` defines. Those using something the IR
+    /// has no form for, such as floating point or struct values, are left
+    /// out and listed with the reason.
+    pub fn lower_program(&mut self, program: &Program) -> IrModule {
+        let mut module = IrModule::default();
+        for function in program.functions.iter().filter(|f| has_body(f)) {
+            match self.lower_function(function) {
+                Ok(function) => module.functions.push(function),
+                Err(error) => module
+                    .skipped
+                    .push((function.name.clone(), error.to_string())),
+            }
+        }
+        let mut strings: Vec<(String, String)> = self
+            .strings
+            .iter()
+            .map(|(content, label)| (label.clone(), content.clone()))
+            .collect();
+        strings.sort_by_key(|(label, _)| {
+            let index = label
+                .rsplit('C')
+                .next()
+                .and_then(|n| n.parse::<usize>().ok());
+            (index, label.clone())
+        });
+        module.strings = strings;
+        module
+    }
+
+    pub fn lower_function(&mut self, function: &Function) -> Result<IrFunction> {
+        if function.is_variadic {
+            return Err(unsupported("a variadic function definition"));
+        }
+        let mut lowering = FunctionLowering::new(self, function);
+        lowering.lower_body(function)?;
+        let mut lowered = lowering.finish();
+        if self.optimize {
+            optimize(&mut lowered);
+        }
+        Ok(lowered)
+    }
+
+    /// Labels of the string literals, including those first met while lowering
+    pub fn strings(&self) -> &HashMap<String, String> {
+        &self.strings
+    }
+
+    fn string_label(&mut self, content: &str) -> String {
+        let count = self.strings.len();
+        self.strings
+            .entry(content.to_string())
+            .or_insert_with(|| format!(".L{}C{}", self.label_prefix, count))
+            .clone()
+    }
+}
+
+/// A value computed by an expression, with its C type after integer
+/// promotion and array decay
+#[derive(Debug, Clone)]
+struct Value {
+    operand: Operand,
+    ty: Type,
+}
+
+impl Value {
+    fn new(operand: Operand, ty: Type) -> Self {
+        Self { operand, ty }
+    }
+
+    fn int(value: i64) -> Self {
+        Self::new(Operand::Imm(value), Type::Int)
+    }
+
+    fn void() -> Self {
+        Self::new(Operand::Imm(0), Type::Void)
+    }
+}
+
+/// Where a name declared in the function or at file scope lives
+enum Variable {
+    Local(SlotId, Type),
+    Global(Type),
+}
+
+struct FunctionLowering<'a> {
+    context: &'a mut LoweringContext,
+    function: IrFunction,
+    current: BlockId,
+    /// Whether the current block still takes instructions, i.e. has no terminator
+    open: bool,
+    /// Blocks in the order they were started, which is how they are laid out
+    order: Vec<BlockId>,
+    scopes: Vec<HashMap<String, (SlotId, Type)>>,
+    /// Where `break` and `continue` go, innermost last
+    breaks: Vec<BlockId>,
+    continues: Vec<BlockId>,
+    labels: HashMap<String, BlockId>,
+    return_type: Type,
+    word: IrType,
+}
+
+impl<'a> FunctionLowering<'a> {
+    fn new(context: &'a mut LoweringContext, function: &Function) -> Self {
+        let word = match context.target.pointer_size() {
+            4 => IrType::I32,
+            _ => IrType::I64,
+        };
+        let return_type = context.layout.resolve(&function.return_type);
+        Self {
+            context,
+            function: IrFunction::new(&function.name),
+            current: BlockId(0),
+            open: false,
+            order: Vec::new(),
+            scopes: Vec::new(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            labels: HashMap::new(),
+            return_type,
+            word,
+        }
+    }
+
+    fn lower_body(&mut self, function: &Function) -> Result<()> {
+        if !matches!(self.return_type, Type::Void) {
+            self.ir_type(&self.return_type.clone())?;
+            self.function.return_type = Some(self.word);
+        }
+        let entry = self.new_block();
+        self.switch_to(entry);
+
+        // Parameters are stored in slots like any other variable
+        self.scopes.push(HashMap::new());
+        for (name, ty) in &function.parameters {
+            let ty = match self.context.layout.resolve(ty) {
+                Type::Array(element, _) => Type::Pointer(element),
+                _ => ty.clone(),
+            };
+            let ir_type = self.ir_type(&ty)?;
+            let reg = self.function.new_vreg(ir_type);
+            self.function.parameters.push((reg, ir_type));
+            let slot = self.new_variable(name, &ty, None);
+            let address = self.slot_address(slot);
+            self.emit(Instruction::Store {
+                ty: ir_type,
+                value: Operand::Reg(reg),
+                address,
+                volatile: ty.qualifiers().is_volatile,
+            });
+        }
+        self.lower_statement(&function.body)?;
+
+        if self.open {
+            let returns_value = !matches!(self.return_type, Type::Void);
+            let mode = match function.name.as_str() {
+                "main" => MissingReturn::Zero,
+                _ => self.context.missing_return,
+            };
+            let terminator = match (mode, returns_value) {
+                (MissingReturn::Zero, true) => {
+                    Terminator::Return(Some((self.word, Operand::Imm(0))))
+                }
+                (MissingReturn::Trap, true) => Terminator::Trap,
+                _ => Terminator::Return(None),
+            };
+            self.terminate(terminator);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> IrFunction {
+        let mut position: HashMap<BlockId, usize> = HashMap::new();
+        for (index, id) in self.order.iter().enumerate() {
+            position.entry(*id).or_insert(index);
+        }
+        self.function
+            .blocks
+            .sort_by_key(|block| position.get(&block.id).copied().unwrap_or(usize::MAX));
+        remove_unreachable_blocks(&mut self.function);
+        self.function
+    }
+
+    // Blocks
+
+    fn new_block(&mut self) -> BlockId {
+        let id = BlockId(self.function.blocks.len());
+        self.function.blocks.push(crate::ir::BasicBlock {
+            id,
+            instructions: Vec::new(),
+            terminator: Terminator::Unreachable,
+        });
+        id
+    }
+
+    /// Continue in `block`, falling through to it from the current one
+    fn switch_to(&mut self, block: BlockId) {
+        if self.open {
+            self.function.blocks[self.current.0].terminator = Terminator::Jump(block);
+        }
+        self.current = block;
+        self.open = true;
+        self.order.push(block);
+    }
+
+    /// End the current block. Code after it, which nothing jumps to, goes to a new block
+    fn terminate(&mut self, terminator: Terminator) {
+        if self.open {
+            self.function.blocks[self.current.0].terminator = terminator;
+            self.open = false;
+        }
+    }
+
+    fn jump(&mut self, target: BlockId) {
+        self.terminate(Terminator::Jump(target));
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        if !self.open {
+            let block = self.new_block();
+            self.switch_to(block);
+        }
+        self.function.blocks[self.current.0]
+            .instructions
+            .push(instruction);
+    }
+
+    /// Emit the instruction `make` builds around a new register of type `ty`
+    fn define(&mut self, ty: IrType, make: impl FnOnce(VReg) -> Instruction) -> Operand {
+        let dest = self.function.new_vreg(ty);
+        self.emit(make(dest));
+        Operand::Reg(dest)
+    }
+
+    fn label_block(&mut self, name: &str) -> BlockId {
+        if let Some(block) = self.labels.get(name) {
+            return *block;
+        }
+        let block = self.new_block();
+        self.labels.insert(name.to_string(), block);
+        block
+    }
+
+    // Types
+
+    fn resolve(&self, ty: &Type) -> Type {
+        self.context.layout.resolve(ty)
+    }
+
+    fn ir_type(&self, ty: &Type) -> Result<IrType> {
+        Ok(match self.resolve(ty) {
+            Type::Char | Type::UnsignedChar | Type::Bool => IrType::I8,
+            Type::Short | Type::UnsignedShort => IrType::I16,
+            Type::Int | Type::UnsignedInt => IrType::I32,
+            Type::Long
+            | Type::UnsignedLong
+            | Type::Pointer(_)
+            | Type::Array(..)
+            | Type::Function { .. } => self.word,
+            // A register pair on i386 is the direct generator's job
+            Type::LongLong | Type::UnsignedLongLong if self.word == IrType::I64 => IrType::I64,
+            Type::LongLong | Type::UnsignedLongLong => {
+                return Err(unsupported("a 64-bit integer on i386"))
+            }
+            Type::Float | Type::Double => return Err(unsupported("floating point")),
+            Type::Struct { .. } | Type::Union { .. } => {
+                return Err(unsupported("a struct or union value"))
+            }
+            Type::VaList => return Err(unsupported("va_list")),
+            Type::Void => return Err(unsupported("a void value")),
+            Type::Enum { .. } | Type::Typedef(..) | Type::Qualified(..) => {
+                unreachable!("resolved above")
+            }
+        })
+    }
+
+    fn is_signed(&self, ty: &Type) -> bool {
+        matches!(
+            self.resolve(ty),
+            Type::Char | Type::Short | Type::Int | Type::Long | Type::LongLong
+        )
+    }
+
+    fn is_pointer(&self, ty: &Type) -> bool {
+        matches!(self.resolve(ty), Type::Pointer(_))
+    }
+
+    /// What a pointer or array type refers to
+    fn pointee(&self, ty: &Type) -> Option<Type> {
+        match self.resolve(ty) {
+            Type::Pointer(inner) | Type::Array(inner, _) => Some(*inner),
+            _ => None,
+        }
+    }
+
+    /// Bytes a pointer of type `ty` moves by per element; `void *` moves by one
+    fn element_size(&self, ty: &Type) -> usize {
+        self.pointee(ty)
+            .map(|pointee| self.context.layout.size_of(&pointee))
+            .filter(|size| *size > 0)
+            .unwrap_or(1)
+    }
+
+    /// The type the usual arithmetic conversions give two promoted integer types
+    fn common_type(&self, a: &Type, b: &Type) -> Type {
+        let rank = |ty: &Type| match ty {
+            Type::LongLong | Type::UnsignedLongLong => 3,
+            Type::Long | Type::UnsignedLong => 2,
+            _ => 1,
+        };
+        let (a, b) = (self.resolve(a), self.resolve(b));
+        let size = |ty: &Type| self.context.layout.size_of(ty);
+        let unsigned = match rank(&a).cmp(&rank(&b)) {
+            std::cmp::Ordering::Equal => a.is_unsigned() || b.is_unsigned(),
+            // The signed type wins only if it holds every value of the unsigned one
+            std::cmp::Ordering::Greater => {
+                a.is_unsigned() || (b.is_unsigned() && size(&a) == size(&b))
+            }
+            std::cmp::Ordering::Less => {
+                b.is_unsigned() || (a.is_unsigned() && size(&a) == size(&b))
+            }
+        };
+        match (rank(&a).max(rank(&b)), unsigned) {
+            (1, false) => Type::Int,
+            (1, true) => Type::UnsignedInt,
+            (2, false) => Type::Long,
+            (2, true) => Type::UnsignedLong,
+            (_, false) => Type::LongLong,
+            (_, true) => Type::UnsignedLongLong,
+        }
+    }
+
+    // Values
+
+    fn convert_operand(
+        &mut self,
+        value: Operand,
+        from: IrType,
+        to: IrType,
+        signed: bool,
+    ) -> Operand {
+        match value {
+            _ if from == to => value,
+            Operand::Imm(value) => Operand::Imm(fold_convert(from, to, signed, value)),
+            Operand::Reg(_) => self.define(to, |dest| Instruction::Convert {
+                dest,
+                from,
+                to,
+                signed,
+                value,
+            }),
+        }
+    }
+
+    /// `value` converted to `ty`, at the width of `ty`
+    fn convert(&mut self, value: &Value, ty: &Type) -> Result<Operand> {
+        let from = self.ir_type(&value.ty)?;
+        if matches!(self.resolve(ty), Type::Bool) {
+            let truth = self.define(IrType::I32, |dest| Instruction::Compare {
+                dest,
+                condition: Condition::Ne,
+                ty: from,
+                left: value.operand,
+                right: Operand::Imm(0),
+            });
+            return Ok(self.convert_operand(truth, IrType::I32, IrType::I8, false));
+        }
+        let to = self.ir_type(ty)?;
+        let signed = self.is_signed(&value.ty);
+        Ok(self.convert_operand(value.operand, from, to, signed))
+    }
+
+    /// The value of an operand of type `ty`, promoted to `int` if narrower
+    fn promote(&mut self, operand: Operand, ty: &Type) -> Result<Value> {
+        let ty = self.resolve(ty);
+        match ty {
+            Type::Char | Type::UnsignedChar | Type::Bool | Type::Short | Type::UnsignedShort => {
+                let from = self.ir_type(&ty)?;
+                let signed = self.is_signed(&ty);
+                let operand = self.convert_operand(operand, from, IrType::I32, signed);
+                Ok(Value::new(operand, Type::Int))
+            }
+            ty => Ok(Value::new(operand, ty)),
+        }
+    }
+
+    /// An operand of type `ty` extended to a whole word, as calls pass and return it
+    fn widen(&mut self, operand: Operand, ty: &Type) -> Result<Operand> {
+        let from = self.ir_type(ty)?;
+        let signed = self.is_signed(ty);
+        Ok(self.convert_operand(operand, from, self.word, signed))
+    }
+
+    fn load(&mut self, address: Operand, ty: &Type) -> Result<Value> {
+        let volatile = ty.qualifiers().is_volatile;
+        match self.resolve(ty) {
+            // An array decays to the address of its first element
+            Type::Array(element, _) => Ok(Value::new(address, Type::Pointer(element))),
+            function @ Type::Function { .. } => {
+                Ok(Value::new(address, Type::Pointer(Box::new(function))))
+            }
+            _ => {
+                let ir_type = self.ir_type(ty)?;
+                let loaded = self.define(ir_type, |dest| Instruction::Load {
+                    dest,
+                    ty: ir_type,
+                    address,
+                    volatile,
+                });
+                self.promote(loaded, ty)
+            }
+        }
+    }
+
+    /// Store `value` converted to `ty` at `address`, and give the stored operand
+    fn store(&mut self, address: Operand, ty: &Type, value: &Value) -> Result<Operand> {
+        let stored = self.convert(value, ty)?;
+        let ir_type = self.ir_type(ty)?;
+        self.emit(Instruction::Store {
+            ty: ir_type,
+            value: stored,
+            address,
+            volatile: ty.qualifiers().is_volatile,
+        });
+        Ok(stored)
+    }
+
+    fn binary_op(&mut self, op: BinaryOp, ty: IrType, left: Operand, right: Operand) -> Operand {
+        self.define(ty, |dest| Instruction::Binary {
+            dest,
+            op,
+            ty,
+            left,
+            right,
+        })
+    }
+
+    fn add_offset(&mut self, address: Operand, offset: usize) -> Operand {
+        match offset {
+            0 => address,
+            offset => self.binary_op(
+                BinaryOp::Add,
+                self.word,
+                address,
+                Operand::Imm(offset as i64),
+            ),
+        }
+    }
+
+    /// `pointer + index` or `pointer - index`, in elements
+    fn offset_pointer(&mut self, pointer: &Value, index: &Value, op: BinaryOp) -> Result<Value> {
+        let size = self.element_size(&pointer.ty);
+        let index = self.widen(index.operand, &index.ty)?;
+        let scaled = match size {
+            1 => index,
+            size => self.binary_op(BinaryOp::Mul, self.word, index, Operand::Imm(size as i64)),
+        };
+        let operand = self.binary_op(op, self.word, pointer.operand, scaled);
+        Ok(Value::new(operand, pointer.ty.clone()))
+    }
+
+    fn new_variable(&mut self, name: &str, ty: &Type, alignment: Option<usize>) -> SlotId {
+        let layout = &self.context.layout;
+        let size = layout.size_of(ty).max(1);
+        let align = layout.align_of(ty).max(alignment.unwrap_or(1));
+        let slot = self.function.new_slot(size, align);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), (slot, ty.clone()));
+        }
+        slot
+    }
+
+    fn slot_address(&mut self, slot: SlotId) -> Operand {
+        self.define(self.word, |dest| Instruction::SlotAddress { dest, slot })
+    }
+
+    fn lookup(&self, name: &str) -> Option<Variable> {
+        for scope in self.scopes.iter().rev() {
+            if let Some((slot, ty)) = scope.get(name) {
+                return Some(Variable::Local(*slot, ty.clone()));
+            }
+        }
+        self.context
+            .globals
+            .get(name)
+            .map(|ty| Variable::Global(ty.clone()))
+    }
+
+    // Statements
+
+    fn lower_statement(&mut self, statement: &Statement) -> Result<()> {
+        self.lower_statement_unlocated(statement)
+            .map_err(|error| error.at(statement.span))
+    }
+
+    fn lower_statement_unlocated(&mut self, statement: &Statement) -> Result<()> {
+        match &statement.kind {
+            StatementKind::Expression(expression) => {
+                self.rvalue(expression)?;
+            }
+            StatementKind::Declaration {
+                name,
+                var_type,
+                initializer,
+                alignment,
+                ..
+            } => {
+                match self.resolve(var_type) {
+                    Type::VaList => return Err(unsupported("va_list")),
+                    Type::Array(_, None) => return Err(unsupported("an array of unknown size")),
+                    _ => {}
+                }
+                let slot = self.new_variable(name, var_type, *alignment);
+                if let Some(initializer) = initializer {
+                    if matches!(
+                        self.resolve(var_type),
+                        Type::Array(..) | Type::Struct { .. } | Type::Union { .. }
+                    ) {
+                        return Err(unsupported("an aggregate initializer"));
+                    }
+                    let value = self.rvalue(initializer)?;
+                    let address = self.slot_address(slot);
+                    self.store(address, var_type, &value)?;
+                }
+            }
+            StatementKind::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                for statement in statements {
+                    self.lower_statement(statement)?;
+                }
+                self.scopes.pop();
+            }
+            StatementKind::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                let then_block = self.new_block();
+                let else_block = else_stmt.as_ref().map(|_| self.new_block());
+                let end = self.new_block();
+                self.branch_on(condition, then_block, else_block.unwrap_or(end))?;
+                self.switch_to(then_block);
+                self.lower_statement(then_stmt)?;
+                if let (Some(else_stmt), Some(else_block)) = (else_stmt, else_block) {
+                    self.jump(end);
+                    self.switch_to(else_block);
+                    self.lower_statement(else_stmt)?;
+                }
+                self.switch_to(end);
+            }
+            StatementKind::While { condition, body } => {
+                let test = self.new_block();
+                let body_block = self.new_block();
+                let end = self.new_block();
+                self.switch_to(test);
+                self.branch_on(condition, body_block, end)?;
+                self.switch_to(body_block);
+                self.lower_loop_body(body, end, test)?;
+                self.jump(test);
+                self.switch_to(end);
+            }
+            StatementKind::DoWhile { body, condition } => {
+                let body_block = self.new_block();
+                let test = self.new_block();
+                let end = self.new_block();
+                self.switch_to(body_block);
+                self.lower_loop_body(body, end, test)?;
+                self.switch_to(test);
+                self.branch_on(condition, body_block, end)?;
+                self.switch_to(end);
+            }
+            StatementKind::For {
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.lower_statement(init)?;
+                }
+                let test = self.new_block();
+                let body_block = self.new_block();
+                let step = self.new_block();
+                let end = self.new_block();
+                self.switch_to(test);
+                match condition {
+                    Some(condition) => self.branch_on(condition, body_block, end)?,
+                    None => self.jump(body_block),
+                }
+                self.switch_to(body_block);
+                self.lower_loop_body(body, end, step)?;
+                self.switch_to(step);
+                if let Some(increment) = increment {
+                    self.rvalue(increment)?;
+                }
+                self.jump(test);
+                self.switch_to(end);
+                self.scopes.pop();
+            }
+            StatementKind::Switch { expression, cases } => {
+                self.lower_switch(expression, cases)?;
+            }
+            StatementKind::Return(expression) => {
+                let value = match expression {
+                    Some(expression) => {
+                        let value = self.rvalue(expression)?;
+                        if matches!(self.return_type, Type::Void) {
+                            None
+                        } else {
+                            let return_type = self.return_type.clone();
+                            let returned = self.convert(&value, &return_type)?;
+                            Some((self.word, self.widen(returned, &return_type)?))
+                        }
+                    }
+                    None => None,
+                };
+                self.terminate(Terminator::Return(value));
+            }
+            StatementKind::Break => {
+                let target =
+                    self.breaks.last().copied().ok_or_else(|| {
+                        codegen_error("break statement not within a loop or switch")
+                    })?;
+                self.jump(target);
+            }
+            StatementKind::Continue => {
+                let target = self
+                    .continues
+                    .last()
+                    .copied()
+                    .ok_or_else(|| codegen_error("continue statement not within a loop"))?;
+                self.jump(target);
+            }
+            StatementKind::Goto(label) => {
+                let target = self.label_block(label);
+                self.jump(target);
+            }
+            StatementKind::Label(label) => {
+                let block = self.label_block(label);
+                self.switch_to(block);
+            }
+            StatementKind::ComputedGoto(_) => return Err(unsupported("a computed goto")),
+            StatementKind::Fallthrough => {}
+        }
+        Ok(())
+    }
+
+    fn lower_loop_body(&mut self, body: &Statement, end: BlockId, next: BlockId) -> Result<()> {
+        self.breaks.push(end);
+        self.continues.push(next);
+        let lowered = self.lower_statement(body);
+        self.breaks.pop();
+        self.continues.pop();
+        lowered
+    }
+
+    /// A chain of comparisons against the case values, then the case bodies in order
+    fn lower_switch(
+        &mut self,
+        expression: &Expression,
+        cases: &[(Option<Expression>, Vec<Statement>)],
+    ) -> Result<()> {
+        let value = self.rvalue(expression)?;
+        let ty = self.ir_type(&value.ty)?;
+        let evaluator = ConstantEvaluator::new().with_layout(&self.context.layout);
+        let labels = cases
+            .iter()
+            .map(|(label, _)| {
+                label
+                    .as_ref()
+                    .map(|label| {
+                        evaluator
+                            .evaluate(label)
+                            .map_err(|error| AleccError::CodegenError {
+                                message: error.to_string(),
+                                span: Some(error.span()),
+                            })
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let blocks: Vec<BlockId> = cases.iter().map(|_| self.new_block()).collect();
+        let end = self.new_block();
+        for (index, label) in labels.iter().enumerate() {
+            let Some(label) = label else {
+                continue;
+            };
+            let matched = self.define(IrType::I32, |dest| Instruction::Compare {
+                dest,
+                condition: Condition::Eq,
+                ty,
+                left: value.operand,
+                right: Operand::Imm(ty.wrap(*label, true)),
+            });
+            let next = self.new_block();
+            self.terminate(Terminator::Branch {
+                condition: matched,
+                ty: IrType::I32,
+                if_true: blocks[index],
+                if_false: next,
+            });
+            self.switch_to(next);
+        }
+        let default = labels
+            .iter()
+            .position(Option::is_none)
+            .map_or(end, |index| blocks[index]);
+        self.jump(default);
+
+        self.breaks.push(end);
+        self.scopes.push(HashMap::new());
+        for ((_, statements), block) in cases.iter().zip(&blocks) {
+            self.switch_to(*block);
+            for statement in statements {
+                self.lower_statement(statement)?;
+            }
+        }
+        self.scopes.pop();
+        self.breaks.pop();
+        self.switch_to(end);
+        Ok(())
+    }
+
+    /// Jump to `if_true` when `condition` holds and to `if_false` otherwise,
+    /// short-circuiting `&&`, `||` and `!` into the branches
+    fn branch_on(
+        &mut self,
+        condition: &Expression,
+        if_true: BlockId,
+        if_false: BlockId,
+    ) -> Result<()> {
+        match &condition.kind {
+            ExpressionKind::Binary {
+                left,
+                operator: BinaryOperator::LogicalAnd,
+                right,
+            } => {
+                let next = self.new_block();
+                self.branch_on(left, next, if_false)?;
+                self.switch_to(next);
+                self.branch_on(right, if_true, if_false)
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: BinaryOperator::LogicalOr,
+                right,
+            } => {
+                let next = self.new_block();
+                self.branch_on(left, if_true, next)?;
+                self.switch_to(next);
+                self.branch_on(right, if_true, if_false)
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::LogicalNot,
+                operand,
+            } => self.branch_on(operand, if_false, if_true),
+            _ => {
+                let value = self.rvalue(condition)?;
+                let ty = self.ir_type(&value.ty)?;
+                self.terminate(Terminator::Branch {
+                    condition: value.operand,
+                    ty,
+                    if_true,
+                    if_false,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    // Expressions
+
+    fn rvalue(&mut self, expression: &Expression) -> Result<Value> {
+        self.rvalue_unlocated(expression)
+            .map_err(|error| error.at(expression.span))
+    }
+
+    fn rvalue_unlocated(&mut self, expression: &Expression) -> Result<Value> {
+        match &expression.kind {
+            ExpressionKind::IntegerLiteral(value) => Ok(Value::new(
+                Operand::Imm(*value),
+                if i32::try_from(*value).is_ok() {
+                    Type::Int
+                } else {
+                    Type::Long
+                },
+            )),
+            ExpressionKind::CharLiteral(c) => Ok(Value::int(*c as i64)),
+            ExpressionKind::BooleanLiteral(b) => Ok(Value::int(*b as i64)),
+            ExpressionKind::FloatLiteral(_) => Err(unsupported("floating point")),
+            ExpressionKind::StringLiteral(content) => {
+                let symbol = self.context.string_label(content);
+                let address = self.define(self.word, |dest| Instruction::SymbolAddress {
+                    dest,
+                    symbol,
+                });
+                Ok(Value::new(address, Type::Pointer(Box::new(Type::Char))))
+            }
+            ExpressionKind::Sizeof(ty) => Ok(Value::new(
+                Operand::Imm(self.context.layout.size_of(ty) as i64),
+                Type::UnsignedLong,
+            )),
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Index { .. }
+            | ExpressionKind::Member { .. }
+            | ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                ..
+            } => {
+                let (address, ty) = self.address(expression)?;
+                self.load(address, &ty)
+            }
+            ExpressionKind::Binary {
+                operator: BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr,
+                ..
+            } => self.truth_value(expression),
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.rvalue(left)?;
+                let right = self.rvalue(right)?;
+                self.arithmetic(operator, left, right)
+            }
+            ExpressionKind::Unary { operator, operand } => self.unary(operator, operand),
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => self.call(function, arguments),
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } => {
+                let value = self.rvalue(expression)?;
+                if matches!(self.resolve(target_type), Type::Void) {
+                    return Ok(Value::void());
+                }
+                let converted = self.convert(&value, target_type)?;
+                self.promote(converted, target_type)
+            }
+            ExpressionKind::Assignment {
+                target,
+                operator,
+                value,
+            } => self.assign(target, operator, value),
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => self.conditional(condition, then_expr, else_expr),
+            ExpressionKind::VaArg { .. } => Err(unsupported("va_arg")),
+            // Blocks have no addresses in the IR
+            ExpressionKind::LabelAddress(_) => Err(unsupported("a label address")),
+        }
+    }
+
+    /// The address of the object an lvalue designates, and the object's type
+    fn address(&mut self, expression: &Expression) -> Result<(Operand, Type)> {
+        self.address_unlocated(expression)
+            .map_err(|error| error.at(expression.span))
+    }
+
+    fn address_unlocated(&mut self, expression: &Expression) -> Result<(Operand, Type)> {
+        match &expression.kind {
+            ExpressionKind::Identifier(name) => match self.lookup(name) {
+                Some(Variable::Local(slot, ty)) => Ok((self.slot_address(slot), ty)),
+                Some(Variable::Global(_)) if self.context.thread_locals.contains(name) => {
+                    Err(unsupported("a thread-local variable"))
+                }
+                Some(Variable::Global(ty)) => {
+                    let symbol = name.clone();
+                    let address = self.define(self.word, |dest| Instruction::SymbolAddress {
+                        dest,
+                        symbol,
+                    });
+                    Ok((address, ty))
+                }
+                None => {
+                    let signature = self
+                        .context
+                        .functions
+                        .get(name)
+                        .cloned()
+                        .ok_or_else(|| codegen_error(&format!("'{}' undeclared", name)))?;
+                    let function = name.clone();
+                    let address = self.define(self.word, |dest| Instruction::FunctionAddress {
+                        dest,
+                        function,
+                    });
+                    Ok((
+                        address,
+                        Type::Function {
+                            return_type: Box::new(signature.return_type),
+                            parameters: signature.parameters.unwrap_or_default(),
+                            variadic: signature.variadic,
+                        },
+                    ))
+                }
+            },
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => {
+                let pointer = self.rvalue(operand)?;
+                let pointee = self
+                    .pointee(&pointer.ty)
+                    .ok_or_else(|| codegen_error("indirection requires a pointer operand"))?;
+                Ok((pointer.operand, pointee))
+            }
+            ExpressionKind::Index { array, index } => {
+                let array = self.rvalue(array)?;
+                let index = self.rvalue(index)?;
+                // `i[a]` is `a[i]`
+                let (pointer, index) = if self.is_pointer(&array.ty) {
+                    (array, index)
+                } else {
+                    (index, array)
+                };
+                let element = self
+                    .pointee(&pointer.ty)
+                    .ok_or_else(|| codegen_error("subscripted value is not an array or pointer"))?;
+                let address = self.offset_pointer(&pointer, &index, BinaryOp::Add)?;
+                Ok((address.operand, element))
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let (base, aggregate) = if *is_arrow {
+                    let pointer = self.rvalue(object)?;
+                    let aggregate = self
+                        .pointee(&pointer.ty)
+                        .ok_or_else(|| codegen_error("'->' requires a pointer operand"))?;
+                    (pointer.operand, aggregate)
+                } else {
+                    self.address(object)?
+                };
+                let (offset, ty) = self
+                    .context
+                    .layout
+                    .member(&aggregate, member)
+                    .ok_or_else(|| codegen_error(&format!("no member named '{}'", member)))?;
+                // The qualifiers of the object apply to its members
+                let qualifiers = aggregate.qualifiers();
+                let ty = if qualifiers.is_empty() {
+                    ty
+                } else {
+                    Type::Qualified(qualifiers, Box::new(ty))
+                };
+                Ok((self.add_offset(base, offset), ty))
+            }
+            ExpressionKind::StringLiteral(content) => {
+                let length = content.len() + 1;
+                let value = self.rvalue(expression)?;
+                Ok((
+                    value.operand,
+                    Type::Array(Box::new(Type::Char), Some(length)),
+                ))
+            }
+            _ => Err(unsupported("the address of this expression")),
+        }
+    }
+
+    fn arithmetic(
+        &mut self,
+        operator: &BinaryOperator,
+        left: Value,
+        right: Value,
+    ) -> Result<Value> {
+        let left_pointer = self.is_pointer(&left.ty);
+        let right_pointer = self.is_pointer(&right.ty);
+        match operator {
+            BinaryOperator::Add if left_pointer => {
+                self.offset_pointer(&left, &right, BinaryOp::Add)
+            }
+            BinaryOperator::Add if right_pointer => {
+                self.offset_pointer(&right, &left, BinaryOp::Add)
+            }
+            BinaryOperator::Subtract if left_pointer && right_pointer => {
+                let size = self.element_size(&left.ty);
+                let bytes = self.binary_op(BinaryOp::Sub, self.word, left.operand, right.operand);
+                let difference = match size {
+                    1 => bytes,
+                    size => {
+                        self.binary_op(BinaryOp::SDiv, self.word, bytes, Operand::Imm(size as i64))
+                    }
+                };
+                Ok(Value::new(difference, Type::Long))
+            }
+            BinaryOperator::Subtract if left_pointer => {
+                self.offset_pointer(&left, &right, BinaryOp::Sub)
+            }
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::Less
+            | BinaryOperator::Greater
+            | BinaryOperator::LessEqual
+            | BinaryOperator::GreaterEqual => self.compare(operator, &left, &right),
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                // The result has the type of the promoted left operand
+                let ty = self.ir_type(&left.ty)?;
+                let count_type = self.ir_type(&right.ty)?;
+                let count = self.convert_operand(right.operand, count_type, ty, false);
+                let op = match operator {
+                    BinaryOperator::LeftShift => BinaryOp::Shl,
+                    _ if self.is_signed(&left.ty) => BinaryOp::AShr,
+                    _ => BinaryOp::LShr,
+                };
+                let shifted = self.binary_op(op, ty, left.operand, count);
+                Ok(Value::new(shifted, left.ty))
+            }
+            BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                unreachable!("short-circuited by the caller")
+            }
+            _ if left_pointer || right_pointer => Err(unsupported("this pointer arithmetic")),
+            _ => {
+                let common = self.common_type(&left.ty, &right.ty);
+                let ty = self.ir_type(&common)?;
+                let signed = self.is_signed(&common);
+                let a = self.convert(&left, &common)?;
+                let b = self.convert(&right, &common)?;
+                let op = match operator {
+                    BinaryOperator::Add => BinaryOp::Add,
+                    BinaryOperator::Subtract => BinaryOp::Sub,
+                    BinaryOperator::Multiply => BinaryOp::Mul,
+                    BinaryOperator::Divide if signed => BinaryOp::SDiv,
+                    BinaryOperator::Divide => BinaryOp::UDiv,
+                    BinaryOperator::Modulo if signed => BinaryOp::SRem,
+                    BinaryOperator::Modulo => BinaryOp::URem,
+                    BinaryOperator::BitwiseAnd => BinaryOp::And,
+                    BinaryOperator::BitwiseOr => BinaryOp::Or,
+                    _ => BinaryOp::Xor,
+                };
+                Ok(Value::new(self.binary_op(op, ty, a, b), common))
+            }
+        }
+    }
+
+    fn compare(&mut self, operator: &BinaryOperator, left: &Value, right: &Value) -> Result<Value> {
+        // Pointers compare as unsigned words
+        let common = if self.is_pointer(&left.ty) || self.is_pointer(&right.ty) {
+            Type::UnsignedLong
+        } else {
+            self.common_type(&left.ty, &right.ty)
+        };
+        let ty = self.ir_type(&common)?;
+        let signed = self.is_signed(&common);
+        let a = self.convert(left, &common)?;
+        let b = self.convert(right, &common)?;
+        let condition = match (operator, signed) {
+            (BinaryOperator::Equal, _) => Condition::Eq,
+            (BinaryOperator::NotEqual, _) => Condition::Ne,
+            (BinaryOperator::Less, true) => Condition::SLt,
+            (BinaryOperator::Less, false) => Condition::ULt,
+            (BinaryOperator::LessEqual, true) => Condition::SLe,
+            (BinaryOperator::LessEqual, false) => Condition::ULe,
+            (BinaryOperator::Greater, true) => Condition::SGt,
+            (BinaryOperator::Greater, false) => Condition::UGt,
+            (_, true) => Condition::SGe,
+            (_, false) => Condition::UGe,
+        };
+        let result = self.define(IrType::I32, |dest| Instruction::Compare {
+            dest,
+            condition,
+            ty,
+            left: a,
+            right: b,
+        });
+        Ok(Value::new(result, Type::Int))
+    }
+
+    fn unary(&mut self, operator: &UnaryOperator, operand: &Expression) -> Result<Value> {
+        match operator {
+            UnaryOperator::Plus => self.rvalue(operand),
+            UnaryOperator::Minus | UnaryOperator::BitwiseNot => {
+                let value = self.rvalue(operand)?;
+                let ty = self.ir_type(&value.ty)?;
+                let op = match operator {
+                    UnaryOperator::Minus => UnaryOp::Neg,
+                    _ => UnaryOp::Not,
+                };
+                let result = self.define(ty, |dest| Instruction::Unary {
+                    dest,
+                    op,
+                    ty,
+                    operand: value.operand,
+                });
+                Ok(Value::new(result, value.ty))
+            }
+            UnaryOperator::LogicalNot => {
+                let value = self.rvalue(operand)?;
+                self.compare(&BinaryOperator::Equal, &value, &Value::int(0))
+            }
+            UnaryOperator::AddressOf => {
+                let (address, ty) = self.address(operand)?;
+                Ok(Value::new(address, Type::Pointer(Box::new(ty))))
+            }
+            UnaryOperator::Dereference => unreachable!("loaded through its address"),
+            UnaryOperator::PreIncrement
+            | UnaryOperator::PostIncrement
+            | UnaryOperator::PreDecrement
+            | UnaryOperator::PostDecrement => {
+                let (address, ty) = self.address(operand)?;
+                let old = self.load(address, &ty)?;
+                let (op, arithmetic) = match operator {
+                    UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => {
+                        (BinaryOp::Add, BinaryOperator::Add)
+                    }
+                    _ => (BinaryOp::Sub, BinaryOperator::Subtract),
+                };
+                let new = if self.is_pointer(&old.ty) {
+                    self.offset_pointer(&old, &Value::int(1), op)?
+                } else {
+                    self.arithmetic(&arithmetic, old.clone(), Value::int(1))?
+                };
+                let stored = self.store(address, &ty, &new)?;
+                match operator {
+                    UnaryOperator::PreIncrement | UnaryOperator::PreDecrement => {
+                        self.promote(stored, &ty)
+                    }
+                    _ => Ok(old),
+                }
+            }
+        }
+    }
+
+    fn assign(
+        &mut self,
+        target: &Expression,
+        operator: &AssignmentOperator,
+        value: &Expression,
+    ) -> Result<Value> {
+        let (address, ty) = self.address(target)?;
+        let value = match operator.binary_operator() {
+            None => self.rvalue(value)?,
+            Some(operator) => {
+                let current = self.load(address, &ty)?;
+                let value = self.rvalue(value)?;
+                self.arithmetic(&operator, current, value)?
+            }
+        };
+        let stored = self.store(address, &ty, &value)?;
+        self.promote(stored, &ty)
+    }
+
+    /// `a ? b : c`. Each arm leaves its value, extended to a word, in a
+    /// temporary; read back at the type of the result, that is the arm's
+    /// value converted to it.
+    fn conditional(
+        &mut self,
+        condition: &Expression,
+        then_expr: &Expression,
+        else_expr: &Expression,
+    ) -> Result<Value> {
+        let slot = self.function.new_slot(self.word.size(), self.word.size());
+        let then_block = self.new_block();
+        let else_block = self.new_block();
+        let end = self.new_block();
+        self.branch_on(condition, then_block, else_block)?;
+
+        let mut types = Vec::new();
+        for (block, arm) in [(then_block, then_expr), (else_block, else_expr)] {
+            self.switch_to(block);
+            let value = self.rvalue(arm)?;
+            if !matches!(value.ty, Type::Void) {
+                let widened = self.widen(value.operand, &value.ty)?;
+                let address = self.slot_address(slot);
+                self.emit(Instruction::Store {
+                    ty: self.word,
+                    value: widened,
+                    address,
+                    volatile: false,
+                });
+            }
+            types.push(value.ty);
+            self.jump(end);
+        }
+        self.switch_to(end);
+
+        let ty = match (&types[0], &types[1]) {
+            (Type::Void, _) | (_, Type::Void) => return Ok(Value::void()),
+            (pointer @ Type::Pointer(_), _) | (_, pointer @ Type::Pointer(_)) => pointer.clone(),
+            (a, b) => self.common_type(a, b),
+        };
+        let address = self.slot_address(slot);
+        let word = self.word;
+        let loaded = self.define(word, |dest| Instruction::Load {
+            dest,
+            ty: word,
+            address,
+            volatile: false,
+        });
+        let ir_type = self.ir_type(&ty)?;
+        let operand = self.convert_operand(loaded, word, ir_type, false);
+        Ok(Value::new(operand, ty))
+    }
+
+    /// The `int` value of `a && b` or `a || b`
+    fn truth_value(&mut self, expression: &Expression) -> Result<Value> {
+        let slot = self.function.new_slot(4, 4);
+        let if_true = self.new_block();
+        let if_false = self.new_block();
+        let end = self.new_block();
+        self.branch_on(expression, if_true, if_false)?;
+        for (block, value) in [(if_true, 1), (if_false, 0)] {
+            self.switch_to(block);
+            let address = self.slot_address(slot);
+            self.emit(Instruction::Store {
+                ty: IrType::I32,
+                value: Operand::Imm(value),
+                address,
+                volatile: false,
+            });
+            self.jump(end);
+        }
+        self.switch_to(end);
+        let address = self.slot_address(slot);
+        let loaded = self.define(IrType::I32, |dest| Instruction::Load {
+            dest,
+            ty: IrType::I32,
+            address,
+            volatile: false,
+        });
+        Ok(Value::new(loaded, Type::Int))
+    }
+
+    fn call(&mut self, function: &Expression, arguments: &[Expression]) -> Result<Value> {
+        let direct = match &function.kind {
+            ExpressionKind::Identifier(name) if self.lookup(name).is_none() => Some(name.clone()),
+            _ => None,
+        };
+        if let Some(name) = &direct {
+            if name.starts_with("__builtin_va_") || name.starts_with("va_") {
+                return Err(unsupported("variable arguments"));
+            }
+            if is_builtin(name) || atomic(name).is_some() {
+                return Err(unsupported("builtin functions"));
+            }
+            if moves_stack_pointer(name) {
+                return Err(unsupported("alloca"));
+            }
+        }
+        let (callee, signature) = match direct {
+            Some(name) => {
+                // An undeclared function is implicitly `int name()`
+                let signature = self
+                    .context
+                    .functions
+                    .get(&name)
+                    .cloned()
+                    .unwrap_or(Signature {
+                        return_type: Type::Int,
+                        parameters: None,
+                        variadic: false,
+                    });
+                (Callee::Direct(name), signature)
+            }
+            None => {
+                let target = self.rvalue(function)?;
+                match self.pointee(&target.ty).map(|ty| self.resolve(&ty)) {
+                    Some(Type::Function {
+                        return_type,
+                        parameters,
+                        variadic,
+                    }) => (
+                        Callee::Indirect(target.operand),
+                        Signature {
+                            return_type: *return_type,
+                            parameters: (!parameters.is_empty()).then_some(parameters),
+                            variadic,
+                        },
+                    ),
+                    _ => return Err(codegen_error("called object is not a function")),
+                }
+            }
+        };
+
+        let mut lowered = Vec::new();
+        for (index, argument) in arguments.iter().enumerate() {
+            let value = self.rvalue(argument)?;
+            let parameter = signature
+                .parameters
+                .as_ref()
+                .and_then(|parameters| parameters.get(index))
+                .map(|parameter| match self.resolve(parameter) {
+                    Type::Array(element, _) => Type::Pointer(element),
+                    _ => parameter.clone(),
+                });
+            let ty = parameter.unwrap_or_else(|| value.ty.clone());
+            let converted = self.convert(&value, &ty)?;
+            lowered.push((self.word, self.widen(converted, &ty)?));
+        }
+
+        let return_type = self.resolve(&signature.return_type);
+        let dest = match return_type {
+            Type::Void => None,
+            _ => {
+                let ty = self.ir_type(&return_type)?;
+                Some((self.function.new_vreg(ty), ty))
+            }
+        };
+        let noreturn = matches!(&callee, Callee::Direct(name) if is_noreturn_function(name, &self.context.noreturn));
+        self.emit(Instruction::Call {
+            dest,
+            callee,
+            arguments: lowered,
+            variadic: signature.variadic,
+        });
+        if noreturn {
+            self.terminate(Terminator::Unreachable);
+        }
+        match dest {
+            Some((dest, _)) => self.promote(Operand::Reg(dest), &return_type),
+            None => Ok(Value::void()),
+        }
+    }
+}
+
+fn has_body(function: &Function) -> bool {
+    !matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+}
+
+fn unsupported(what: &str) -> AleccError {
+    codegen_error(&format!("{} cannot be lowered to IR", what))
+}
+
+fn codegen_error(message: &str) -> AleccError {
+    AleccError::CodegenError {
+        message: message.to_string(),
+        span: None,
+    }
+}
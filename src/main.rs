@@ -1,18 +1,50 @@
 use anyhow::Result;
 use clap::Parser;
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
+mod abi;
+mod analysis;
+mod att;
+mod builtins;
+mod cache;
+mod callgraph;
 mod cli;
 mod codegen;
 mod compiler;
+mod constant;
+#[cfg(feature = "cranelift")]
+mod cranelift_backend;
+#[cfg(all(feature = "daemon", unix))]
+mod daemon;
+mod diagnostics;
+mod doc;
 mod error;
+mod explain;
+mod fmt;
+mod format;
+mod frame;
+mod headers;
+mod host;
+mod ir;
+mod ir_codegen;
+mod language;
+mod layout;
 mod lexer;
 mod linker;
+mod llvm_ir;
+mod lower;
+mod merge;
 mod optimizer;
 mod parser;
+mod regalloc;
+mod replay;
+mod runtime;
+mod sema;
+mod summary;
 mod targets;
+mod warnings;
 
-use cli::Args;
+use cli::{Args, Command};
 use compiler::Compiler;
 
 #[tokio::main]
@@ -22,12 +54,75 @@ async fn main() -> Result<()> {
 
     let args = Args::parse();
 
+    if let Some(Command::Doc(doc)) = &args.command {
+        if let Err(e) = doc::run(doc).await {
+            error!("Documentation failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Fmt(options)) = &args.command {
+        match fmt::run(options).await {
+            Ok(true) => return Ok(()),
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                error!("Formatting failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(Command::ExplainAsm(options)) = &args.command {
+        if let Err(e) = explain::run(options).await {
+            error!("Explaining assembly failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Replay(options)) = &args.command {
+        match replay::run(options).await {
+            Ok(0) => return Ok(()),
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                error!("Replay failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     info!("Starting ALECC compiler v{}", env!("CARGO_PKG_VERSION"));
 
+    if let Some(socket) = &args.daemon {
+        #[cfg(all(feature = "daemon", unix))]
+        return Ok(daemon::serve(socket).await?);
+        #[cfg(not(all(feature = "daemon", unix)))]
+        {
+            error!(
+                "Cannot serve on {}: alecc was built without the daemon (enable the `daemon` feature)",
+                socket.display()
+            );
+            std::process::exit(1);
+        }
+    }
+
     let mut compiler = Compiler::new(args.clone())?;
 
     match compiler.compile().await {
-        Ok(()) => {
+        Ok(output) => {
+            for timing in &output.timings {
+                debug!(
+                    "{} {}: {:?}",
+                    timing.phase,
+                    timing
+                        .input
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default(),
+                    timing.duration
+                );
+            }
             info!("Compilation completed successfully");
             Ok(())
         }
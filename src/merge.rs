@@ -0,0 +1,306 @@
+use crate::error::{AleccError, Result};
+use crate::optimizer::rename_functions;
+use crate::parser::{Function, GlobalStorage, GlobalVariable, Program, StatementKind};
+use std::collections::{HashMap, HashSet};
+
+/// The translation units of a `--fwhole-program` build, combined
+#[derive(Debug)]
+pub struct WholeProgram {
+    pub program: Program,
+    /// The functions each unit defines, by their names in `program`
+    pub definitions: Vec<HashSet<String>>,
+}
+
+/// What the units merged so far say about an external name
+#[derive(Debug)]
+struct External {
+    /// The unit that first declared it
+    file: String,
+    /// Parameter count and variadicness of a prototyped function; `None` for a variable
+    kind: Option<Option<(usize, bool)>>,
+    /// The unit with its body or initializer
+    defined_in: Option<String>,
+    /// Whether that body is a C99 inline definition, which another may replace
+    inline: bool,
+}
+
+/// What becomes of a definition once earlier units have been merged
+#[derive(Debug, PartialEq, Eq)]
+enum Definition {
+    /// The first definition, or no definition at all
+    Kept,
+    /// It replaces an earlier inline definition
+    Replaces,
+    /// An inline definition of a name already defined, which is dropped
+    Redundant,
+}
+
+/// Combine checked translation units into one, the way the linker would
+/// combine their objects. A `static` function or variable takes the name
+/// `name.N`, N being its unit's position, when another unit uses its name
+/// too, and never clashes with another unit's names. An
+/// external name may be defined only once, and must name the same kind of
+/// entity, with the same number of parameters, in every unit.
+pub fn merge_programs(units: Vec<(String, Program)>) -> Result<WholeProgram> {
+    let names: Vec<HashSet<String>> = units
+        .iter()
+        .map(|(_, program)| file_scope_names(program))
+        .collect();
+
+    let mut merged = Program {
+        functions: Vec::new(),
+        global_variables: Vec::new(),
+        type_definitions: HashMap::new(),
+    };
+    let mut externals: HashMap<String, External> = HashMap::new();
+    let mut definitions: Vec<HashSet<String>> = Vec::new();
+
+    for (index, (file, mut program)) in units.into_iter().enumerate() {
+        let statics: HashSet<String> = program
+            .functions
+            .iter()
+            .filter(|function| function.is_static)
+            .map(|function| function.name.clone())
+            .collect();
+        let static_variables: HashSet<String> = program
+            .global_variables
+            .iter()
+            .filter(|global| global.storage == Some(GlobalStorage::Static))
+            .map(|global| global.name.clone())
+            .collect();
+        let renames: HashMap<String, String> = statics
+            .iter()
+            .chain(&static_variables)
+            .filter(|name| {
+                names
+                    .iter()
+                    .enumerate()
+                    .any(|(other, names)| other != index && names.contains(*name))
+            })
+            .map(|name| (name.clone(), format!("{}.{}", name, index)))
+            .collect();
+        rename_functions(&mut program, &renames);
+        rename_variables(&mut program, &renames);
+        let static_variables: HashSet<String> = static_variables
+            .into_iter()
+            .map(|name| renames.get(&name).cloned().unwrap_or(name))
+            .collect();
+
+        let mut defined = HashSet::new();
+        for function in program.functions {
+            let body = has_body(&function);
+            if !statics.contains(&function.name) {
+                let signature = function
+                    .has_prototype
+                    .then_some((function.parameters.len(), function.is_variadic));
+                let definition = declare(
+                    &mut externals,
+                    &function.name,
+                    &file,
+                    Some(signature),
+                    body.then_some(function.is_inline_definition()),
+                )?;
+                match definition {
+                    Definition::Kept => {}
+                    Definition::Replaces => {
+                        merged
+                            .functions
+                            .retain(|other| other.name != function.name || !has_body(other));
+                        for earlier in &mut definitions {
+                            earlier.remove(&function.name);
+                        }
+                    }
+                    Definition::Redundant => continue,
+                }
+            }
+            if body {
+                defined.insert(function.name.clone());
+            }
+            merged.functions.push(function);
+        }
+
+        for global in program.global_variables {
+            let initialized = global.initializer.is_some();
+            let seen = if static_variables.contains(&global.name) {
+                // No other unit can refer to it
+                merged
+                    .global_variables
+                    .iter()
+                    .any(|other| other.name == global.name)
+            } else {
+                let seen = externals.contains_key(&global.name);
+                declare(
+                    &mut externals,
+                    &global.name,
+                    &file,
+                    None,
+                    initialized.then_some(false),
+                )?;
+                seen
+            };
+            let earlier = merged
+                .global_variables
+                .iter()
+                .position(|other| other.name == global.name);
+            match earlier {
+                // The others were tentative definitions or `extern` declarations
+                Some(index) if seen && initialized => {
+                    let mut earlier = merged.global_variables.remove(index);
+                    earlier.merge(&global);
+                    merged.global_variables.push(GlobalVariable {
+                        location: earlier.location,
+                        storage: earlier.storage,
+                        thread_local: earlier.thread_local,
+                        attributes: earlier.attributes,
+                        ..global
+                    });
+                }
+                Some(index) if seen => merged.global_variables[index].merge(&global),
+                _ => merged.global_variables.push(global),
+            }
+        }
+
+        for (name, ty) in program.type_definitions {
+            merged.type_definitions.entry(name).or_insert(ty);
+        }
+        definitions.push(defined);
+    }
+
+    Ok(WholeProgram {
+        program: merged,
+        definitions,
+    })
+}
+
+/// Give the variables in `renames` their new names; `rename_functions`
+/// has already renamed the references to them
+fn rename_variables(program: &mut Program, renames: &HashMap<String, String>) {
+    let renamed = |name: &mut String| {
+        if let Some(new) = renames.get(name.as_str()) {
+            *name = new.clone();
+        }
+    };
+    for global in &mut program.global_variables {
+        renamed(&mut global.name);
+    }
+}
+
+/// Make every function the program defines `static` except `main` and
+/// `_start`: nothing outside a whole program calls them, so the optimizer
+/// may drop those it no longer needs.
+pub fn internalize(program: &mut Program) {
+    let defined: HashSet<String> = program
+        .functions
+        .iter()
+        .filter(|function| has_body(function))
+        .map(|function| function.name.clone())
+        .collect();
+    for function in &mut program.functions {
+        if defined.contains(&function.name) && !matches!(function.name.as_str(), "main" | "_start")
+        {
+            function.is_static = true;
+        }
+    }
+}
+
+/// Record a declaration of the external `name` in `file`. `kind` is `None`
+/// for a variable; `definition` says, for a definition, whether it is an
+/// inline one.
+fn declare(
+    externals: &mut HashMap<String, External>,
+    name: &str,
+    file: &str,
+    kind: Option<Option<(usize, bool)>>,
+    definition: Option<bool>,
+) -> Result<Definition> {
+    let external = externals
+        .entry(name.to_string())
+        .or_insert_with(|| External {
+            file: file.to_string(),
+            kind,
+            defined_in: None,
+            inline: false,
+        });
+
+    let conflict = |message: String| Err(AleccError::LinkerError { message });
+    match (external.kind, kind) {
+        (Some(_), None) | (None, Some(_)) => {
+            let what = |kind: Option<_>| {
+                if kind.is_some() {
+                    "a function"
+                } else {
+                    "a variable"
+                }
+            };
+            return conflict(format!(
+                "'{}' is {} in {} but {} in {}",
+                name,
+                what(external.kind),
+                external.file,
+                what(kind),
+                file
+            ));
+        }
+        (Some(Some(first)), Some(Some(second))) if first != second => {
+            let parameters = |(count, variadic): (usize, bool)| {
+                format!(
+                    "{} parameters{}",
+                    count,
+                    if variadic { " and '...'" } else { "" }
+                )
+            };
+            return conflict(format!(
+                "conflicting types for '{}': {} in {}, {} in {}",
+                name,
+                parameters(first),
+                external.file,
+                parameters(second),
+                file
+            ));
+        }
+        // A prototype says more than `int f()`
+        (Some(None), Some(Some(_))) => external.kind = kind,
+        _ => {}
+    }
+
+    let Some(inline) = definition else {
+        return Ok(Definition::Kept);
+    };
+    match &external.defined_in {
+        None => {}
+        Some(_) if inline => return Ok(Definition::Redundant),
+        Some(_) if external.inline => {
+            external.defined_in = Some(file.to_string());
+            external.inline = false;
+            return Ok(Definition::Replaces);
+        }
+        Some(first) => {
+            return conflict(format!(
+                "multiple definition of '{}': in {} and {}",
+                name, first, file
+            ))
+        }
+    }
+    external.defined_in = Some(file.to_string());
+    external.inline = inline;
+    Ok(Definition::Kept)
+}
+
+/// Functions and variables a unit declares at file scope
+fn file_scope_names(program: &Program) -> HashSet<String> {
+    program
+        .functions
+        .iter()
+        .map(|function| function.name.clone())
+        .chain(
+            program
+                .global_variables
+                .iter()
+                .map(|global| global.name.clone()),
+        )
+        .collect()
+}
+
+fn has_body(function: &Function) -> bool {
+    !matches!(&function.body.kind, StatementKind::Block(statements) if statements.is_empty())
+}
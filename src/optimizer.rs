@@ -1,11 +1,16 @@
+use crate::analysis::{is_noreturn_call, noreturn_functions};
+use crate::callgraph::CallGraph;
 use crate::error::Result;
-use crate::parser::Program;
+use crate::parser::{
+    Expression, ExpressionKind, Function, Program, Statement, StatementKind, Type, UnaryOperator,
+};
+use std::collections::{HashMap, HashSet};
 
 pub struct Optimizer {
     level: OptimizationLevel,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OptimizationLevel {
     None,       // -O0
     Basic,      // -O1
@@ -122,11 +127,12 @@ impl Optimizer {
     }
 
     // Basic optimization implementations
-    fn eliminate_dead_code(&mut self, _program: &mut Program) -> Result<()> {
-        // TODO: Implement dead code elimination
-        // - Remove unreachable code
-        // - Remove unused variables
-        // - Remove functions that are never called
+    fn eliminate_dead_code(&mut self, program: &mut Program) -> Result<()> {
+        // TODO: Remove unused variables and other unreachable code
+        let noreturn = noreturn_functions(program);
+        for function in &mut program.functions {
+            remove_code_after_noreturn_calls(&mut function.body, &noreturn);
+        }
         Ok(())
     }
 
@@ -153,11 +159,9 @@ impl Optimizer {
         Ok(())
     }
 
-    fn inline_small_functions(&mut self, _program: &mut Program) -> Result<()> {
-        // TODO: Implement function inlining
-        // - Inline functions that are called only once
-        // - Inline very small functions
-        // - Consider call frequency and function size
+    fn inline_small_functions(&mut self, program: &mut Program) -> Result<()> {
+        // Only functions the programmer marked `inline` at this level
+        inline_calls(program, |function| function.is_inline);
         Ok(())
     }
 
@@ -177,18 +181,15 @@ impl Optimizer {
         Ok(())
     }
 
-    fn aggressive_inlining(&mut self, _program: &mut Program) -> Result<()> {
-        // TODO: Implement aggressive inlining
-        // - Inline more functions based on profiling data
-        // - Cross-module inlining
+    fn aggressive_inlining(&mut self, program: &mut Program) -> Result<()> {
+        // TODO: Cross-module inlining
+        inline_calls(program, |_| true);
         Ok(())
     }
 
-    fn interprocedural_optimizations(&mut self, _program: &mut Program) -> Result<()> {
-        // TODO: Implement IPO
-        // - Whole-program analysis
-        // - Cross-function optimizations
-        // - Global dead code elimination
+    fn interprocedural_optimizations(&mut self, program: &mut Program) -> Result<()> {
+        // TODO: Cross-function optimizations
+        remove_unreachable_functions(program);
         Ok(())
     }
 
@@ -222,6 +223,538 @@ impl Optimizer {
     }
 }
 
+/// A function simple enough to substitute at its call sites: its body is
+/// `return <expr>;`, where the expression only reads its parameters
+struct InlineCandidate {
+    return_type: Type,
+    parameters: Vec<(String, Type)>,
+    body: Expression,
+}
+
+/// Replace calls to eligible single-expression functions with their bodies.
+/// Arguments must be free of side effects, since a parameter may be read any
+/// number of times (or not at all) once substituted; reading a volatile
+/// object counts as one.
+fn inline_calls(program: &mut Program, eligible: impl Fn(&Function) -> bool) {
+    let candidates: HashMap<String, InlineCandidate> = program
+        .functions
+        .iter()
+        .filter(|function| eligible(function))
+        .filter_map(|function| Some((function.name.clone(), inline_candidate(function)?)))
+        .collect();
+    if candidates.is_empty() {
+        return;
+    }
+
+    let globals: HashMap<String, Type> = program
+        .global_variables
+        .iter()
+        .map(|global| (global.name.clone(), global.var_type.clone()))
+        .collect();
+    for function in &mut program.functions {
+        // A local of the same name hides the function being called
+        let mut locals: HashMap<String, Type> = function.parameters.iter().cloned().collect();
+        declarations(&function.body, &mut |name, ty| {
+            locals.insert(name.to_string(), ty.clone());
+        });
+        let volatile: HashSet<String> = globals
+            .iter()
+            .chain(locals.iter())
+            .filter(|(_, ty)| mentions_volatile(ty))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        visit_statement(&mut function.body, &mut |expr| {
+            let ExpressionKind::Call {
+                function,
+                arguments,
+            } = &mut expr.kind
+            else {
+                return;
+            };
+            let ExpressionKind::Identifier(name) = &function.kind else {
+                return;
+            };
+            let Some(candidate) = candidates.get(name) else {
+                return;
+            };
+            if locals.contains_key(name)
+                || arguments.len() != candidate.parameters.len()
+                || !arguments
+                    .iter()
+                    .all(|argument| is_pure(argument, &volatile))
+            {
+                return;
+            }
+
+            let substitutions: HashMap<&str, Expression> = candidate
+                .parameters
+                .iter()
+                .zip(arguments.iter())
+                .map(|((name, ty), argument)| {
+                    let converted = Expression::new(
+                        ExpressionKind::Cast {
+                            target_type: ty.clone(),
+                            expression: Box::new(argument.clone()),
+                        },
+                        argument.span,
+                    );
+                    (name.as_str(), converted)
+                })
+                .collect();
+            let mut body = candidate.body.clone();
+            visit_expression(&mut body, &mut |expr| {
+                if let ExpressionKind::Identifier(name) = &mut expr.kind {
+                    if let Some(argument) = substitutions.get(name.as_str()) {
+                        *expr = argument.clone();
+                    }
+                }
+            });
+            expr.kind = ExpressionKind::Cast {
+                target_type: candidate.return_type.clone(),
+                expression: Box::new(body),
+            };
+        });
+    }
+}
+
+/// Drop the static functions that nothing outside the unit can end up
+/// calling, such as those whose every call was inlined
+fn remove_unreachable_functions(program: &mut Program) {
+    let graph = CallGraph::build(program);
+    let is_static = |name: &str| graph.functions().get(name).is_some_and(|f| f.is_static);
+    let roots = program
+        .functions
+        .iter()
+        .filter(|function| {
+            !is_static(&function.name)
+                || function.attributes.constructor
+                || function.attributes.destructor
+                || graph.functions()[&function.name].address_taken
+        })
+        .map(|function| function.name.as_str());
+    let reachable = graph.reachable(roots);
+    program
+        .functions
+        .retain(|function| !is_static(&function.name) || reachable.contains(&function.name));
+}
+
+/// Drop the statements that follow a call to a `noreturn` function, up to
+/// the next label a `goto` could jump to. Declarations stay, as code after
+/// the label may use them.
+fn remove_code_after_noreturn_calls(statement: &mut Statement, noreturn: &HashSet<String>) {
+    match &mut statement.kind {
+        StatementKind::Block(statements) => remove_dead_statements(statements, noreturn),
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            remove_code_after_noreturn_calls(then_stmt, noreturn);
+            if let Some(else_stmt) = else_stmt {
+                remove_code_after_noreturn_calls(else_stmt, noreturn);
+            }
+        }
+        StatementKind::While { body, .. }
+        | StatementKind::DoWhile { body, .. }
+        | StatementKind::For { body, .. } => remove_code_after_noreturn_calls(body, noreturn),
+        StatementKind::Switch { cases, .. } => {
+            for (_, statements) in cases {
+                remove_dead_statements(statements, noreturn);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn remove_dead_statements(statements: &mut Vec<Statement>, noreturn: &HashSet<String>) {
+    let mut reachable = true;
+    statements.retain_mut(|statement| {
+        if has_label(statement) {
+            reachable = true;
+        }
+        if !reachable {
+            return matches!(statement.kind, StatementKind::Declaration { .. });
+        }
+        remove_code_after_noreturn_calls(statement, noreturn);
+        if is_noreturn_call(statement, noreturn) {
+            reachable = false;
+        }
+        true
+    });
+}
+
+fn has_label(statement: &Statement) -> bool {
+    match &statement.kind {
+        StatementKind::Label(_) => true,
+        StatementKind::Block(statements) => statements.iter().any(has_label),
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => has_label(then_stmt) || else_stmt.as_deref().is_some_and(has_label),
+        StatementKind::While { body, .. }
+        | StatementKind::DoWhile { body, .. }
+        | StatementKind::For { body, .. } => has_label(body),
+        StatementKind::Switch { cases, .. } => cases
+            .iter()
+            .flat_map(|(_, statements)| statements)
+            .any(has_label),
+        _ => false,
+    }
+}
+
+fn inline_candidate(function: &Function) -> Option<InlineCandidate> {
+    if function.is_variadic
+        || !is_scalar(&function.return_type)
+        || !function.parameters.iter().all(|(_, ty)| is_scalar(ty))
+    {
+        return None;
+    }
+    let body = match &function.body.kind {
+        StatementKind::Block(statements) => match statements.as_slice() {
+            [Statement {
+                kind: StatementKind::Return(Some(expr)),
+                ..
+            }] => expr,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let parameters: HashSet<&str> = function
+        .parameters
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    reads_only(body, &parameters).then(|| InlineCandidate {
+        return_type: function.return_type.clone(),
+        parameters: function.parameters.clone(),
+        body: body.clone(),
+    })
+}
+
+/// Types a call's arguments and result can be converted to with a cast
+fn is_scalar(ty: &Type) -> bool {
+    match ty {
+        Type::Typedef(_, inner) | Type::Qualified(_, inner) => is_scalar(inner),
+        Type::Void
+        | Type::Array(..)
+        | Type::Function { .. }
+        | Type::Struct { .. }
+        | Type::Union { .. }
+        | Type::VaList => false,
+        _ => true,
+    }
+}
+
+/// Whether `expr` computes a value from `parameters` alone, without side
+/// effects, calls or taking addresses
+fn reads_only(expr: &Expression, parameters: &HashSet<&str>) -> bool {
+    match &expr.kind {
+        ExpressionKind::Identifier(name) => parameters.contains(name.as_str()),
+        ExpressionKind::IntegerLiteral(_)
+        | ExpressionKind::FloatLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::CharLiteral(_)
+        | ExpressionKind::BooleanLiteral(_)
+        | ExpressionKind::Sizeof(_) => true,
+        ExpressionKind::Binary { left, right, .. } => {
+            reads_only(left, parameters) && reads_only(right, parameters)
+        }
+        ExpressionKind::Unary { operator, operand } => {
+            matches!(
+                operator,
+                UnaryOperator::Plus
+                    | UnaryOperator::Minus
+                    | UnaryOperator::LogicalNot
+                    | UnaryOperator::BitwiseNot
+                    | UnaryOperator::Dereference
+            ) && reads_only(operand, parameters)
+        }
+        ExpressionKind::Index { array, index } => {
+            reads_only(array, parameters) && reads_only(index, parameters)
+        }
+        ExpressionKind::Member { object, .. } => reads_only(object, parameters),
+        ExpressionKind::Cast { expression, .. } => reads_only(expression, parameters),
+        ExpressionKind::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            reads_only(condition, parameters)
+                && reads_only(then_expr, parameters)
+                && reads_only(else_expr, parameters)
+        }
+        ExpressionKind::Call { .. }
+        | ExpressionKind::Assignment { .. }
+        | ExpressionKind::VaArg { .. }
+        | ExpressionKind::LabelAddress(_) => false,
+    }
+}
+
+/// Whether evaluating `expr` has no side effects, so it may be evaluated any
+/// number of times; the names in `volatile` are variables whose reads count as one
+fn is_pure(expr: &Expression, volatile: &HashSet<String>) -> bool {
+    match &expr.kind {
+        ExpressionKind::Call { .. }
+        | ExpressionKind::Assignment { .. }
+        | ExpressionKind::VaArg { .. } => false,
+        ExpressionKind::Unary { operator, operand } => {
+            !matches!(
+                operator,
+                UnaryOperator::PreIncrement
+                    | UnaryOperator::PostIncrement
+                    | UnaryOperator::PreDecrement
+                    | UnaryOperator::PostDecrement
+            ) && is_pure(operand, volatile)
+        }
+        ExpressionKind::Binary { left, right, .. }
+        | ExpressionKind::Index {
+            array: left,
+            index: right,
+        } => is_pure(left, volatile) && is_pure(right, volatile),
+        ExpressionKind::Member { object, .. } => is_pure(object, volatile),
+        ExpressionKind::Cast { expression, .. } => is_pure(expression, volatile),
+        ExpressionKind::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            is_pure(condition, volatile)
+                && is_pure(then_expr, volatile)
+                && is_pure(else_expr, volatile)
+        }
+        ExpressionKind::IntegerLiteral(_)
+        | ExpressionKind::FloatLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::CharLiteral(_)
+        | ExpressionKind::BooleanLiteral(_)
+        | ExpressionKind::Sizeof(_)
+        | ExpressionKind::LabelAddress(_) => true,
+        ExpressionKind::Identifier(name) => !volatile.contains(name),
+    }
+}
+
+/// Whether `ty` is volatile-qualified at any level, so that reading a
+/// variable of the type may access a volatile object
+fn mentions_volatile(ty: &Type) -> bool {
+    match ty {
+        Type::Qualified(qualifiers, inner) => qualifiers.is_volatile || mentions_volatile(inner),
+        Type::Pointer(inner) | Type::Array(inner, _) | Type::Typedef(_, inner) => {
+            mentions_volatile(inner)
+        }
+        _ => false,
+    }
+}
+
+fn declared_names(statement: &Statement, names: &mut HashSet<String>) {
+    declarations(statement, &mut |name, _| {
+        names.insert(name.to_string());
+    });
+}
+
+/// Call `f` with the name and type of every local `statement` declares
+fn declarations(statement: &Statement, f: &mut impl FnMut(&str, &Type)) {
+    match &statement.kind {
+        StatementKind::Declaration { name, var_type, .. } => f(name, var_type),
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                declarations(statement, f);
+            }
+        }
+        StatementKind::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            declarations(then_stmt, f);
+            if let Some(else_stmt) = else_stmt {
+                declarations(else_stmt, f);
+            }
+        }
+        StatementKind::While { body, .. } | StatementKind::DoWhile { body, .. } => {
+            declarations(body, f)
+        }
+        StatementKind::For { init, body, .. } => {
+            if let Some(init) = init {
+                declarations(init, f);
+            }
+            declarations(body, f);
+        }
+        StatementKind::Switch { cases, .. } => {
+            for statement in cases.iter().flat_map(|(_, statements)| statements) {
+                declarations(statement, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Apply `f` to every expression in `statement`, innermost first
+fn visit_statement(statement: &mut Statement, f: &mut impl FnMut(&mut Expression)) {
+    match &mut statement.kind {
+        StatementKind::Expression(expr)
+        | StatementKind::Return(Some(expr))
+        | StatementKind::ComputedGoto(expr) => visit_expression(expr, f),
+        StatementKind::Declaration {
+            initializer: Some(expr),
+            ..
+        } => visit_expression(expr, f),
+        StatementKind::Block(statements) => {
+            for statement in statements {
+                visit_statement(statement, f);
+            }
+        }
+        StatementKind::If {
+            condition,
+            then_stmt,
+            else_stmt,
+        } => {
+            visit_expression(condition, f);
+            visit_statement(then_stmt, f);
+            if let Some(else_stmt) = else_stmt {
+                visit_statement(else_stmt, f);
+            }
+        }
+        StatementKind::While { condition, body } | StatementKind::DoWhile { body, condition } => {
+            visit_expression(condition, f);
+            visit_statement(body, f);
+        }
+        StatementKind::For {
+            init,
+            condition,
+            increment,
+            body,
+        } => {
+            if let Some(init) = init {
+                visit_statement(init, f);
+            }
+            if let Some(condition) = condition {
+                visit_expression(condition, f);
+            }
+            if let Some(increment) = increment {
+                visit_expression(increment, f);
+            }
+            visit_statement(body, f);
+        }
+        StatementKind::Switch { expression, cases } => {
+            visit_expression(expression, f);
+            for statement in cases.iter_mut().flat_map(|(_, statements)| statements) {
+                visit_statement(statement, f);
+            }
+        }
+        StatementKind::Declaration { .. }
+        | StatementKind::Return(None)
+        | StatementKind::Break
+        | StatementKind::Continue
+        | StatementKind::Goto(_)
+        | StatementKind::Label(_)
+        | StatementKind::Fallthrough => {}
+    }
+}
+
+fn visit_expression(expr: &mut Expression, f: &mut impl FnMut(&mut Expression)) {
+    match &mut expr.kind {
+        ExpressionKind::Binary { left, right, .. }
+        | ExpressionKind::Index {
+            array: left,
+            index: right,
+        } => {
+            visit_expression(left, f);
+            visit_expression(right, f);
+        }
+        ExpressionKind::Assignment { target, value, .. } => {
+            visit_expression(target, f);
+            visit_expression(value, f);
+        }
+        ExpressionKind::Unary { operand, .. } => visit_expression(operand, f),
+        ExpressionKind::Call {
+            function,
+            arguments,
+        } => {
+            visit_expression(function, f);
+            for argument in arguments {
+                visit_expression(argument, f);
+            }
+        }
+        ExpressionKind::Member { object, .. } => visit_expression(object, f),
+        ExpressionKind::Cast { expression, .. } => visit_expression(expression, f),
+        ExpressionKind::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            visit_expression(condition, f);
+            visit_expression(then_expr, f);
+            visit_expression(else_expr, f);
+        }
+        ExpressionKind::VaArg { list, .. } => visit_expression(list, f),
+        ExpressionKind::IntegerLiteral(_)
+        | ExpressionKind::FloatLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::CharLiteral(_)
+        | ExpressionKind::BooleanLiteral(_)
+        | ExpressionKind::Identifier(_)
+        | ExpressionKind::Sizeof(_)
+        | ExpressionKind::LabelAddress(_) => {}
+    }
+    f(expr);
+}
+
+/// Rename the `static` functions of a translation unit to `{prefix}{name}`, so
+/// internal symbols from different objects can be told apart (`--symbol-prefix`)
+pub fn prefix_internal_symbols(program: &mut Program, prefix: &str) {
+    let renames: HashMap<String, String> = program
+        .functions
+        .iter()
+        .filter(|function| function.is_static)
+        .map(|function| {
+            (
+                function.name.clone(),
+                format!("{}{}", prefix, function.name),
+            )
+        })
+        .collect();
+    rename_functions(program, &renames);
+}
+
+/// Rename functions, and every reference to them that a local or parameter
+/// of the same name doesn't hide
+pub fn rename_functions(program: &mut Program, renames: &HashMap<String, String>) {
+    if renames.is_empty() {
+        return;
+    }
+
+    for function in &mut program.functions {
+        let mut shadowed = HashSet::new();
+        declared_names(&function.body, &mut shadowed);
+        shadowed.extend(function.parameters.iter().map(|(name, _)| name.clone()));
+
+        visit_statement(&mut function.body, &mut |expr| {
+            if let ExpressionKind::Identifier(name) = &mut expr.kind {
+                if let Some(renamed) = renames.get(name).filter(|_| !shadowed.contains(name)) {
+                    *name = renamed.clone();
+                }
+            }
+        });
+        if let Some(renamed) = renames.get(&function.name) {
+            function.name = renamed.clone();
+        }
+    }
+
+    for global in &mut program.global_variables {
+        if let Some(initializer) = &mut global.initializer {
+            visit_expression(initializer, &mut |expr| {
+                if let ExpressionKind::Identifier(name) = &mut expr.kind {
+                    if let Some(renamed) = renames.get(name) {
+                        *name = renamed.clone();
+                    }
+                }
+            });
+        }
+    }
+}
+
 // Additional optimization passes that can be applied independently
 #[allow(dead_code)]
 pub struct OptimizationPasses;
@@ -1,8 +1,15 @@
+use crate::builtins::{atomic, signature};
+use crate::constant::{integer_constant, ConstantError, ConstantEvaluator};
 use crate::error::{AleccError, Result};
-use crate::lexer::{Token, TokenType};
+use crate::format;
+use crate::language::LanguageOptions;
+use crate::layout::TypeLayout;
+use crate::lexer::{IntegerSuffix, Span, Token, TokenType};
+use crate::targets::{Target, TargetInfo};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Void,
     Char,
@@ -12,6 +19,14 @@ pub enum Type {
     Float,
     Double,
     Bool,
+    UnsignedChar,
+    UnsignedShort,
+    UnsignedInt,
+    UnsignedLong,
+    /// `long long`, 64 bits on every target: on i386 it is twice as wide as
+    /// `long` and takes a pair of registers
+    LongLong,
+    UnsignedLongLong,
     #[allow(dead_code)]
     Pointer(Box<Type>),
     #[allow(dead_code)]
@@ -26,11 +41,14 @@ pub enum Type {
     Struct {
         name: String,
         fields: Vec<(String, Type)>,
+        /// `__attribute__((packed))`: members are not padded to their alignment
+        packed: bool,
     },
     #[allow(dead_code)]
     Union {
         name: String,
         fields: Vec<(String, Type)>,
+        packed: bool,
     },
     #[allow(dead_code)]
     Enum {
@@ -39,10 +57,183 @@ pub enum Type {
     },
     #[allow(dead_code)]
     Typedef(String, Box<Type>),
+    /// A type with `const`, `volatile` or `restrict` applied
+    Qualified(Qualifiers, Box<Type>),
+    /// `__builtin_va_list`; its size and layout depend on the target ABI
+    VaList,
+}
+
+/// The GCC `__attribute__((...))` and C23 `[[...]]` attributes the compiler
+/// acts on; others are accepted and ignored
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attributes {
+    /// `aligned(N)`, in bytes
+    pub aligned: Option<usize>,
+    pub packed: bool,
+    pub noreturn: bool,
+    /// Run before `main` (`constructor`) or after it returns (`destructor`)
+    pub constructor: bool,
+    pub destructor: bool,
+    /// `constructor(N)` and `destructor(N)`: lower priorities run their
+    /// constructors earlier and their destructors later
+    pub constructor_priority: Option<u16>,
+    pub destructor_priority: Option<u16>,
+    pub section: Option<String>,
+    /// `[[nodiscard]]` or `warn_unused_result`: calls shouldn't ignore the result
+    pub nodiscard: bool,
+    /// `[[fallthrough]]`, which only applies to an empty statement
+    pub fallthrough: bool,
+    /// `unused` or `[[maybe_unused]]`: no -Wunused-* warning when it isn't used
+    pub unused: bool,
+}
+
+impl Attributes {
+    pub fn merge(&mut self, other: Attributes) {
+        self.aligned = self.aligned.max(other.aligned);
+        self.packed |= other.packed;
+        self.noreturn |= other.noreturn;
+        self.constructor |= other.constructor;
+        self.destructor |= other.destructor;
+        self.constructor_priority = other.constructor_priority.or(self.constructor_priority);
+        self.destructor_priority = other.destructor_priority.or(self.destructor_priority);
+        if other.section.is_some() {
+            self.section = other.section;
+        }
+        self.nodiscard |= other.nodiscard;
+        self.fallthrough |= other.fallthrough;
+        self.unused |= other.unused;
+    }
+
+    /// Names of the attributes that are set
+    fn names(&self) -> Vec<&'static str> {
+        [
+            ("aligned", self.aligned.is_some()),
+            ("packed", self.packed),
+            ("noreturn", self.noreturn),
+            ("constructor", self.constructor),
+            ("destructor", self.destructor),
+            ("section", self.section.is_some()),
+            ("nodiscard", self.nodiscard),
+            ("fallthrough", self.fallthrough),
+            ("unused", self.unused),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
+}
+
+/// Type qualifiers; `restrict` only applies to pointer types
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Qualifiers {
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_restrict: bool,
+}
+
+impl Qualifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn union(self, other: Qualifiers) -> Qualifiers {
+        Qualifiers {
+            is_const: self.is_const || other.is_const,
+            is_volatile: self.is_volatile || other.is_volatile,
+            is_restrict: self.is_restrict || other.is_restrict,
+        }
+    }
+}
+
+impl Function {
+    /// A C99 inline definition: other translation units may define the function too,
+    /// so it is emitted as a weak symbol rather than a strong one
+    pub fn is_inline_definition(&self) -> bool {
+        self.is_inline && !self.is_static && !self.is_extern
+    }
+}
+
+#[allow(dead_code)]
+impl Type {
+    /// Qualifiers at the top level of the type, including those of a typedef's type
+    pub fn qualifiers(&self) -> Qualifiers {
+        match self {
+            Type::Qualified(qualifiers, inner) => qualifiers.union(inner.qualifiers()),
+            Type::Typedef(_, aliased) => aliased.qualifiers(),
+            _ => Qualifiers::default(),
+        }
+    }
+
+    /// The type without the qualifiers written directly on it
+    pub fn unqualified(&self) -> &Type {
+        match self {
+            Type::Qualified(_, inner) => inner.unqualified(),
+            ty => ty,
+        }
+    }
+
+    /// Whether this is one of the `unsigned` integer types
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            Type::UnsignedChar
+                | Type::UnsignedShort
+                | Type::UnsignedInt
+                | Type::UnsignedLong
+                | Type::UnsignedLongLong
+        )
+    }
+
+    /// The signed integer type of the same width as an `unsigned` one; other
+    /// types are returned as they are
+    pub fn to_signed(&self) -> Type {
+        match self {
+            Type::UnsignedChar => Type::Char,
+            Type::UnsignedShort => Type::Short,
+            Type::UnsignedInt => Type::Int,
+            Type::UnsignedLong => Type::Long,
+            Type::UnsignedLongLong => Type::LongLong,
+            ty => ty.clone(),
+        }
+    }
+}
+
+/// An expression and where it was parsed from
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Whether the expression designates an object in memory; a member of a
+    /// struct returned by value (`f().x`) does not
+    pub fn is_lvalue(&self) -> bool {
+        match &self.kind {
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Index { .. }
+            | ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                ..
+            } => true,
+            ExpressionKind::Member {
+                object, is_arrow, ..
+            } => *is_arrow || object.is_lvalue(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
-pub enum Expression {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExpressionKind {
     IntegerLiteral(i64),
     #[allow(dead_code)]
     FloatLiteral(f64),
@@ -93,9 +284,17 @@ pub enum Expression {
         then_expr: Box<Expression>,
         else_expr: Box<Expression>,
     },
+    /// `__builtin_va_arg(list, type)`, which takes a type name as its second operand
+    VaArg {
+        list: Box<Expression>,
+        arg_type: Type,
+    },
+    /// GNU C's `&&label`: the address of a label in the same function, a `void *`
+    LabelAddress(String),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOperator {
     Add,
     Subtract,
@@ -118,6 +317,7 @@ pub enum BinaryOperator {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOperator {
     Plus,
     Minus,
@@ -132,33 +332,66 @@ pub enum UnaryOperator {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AssignmentOperator {
     Assign,
     PlusAssign,
     MinusAssign,
     MultiplyAssign,
     DivideAssign,
-    #[allow(dead_code)]
     ModuloAssign,
-    #[allow(dead_code)]
     BitwiseAndAssign,
-    #[allow(dead_code)]
     BitwiseOrAssign,
-    #[allow(dead_code)]
     BitwiseXorAssign,
-    #[allow(dead_code)]
     LeftShiftAssign,
-    #[allow(dead_code)]
     RightShiftAssign,
 }
 
+impl AssignmentOperator {
+    /// The operator `a op= b` applies to `a` and `b`; `None` for plain `=`
+    pub fn binary_operator(&self) -> Option<BinaryOperator> {
+        Some(match self {
+            AssignmentOperator::Assign => return None,
+            AssignmentOperator::PlusAssign => BinaryOperator::Add,
+            AssignmentOperator::MinusAssign => BinaryOperator::Subtract,
+            AssignmentOperator::MultiplyAssign => BinaryOperator::Multiply,
+            AssignmentOperator::DivideAssign => BinaryOperator::Divide,
+            AssignmentOperator::ModuloAssign => BinaryOperator::Modulo,
+            AssignmentOperator::BitwiseAndAssign => BinaryOperator::BitwiseAnd,
+            AssignmentOperator::BitwiseOrAssign => BinaryOperator::BitwiseOr,
+            AssignmentOperator::BitwiseXorAssign => BinaryOperator::BitwiseXor,
+            AssignmentOperator::LeftShiftAssign => BinaryOperator::LeftShift,
+            AssignmentOperator::RightShiftAssign => BinaryOperator::RightShift,
+        })
+    }
+}
+
+/// A statement and where it was parsed from
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
 #[derive(Debug, Clone)]
-pub enum Statement {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StatementKind {
     Expression(Expression),
     Declaration {
         name: String,
         var_type: Type,
         initializer: Option<Expression>,
+        /// Alignment requested with `_Alignas`, in bytes
+        alignment: Option<usize>,
+        /// Marked `unused` or `[[maybe_unused]]`
+        unused: bool,
     },
     Block(Vec<Statement>),
     If {
@@ -181,69 +414,251 @@ pub enum Statement {
         body: Box<Statement>,
         condition: Expression,
     },
-    #[allow(dead_code)]
     Switch {
         expression: Expression,
         cases: Vec<(Option<Expression>, Vec<Statement>)>,
     },
     Return(Option<Expression>),
-    #[allow(dead_code)]
     Break,
-    #[allow(dead_code)]
     Continue,
-    #[allow(dead_code)]
     Goto(String),
-    #[allow(dead_code)]
+    /// GNU C's `goto *address;`, to a label whose address `&&label` took
+    ComputedGoto(Expression),
     Label(String),
+    /// `[[fallthrough]];`, which does nothing but mark a switch fall-through as intended
+    Fallthrough,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     pub name: String,
     #[allow(dead_code)]
     pub return_type: Type,
     pub parameters: Vec<(String, Type)>,
     pub body: Statement,
-    #[allow(dead_code)]
+    /// Where the body's closing brace is; the `;` of a declaration without one
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub end: Span,
     pub is_inline: bool,
-    #[allow(dead_code)]
     pub is_static: bool,
     #[allow(dead_code)]
     pub is_extern: bool,
     #[allow(dead_code)]
     pub is_variadic: bool,
+    /// Whether the declaration says what the parameters are; `int f()` doesn't before C23
+    pub has_prototype: bool,
+    pub attributes: Attributes,
+    /// Parameters marked `unused` or `[[maybe_unused]]`
+    pub unused_parameters: Vec<String>,
+}
+
+/// A file-scope variable, or a `static` local, which becomes one. A
+/// variable declared more than once has an entry for each declaration, all
+/// agreeing on its linkage, attributes and where it was first declared
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlobalVariable {
+    pub name: String,
+    pub var_type: Type,
+    pub initializer: Option<Expression>,
+    /// Where the variable is first declared
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub location: Span,
+    /// `static`, or only ever `extern` without an initializer; `None` when
+    /// it is defined here with external linkage
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub storage: Option<GlobalStorage>,
+    /// Declared `_Thread_local` (or `__thread`): each thread has its own copy
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub thread_local: bool,
+    /// `aligned` also holds what `_Alignas` asks for
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub attributes: Attributes,
+}
+
+impl GlobalVariable {
+    /// Take in what another declaration of the same variable says: `static`
+    /// anywhere makes it internal, and a definition anywhere makes an
+    /// `extern` declaration refer to it
+    pub fn merge(&mut self, other: &GlobalVariable) {
+        self.storage = match (self.storage, other.storage) {
+            (Some(GlobalStorage::Static), _) | (_, Some(GlobalStorage::Static)) => {
+                Some(GlobalStorage::Static)
+            }
+            (Some(GlobalStorage::Extern), Some(GlobalStorage::Extern)) => {
+                Some(GlobalStorage::Extern)
+            }
+            _ => None,
+        };
+        self.thread_local |= other.thread_local;
+        self.attributes.merge(other.attributes.clone());
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub functions: Vec<Function>,
-    pub global_variables: Vec<(String, Type, Option<Expression>)>,
+    pub global_variables: Vec<GlobalVariable>,
     #[allow(dead_code)]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_sorted"))]
     pub type_definitions: HashMap<String, Type>,
 }
 
+/// How a global variable is declared, where that isn't a plain definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GlobalStorage {
+    /// Defined here, visible only in this translation unit
+    Static,
+    /// Defined in another translation unit
+    Extern,
+}
+
+/// Write a map with its keys in order, so the same program always serializes the same way
+#[cfg(feature = "serde")]
+fn serialize_sorted<S, V>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: serde::Serialize,
+{
+    let sorted: std::collections::BTreeMap<_, _> = map.iter().collect();
+    serde::Serialize::serialize(&sorted, serializer)
+}
+
+/// A warning about a type-dependent construct, found while parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeWarning {
+    /// The `-W` option that controls it, e.g. `conversion`
+    pub option: &'static str,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    /// A follow-up note and where it points, e.g. which header to include
+    pub note: Option<(String, Span)>,
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    typedefs: HashMap<String, Type>,
+    /// Declared types of variables and functions, innermost scope last,
+    /// so `_Generic` can be resolved while parsing
+    scopes: Vec<HashMap<String, Type>>,
+    /// Values of the enumeration constants declared so far, scoped like `scopes`
+    enumerators: Vec<HashMap<String, i64>>,
+    /// Sizes of the types and struct/union definitions seen so far, for `sizeof`
+    layout: TypeLayout,
+    /// Target whose type alignments `_Alignof` and `_Alignas(type)` evaluate to
+    target: Target,
+    warnings: Vec<TypeWarning>,
+    /// Attributes of each function declared so far, which carry over to later declarations
+    function_attributes: HashMap<String, Attributes>,
+    language: LanguageOptions,
+    /// The function whose body is being parsed, which `__func__` names
+    current_function: Option<String>,
+    /// The global names of the `static` locals declared in each scope, like `scopes`
+    static_names: Vec<HashMap<String, String>>,
+    /// `static` locals of the function being parsed, which become globals
+    /// named after it
+    static_locals: Vec<GlobalVariable>,
+    /// The hidden local holding the size of each variable length array
+    /// declared in a scope, like `scopes`, which `sizeof` reads
+    variable_lengths: Vec<HashMap<String, String>>,
+    /// Whether the declarator being parsed may be a variable length array,
+    /// as a local declared directly in a block can
+    variable_length_allowed: bool,
+    /// The length of the variable length array the last declarator declared
+    variable_length: Option<Expression>,
+    /// Whether the statement about to be parsed is directly in a block
+    block_statement: bool,
+    /// Statements a declaration needs before it, which its block takes
+    hidden_statements: Vec<Statement>,
+    /// For each block being parsed, innermost last, the hidden local its
+    /// stack pointer is saved in once it declares a variable length array
+    stack_saves: Vec<Option<String>>,
+    /// The loops and switches being parsed, innermost last: whether
+    /// `continue` goes to it, and how many blocks were open around it
+    jump_scopes: Vec<(bool, usize)>,
+    /// Hidden locals declared so far, which numbers their names apart
+    hidden_locals: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        let mut parser = Self { tokens, current: 0 };
+        let mut typedefs = HashMap::new();
+        // <stdarg.h> can't be processed by the preprocessor yet, so provide its typedef
+        typedefs.insert("va_list".to_string(), Type::VaList);
+
+        let mut parser = Self {
+            tokens,
+            current: 0,
+            typedefs,
+            scopes: vec![HashMap::new()],
+            enumerators: vec![HashMap::new()],
+            layout: TypeLayout::new(Target::native()),
+            target: Target::native(),
+            warnings: Vec::new(),
+            function_attributes: HashMap::new(),
+            language: LanguageOptions::default(),
+            current_function: None,
+            static_names: vec![HashMap::new()],
+            static_locals: Vec::new(),
+            variable_lengths: vec![HashMap::new()],
+            variable_length_allowed: false,
+            variable_length: None,
+            block_statement: false,
+            hidden_statements: Vec::new(),
+            stack_saves: Vec::new(),
+            jump_scopes: Vec::new(),
+            hidden_locals: 0,
+        };
         parser.skip_newlines(); // Skip initial newlines
         parser
     }
 
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self.layout = TypeLayout::new(target);
+        self
+    }
+
+    pub fn with_language(mut self, language: LanguageOptions) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Warnings found by the last `parse`, in source order
+    pub fn warnings(&self) -> &[TypeWarning] {
+        &self.warnings
+    }
+
     pub fn parse(&mut self) -> Result<Program> {
         let mut functions = Vec::new();
-        let mut global_variables = Vec::new();
+        let mut global_variables: Vec<GlobalVariable> = Vec::new();
         let mut type_definitions = HashMap::new();
 
         while !self.is_at_end() {
+            if self.match_token(&TokenType::StaticAssert) {
+                self.parse_static_assert()?;
+                continue;
+            }
+            let span = self.current_token()?.span();
             match self.parse_declaration()? {
-                Declaration::Function(func) => functions.push(func),
-                Declaration::Variable(name, var_type, init) => {
-                    global_variables.push((name, var_type, init));
+                Declaration::Function(func) => {
+                    functions.push(*func);
+                    for mut global in self.static_locals.drain(..) {
+                        global.location = span;
+                        global_variables.push(global);
+                    }
+                }
+                Declaration::Variable(mut global) => {
+                    global.location = span;
+                    global_variables.push(*global);
                 }
                 Declaration::TypeDef(name, type_def) => {
                     type_definitions.insert(name, type_def);
@@ -251,6 +666,24 @@ impl Parser {
             }
         }
 
+        // Every declaration of a variable carries what all of them say
+        let mut declared: HashMap<String, GlobalVariable> = HashMap::new();
+        for global in &global_variables {
+            match declared.get_mut(&global.name) {
+                Some(first) => first.merge(global),
+                None => {
+                    declared.insert(global.name.clone(), global.clone());
+                }
+            }
+        }
+        for global in &mut global_variables {
+            let first = &declared[&global.name];
+            global.location = first.location;
+            global.storage = first.storage;
+            global.thread_local = first.thread_local;
+            global.attributes = first.attributes.clone();
+        }
+
         Ok(Program {
             functions,
             global_variables,
@@ -262,41 +695,64 @@ impl Parser {
         if self.match_token(&TokenType::Typedef) {
             self.parse_typedef()
         } else {
-            let storage_class = self.parse_storage_class();
+            let mut attributes = self.parse_attributes()?;
+            let alignment = self.parse_alignment_specifiers()?;
+            let (storage_class, is_inline, thread_local) = self.parse_storage_class();
+            attributes.merge(self.parse_attributes()?);
             let base_type = self.parse_type()?;
-
-            if self.check(&TokenType::LeftParen)
-                || (self.check(&TokenType::Identifier("".to_string()))
-                    && self.peek_ahead(1)?.token_type == TokenType::LeftParen)
+            attributes.merge(self.parse_attributes()?);
+
+            let function_pointer = self.check(&TokenType::LeftParen)
+                && self.peek_ahead(1)?.token_type == TokenType::Multiply;
+            if alignment.is_none()
+                && !function_pointer
+                && (self.check(&TokenType::LeftParen)
+                    || (self.check(&TokenType::Identifier("".to_string()))
+                        && self.peek_ahead(1)?.token_type == TokenType::LeftParen))
             {
-                self.parse_function_declaration(storage_class, base_type)
+                self.parse_function_declaration(storage_class, is_inline, attributes, base_type)
+            } else if is_inline {
+                let token = self.current_token()?;
+                Err(AleccError::ParseError {
+                    line: token.line,
+                    column: token.column,
+                    message: "'inline' can only be applied to functions".to_string(),
+                })
             } else {
-                self.parse_variable_declaration(storage_class, base_type)
+                self.parse_variable_declaration(
+                    storage_class,
+                    base_type,
+                    alignment,
+                    attributes,
+                    thread_local,
+                )
             }
         }
     }
 
     fn parse_type(&mut self) -> Result<Type> {
-        // Skip type qualifiers like const, volatile
-        while self.match_token(&TokenType::Const) || self.match_token(&TokenType::Volatile) {
-            // Just consume the qualifier for now
-        }
+        let leading = self.parse_qualifiers();
 
-        let mut base_type = match &self.advance()?.token_type {
+        let base_type = match &self.advance()?.token_type {
             TokenType::Void => Type::Void,
-            TokenType::Char => Type::Char,
-            TokenType::Short => Type::Short,
-            TokenType::Int => Type::Int,
-            TokenType::Long => Type::Long,
+            TokenType::Char
+            | TokenType::Short
+            | TokenType::Int
+            | TokenType::Long
+            | TokenType::Signed
+            | TokenType::Unsigned => self.parse_integer_specifiers()?,
             TokenType::Float => Type::Float,
             TokenType::Double => Type::Double,
             TokenType::Bool => Type::Bool,
             TokenType::Struct => self.parse_struct_type()?,
             TokenType::Union => self.parse_union_type()?,
             TokenType::Enum => self.parse_enum_type()?,
+            TokenType::Identifier(name) if name == "__builtin_va_list" => Type::VaList,
             TokenType::Identifier(name) => {
                 // Could be a typedef name
-                Type::Typedef(name.clone(), Box::new(Type::Void)) // Placeholder
+                let name = name.clone();
+                let aliased = self.typedefs.get(&name).cloned().unwrap_or(Type::Void); // Placeholder if unknown
+                Type::Typedef(name, Box::new(aliased))
             }
             _ => {
                 return Err(AleccError::ParseError {
@@ -306,20 +762,291 @@ impl Parser {
                 });
             }
         };
+        // A later `sizeof(struct tag)` needs the definition
+        self.layout.record_aggregate(&base_type);
+
+        // Qualifiers may also follow the specifier (`int const`)
+        let qualifiers = leading.union(self.parse_qualifiers());
+        let mut base_type = self.qualify(base_type, qualifiers)?;
 
-        // Handle pointer declarators
+        // Handle pointer declarators, each with its own qualifiers (`char *const p`)
         while self.match_token(&TokenType::Multiply) {
-            // Skip const after *
-            while self.match_token(&TokenType::Const) || self.match_token(&TokenType::Volatile) {
-                // Just consume the qualifier for now
-            }
-            base_type = Type::Pointer(Box::new(base_type));
+            let qualifiers = self.parse_qualifiers();
+            base_type = self.qualify(Type::Pointer(Box::new(base_type)), qualifiers)?;
         }
 
         Ok(base_type)
     }
 
+    /// The rest of a sequence of integer type specifiers, in any order, whose
+    /// first has just been consumed: `unsigned`, `long int`, `short signed`
+    fn parse_integer_specifiers(&mut self) -> Result<Type> {
+        let (mut signed, mut unsigned, mut char, mut short, mut int, mut long) = (0, 0, 0, 0, 0, 0);
+        let mut token = self.previous()?.clone();
+        loop {
+            match token.token_type {
+                TokenType::Signed => signed += 1,
+                TokenType::Unsigned => unsigned += 1,
+                TokenType::Char => char += 1,
+                TokenType::Short => short += 1,
+                TokenType::Int => int += 1,
+                TokenType::Long => long += 1,
+                _ => unreachable!("only integer specifiers are counted"),
+            }
+            let next = self.current_token()?;
+            if !matches!(
+                next.token_type,
+                TokenType::Signed
+                    | TokenType::Unsigned
+                    | TokenType::Char
+                    | TokenType::Short
+                    | TokenType::Int
+                    | TokenType::Long
+            ) {
+                break;
+            }
+            token = self.advance()?.clone();
+        }
+
+        let invalid = |message: &str| {
+            Err(AleccError::ParseError {
+                line: token.line,
+                column: token.column,
+                message: message.to_string(),
+            })
+        };
+        if signed + unsigned > 1 {
+            return invalid("both 'signed' and 'unsigned' in declaration specifiers");
+        }
+        if long > 2 {
+            return invalid("'long long long' is too long for GCC");
+        }
+        if char + short + long.min(1) > 1 || int > 1 || (char == 1 && int == 1) {
+            return invalid("two or more data types in declaration specifiers");
+        }
+        let unsigned = unsigned == 1;
+        Ok(match (char, short, long, unsigned) {
+            (_, _, 2, false) => Type::LongLong,
+            (_, _, 2, true) => Type::UnsignedLongLong,
+            (1, _, _, false) => Type::Char,
+            (1, _, _, true) => Type::UnsignedChar,
+            (_, 1, _, false) => Type::Short,
+            (_, 1, _, true) => Type::UnsignedShort,
+            (_, _, 1, false) => Type::Long,
+            (_, _, 1, true) => Type::UnsignedLong,
+            (_, _, _, false) => Type::Int,
+            (_, _, _, true) => Type::UnsignedInt,
+        })
+    }
+
+    /// Array suffixes after a declarator's name; `int a[2][3]` is an array of
+    /// two arrays of three ints. Where a variable length array may be
+    /// declared, the first size can be computed at run time: the array's
+    /// size is then left out and the expression kept in `variable_length`
+    fn parse_array_declarator(&mut self, element: Type) -> Result<Type> {
+        // Sizes are expressions of their own, where no array can vary
+        let variable_length_allowed = std::mem::take(&mut self.variable_length_allowed);
+        let mut sizes = Vec::new();
+        while self.match_token(&TokenType::LeftBracket) {
+            let size = if self.check(&TokenType::RightBracket) {
+                None
+            } else {
+                let size_expr = self.parse_expression()?;
+                let size = match self.evaluate(&size_expr) {
+                    Ok(size) => size,
+                    Err(ConstantError::NotConstant(_))
+                        if sizes.is_empty()
+                            && variable_length_allowed
+                            && self.language.variable_length_arrays() =>
+                    {
+                        self.consume(&TokenType::RightBracket, "Expected ']' after array size")?;
+                        self.variable_length = Some(size_expr);
+                        sizes.push(None);
+                        continue;
+                    }
+                    Err(ConstantError::NotConstant(span)) => {
+                        return Err(AleccError::ParseError {
+                            line: span.line,
+                            column: span.column,
+                            message: if !self.language.variable_length_arrays() {
+                                "ISO C90 forbids variable length arrays".to_string()
+                            } else if variable_length_allowed {
+                                "only the first size of a variable length array can vary"
+                                    .to_string()
+                            } else {
+                                "variable length arrays are only supported as locals declared in a block"
+                                    .to_string()
+                            },
+                        });
+                    }
+                    Err(error) => return Err(constant_error(error)),
+                };
+                if size < 0 {
+                    return Err(AleccError::ParseError {
+                        line: size_expr.span.line,
+                        column: size_expr.span.column,
+                        message: "size of array is negative".to_string(),
+                    });
+                }
+                Some(size as usize)
+            };
+            self.consume(&TokenType::RightBracket, "Expected ']' after array size")?;
+            sizes.push(size);
+        }
+        Ok(sizes
+            .into_iter()
+            .rev()
+            .fold(element, |ty, size| Type::Array(Box::new(ty), size)))
+    }
+
+    /// A declarator that must name what it declares: `name`, `name[2][3]`, or
+    /// a function pointer such as `(*name)(int)`
+    fn parse_named_declarator(&mut self, base: Type, what: &str) -> Result<(String, Type)> {
+        let declarator = match self.parse_function_pointer_declarator(base.clone())? {
+            Some((name, ty)) => name.map(|name| (name, ty)),
+            None => match &self.current_token()?.token_type {
+                TokenType::Identifier(name) => {
+                    let name = name.clone();
+                    self.advance()?;
+                    Some((name, self.parse_array_declarator(base)?))
+                }
+                _ => None,
+            },
+        };
+        declarator.ok_or_else(|| {
+            let token = self.current_token().ok();
+            AleccError::ParseError {
+                line: token.map_or(0, |token| token.line),
+                column: token.map_or(0, |token| token.column),
+                message: format!("Expected {} name", what),
+            }
+        })
+    }
+
+    /// A function pointer declarator, `(*name)(int, char *)`, returning
+    /// `return_type`. The name is left out in a type name, and array suffixes
+    /// inside the parentheses make a table of them: `(*handlers[4])(void)`.
+    /// `None` when the next tokens don't start one.
+    fn parse_function_pointer_declarator(
+        &mut self,
+        return_type: Type,
+    ) -> Result<Option<(Option<String>, Type)>> {
+        if !self.check(&TokenType::LeftParen)
+            || self.peek_ahead(1)?.token_type != TokenType::Multiply
+        {
+            return Ok(None);
+        }
+        self.advance()?;
+        let mut pointers = Vec::new();
+        while self.match_token(&TokenType::Multiply) {
+            pointers.push(self.parse_qualifiers());
+        }
+        let name = match &self.current_token()?.token_type {
+            TokenType::Identifier(name) => {
+                let name = name.clone();
+                self.advance()?;
+                Some(name)
+            }
+            _ => None,
+        };
+        // The element type is only known after the parameters
+        let table = self.parse_array_declarator(Type::Void)?;
+        self.consume(
+            &TokenType::RightParen,
+            "Expected ')' in function pointer declarator",
+        )?;
+        self.consume(
+            &TokenType::LeftParen,
+            "Expected '(' before function pointer parameters",
+        )?;
+        let (parameters, variadic) = self.parse_parameter_types()?;
+
+        let mut ty = Type::Function {
+            return_type: Box::new(return_type),
+            parameters,
+            variadic,
+        };
+        for qualifiers in pointers {
+            ty = self.qualify(Type::Pointer(Box::new(ty)), qualifiers)?;
+        }
+        fn fill(table: Type, element: Type) -> Type {
+            match table {
+                Type::Array(inner, size) => Type::Array(Box::new(fill(*inner, element)), size),
+                _ => element,
+            }
+        }
+        Ok(Some((name, fill(table, ty))))
+    }
+
+    /// The parameter list of a function pointer, after its `(`: the types,
+    /// whose names may be given or not, and whether it ends in `...`
+    fn parse_parameter_types(&mut self) -> Result<(Vec<Type>, bool)> {
+        let mut parameters = Vec::new();
+        let mut variadic = false;
+        if self.check(&TokenType::Void)
+            && matches!(self.peek_ahead(1)?.token_type, TokenType::RightParen)
+        {
+            self.advance()?;
+        }
+        while !self.check(&TokenType::RightParen) && !self.is_at_end() {
+            if self.match_token(&TokenType::Ellipsis) {
+                variadic = true;
+                break;
+            }
+            let ty = self.parse_type()?;
+            let ty = match self.parse_function_pointer_declarator(ty.clone())? {
+                Some((_, ty)) => ty,
+                None => {
+                    if let TokenType::Identifier(_) = self.current_token()?.token_type {
+                        self.advance()?;
+                    }
+                    self.parse_array_declarator(ty)?
+                }
+            };
+            parameters.push(match ty {
+                Type::Array(element, _) => Type::Pointer(element),
+                ty => ty,
+            });
+            if !self.check(&TokenType::RightParen) {
+                self.consume(&TokenType::Comma, "Expected ',' between parameters")?;
+            }
+        }
+        self.consume(&TokenType::RightParen, "Expected ')' after parameters")?;
+        Ok((parameters, variadic))
+    }
+
+    fn parse_qualifiers(&mut self) -> Qualifiers {
+        let mut qualifiers = Qualifiers::default();
+        loop {
+            if self.match_token(&TokenType::Const) {
+                qualifiers.is_const = true;
+            } else if self.match_token(&TokenType::Volatile) {
+                qualifiers.is_volatile = true;
+            } else if self.match_token(&TokenType::Restrict) {
+                qualifiers.is_restrict = true;
+            } else {
+                return qualifiers;
+            }
+        }
+    }
+
+    fn qualify(&self, ty: Type, qualifiers: Qualifiers) -> Result<Type> {
+        if qualifiers.is_empty() {
+            return Ok(ty);
+        }
+        if qualifiers.is_restrict && !matches!(self.resolve_typedef(&ty), Type::Pointer(_)) {
+            let token = self.previous()?;
+            return Err(AleccError::ParseError {
+                line: token.line,
+                column: token.column,
+                message: "restrict requires a pointer type".to_string(),
+            });
+        }
+        Ok(Type::Qualified(qualifiers, Box::new(ty)))
+    }
+
     fn parse_struct_type(&mut self) -> Result<Type> {
+        let mut attributes = self.parse_attributes()?;
         let name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
             name.clone()
         } else {
@@ -335,15 +1062,7 @@ impl Parser {
         if self.match_token(&TokenType::LeftBrace) {
             while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
                 let field_type = self.parse_type()?;
-                let field_name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
-                    name.clone()
-                } else {
-                    return Err(AleccError::ParseError {
-                        line: self.current_token()?.line,
-                        column: self.current_token()?.column,
-                        message: "Expected field name".to_string(),
-                    });
-                };
+                let (field_name, field_type) = self.parse_named_declarator(field_type, "field")?;
 
                 self.consume(
                     &TokenType::Semicolon,
@@ -353,12 +1072,20 @@ impl Parser {
             }
 
             self.consume(&TokenType::RightBrace, "Expected '}' after struct body")?;
+            attributes.merge(self.parse_attributes()?);
         }
 
-        Ok(Type::Struct { name, fields })
+        let at = self.previous()?.clone();
+        self.ignore_attributes(&attributes, &["packed"], &at);
+        Ok(Type::Struct {
+            name,
+            fields,
+            packed: attributes.packed,
+        })
     }
 
     fn parse_union_type(&mut self) -> Result<Type> {
+        let mut attributes = self.parse_attributes()?;
         // Similar to struct parsing
         let name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
             name.clone()
@@ -375,15 +1102,7 @@ impl Parser {
         if self.match_token(&TokenType::LeftBrace) {
             while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
                 let field_type = self.parse_type()?;
-                let field_name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
-                    name.clone()
-                } else {
-                    return Err(AleccError::ParseError {
-                        line: self.current_token()?.line,
-                        column: self.current_token()?.column,
-                        message: "Expected field name".to_string(),
-                    });
-                };
+                let (field_name, field_type) = self.parse_named_declarator(field_type, "field")?;
 
                 self.consume(
                     &TokenType::Semicolon,
@@ -393,9 +1112,16 @@ impl Parser {
             }
 
             self.consume(&TokenType::RightBrace, "Expected '}' after union body")?;
+            attributes.merge(self.parse_attributes()?);
         }
 
-        Ok(Type::Union { name, fields })
+        let at = self.previous()?.clone();
+        self.ignore_attributes(&attributes, &["packed"], &at);
+        Ok(Type::Union {
+            name,
+            fields,
+            packed: attributes.packed,
+        })
     }
 
     fn parse_enum_type(&mut self) -> Result<Type> {
@@ -426,17 +1152,14 @@ impl Parser {
                 };
 
                 if self.match_token(&TokenType::Assign) {
-                    if let TokenType::IntegerLiteral(value) = &self.advance()?.token_type {
-                        current_value = *value;
-                    } else {
-                        return Err(AleccError::ParseError {
-                            line: self.current_token()?.line,
-                            column: self.current_token()?.column,
-                            message: "Expected integer literal for enum value".to_string(),
-                        });
-                    }
+                    let value = self.parse_expression()?;
+                    current_value = self.integer_constant(&value)?;
                 }
 
+                // Visible from the next enumerator's initializer on
+                if let Some(scope) = self.enumerators.last_mut() {
+                    scope.insert(variant_name.clone(), current_value);
+                }
                 variants.push((variant_name, current_value));
                 current_value += 1;
 
@@ -484,9 +1207,12 @@ impl Parser {
         }
     }
 
+    /// The last token consumed, not counting the newlines `advance` skips after it
     fn previous(&self) -> Result<&Token> {
-        self.tokens
-            .get(self.current - 1)
+        self.tokens[..self.current]
+            .iter()
+            .rev()
+            .find(|token| token.token_type != TokenType::Newline)
             .ok_or_else(|| AleccError::ParseError {
                 line: 0,
                 column: 0,
@@ -552,30 +1278,41 @@ impl Parser {
         }
     }
 
-    // Placeholder implementations for missing methods
-    fn parse_storage_class(&mut self) -> StorageClass {
-        StorageClass::None // Simplified for now
+    /// The storage class and whether `inline` was given, in either order
+    /// Storage class and function specifiers in any order: the storage
+    /// class, whether `inline` is among them and whether `_Thread_local` is
+    fn parse_storage_class(&mut self) -> (StorageClass, bool, bool) {
+        let mut storage = StorageClass::None;
+        let mut is_inline = false;
+        let mut thread_local = false;
+        loop {
+            if self.match_token(&TokenType::Static) {
+                storage = StorageClass::Static;
+            } else if self.match_token(&TokenType::Extern) {
+                storage = StorageClass::Extern;
+            } else if self.match_token(&TokenType::Inline) {
+                is_inline = true;
+            } else if self.match_token(&TokenType::ThreadLocal) {
+                thread_local = true;
+            } else {
+                return (storage, is_inline, thread_local);
+            }
+        }
     }
 
     fn parse_typedef(&mut self) -> Result<Declaration> {
         let base_type = self.parse_type()?;
-        let name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
-            name.clone()
-        } else {
-            return Err(AleccError::ParseError {
-                line: self.current_token()?.line,
-                column: self.current_token()?.column,
-                message: "Expected typedef name".to_string(),
-            });
-        };
-
+        let (name, base_type) = self.parse_named_declarator(base_type, "typedef")?;
         self.consume(&TokenType::Semicolon, "Expected ';' after typedef")?;
+        self.typedefs.insert(name.clone(), base_type.clone());
         Ok(Declaration::TypeDef(name, base_type))
     }
 
     fn parse_function_declaration(
         &mut self,
-        _storage: StorageClass,
+        storage: StorageClass,
+        is_inline: bool,
+        mut attributes: Attributes,
         return_type: Type,
     ) -> Result<Declaration> {
         let name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
@@ -591,7 +1328,16 @@ impl Parser {
         self.consume(&TokenType::LeftParen, "Expected '(' after function name")?;
 
         let mut parameters = Vec::new();
+        let mut unused_parameters = Vec::new();
         let mut is_variadic = false;
+        // `()` leaves the parameters unspecified before C23; `(void)` says there are none
+        let has_prototype =
+            !self.check(&TokenType::RightParen) || !self.language.unprototyped_functions();
+        if self.check(&TokenType::Void)
+            && matches!(self.peek_ahead(1)?.token_type, TokenType::RightParen)
+        {
+            self.advance()?;
+        }
 
         while !self.check(&TokenType::RightParen) && !self.is_at_end() {
             if self.match_token(&TokenType::Ellipsis) {
@@ -599,16 +1345,20 @@ impl Parser {
                 break;
             }
 
+            let at = self.current_token()?.clone();
+            let mut param_attributes = self.parse_attributes()?;
             let param_type = self.parse_type()?;
-            let param_name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
-                name.clone()
-            } else {
-                return Err(AleccError::ParseError {
-                    line: self.current_token()?.line,
-                    column: self.current_token()?.column,
-                    message: "Expected parameter name".to_string(),
-                });
+            let (param_name, param_type) = self.parse_named_declarator(param_type, "parameter")?;
+            // A parameter declared as an array, like `char *argv[]`, is a pointer
+            let param_type = match param_type {
+                Type::Array(element, _) => Type::Pointer(element),
+                ty => ty,
             };
+            param_attributes.merge(self.parse_attributes()?);
+            self.ignore_attributes(&param_attributes, &["unused"], &at);
+            if param_attributes.unused {
+                unused_parameters.push(param_name.clone());
+            }
 
             parameters.push((param_name, param_type));
 
@@ -618,47 +1368,97 @@ impl Parser {
         }
 
         self.consume(&TokenType::RightParen, "Expected ')' after parameters")?;
-
-        let body = if self.check(&TokenType::LeftBrace) {
+        let at = self.previous()?.clone();
+        attributes.merge(self.parse_attributes()?);
+        self.ignore_attributes(
+            &attributes,
+            &[
+                "aligned",
+                "noreturn",
+                "constructor",
+                "destructor",
+                "nodiscard",
+                "section",
+                "unused",
+            ],
+            &at,
+        );
+        let attributes = match self.function_attributes.get(&name) {
+            Some(earlier) => {
+                let mut merged = earlier.clone();
+                merged.merge(attributes);
+                merged
+            }
+            None => attributes,
+        };
+        self.function_attributes
+            .insert(name.clone(), attributes.clone());
+
+        self.declare_name(
+            &name,
+            Type::Function {
+                return_type: Box::new(return_type.clone()),
+                parameters: parameters.iter().map(|(_, ty)| ty.clone()).collect(),
+                variadic: is_variadic,
+            },
+        );
+
+        let (body, end) = if self.check(&TokenType::LeftBrace) {
             self.advance()?; // Consume the LeftBrace
-            self.parse_block_statement()?
+            self.push_scope();
+            for (param_name, param_type) in &parameters {
+                self.declare_name(param_name, param_type.clone());
+            }
+            let span = self.previous()?.span();
+            self.current_function = Some(name.clone());
+            let body = self.parse_block_statement();
+            self.current_function = None;
+            self.pop_scope();
+            (Statement::new(body?, span), self.previous()?.span())
         } else {
+            let span = self.current_token()?.span();
             self.consume(
                 &TokenType::Semicolon,
                 "Expected ';' after function declaration",
             )?;
-            Statement::Block(Vec::new()) // Forward declaration
+            // Forward declaration
+            (Statement::new(StatementKind::Block(Vec::new()), span), span)
         };
 
-        Ok(Declaration::Function(Function {
+        Ok(Declaration::Function(Box::new(Function {
             name,
             return_type,
             parameters,
             body,
-            is_inline: false,
-            is_static: false,
-            is_extern: false,
+            end,
+            is_inline,
+            is_static: matches!(storage, StorageClass::Static),
+            is_extern: matches!(storage, StorageClass::Extern),
             is_variadic,
-        }))
+            has_prototype,
+            attributes,
+            unused_parameters,
+        })))
     }
 
     fn parse_variable_declaration(
         &mut self,
-        _storage: StorageClass,
+        storage: StorageClass,
         var_type: Type,
+        alignment: Option<usize>,
+        mut attributes: Attributes,
+        thread_local: bool,
     ) -> Result<Declaration> {
-        let name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
-            name.clone()
-        } else {
-            return Err(AleccError::ParseError {
-                line: self.current_token()?.line,
-                column: self.current_token()?.column,
-                message: "Expected variable name".to_string(),
-            });
-        };
+        let at = self.current_token()?.clone();
+        let (name, var_type) = self.parse_named_declarator(var_type, "variable")?;
+        attributes.merge(self.parse_attributes()?);
+        self.ignore_attributes(&attributes, &["aligned", "section", "unused"], &at);
 
         let initializer = if self.match_token(&TokenType::Assign) {
-            Some(self.parse_expression()?)
+            let assign = self.previous()?.clone();
+            let value = self.parse_expression()?;
+            self.check_conversion(&var_type, &value, &assign);
+            Some(value)
         } else {
             None
         };
@@ -668,71 +1468,256 @@ impl Parser {
             "Expected ';' after variable declaration",
         )?;
 
-        Ok(Declaration::Variable(name, var_type, initializer))
+        attributes.aligned = self.check_alignment(&var_type, alignment.max(attributes.aligned))?;
+        self.declare_name(&name, var_type.clone());
+        let storage = match storage {
+            StorageClass::Static => Some(GlobalStorage::Static),
+            StorageClass::Extern if initializer.is_none() => Some(GlobalStorage::Extern),
+            _ => None,
+        };
+        Ok(Declaration::Variable(Box::new(GlobalVariable {
+            name,
+            var_type,
+            initializer,
+            location: Span::default(),
+            storage,
+            thread_local,
+            attributes: Attributes {
+                aligned: attributes.aligned,
+                section: attributes.section,
+                ..Attributes::default()
+            },
+        })))
     }
 
-    fn parse_block_statement(&mut self) -> Result<Statement> {
+    fn parse_block_statement(&mut self) -> Result<StatementKind> {
         // Note: LeftBrace was already consumed by match_token in parse_statement
+        self.push_scope();
+        self.stack_saves.push(None);
         let mut statements = Vec::new();
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.parse_statement()?);
+            self.block_statement = true;
+            match self.parse_statement() {
+                Ok(statement) => {
+                    statements.append(&mut self.hidden_statements);
+                    statements.push(statement);
+                }
+                Err(e) => {
+                    self.stack_saves.pop();
+                    self.pop_scope();
+                    return Err(e);
+                }
+            }
+        }
+        self.pop_scope();
+
+        // The variable length arrays of the block are freed when control
+        // leaves it at the end, or by `break` and `continue`
+        if let Some(save) = self.stack_saves.pop().flatten() {
+            let span = self.current_token()?.span();
+            let jumps = statements.last().is_some_and(|statement| {
+                matches!(
+                    statement.kind,
+                    StatementKind::Return(_)
+                        | StatementKind::Break
+                        | StatementKind::Continue
+                        | StatementKind::Goto(_)
+                        | StatementKind::ComputedGoto(_)
+                )
+            });
+            if !jumps {
+                statements.push(self.stack_restore(&save, span));
+            }
         }
 
         self.consume(&TokenType::RightBrace, "Expected '}'")?;
-        Ok(Statement::Block(statements))
+        Ok(StatementKind::Block(statements))
+    }
+
+    /// `__builtin_stack_restore(save);`, freeing what was allocated on the
+    /// stack since `save` was taken
+    fn stack_restore(&self, save: &str, span: Span) -> Statement {
+        let identifier =
+            |name: &str| Expression::new(ExpressionKind::Identifier(name.into()), span);
+        let call = ExpressionKind::Call {
+            function: Box::new(identifier("__builtin_stack_restore")),
+            arguments: vec![identifier(save)],
+        };
+        Statement::new(StatementKind::Expression(Expression::new(call, span)), span)
+    }
+
+    /// A `break` or `continue` leaving the blocks of the innermost loop or
+    /// switch (the innermost loop for `continue`), restoring the stack
+    /// pointer first when they declared variable length arrays
+    fn jump_out(&self, jump: StatementKind, span: Span) -> StatementKind {
+        let continues = matches!(jump, StatementKind::Continue);
+        let open = self
+            .jump_scopes
+            .iter()
+            .rev()
+            .find(|(is_loop, _)| *is_loop || !continues)
+            .map_or(0, |&(_, open)| open);
+        match self.stack_saves.iter().skip(open).flatten().next() {
+            Some(save) => StatementKind::Block(vec![
+                self.stack_restore(save, span),
+                Statement::new(jump, span),
+            ]),
+            None => jump,
+        }
     }
 
     fn parse_statement(&mut self) -> Result<Statement> {
+        let span = self.current_token()?.span();
+        let kind = self.parse_statement_kind()?;
+        Ok(Statement::new(kind, span))
+    }
+
+    fn parse_statement_kind(&mut self) -> Result<StatementKind> {
+        let in_block = std::mem::take(&mut self.block_statement);
+        let at = self.current_token()?.clone();
+        let start = self.current;
+        let leading = self.parse_attributes()?;
+        if self.current > start && self.match_token(&TokenType::Semicolon) {
+            // An attribute declaration, which only `fallthrough` gives a meaning
+            self.ignore_attributes(&leading, &["fallthrough"], &at);
+            return Ok(if leading.fallthrough {
+                StatementKind::Fallthrough
+            } else {
+                StatementKind::Block(Vec::new())
+            });
+        }
+        // Only declarations take attributes
+        if !self.is_type(&self.current_token()?.token_type) {
+            self.ignore_attributes(&leading, &[], &at);
+        }
+
         // Try to parse different types of statements
         if self.match_token(&TokenType::Return) {
+            let at = self.previous()?.clone();
             let expr = if !self.check(&TokenType::Semicolon) {
-                Some(self.parse_expression()?)
+                let value = self.parse_expression()?;
+                let function = self.current_function.clone();
+                if let Some(Type::Function { return_type, .. }) =
+                    function.and_then(|name| self.lookup_name(&name))
+                {
+                    self.check_conversion(&return_type, &value, &at);
+                }
+                Some(value)
             } else {
                 None
             };
             self.consume(&TokenType::Semicolon, "Expected ';' after return")?;
-            Ok(Statement::Return(expr))
+            Ok(StatementKind::Return(expr))
         } else if self.match_token(&TokenType::If) {
             self.parse_if_statement()
         } else if self.match_token(&TokenType::While) {
             self.parse_while_statement()
         } else if self.match_token(&TokenType::For) {
             self.parse_for_statement()
+        } else if self.match_token(&TokenType::Switch) {
+            self.parse_switch_statement()
         } else if self.match_token(&TokenType::LeftBrace) {
             self.parse_block_statement()
-        } else if self.is_type(&self.current_token()?.token_type) {
+        } else if self.match_token(&TokenType::StaticAssert) {
+            self.parse_static_assert()?;
+            Ok(StatementKind::Block(Vec::new()))
+        } else if self.match_token(&TokenType::Break) {
+            self.consume(&TokenType::Semicolon, "Expected ';' after 'break'")?;
+            Ok(self.jump_out(StatementKind::Break, at.span()))
+        } else if self.match_token(&TokenType::Continue) {
+            self.consume(&TokenType::Semicolon, "Expected ';' after 'continue'")?;
+            Ok(self.jump_out(StatementKind::Continue, at.span()))
+        } else if self.match_token(&TokenType::Goto) {
+            if self.match_token(&TokenType::Multiply) {
+                let address = self.parse_expression()?;
+                self.consume(&TokenType::Semicolon, "Expected ';' after 'goto'")?;
+                return Ok(StatementKind::ComputedGoto(address));
+            }
+            let label = match &self.advance()?.token_type {
+                TokenType::Identifier(name) => name.clone(),
+                _ => {
+                    return Err(AleccError::ParseError {
+                        line: self.previous()?.line,
+                        column: self.previous()?.column,
+                        message: "Expected label name after 'goto'".to_string(),
+                    })
+                }
+            };
+            self.consume(&TokenType::Semicolon, "Expected ';' after 'goto'")?;
+            Ok(StatementKind::Goto(label))
+        } else if let (TokenType::Identifier(name), TokenType::Colon) = (
+            &self.current_token()?.token_type,
+            &self.peek_ahead(1)?.token_type,
+        ) {
+            // The labelled statement follows as a statement of its own
+            let label = name.clone();
+            self.advance()?;
+            self.advance()?;
+            Ok(StatementKind::Label(label))
+        } else if self.check(&TokenType::Static)
+            || self.check(&TokenType::ThreadLocal)
+            || self.is_type(&self.current_token()?.token_type)
+        {
             // Variable declaration - convert to Statement format
-            let mut var_type = self.parse_type()?;
-            let name = if let TokenType::Identifier(name) = &self.advance()?.token_type {
-                name.clone()
-            } else {
+            let (mut is_static, mut thread_local) = (false, false);
+            loop {
+                if self.match_token(&TokenType::Static) {
+                    is_static = true;
+                } else if self.match_token(&TokenType::ThreadLocal) {
+                    thread_local = true;
+                } else {
+                    break;
+                }
+            }
+            if thread_local && !is_static {
+                let token = self.previous()?;
                 return Err(AleccError::ParseError {
-                    line: self.current_token()?.line,
-                    column: self.current_token()?.column,
-                    message: "Expected variable name".to_string(),
+                    line: token.line,
+                    column: token.column,
+                    message: "'_Thread_local' in block scope requires 'static' or 'extern'"
+                        .to_string(),
+                });
+            }
+            let alignment = self.parse_alignment_specifiers()?;
+            let var_type = self.parse_type()?;
+            self.variable_length_allowed = in_block && !is_static;
+            let declarator = self.parse_named_declarator(var_type, "variable");
+            self.variable_length_allowed = false;
+            let (name, var_type) = declarator?;
+            let variable_length = self.variable_length.take();
+
+            let at = self.previous()?.clone();
+            let mut attributes = leading;
+            attributes.merge(self.parse_attributes()?);
+            if attributes.section.is_some() {
+                return Err(AleccError::ParseError {
+                    line: at.line,
+                    column: at.column,
+                    message: format!(
+                        "section attribute cannot be specified for local variable '{}'",
+                        name
+                    ),
+                });
+            }
+            self.ignore_attributes(&attributes, &["aligned", "unused"], &at);
+            let alignment = alignment.max(attributes.aligned);
+            if variable_length.is_some() && (alignment.is_some() || self.check(&TokenType::Assign))
+            {
+                return Err(AleccError::ParseError {
+                    line: at.line,
+                    column: at.column,
+                    message: format!(
+                        "variable length array '{}' can't be initialized or aligned",
+                        name
+                    ),
                 });
-            };
-
-            // Check for array declaration
-            if self.match_token(&TokenType::LeftBracket) {
-                let size = if self.check(&TokenType::RightBracket) {
-                    None
-                } else {
-                    // Parse array size (should be a constant expression)
-                    let size_expr = self.parse_expression()?;
-                    if let Expression::IntegerLiteral(size) = size_expr {
-                        Some(size as usize)
-                    } else {
-                        // For now, just use a default size if not a simple integer
-                        Some(10)
-                    }
-                };
-                self.consume(&TokenType::RightBracket, "Expected ']' after array size")?;
-                var_type = Type::Array(Box::new(var_type), size);
             }
 
             let initializer = if self.match_token(&TokenType::Assign) {
-                Some(self.parse_expression()?)
+                let assign = self.previous()?.clone();
+                let value = self.parse_expression()?;
+                self.check_conversion(&var_type, &value, &assign);
+                Some(value)
             } else {
                 None
             };
@@ -742,20 +1727,136 @@ impl Parser {
                 "Expected ';' after variable declaration",
             )?;
 
-            Ok(Statement::Declaration {
+            if let Some(length) = variable_length {
+                return Ok(self.variable_length_array(
+                    name,
+                    var_type,
+                    length,
+                    attributes.unused,
+                    at.span(),
+                ));
+            }
+            let alignment = self.check_alignment(&var_type, alignment)?;
+            self.declare_name(&name, var_type.clone());
+            if is_static {
+                // Initialized once, before the program starts, and kept between calls
+                let global = self.static_local_name(&name);
+                if let Some(scope) = self.static_names.last_mut() {
+                    scope.insert(name, global.clone());
+                }
+                if let Some(file_scope) = self.scopes.first_mut() {
+                    file_scope.insert(global.clone(), var_type.clone());
+                }
+                self.static_locals.push(GlobalVariable {
+                    name: global,
+                    var_type,
+                    initializer,
+                    location: Span::default(),
+                    storage: Some(GlobalStorage::Static),
+                    thread_local,
+                    attributes: Attributes {
+                        aligned: alignment,
+                        ..Attributes::default()
+                    },
+                });
+                return Ok(StatementKind::Block(Vec::new()));
+            }
+            Ok(StatementKind::Declaration {
                 name,
                 var_type,
                 initializer,
+                alignment,
+                unused: attributes.unused,
             })
         } else {
             // Expression statement
             let expr = self.parse_expression()?;
             self.consume(&TokenType::Semicolon, "Expected ';' after expression")?;
-            Ok(Statement::Expression(expr))
+            self.check_discarded_result(&expr, &at);
+            Ok(StatementKind::Expression(expr))
+        }
+    }
+
+    /// A variable length array, `element name[length]`: a pointer to its
+    /// first element, allocated by `__builtin_alloca` and freed when control
+    /// leaves its block. Its size in bytes is computed once, into a hidden
+    /// local that `sizeof` reads, and the stack pointer is saved before the
+    /// first one in the block so the block can restore it
+    fn variable_length_array(
+        &mut self,
+        name: String,
+        var_type: Type,
+        length: Expression,
+        unused: bool,
+        span: Span,
+    ) -> StatementKind {
+        let element = match var_type {
+            Type::Array(element, _) => element,
+            _ => unreachable!("the declarator had a variable length"),
+        };
+        let expression = |kind| Expression::new(kind, span);
+        let call = |function: &str, arguments| {
+            expression(ExpressionKind::Call {
+                function: Box::new(expression(ExpressionKind::Identifier(function.into()))),
+                arguments,
+            })
+        };
+        let hidden = |name: &String, var_type, initializer| {
+            Statement::new(
+                StatementKind::Declaration {
+                    name: name.clone(),
+                    var_type,
+                    initializer: Some(initializer),
+                    alignment: None,
+                    unused: true,
+                },
+                span,
+            )
+        };
+
+        if let Some(None) = self.stack_saves.last() {
+            let save = self.hidden_local("stack");
+            self.stack_saves.pop();
+            self.stack_saves.push(Some(save.clone()));
+            let saved = call("__builtin_stack_save", Vec::new());
+            let void_pointer = Type::Pointer(Box::new(Type::Void));
+            self.hidden_statements
+                .push(hidden(&save, void_pointer.clone(), saved));
+            self.declare_name(&save, void_pointer);
+        }
+
+        let size = self.hidden_local(&format!("{}.size", name));
+        let bytes = expression(ExpressionKind::Binary {
+            left: Box::new(expression(ExpressionKind::Cast {
+                target_type: Type::UnsignedLong,
+                expression: Box::new(length),
+            })),
+            operator: BinaryOperator::Multiply,
+            right: Box::new(expression(ExpressionKind::Sizeof((*element).clone()))),
+        });
+        self.hidden_statements
+            .push(hidden(&size, Type::UnsignedLong, bytes));
+        self.declare_name(&size, Type::UnsignedLong);
+        if let Some(scope) = self.variable_lengths.last_mut() {
+            scope.insert(name.clone(), size.clone());
+        }
+
+        let pointer = Type::Pointer(element);
+        self.declare_name(&name, pointer.clone());
+        let allocated = call(
+            "__builtin_alloca",
+            vec![expression(ExpressionKind::Identifier(size))],
+        );
+        StatementKind::Declaration {
+            name,
+            var_type: pointer,
+            initializer: Some(allocated),
+            alignment: None,
+            unused,
         }
     }
 
-    fn parse_if_statement(&mut self) -> Result<Statement> {
+    fn parse_if_statement(&mut self) -> Result<StatementKind> {
         self.consume(&TokenType::LeftParen, "Expected '(' after 'if'")?;
         let condition = self.parse_expression()?;
         self.consume(&TokenType::RightParen, "Expected ')' after if condition")?;
@@ -767,23 +1868,94 @@ impl Parser {
             None
         };
 
-        Ok(Statement::If {
+        Ok(StatementKind::If {
             condition,
             then_stmt,
             else_stmt,
         })
     }
 
-    fn parse_while_statement(&mut self) -> Result<Statement> {
+    fn parse_while_statement(&mut self) -> Result<StatementKind> {
         self.consume(&TokenType::LeftParen, "Expected '(' after 'while'")?;
         let condition = self.parse_expression()?;
         self.consume(&TokenType::RightParen, "Expected ')' after while condition")?;
-        let body = Box::new(self.parse_statement()?);
+        self.jump_scopes.push((true, self.stack_saves.len()));
+        let body = self.parse_statement();
+        self.jump_scopes.pop();
+        let body = Box::new(body?);
+
+        Ok(StatementKind::While { condition, body })
+    }
+
+    /// A switch body is a block of `case` and `default` groups; labels nested
+    /// in inner statements aren't supported
+    fn parse_switch_statement(&mut self) -> Result<StatementKind> {
+        self.consume(&TokenType::LeftParen, "Expected '(' after 'switch'")?;
+        let expression = self.parse_expression()?;
+        self.consume(
+            &TokenType::RightParen,
+            "Expected ')' after switch expression",
+        )?;
+        self.consume(
+            &TokenType::LeftBrace,
+            "Expected '{' after switch expression",
+        )?;
+
+        self.push_scope();
+        self.jump_scopes.push((false, self.stack_saves.len()));
+        let cases = self.parse_switch_cases();
+        self.jump_scopes.pop();
+        self.pop_scope();
+        let cases = cases?;
+        self.consume(&TokenType::RightBrace, "Expected '}' after switch body")?;
+
+        Ok(StatementKind::Switch { expression, cases })
+    }
 
-        Ok(Statement::While { condition, body })
+    fn parse_switch_cases(&mut self) -> Result<Vec<(Option<Expression>, Vec<Statement>)>> {
+        let mut cases: Vec<(Option<Expression>, Vec<Statement>)> = Vec::new();
+        let mut values: HashMap<i64, usize> = HashMap::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            let token = self.current_token()?.clone();
+            let error = |message: String| AleccError::ParseError {
+                line: token.line,
+                column: token.column,
+                message,
+            };
+            if self.match_token(&TokenType::Case) {
+                let value = self.parse_expression()?;
+                self.consume(&TokenType::Colon, "Expected ':' after case value")?;
+                let constant = self.integer_constant(&value)?;
+                if let Some(line) = values.insert(constant, token.line) {
+                    return Err(error(format!(
+                        "duplicate case value {} (previously used on line {})",
+                        constant, line
+                    )));
+                }
+                cases.push((Some(value), Vec::new()));
+            } else if self.match_token(&TokenType::Default) {
+                self.consume(&TokenType::Colon, "Expected ':' after 'default'")?;
+                if cases.iter().any(|(value, _)| value.is_none()) {
+                    return Err(error("multiple default labels in one switch".to_string()));
+                }
+                cases.push((None, Vec::new()));
+            } else {
+                let statement = self.parse_statement()?;
+                match cases.last_mut() {
+                    Some((_, statements)) => statements.push(statement),
+                    None => {
+                        return Err(error(
+                            "statement in switch body before the first 'case' or 'default'"
+                                .to_string(),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(cases)
     }
 
-    fn parse_for_statement(&mut self) -> Result<Statement> {
+    fn parse_for_statement(&mut self) -> Result<StatementKind> {
         self.consume(&TokenType::LeftParen, "Expected '(' after 'for'")?;
 
         let init = if self.check(&TokenType::Semicolon) {
@@ -810,9 +1982,12 @@ impl Parser {
         };
         self.consume(&TokenType::RightParen, "Expected ')' after for clauses")?;
 
-        let body = Box::new(self.parse_statement()?);
+        self.jump_scopes.push((true, self.stack_saves.len()));
+        let body = self.parse_statement();
+        self.jump_scopes.pop();
+        let body = Box::new(body?);
 
-        Ok(Statement::For {
+        Ok(StatementKind::For {
             init,
             condition,
             increment,
@@ -826,15 +2001,33 @@ impl Parser {
             TokenType::Int
                 | TokenType::Float
                 | TokenType::Double
+                | TokenType::Bool
                 | TokenType::Char
                 | TokenType::Void
                 | TokenType::Short
                 | TokenType::Long
                 | TokenType::Signed
                 | TokenType::Unsigned
+                | TokenType::Alignas
+                | TokenType::Const
+                | TokenType::Volatile
+                | TokenType::Restrict
+        ) || matches!(
+            token_type,
+            TokenType::Identifier(name)
+                if name == "__builtin_va_list" || self.typedefs.contains_key(name)
         )
     }
 
+    /// Tokens that can start the type name of a cast
+    fn is_type_name(&self, token_type: &TokenType) -> bool {
+        match token_type {
+            TokenType::Struct | TokenType::Union | TokenType::Enum => true,
+            TokenType::Alignas => false,
+            token_type => self.is_type(token_type),
+        }
+    }
+
     fn parse_expression(&mut self) -> Result<Expression> {
         self.parse_assignment()
     }
@@ -843,40 +2036,65 @@ impl Parser {
         let expr = self.parse_logical_or()?;
 
         if self.match_token(&TokenType::Assign) {
+            let span = self.previous()?.span();
+            let assign = self.previous()?.clone();
             let value = self.parse_assignment()?; // Right associative
-            return Ok(Expression::Assignment {
-                target: Box::new(expr),
-                operator: AssignmentOperator::Assign,
-                value: Box::new(value),
-            });
-        } else if self.match_token(&TokenType::PlusAssign) {
-            let value = self.parse_assignment()?;
-            return Ok(Expression::Assignment {
-                target: Box::new(expr),
-                operator: AssignmentOperator::PlusAssign,
-                value: Box::new(value),
-            });
-        } else if self.match_token(&TokenType::MinusAssign) {
-            let value = self.parse_assignment()?;
-            return Ok(Expression::Assignment {
-                target: Box::new(expr),
-                operator: AssignmentOperator::MinusAssign,
-                value: Box::new(value),
-            });
-        } else if self.match_token(&TokenType::MultiplyAssign) {
-            let value = self.parse_assignment()?;
-            return Ok(Expression::Assignment {
-                target: Box::new(expr),
-                operator: AssignmentOperator::MultiplyAssign,
-                value: Box::new(value),
-            });
-        } else if self.match_token(&TokenType::DivideAssign) {
-            let value = self.parse_assignment()?;
-            return Ok(Expression::Assignment {
-                target: Box::new(expr),
-                operator: AssignmentOperator::DivideAssign,
-                value: Box::new(value),
-            });
+            if let Some(target_type) = self.object_type(&expr) {
+                self.check_conversion(&target_type, &value, &assign);
+            }
+            return Ok(Expression::new(
+                ExpressionKind::Assignment {
+                    target: Box::new(expr),
+                    operator: AssignmentOperator::Assign,
+                    value: Box::new(value),
+                },
+                span,
+            ));
+        }
+
+        let compound = [
+            (TokenType::PlusAssign, AssignmentOperator::PlusAssign),
+            (TokenType::MinusAssign, AssignmentOperator::MinusAssign),
+            (
+                TokenType::MultiplyAssign,
+                AssignmentOperator::MultiplyAssign,
+            ),
+            (TokenType::DivideAssign, AssignmentOperator::DivideAssign),
+            (TokenType::ModuloAssign, AssignmentOperator::ModuloAssign),
+            (
+                TokenType::BitwiseAndAssign,
+                AssignmentOperator::BitwiseAndAssign,
+            ),
+            (
+                TokenType::BitwiseOrAssign,
+                AssignmentOperator::BitwiseOrAssign,
+            ),
+            (
+                TokenType::BitwiseXorAssign,
+                AssignmentOperator::BitwiseXorAssign,
+            ),
+            (
+                TokenType::LeftShiftAssign,
+                AssignmentOperator::LeftShiftAssign,
+            ),
+            (
+                TokenType::RightShiftAssign,
+                AssignmentOperator::RightShiftAssign,
+            ),
+        ];
+        for (token, operator) in compound {
+            if self.match_token(&token) {
+                let span = self.previous()?.span();
+                let value = self.parse_assignment()?;
+                return Ok(Expression::new(
+                    ExpressionKind::Assignment {
+                        target: Box::new(expr),
+                        operator,
+                        value: Box::new(value),
+                    },
+                    span,
+                ));
+            }
         }
 
         Ok(expr)
@@ -886,13 +2104,17 @@ impl Parser {
         let mut expr = self.parse_logical_and()?;
 
         while self.match_token(&TokenType::LogicalOr) {
+            let span = self.previous()?.span();
             let operator = BinaryOperator::LogicalOr;
             let right = self.parse_logical_and()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -902,13 +2124,17 @@ impl Parser {
         let mut expr = self.parse_bitwise_or()?;
 
         while self.match_token(&TokenType::LogicalAnd) {
+            let span = self.previous()?.span();
             let operator = BinaryOperator::LogicalAnd;
             let right = self.parse_bitwise_or()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -918,13 +2144,17 @@ impl Parser {
         let mut expr = self.parse_bitwise_xor()?;
 
         while self.match_token(&TokenType::BitwiseOr) {
+            let span = self.previous()?.span();
             let operator = BinaryOperator::BitwiseOr;
             let right = self.parse_bitwise_xor()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -934,13 +2164,17 @@ impl Parser {
         let mut expr = self.parse_bitwise_and()?;
 
         while self.match_token(&TokenType::BitwiseXor) {
+            let span = self.previous()?.span();
             let operator = BinaryOperator::BitwiseXor;
             let right = self.parse_bitwise_and()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -950,13 +2184,17 @@ impl Parser {
         let mut expr = self.parse_equality()?;
 
         while self.match_token(&TokenType::BitwiseAnd) {
+            let span = self.previous()?.span();
             let operator = BinaryOperator::BitwiseAnd;
             let right = self.parse_equality()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -966,17 +2204,24 @@ impl Parser {
         let mut expr = self.parse_comparison()?;
 
         while self.match_tokens(&[TokenType::Equal, TokenType::NotEqual]) {
+            let span = self.previous()?.span();
             let operator = match self.previous()?.token_type {
                 TokenType::Equal => BinaryOperator::Equal,
                 TokenType::NotEqual => BinaryOperator::NotEqual,
                 _ => unreachable!(),
             };
+            let at = self.previous()?.clone();
             let right = self.parse_comparison()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            self.check_pointer_comparison(&expr, &right, &at);
+            self.check_sign_comparison(&expr, &right, &at);
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -991,6 +2236,7 @@ impl Parser {
             TokenType::Less,
             TokenType::LessEqual,
         ]) {
+            let span = self.previous()?.span();
             let operator = match self.previous()?.token_type {
                 TokenType::Greater => BinaryOperator::Greater,
                 TokenType::GreaterEqual => BinaryOperator::GreaterEqual,
@@ -998,12 +2244,18 @@ impl Parser {
                 TokenType::LessEqual => BinaryOperator::LessEqual,
                 _ => unreachable!(),
             };
+            let at = self.previous()?.clone();
             let right = self.parse_shift()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            self.check_pointer_comparison(&expr, &right, &at);
+            self.check_sign_comparison(&expr, &right, &at);
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -1013,17 +2265,23 @@ impl Parser {
         let mut expr = self.parse_term()?;
 
         while self.match_tokens(&[TokenType::LeftShift, TokenType::RightShift]) {
+            let span = self.previous()?.span();
             let operator = match self.previous()?.token_type {
                 TokenType::LeftShift => BinaryOperator::LeftShift,
                 TokenType::RightShift => BinaryOperator::RightShift,
                 _ => unreachable!(),
             };
+            let shift = self.previous()?.clone();
             let right = self.parse_term()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            self.check_shift_count(&expr, &operator, &right, &shift);
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -1033,17 +2291,21 @@ impl Parser {
         let mut expr = self.parse_factor()?;
 
         while self.match_tokens(&[TokenType::Minus, TokenType::Plus]) {
+            let span = self.previous()?.span();
             let operator = match self.previous()?.token_type {
                 TokenType::Minus => BinaryOperator::Subtract,
                 TokenType::Plus => BinaryOperator::Add,
                 _ => unreachable!(),
             };
             let right = self.parse_factor()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
@@ -1053,6 +2315,7 @@ impl Parser {
         let mut expr = self.parse_unary()?;
 
         while self.match_tokens(&[TokenType::Divide, TokenType::Multiply, TokenType::Modulo]) {
+            let span = self.previous()?.span();
             let operator = match self.previous()?.token_type {
                 TokenType::Divide => BinaryOperator::Divide,
                 TokenType::Multiply => BinaryOperator::Multiply,
@@ -1060,17 +2323,30 @@ impl Parser {
                 _ => unreachable!(),
             };
             let right = self.parse_unary()?;
-            expr = Expression::Binary {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            };
+            expr = Expression::new(
+                ExpressionKind::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                },
+                span,
+            );
         }
 
         Ok(expr)
     }
 
     fn parse_unary(&mut self) -> Result<Expression> {
+        if self.check(&TokenType::LogicalAnd)
+            && matches!(self.peek_ahead(1)?.token_type, TokenType::Identifier(_))
+        {
+            let span = self.advance()?.span();
+            let TokenType::Identifier(label) = self.advance()?.token_type.clone() else {
+                unreachable!()
+            };
+            return Ok(Expression::new(ExpressionKind::LabelAddress(label), span));
+        }
+
         if self.match_tokens(&[
             TokenType::LogicalNot,
             TokenType::Minus,
@@ -1092,11 +2368,65 @@ impl Parser {
                 TokenType::BitwiseNot => UnaryOperator::BitwiseNot,
                 _ => unreachable!(),
             };
+            let span = self.previous()?.span();
             let right = self.parse_unary()?;
-            return Ok(Expression::Unary {
-                operator,
-                operand: Box::new(right),
-            });
+            return Ok(Expression::new(
+                ExpressionKind::Unary {
+                    operator,
+                    operand: Box::new(right),
+                },
+                span,
+            ));
+        }
+
+        if self.match_token(&TokenType::Sizeof) {
+            let span = self.previous()?.span();
+            let ty = if self.check(&TokenType::LeftParen)
+                && self.is_type_name(&self.peek_ahead(1)?.token_type)
+            {
+                self.advance()?;
+                let ty = self.parse_type()?;
+                let ty = match self.parse_function_pointer_declarator(ty.clone())? {
+                    Some((_, ty)) => ty,
+                    None => self.parse_array_declarator(ty)?,
+                };
+                self.consume(&TokenType::RightParen, "Expected ')' after sizeof type")?;
+                ty
+            } else {
+                let operand = self.parse_unary()?;
+                // A variable length array's size was computed when it was declared
+                if let ExpressionKind::Identifier(name) = &operand.kind {
+                    if let Some(size) = self.variable_length_size(name) {
+                        return Ok(Expression::new(ExpressionKind::Identifier(size), span));
+                    }
+                }
+                self.operand_type(&operand)
+                    .ok_or_else(|| AleccError::ParseError {
+                        line: operand.span.line,
+                        column: operand.span.column,
+                        message: "cannot determine the type of the operand of 'sizeof'".to_string(),
+                    })?
+            };
+            return Ok(Expression::new(ExpressionKind::Sizeof(ty), span));
+        }
+
+        // `(type-name) operand`, told apart from a parenthesized expression by the type
+        if self.check(&TokenType::LeftParen) && self.is_type_name(&self.peek_ahead(1)?.token_type) {
+            let span = self.advance()?.span();
+            let target_type = self.parse_type()?;
+            let target_type = match self.parse_function_pointer_declarator(target_type.clone())? {
+                Some((_, ty)) => ty,
+                None => target_type,
+            };
+            self.consume(&TokenType::RightParen, "Expected ')' after cast type")?;
+            let expression = self.parse_unary()?;
+            return Ok(Expression::new(
+                ExpressionKind::Cast {
+                    target_type,
+                    expression: Box::new(expression),
+                },
+                span,
+            ));
         }
 
         self.parse_call()
@@ -1107,25 +2437,64 @@ impl Parser {
 
         loop {
             if self.match_token(&TokenType::LeftParen) {
+                if matches!(&expr.kind, ExpressionKind::Identifier(name) if name == "__builtin_va_arg" || name == "va_arg")
+                {
+                    expr = self.finish_va_arg(expr.span)?;
+                    continue;
+                }
                 expr = self.finish_call(expr)?;
             } else if self.match_token(&TokenType::LeftBracket) {
                 // Array indexing
                 let index = self.parse_expression()?;
                 self.consume(&TokenType::RightBracket, "Expected ']' after array index")?;
-                expr = Expression::Index {
-                    array: Box::new(expr),
-                    index: Box::new(index),
+                let span = expr.span;
+                expr = Expression::new(
+                    ExpressionKind::Index {
+                        array: Box::new(expr),
+                        index: Box::new(index),
+                    },
+                    span,
+                );
+            } else if self.match_token(&TokenType::Dot) || self.match_token(&TokenType::Arrow) {
+                let is_arrow = self.previous()?.token_type == TokenType::Arrow;
+                let member = match &self.advance()?.token_type {
+                    TokenType::Identifier(name) => name.clone(),
+                    _ => {
+                        let token = self.previous()?;
+                        return Err(AleccError::ParseError {
+                            line: token.line,
+                            column: token.column,
+                            message: "Expected member name".to_string(),
+                        });
+                    }
                 };
+                let span = expr.span;
+                expr = Expression::new(
+                    ExpressionKind::Member {
+                        object: Box::new(expr),
+                        member,
+                        is_arrow,
+                    },
+                    span,
+                );
             } else if self.match_token(&TokenType::Increment) {
-                expr = Expression::Unary {
-                    operator: UnaryOperator::PostIncrement,
-                    operand: Box::new(expr),
-                };
+                let span = self.previous()?.span();
+                expr = Expression::new(
+                    ExpressionKind::Unary {
+                        operator: UnaryOperator::PostIncrement,
+                        operand: Box::new(expr),
+                    },
+                    span,
+                );
             } else if self.match_token(&TokenType::Decrement) {
-                expr = Expression::Unary {
-                    operator: UnaryOperator::PostDecrement,
-                    operand: Box::new(expr),
-                };
+                let span = self.previous()?.span();
+                expr = Expression::new(
+                    ExpressionKind::Unary {
+                        operator: UnaryOperator::PostDecrement,
+                        operand: Box::new(expr),
+                    },
+                    span,
+                );
             } else {
                 break;
             }
@@ -1136,9 +2505,12 @@ impl Parser {
 
     fn finish_call(&mut self, callee: Expression) -> Result<Expression> {
         let mut arguments = Vec::new();
+        // Where each argument starts, to point warnings at
+        let mut starts = Vec::new();
 
         if !self.check(&TokenType::RightParen) {
             loop {
+                starts.push(self.current_token()?.clone());
                 arguments.push(self.parse_expression()?);
                 if !self.match_token(&TokenType::Comma) {
                     break;
@@ -1147,49 +2519,1336 @@ impl Parser {
         }
 
         self.consume(&TokenType::RightParen, "Expected ')' after arguments")?;
+        if let ExpressionKind::Identifier(name) = &callee.kind {
+            self.check_format(name, &arguments, &starts);
+            // Arguments convert to the prototype's parameter types as if by assignment
+            if let Some(Type::Function { parameters, .. }) = self.lookup_name(name) {
+                for ((parameter, argument), start) in parameters.iter().zip(&arguments).zip(&starts)
+                {
+                    self.check_conversion(parameter, argument, start);
+                }
+            }
+        }
 
-        Ok(Expression::Call {
-            function: Box::new(callee),
-            arguments,
-        })
+        let span = callee.span;
+        Ok(Expression::new(
+            ExpressionKind::Call {
+                function: Box::new(callee),
+                arguments,
+            },
+            span,
+        ))
+    }
+
+    fn finish_va_arg(&mut self, span: Span) -> Result<Expression> {
+        let list = self.parse_assignment()?;
+        self.consume(&TokenType::Comma, "Expected ',' after va_list in va_arg")?;
+        let arg_type = self.parse_type()?;
+        self.consume(&TokenType::RightParen, "Expected ')' after va_arg type")?;
+
+        Ok(Expression::new(
+            ExpressionKind::VaArg {
+                list: Box::new(list),
+                arg_type,
+            },
+            span,
+        ))
     }
 
     fn parse_primary(&mut self) -> Result<Expression> {
+        if self.match_token(&TokenType::Generic) {
+            return self.parse_generic_selection();
+        }
+
+        if self.match_token(&TokenType::Alignof) {
+            let line = self.previous()?.line;
+            let column = self.previous()?.column;
+            self.consume(&TokenType::LeftParen, "Expected '(' after '_Alignof'")?;
+            let ty = self.parse_type()?;
+            self.consume(&TokenType::RightParen, "Expected ')' after _Alignof type")?;
+            let alignment = self.align_of(&ty, line, column)?;
+            return Ok(Expression::new(
+                ExpressionKind::IntegerLiteral(alignment as i64),
+                Span { line, column },
+            ));
+        }
+
         if self.match_token(&TokenType::LeftParen) {
             let expr = self.parse_expression()?;
             self.consume(&TokenType::RightParen, "Expected ')' after expression")?;
             return Ok(expr);
         }
 
+        if let TokenType::SuffixedIntegerLiteral(value, suffix) = self.current_token()?.token_type {
+            let at = self.advance()?.clone();
+            return Ok(self.suffixed_literal(value, suffix, &at));
+        }
+        let function = self.current_function.clone();
+        let static_local = match &self.current_token()?.token_type {
+            TokenType::Identifier(name) => self.static_local(name),
+            _ => None,
+        };
         let token = self.advance()?;
-        match &token.token_type {
-            TokenType::IntegerLiteral(value) => Ok(Expression::IntegerLiteral(*value)),
-            TokenType::FloatLiteral(value) => Ok(Expression::FloatLiteral(*value)),
-            TokenType::StringLiteral(value) => Ok(Expression::StringLiteral(value.clone())),
-            TokenType::CharLiteral(value) => Ok(Expression::CharLiteral(*value)),
-            TokenType::Identifier(name) => Ok(Expression::Identifier(name.clone())),
-            _ => Err(AleccError::ParseError {
-                line: token.line,
-                column: token.column,
-                message: format!("Expected expression, found {:?}", token.token_type),
-            }),
+        let kind = match &token.token_type {
+            TokenType::IntegerLiteral(value) => ExpressionKind::IntegerLiteral(*value),
+            TokenType::FloatLiteral(value) => ExpressionKind::FloatLiteral(*value),
+            TokenType::StringLiteral(value) => ExpressionKind::StringLiteral(value.clone()),
+            TokenType::CharLiteral(value) => ExpressionKind::CharLiteral(*value),
+            // Each function body implicitly declares `static const char __func__[]`
+            // holding its name; gcc spells it two more ways
+            TokenType::Identifier(name) => match function {
+                Some(function)
+                    if matches!(
+                        name.as_str(),
+                        "__func__" | "__FUNCTION__" | "__PRETTY_FUNCTION__"
+                    ) =>
+                {
+                    ExpressionKind::StringLiteral(function)
+                }
+                _ => ExpressionKind::Identifier(static_local.unwrap_or_else(|| name.clone())),
+            },
+            _ => {
+                return Err(AleccError::ParseError {
+                    line: token.line,
+                    column: token.column,
+                    message: format!("Expected expression, found {:?}", token.token_type),
+                })
+            }
+        };
+        Ok(Expression::new(kind, token.span()))
+    }
+
+    /// An integer literal with a suffix, as its value cast to the type the
+    /// suffix and the value call for
+    fn suffixed_literal(&mut self, value: u64, suffix: IntegerSuffix, at: &Token) -> Expression {
+        // The first of the types the suffix allows that holds the value; one
+        // too big for any signed type is unsigned, like gcc does
+        let candidates = match (suffix.unsigned, suffix.long, suffix.long_long) {
+            (false, _, true) => vec![Type::LongLong, Type::UnsignedLongLong],
+            (true, _, true) => vec![Type::UnsignedLongLong],
+            (true, true, false) => vec![Type::UnsignedLong, Type::UnsignedLongLong],
+            (true, false, false) => vec![
+                Type::UnsignedInt,
+                Type::UnsignedLong,
+                Type::UnsignedLongLong,
+            ],
+            (false, _, false) => vec![
+                Type::Long,
+                Type::LongLong,
+                Type::UnsignedLong,
+                Type::UnsignedLongLong,
+            ],
+        };
+        let target_type = candidates
+            .into_iter()
+            .find(|ty| {
+                let width = self.integer_width(ty).unwrap_or(64) - !ty.is_unsigned() as u32;
+                width == 64 || value >> width == 0
+            })
+            .unwrap_or(Type::UnsignedLongLong);
+        Expression::new(
+            ExpressionKind::Cast {
+                target_type,
+                expression: Box::new(Expression::new(
+                    ExpressionKind::IntegerLiteral(value as i64),
+                    at.span(),
+                )),
+            },
+            at.span(),
+        )
+    }
+
+    /// `_Static_assert(condition, "message");`, checked here. The message is
+    /// optional from C23 on, and accepted without it in every mode like gcc does.
+    fn parse_static_assert(&mut self) -> Result<()> {
+        let at = self.previous()?.clone();
+        self.consume(&TokenType::LeftParen, "Expected '(' after '_Static_assert'")?;
+        let condition = self.parse_assignment()?;
+        let message = if self.match_token(&TokenType::Comma) {
+            let TokenType::StringLiteral(message) = &self.advance()?.token_type else {
+                let token = self.previous()?;
+                return Err(AleccError::ParseError {
+                    line: token.line,
+                    column: token.column,
+                    message: "Expected string literal in static assertion".to_string(),
+                });
+            };
+            Some(message.clone())
+        } else {
+            None
+        };
+        self.consume(
+            &TokenType::RightParen,
+            "Expected ')' after static assertion",
+        )?;
+        self.consume(&TokenType::Semicolon, "Expected ';' after static assertion")?;
+
+        if self.integer_constant(&condition)? != 0 {
+            return Ok(());
+        }
+        Err(AleccError::ParseError {
+            line: at.line,
+            column: at.column,
+            message: match message {
+                Some(message) => format!("static assertion failed: \"{}\"", message),
+                None => "static assertion failed".to_string(),
+            },
+        })
+    }
+
+    /// `_Generic(controlling, type: expr, ..., default: expr)`, resolved here to the
+    /// selected association; the controlling expression itself is never evaluated
+    fn parse_generic_selection(&mut self) -> Result<Expression> {
+        let line = self.previous()?.line;
+        let column = self.previous()?.column;
+        self.consume(&TokenType::LeftParen, "Expected '(' after '_Generic'")?;
+        let controlling = self.parse_assignment()?;
+
+        let mut selected = None;
+        let mut default = None;
+        while self.match_token(&TokenType::Comma) {
+            let association_type = if self.match_token(&TokenType::Default) {
+                None
+            } else {
+                Some(self.parse_type()?)
+            };
+            self.consume(
+                &TokenType::Colon,
+                "Expected ':' after _Generic association type",
+            )?;
+            let expr = self.parse_assignment()?;
+
+            match association_type {
+                None if default.is_some() => {
+                    return Err(AleccError::ParseError {
+                        line,
+                        column,
+                        message: "duplicate default association in _Generic".to_string(),
+                    });
+                }
+                None => default = Some(expr),
+                Some(ty) => {
+                    if selected.is_none() {
+                        let controlling_type = self.expression_type(&controlling).ok_or_else(|| {
+                            AleccError::ParseError {
+                                line,
+                                column,
+                                message: "cannot determine the type of the _Generic controlling expression".to_string(),
+                            }
+                        })?;
+                        if self.same_type(&controlling_type, &ty) {
+                            selected = Some(expr);
+                        }
+                    }
+                }
+            }
+        }
+        self.consume(
+            &TokenType::RightParen,
+            "Expected ')' after _Generic associations",
+        )?;
+
+        selected.or(default).ok_or_else(|| AleccError::ParseError {
+            line,
+            column,
+            message: "no _Generic association matches the controlling expression".to_string(),
+        })
+    }
+
+    /// Any number of `_Alignas(constant)` or `_Alignas(type)` specifiers; the
+    /// strictest one wins, and `_Alignas(0)` has no effect
+    /// Any number of `__attribute__((...))` lists, and the `_Noreturn`
+    /// function specifier, which means the same as the attribute
+    fn parse_attributes(&mut self) -> Result<Attributes> {
+        let mut attributes = Attributes::default();
+        loop {
+            if self.match_token(&TokenType::Noreturn) {
+                attributes.noreturn = true;
+            } else if matches!(&self.current_token()?.token_type,
+                TokenType::Identifier(name) if name == "__attribute__" || name == "__attribute")
+            {
+                self.advance()?;
+                self.consume(&TokenType::LeftParen, "Expected '((' after '__attribute__'")?;
+                self.consume(&TokenType::LeftParen, "Expected '((' after '__attribute__'")?;
+                while !self.check(&TokenType::RightParen) {
+                    self.parse_attribute(&mut attributes)?;
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.consume(&TokenType::RightParen, "Expected '))' after attributes")?;
+                self.consume(&TokenType::RightParen, "Expected '))' after attributes")?;
+            } else if self.check(&TokenType::LeftBracket)
+                && self.peek_ahead(1)?.token_type == TokenType::LeftBracket
+            {
+                self.advance()?;
+                self.advance()?;
+                while !self.check(&TokenType::RightBracket) {
+                    self.parse_standard_attribute(&mut attributes)?;
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.consume(&TokenType::RightBracket, "Expected ']]' after attributes")?;
+                self.consume(&TokenType::RightBracket, "Expected ']]' after attributes")?;
+            } else {
+                return Ok(attributes);
+            }
+        }
+    }
+
+    /// One attribute of a C23 `[[...]]` list; `gnu::x` is the same as `__attribute__((x))`
+    fn parse_standard_attribute(&mut self, attributes: &mut Attributes) -> Result<()> {
+        let token = self.advance()?.clone();
+        let name = match &token.token_type {
+            TokenType::Identifier(name) => name.clone(),
+            // `[[_Noreturn]]`, the C11 spelling C23 keeps as a deprecated attribute
+            TokenType::Noreturn => "_Noreturn".to_string(),
+            _ => {
+                return Err(AleccError::ParseError {
+                    line: token.line,
+                    column: token.column,
+                    message: "Expected attribute name".to_string(),
+                });
+            }
+        };
+        let name = &name;
+
+        if self.check(&TokenType::Colon) && self.peek_ahead(1)?.token_type == TokenType::Colon {
+            self.advance()?;
+            self.advance()?;
+            if name == "gnu" || name == "__gnu__" {
+                return self.parse_attribute(attributes);
+            }
+            let scoped = match &self.advance()?.token_type {
+                TokenType::Identifier(scoped) => scoped.clone(),
+                _ => String::new(),
+            };
+            self.skip_attribute_arguments()?;
+            let message = format!(
+                "'{}::{}' scoped attribute directive ignored [-Wattributes]",
+                name, scoped
+            );
+            self.warn("attributes", message, &token);
+            return Ok(());
+        }
+
+        match name.trim_start_matches("__").trim_end_matches("__") {
+            "nodiscard" => {
+                // The optional reason is only for the diagnostic
+                self.skip_attribute_arguments()?;
+                attributes.nodiscard = true;
+            }
+            "fallthrough" => attributes.fallthrough = true,
+            "noreturn" | "_Noreturn" => attributes.noreturn = true,
+            "maybe_unused" => attributes.unused = true,
+            "deprecated" | "unsequenced" | "reproducible" => {
+                self.skip_attribute_arguments()?;
+            }
+            name => {
+                let message = format!("'{}' attribute directive ignored [-Wattributes]", name);
+                self.skip_attribute_arguments()?;
+                self.warn("attributes", message, &token);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_attribute(&mut self, attributes: &mut Attributes) -> Result<()> {
+        let token = self.advance()?.clone();
+        let name = match &token.token_type {
+            // `__packed__` and `packed` are the same attribute
+            TokenType::Identifier(name) => name.trim_start_matches("__").trim_end_matches("__"),
+            TokenType::Const => "const",
+            // `__noreturn__` lexes as the keyword
+            TokenType::Noreturn => "noreturn",
+            _ => {
+                return Err(AleccError::ParseError {
+                    line: token.line,
+                    column: token.column,
+                    message: "Expected attribute name".to_string(),
+                });
+            }
+        };
+        let error = |message: &str| AleccError::ParseError {
+            line: token.line,
+            column: token.column,
+            message: message.to_string(),
+        };
+
+        match name {
+            "packed" => attributes.packed = true,
+            "noreturn" => attributes.noreturn = true,
+            "warn_unused_result" => attributes.nodiscard = true,
+            "fallthrough" => attributes.fallthrough = true,
+            "unused" => attributes.unused = true,
+            "aligned" => {
+                // Without an argument, the largest alignment any type needs
+                let mut alignment = self.target.stack_alignment();
+                if self.match_token(&TokenType::LeftParen) {
+                    let value = self.parse_assignment()?;
+                    self.consume(&TokenType::RightParen, "Expected ')' after alignment")?;
+                    alignment = self
+                        .evaluate(&value)
+                        .ok()
+                        .and_then(|value| usize::try_from(value).ok())
+                        .filter(|value| value.is_power_of_two())
+                        .ok_or_else(|| error("requested alignment is not a positive power of 2"))?;
+                }
+                attributes.aligned = attributes.aligned.max(Some(alignment));
+            }
+            "constructor" | "destructor" => {
+                let mut priority = None;
+                if self.match_token(&TokenType::LeftParen) {
+                    let value = self.parse_assignment()?;
+                    self.consume(&TokenType::RightParen, "Expected ')' after priority")?;
+                    let value = self
+                        .evaluate(&value)
+                        .ok()
+                        .and_then(|value| u16::try_from(value).ok())
+                        .ok_or_else(|| {
+                            error(&format!(
+                                "{} priorities must be integers from 0 to 65535 inclusive",
+                                name
+                            ))
+                        })?;
+                    if value <= 100 {
+                        let message = format!(
+                            "{} priorities from 0 to 100 are reserved for the implementation [-Wprio-ctor-dtor]",
+                            name
+                        );
+                        self.warn("prio-ctor-dtor", message, &token);
+                    }
+                    priority = Some(value);
+                }
+                if name == "constructor" {
+                    attributes.constructor = true;
+                    attributes.constructor_priority = priority;
+                } else {
+                    attributes.destructor = true;
+                    attributes.destructor_priority = priority;
+                }
+            }
+            "section" => {
+                self.consume(&TokenType::LeftParen, "Expected '(' after 'section'")?;
+                let TokenType::StringLiteral(section) = &self.advance()?.token_type else {
+                    return Err(error("section attribute argument not a string constant"));
+                };
+                // It goes into the assembly as it is written
+                let valid = !section.is_empty()
+                    && section
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '$' | '-'));
+                if !valid {
+                    return Err(error(&format!("invalid section name {:?}", section)));
+                }
+                attributes.section = Some(section.clone());
+                self.consume(&TokenType::RightParen, "Expected ')' after section name")?;
+            }
+            // Common in system headers, and safe to ignore
+            "used" | "always_inline" | "noinline" | "format" | "format_arg" | "nonnull"
+            | "deprecated" | "pure" | "const" | "malloc" | "nothrow" | "leaf" | "cold" | "hot"
+            | "visibility" | "may_alias" | "returns_twice" | "sentinel" | "access"
+            | "alloc_size" | "artificial" | "gnu_inline" | "mode" => {
+                self.skip_attribute_arguments()?;
+            }
+            _ => {
+                let message = format!("'{}' attribute directive ignored [-Wattributes]", name);
+                self.skip_attribute_arguments()?;
+                self.warn("attributes", message, &token);
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip an attribute's parenthesized arguments, if it has any
+    fn skip_attribute_arguments(&mut self) -> Result<()> {
+        if !self.match_token(&TokenType::LeftParen) {
+            return Ok(());
+        }
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance()?.token_type {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => depth -= 1,
+                TokenType::Eof => {
+                    return Err(AleccError::ParseError {
+                        line: self.previous()?.line,
+                        column: self.previous()?.column,
+                        message: "Expected ')' after attribute arguments".to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// `-Wattributes` for attributes that don't apply to what they're written on
+    fn ignore_attributes(&mut self, attributes: &Attributes, applicable: &[&str], at: &Token) {
+        for name in attributes.names() {
+            if !applicable.contains(&name) {
+                let message = format!("'{}' attribute ignored [-Wattributes]", name);
+                self.warn("attributes", message, at);
+            }
+        }
+    }
+
+    fn parse_alignment_specifiers(&mut self) -> Result<Option<usize>> {
+        let mut alignment = None;
+        while self.match_token(&TokenType::Alignas) {
+            let line = self.previous()?.line;
+            let column = self.previous()?.column;
+            self.consume(&TokenType::LeftParen, "Expected '(' after '_Alignas'")?;
+            let value = if self.is_type(&self.current_token()?.token_type)
+                || self.check(&TokenType::Struct)
+                || self.check(&TokenType::Union)
+                || self.check(&TokenType::Enum)
+                || self.check(&TokenType::Bool)
+            {
+                let ty = self.parse_type()?;
+                self.align_of(&ty, line, column)?
+            } else {
+                match self.parse_assignment()?.kind {
+                    ExpressionKind::IntegerLiteral(value) if value >= 0 => value as usize,
+                    _ => {
+                        return Err(AleccError::ParseError {
+                            line,
+                            column,
+                            message: "_Alignas requires a non-negative integer constant"
+                                .to_string(),
+                        });
+                    }
+                }
+            };
+            self.consume(&TokenType::RightParen, "Expected ')' after _Alignas")?;
+
+            if value != 0 && !value.is_power_of_two() {
+                return Err(AleccError::ParseError {
+                    line,
+                    column,
+                    message: format!("requested alignment {} is not a power of 2", value),
+                });
+            }
+            if value != 0 {
+                alignment = Some(alignment.map_or(value, |current: usize| current.max(value)));
+            }
+        }
+        Ok(alignment)
+    }
+
+    /// `_Alignas` may not weaken the natural alignment of the declared type
+    fn check_alignment(&self, ty: &Type, alignment: Option<usize>) -> Result<Option<usize>> {
+        let Some(alignment) = alignment else {
+            return Ok(None);
+        };
+        let line = self.previous()?.line;
+        let column = self.previous()?.column;
+        let natural = self.align_of(ty, line, column)?;
+        if alignment < natural {
+            return Err(AleccError::ParseError {
+                line,
+                column,
+                message: format!(
+                    "_Alignas({}) is weaker than the natural alignment {} of the declared type",
+                    alignment, natural
+                ),
+            });
+        }
+        Ok(Some(alignment))
+    }
+
+    /// Alignment of a complete object type on the target, as `_Alignof` reports it
+    fn align_of(&self, ty: &Type, line: usize, column: usize) -> Result<usize> {
+        let type_name = match self.resolve_typedef(ty) {
+            Type::Bool => "_Bool",
+            Type::Char | Type::UnsignedChar => "char",
+            Type::Short | Type::UnsignedShort => "short",
+            Type::Int | Type::UnsignedInt | Type::Enum { .. } => "int",
+            Type::Long | Type::UnsignedLong => "long",
+            Type::LongLong | Type::UnsignedLongLong => "long long",
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::Pointer(_) | Type::VaList => "void*",
+            Type::Array(element, _) => return self.align_of(&element, line, column),
+            Type::Struct { packed: true, .. } | Type::Union { packed: true, .. } => return Ok(1),
+            Type::Struct { fields, .. } | Type::Union { fields, .. } if !fields.is_empty() => {
+                let mut alignment = 1;
+                for (_, field) in &fields {
+                    alignment = alignment.max(self.align_of(field, line, column)?);
+                }
+                return Ok(alignment);
+            }
+            _ => "",
+        };
+        TargetInfo::new(self.target)
+            .align_of_type(type_name)
+            .ok_or_else(|| AleccError::ParseError {
+                line,
+                column,
+                message: "alignment of an incomplete or function type requested".to_string(),
+            })
+    }
+
+    /// Evaluate a constant expression with what has been declared so far
+    fn evaluate(&self, expr: &Expression) -> std::result::Result<i64, ConstantError> {
+        let enumerator = |name: &str| self.enumerator(name);
+        ConstantEvaluator::new()
+            .with_layout(&self.layout)
+            .with_enumerators(&enumerator)
+            .evaluate(expr)
+    }
+
+    /// Value of an integer constant expression, which the context requires
+    fn integer_constant(&self, expr: &Expression) -> Result<i64> {
+        self.evaluate(expr).map_err(constant_error)
+    }
+
+    /// Value of the enumeration constant `name`, unless a variable in a
+    /// nearer scope hides it
+    fn enumerator(&self, name: &str) -> Option<i64> {
+        for (variables, enumerators) in self.scopes.iter().zip(&self.enumerators).rev() {
+            if let Some(value) = enumerators.get(name) {
+                return Some(*value);
+            }
+            if variables.contains_key(name) {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.enumerators.push(HashMap::new());
+        self.static_names.push(HashMap::new());
+        self.variable_lengths.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.enumerators.pop();
+        self.static_names.pop();
+        self.variable_lengths.pop();
+    }
+
+    /// The global a `static` local is kept in, when `name` refers to one in
+    /// the innermost scope declaring it
+    fn static_local(&self, name: &str) -> Option<String> {
+        let scope = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name))?;
+        self.static_names.get(scope)?.get(name).cloned()
+    }
+
+    /// The hidden local holding the size of `name`, when `name` refers to a
+    /// variable length array in the innermost scope declaring it
+    fn variable_length_size(&self, name: &str) -> Option<String> {
+        let scope = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name))?;
+        self.variable_lengths.get(scope)?.get(name).cloned()
+    }
+
+    /// A local the parser declares itself, named `base.n` so it can't meet
+    /// an identifier of the program
+    fn hidden_local(&mut self, base: &str) -> String {
+        self.hidden_locals += 1;
+        format!("{}.{}", base, self.hidden_locals)
+    }
+
+    /// A global name for the `static` local `name` of the current function,
+    /// `function.name`, numbered when another block already took it
+    fn static_local_name(&self, name: &str) -> String {
+        let function = self.current_function.as_deref().unwrap_or("");
+        let base = format!("{}.{}", function, name);
+        let taken = |candidate: &String| {
+            self.static_locals
+                .iter()
+                .any(|other| other.name == *candidate)
+        };
+        std::iter::once(base.clone())
+            .chain((1..).map(|n| format!("{}.{}", base, n)))
+            .find(|candidate| !taken(candidate))
+            .unwrap_or(base)
+    }
+
+    fn declare_name(&mut self, name: &str, ty: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), ty);
+        }
+    }
+
+    fn lookup_name(&self, name: &str) -> Option<Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    /// Strip typedefs and top-level qualifiers
+    fn resolve_typedef(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Typedef(name, aliased) => match self.typedefs.get(name) {
+                Some(ty) => self.resolve_typedef(ty),
+                None => self.resolve_typedef(aliased),
+            },
+            Type::Qualified(_, inner) => self.resolve_typedef(inner),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Top-level qualifiers, including those of typedefs declared after the use was parsed
+    fn qualifiers_of(&self, ty: &Type) -> Qualifiers {
+        match ty {
+            Type::Typedef(name, aliased) => match self.typedefs.get(name) {
+                Some(ty) => self.qualifiers_of(ty),
+                None => self.qualifiers_of(aliased),
+            },
+            Type::Qualified(qualifiers, inner) => qualifiers.union(self.qualifiers_of(inner)),
+            _ => Qualifiers::default(),
+        }
+    }
+
+    fn same_type(&self, a: &Type, b: &Type) -> bool {
+        if self.qualifiers_of(a) != self.qualifiers_of(b) {
+            return false;
+        }
+        match (self.resolve_typedef(a), self.resolve_typedef(b)) {
+            (Type::Pointer(a), Type::Pointer(b)) => self.same_type(&a, &b),
+            (Type::Array(a, n), Type::Array(b, m)) => {
+                (n.is_none() || m.is_none() || n == m) && self.same_type(&a, &b)
+            }
+            (
+                Type::Function {
+                    return_type: a_ret,
+                    parameters: a_params,
+                    variadic: a_variadic,
+                },
+                Type::Function {
+                    return_type: b_ret,
+                    parameters: b_params,
+                    variadic: b_variadic,
+                },
+            ) => {
+                a_variadic == b_variadic
+                    && a_params.len() == b_params.len()
+                    && self.same_type(&a_ret, &b_ret)
+                    && a_params
+                        .iter()
+                        .zip(&b_params)
+                        .all(|(a, b)| self.same_type(a, b))
+            }
+            (Type::Struct { name: a, .. }, Type::Struct { name: b, .. })
+            | (Type::Union { name: a, .. }, Type::Union { name: b, .. })
+            | (Type::Enum { name: a, .. }, Type::Enum { name: b, .. }) => a == b,
+            (a, b) => std::mem::discriminant(&a) == std::mem::discriminant(&b),
+        }
+    }
+
+    /// Type of an expression after lvalue conversion (arrays and functions decay
+    /// to pointers), as far as the parser can tell without full semantic analysis
+    fn expression_type(&self, expr: &Expression) -> Option<Type> {
+        let ty = match &expr.kind {
+            // `long long` once a literal outgrows a 32-bit `long`
+            ExpressionKind::IntegerLiteral(value) => {
+                if i32::try_from(*value).is_ok() {
+                    Type::Int
+                } else if self.integer_width(&Type::Long) == Some(64) {
+                    Type::Long
+                } else {
+                    Type::LongLong
+                }
+            }
+            ExpressionKind::FloatLiteral(_) => Type::Double,
+            ExpressionKind::CharLiteral(_) | ExpressionKind::BooleanLiteral(_) => Type::Int,
+            ExpressionKind::StringLiteral(_) => Type::Pointer(Box::new(Type::Char)),
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Index { .. }
+            | ExpressionKind::Member { .. } => self.object_type(expr)?,
+            ExpressionKind::Unary { operator, operand } => match operator {
+                UnaryOperator::LogicalNot => Type::Int,
+                UnaryOperator::Plus | UnaryOperator::Minus | UnaryOperator::BitwiseNot => {
+                    self.promote(&self.expression_type(operand)?)
+                }
+                UnaryOperator::AddressOf => Type::Pointer(Box::new(self.object_type(operand)?)),
+                UnaryOperator::Dereference => self.object_type(expr)?,
+                _ => self.expression_type(operand)?,
+            },
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => match operator {
+                BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::Greater
+                | BinaryOperator::LessEqual
+                | BinaryOperator::GreaterEqual
+                | BinaryOperator::LogicalAnd
+                | BinaryOperator::LogicalOr => Type::Int,
+                BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                    self.promote(&self.expression_type(left)?)
+                }
+                _ => {
+                    let l = self.expression_type(left)?;
+                    let r = self.expression_type(right)?;
+                    match (&l, &r) {
+                        (Type::Pointer(_), Type::Pointer(_)) => Type::Long,
+                        (Type::Pointer(_), _) => l,
+                        (_, Type::Pointer(_)) => r,
+                        _ => self.common_type(&l, &r),
+                    }
+                }
+            },
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => match self.expression_type(function) {
+                Some(Type::Pointer(inner)) => match self.resolve_typedef(&inner) {
+                    Type::Function { return_type, .. } => *return_type,
+                    _ => return None,
+                },
+                // Builtins are declared without a header, and the atomic ones
+                // take their type from their operand; implicitly declared
+                // functions return int
+                None => match &function.kind {
+                    ExpressionKind::Identifier(name) => match (signature(name), atomic(name)) {
+                        (Some(Type::Function { return_type, .. }), _) => *return_type,
+                        (_, Some(atomic)) => {
+                            let operand = arguments
+                                .first()
+                                .and_then(|pointer| self.expression_type(pointer))
+                                .and_then(|ty| match self.resolve_typedef(&ty) {
+                                    Type::Pointer(inner) => Some(*inner),
+                                    _ => None,
+                                });
+                            atomic.result_type(operand)?
+                        }
+                        _ => Type::Int,
+                    },
+                    _ => return None,
+                },
+                _ => return None,
+            },
+            ExpressionKind::Cast { target_type, .. } => target_type.clone(),
+            ExpressionKind::Sizeof(_) => Type::Long,
+            ExpressionKind::LabelAddress(_) => Type::Pointer(Box::new(Type::Void)),
+            ExpressionKind::Assignment { target, .. } => self.expression_type(target)?,
+            ExpressionKind::Conditional {
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                let t = self.expression_type(then_expr)?;
+                let e = self.expression_type(else_expr)?;
+                if matches!(t, Type::Pointer(_)) {
+                    t
+                } else if matches!(e, Type::Pointer(_)) {
+                    e
+                } else {
+                    self.common_type(&t, &e)
+                }
+            }
+            ExpressionKind::VaArg { arg_type, .. } => arg_type.clone(),
+        };
+
+        Some(match self.resolve_typedef(&ty) {
+            Type::Array(element, _) => Type::Pointer(element),
+            function @ Type::Function { .. } => Type::Pointer(Box::new(function)),
+            // Lvalue conversion drops qualifiers
+            resolved if !self.qualifiers_of(&ty).is_empty() => resolved,
+            // Keep the typedef name (e.g. va_list) when nothing decays
+            _ => ty,
+        })
+    }
+
+    /// Type of the operand of `sizeof`, where arrays don't decay
+    fn operand_type(&self, expr: &Expression) -> Option<Type> {
+        match &expr.kind {
+            ExpressionKind::StringLiteral(content) => {
+                Some(Type::Array(Box::new(Type::Char), Some(content.len() + 1)))
+            }
+            ExpressionKind::Identifier(name) if self.enumerator(name).is_some() => Some(Type::Int),
+            _ => self
+                .object_type(expr)
+                .or_else(|| self.expression_type(expr)),
+        }
+    }
+
+    /// Declared type of the object an lvalue designates, before decay
+    fn object_type(&self, expr: &Expression) -> Option<Type> {
+        match &expr.kind {
+            ExpressionKind::Identifier(name) => self.lookup_name(name),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => match self.resolve_typedef(&self.expression_type(operand)?) {
+                Type::Pointer(inner) => Some(*inner),
+                _ => None,
+            },
+            ExpressionKind::Index { array, index } => {
+                let base = match self.expression_type(array)? {
+                    Type::Pointer(inner) => inner,
+                    _ => match self.expression_type(index)? {
+                        Type::Pointer(inner) => inner,
+                        _ => return None,
+                    },
+                };
+                Some(*base)
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let aggregate = if *is_arrow {
+                    match self.resolve_typedef(&self.expression_type(object)?) {
+                        Type::Pointer(inner) => *inner,
+                        _ => return None,
+                    }
+                } else {
+                    // `f().x` names a member of a struct returned by value
+                    self.object_type(object)
+                        .or_else(|| self.expression_type(object))?
+                };
+                match self.resolve_typedef(&aggregate) {
+                    Type::Struct { fields, .. } | Type::Union { fields, .. } => fields
+                        .into_iter()
+                        .find(|(name, _)| name == member)
+                        .map(|(_, ty)| ty),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn promote(&self, ty: &Type) -> Type {
+        match self.resolve_typedef(ty) {
+            Type::Char
+            | Type::Short
+            | Type::UnsignedChar
+            | Type::UnsignedShort
+            | Type::Bool
+            | Type::Enum { .. } => Type::Int,
+            _ => ty.clone(),
+        }
+    }
+
+    /// The usual arithmetic conversions, for the types the parser knows about
+    fn common_type(&self, a: &Type, b: &Type) -> Type {
+        let (a, b) = (
+            self.resolve_typedef(&self.promote(a)),
+            self.resolve_typedef(&self.promote(b)),
+        );
+        // Between an int and a long, and then the unsigned type of the two
+        let rank = |ty: &Type| match ty {
+            Type::Double => 7,
+            Type::Float => 6,
+            Type::UnsignedLongLong => 5,
+            Type::LongLong => 4,
+            Type::UnsignedLong => 3,
+            Type::Long => 2,
+            Type::UnsignedInt => 1,
+            _ => 0,
+        };
+        if rank(&a) >= rank(&b) {
+            a
+        } else {
+            b
+        }
+    }
+
+    /// Width in bits of an integer type on the target
+    fn integer_width(&self, ty: &Type) -> Option<u32> {
+        let type_name = match self.resolve_typedef(ty) {
+            Type::Bool => "_Bool",
+            Type::Char | Type::UnsignedChar => "char",
+            Type::Short | Type::UnsignedShort => "short",
+            Type::Int | Type::UnsignedInt | Type::Enum { .. } => "int",
+            Type::Long | Type::UnsignedLong => "long",
+            Type::LongLong | Type::UnsignedLongLong => "long long",
+            _ => return None,
+        };
+        let bytes = TargetInfo::new(self.target).size_of_type(type_name)?;
+        Some(bytes as u32 * 8)
+    }
+
+    /// The type as GCC spells it in diagnostics, e.g. `const char *`
+    fn type_name(&self, ty: &Type) -> String {
+        let qualifiers = self.qualifiers_of(ty);
+        let mut name = String::new();
+        if qualifiers.is_const {
+            name.push_str("const ");
+        }
+        if qualifiers.is_volatile {
+            name.push_str("volatile ");
+        }
+        match self.resolve_typedef(ty) {
+            Type::Pointer(inner) => {
+                let inner = self.type_name(&inner);
+                let star = if inner.ends_with('*') { "*" } else { " *" };
+                // Qualifiers of the pointer itself follow the `*`
+                return format!("{}{}{}", inner, star, name.trim_end())
+                    .trim_end()
+                    .to_string();
+            }
+            Type::Void => name.push_str("void"),
+            Type::Char => name.push_str("char"),
+            Type::Short => name.push_str("short int"),
+            Type::Long => name.push_str("long int"),
+            Type::UnsignedChar => name.push_str("unsigned char"),
+            Type::UnsignedShort => name.push_str("short unsigned int"),
+            Type::UnsignedInt => name.push_str("unsigned int"),
+            Type::UnsignedLong => name.push_str("long unsigned int"),
+            Type::LongLong => name.push_str("long long int"),
+            Type::UnsignedLongLong => name.push_str("long long unsigned int"),
+            Type::Float => name.push_str("float"),
+            Type::Double => name.push_str("double"),
+            Type::Bool => name.push_str("_Bool"),
+            Type::Struct { name: tag, .. } => name.push_str(&format!("struct {}", tag)),
+            Type::Union { name: tag, .. } => name.push_str(&format!("union {}", tag)),
+            Type::Enum { name: tag, .. } => name.push_str(&format!("enum {}", tag)),
+            Type::VaList => name.push_str("__builtin_va_list"),
+            _ => name.push_str("int"),
+        }
+        name
+    }
+
+    fn warn(&mut self, option: &'static str, message: String, at: &Token) {
+        self.warnings.push(TypeWarning {
+            option,
+            message,
+            line: at.line,
+            column: at.column,
+            note: None,
+        });
+    }
+
+    /// `-Wshift-count-overflow` and `-Wshift-count-negative` for constant shift counts
+    fn check_shift_count(
+        &mut self,
+        left: &Expression,
+        operator: &BinaryOperator,
+        count: &Expression,
+        at: &Token,
+    ) {
+        let (Some(count), Some(width)) = (
+            self.evaluate(count).ok(),
+            self.expression_type(left)
+                .and_then(|ty| self.integer_width(&self.promote(&ty))),
+        ) else {
+            return;
+        };
+        let direction = match operator {
+            BinaryOperator::LeftShift => "left",
+            _ => "right",
+        };
+        if count < 0 {
+            let message = format!(
+                "{} shift count is negative [-Wshift-count-negative]",
+                direction
+            );
+            self.warn("shift-count-negative", message, at);
+        } else if count >= i64::from(width) {
+            let message = format!(
+                "{} shift count >= width of type [-Wshift-count-overflow]",
+                direction
+            );
+            self.warn("shift-count-overflow", message, at);
+        }
+    }
+
+    /// `-Wunused-result` for an expression statement that drops the result of a
+    /// call to a `nodiscard` function; a cast to `void` keeps it quiet
+    fn check_discarded_result(&mut self, expr: &Expression, at: &Token) {
+        let ExpressionKind::Call { function, .. } = &expr.kind else {
+            return;
+        };
+        let ExpressionKind::Identifier(name) = &function.kind else {
+            return;
+        };
+        if self
+            .function_attributes
+            .get(name)
+            .is_some_and(|attributes| attributes.nodiscard)
+        {
+            let message = format!(
+                "ignoring return value of '{}' declared with attribute 'nodiscard' [-Wunused-result]",
+                name
+            );
+            self.warn("unused-result", message, at);
+        }
+    }
+
+    /// A pointer compared with an integer other than a null pointer constant
+    fn check_pointer_comparison(&mut self, left: &Expression, right: &Expression, at: &Token) {
+        let (Some(left_type), Some(right_type)) =
+            (self.expression_type(left), self.expression_type(right))
+        else {
+            return;
+        };
+        let is_pointer =
+            |ty: &Type| matches!(self.resolve_typedef(ty), Type::Pointer(_) | Type::Array(..));
+        let integer = if is_pointer(&left_type) && self.integer_width(&right_type).is_some() {
+            right
+        } else if is_pointer(&right_type) && self.integer_width(&left_type).is_some() {
+            left
+        } else {
+            return;
+        };
+        if !is_null_pointer_constant(integer) {
+            let message = "comparison between pointer and integer".to_string();
+            self.warn("pointer-integer-compare", message, at);
+        }
+    }
+
+    /// `-Wsign-compare` for a comparison that converts a signed operand to
+    /// unsigned, unless it is a constant known not to be negative
+    fn check_sign_comparison(&mut self, left: &Expression, right: &Expression, at: &Token) {
+        let (Some(left_type), Some(right_type)) =
+            (self.expression_type(left), self.expression_type(right))
+        else {
+            return;
+        };
+        let (left_type, right_type) = (
+            self.resolve_typedef(&self.promote(&left_type)),
+            self.resolve_typedef(&self.promote(&right_type)),
+        );
+        if self.integer_width(&left_type).is_none() || self.integer_width(&right_type).is_none() {
+            return;
+        }
+        let signed = match (left_type.is_unsigned(), right_type.is_unsigned()) {
+            (false, true) => left,
+            (true, false) => right,
+            _ => return,
+        };
+        // A long holds every unsigned int where it is wider
+        if !self.common_type(&left_type, &right_type).is_unsigned() {
+            return;
+        }
+        if self.evaluate(signed).is_ok_and(|value| value >= 0) {
+            return;
+        }
+        let message = format!(
+            "comparison of integer expressions of different signedness: '{}' and '{}' [-Wsign-compare]",
+            self.type_name(&left_type),
+            self.type_name(&right_type)
+        );
+        self.warn("sign-compare", message, at);
+    }
+
+    /// `-Wformat` and `-Wformat-extra-args` for calls to the printf and scanf
+    /// families whose format string is a literal
+    fn check_format(&mut self, function: &str, arguments: &[Expression], starts: &[Token]) {
+        let Some((index, style)) = format::format_function(function) else {
+            return;
+        };
+        let Some(ExpressionKind::StringLiteral(format)) = arguments.get(index).map(|a| &a.kind)
+        else {
+            return;
+        };
+        let at = &starts[index];
+        let directives = match format::directives(format, style) {
+            Ok(directives) => directives,
+            Err(message) => {
+                self.warn("format", format!("{} [-Wformat=]", message), at);
+                return;
+            }
+        };
+
+        let values = &arguments[index + 1..];
+        for (i, directive) in directives.iter().enumerate() {
+            let Some(expected) = &directive.argument else {
+                continue;
+            };
+            let Some(value) = values.get(i) else {
+                let message = format!(
+                    "format '{}' expects a matching '{}' argument [-Wformat=]",
+                    directive.text,
+                    self.type_name(expected)
+                );
+                self.warn("format", message, at);
+                return;
+            };
+            let Some(actual) = self.expression_type(value) else {
+                continue;
+            };
+            if !self.format_argument_matches(expected, &actual) {
+                let message = format!(
+                    "format '{}' expects argument of type '{}', but argument {} has type '{}' [-Wformat=]",
+                    directive.text,
+                    self.type_name(expected),
+                    index + i + 2,
+                    self.type_name(&actual)
+                );
+                self.warn("format", message, &starts[index + 1 + i]);
+            }
+        }
+        if values.len() > directives.len() {
+            let message = "too many arguments for format [-Wformat-extra-args]".to_string();
+            self.warn("format-extra-args", message, at);
+        }
+    }
+
+    /// Whether a variadic argument of type `actual` suits a directive expecting `expected`
+    fn format_argument_matches(&self, expected: &Type, actual: &Type) -> bool {
+        match (self.resolve_typedef(expected), self.resolve_typedef(actual)) {
+            (Type::Pointer(expected), Type::Pointer(actual)) => {
+                // `%p` takes any object pointer
+                matches!(self.resolve_typedef(&expected), Type::Void)
+                    || self.same_unqualified_type(&expected, &actual)
+                    || self.same_rank_integers(&expected, &actual)
+            }
+            // Default argument promotions, which the pointees of scanf arguments don't undergo
+            (Type::Int | Type::UnsignedInt, actual) => matches!(
+                self.promote(&actual).to_signed(),
+                Type::Int | Type::Enum { .. }
+            ),
+            (Type::Double, Type::Float | Type::Double) => true,
+            (expected, actual) => self.same_rank_integers(&expected, &actual),
+        }
+    }
+
+    /// Whether two integer types differ at most in signedness, which the
+    /// format checks accept as GCC does without `-Wformat-signedness`
+    fn same_rank_integers(&self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.resolve_typedef(a), self.resolve_typedef(b));
+        self.integer_width(&a).is_some()
+            && self.integer_width(&b).is_some()
+            && std::mem::discriminant(&a.to_signed()) == std::mem::discriminant(&b.to_signed())
+    }
+
+    /// Like `same_type`, but ignoring qualifiers at every level
+    fn same_unqualified_type(&self, a: &Type, b: &Type) -> bool {
+        match (self.resolve_typedef(a), self.resolve_typedef(b)) {
+            (Type::Pointer(a), Type::Pointer(b)) => self.same_unqualified_type(&a, &b),
+            (a, b) => self.same_type(&a, &b),
+        }
+    }
+
+    /// `-Woverflow` for constants that don't fit an integer type they are stored in,
+    /// and `-Wconversion`, `-Wsign-conversion` and `-Wfloat-conversion` for
+    /// values that may not
+    fn check_conversion(&mut self, target: &Type, value: &Expression, at: &Token) {
+        let target_width = match self.resolve_typedef(target) {
+            // Conversion to _Bool is well defined for every value
+            Type::Bool => return,
+            ty => match self.integer_width(&ty) {
+                Some(width) => width,
+                None => return,
+            },
+        };
+        let Some(value_type) = self.expression_type(value) else {
+            return;
+        };
+        let (from, to) = (self.type_name(&value_type), self.type_name(target));
+        let to_unsigned = self.resolve_typedef(target).is_unsigned();
+
+        if let Ok(constant) = self.evaluate(value) {
+            // A literal that doesn't fit its own type has been reported already
+            let own_width = self.integer_width(&value_type).unwrap_or(64);
+            let own_shift = 64 - own_width;
+            if (constant << own_shift) >> own_shift != constant
+                && ((constant as u64) << own_shift >> own_shift) as i64 != constant
+            {
+                return;
+            }
+            let shift = 64 - target_width;
+            let truncated = (constant << shift) >> shift;
+            let unsigned = ((constant as u64) << shift >> shift) as i64;
+            let (converted, kind) = if to_unsigned {
+                (unsigned, "unsigned conversion")
+            } else {
+                (truncated, "overflow in conversion")
+            };
+            if converted == constant {
+                return;
+            }
+            // Only the sign changes when the bits are kept as they are
+            let same_bits = own_width == target_width
+                && self.resolve_typedef(&value_type).is_unsigned() != to_unsigned;
+            let (option, kind) = match (same_bits, to_unsigned) {
+                (false, _) => ("overflow", kind),
+                (true, true) => ("sign-conversion", kind),
+                (true, false) => ("sign-conversion", "conversion"),
+            };
+            let message = format!(
+                "{} from '{}' to '{}' changes value from '{}' to '{}' [-W{}]",
+                kind, from, to, constant, converted, option
+            );
+            self.warn(option, message, at);
+            return;
+        }
+
+        match self.resolve_typedef(&value_type) {
+            Type::Float | Type::Double => {
+                let message = format!(
+                    "conversion from '{}' to '{}' may change value [-Wfloat-conversion]",
+                    from, to
+                );
+                self.warn("float-conversion", message, at);
+            }
+            ty => {
+                let Some(width) = self.integer_width(&ty) else {
+                    return;
+                };
+                if width > target_width {
+                    let message = format!(
+                        "conversion from '{}' to '{}' may change value [-Wconversion]",
+                        from, to
+                    );
+                    self.warn("conversion", message, at);
+                } else if (to_unsigned && !ty.is_unsigned() && !matches!(ty, Type::Bool))
+                    || (ty.is_unsigned() && !to_unsigned && width == target_width)
+                {
+                    let message = format!(
+                        "conversion to '{}' from '{}' may change the sign of the result [-Wsign-conversion]",
+                        to, from
+                    );
+                    self.warn("sign-conversion", message, at);
+                }
+            }
         }
     }
 }
 
+fn constant_error(error: ConstantError) -> AleccError {
+    let span = error.span();
+    AleccError::ParseError {
+        line: span.line,
+        column: span.column,
+        message: error.to_string(),
+    }
+}
+
+/// An integer constant 0, possibly cast to `void *`, which converts to a null pointer
+pub fn is_null_pointer_constant(expr: &Expression) -> bool {
+    match &expr.kind {
+        ExpressionKind::Cast {
+            target_type: Type::Pointer(pointee),
+            expression,
+        } if matches!(pointee.unqualified(), Type::Void) => is_null_pointer_constant(expression),
+        _ => integer_constant(expr) == Some(0),
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Declaration {
-    Function(Function),
-    Variable(String, Type, Option<Expression>),
+    Function(Box<Function>),
+    Variable(Box<GlobalVariable>),
     TypeDef(String, Type),
 }
 
 #[derive(Debug, Clone)]
 enum StorageClass {
     None,
-    #[allow(dead_code)]
     Static,
-    #[allow(dead_code)]
     Extern,
     #[allow(dead_code)]
     Auto,
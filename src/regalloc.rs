@@ -0,0 +1,219 @@
+use crate::ir::{BlockId, Instruction, IrFunction, Operand, VReg};
+use crate::targets::Target;
+use std::collections::{HashMap, HashSet};
+
+/// Registers `IrAssembler` uses itself, to compute in, pass arguments and
+/// reach far-away frame offsets, and that are never handed out
+fn reserved_registers(target: Target) -> &'static [&'static str] {
+    match target {
+        Target::I386 | Target::Amd64 => &["eax", "ecx", "edx", "rax", "rcx", "rdx", "r11"],
+        Target::Arm64 => &["x9", "x10", "x11", "x16", "x17", "x18"],
+    }
+}
+
+/// Where each virtual register of a function lives: in a machine register,
+/// or, when none was free, in a frame slot of its own. Only `--backend=ir`
+/// allocates registers; `CodeGenerator` keeps its temporaries on the stack
+#[derive(Debug, Clone, Default)]
+pub struct Allocation {
+    registers: Vec<Option<&'static str>>,
+    /// The callee-saved registers handed out, which the prologue saves
+    pub callee_saved: Vec<&'static str>,
+}
+
+impl Allocation {
+    /// The machine register `reg` lives in, by its widest name
+    pub fn register(&self, reg: VReg) -> Option<&'static str> {
+        self.registers.get(reg.0).copied().flatten()
+    }
+}
+
+/// The positions, in layout order, from a virtual register's definition to
+/// its last use, counting each block's instructions and then its terminator
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    reg: VReg,
+    start: usize,
+    end: usize,
+    /// Live while a call is made, so only a callee-saved register keeps it
+    across_call: bool,
+    /// Live until a call or the prologue's moves of the parameters, when the
+    /// argument registers are being written
+    meets_arguments: bool,
+}
+
+/// Linear-scan register allocation: walk the live intervals by start,
+/// giving each a register no longer in use. Values live across a call get a
+/// callee-saved register; the others prefer a caller-saved one, which costs
+/// no save in the prologue, and those away from calls and parameters may
+/// use the argument registers too. When none is free, whichever of the
+/// interval and the active ones ends last is spilled.
+pub fn allocate(function: &IrFunction, target: Target) -> Allocation {
+    let set = target.register_names();
+    let reserved = reserved_registers(target);
+    let (arguments, caller_saved): (Vec<&'static str>, Vec<&'static str>) = set
+        .caller_saved_registers()
+        .iter()
+        .copied()
+        .filter(|r| !reserved.contains(r))
+        .partition(|r| set.parameter_registers().contains(r));
+    let callee_saved = set.callee_saved_registers();
+
+    let mut allocation = Allocation {
+        registers: vec![None; function.types.len()],
+        callee_saved: Vec::new(),
+    };
+    let mut intervals = live_intervals(function);
+    // Parameters the caller passed on the stack are already in memory
+    let on_stack: HashSet<VReg> = function
+        .parameters
+        .iter()
+        .skip(set.parameter_registers().len())
+        .map(|(reg, _)| *reg)
+        .collect();
+    intervals.retain(|interval| !on_stack.contains(&interval.reg));
+    intervals.sort_by_key(|interval| (interval.start, interval.end, interval.reg.0));
+
+    let mut active: Vec<(Interval, &'static str)> = Vec::new();
+    for interval in intervals {
+        // An operand's register can be the result's: operands are read first
+        active.retain(|(other, _)| other.end > interval.start);
+        let in_use: Vec<&str> = active.iter().map(|(_, register)| *register).collect();
+        let candidates: Vec<&'static str> = if interval.across_call {
+            callee_saved.to_vec()
+        } else if interval.meets_arguments {
+            caller_saved.iter().chain(callee_saved).copied().collect()
+        } else {
+            let caller_saved = caller_saved.iter().chain(&arguments);
+            caller_saved.chain(callee_saved).copied().collect()
+        };
+        let register = match candidates.iter().find(|r| !in_use.contains(r)) {
+            Some(register) => Some(*register),
+            None => {
+                // Take the register of the active interval that ends last
+                let victim = active
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (other, register))| {
+                        other.end > interval.end && candidates.contains(register)
+                    })
+                    .max_by_key(|(_, (other, _))| other.end)
+                    .map(|(index, _)| index);
+                victim.map(|index| {
+                    let (spilled, register) = active.remove(index);
+                    allocation.registers[spilled.reg.0] = None;
+                    register
+                })
+            }
+        };
+        if let Some(register) = register {
+            allocation.registers[interval.reg.0] = Some(register);
+            active.push((interval, register));
+            if callee_saved.contains(&register) && !allocation.callee_saved.contains(&register) {
+                allocation.callee_saved.push(register);
+            }
+        }
+    }
+    // Keep the save order stable, whichever interval took a register first
+    allocation
+        .callee_saved
+        .sort_by_key(|register| callee_saved.iter().position(|r| r == register));
+    allocation
+}
+
+/// The live interval of every virtual register defined or used in
+/// `function`, from the liveness of each block's entry and exit
+fn live_intervals(function: &IrFunction) -> Vec<Interval> {
+    let uses = |operands: Vec<Operand>| {
+        operands.into_iter().filter_map(|operand| match operand {
+            Operand::Reg(reg) => Some(reg),
+            Operand::Imm(_) => None,
+        })
+    };
+
+    // Registers each block reads before writing, and those it writes
+    let mut used_first: HashMap<BlockId, HashSet<VReg>> = HashMap::new();
+    let mut defined: HashMap<BlockId, HashSet<VReg>> = HashMap::new();
+    for block in &function.blocks {
+        let used = used_first.entry(block.id).or_default();
+        let written = defined.entry(block.id).or_default();
+        for instruction in &block.instructions {
+            used.extend(uses(instruction.operands()).filter(|reg| !written.contains(reg)));
+            written.extend(instruction.dest());
+        }
+        used.extend(uses(block.terminator.operands()).filter(|reg| !written.contains(reg)));
+    }
+
+    let mut live_in: HashMap<BlockId, HashSet<VReg>> = HashMap::new();
+    let mut live_out: HashMap<BlockId, HashSet<VReg>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in function.blocks.iter().rev() {
+            let out: HashSet<VReg> = block
+                .terminator
+                .successors()
+                .iter()
+                .filter_map(|successor| live_in.get(successor))
+                .flatten()
+                .copied()
+                .collect();
+            let mut entry = used_first[&block.id].clone();
+            entry.extend(out.difference(&defined[&block.id]));
+            if live_in.get(&block.id) != Some(&entry) {
+                live_in.insert(block.id, entry);
+                changed = true;
+            }
+            live_out.insert(block.id, out);
+        }
+    }
+
+    let mut ranges: HashMap<VReg, (usize, usize)> = HashMap::new();
+    let mut extend = |reg: VReg, position: usize| {
+        let range = ranges.entry(reg).or_insert((position, position));
+        range.0 = range.0.min(position);
+        range.1 = range.1.max(position);
+    };
+    // Parameters arrive before the first instruction
+    for (reg, _) in &function.parameters {
+        extend(*reg, 0);
+    }
+    let mut calls = Vec::new();
+    let mut position = 1;
+    for block in &function.blocks {
+        let first = position;
+        for reg in &live_in[&block.id] {
+            extend(*reg, first);
+        }
+        for instruction in &block.instructions {
+            for reg in uses(instruction.operands()) {
+                extend(reg, position);
+            }
+            if let Some(dest) = instruction.dest() {
+                extend(dest, position);
+            }
+            if matches!(instruction, Instruction::Call { .. }) {
+                calls.push(position);
+            }
+            position += 1;
+        }
+        for reg in uses(block.terminator.operands()) {
+            extend(reg, position);
+        }
+        for reg in &live_out[&block.id] {
+            extend(*reg, position);
+        }
+        position += 1;
+    }
+
+    ranges
+        .into_iter()
+        .map(|(reg, (start, end))| Interval {
+            reg,
+            start,
+            end,
+            across_call: calls.iter().any(|call| start < *call && *call < end),
+            meets_arguments: start == 0 || calls.iter().any(|call| start < *call && *call <= end),
+        })
+        .collect()
+}
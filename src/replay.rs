@@ -0,0 +1,283 @@
+use crate::cli::ReplayArgs;
+use crate::error::{AleccError, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// One compilation recorded in a `compile_commands.json`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Where the command ran; relative paths in it start here
+    pub directory: PathBuf,
+    pub file: PathBuf,
+    /// The command line, starting with the compiler that ran it
+    pub arguments: Vec<String>,
+}
+
+impl Entry {
+    /// The source file, with the entry's directory in front when it is relative
+    pub fn path(&self) -> PathBuf {
+        self.directory.join(&self.file)
+    }
+}
+
+pub async fn run(args: &ReplayArgs) -> Result<i32> {
+    let database = if args.database.is_dir() {
+        args.database.join("compile_commands.json")
+    } else {
+        args.database.clone()
+    };
+    let json =
+        tokio::fs::read_to_string(&database)
+            .await
+            .map_err(|_| AleccError::FileNotFound {
+                path: database.to_string_lossy().to_string(),
+            })?;
+    let entries = parse_database(&json)?;
+
+    if args.list {
+        for (index, entry) in entries.iter().enumerate() {
+            println!("{:4}  {}", index, entry.path().display());
+        }
+        return Ok(0);
+    }
+
+    let entry = select(&entries, args.file.as_deref(), args.index)?;
+    let arguments = replay_arguments(entry, args.optimization.as_deref(), args.target.as_deref());
+    if args.print {
+        let mut line = format!("cd {} && alecc", quote(&entry.directory.to_string_lossy()));
+        for argument in &arguments {
+            line.push(' ');
+            line.push_str(&quote(argument));
+        }
+        println!("{}", line);
+        return Ok(0);
+    }
+
+    let status = Command::new(std::env::current_exe()?)
+        .args(&arguments)
+        .current_dir(&entry.directory)
+        .status()?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// The entries of a compilation database, whether each spells its command
+/// line as `arguments` (a list) or as a `command` string
+#[cfg(feature = "serde")]
+pub fn parse_database(json: &str) -> Result<Vec<Entry>> {
+    #[derive(serde::Deserialize)]
+    struct Recorded {
+        directory: PathBuf,
+        file: PathBuf,
+        arguments: Option<Vec<String>>,
+        command: Option<String>,
+    }
+
+    let invalid = |message: String| AleccError::InvalidArgument { message };
+    let recorded: Vec<Recorded> = serde_json::from_str(json)
+        .map_err(|e| invalid(format!("malformed compilation database: {}", e)))?;
+    recorded
+        .into_iter()
+        .map(|entry| {
+            let arguments = match (entry.arguments, entry.command) {
+                (Some(arguments), _) => arguments,
+                (None, Some(command)) => split_command(&command),
+                (None, None) => {
+                    return Err(invalid(format!(
+                        "the entry for {} has neither `arguments` nor `command`",
+                        entry.file.display()
+                    )))
+                }
+            };
+            Ok(Entry {
+                directory: entry.directory,
+                file: entry.file,
+                arguments,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn parse_database(_json: &str) -> Result<Vec<Entry>> {
+    Err(AleccError::InvalidArgument {
+        message: "alecc was built without JSON support to read compilation databases (enable the `serde` feature)".to_string(),
+    })
+}
+
+/// Split a `command` string into arguments the way a POSIX shell does for
+/// the quoting build systems use: single and double quotes and backslashes
+#[cfg(feature = "serde")]
+pub fn split_command(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => arguments.extend(current.take()),
+            '\'' => {
+                let argument = current.get_or_insert_with(String::new);
+                argument.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                let argument = current.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => argument.extend(chars.next()),
+                        c => argument.push(c),
+                    }
+                }
+            }
+            '\\' => current.get_or_insert_with(String::new).extend(chars.next()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    arguments.extend(current);
+    arguments
+}
+
+/// The entry to re-run: the one at `index`, or the only one compiling `file`.
+/// A file matches when it is the same file as the entry's, or when the
+/// entry's path ends with it
+pub fn select<'a>(
+    entries: &'a [Entry],
+    file: Option<&Path>,
+    index: Option<usize>,
+) -> Result<&'a Entry> {
+    let invalid = |message: String| AleccError::InvalidArgument { message };
+    if let Some(index) = index {
+        return entries.get(index).ok_or_else(|| {
+            invalid(format!(
+                "the compilation database has {} entries, so there is no entry {}",
+                entries.len(),
+                index
+            ))
+        });
+    }
+    let Some(file) = file else {
+        return match entries {
+            [entry] => Ok(entry),
+            _ => Err(invalid(
+                "name the source file to re-run, or pick an entry with --index (see --list)"
+                    .to_string(),
+            )),
+        };
+    };
+
+    let wanted = file.canonicalize().ok();
+    let matches: Vec<(usize, &Entry)> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            let path = entry.path();
+            match (&wanted, path.canonicalize()) {
+                (Some(wanted), Ok(path)) => *wanted == path,
+                _ => path.ends_with(file),
+            }
+        })
+        .collect();
+    match matches.as_slice() {
+        [] => Err(invalid(format!(
+            "no entry in the compilation database compiles {}",
+            file.display()
+        ))),
+        [(_, entry)] => Ok(entry),
+        _ => {
+            let indices: Vec<String> = matches.iter().map(|(index, _)| index.to_string()).collect();
+            Err(invalid(format!(
+                "{} entries compile {} (entries {}); pick one with --index",
+                matches.len(),
+                file.display(),
+                indices.join(", ")
+            )))
+        }
+    }
+}
+
+/// alecc's arguments for re-running `entry`: the recorded command line
+/// without the compiler, with the GCC spellings of options alecc knows
+/// translated to alecc's, dependency-file options dropped, and the `-O`
+/// level and target replaced when `optimization` or `target` are given
+pub fn replay_arguments(
+    entry: &Entry,
+    optimization: Option<&str>,
+    target: Option<&str>,
+) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut recorded = entry.arguments.iter().skip(1);
+    while let Some(argument) = recorded.next() {
+        let argument = argument.as_str();
+        match argument {
+            // Dependency files are the build system's business
+            "-MD" | "-MMD" | "-MP" => {}
+            "-MF" | "-MT" | "-MQ" => {
+                recorded.next();
+            }
+            "-t" | "--target" | "-target" if target.is_some() => {
+                recorded.next();
+            }
+            "-O" | "--optimize" if optimization.is_some() => {
+                recorded.next();
+            }
+            _ if target.is_some()
+                && (argument.starts_with("--target=")
+                    || (argument.starts_with("-t") && argument.len() > 2)) => {}
+            _ if optimization.is_some()
+                && (argument.starts_with("--optimize=")
+                    || (argument.starts_with("-O") && argument.len() > 2)) => {}
+            "-isystem" | "-iquote" | "-idirafter" => {
+                arguments.push("-I".to_string());
+                arguments.extend(recorded.next().cloned());
+            }
+            _ => arguments.push(gcc_option(argument).to_string()),
+        }
+    }
+    if let Some(level) = optimization {
+        arguments.push(format!("-O{}", level));
+    }
+    if let Some(target) = target {
+        arguments.push("-t".to_string());
+        arguments.push(target.to_string());
+    }
+    arguments
+}
+
+/// alecc's spelling of a GCC option it also takes; other arguments are
+/// returned as they are
+fn gcc_option(argument: &str) -> String {
+    if let Some(standard) = argument.strip_prefix("-std=") {
+        return format!("--std={}", standard);
+    }
+    match argument {
+        "-fPIC" | "-fpic" | "-fPIE" | "-fpie" => "--pic",
+        "-pie" => "--pie",
+        "-no-pie" => "--no-pie",
+        "-static" => "--static",
+        "-shared" => "--shared",
+        "-nostdlib" => "--nostdlib",
+        "-nostartfiles" => "--nostartfiles",
+        "-msse2" => "--msse2",
+        "-masm=att" => "--masm=att",
+        "-masm=intel" => "--masm=intel",
+        "-mno-red-zone" => "--mno-red-zone",
+        "-fstack-usage" => "--fstack-usage",
+        "-fwhole-program" => "--fwhole-program",
+        "-fanalyzer" => "--analyze",
+        "-flto" => "--lto",
+        argument => argument,
+    }
+    .to_string()
+}
+
+/// `argument` quoted for a POSIX shell when it needs to be
+fn quote(argument: &str) -> String {
+    let plain = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=+./,:@%".contains(c));
+    if plain {
+        argument.to_string()
+    } else {
+        format!("'{}'", argument.replace('\'', r"'\''"))
+    }
+}
@@ -0,0 +1,304 @@
+use crate::cli::AsDialect;
+use crate::targets::Target;
+
+/// Assembly for the runtime linked with `--nostdlib`: thin wrappers around raw Linux
+/// system calls, so programs can do I/O and exit without libc.
+///
+/// The wrappers return the kernel's result unchanged (a negative errno on
+/// failure) since there is no `errno` to set.
+///
+/// It also brings the guard and failure handler that `-fstack-protector`
+/// code refers to.
+pub fn runtime_assembly(target: Target, as_dialect: AsDialect) -> String {
+    let mut asm = String::new();
+    let mut emit = |line: &str| {
+        asm.push_str(line);
+        asm.push('\n');
+    };
+
+    match target {
+        Target::I386 => {
+            match as_dialect {
+                AsDialect::Gnu => emit(".arch i386"),
+                AsDialect::Llvm => emit(".code32"),
+            }
+            emit(".intel_syntax noprefix");
+        }
+        Target::Amd64 => emit(".intel_syntax noprefix"),
+        Target::Arm64 => emit(".arch armv8-a"),
+    }
+    emit("");
+    emit(".section .text");
+
+    // (name, syscall number) for read/write, which take (fd, buf, count)
+    let io_calls = match target {
+        Target::I386 => [("read", 3), ("write", 4)],
+        Target::Amd64 => [("read", 0), ("write", 1)],
+        Target::Arm64 => [("read", 63), ("write", 64)],
+    };
+    for (name, number) in io_calls {
+        emit("");
+        emit(&format!(".globl {}", name));
+        emit(&format!("{}:", name));
+        match target {
+            Target::I386 => {
+                // cdecl: arguments on the stack, ebx is callee-saved
+                emit("    push ebx");
+                emit(&format!("    mov eax, {}", number));
+                emit("    mov ebx, DWORD PTR [esp + 8]");
+                emit("    mov ecx, DWORD PTR [esp + 12]");
+                emit("    mov edx, DWORD PTR [esp + 16]");
+                emit("    int 0x80");
+                emit("    pop ebx");
+                emit("    ret");
+            }
+            Target::Amd64 => {
+                // Arguments are already in rdi, rsi, rdx
+                emit(&format!("    mov eax, {}", number));
+                emit("    syscall");
+                emit("    ret");
+            }
+            Target::Arm64 => {
+                // Arguments are already in x0-x2
+                emit(&format!("    mov x8, #{}", number));
+                emit("    svc #0");
+                emit("    ret");
+            }
+        }
+    }
+
+    // exit and _exit are the same without stdio buffers to flush
+    emit("");
+    emit(".globl exit");
+    emit(".globl _exit");
+    emit("exit:");
+    emit("_exit:");
+    match target {
+        Target::I386 => {
+            emit("    mov ebx, DWORD PTR [esp + 4]");
+            emit("    mov eax, 1");
+            emit("    int 0x80");
+        }
+        Target::Amd64 => {
+            emit("    mov eax, 60");
+            emit("    syscall");
+        }
+        Target::Arm64 => {
+            emit("    mov x8, #93");
+            emit("    svc #0");
+        }
+    }
+
+    // The canary reference for -fstack-protector. Without a loader to hand
+    // over random bytes it is a terminator canary: the zero, newline and
+    // 0xff bytes end the string copies that usually overflow a buffer
+    emit("");
+    emit(".section .data");
+    emit(".globl __stack_chk_guard");
+    match target {
+        Target::I386 => {
+            emit(".balign 4");
+            emit("__stack_chk_guard:");
+            emit("    .long 0xff0a0d00");
+        }
+        Target::Amd64 | Target::Arm64 => {
+            emit(".balign 8");
+            emit("__stack_chk_guard:");
+            emit("    .quad 0xff0a0d00ff0a0d00");
+        }
+    }
+    emit("");
+    emit(".section .rodata");
+    emit(".Lstack_smashed:");
+    emit(&format!("    .ascii \"{}\\n\"", STACK_SMASHED));
+
+    // Called when a canary was overwritten: report it and abort like libc,
+    // with SIGABRT sent to the process itself
+    let length = STACK_SMASHED.len() + 1;
+    emit("");
+    emit(".section .text");
+    emit(".globl __stack_chk_fail");
+    emit("__stack_chk_fail:");
+    match target {
+        Target::I386 => {
+            emit("    mov ebx, 2");
+            emit("    lea ecx, [.Lstack_smashed]");
+            emit(&format!("    mov edx, {}", length));
+            emit("    mov eax, 4");
+            emit("    int 0x80");
+            emit("    mov eax, 20");
+            emit("    int 0x80");
+            emit("    mov ebx, eax");
+            emit("    mov ecx, 6");
+            emit("    mov eax, 37");
+            emit("    int 0x80");
+            emit("    mov ebx, 127");
+            emit("    mov eax, 1");
+            emit("    int 0x80");
+        }
+        Target::Amd64 => {
+            emit("    mov edi, 2");
+            emit("    lea rsi, [rip + .Lstack_smashed]");
+            emit(&format!("    mov edx, {}", length));
+            emit("    mov eax, 1");
+            emit("    syscall");
+            emit("    mov eax, 39");
+            emit("    syscall");
+            emit("    mov edi, eax");
+            emit("    mov esi, 6");
+            emit("    mov eax, 62");
+            emit("    syscall");
+            emit("    mov edi, 127");
+            emit("    mov eax, 60");
+            emit("    syscall");
+        }
+        Target::Arm64 => {
+            emit("    mov x0, #2");
+            emit("    adrp x1, .Lstack_smashed");
+            emit("    add x1, x1, :lo12:.Lstack_smashed");
+            emit(&format!("    mov x2, #{}", length));
+            emit("    mov x8, #64");
+            emit("    svc #0");
+            emit("    mov x8, #172");
+            emit("    svc #0");
+            emit("    mov x1, #6");
+            emit("    mov x8, #129");
+            emit("    svc #0");
+            emit("    mov x0, #127");
+            emit("    mov x8, #93");
+            emit("    svc #0");
+        }
+    }
+
+    asm
+}
+
+/// What `__stack_chk_fail` writes to stderr, as glibc words it
+const STACK_SMASHED: &str = "*** stack smashing detected ***: terminated";
+
+/// `__divdi3`, `__udivdi3`, `__moddi3` and `__umoddi3`, which i386 code calls
+/// to divide `long long` values. libgcc has them, but alecc doesn't link it,
+/// so each object that divides brings weak copies of its own. They share a
+/// restoring division that takes the dividend in edx:eax and the divisor in
+/// ecx:ebx, and leaves the quotient in edx:eax and the remainder in ecx:ebx
+pub const I386_DIVISION_HELPERS: &str = "\
+.weak __udivdi3
+.hidden __udivdi3
+__udivdi3:
+    push ebx
+    mov eax, DWORD PTR [esp + 8]
+    mov edx, DWORD PTR [esp + 12]
+    mov ebx, DWORD PTR [esp + 16]
+    mov ecx, DWORD PTR [esp + 20]
+    call .Ldi3_divide
+    pop ebx
+    ret
+
+.weak __umoddi3
+.hidden __umoddi3
+__umoddi3:
+    push ebx
+    mov eax, DWORD PTR [esp + 8]
+    mov edx, DWORD PTR [esp + 12]
+    mov ebx, DWORD PTR [esp + 16]
+    mov ecx, DWORD PTR [esp + 20]
+    call .Ldi3_divide
+    mov eax, ebx
+    mov edx, ecx
+    pop ebx
+    ret
+
+.weak __divdi3
+.hidden __divdi3
+__divdi3:
+    push ebx
+    push esi
+    mov eax, DWORD PTR [esp + 12]
+    mov edx, DWORD PTR [esp + 16]
+    mov ebx, DWORD PTR [esp + 20]
+    mov ecx, DWORD PTR [esp + 24]
+    mov esi, edx
+    xor esi, ecx
+    call .Ldi3_magnitudes
+    call .Ldi3_divide
+    test esi, esi
+    jns .Ldi3_quotient
+    neg eax
+    adc edx, 0
+    neg edx
+.Ldi3_quotient:
+    pop esi
+    pop ebx
+    ret
+
+.weak __moddi3
+.hidden __moddi3
+__moddi3:
+    push ebx
+    push esi
+    mov eax, DWORD PTR [esp + 12]
+    mov edx, DWORD PTR [esp + 16]
+    mov ebx, DWORD PTR [esp + 20]
+    mov ecx, DWORD PTR [esp + 24]
+    mov esi, edx
+    call .Ldi3_magnitudes
+    call .Ldi3_divide
+    mov eax, ebx
+    mov edx, ecx
+    test esi, esi
+    jns .Ldi3_remainder
+    neg eax
+    adc edx, 0
+    neg edx
+.Ldi3_remainder:
+    pop esi
+    pop ebx
+    ret
+
+.Ldi3_magnitudes:
+    test edx, edx
+    jns .Ldi3_dividend
+    neg eax
+    adc edx, 0
+    neg edx
+.Ldi3_dividend:
+    test ecx, ecx
+    jns .Ldi3_divisor
+    neg ebx
+    adc ecx, 0
+    neg ecx
+.Ldi3_divisor:
+    ret
+
+.Ldi3_divide:
+    push esi
+    push edi
+    push ebp
+    xor esi, esi
+    xor edi, edi
+    mov ebp, 64
+.Ldi3_bit:
+    shl eax, 1
+    rcl edx, 1
+    rcl edi, 1
+    rcl esi, 1
+    jc .Ldi3_subtract
+    cmp esi, ecx
+    jb .Ldi3_next
+    ja .Ldi3_subtract
+    cmp edi, ebx
+    jb .Ldi3_next
+.Ldi3_subtract:
+    sub edi, ebx
+    sbb esi, ecx
+    inc eax
+.Ldi3_next:
+    dec ebp
+    jnz .Ldi3_bit
+    mov ebx, edi
+    mov ecx, esi
+    pop ebp
+    pop edi
+    pop esi
+    ret
+";
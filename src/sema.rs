@@ -0,0 +1,1410 @@
+use crate::builtins::{atomic, signature, BUILTINS};
+use crate::constant::integer_constant;
+use crate::error::{AleccError, Result};
+use crate::language::LanguageOptions;
+use crate::lexer::Span;
+use crate::parser::{
+    is_null_pointer_constant, AssignmentOperator, BinaryOperator, Expression, ExpressionKind,
+    Function, GlobalVariable, Program, Qualifiers, Statement, StatementKind, Type, TypeWarning,
+    UnaryOperator,
+};
+use std::collections::{HashMap, HashSet};
+
+/// What an ordinary identifier refers to
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum Symbol {
+    Variable(Type),
+    /// A function parameter; it lives in the scope of the function body
+    Parameter(Type),
+    /// A function's signature; without a prototype (`int f()` before C23)
+    /// calls can't be checked against its parameters
+    Function {
+        ty: Type,
+        has_prototype: bool,
+    },
+    Typedef(Type),
+    /// An enumeration constant, with its value
+    Enumerator(i64),
+}
+
+impl Symbol {
+    /// The kind of entity, for "redeclared as different kind of symbol"
+    fn same_kind(&self, other: &Symbol) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// The declarations of one scope. Struct, union and enum tags have a
+/// namespace of their own.
+#[derive(Debug, Default)]
+struct Scope {
+    symbols: HashMap<String, Symbol>,
+    tags: HashMap<String, Type>,
+}
+
+/// Nested symbol tables, file scope first and the innermost block last
+#[derive(Debug)]
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![Scope::default()],
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    /// Declare `name` in the innermost scope, returning what it replaced there
+    pub fn declare(&mut self, name: &str, symbol: Symbol) -> Option<Symbol> {
+        self.innermost().symbols.insert(name.to_string(), symbol)
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.symbols.get(name))
+    }
+
+    /// The declaration of `name` in the innermost scope only
+    pub fn lookup_local(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.last()?.symbols.get(name)
+    }
+
+    pub fn declare_tag(&mut self, tag: &str, ty: Type) {
+        self.innermost().tags.insert(tag.to_string(), ty);
+    }
+
+    pub fn lookup_tag(&self, tag: &str) -> Option<&Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.tags.get(tag))
+    }
+
+    fn innermost(&mut self) -> &mut Scope {
+        self.scopes
+            .last_mut()
+            .expect("the file scope is never popped")
+    }
+}
+
+/// A local variable or parameter, tracked until its scope ends for -Wunused-*
+#[derive(Debug)]
+struct Local {
+    name: String,
+    span: Span,
+    parameter: bool,
+    /// How many scopes were open where it was declared
+    depth: usize,
+    used: bool,
+}
+
+/// Semantic analysis, between parsing and optimization. It builds the symbol
+/// tables, rejects conflicting declarations in a scope, and replaces each
+/// enumeration constant with its value so later passes only see integers.
+#[derive(Debug, Default)]
+pub struct Sema {
+    symbols: SymbolTable,
+    warnings: Vec<TypeWarning>,
+    locals: Vec<Local>,
+    /// Names used that aren't locals: functions and globals
+    referenced: HashSet<String>,
+    /// Functions already warned about as implicitly declared
+    implicit: HashSet<String>,
+    /// Where the program's global variables are declared, for `-Wshadow` notes
+    global_locations: HashMap<String, Span>,
+    /// Return type of the function being analyzed
+    return_type: Option<Type>,
+    /// Labels the function being analyzed defines, and those its `goto`s and
+    /// `&&label`s name, with where
+    labels: HashSet<String>,
+    label_uses: Vec<(String, Span)>,
+    language: LanguageOptions,
+}
+
+impl Sema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_language(mut self, language: LanguageOptions) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Warnings found along the way, reported like the parser's
+    pub fn warnings(&self) -> &[TypeWarning] {
+        &self.warnings
+    }
+
+    /// The file-scope declarations once `analyze` has run
+    #[allow(dead_code)]
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn analyze(&mut self, program: &mut Program) -> Result<()> {
+        self.global_locations = program
+            .global_variables
+            .iter()
+            .map(|global| (global.name.clone(), global.location))
+            .collect();
+        // The AST keeps typedefs, globals and functions apart, so file scope is
+        // declared up front and every body sees all of it
+        let mut typedefs: Vec<_> = program.type_definitions.iter().collect();
+        typedefs.sort_by_key(|(name, _)| name.as_str());
+        for (name, ty) in typedefs {
+            self.declare_types(ty, None)?;
+            self.declare(name, Symbol::Typedef(ty.clone()), None)?;
+        }
+
+        let mut initialized = Vec::new();
+        for GlobalVariable {
+            name,
+            var_type: ty,
+            initializer,
+            ..
+        } in &program.global_variables
+        {
+            self.declare_types(ty, None)?;
+            if initializer.is_some() {
+                if initialized.contains(&name) {
+                    return Err(redefinition(name, None));
+                }
+                initialized.push(name);
+            }
+            // Repeated file-scope declarations are tentative definitions
+            if let Some(previous) = self.symbols.lookup_local(name) {
+                if !matches!(previous, Symbol::Variable(_)) {
+                    return Err(different_kind(name, None));
+                }
+            }
+            self.symbols.declare(name, Symbol::Variable(ty.clone()));
+        }
+
+        for function in &program.functions {
+            self.declare_types(&function.return_type, None)?;
+            let ty = Type::Function {
+                return_type: Box::new(function.return_type.clone()),
+                parameters: function
+                    .parameters
+                    .iter()
+                    .map(|(_, ty)| ty.clone())
+                    .collect(),
+                variadic: function.is_variadic,
+            };
+            // Prototypes and the definition all declare the same function; a
+            // later `int f()` doesn't take away what an earlier prototype said
+            match self.symbols.lookup_local(&function.name) {
+                Some(Symbol::Function {
+                    has_prototype: true,
+                    ..
+                }) if !function.has_prototype => continue,
+                Some(Symbol::Function { .. }) | None => {}
+                Some(_) => return Err(different_kind(&function.name, Some(function.body.span))),
+            }
+            self.symbols.declare(
+                &function.name,
+                Symbol::Function {
+                    ty,
+                    has_prototype: function.has_prototype,
+                },
+            );
+        }
+        // GCC's builtins are declared without any header
+        for &name in BUILTINS {
+            if self.symbols.lookup_local(name).is_none() {
+                let ty = signature(name).expect("every builtin has a signature");
+                self.symbols.declare(
+                    name,
+                    Symbol::Function {
+                        ty,
+                        has_prototype: true,
+                    },
+                );
+            }
+        }
+
+        for GlobalVariable {
+            var_type: ty,
+            initializer,
+            ..
+        } in &mut program.global_variables
+        {
+            if let Some(initializer) = initializer {
+                self.expression(initializer)?;
+                self.check_discarded_qualifiers("initialization", ty, initializer);
+            }
+        }
+
+        for function in &mut program.functions {
+            if function.name == "main" {
+                self.check_main(function)?;
+            }
+            self.function(function)?;
+        }
+        self.warn_unused_functions(program);
+        Ok(())
+    }
+
+    /// `-Wunused-function` for static functions that are defined and never
+    /// named. Inline functions, and ones the runtime calls, are exempt.
+    fn warn_unused_functions(&mut self, program: &Program) {
+        let is_static: HashSet<&str> = program
+            .functions
+            .iter()
+            .filter(|f| f.is_static)
+            .map(|f| f.name.as_str())
+            .collect();
+        for function in &program.functions {
+            let defined =
+                !matches!(&function.body.kind, StatementKind::Block(body) if body.is_empty());
+            let exempt = function.is_inline
+                || function.attributes.unused
+                || function.attributes.constructor
+                || function.attributes.destructor;
+            if defined
+                && !exempt
+                && is_static.contains(function.name.as_str())
+                && !self.referenced.contains(&function.name)
+            {
+                let message = format!(
+                    "'{}' defined but not used [-Wunused-function]",
+                    function.name
+                );
+                self.warn("unused-function", message, function.body.span);
+            }
+        }
+    }
+
+    /// `main` takes no parameters, `(int, char **)` or `(int, char **, char **)`,
+    /// which is what `_start` passes; a return type other than `int` only warns
+    fn check_main(&mut self, function: &Function) -> Result<()> {
+        let span = function.body.span;
+        if !matches!(self.resolve(&function.return_type), Type::Int) {
+            self.warn(
+                "main",
+                "return type of 'main' is not 'int' [-Wmain]".to_string(),
+                span,
+            );
+        }
+        let error = |message: &str| {
+            Err(AleccError::SemanticError {
+                message: message.to_string(),
+                span: Some(span),
+            })
+        };
+        if function.is_variadic || !matches!(function.parameters.len(), 0 | 2 | 3) {
+            return error("'main' takes only zero, two or three arguments");
+        }
+        let is_strings = |ty: &Type| match self.resolve(ty) {
+            Type::Pointer(element) | Type::Array(element, _) => matches!(
+                self.resolve(&element),
+                Type::Pointer(pointee) if matches!(self.resolve(&pointee), Type::Char)
+            ),
+            _ => false,
+        };
+        let mut parameters = function.parameters.iter().map(|(_, ty)| ty);
+        if let Some(argc) = parameters.next() {
+            if !matches!(self.resolve(argc), Type::Int) {
+                return error("first argument of 'main' should be 'int'");
+            }
+        }
+        if parameters.next().is_some_and(|argv| !is_strings(argv)) {
+            return error("second argument of 'main' should be 'char **'");
+        }
+        if parameters.next().is_some_and(|envp| !is_strings(envp)) {
+            return error("third argument of 'main' should be 'char **'");
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, function: &mut Function) -> Result<()> {
+        let span = function.body.span;
+        let prototype =
+            matches!(&function.body.kind, StatementKind::Block(body) if body.is_empty());
+        self.return_type = Some(function.return_type.clone());
+        self.labels.clear();
+        self.label_uses.clear();
+        self.push_scope();
+        for (name, ty) in &function.parameters {
+            // Prototypes may leave parameters unnamed
+            if !name.is_empty() {
+                self.declare(name, Symbol::Parameter(ty.clone()), Some(span))?;
+                if !prototype {
+                    let unused = function.unused_parameters.contains(name);
+                    self.track(name, span, true, unused);
+                }
+            }
+        }
+
+        // The outermost block shares the parameters' scope
+        let result = match &mut function.body.kind {
+            StatementKind::Block(statements) => statements
+                .iter_mut()
+                .try_for_each(|statement| self.statement(statement)),
+            _ => self.statement(&mut function.body),
+        };
+        self.pop_scope();
+        result?;
+
+        // Labels have function scope, so a goto may come before its label
+        match self
+            .label_uses
+            .iter()
+            .find(|(label, _)| !self.labels.contains(label))
+        {
+            Some((label, span)) => Err(AleccError::SemanticError {
+                message: format!("label '{}' used but not defined", label),
+                span: Some(*span),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Statement) -> Result<()> {
+        let span = statement.span;
+        match &mut statement.kind {
+            StatementKind::Expression(expr) => self.expression(expr)?,
+            StatementKind::Declaration {
+                name,
+                var_type,
+                initializer,
+                unused,
+                ..
+            } => {
+                self.declare_types(var_type, Some(span))?;
+                self.check_shadowing(name, span);
+                // A variable is in scope from its declarator on, so in its own initializer too
+                self.declare(name, Symbol::Variable(var_type.clone()), Some(span))?;
+                self.track(name, span, false, *unused);
+                if let Some(initializer) = initializer {
+                    self.expression(initializer)?;
+                    self.check_discarded_qualifiers("initialization", var_type, initializer);
+                }
+            }
+            StatementKind::Block(statements) => {
+                self.push_scope();
+                let result = statements
+                    .iter_mut()
+                    .try_for_each(|statement| self.statement(statement));
+                self.pop_scope();
+                result?;
+            }
+            StatementKind::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                self.expression(condition)?;
+                self.statement(then_stmt)?;
+                if let Some(else_stmt) = else_stmt {
+                    self.statement(else_stmt)?;
+                }
+            }
+            StatementKind::While { condition, body }
+            | StatementKind::DoWhile { body, condition } => {
+                self.expression(condition)?;
+                self.statement(body)?;
+            }
+            StatementKind::For {
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                self.push_scope();
+                let result = (|| {
+                    if let Some(init) = init {
+                        self.statement(init)?;
+                    }
+                    for expr in condition.iter_mut().chain(increment.iter_mut()) {
+                        self.expression(expr)?;
+                    }
+                    self.statement(body)
+                })();
+                self.pop_scope();
+                result?;
+            }
+            StatementKind::Switch { expression, cases } => {
+                self.expression(expression)?;
+                // All the cases are one block
+                self.push_scope();
+                let result = cases.iter_mut().try_for_each(|(value, statements)| {
+                    if let Some(value) = value {
+                        self.expression(value)?;
+                    }
+                    statements
+                        .iter_mut()
+                        .try_for_each(|statement| self.statement(statement))
+                });
+                self.pop_scope();
+                result?;
+                self.check_switch(expression, cases, span);
+            }
+            StatementKind::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr)?;
+                    if let Some(return_type) = self.return_type.clone() {
+                        self.check_discarded_qualifiers("return", &return_type, expr);
+                    }
+                }
+            }
+            StatementKind::Goto(label) => self.label_uses.push((label.clone(), span)),
+            StatementKind::ComputedGoto(address) => self.expression(address)?,
+            StatementKind::Label(label) => {
+                if !self.labels.insert(label.clone()) {
+                    return Err(AleccError::SemanticError {
+                        message: format!("duplicate label '{}'", label),
+                        span: Some(span),
+                    });
+                }
+            }
+            StatementKind::Break | StatementKind::Continue | StatementKind::Fallthrough => {}
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &mut Expression) -> Result<()> {
+        let span = expr.span;
+        match &mut expr.kind {
+            ExpressionKind::Identifier(name) => match self.symbols.lookup(name) {
+                Some(Symbol::Enumerator(value)) => {
+                    expr.kind = ExpressionKind::IntegerLiteral(*value)
+                }
+                Some(_) => {
+                    let name = name.clone();
+                    self.mark_used(name);
+                }
+                None => {
+                    return Err(AleccError::SemanticError {
+                        message: format!("use of undeclared identifier '{}'", name),
+                        span: Some(span),
+                    })
+                }
+            },
+            ExpressionKind::IntegerLiteral(_)
+            | ExpressionKind::FloatLiteral(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::CharLiteral(_)
+            | ExpressionKind::BooleanLiteral(_)
+            | ExpressionKind::Sizeof(_) => {}
+            ExpressionKind::LabelAddress(label) => self.label_uses.push((label.clone(), span)),
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                if matches!(operator, BinaryOperator::Divide | BinaryOperator::Modulo) {
+                    self.check_division(left, right, span);
+                }
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => self.address(operand)?,
+            ExpressionKind::Unary { operator, operand } => {
+                if matches!(operator, UnaryOperator::Dereference) {
+                    self.check_null_dereference(operand, span);
+                }
+                self.expression(operand)?;
+                match operator {
+                    UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => {
+                        self.check_writable(operand, "increment")?
+                    }
+                    UnaryOperator::PreDecrement | UnaryOperator::PostDecrement => {
+                        self.check_writable(operand, "decrement")?
+                    }
+                    _ => {}
+                }
+            }
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => {
+                let callee = match &function.kind {
+                    ExpressionKind::Identifier(name) => Some(name.clone()),
+                    _ => None,
+                };
+                // Calling an undeclared function declares it implicitly, as C89 did
+                match callee.as_deref() {
+                    Some(name) if self.symbols.lookup(name).is_none() => {
+                        self.implicit_declaration(name, span)?
+                    }
+                    _ => self.expression(function)?,
+                }
+                for argument in arguments.iter_mut() {
+                    self.expression(argument)?;
+                }
+                if let Some(name) = callee {
+                    self.check_call(&name, arguments, span)?;
+                }
+            }
+            ExpressionKind::Member {
+                object, is_arrow, ..
+            } => {
+                if *is_arrow {
+                    self.check_null_dereference(object, span);
+                }
+                self.expression(object)?
+            }
+            ExpressionKind::Index { array, index } => {
+                self.check_null_dereference(array, span);
+                self.expression(array)?;
+                self.expression(index)?;
+            }
+            ExpressionKind::Cast { expression, .. } => self.expression(expression)?,
+            ExpressionKind::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                self.expression(target)?;
+                self.expression(value)?;
+                self.check_writable(target, "assignment")?;
+                if matches!(
+                    operator.binary_operator(),
+                    Some(BinaryOperator::Divide | BinaryOperator::Modulo)
+                ) {
+                    self.check_division(target, value, span);
+                }
+                if let (AssignmentOperator::Assign, Some(target_type)) =
+                    (operator, self.object_type(target))
+                {
+                    self.check_discarded_qualifiers("assignment", &target_type, value);
+                }
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expression(condition)?;
+                self.expression(then_expr)?;
+                self.expression(else_expr)?;
+            }
+            ExpressionKind::VaArg { list, .. } => self.expression(list)?,
+        }
+        Ok(())
+    }
+
+    /// The operand of `&`: member accesses and subscripts in it only compute an
+    /// address, so `&((struct s *)0)->member` reads nothing
+    fn address(&mut self, expr: &mut Expression) -> Result<()> {
+        match &mut expr.kind {
+            ExpressionKind::Member { object, .. } => self.address(object),
+            ExpressionKind::Index { array, index } => {
+                self.address(array)?;
+                self.expression(index)
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => self.expression(operand),
+            _ => self.expression(expr),
+        }
+    }
+
+    /// Reject storing to an object that is `const`, itself or as part of a
+    /// `const` struct or union; `what` is the kind of store
+    fn check_writable(&self, target: &Expression, what: &str) -> Result<()> {
+        let Some(ty) = self.object_type(target) else {
+            return Ok(());
+        };
+        let read_only = self.qualifiers(&ty).is_const;
+        let message = match &target.kind {
+            ExpressionKind::Identifier(name) if read_only => {
+                format!("{} of read-only variable '{}'", what, name)
+            }
+            ExpressionKind::Member { member, .. } if read_only => {
+                format!("{} of read-only member '{}'", what, member)
+            }
+            ExpressionKind::Member { member, .. } if self.in_read_only_object(target) => {
+                format!("{} of member '{}' in read-only object", what, member)
+            }
+            _ if read_only => format!("{} of read-only location", what),
+            _ => return Ok(()),
+        };
+        Err(AleccError::SemanticError {
+            message,
+            span: Some(target.span),
+        })
+    }
+
+    /// Whether a member access reaches into a `const` struct or union
+    fn in_read_only_object(&self, expr: &Expression) -> bool {
+        let ExpressionKind::Member {
+            object, is_arrow, ..
+        } = &expr.kind
+        else {
+            return false;
+        };
+        let aggregate = if *is_arrow {
+            match self.expression_type(object).map(|ty| self.resolve(&ty)) {
+                Some(Type::Pointer(pointee)) => Some(*pointee),
+                _ => None,
+            }
+        } else {
+            self.object_type(object)
+        };
+        aggregate.is_some_and(|ty| self.qualifiers(&ty).is_const)
+            || (!*is_arrow && self.in_read_only_object(object))
+    }
+
+    /// `-Wdiscarded-qualifiers` when a pointer converts implicitly to one whose
+    /// pointee lacks a qualifier the value's has; `context` says where
+    fn check_discarded_qualifiers(&mut self, context: &str, target: &Type, value: &Expression) {
+        let Some(actual) = self.expression_type(value) else {
+            return;
+        };
+        let (Type::Pointer(to), Type::Pointer(from)) =
+            (self.resolve(target), self.resolve(&actual))
+        else {
+            return;
+        };
+        let (to, from) = (self.qualifiers(&to), self.qualifiers(&from));
+        let discarded: Vec<&str> = [
+            (from.is_const && !to.is_const, "const"),
+            (from.is_volatile && !to.is_volatile, "volatile"),
+        ]
+        .into_iter()
+        .filter(|(discarded, _)| *discarded)
+        .map(|(_, name)| name)
+        .collect();
+        if discarded.is_empty() {
+            return;
+        }
+        self.warn(
+            "discarded-qualifiers",
+            format!(
+                "{} discards '{}' qualifier from pointer target type [-Wdiscarded-qualifiers]",
+                context,
+                discarded.join(" ")
+            ),
+            value.span,
+        );
+    }
+
+    /// `-Wdiv-by-zero` for an integer division or remainder by a constant zero,
+    /// which traps at run time
+    /// `-Wswitch-default` for a switch without `default`, and for a switch on
+    /// an enumeration, enumerators without a case (`-Wswitch`, or
+    /// `-Wswitch-enum` when there is a `default`) and case values no
+    /// enumerator has (`-Wswitch`)
+    fn check_switch(
+        &mut self,
+        expression: &Expression,
+        cases: &[(Option<Expression>, Vec<Statement>)],
+        span: Span,
+    ) {
+        let has_default = cases.iter().any(|(value, _)| value.is_none());
+        if !has_default {
+            self.warn(
+                "switch-default",
+                "switch missing default case [-Wswitch-default]".to_string(),
+                span,
+            );
+        }
+        let Some(ty) = self.expression_type(expression) else {
+            return;
+        };
+        let Type::Enum { name, variants } = self.complete(self.resolve(&ty)) else {
+            return;
+        };
+        let values: Vec<(i64, Span)> = cases
+            .iter()
+            .filter_map(|(value, _)| value.as_ref())
+            .filter_map(|value| integer_constant(value).map(|constant| (constant, value.span)))
+            .collect();
+        let option = if has_default { "switch-enum" } else { "switch" };
+        for (variant, value) in &variants {
+            if !values.iter().any(|(handled, _)| handled == value) {
+                self.warn(
+                    option,
+                    format!(
+                        "enumeration value '{}' not handled in switch [-W{}]",
+                        variant, option
+                    ),
+                    span,
+                );
+            }
+        }
+        for (value, at) in &values {
+            if !variants.iter().any(|(_, variant)| variant == value) {
+                self.warn(
+                    "switch",
+                    format!(
+                        "case value '{}' not in enumerated type 'enum {}' [-Wswitch]",
+                        value, name
+                    ),
+                    *at,
+                );
+            }
+        }
+    }
+
+    fn check_division(&mut self, dividend: &Expression, divisor: &Expression, span: Span) {
+        let floating = self
+            .expression_type(dividend)
+            .is_some_and(|ty| matches!(self.resolve(&ty), Type::Float | Type::Double));
+        if !floating && integer_constant(divisor) == Some(0) {
+            self.warn(
+                "div-by-zero",
+                "division by zero [-Wdiv-by-zero]".to_string(),
+                span,
+            );
+        }
+    }
+
+    /// `-Wnull-dereference` for `*`, `->` or `[]` applied to a null pointer
+    /// constant of pointer type; `0[array]` indexes the array
+    fn check_null_dereference(&mut self, pointer: &Expression, span: Span) {
+        let is_pointer = self
+            .expression_type(pointer)
+            .is_some_and(|ty| matches!(self.resolve(&ty), Type::Pointer(_)));
+        if is_pointer && self.is_null_pointer(pointer) {
+            self.warn(
+                "null-dereference",
+                "null pointer dereference [-Wnull-dereference]".to_string(),
+                span,
+            );
+        }
+    }
+
+    /// A null pointer constant, or one cast to another pointer type like `(int *)0`
+    fn is_null_pointer(&self, expr: &Expression) -> bool {
+        match &expr.kind {
+            ExpressionKind::Cast {
+                target_type,
+                expression,
+            } if matches!(self.resolve(target_type), Type::Pointer(_)) => {
+                self.is_null_pointer(expression)
+            }
+            _ => is_null_pointer_constant(expr),
+        }
+    }
+
+    /// Check a call to `name` against its prototype: the number of arguments,
+    /// and arguments that can't be converted to their parameter's type
+    fn check_call(&mut self, name: &str, arguments: &[Expression], span: Span) -> Result<()> {
+        let Some(Symbol::Function {
+            ty:
+                Type::Function {
+                    parameters,
+                    variadic,
+                    ..
+                },
+            has_prototype,
+        }) = self.symbols.lookup(name)
+        else {
+            // Implicitly declared, or called through a pointer
+            return Ok(());
+        };
+        let (parameters, variadic) = (parameters.clone(), *variadic);
+
+        if !has_prototype {
+            if !arguments.is_empty() {
+                self.warn(
+                    "deprecated-non-prototype",
+                    format!(
+                        "passing arguments to '{}' without a prototype is deprecated in all versions of C and is not supported in C23 [-Wdeprecated-non-prototype]",
+                        name
+                    ),
+                    span,
+                );
+            }
+            return Ok(());
+        }
+
+        let (expected, have) = (parameters.len(), arguments.len());
+        if have < expected || (have > expected && !variadic) {
+            return Err(AleccError::SemanticError {
+                message: format!(
+                    "too {} arguments to function '{}'; expected {}, have {}",
+                    if have < expected { "few" } else { "many" },
+                    name,
+                    expected,
+                    have
+                ),
+                span: Some(span),
+            });
+        }
+
+        for (position, (argument, parameter)) in arguments.iter().zip(&parameters).enumerate() {
+            self.check_argument(name, position + 1, parameter, argument)?;
+        }
+        Ok(())
+    }
+
+    /// Reject an argument whose type doesn't convert to its parameter's by
+    /// assignment; a pointer to the wrong type is only a warning, as in gcc
+    fn check_argument(
+        &mut self,
+        name: &str,
+        position: usize,
+        parameter: &Type,
+        argument: &Expression,
+    ) -> Result<()> {
+        let Some(actual) = self.expression_type(argument) else {
+            return Ok(());
+        };
+        let (expected, actual) = (self.resolve(parameter), self.resolve(&actual));
+        let error = |message: String| {
+            Err(AleccError::SemanticError {
+                message,
+                span: Some(argument.span),
+            })
+        };
+        let incompatible = || {
+            error(format!(
+                "incompatible type for argument {} of '{}'",
+                position, name
+            ))
+        };
+
+        self.check_discarded_qualifiers(
+            &format!("passing argument {} of '{}'", position, name),
+            parameter,
+            argument,
+        );
+        match (&expected, &actual) {
+            (Type::Pointer(to), Type::Pointer(from)) if !self.compatible_pointees(to, from) => {
+                self.warn(
+                    "incompatible-pointer-types",
+                    format!(
+                        "passing argument {} of '{}' from incompatible pointer type [-Wincompatible-pointer-types]",
+                        position, name
+                    ),
+                    argument.span,
+                );
+            }
+            (Type::Pointer(_), Type::Pointer(_)) => {}
+            (Type::Pointer(_), from) if is_integer(from) && !is_null_pointer_constant(argument) => {
+                return error(format!(
+                    "passing argument {} of '{}' makes pointer from integer without a cast",
+                    position, name
+                ));
+            }
+            // Any pointer converts to _Bool
+            (Type::Bool, Type::Pointer(_)) => {}
+            (to, Type::Pointer(_)) if is_integer(to) => {
+                return error(format!(
+                    "passing argument {} of '{}' makes integer from pointer without a cast",
+                    position, name
+                ));
+            }
+            (Type::Pointer(_), Type::Float | Type::Double)
+            | (Type::Float | Type::Double, Type::Pointer(_)) => return incompatible(),
+            (Type::Struct { name: a, .. }, Type::Struct { name: b, .. })
+            | (Type::Union { name: a, .. }, Type::Union { name: b, .. })
+                if a != b =>
+            {
+                return incompatible()
+            }
+            (Type::Struct { .. }, Type::Struct { .. })
+            | (Type::Union { .. }, Type::Union { .. }) => {}
+            (Type::Struct { .. } | Type::Union { .. }, _)
+            | (_, Type::Struct { .. } | Type::Union { .. }) => return incompatible(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether pointers to `a` and `b` convert to each other without a cast
+    fn compatible_pointees(&self, a: &Type, b: &Type) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            // Only `void *` itself converts to and from any object pointer
+            (Type::Void, _) | (_, Type::Void) => true,
+            _ => self.same_object_type(a, b),
+        }
+    }
+
+    /// Whether `a` and `b` are compatible types, ignoring qualifiers
+    fn same_object_type(&self, a: &Type, b: &Type) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Type::Pointer(a), Type::Pointer(b)) | (Type::Array(a, _), Type::Array(b, _)) => {
+                self.same_object_type(&a, &b)
+            }
+            (Type::Struct { name: a, .. }, Type::Struct { name: b, .. })
+            | (Type::Union { name: a, .. }, Type::Union { name: b, .. }) => a == b,
+            // An enum is compatible with the integer type holding its values
+            (Type::Enum { .. }, Type::Int | Type::Enum { .. }) | (Type::Int, Type::Enum { .. }) => {
+                true
+            }
+            (a, b) => std::mem::discriminant(&a) == std::mem::discriminant(&b),
+        }
+    }
+
+    /// Type of an expression after arrays and functions decay to pointers, or
+    /// `None` where sema can't tell
+    fn expression_type(&self, expr: &Expression) -> Option<Type> {
+        let ty = match &expr.kind {
+            ExpressionKind::IntegerLiteral(value) => {
+                if i32::try_from(*value).is_ok() {
+                    Type::Int
+                } else {
+                    Type::Long
+                }
+            }
+            ExpressionKind::FloatLiteral(_) => Type::Double,
+            ExpressionKind::CharLiteral(_) | ExpressionKind::BooleanLiteral(_) => Type::Int,
+            ExpressionKind::StringLiteral(_) => Type::Pointer(Box::new(Type::Char)),
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Index { .. }
+            | ExpressionKind::Member { .. } => self.object_type(expr)?,
+            ExpressionKind::Unary { operator, operand } => match operator {
+                UnaryOperator::LogicalNot => Type::Int,
+                UnaryOperator::AddressOf => Type::Pointer(Box::new(self.object_type(operand)?)),
+                UnaryOperator::Dereference => self.object_type(expr)?,
+                _ => self.expression_type(operand)?,
+            },
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => match operator {
+                BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::Greater
+                | BinaryOperator::LessEqual
+                | BinaryOperator::GreaterEqual
+                | BinaryOperator::LogicalAnd
+                | BinaryOperator::LogicalOr => Type::Int,
+                BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                    self.expression_type(left)?
+                }
+                _ => {
+                    let (l, r) = (self.expression_type(left)?, self.expression_type(right)?);
+                    match (self.resolve(&l), self.resolve(&r)) {
+                        (Type::Pointer(_), Type::Pointer(_)) => Type::Long,
+                        (Type::Pointer(_), _) => l,
+                        (_, Type::Pointer(_)) => r,
+                        (Type::Double, _) | (_, Type::Double) => Type::Double,
+                        (Type::Float, _) | (_, Type::Float) => Type::Float,
+                        (Type::LongLong, _) | (_, Type::LongLong) => Type::LongLong,
+                        (Type::Long, _) | (_, Type::Long) => Type::Long,
+                        _ => Type::Int,
+                    }
+                }
+            },
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => match self.expression_type(function) {
+                Some(Type::Pointer(inner)) => match self.resolve(&inner) {
+                    Type::Function { return_type, .. } => *return_type,
+                    _ => return None,
+                },
+                None => match &function.kind {
+                    // The atomic builtins take their type from their operand
+                    ExpressionKind::Identifier(name) if atomic(name).is_some() => {
+                        let operand = arguments
+                            .first()
+                            .and_then(|pointer| self.expression_type(pointer))
+                            .and_then(|ty| match self.resolve(&ty) {
+                                Type::Pointer(inner) => Some(*inner),
+                                _ => None,
+                            });
+                        atomic(name)?.result_type(operand)?
+                    }
+                    // Implicitly declared functions return int
+                    ExpressionKind::Identifier(_) => Type::Int,
+                    _ => return None,
+                },
+                _ => return None,
+            },
+            ExpressionKind::Cast { target_type, .. } => target_type.clone(),
+            ExpressionKind::Sizeof(_) => Type::Long,
+            ExpressionKind::LabelAddress(_) => Type::Pointer(Box::new(Type::Void)),
+            ExpressionKind::Assignment { target, .. } => self.expression_type(target)?,
+            ExpressionKind::Conditional { then_expr, .. } => self.expression_type(then_expr)?,
+            ExpressionKind::VaArg { arg_type, .. } => arg_type.clone(),
+        };
+
+        Some(match self.resolve(&ty) {
+            Type::Array(element, _) => Type::Pointer(element),
+            function @ Type::Function { .. } => Type::Pointer(Box::new(function)),
+            _ => ty,
+        })
+    }
+
+    /// Declared type of the object an lvalue designates, before decay
+    fn object_type(&self, expr: &Expression) -> Option<Type> {
+        match &expr.kind {
+            ExpressionKind::Identifier(name) => match self.symbols.lookup(name)? {
+                Symbol::Variable(ty) | Symbol::Parameter(ty) | Symbol::Function { ty, .. } => {
+                    Some(ty.clone())
+                }
+                Symbol::Enumerator(_) => Some(Type::Int),
+                Symbol::Typedef(_) => None,
+            },
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Dereference,
+                operand,
+            } => match self.resolve(&self.expression_type(operand)?) {
+                Type::Pointer(inner) => Some(*inner),
+                _ => None,
+            },
+            ExpressionKind::Index { array, index } => {
+                match (
+                    self.resolve(&self.expression_type(array)?),
+                    self.resolve(&self.expression_type(index)?),
+                ) {
+                    (Type::Pointer(element), _) | (_, Type::Pointer(element)) => Some(*element),
+                    _ => None,
+                }
+            }
+            ExpressionKind::Member {
+                object,
+                member,
+                is_arrow,
+            } => {
+                let aggregate = if *is_arrow {
+                    match self.resolve(&self.expression_type(object)?) {
+                        Type::Pointer(inner) => *inner,
+                        _ => return None,
+                    }
+                } else {
+                    self.object_type(object)
+                        .or_else(|| self.expression_type(object))?
+                };
+                match self.complete(self.resolve(&aggregate)) {
+                    Type::Struct { fields, .. } | Type::Union { fields, .. } => fields
+                        .into_iter()
+                        .find(|(name, _)| name == member)
+                        .map(|(_, ty)| ty),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Strip typedefs and top-level qualifiers
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Typedef(name, aliased) => match self.symbols.lookup(name) {
+                Some(Symbol::Typedef(ty)) => self.resolve(ty),
+                _ => self.resolve(aliased),
+            },
+            Type::Qualified(_, inner) => self.resolve(inner),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Top-level qualifiers, including those a typedef name stands for
+    fn qualifiers(&self, ty: &Type) -> Qualifiers {
+        match ty {
+            Type::Typedef(name, aliased) => match self.symbols.lookup(name) {
+                Some(Symbol::Typedef(ty)) => self.qualifiers(ty),
+                _ => self.qualifiers(aliased),
+            },
+            Type::Qualified(qualifiers, inner) => qualifiers.union(self.qualifiers(inner)),
+            _ => Qualifiers::default(),
+        }
+    }
+
+    /// The definition of a struct, union or enumeration only named by its tag where it's used
+    fn complete(&self, ty: Type) -> Type {
+        match &ty {
+            Type::Struct { name, fields, .. } | Type::Union { name, fields, .. }
+                if fields.is_empty() =>
+            {
+                self.symbols.lookup_tag(name).cloned().unwrap_or(ty)
+            }
+            Type::Enum { name, variants } if variants.is_empty() => {
+                self.symbols.lookup_tag(name).cloned().unwrap_or(ty)
+            }
+            _ => ty,
+        }
+    }
+
+    fn push_scope(&mut self) {
+        self.symbols.push_scope();
+    }
+
+    /// Close the innermost scope, warning about the locals in it that were never used
+    fn pop_scope(&mut self) {
+        let depth = self.symbols.scopes.len();
+        let first = self
+            .locals
+            .iter()
+            .position(|local| local.depth >= depth)
+            .unwrap_or(self.locals.len());
+        for local in self.locals.split_off(first) {
+            if local.used {
+                continue;
+            }
+            let (option, message) = if local.parameter {
+                (
+                    "unused-parameter",
+                    format!("unused parameter '{}' [-Wunused-parameter]", local.name),
+                )
+            } else {
+                (
+                    "unused-variable",
+                    format!("unused variable '{}' [-Wunused-variable]", local.name),
+                )
+            };
+            self.warn(option, message, local.span);
+        }
+        self.symbols.pop_scope();
+    }
+
+    /// `-Wshadow` for a block-scope variable named like a parameter, a local of
+    /// an enclosing block or a global variable, with a note where that one is
+    /// declared. A second declaration in the same scope is an error instead.
+    fn check_shadowing(&mut self, name: &str, span: Span) {
+        if self.symbols.lookup_local(name).is_some() {
+            return;
+        }
+        if !matches!(
+            self.symbols.lookup(name),
+            Some(Symbol::Variable(_) | Symbol::Parameter(_))
+        ) {
+            return;
+        }
+        let (what, declared) = match self.locals.iter().rev().find(|local| local.name == name) {
+            Some(local) if local.parameter => ("a parameter", Some(local.span)),
+            Some(local) => ("a previous local", Some(local.span)),
+            None => (
+                "a global declaration",
+                self.global_locations.get(name).copied(),
+            ),
+        };
+        self.warnings.push(TypeWarning {
+            option: "shadow",
+            message: format!("declaration of '{}' shadows {} [-Wshadow]", name, what),
+            line: span.line,
+            column: span.column,
+            note: declared.map(|declared| ("shadowed declaration is here".to_string(), declared)),
+        });
+    }
+
+    /// Start tracking a local; one marked `unused` counts as used from the start
+    fn track(&mut self, name: &str, span: Span, parameter: bool, unused: bool) {
+        self.locals.push(Local {
+            name: name.to_string(),
+            span,
+            parameter,
+            depth: self.symbols.scopes.len(),
+            used: unused,
+        });
+    }
+
+    /// Record a use of `name`, which is in scope: a local if one has the
+    /// name, since locals of closed scopes are gone
+    fn mark_used(&mut self, name: String) {
+        match self
+            .locals
+            .iter_mut()
+            .rev()
+            .find(|local| local.name == name)
+        {
+            Some(local) => local.used = true,
+            None => {
+                self.referenced.insert(name);
+            }
+        }
+    }
+
+    fn warn(&mut self, option: &'static str, message: String, span: Span) {
+        self.warnings.push(TypeWarning {
+            option,
+            message,
+            line: span.line,
+            column: span.column,
+            note: None,
+        });
+    }
+
+    /// A call to `name`, which nothing declares: an error since C99, but
+    /// GNU dialects and C89 only warn, once per function
+    fn implicit_declaration(&mut self, name: &str, span: Span) -> Result<()> {
+        // The backends expand the `<stdarg.h>` operations and the atomic
+        // builtins themselves
+        if matches!(
+            name.strip_prefix("__builtin_").unwrap_or(name),
+            "va_start" | "va_copy" | "va_end"
+        ) || atomic(name).is_some()
+        {
+            return Ok(());
+        }
+        let hint = match libc_header(name) {
+            Some(header) => format!(
+                "include '<{}>' or provide a declaration of '{}'",
+                header, name
+            ),
+            None => format!("provide a declaration of '{}'", name),
+        };
+        if !self.language.implicit_function_declarations() {
+            return Err(AleccError::SemanticError {
+                message: format!(
+                    "implicit declaration of function '{}' is invalid in {}; {}",
+                    name,
+                    self.language.name(),
+                    hint
+                ),
+                span: Some(span),
+            });
+        }
+        if self.implicit.insert(name.to_string()) {
+            self.warnings.push(TypeWarning {
+                option: "implicit-function-declaration",
+                message: format!(
+                    "implicit declaration of function '{}' [-Wimplicit-function-declaration]",
+                    name
+                ),
+                line: span.line,
+                column: span.column,
+                note: Some((hint, span)),
+            });
+        }
+        Ok(())
+    }
+
+    /// Declare a block-scope name, rejecting a second declaration in the same scope
+    fn declare(&mut self, name: &str, symbol: Symbol, span: Option<Span>) -> Result<()> {
+        if let Some(previous) = self.symbols.lookup_local(name) {
+            return Err(if previous.same_kind(&symbol) {
+                redefinition(name, span)
+            } else {
+                different_kind(name, span)
+            });
+        }
+        self.symbols.declare(name, symbol);
+        Ok(())
+    }
+
+    /// Declare the tags and enumeration constants a type defines in place
+    fn declare_types(&mut self, ty: &Type, span: Option<Span>) -> Result<()> {
+        match ty {
+            Type::Enum { name, variants } if !variants.is_empty() => {
+                self.symbols.declare_tag(name, ty.clone());
+                for (variant, value) in variants {
+                    match self.symbols.lookup_local(variant) {
+                        // The same definition seen again, e.g. through a second declarator
+                        Some(Symbol::Enumerator(previous)) if previous == value => continue,
+                        Some(_) => {
+                            return Err(AleccError::SemanticError {
+                                message: format!("redeclaration of enumerator '{}'", variant),
+                                span,
+                            })
+                        }
+                        None => {
+                            self.symbols.declare(variant, Symbol::Enumerator(*value));
+                        }
+                    }
+                }
+            }
+            Type::Struct { name, fields, .. } | Type::Union { name, fields, .. }
+                if !fields.is_empty() =>
+            {
+                self.symbols.declare_tag(name, ty.clone());
+                for (_, field) in fields {
+                    self.declare_types(field, span)?;
+                }
+            }
+            Type::Pointer(inner) | Type::Array(inner, _) | Type::Qualified(_, inner) => {
+                self.declare_types(inner, span)?;
+            }
+            Type::Function {
+                return_type,
+                parameters,
+                ..
+            } => {
+                self.declare_types(return_type, span)?;
+                for parameter in parameters {
+                    self.declare_types(parameter, span)?;
+                }
+            }
+            // A typedef's type was declared where the typedef was
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn is_integer(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::Char
+            | Type::Short
+            | Type::Int
+            | Type::Long
+            | Type::LongLong
+            | Type::UnsignedChar
+            | Type::UnsignedShort
+            | Type::UnsignedInt
+            | Type::UnsignedLong
+            | Type::UnsignedLongLong
+            | Type::Bool
+            | Type::Enum { .. }
+    )
+}
+
+/// The standard header that declares a well-known C library function
+fn libc_header(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "printf" | "fprintf" | "sprintf" | "snprintf" | "vprintf" | "vfprintf" | "vsprintf"
+        | "vsnprintf" | "scanf" | "fscanf" | "sscanf" | "puts" | "fputs" | "gets" | "fgets"
+        | "putchar" | "putc" | "fputc" | "getchar" | "getc" | "fgetc" | "ungetc" | "fopen"
+        | "freopen" | "fclose" | "fflush" | "fread" | "fwrite" | "fseek" | "ftell" | "rewind"
+        | "feof" | "ferror" | "perror" | "remove" | "rename" | "tmpfile" | "setvbuf" => "stdio.h",
+        "malloc" | "calloc" | "realloc" | "free" | "exit" | "abort" | "atexit" | "_Exit"
+        | "atoi" | "atol" | "atoll" | "atof" | "strtol" | "strtoul" | "strtoll" | "strtoull"
+        | "strtod" | "strtof" | "qsort" | "bsearch" | "abs" | "labs" | "llabs" | "div" | "rand"
+        | "srand" | "getenv" | "system" => "stdlib.h",
+        "strlen" | "strcpy" | "strncpy" | "strcat" | "strncat" | "strcmp" | "strncmp"
+        | "strchr" | "strrchr" | "strstr" | "strspn" | "strcspn" | "strpbrk" | "strtok"
+        | "strdup" | "strerror" | "memcpy" | "memmove" | "memset" | "memcmp" | "memchr" => {
+            "string.h"
+        }
+        "isalnum" | "isalpha" | "isblank" | "iscntrl" | "isdigit" | "isgraph" | "islower"
+        | "isprint" | "ispunct" | "isspace" | "isupper" | "isxdigit" | "tolower" | "toupper" => {
+            "ctype.h"
+        }
+        "sqrt" | "pow" | "exp" | "log" | "log2" | "log10" | "sin" | "cos" | "tan" | "asin"
+        | "acos" | "atan" | "atan2" | "fabs" | "floor" | "ceil" | "round" | "fmod" => "math.h",
+        "time" | "clock" | "difftime" | "mktime" | "localtime" | "gmtime" | "strftime" => "time.h",
+        "signal" | "raise" => "signal.h",
+        "setjmp" | "longjmp" => "setjmp.h",
+        _ => return None,
+    })
+}
+
+fn redefinition(name: &str, span: Option<Span>) -> AleccError {
+    AleccError::SemanticError {
+        message: format!("redefinition of '{}'", name),
+        span,
+    }
+}
+
+fn different_kind(name: &str, span: Option<Span>) -> AleccError {
+    AleccError::SemanticError {
+        message: format!("'{}' redeclared as different kind of symbol", name),
+        span,
+    }
+}
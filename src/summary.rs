@@ -0,0 +1,90 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// How many of the biggest functions a summary lists
+const BIGGEST_FUNCTIONS: usize = 5;
+
+/// What `--summary` reports about one translation unit
+#[derive(Debug, Clone, Default)]
+pub struct UnitSummary {
+    pub input: PathBuf,
+    /// Lines of the source file, before preprocessing
+    pub lines: usize,
+    /// Tokens the parser saw, not counting line breaks
+    pub tokens: usize,
+    /// Function definitions; prototypes aren't counted
+    pub functions: usize,
+    pub globals: usize,
+    /// Instructions generated for each function definition, in source order
+    pub instructions: Vec<(String, usize)>,
+}
+
+impl UnitSummary {
+    pub fn total_instructions(&self) -> usize {
+        self.instructions.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Functions by instruction count, biggest first; ties keep source order
+    pub fn biggest_functions(&self) -> Vec<(&str, usize)> {
+        let mut functions: Vec<_> = self
+            .instructions
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        functions.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        functions.truncate(BIGGEST_FUNCTIONS);
+        functions
+    }
+}
+
+impl fmt::Display for UnitSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.input.display())?;
+        writeln!(f, "  lines:        {}", self.lines)?;
+        writeln!(f, "  tokens:       {}", self.tokens)?;
+        writeln!(f, "  functions:    {}", self.functions)?;
+        writeln!(f, "  globals:      {}", self.globals)?;
+        write!(f, "  instructions: {}", self.total_instructions())?;
+
+        let biggest = self.biggest_functions();
+        if !biggest.is_empty() {
+            write!(f, "\n  biggest functions:")?;
+            let width = biggest
+                .iter()
+                .map(|(name, _)| name.len())
+                .max()
+                .unwrap_or(0);
+            for (name, count) in biggest {
+                write!(f, "\n    {:width$}  {}", name, count, width = width)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Instructions in each function defined in LLVM IR text
+pub fn llvm_instruction_counts(ir: &str) -> Vec<(String, usize)> {
+    let mut counts = Vec::new();
+    let mut current: Option<(String, usize)> = None;
+
+    for line in ir.lines() {
+        if let Some(rest) = line.strip_prefix("define ") {
+            let name = rest
+                .split_once('@')
+                .and_then(|(_, name)| name.split_once('('))
+                .map(|(name, _)| name.trim_matches('"').to_string())
+                .unwrap_or_default();
+            current = Some((name, 0));
+        } else if line == "}" {
+            counts.extend(current.take());
+        } else if let Some((_, count)) = &mut current {
+            // Labels start in the first column
+            let text = line.trim();
+            if line.starts_with(' ') && !text.is_empty() && !text.starts_with(';') {
+                *count += 1;
+            }
+        }
+    }
+
+    counts
+}
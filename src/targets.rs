@@ -30,6 +30,49 @@ impl Target {
         return Target::Amd64; // Default fallback
     }
 
+    /// Macros gcc predefines for the target that alecc's builtin headers use
+    pub fn predefined_macros(&self) -> Vec<(&'static str, &'static str)> {
+        let mut macros = vec![
+            ("__CHAR_BIT__", "8"),
+            ("__linux__", "1"),
+            ("__ELF__", "1"),
+            ("__SIZEOF_LONG_LONG__", "8"),
+            ("__LONG_LONG_MAX__", "9223372036854775807LL"),
+        ];
+        match self {
+            Target::I386 => macros.extend([
+                ("__i386__", "1"),
+                ("__SIZEOF_POINTER__", "4"),
+                ("__SIZEOF_LONG__", "4"),
+                ("__LONG_MAX__", "2147483647L"),
+                ("__SIZE_TYPE__", "unsigned int"),
+                ("__PTRDIFF_TYPE__", "int"),
+                ("__INTPTR_TYPE__", "int"),
+                ("__UINTPTR_TYPE__", "unsigned int"),
+            ]),
+            Target::Amd64 | Target::Arm64 => macros.extend([
+                ("__LP64__", "1"),
+                ("__SIZEOF_POINTER__", "8"),
+                ("__SIZEOF_LONG__", "8"),
+                ("__LONG_MAX__", "9223372036854775807L"),
+                ("__SIZE_TYPE__", "unsigned long"),
+                ("__PTRDIFF_TYPE__", "long"),
+                ("__INTPTR_TYPE__", "long"),
+                ("__UINTPTR_TYPE__", "unsigned long"),
+            ]),
+        }
+        match self {
+            Target::I386 | Target::Amd64 => macros.push(("__WCHAR_TYPE__", "int")),
+            Target::Arm64 => macros.push(("__WCHAR_TYPE__", "unsigned int")),
+        }
+        match self {
+            Target::I386 => {}
+            Target::Amd64 => macros.push(("__x86_64__", "1")),
+            Target::Arm64 => macros.push(("__aarch64__", "1")),
+        }
+        macros
+    }
+
     pub fn pointer_size(&self) -> usize {
         match self {
             Target::I386 => 4,
@@ -47,6 +90,20 @@ impl Target {
         }
     }
 
+    /// Stack pointer alignment required at call sites
+    pub fn stack_alignment(&self) -> usize {
+        16
+    }
+
+    /// Bytes below the stack pointer a leaf function may use without adjusting it
+    pub fn red_zone_size(&self) -> usize {
+        match self {
+            Target::I386 => 0,
+            Target::Amd64 => 128,
+            Target::Arm64 => 0,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Target::I386 => "i386",
@@ -64,6 +121,15 @@ impl Target {
         }
     }
 
+    /// Prefix of the GNU cross binutils for this target, e.g. `x86_64-linux-gnu`
+    pub fn gnu_prefix(&self) -> &'static str {
+        match self {
+            Target::I386 => "i686-linux-gnu",
+            Target::Amd64 => "x86_64-linux-gnu",
+            Target::Arm64 => "aarch64-linux-gnu",
+        }
+    }
+
     #[allow(dead_code)]
     pub fn assembler(&self) -> &'static str {
         match self {
@@ -91,7 +157,6 @@ impl Target {
         }
     }
 
-    #[allow(dead_code)]
     pub fn calling_convention(&self) -> CallingConvention {
         match self {
             Target::I386 => CallingConvention::Cdecl,
@@ -100,7 +165,6 @@ impl Target {
         }
     }
 
-    #[allow(dead_code)]
     pub fn register_names(&self) -> RegisterSet {
         match self {
             Target::I386 => RegisterSet::X86_32,
@@ -110,7 +174,6 @@ impl Target {
     }
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 pub enum CallingConvention {
     Cdecl,   // x86-32
@@ -118,7 +181,16 @@ pub enum CallingConvention {
     Aapcs64, // ARM64
 }
 
-#[allow(dead_code)]
+impl CallingConvention {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CallingConvention::Cdecl => "cdecl",
+            CallingConvention::SystemV => "System V AMD64",
+            CallingConvention::Aapcs64 => "AAPCS64",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum RegisterSet {
     X86_32,
@@ -151,6 +223,29 @@ impl RegisterSet {
         }
     }
 
+    /// Registers a call may change, so values living across one can't be kept in them
+    pub fn caller_saved_registers(&self) -> &'static [&'static str] {
+        match self {
+            RegisterSet::X86_32 => &["eax", "ecx", "edx"],
+            RegisterSet::X86_64 => &["rax", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11"],
+            RegisterSet::Aarch64 => &[
+                "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12",
+                "x13", "x14", "x15", "x16", "x17", "x18",
+            ],
+        }
+    }
+
+    /// Registers a function must give back as it found them
+    pub fn callee_saved_registers(&self) -> &'static [&'static str] {
+        match self {
+            RegisterSet::X86_32 => &["ebx", "esi", "edi"],
+            RegisterSet::X86_64 => &["rbx", "r12", "r13", "r14", "r15"],
+            RegisterSet::Aarch64 => &[
+                "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28",
+            ],
+        }
+    }
+
     pub fn return_register(&self) -> &'static str {
         match self {
             RegisterSet::X86_32 => "eax",
@@ -174,6 +269,28 @@ impl RegisterSet {
             RegisterSet::Aarch64 => "x29",
         }
     }
+
+    /// The number DWARF unwind information gives `register`; `rip`, `eip`
+    /// and `x30` stand for the return address
+    pub fn dwarf_number(&self, register: &str) -> Option<u16> {
+        const X86_32: [&str; 9] = [
+            "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "eip",
+        ];
+        const X86_64: [&str; 17] = [
+            "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11",
+            "r12", "r13", "r14", "r15", "rip",
+        ];
+        let position = match self {
+            RegisterSet::X86_32 => X86_32.iter().position(|name| *name == register),
+            RegisterSet::X86_64 => X86_64.iter().position(|name| *name == register),
+            RegisterSet::Aarch64 if register == "sp" => Some(31),
+            RegisterSet::Aarch64 => register
+                .strip_prefix('x')
+                .and_then(|number| number.parse().ok())
+                .filter(|number| *number <= 30),
+        };
+        position.map(|number| number as u16)
+    }
 }
 
 #[allow(dead_code)]
@@ -214,7 +331,7 @@ impl TargetInfo {
 
     pub fn size_of_type(&self, type_name: &str) -> Option<usize> {
         match type_name {
-            "char" | "signed char" | "unsigned char" => Some(1),
+            "_Bool" | "char" | "signed char" | "unsigned char" => Some(1),
             "short" | "unsigned short" => Some(2),
             "int" | "unsigned int" => Some(4),
             "long" | "unsigned long" => Some(self.word_size),
@@ -231,9 +348,79 @@ impl TargetInfo {
         }
     }
 
+    /// The `__INTn_TYPE__`, `__UINTn_TYPE__`, `__INTn_MAX__` and `__UINTn_MAX__`
+    /// macros `<stdint.h>` builds on, for each width some integer type has
+    /// here, and the limits of the pointer-sized types
+    pub fn stdint_macros(&self) -> Vec<(String, String)> {
+        let mut macros = Vec::new();
+        for bits in [8, 16, 32, 64] {
+            let Some(name) = ["signed char", "short", "int", "long", "long long"]
+                .into_iter()
+                .find(|name| self.size_of_type(name) == Some(bits / 8))
+            else {
+                continue;
+            };
+            let unsigned = match name {
+                "signed char" => "unsigned char".to_string(),
+                name => format!("unsigned {}", name),
+            };
+            // Limits carry the suffix of their type once it is wider than int
+            let suffix = match name {
+                "long" => "L",
+                "long long" => "LL",
+                _ => "",
+            };
+            let unsigned_suffix = match name {
+                "long" => "UL",
+                "long long" => "ULL",
+                "int" => "U",
+                _ => "",
+            };
+            macros.extend([
+                (format!("__INT{}_TYPE__", bits), name.to_string()),
+                (format!("__UINT{}_TYPE__", bits), unsigned),
+                (
+                    format!("__INT{}_MAX__", bits),
+                    format!("{}{}", i64::MAX >> (64 - bits), suffix),
+                ),
+                (
+                    format!("__UINT{}_MAX__", bits),
+                    format!("{}{}", u64::MAX >> (64 - bits), unsigned_suffix),
+                ),
+            ]);
+        }
+        let bits = self.word_size * 8;
+        let (suffix, unsigned_suffix) = if self.word_size == self.size_of_type("int").unwrap_or(4) {
+            ("", "U")
+        } else {
+            ("L", "UL")
+        };
+        for (name, max) in [
+            (
+                "__INTPTR_MAX__",
+                format!("{}{}", i64::MAX >> (64 - bits), suffix),
+            ),
+            (
+                "__PTRDIFF_MAX__",
+                format!("{}{}", i64::MAX >> (64 - bits), suffix),
+            ),
+            (
+                "__UINTPTR_MAX__",
+                format!("{}{}", u64::MAX >> (64 - bits), unsigned_suffix),
+            ),
+            (
+                "__SIZE_MAX__",
+                format!("{}{}", u64::MAX >> (64 - bits), unsigned_suffix),
+            ),
+        ] {
+            macros.push((name.to_string(), max));
+        }
+        macros
+    }
+
     pub fn align_of_type(&self, type_name: &str) -> Option<usize> {
         match type_name {
-            "char" | "signed char" | "unsigned char" => Some(1),
+            "_Bool" | "char" | "signed char" | "unsigned char" => Some(1),
             "short" | "unsigned short" => Some(2),
             "int" | "unsigned int" => Some(4),
             "long" | "unsigned long" => Some(self.word_size),
@@ -0,0 +1,198 @@
+/// The option groups that turn on several warnings at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Group {
+    /// `-Wall`
+    All,
+    /// `-Wextra`
+    Extra,
+}
+
+/// A warning `-W<name>` controls
+struct Warning {
+    name: &'static str,
+    /// Whether it is on when no option mentions it
+    default: bool,
+    /// A broader option whose `-W`/`-Wno-` form also applies to this one
+    parent: Option<&'static str>,
+    group: Option<Group>,
+}
+
+const fn warning(name: &'static str, default: bool) -> Warning {
+    Warning {
+        name,
+        default,
+        parent: None,
+        group: None,
+    }
+}
+
+impl Warning {
+    const fn under(self, parent: &'static str) -> Self {
+        Warning {
+            parent: Some(parent),
+            ..self
+        }
+    }
+
+    const fn in_group(self, group: Group) -> Self {
+        Warning {
+            group: Some(group),
+            ..self
+        }
+    }
+}
+
+/// Every warning the compiler can emit, with how it is turned on
+const WARNINGS: &[Warning] = &[
+    warning("analyzer-null-dereference", true),
+    warning("analyzer-out-of-bounds", true),
+    warning("analyzer-return-local-addr", true),
+    warning("attributes", true),
+    warning("conversion", false),
+    warning("deprecated-non-prototype", true),
+    warning("discarded-qualifiers", true),
+    warning("div-by-zero", true),
+    warning("float-conversion", false).under("conversion"),
+    warning("format", true).in_group(Group::All),
+    warning("format-extra-args", true).under("format"),
+    warning("implicit-fallthrough", false).in_group(Group::Extra),
+    warning("implicit-function-declaration", true).in_group(Group::All),
+    warning("incompatible-pointer-types", true),
+    warning("invalid-noreturn", true),
+    warning("main", true).in_group(Group::All),
+    warning("maybe-uninitialized", true)
+        .under("uninitialized")
+        .in_group(Group::All),
+    warning("null-dereference", true),
+    warning("overflow", true),
+    warning("pointer-integer-compare", true),
+    warning("prio-ctor-dtor", true),
+    warning("return-type", true).in_group(Group::All),
+    warning("shadow", false),
+    warning("shift-count-negative", true),
+    warning("shift-count-overflow", true),
+    warning("uninitialized", true).in_group(Group::All),
+    warning("sign-compare", false).in_group(Group::Extra),
+    warning("sign-conversion", false).under("conversion"),
+    warning("switch", true).in_group(Group::All),
+    warning("switch-default", false),
+    warning("switch-enum", false),
+    warning("unknown-warning-option", true),
+    warning("unreachable-code", false),
+    warning("unused", true).in_group(Group::All),
+    warning("unused-function", true)
+        .under("unused")
+        .in_group(Group::All),
+    warning("unused-parameter", false)
+        .under("unused")
+        .in_group(Group::Extra),
+    warning("unused-result", true),
+    warning("unused-variable", true)
+        .under("unused")
+        .in_group(Group::All),
+];
+
+fn lookup(name: &str) -> Option<&'static Warning> {
+    WARNINGS.iter().find(|warning| warning.name == name)
+}
+
+/// What the `-W` options of a compilation say about each warning, read the
+/// way gcc does: the last option naming a warning wins, `-Werror=<name>`
+/// turns it on as an error, and `-Wno-error=<name>` keeps it a warning
+/// under `-Werror`.
+#[derive(Debug, Clone, Default)]
+pub struct WarningOptions {
+    /// The values of `-W`, without the `-W`, in command-line order
+    options: Vec<String>,
+}
+
+impl WarningOptions {
+    pub fn new(options: &[String]) -> Self {
+        Self {
+            options: options.to_vec(),
+        }
+    }
+
+    /// Options that name no warning or group, as written on the command line
+    pub fn unknown(&self) -> Vec<String> {
+        self.options
+            .iter()
+            .filter(|option| {
+                let name = option
+                    .strip_prefix("no-")
+                    .unwrap_or(option)
+                    .trim_start_matches("error=");
+                !matches!(name, "all" | "extra" | "error") && lookup(name).is_none()
+            })
+            .map(|option| format!("-W{}", option))
+            .collect()
+    }
+
+    /// Whether the warning `name` is reported
+    pub fn enabled(&self, name: &str) -> bool {
+        let Some(warning) = lookup(name) else {
+            return self.explicit(name).unwrap_or(false);
+        };
+        let mut current = Some(warning);
+        while let Some(warning) = current {
+            if let Some(enabled) = self.explicit(warning.name) {
+                return enabled;
+            }
+            current = warning.parent.and_then(lookup);
+        }
+        let grouped = match warning.group {
+            Some(Group::All) => self.explicit("all"),
+            Some(Group::Extra) => self.explicit("extra"),
+            None => None,
+        };
+        grouped.unwrap_or(warning.default)
+    }
+
+    /// Whether the warning `name` is reported as an error. `-W[no-]error=<name>`
+    /// for it or a broader option takes precedence over plain `-Werror`.
+    pub fn is_error(&self, name: &str) -> bool {
+        let mut current = Some(name);
+        while let Some(name) = current {
+            let specific = self.options.iter().rev().find_map(|option| {
+                if option.strip_prefix("error=") == Some(name) {
+                    Some(true)
+                } else if option.strip_prefix("no-error=") == Some(name) {
+                    Some(false)
+                } else {
+                    None
+                }
+            });
+            if let Some(error) = specific {
+                return error;
+            }
+            current = lookup(name).and_then(|warning| warning.parent);
+        }
+        self.all_errors()
+    }
+
+    /// Whether plain `-Werror` is in effect, rather than only `-Werror=<name>`
+    pub fn all_errors(&self) -> bool {
+        self.options
+            .iter()
+            .rev()
+            .find_map(|option| match option.as_str() {
+                "error" => Some(true),
+                "no-error" => Some(false),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+
+    /// The last `-W<name>`, `-Werror=<name>` or `-Wno-<name>`, if any
+    fn explicit(&self, name: &str) -> Option<bool> {
+        self.options.iter().rev().find_map(|option| {
+            if option == name || option.strip_prefix("error=") == Some(name) {
+                Some(true)
+            } else if option.strip_prefix("no-") == Some(name) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+    }
+}
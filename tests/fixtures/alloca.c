@@ -0,0 +1,76 @@
+#include <stdarg.h>
+
+int sum(int count, ...) {
+    va_list args;
+    int total = 0;
+    va_start(args, count);
+    for (int i = 0; i < count; i++)
+        total = total + va_arg(args, int);
+    va_end(args);
+    return total;
+}
+
+int fill(int *block, int n) {
+    for (int i = 0; i < n; i++)
+        block[i] = i + 1;
+    return block[n - 1];
+}
+
+int add3(int a, int b, int c) {
+    return a + b + c;
+}
+
+// A block from alloca lasts until the function returns, and later ones
+// don't disturb it
+int blocks(int n) {
+    int *first = __builtin_alloca(n * sizeof(int));
+    fill(first, n);
+    char *second = __builtin_alloca(3);
+    second[0] = 1;
+    second[2] = 2;
+    return first[n - 1] + second[0] + second[2];
+}
+
+// alloca while other arguments wait on the stack, some of a variadic call
+int among_arguments(int n) {
+    return add3(n * 2, fill(__builtin_alloca(n * sizeof(int)), n),
+                sum(3, n, fill(__builtin_alloca(4 * sizeof(int)), 4), 1));
+}
+
+// Every round's array is freed before the next, however the round ends
+int rounds(int n) {
+    int *first = 0;
+    int total = 0;
+    for (int round = 0; round < 1000; round++) {
+        int values[n];
+        if (first == 0)
+            first = values;
+        if (values != first)
+            return -1;
+        if (round % 2)
+            continue;
+        if (round > 10)
+            break;
+        total = total + fill(values, n) + sizeof values / sizeof values[0];
+    }
+    return total;
+}
+
+int nested(int n) {
+    int outer[n];
+    int grid[n][3];
+    outer[0] = 5;
+    {
+        int inner[n * 2];
+        inner[n * 2 - 1] = 7;
+        outer[1] = inner[n * 2 - 1];
+    }
+    grid[n - 1][2] = 9;
+    return outer[0] + outer[1] + grid[n - 1][2] + sizeof outer + sizeof grid;
+}
+
+int main(void) {
+    int n = 3;
+    // 6, 3 * 3 + 3 + 4 + 1 = 17, 6 * (3 + 3) = 36 and 5 + 7 + 9 + 12 + 36 = 69
+    return blocks(n) + among_arguments(n) + rounds(n) + nested(n) - 100;
+}
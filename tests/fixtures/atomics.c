@@ -0,0 +1,23 @@
+int counter;
+unsigned char flags;
+short small;
+
+int main(void) {
+    int expected = 3;
+    __atomic_store_n(&counter, 10, __ATOMIC_RELEASE);
+    if (__atomic_load_n(&counter, __ATOMIC_ACQUIRE) != 10) return 1;
+    if (__atomic_fetch_add(&counter, 5, __ATOMIC_SEQ_CST) != 10) return 2;
+    if (__atomic_sub_fetch(&counter, 8, __ATOMIC_RELAXED) != 7) return 3;
+    if (__atomic_compare_exchange_n(&counter, &expected, 9, 0, 5, 5)) return 4;
+    if (expected != 7) return 5;
+    if (!__atomic_compare_exchange_n(&counter, &expected, 9, 0, 5, 5)) return 6;
+    flags = 12;
+    if (__atomic_fetch_or(&flags, 3, __ATOMIC_SEQ_CST) != 12) return 7;
+    if (__atomic_fetch_nand(&flags, 6, __ATOMIC_SEQ_CST) != 15) return 8;
+    if (flags != 249) return 9;
+    small = -3;
+    if (__sync_sub_and_fetch(&small, 10) != -13) return 10;
+    if (__sync_val_compare_and_swap(&small, -13, 4) != -13) return 11;
+    __sync_synchronize();
+    return __atomic_exchange_n(&counter, 0, __ATOMIC_SEQ_CST) + small;
+}
@@ -0,0 +1,38 @@
+/* Uses only the headers alecc ships; exits with 42 on every target */
+#include <stddef.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <limits.h>
+#include <stdint.h>
+#include <stdint.h>
+
+int sum(int count, ...) {
+    va_list args;
+    va_start(args, count);
+    int total = 0;
+    while (count) {
+        total = total + va_arg(args, int);
+        count = count - 1;
+    }
+    va_end(args);
+    return total;
+}
+
+int main() {
+    int32_t r = sum(3, 1, 2, 3);               /* 6 */
+    size_t word = sizeof(size_t);
+    r = r + word / sizeof(void *);             /* 7 */
+    r = r + 2 * sizeof(intptr_t) / sizeof(long);   /* 9 */
+    if (!(INT_MAX + INT_MIN + 1)) r = r + 4;   /* 13 */
+    r = r + CHAR_BIT;                          /* 21 */
+    uint8_t byte = UINT8_MAX;
+    byte = byte + 1;
+    if (!byte) r = r + 10;                     /* 31 */
+    bool done = true;
+    if (done) r = r + 1;                       /* 32 */
+    if (!(LONG_MAX + LONG_MIN + 1)) r = r + 4;     /* 36 */
+    if (!(ULONG_MAX + 1)) r = r + 6;               /* 42 */
+    int *nothing = NULL;
+    if (nothing) r = 0;
+    return r;
+}
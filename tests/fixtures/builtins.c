@@ -0,0 +1,27 @@
+typedef struct point { int x; int y; char name[21]; } point;
+
+int check(int v) {
+    if (v > 3) return v;
+    __builtin_unreachable();
+}
+
+int main(void) {
+    point a;
+    point b;
+    int i;
+    int sum = 0;
+    unsigned int word = 287454020;
+    __builtin_memset(&a, 0, sizeof(a));
+    a.x = 3;
+    for (i = 0; i < 20; i++) a.name[i] = 97 + i;
+    if (__builtin_memcpy(&b, &a, sizeof(a)) != &b) return 1;
+    if (b.x != 3 || b.name[19] != 116 || b.name[20] != 0) return 2;
+    if (__builtin_popcount(16711935) != 16) return 3;
+    if (__builtin_popcountl(-1L) != 8 * sizeof(long)) return 4;
+    if (__builtin_bswap32(word) != 1144201745) return 5;
+    if (__builtin_bswap16(4660) != 13330) return 6;
+    if (__builtin_clz(word) != 3 || __builtin_clzl(1UL) != 8 * sizeof(long) - 1) return 7;
+    if (__builtin_ctz(word) != 2 || __builtin_ctzll(1UL << 20) != 20) return 8;
+    if (__builtin_expect(word == 287454020, 1)) sum = sum + 1;
+    return sum + check(5);
+}
@@ -0,0 +1,29 @@
+/* Compound assignment and ++/-- on array elements, members and pointers */
+typedef struct counter { char tag; int count; long total; } counter_t;
+
+int table[4];
+
+int main() {
+    counter_t s;
+    counter_t *p = &s;
+    int local[3];
+    int i = 1;
+    int *q = local;
+    s.count = 5;
+    s.total = 100;
+    s.tag = 1;
+    local[0] = 1;
+    local[1] = 2;
+    local[2] = 3;
+    table[i] = 10;
+    table[i] += 2;
+    table[i + 1] -= 3;
+    local[i++] *= 7;
+    s.count++;
+    ++p->count;
+    p->total /= 4;
+    s.tag += 126;
+    q += 2;
+    *q += 1;
+    return table[1] + table[2] + local[1] + s.count + p->total + s.tag + local[2] + i;
+}
@@ -0,0 +1,47 @@
+// A bytecode interpreter dispatching through a table of label addresses:
+// 1 adds one, 2 doubles, 3 subtracts one and 0 stops
+int run(int *program) {
+    void *dispatch[4];
+    int acc = 0;
+    int pc = 0;
+    dispatch[0] = &&halt;
+    dispatch[1] = &&inc;
+    dispatch[2] = &&dbl;
+    dispatch[3] = &&dec;
+    goto *dispatch[program[pc++]];
+inc:
+    acc = acc + 1;
+    goto *dispatch[program[pc++]];
+dbl:
+    acc = acc * 2;
+    goto *dispatch[program[pc++]];
+dec:
+    acc = acc - 1;
+    goto *dispatch[program[pc++]];
+halt:
+    return acc;
+}
+
+int count(int n) {
+    int total = 0;
+again:
+    total = total + n;
+    n = n - 1;
+    if (n > 0)
+        goto again;
+    return total;
+}
+
+int main(void) {
+    int program[10];
+    void *target = &&done;
+    int result;
+    program[0] = 1; program[1] = 1; program[2] = 2; program[3] = 2;
+    program[4] = 1; program[5] = 2; program[6] = 2; program[7] = 2;
+    program[8] = 3; program[9] = 0;
+    result = run(program) + count(3);
+    goto *target;
+    return 1;
+done:
+    return result;
+}
@@ -0,0 +1,16 @@
+/* Pointer conversions that drop the const of what they point to */
+const char *greeting(void) { return "hi"; }
+
+char *unconst(const char *s) {
+    return s;
+}
+
+int first(char *s) { return s[0]; }
+
+int main(void) {
+    const char *g = greeting();
+    char *p = (const char *)g;
+    const char *kept = p;
+    p = g;
+    return first(g) - first(p) + kept[1] - unconst(g)[1];
+}
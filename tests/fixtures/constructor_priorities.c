@@ -0,0 +1,13 @@
+// Prioritized constructors run first, lowest priority first, and their
+// destructors last, in the opposite order
+int write(int fd, char *buf, int count);
+
+__attribute__((constructor)) void plain() { write(1, "c", 1); }
+__attribute__((constructor(200))) void late() { write(1, "b", 1); }
+__attribute__((constructor(101))) void early() { write(1, "a", 1); }
+
+__attribute__((destructor(101))) void last() { write(1, "z", 1); }
+__attribute__((destructor)) void first() { write(1, "x", 1); }
+__attribute__((destructor(300))) void middle() { write(1, "y", 1); }
+
+int main() { write(1, " main ", 6); return 0; }
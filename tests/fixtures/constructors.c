@@ -0,0 +1,11 @@
+// Constructors run before main and destructors after it returns
+int write(int fd, char *buf, int count);
+
+int counter __attribute__((section(".data.counters")));
+
+__attribute__((constructor)) void scale() { counter = counter + 20; }
+
+void goodbye() __attribute__((destructor));
+void goodbye() { write(1, "destructor\n", 11); }
+
+int main() { return counter + 2; }
@@ -0,0 +1,39 @@
+typedef struct Pair { short lo; short hi; } Pair;
+
+Pair make_pair(int lo) {
+    Pair pair;
+    pair.lo = lo;
+    pair.hi = lo + 1;
+    return pair;
+}
+
+int add(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+    return a + j;
+}
+
+int counter;
+
+/* Expression statements whose values are thrown away. If any of them left
+   a temporary on the stack, a million iterations would overflow it. */
+int main() {
+    int values[4];
+    int *p = values;
+    int i = 0;
+    values[1] = 0;
+    while (i < 1000000) {
+        i + 1;
+        i * (i + 3);
+        add(1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+        make_pair(i).hi;
+        make_pair(i);
+        values[i % 4];
+        *p;
+        -i;
+        !i;
+        ~i;
+        values[1]++;
+        counter += 1;
+        i++;
+    }
+    return values[1] == counter;
+}
@@ -0,0 +1,56 @@
+#ifndef DOC_API_H
+#define DOC_API_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/** Largest buffer the API accepts, in bytes. */
+#define API_MAX_BUFFER 4096
+
+/**
+ * A point on the screen.
+ *
+ * Coordinates grow right and down.
+ */
+typedef struct point {
+    /** Column */
+    int x;
+    int y;
+} point_t;
+
+/** Shapes the API can draw */
+enum shape { SHAPE_LINE, SHAPE_BOX };
+
+/**
+ * @brief Adds two numbers.
+ *
+ * @param a the first number
+ * @param[in] b the second number,
+ *        which may be negative
+ * @return a + b
+ */
+int api_add(int a, int b);
+
+/* An ordinary comment, which is not documentation */
+int api_undocumented(void);
+
+/** Number of calls made so far */
+extern int api_calls;
+
+/** Called for every event; may be NULL */
+extern void (*api_handler)(int event);
+
+/** Returns the larger of @p a and @p b. */
+static inline int api_max(int a, int b) {
+    /** Not documentation for anything outside this body */
+    return a > b ? a : b;
+}
+
+int api_after_body(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,16 @@
+typedef enum Color { RED, GREEN = 5, BLUE } Color;
+enum Level { LOW = 1, HIGH } level;
+int pick(Color c) {
+    if (c == RED) return 1;
+    if (c == BLUE) return 2;
+    return HIGH;
+}
+int main() {
+    int x = BLUE;
+    {
+        int x = 3;
+        x = x + 1;
+    }
+    for (int i = 0; i < 2; i++) { x = x + GREEN; }
+    return x + pick(BLUE) + LOW;
+}
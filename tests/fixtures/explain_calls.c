@@ -0,0 +1,11 @@
+/* A global, a call with register arguments and a branch, for explain-asm */
+int counter = 3;
+
+int add(int a, int b) { return a + b; }
+
+int main() {
+    int x = add(counter, 4);
+    if (x > 5)
+        return x * 2;
+    return 0;
+}
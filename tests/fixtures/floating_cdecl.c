@@ -0,0 +1,26 @@
+/* float and double on the stack and in st(0) for i386 cdecl, with unused results; exits with 42 */
+typedef struct sample { double weight; float bias; } sample_t;
+
+double total = 0.5;
+
+double twice(double x) { return x * 2; }
+float third(float x) { return x / 3; }
+int pick(int a, double b, int c, float d) { return a + (int)b + c + (int)d; }
+
+int main() {
+    sample_t s;
+    int i = 20;
+    while (i) {
+        twice(i);                   /* each result still leaves the x87 stack */
+        i = i - 1;
+    }
+    s.weight = twice(1.25);         /* 2.5 */
+    s.bias = third(4.5f);           /* 1.5 */
+    total += s.weight * s.bias;     /* 4.25 */
+    int r = pick(1, 2.75, 3, 4.5f); /* 10 */
+    r = r + (int)(total * 4);       /* 27 */
+    double neg = -s.weight;
+    if (neg < -2) r = r + 5;        /* 32 */
+    if (!(s.bias != 1.5)) r = r + 10;   /* 42 */
+    return r;
+}
@@ -0,0 +1,41 @@
+/* float and double arithmetic, conversions, comparisons with NaN and calls; exits with 107 */
+double scale = 2.5;
+float third = -0.25f;
+
+double mix(int a, double b, float c, long d) { return a * b + c - d; }
+float half(float x) { return x / 2; }
+double many(double a, double b, double c, double d, double e, double f, double g, double h, double i, double j) {
+    return a + b + c + d + e + f + g + h + i * 100 + j * 1000;
+}
+
+int main() {
+    double x = mix(3, 1.5, 0.5f, 1);   /* 4.0 */
+    float h = half(7);                /* 3.5 */
+    int r = (int)(x * 10) + (int)(h * 2);   /* 40 + 7 = 47 */
+    double y = 1e2;
+    y += 0.5;
+    y -= scale;                        /* 98 */
+    if (y > 97.5 && y < 98.5) r = r + 1;     /* 48 */
+    if (!(y != 98)) r = r + 2;               /* 50 */
+    double z = -y;
+    if (z < 0) r = r + 4;                    /* 54 */
+    float f = 1.5;
+    f++;
+    ++f;                                      /* 3.5 */
+    double n = 0;
+    n = n / n;                                 /* NaN */
+    if (n == n) r = r + 100;
+    if (n < 1 || n > 1 || n >= 1 || n <= 1) r = r + 100;
+    if (n != n) r = r + 8;                    /* 62 */
+    if (n) r = r + 16;                        /* 78 */
+    r = r + (int)(f * 2);                     /* 85 */
+    r = r + (int)many(1, 1, 1, 1, 1, 1, 1, 1, 0.01, 0.001);   /* 8 + 1 + 1 = 10 -> 95 */
+    r = r + (int)(third * -4);                 /* 96 */
+    long big = 3;
+    double q = big;
+    q *= 1.5;                                   /* 4.5 */
+    int k = 7;
+    k += 0.9;                                   /* 7.9 -> 7 */
+    r = r + (int)q + k;                         /* 96 + 4 + 7 = 107 */
+    return r;
+}
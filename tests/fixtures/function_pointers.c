@@ -0,0 +1,17 @@
+/* Calls through function pointers, arrays of them and callee expressions; exits with 75 */
+typedef int (*binop)(int, int);
+unsigned long strlen(const char *s);
+
+int add(int a, int b) { return a + b; }
+int mul(int a, int b) { return a * b; }
+int apply(binop f, int a, int b) { return f(a, b); }
+
+int main() {
+    int (*fp)(int, int) = add;
+    unsigned long (*length)(const char *) = strlen;
+    binop table[2];
+    table[0] = add;
+    table[1] = mul;
+    return fp(2, 3) + apply(mul, 4, 5) + table[1](6, 7) + (*fp)(1, 1)
+        + (int)length("abc") + (&add)(1, 2);
+}
@@ -0,0 +1,35 @@
+/* Globals initialized with addresses, string literals and folded
+   floating constants, which the linker or assembler fills in; exits with 49 */
+struct point {
+    int x;
+    int y;
+} origin;
+int value = 5;
+int table[4];
+int nine(void) {
+    return 9;
+}
+
+int *pointer = &value;
+int (*function)(void) = nine;
+int (*address_of_function)(void) = &nine;
+char *greeting = "hi";
+char *suffix = "hello" + 1;
+int *element = &table[1];
+int *past = table + 3;
+int *member = &origin.y;
+long address = (long)&value;
+int *none = 0;
+char letters[8] = "abc";
+double quarter = 1.0 / 4;
+
+int main(void) {
+    static int **indirect = &pointer;
+    table[1] = 2;
+    table[3] = 4;
+    origin.y = 3;
+    // 5 + 9 + 9 + 'i' + 'e' + 2 + 4 + 3 + 5 + 1 + 'c' + 1 + 5 - 300
+    return *pointer + function() + address_of_function() + greeting[1] + suffix[0] +
+           *element + *past + *member + *(int *)address + (none == 0) + letters[2] +
+           (quarter == 0.25) + **indirect - 300;
+}
@@ -0,0 +1,16 @@
+/* Zeroed globals in .bss, even a null pointer, an address in .data, a static one kept local
+   and an extern one defined elsewhere; exits with 54 */
+int zero;
+int five = 5;
+static int hidden = 2;
+char buffer[4096];
+int *where = &five;
+int *nowhere = 0;
+extern int counter;
+int bump(void);
+
+int main(void) {
+    bump();
+    buffer[4095] = 7;
+    return zero + *where + (nowhere != 0) + five - 5 + hidden + buffer[4095] + counter * 10;
+}
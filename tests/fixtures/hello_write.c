@@ -0,0 +1,7 @@
+// Freestanding hello world: link with --nostdlib, no libc involved
+int write(int fd, char *buf, int count);
+
+int main() {
+    write(1, "hello, world\n", 13);
+    return 0;
+}
@@ -0,0 +1,23 @@
+/* ++ and -- on parameters, globals, dereferenced pointers and array elements */
+int g = 0;
+
+int bump(int n) {
+    n++;
+    ++n;
+    return n--;
+}
+
+int main() {
+    int x = 10;
+    int *p = &x;
+    int a[3];
+    a[1] = 0;
+    g++;
+    ++g;
+    (*p)++;
+    --*p;
+    (*p)--;
+    int old = x++;
+    a[1]++;
+    return bump(g) + x + old + a[1] + *&g;
+}
@@ -0,0 +1,29 @@
+/* truncation and sign or zero extension between integer types; exits with 63 */
+unsigned char bytes[2];
+short halves[2];
+
+char narrow(int x) { return x; }
+unsigned short wrap(int x) { return x; }
+_Bool truth(long x) { return x; }
+unsigned int big() { return 4000000000; }
+
+int main() {
+    int r = 0;
+    bytes[0] = 200;
+    bytes[1] = 100;
+    halves[0] = -2;
+    halves[1] = 40000;
+    char c = 300;                              /* 44 */
+    if (c == 44) r = r + 1;
+    unsigned char u = 255;
+    u = u + 1;                                 /* 0 */
+    if (u == 0) r = r + 2;
+    signed char s = 127;
+    s += 1;                                    /* -128 */
+    if (s == -128) r = r + 4;
+    if (bytes[0] + bytes[1] == 300 && halves[0] + 2 == 0 && halves[1] == -25536) r = r + 8;
+    if ((char)511 == -1 && (unsigned char)-1 == 255 && (short)65537 == 1) r = r + 16;
+    long l = big();
+    if (l == 4000000000 && narrow(511) == -1 && wrap(-1) == 65535 && truth(256)) r = r + 32;
+    return r;                                  /* 63 */
+}
@@ -0,0 +1,47 @@
+/* Only the IR path compiles this: switch, goto and break, plus struct members, narrow and unsigned arithmetic and stack arguments */
+typedef struct P { int x; char c; long y; } Point;
+
+int add(int a, int b) { return a + b; }
+int many(int a, int b, int c, int d, int e, int f, int g, int h) { return a + b * 2 + c + d + e + f + g * 3 + h; }
+int fact(int n) { if (n <= 1) return 1; return n * fact(n - 1); }
+
+int classify(int v) {
+    switch (v) {
+    case 0: return 10;
+    case 1:
+    case 2: return 20;
+    default: break;
+    }
+    return 30;
+}
+
+int main() {
+    int total = 0;
+    int i;
+    int arr[5];
+    Point p;
+    char ch = -56;
+    unsigned char uc = 200;
+    unsigned int u = 7;
+    int *q = &total;
+    for (i = 0; i < 5; i++) arr[i] = i * i;
+    for (i = 0; i < 5; i++) {
+        if (i == 3) continue;
+        total += arr[i];
+    }
+    i = 0;
+    while (1) { if (++i > 4) break; }
+    while (i > 2) i--;
+    p.x = 5; p.c = 'a'; p.y = 100;
+    total += p.x + (p.c == 'a') + (int)(p.y / 50);
+    if (ch < 0) total += 1;
+    if (uc > 100) total += 1;
+    total += (int)(u / 2) + (int)(u % 4);
+    *q += add(1, 2) + many(1, 2, 3, 4, 5, 6, 7, 8) + fact(5);
+    total += classify(0) + classify(2) + classify(9);
+    total += i && total;
+    if (total > 1000) goto done;
+    total -= 100;
+done:
+    return total & 255;
+}
@@ -0,0 +1,16 @@
+/* Integer constants that don't fit where they are stored */
+long big = 5000000000;
+char small = 200;
+
+short narrow(void) {
+    return 100000;
+}
+
+char pick(char c) {
+    return c;
+}
+
+int main(void) {
+    int x = 5000000000;
+    return pick(1000) + narrow() + x;
+}
@@ -0,0 +1,9 @@
+/* Self-referential and mutually recursive macros expand once, then stop */
+#define COUNT COUNT + 1
+#define PING PONG
+#define PONG PING
+#define LEVEL0 LEVEL1
+#define LEVEL1 LEVEL2
+#define LEVEL2 LEVEL3
+#define LEVEL3 4
+int main(void) { int COUNT = 1; int PING = 2; return LEVEL0; }
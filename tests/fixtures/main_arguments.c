@@ -0,0 +1,8 @@
+/* main with argc, argv as an array parameter, and envp */
+int main(int argc, char *argv[], char **envp) {
+    int count = 0;
+    while (envp[count]) {
+        count++;
+    }
+    return argc * 10 + count + argv[1][0] - 97;
+}
@@ -0,0 +1,10 @@
+/* pick reaches its closing brace without a return when x is 0 */
+int pick(int x) {
+    if (x) {
+        return 7;
+    }
+}
+
+int main(void) {
+    return pick(1) + pick(0);
+}
@@ -0,0 +1,16 @@
+/* arguments computed with calls, divisions and shifts that clobber argument registers; exits with 134 */
+int mix(int a, int b, int c, int d) { return a * 1000 + b * 100 + c * 10 + d; }
+int add3(int a, int b, int c) { return a + b + c; }
+int twice(int x) { return mix(0, 0, x, x); }
+
+int main() {
+    int six = 6;
+    int n = 3;
+    /* each inner call overwrites the registers of the arguments already computed */
+    int r = mix(add3(1, 0, 0), twice(0) + 2, add3(0, 0, 3), add3(4, 0, 0));  /* 1234 */
+    /* six / 3 uses rdx and 1 << n uses rcx, the third and fourth argument registers */
+    r = r + mix(six / 3, six % 4, 1 << n, twice(1) - 11);                      /* 2280 -> 3514 */
+    /* nested twice over */
+    r = r + mix(0, add3(mix(0, 0, 0, 1), twice(0), 1), 0, n);                  /* 203 -> 3717 */
+    return r % 256 + (r == 3717);
+}
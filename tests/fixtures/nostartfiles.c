@@ -0,0 +1,8 @@
+// Brings its own entry point: link with --nostdlib --nostartfiles
+int write(int fd, char *buf, int count);
+void exit(int status);
+
+void _start() {
+    write(1, "no start files\n", 15);
+    exit(3);
+}
@@ -0,0 +1,11 @@
+// NULL comes from <stddef.h>; the header itself is never read
+#include <stddef.h>
+
+int main() {
+    int x = 5;
+    int *p = NULL;
+    char *s = (char *)0;
+    if (p == NULL) x = x + 1;
+    if (s != 0) x = x + 100;
+    return x;
+}
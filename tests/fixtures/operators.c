@@ -0,0 +1,23 @@
+/* comparisons, logical, bitwise and shift operators, signed and unsigned; exits with 255 */
+int main() {
+    int a = 7;
+    int b = -3;
+    unsigned int u = 40000;
+    unsigned int big = 4000000000u;
+    int bits = 0;
+
+    if (a > b && b < 0 && a >= 7 && b <= -3 && a != b && !(a == b)) bits = bits | 1;
+    if ((a < b || a == 7) && (0 || 1) && !(0 && 1)) bits = bits | 2;
+    if ((a & 3) == 3 && (a | 8) == 15 && (a ^ 5) == 2 && (~a) == -8) bits = bits | 4;
+    if ((a << 4) == 112 && (b >> 1) == -2 && (-64 >> 3) == -8) bits = bits | 8;
+    /* unsigned: logical shift, unsigned division and comparison */
+    if ((big >> 28) == 14 && big / 3 == 1333333333 && big % 7 == 3) bits = bits | 16;
+    if (big > u && u < big && big >= 1u) bits = bits | 32;
+    u >>= 3;
+    u /= 5;
+    if (u == 1000) bits = bits | 64;
+    b <<= 2;
+    b >>= 1;
+    if (b == -6 && (a > 0) + (b > 0) == 1) bits = bits | 128;
+    return bits;
+}
@@ -0,0 +1,16 @@
+int puts(char *s);
+
+int counter;
+char *message;
+
+int bump(int n) {
+    counter = counter + n;
+    return counter;
+}
+
+int main() {
+    counter = 3;
+    message = "hello from a PIE";
+    puts(message);
+    return bump(4);
+}
@@ -0,0 +1,47 @@
+typedef struct Pair { short lo; short hi; } Pair;
+typedef struct Node { char tag; int values[4]; struct Node *next; } Node;
+
+int grid[2][3];
+
+Pair make_pair(int lo) {
+    Pair pair;
+    pair.lo = lo;
+    pair.hi = lo + 1;
+    return pair;
+}
+
+int *first(int *values) {
+    return values;
+}
+
+int main() {
+    int m[3][4];
+    int i = 1;
+    int j = 2;
+    m[i][j] = 7;
+    m[i + 1][j + 1] = 5;
+
+    int row[4];
+    int *cursor = row;
+    int **p = &cursor;
+    (*p)[2] = 9;
+    row[1] = 3;
+
+    Node node;
+    Node *q = &node;
+    node.next = q;
+    node.tag = 1;
+    node.values[0] = 0;
+    node.values[0]++;
+    node.values[1] = 6;
+    q->values[2] = 2;
+    node.next->values[3] = 4;
+    first(node.values)[1] = node.values[1] + 1;
+
+    grid[1][2] = 5;
+    grid[0][2] = 1;
+
+    /* 7 + 5 + 9 + 3 + 1 + 7 + 2 + 4 + 50 + 1 + 1 + 4 = 94 */
+    return m[1][2] + m[2][3] + row[2] + (*p)[1] + node.values[0] + node.values[1] + q->values[2]
+        + node.next->values[3] + grid[1][2] * 10 + grid[0][2] + node.tag + make_pair(3).hi;
+}
@@ -0,0 +1,6 @@
+/* Fourteen call results live at once: more than the callee-saved registers, so some spill */
+int id(int x) { return x; }
+int main() {
+    int s = id(1) + (id(2) * (id(3) + (id(4) - (id(5) + (id(6) * (id(7) + (id(8) - (id(9) + (id(10) * (id(11) + (id(12) - (id(13) + id(14)))))))))))));
+    return s & 255;
+}
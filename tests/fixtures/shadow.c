@@ -0,0 +1,20 @@
+/* Block-scope declarations that shadow a global, a parameter and a local */
+int count = 1;
+
+int sum(int n) {
+    int total = 0;
+    for (int i = 0; i < n; i++) {
+        int n = i;
+        int count = 2;
+        {
+            int total = n + count;
+            (void)total;
+        }
+        total += n;
+    }
+    return total;
+}
+
+int main(void) {
+    return sum(3) + count;
+}
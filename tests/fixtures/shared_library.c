@@ -0,0 +1,11 @@
+/* A shared library: exported variables and functions are reached through the GOT, the static one directly */
+int counter = 5;
+int zeroed;
+static int hidden = 2;
+int get(void) { return counter + hidden; }
+int bump(int n) {
+    int (*f)(void) = get;
+    counter = counter + n;
+    zeroed = zeroed + 1;
+    return f() + zeroed;
+}
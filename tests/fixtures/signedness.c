@@ -0,0 +1,19 @@
+/* Signed and unsigned integers mixed in comparisons and conversions */
+unsigned int halve(unsigned int u) {
+    return u / 2;
+}
+
+int main(void) {
+    int i = -1;
+    unsigned int u = 3;
+    unsigned char small = 300;
+    short unsigned int mask = 7;
+    int back = u;
+    if (i < u) {
+        return 1;
+    }
+    if (u > 2) {
+        u = i;
+    }
+    return back + small + mask + (int)halve(8);
+}
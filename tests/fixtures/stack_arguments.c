@@ -0,0 +1,15 @@
+/* More arguments than any target passes in registers */
+int weigh(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h + 9 * i + 10 * j;
+}
+
+/* An odd number of stack arguments leaves a padding slot on arm64 */
+int last(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j, int k) {
+    return k - i;
+}
+
+int main() {
+    int x = weigh(1, 0, 0, 0, 0, 0, 0, 0, 2, 3);
+    int y = last(0, 0, 0, 0, 0, 0, 0, 0, 5, 0, weigh(0, 0, 0, 0, 0, 0, 0, 0, 1, 1));
+    return x + y;
+}
@@ -0,0 +1,23 @@
+/* static locals keep their value between calls, one object per block; exits with 119 */
+int next(void) {
+    static int counter = 10;
+    counter = counter + 1;
+    return counter;
+}
+int twice(void) {
+    static int counter;
+    int total = 0;
+    {
+        static int counter = 100;
+        counter = counter + 1;
+        total = counter;
+    }
+    counter = counter + 2;
+    return total + counter;
+}
+int main(void) {
+    next();
+    next();
+    twice();
+    return next() + twice();
+}
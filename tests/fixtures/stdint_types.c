@@ -0,0 +1,22 @@
+/* <stdint.h> types and limits laid out and folded like gcc on 64-bit targets; exits with 160 */
+#include <stdint.h>
+#include <limits.h>
+_Static_assert(sizeof(int8_t) == 1 && sizeof(uint16_t) == 2 && sizeof(int32_t) == 4, "widths");
+_Static_assert(sizeof(intptr_t) == sizeof(void *) && sizeof(uintptr_t) == 8, "pointer");
+_Static_assert(INT32_MIN == -2147483647 - 1 && INT32_MAX == 2147483647, "int32");
+_Static_assert(UINT32_MAX == 4294967295U && UINT16_MAX == 65535 && INT8_MIN == -128, "limits");
+_Static_assert(INTPTR_MIN < 0 && SIZE_MAX > 0 && UINTPTR_MAX == SIZE_MAX, "pointer limits");
+_Static_assert(UINT32_MAX + 1 == 0 && -1 > 0U && ULONG_MAX > 0 && LONG_MIN < 0, "unsigned");
+_Static_assert(sizeof(int64_t) == 8 && INT64_MAX == 9223372036854775807L && INT64_MIN < 0, "int64");
+_Static_assert(UINT64_MAX == 18446744073709551615UL && UINT64_MAX / 2 == INT64_MAX, "uint64");
+_Static_assert((UINT64_MAX >> 63) == 1 && (INT64_MIN >> 63) == -1, "shifts");
+
+typedef struct record { uint8_t tag; uint32_t value; int16_t delta; uintptr_t link; int64_t wide; } record;
+char check[sizeof(record)];
+uint32_t mask = UINT32_MAX;
+int8_t small = INT8_MIN;
+uint16_t half = UINT16_MAX;
+
+int main() {
+    return sizeof(check) + small + (int)(mask - UINT32_MAX) + (half - 65535);   /* 32 - 128 */
+}
@@ -0,0 +1,40 @@
+/* structs passed and returned by value in registers and in memory; exits with 99 */
+typedef struct small { int a; int b; } small;
+typedef struct pair { long x; long y; } pair;
+typedef struct mixed { double d; int i; } mixed;
+typedef struct floats { float a; float b; float c; } floats;
+typedef struct odd { char c[6]; } odd;
+typedef struct big { long v[4]; } big;
+
+small make_small(int a, int b) { small s; s.a = a; s.b = b; return s; }
+pair make_pair(long x, long y) { pair p; p.x = x; p.y = y; return p; }
+mixed make_mixed(double d, int i) { mixed m; m.d = d; m.i = i; return m; }
+floats make_floats(float a) { floats f; f.a = a; f.b = a * 2; f.c = a * 3; return f; }
+odd make_odd(int base) { odd o; o.c[0] = base; o.c[5] = base + 5; return o; }
+big make_big(long base) { big b; b.v[0] = base; b.v[1] = base + 1; b.v[2] = base + 2; b.v[3] = base + 3; return b; }
+
+long sum_small(small s) { return s.a * 10 + s.b; }
+long sum_pair(pair p) { return p.x * 10 + p.y; }
+long sum_mixed(mixed m) { return (long)(m.d * 10) + m.i; }
+long sum_floats(floats f) { return (long)(f.a + f.b + f.c); }
+long sum_odd(odd o) { return o.c[0] + o.c[5]; }
+long sum_big(big b) { return b.v[0] + b.v[1] + b.v[2] + b.v[3]; }
+/* p no longer fits in the six integer registers, so it goes on the stack */
+long many(long a, long b, long c, long d, long e, pair p, small s) { return a + b + c + d + e + p.x + p.y + s.a + s.b; }
+
+int main() {
+    small s = make_small(1, 2);
+    long r = sum_small(s);                          /* 12 */
+    pair p = make_pair(3, 4);
+    r = r + sum_pair(p);                            /* 34 -> 46 */
+    mixed m = make_mixed(0.5, 2);
+    r = r + sum_mixed(m);                           /* 7 -> 53 */
+    floats f = make_floats(1.5);
+    r = r + sum_floats(f);                          /* 9 -> 62 */
+    r = r + sum_odd(make_odd(1));                   /* 7 -> 69 */
+    big b = make_big(1);
+    r = r + sum_big(b) - make_big(2).v[3];          /* 10 - 5 -> 74 */
+    r = r + many(1, 1, 1, 1, 1, p, s);              /* 15 -> 89 */
+    s = make_small(4, make_pair(5, 6).y);
+    return r + s.a + s.b;                           /* 99 */
+}
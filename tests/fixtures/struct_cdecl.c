@@ -0,0 +1,26 @@
+/* A 12-byte struct is returned through a hidden pointer and passed by
+   value on the stack under the i386 cdecl convention */
+typedef struct triple {
+    int a;
+    int b;
+    int c;
+} triple;
+
+triple make(int a) {
+    triple t;
+    t.a = a;
+    t.b = a + 1;
+    t.c = a + 2;
+    return t;
+}
+
+int sum(int k, triple t, int m) {
+    return k + t.a * 100 + t.b * 10 + t.c + m;
+}
+
+int main(void) {
+    triple t = make(1);
+    triple u;
+    u = make(t.c);
+    return sum(1000, t, 7) - 1000 - 100 + u.a;
+}
@@ -0,0 +1,30 @@
+/* Switches on an enumeration that miss enumerators and fall through */
+typedef enum color { RED, GREEN, BLUE } color;
+
+int weight(enum color c) {
+    int total = 0;
+    switch (c) {
+    case RED:
+        total = total + 1;
+    case GREEN:
+        total = total + 2;
+        [[fallthrough]];
+    case 7:
+        total = total + 4;
+        break;
+    }
+    return total;
+}
+
+int named(color c) {
+    switch (c) {
+    case BLUE:
+        return 3;
+    default:
+        return 0;
+    }
+}
+
+int main(void) {
+    return weight(RED) + named(BLUE);
+}
@@ -0,0 +1,22 @@
+/* Thread-locals read, written and taken the address of: exits with 80 */
+_Thread_local int counter = 40;
+__thread long total;
+static _Thread_local char tag = 3;
+extern _Thread_local int counter;
+
+int bump(int by) {
+    static _Thread_local int calls;
+    calls++;
+    counter += by;
+    return calls;
+}
+
+int main(void) {
+    int *p = &counter;
+    bump(10);
+    int calls = bump(20);
+    total = *p + calls;
+    long *q = &total;
+    *q += tag;
+    return (int)total + tag + calls;
+}
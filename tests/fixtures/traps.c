@@ -0,0 +1,18 @@
+/* Operations the constant evaluator proves will trap */
+typedef struct point { int x; int y; } point_t;
+
+int divide(int a) {
+    int q = a / 0;
+    int r = a % (2 - 2);
+    q /= 0;
+    return q + r;
+}
+
+int main(void) {
+    long offset = (long)&((point_t *)0)->y;
+    int *null = (int *)0;
+    int v = *(int *)0;
+    int w = ((point_t *)0)->x;
+    int u = ((int *)0)[2];
+    return (int)offset + v + w + u + (null == 0);
+}
@@ -0,0 +1,42 @@
+/* calls to variadic functions with floating-point arguments, some nested where rsp is 8 bytes off; exits with 15 */
+#include <stdarg.h>
+
+int snprintf(char *buffer, unsigned long size, const char *format, ...);
+int strcmp(const char *a, const char *b);
+
+typedef struct big { long v[4]; } big;
+
+big spread(int count, ...) {
+    va_list list;
+    big b;
+    va_start(list, count);
+    b.v[0] = count;
+    b.v[1] = va_arg(list, int);
+    b.v[2] = va_arg(list, int);
+    b.v[3] = va_arg(list, int);
+    va_end(list);
+    return b;
+}
+
+int main() {
+    char buffer[128];
+    double d = 2.5;
+    float f = 1.25f;
+    int checks = 0;
+    int n;
+
+    /* the float is promoted to double, and al says two xmm registers are used */
+    snprintf(buffer, 128, "%d %.2f %.3f %d", 3, d, f, 7);
+    if (strcmp(buffer, "3 2.50 1.250 7") == 0) checks = checks + 1;
+
+    /* the ninth double goes on the stack; the call happens with 100 pushed */
+    n = 100 * checks + snprintf(buffer, 128, "%g %g %g %g %g %g %g %g %g %d",
+                                1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.5, 10);
+    if (strcmp(buffer, "1 2 3 4 5 6 7 8 9.5 10") == 0) checks = checks + 2;
+    if (n == 122) checks = checks + 4;
+
+    /* the result's address survives the realignment */
+    n = checks * spread(3, 10, 20, 30).v[2];
+    if (n == 140) checks = checks + 8;
+    return checks;
+}
@@ -0,0 +1,37 @@
+/* va_arg(ap, double) takes doubles from the vector register save area, then
+   from the overflow area once the eight xmm registers are used up; exits with 42 */
+#include <stdarg.h>
+
+double sum(int count, ...) {
+    va_list list;
+    double total = 0.0;
+    int i;
+    va_start(list, count);
+    for (i = 0; i < count; i++) {
+        total = total + va_arg(list, double);
+    }
+    va_end(list);
+    return total;
+}
+
+/* integers and doubles come from their own register areas */
+double mixed(int count, ...) {
+    va_list list;
+    double total = 0.0;
+    int i;
+    int weight;
+    va_start(list, count);
+    for (i = 0; i < count; i++) {
+        weight = va_arg(list, int);
+        total = total + weight * va_arg(list, double);
+    }
+    va_end(list);
+    return total;
+}
+
+int main() {
+    double small = sum(3, 1.5, 2.25, 0.25);
+    double many = sum(10, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.5, 0.5);
+    double both = mixed(3, 2, 0.5, 3, 1.5, 4, 0.25);
+    return (int)(small + many + both) - 14;
+}
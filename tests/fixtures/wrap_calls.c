@@ -0,0 +1,17 @@
+/* --wrap=add sends main's call through __wrap_add, and only --defsym defines unused; exits with 50 */
+int add(int a, int b) { return a + b; }
+
+int __real_add(int a, int b);
+
+int __wrap_add(int a, int b) {
+    return __real_add(a, b) * 10;
+}
+
+int unused(void);
+
+int main(int argc, char **argv) {
+    if (argc > 100) {
+        return unused();
+    }
+    return add(2, 3);
+}
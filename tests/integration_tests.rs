@@ -1,11 +1,12 @@
 #[cfg(test)]
 mod tests {
-    use alecc::cli::Args;
+    use alecc::cli::{Args, AsDialect, AsmSyntax, Backend, MissingReturn};
     use alecc::codegen::CodeGenerator;
-    use alecc::compiler::Compiler;
+    use alecc::compiler::{Compiler, Phase};
     use alecc::lexer::{Lexer, TokenType};
     use alecc::parser::Parser;
     use alecc::targets::Target;
+    use clap::Parser as _;
     use std::path::PathBuf;
 
     #[test]
@@ -73,6 +74,610 @@ mod tests {
         assert_eq!(program.functions[0].name, "main");
     }
 
+    #[test]
+    fn test_parser_generic_selection() {
+        use alecc::parser::{Expression, ExpressionKind, StatementKind};
+
+        let input = r#"
+            int main() {
+                long value = 1;
+                return _Generic(value, int: 1, long: 2, default: 3);
+            }
+        "#
+        .to_string();
+        let mut lexer = Lexer::new(input);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let StatementKind::Block(body) = &program.functions[0].body.kind else {
+            panic!("expected a block body");
+        };
+        assert!(matches!(
+            &body[1].kind,
+            StatementKind::Return(Some(Expression {
+                kind: ExpressionKind::IntegerLiteral(2),
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_alignof_and_alignas() {
+        use alecc::parser::{Expression, ExpressionKind, StatementKind};
+
+        let parse = |input: &str, target: Target| {
+            let tokens = Lexer::new(input.to_string()).tokenize().unwrap();
+            Parser::new(tokens).with_target(target).parse()
+        };
+
+        for (target, expected) in [(Target::I386, 4), (Target::Amd64, 8)] {
+            let program = parse("int main() { return _Alignof(long); }", target).unwrap();
+            let StatementKind::Block(body) = &program.functions[0].body.kind else {
+                panic!("expected a block body");
+            };
+            assert!(matches!(
+                &body[0].kind,
+                StatementKind::Return(Some(Expression {
+                    kind: ExpressionKind::IntegerLiteral(value),
+                    ..
+                })) if *value == expected
+            ));
+        }
+
+        let program = parse(
+            "_Alignas(16) int g;\nint main() { char a; char b; _Alignas(16) int x = 1; return x; }",
+            Target::Amd64,
+        )
+        .unwrap();
+        assert_eq!(program.global_variables[0].name, "g");
+        assert_eq!(program.global_variables[0].attributes.aligned, Some(16));
+        let assembly = CodeGenerator::new(Target::Amd64)
+            .generate(&program)
+            .unwrap();
+        assert!(assembly.contains(".balign 16\ng:"));
+        assert!(assembly.contains("[rbp + -32]"));
+
+        assert!(parse("_Alignas(2) int g;", Target::Amd64).is_err());
+        assert!(parse("_Alignas(3) int g;", Target::Amd64).is_err());
+    }
+
+    #[test]
+    fn test_uninitialized_uses_on_branches_and_loops() {
+        use alecc::analysis::uninitialized_uses;
+
+        let uses = |body: &str| {
+            let source = format!("int f(int c) {{ {} }}", body);
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            uninitialized_uses(&program.functions[0])
+                .into_iter()
+                .map(|found| (found.variable, found.definitely))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(uses("int x; return x;"), vec![("x".to_string(), true)]);
+        assert!(uses("int x = 1; return x;").is_empty());
+
+        // Branches
+        assert_eq!(
+            uses("int x; if (c) { x = 1; } return x;"),
+            vec![("x".to_string(), false)]
+        );
+        assert!(uses("int x; if (c) { x = 1; } else { x = 2; } return x;").is_empty());
+        assert!(uses("int x; if (c) { x = 1; } else { return 0; } return x;").is_empty());
+        assert_eq!(
+            uses("int x; if (c && (x = 1)) { c = 2; } return x;"),
+            vec![("x".to_string(), false)]
+        );
+
+        // Loops may run zero times, and later iterations see earlier ones
+        assert_eq!(
+            uses("int x; while (c) { x = 1; c = c - 1; } return x;"),
+            vec![("x".to_string(), false)]
+        );
+        assert_eq!(
+            uses("int s; int i; for (i = 0; i < c; i++) { s = s + i; } return 0;"),
+            vec![("s".to_string(), false)]
+        );
+        assert!(
+            uses("int s = 0; int i; for (i = 0; i < c; i++) { s = s + i; } return s;").is_empty()
+        );
+
+        // Shadowing and taking the address
+        assert_eq!(
+            uses("int x = 1; { int x; c = x; } return x;"),
+            vec![("x".to_string(), true)]
+        );
+        assert!(uses("int x; int *p = &x; return x;").is_empty());
+
+        // Each is found at the read, in a compound assignment too
+        let source = "int f(int c) {
+    int x;
+    int y;
+    c = c + x;
+    y += 2;
+    return x + y;
+}";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let spans: Vec<_> = uninitialized_uses(&program.functions[0])
+            .into_iter()
+            .map(|found| (found.variable, found.span.line, found.span.column))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![("x".to_string(), 4, 13), ("y".to_string(), 5, 5)]
+        );
+    }
+
+    #[test]
+    fn test_parser_keeps_qualifiers() {
+        use alecc::parser::{Expression, ExpressionKind, StatementKind, Type};
+
+        let parse = |input: &str| {
+            let tokens = Lexer::new(input.to_string()).tokenize().unwrap();
+            Parser::new(tokens).parse()
+        };
+
+        let program = parse(
+            "int f(const char *restrict s) { volatile int v = 0; int const w = 1; return _Generic(&w, const int *: 1, int *: 2); }",
+        )
+        .unwrap();
+        let function = &program.functions[0];
+
+        let param = &function.parameters[0].1;
+        assert!(param.qualifiers().is_restrict);
+        let Type::Pointer(pointee) = param.unqualified() else {
+            panic!("expected a pointer parameter, got {:?}", param);
+        };
+        assert!(pointee.qualifiers().is_const);
+        assert!(matches!(pointee.unqualified(), Type::Char));
+
+        let StatementKind::Block(body) = &function.body.kind else {
+            panic!("expected a block body");
+        };
+        let StatementKind::Declaration { var_type, .. } = &body[0].kind else {
+            panic!("expected a declaration");
+        };
+        assert!(var_type.qualifiers().is_volatile);
+        assert!(matches!(
+            &body[2].kind,
+            StatementKind::Return(Some(Expression {
+                kind: ExpressionKind::IntegerLiteral(1),
+                ..
+            }))
+        ));
+
+        assert!(parse("int restrict x;").is_err());
+    }
+
+    #[test]
+    fn test_parser_warns_on_shift_counts_and_narrowing() {
+        let source = "long wide() { return 1; }\nint main() { int x = 1; char c = wide(); char d = 300; char e = -5;\nlong z = x << 31; z = x << 32; z = z << 40; x = x >> -1; c = x; return 0; }";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+
+        let mut parser = Parser::new(tokens).with_target(Target::Amd64);
+        parser.parse().unwrap();
+        let options: Vec<_> = parser.warnings().iter().map(|w| w.option).collect();
+        assert_eq!(
+            options,
+            [
+                "conversion",
+                "overflow",
+                "shift-count-overflow",
+                "shift-count-negative",
+                "conversion"
+            ]
+        );
+        assert!(parser.warnings()[1].message.contains("from '300' to '44'"));
+        assert_eq!(parser.warnings()[2].line, 3);
+
+        // `long` is as wide as `int` on i386, so neither shift by 40 nor narrowing it is suspicious
+        let tokens = Lexer::new("long f(long z) { int x = z; return z << 40; }".to_string())
+            .tokenize()
+            .unwrap();
+        let mut parser = Parser::new(tokens).with_target(Target::I386);
+        parser.parse().unwrap();
+        let options: Vec<_> = parser.warnings().iter().map(|w| w.option).collect();
+        assert_eq!(options, ["shift-count-overflow"]);
+    }
+
+    #[test]
+    fn test_inline_functions() {
+        use alecc::optimizer::{OptimizationLevel, Optimizer};
+        use alecc::parser::{Expression, ExpressionKind, StatementKind};
+
+        let source = "inline int twice(int x) { return x + x; }\nstatic inline int sq(int x) { return x * x; }\nint main() { int a = 3; return sq(twice(a)) + twice(a++); }";
+        let parse = || {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap()
+        };
+
+        let program = parse();
+        assert!(program.functions[0].is_inline && !program.functions[0].is_static);
+        assert!(program.functions[1].is_inline && program.functions[1].is_static);
+
+        // Inline definitions may be repeated in other units, and static ones stay local
+        let asm = CodeGenerator::new(Target::Amd64)
+            .generate(&program)
+            .unwrap();
+        assert!(asm.contains(".weak twice"));
+        assert!(!asm.contains(".globl twice") && !asm.contains(".globl sq"));
+        assert!(asm.contains(".globl main"));
+
+        // -O2 substitutes the bodies, except where an argument has side effects
+        let mut program = parse();
+        Optimizer::new(OptimizationLevel::Moderate)
+            .optimize(&mut program)
+            .unwrap();
+        let StatementKind::Block(body) = &program.functions[2].body.kind else {
+            panic!("expected a block body");
+        };
+        let StatementKind::Return(Some(Expression {
+            kind: ExpressionKind::Binary { left, right, .. },
+            ..
+        })) = &body[1].kind
+        else {
+            panic!("expected a return of a sum, got {:?}", body[1]);
+        };
+        assert!(matches!(left.kind, ExpressionKind::Cast { .. }));
+        assert!(matches!(right.kind, ExpressionKind::Call { .. }));
+
+        assert!(
+            Parser::new(Lexer::new("inline int x;".to_string()).tokenize().unwrap())
+                .parse()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_format_string_checks() {
+        let source = r#"int main() { int i = 1; long l = 2; char buf[8];
+printf("%d %ld %s %5.*d%%\n", i, l, buf, 3, i);
+printf("%d %s\n", l, i);
+printf("%d\n");
+fprintf(0, "%c\n", 'a', i);
+scanf("%d %*d %7s %ld", &i, buf, &i);
+return 0; }"#;
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens).with_target(Target::Amd64);
+        parser.parse().unwrap();
+
+        let warnings: Vec<_> = parser
+            .warnings()
+            .iter()
+            .map(|w| (w.line, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (3, "format '%d' expects argument of type 'int', but argument 2 has type 'long int' [-Wformat=]"),
+                (3, "format '%s' expects argument of type 'char *', but argument 3 has type 'int' [-Wformat=]"),
+                (4, "format '%d' expects a matching 'int' argument [-Wformat=]"),
+                (5, "too many arguments for format [-Wformat-extra-args]"),
+                (6, "format '%ld' expects argument of type 'long int *', but argument 4 has type 'int *' [-Wformat=]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_unsigned_conversions() {
+        let source = r#"int main() { unsigned u = 1; unsigned long ul = 2; unsigned short us = 3;
+unsigned long long ull = 4; int i = 5; unsigned char uc;
+printf("%u %lu %x %o %X %llu %hu\n", u, ul, u, u, i, ull, us);
+scanf("%u %lx %hhu %d", &u, &ul, &uc, &u);
+printf("%lu %u\n", u, ul);
+return 0; }"#;
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens).with_target(Target::Amd64);
+        parser.parse().unwrap();
+
+        // Only a mismatch in rank is reported, not one in signedness
+        let warnings: Vec<_> = parser
+            .warnings()
+            .iter()
+            .map(|w| (w.line, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (5, "format '%lu' expects argument of type 'long unsigned int', but argument 2 has type 'unsigned int' [-Wformat=]"),
+                (5, "format '%u' expects argument of type 'unsigned int', but argument 3 has type 'long unsigned int' [-Wformat=]"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gcc_attributes() {
+        use alecc::layout::TypeLayout;
+
+        let source = "typedef struct __attribute__((packed)) P { char c; int i; } P;
+long table __attribute__((aligned(32)));
+__attribute__((noreturn, cold)) void fail();
+static int __attribute__((section(\".text.hot\"))) hot() { return _Alignof(P); }
+int main() __attribute__((unknown_thing)) { return hot(); }";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens).with_target(Target::Amd64);
+        let program = parser.parse().unwrap();
+
+        let mut layout = TypeLayout::new(Target::Amd64);
+        layout.record_aggregates(&program);
+        let packed = layout.resolve(&program.type_definitions["P"]);
+        assert_eq!((layout.size_of(&packed), layout.align_of(&packed)), (5, 1));
+        let table = program
+            .global_variables
+            .iter()
+            .find(|global| global.name == "table")
+            .unwrap();
+        assert_eq!(table.attributes.aligned, Some(32));
+        assert!(program.functions[0].attributes.noreturn);
+        assert_eq!(
+            parser.warnings()[0].message,
+            "'unknown_thing' attribute directive ignored [-Wattributes]"
+        );
+
+        let asm = CodeGenerator::new(Target::Amd64)
+            .generate(&program)
+            .unwrap();
+        assert!(asm.contains(".pushsection .text.hot,\"ax\",@progbits\n.type hot, @function\nhot:"));
+
+        // The startup code runs the .init_array and .fini_array entries
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("constructors.c");
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("constructors-{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.stdout, b"destructor\n", "target {}", target);
+            assert_eq!(run.status.code(), Some(22), "target {}", target);
+        }
+    }
+
+    #[test]
+    fn test_c23_attributes() {
+        use alecc::parser::StatementKind;
+
+        let source = "[[nodiscard]] int compute(int x) { return x * 2; }
+[[gnu::noreturn, maybe_unused]] void stop();
+int main() {
+    [[gnu::aligned(16)]] int n = 4;
+    compute(1);
+    (void)compute(2);
+    [[fallthrough]];
+    [[vendor::thing]];
+    return compute(n);
+}";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens).with_target(Target::Amd64);
+        let program = parser.parse().unwrap();
+
+        assert!(program.functions[0].attributes.nodiscard);
+        assert!(program.functions[1].attributes.noreturn);
+        let StatementKind::Block(body) = &program.functions[2].body.kind else {
+            panic!("expected a block body");
+        };
+        assert!(matches!(
+            body[0].kind,
+            StatementKind::Declaration {
+                alignment: Some(16),
+                ..
+            }
+        ));
+        assert!(matches!(body[3].kind, StatementKind::Fallthrough));
+
+        let warnings: Vec<_> = parser
+            .warnings()
+            .iter()
+            .map(|w| (w.line, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (5, "ignoring return value of 'compute' declared with attribute 'nodiscard' [-Wunused-result]"),
+                (8, "'vendor::thing' scoped attribute directive ignored [-Wattributes]"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_null_pointer_constants() {
+        use alecc::lexer::Span;
+        use alecc::parser::{is_null_pointer_constant, Expression, ExpressionKind, Type};
+
+        let source = "int main() { int *p = ((void*)0); int n = 4;\nreturn (p == 0) + (p != (void *)0) + (p == 1) + (n == (int)0); }";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens).with_target(Target::Amd64);
+        parser.parse().unwrap();
+        let warnings: Vec<_> = parser
+            .warnings()
+            .iter()
+            .map(|w| (w.line, w.column, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [(2, 41, "comparison between pointer and integer")]
+        );
+
+        let literal =
+            |value| Expression::new(ExpressionKind::IntegerLiteral(value), Span::default());
+        let null = Expression::new(
+            ExpressionKind::Cast {
+                target_type: Type::Pointer(Box::new(Type::Void)),
+                expression: Box::new(literal(0)),
+            },
+            Span::default(),
+        );
+        assert!(is_null_pointer_constant(&null));
+        assert!(!is_null_pointer_constant(&literal(1)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("null_pointers");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "amd64",
+            "--nostdlib",
+            "-o",
+            exe.to_str().unwrap(),
+            fixture("null_pointers.c").to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(6));
+    }
+
+    #[test]
+    fn test_source_locations() {
+        use alecc::error::AleccError;
+        use alecc::lexer::Span;
+        use alecc::llvm_ir::LlvmIrGenerator;
+        use alecc::parser::{ExpressionKind, StatementKind};
+
+        let source = "int main() {\n    int x = 1;\n    return x + y;\n}";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let body = &program.functions[0].body;
+        assert_eq!(
+            body.span,
+            Span {
+                line: 1,
+                column: 12
+            }
+        );
+        let StatementKind::Block(statements) = &body.kind else {
+            panic!("expected a block body");
+        };
+        assert_eq!(statements[1].span, Span { line: 3, column: 5 });
+        let StatementKind::Return(Some(sum)) = &statements[1].kind else {
+            panic!("expected a return");
+        };
+        // A binary expression is located at its operator
+        assert_eq!(
+            sum.span,
+            Span {
+                line: 3,
+                column: 14
+            }
+        );
+        let ExpressionKind::Binary { right, .. } = &sum.kind else {
+            panic!("expected a sum");
+        };
+        assert_eq!(
+            right.span,
+            Span {
+                line: 3,
+                column: 16
+            }
+        );
+
+        // Errors point at the innermost node they come from
+        let error = LlvmIrGenerator::new(Target::Amd64)
+            .generate(&program)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            AleccError::CodegenError {
+                span: Some(Span {
+                    line: 3,
+                    column: 16
+                }),
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Code generation error at line 3, column 16: use of undeclared identifier 'y'"
+        );
+    }
+
+    #[test]
+    fn test_static_analyzer() {
+        use alecc::analysis::{analyze, AnalyzerCheck};
+
+        let findings = |body: &str| {
+            let source = format!("int *f(int *p, int c) {{ {} }}", body);
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            analyze(&program.functions[0])
+                .into_iter()
+                .map(|finding| (finding.check, finding.message))
+                .collect::<Vec<_>>()
+        };
+
+        // Null dereferences after null checks and assignments
+        assert_eq!(
+            findings("if (p == 0) { c = *p; } return p;"),
+            [(
+                AnalyzerCheck::NullDereference,
+                "dereference of NULL 'p' in function 'f' [-Wanalyzer-null-dereference]".to_string()
+            )]
+        );
+        assert_eq!(findings("if (!p) { return p; } *p = 1; return p;"), []);
+        assert_eq!(
+            findings("if (p != ((void*)0)) { c = 1; } else { p[0] = c; } return p;").len(),
+            1
+        );
+        assert_eq!(findings("if (p) { return p; } c = *p; return p;").len(), 1);
+        assert_eq!(
+            findings("if (p == 0 && p[1]) { c = 1; } return p;").len(),
+            1
+        );
+        assert_eq!(findings("if (p == 0 || *p) { c = 1; } return p;"), []);
+        assert_eq!(findings("int *q = 0; q = p; return q + *q;"), []);
+        assert_eq!(findings("int *q = 0; if (c) { q = p; } return q + *q;"), []);
+        assert_eq!(
+            findings("int *q; q = 0; while (c) { c = *q; } return p;").len(),
+            1
+        );
+        assert_eq!(
+            findings("int *q = 0; while (c) { c = *q; q = p; } return p;"),
+            []
+        );
+
+        // Addresses of locals and parameters
+        let returns_local = findings("int x = c; return &x;");
+        assert_eq!(
+            returns_local,
+            [(
+                AnalyzerCheck::ReturnLocalAddress,
+                "function 'f' returns address of local variable 'x' [-Wanalyzer-return-local-addr]"
+                    .to_string()
+            )]
+        );
+        assert_eq!(findings("int a[4]; return a;").len(), 1);
+        assert_eq!(findings("int a[4]; return &a[1];").len(), 1);
+        assert_eq!(findings("return &c;").len(), 1);
+        assert_eq!(findings("return &p[1];"), []);
+
+        // Constant indices past a fixed-size array; the address one past the end is fine
+        assert_eq!(
+            findings("int a[4]; a[4] = 1; return p;"),
+            [(
+                AnalyzerCheck::OutOfBounds,
+                "array subscript 4 is outside the bounds of 'a' with 4 elements in function 'f' [-Wanalyzer-out-of-bounds]"
+                    .to_string()
+            )]
+        );
+        assert_eq!(findings("int a[4]; c = a[-1]; return p;").len(), 1);
+        assert_eq!(
+            findings("int a[4]; a[3] = 1; return &p[c] + (&a[4] - a);"),
+            []
+        );
+    }
+
     #[test]
     fn test_target_from_string() {
         assert_eq!(Target::from_string("i386"), Some(Target::I386));
@@ -104,9 +709,79 @@ mod tests {
         assert!(assembly.contains("ret"));
     }
 
+    #[test]
+    fn test_codegen_variadic_function() {
+        let input = r#"
+            int sum(int count, ...) {
+                va_list ap;
+                __builtin_va_start(ap, count);
+                int total = __builtin_va_arg(ap, int);
+                va_end(ap);
+                return total;
+            }
+        "#
+        .to_string();
+        let mut lexer = Lexer::new(input);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert!(program.functions[0].is_variadic);
+
+        let mut codegen = CodeGenerator::new(Target::Amd64);
+        let assembly = codegen.generate(&program).unwrap();
+
+        // Register save area spilled in the prologue, gp_offset starts past `count`
+        assert!(assembly.contains("mov QWORD PTR [rsp + 40], r9"));
+        assert!(assembly.contains("mov DWORD PTR [rax], 8"));
+        assert!(!assembly.contains("__alecc_frame_size__"));
+    }
+
+    #[test]
+    fn test_codegen_llvm_as_dialect() {
+        let input = "int puts(char *s);\nint main() { puts(\"hi\"); return 0; }".to_string();
+        let mut lexer = Lexer::new(input);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let mut codegen = CodeGenerator::new(Target::I386).with_as_dialect(AsDialect::Llvm);
+        let assembly = codegen.generate(&program).unwrap();
+
+        assert!(assembly.starts_with(".code32\n"));
+        assert!(assembly.contains(".asciz \"hi\""));
+        assert!(!assembly.contains(".arch i386"));
+        assert!(!assembly.contains(".extern puts"));
+    }
+
+    #[test]
+    fn test_codegen_red_zone_leaf_function() {
+        let input =
+            "int seven() { int x = 7; return x; }\nint main() { return seven(); }".to_string();
+        let mut lexer = Lexer::new(input);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let assembly = CodeGenerator::new(Target::Amd64)
+            .generate(&program)
+            .unwrap();
+        let seven = &assembly[assembly.find("seven:").unwrap()..assembly.find("main:").unwrap()];
+        assert!(!seven.contains("sub rsp"));
+        assert!(assembly.contains("and rsp, -16"));
+
+        let assembly = CodeGenerator::new(Target::Amd64)
+            .with_red_zone(false)
+            .generate(&program)
+            .unwrap();
+        let seven = &assembly[assembly.find("seven:").unwrap()..assembly.find("main:").unwrap()];
+        assert!(seven.contains("sub rsp"));
+    }
+
     #[tokio::test]
     async fn test_compiler_invalid_target() {
         let args = Args {
+            command: None,
             input_files: vec![PathBuf::from("test.c")],
             target: "invalid_target".to_string(),
             output: None,
@@ -119,18 +794,57 @@ mod tests {
             include_dirs: vec![],
             library_dirs: vec![],
             libraries: vec![],
+            wrap: vec![],
+            defsym: vec![],
             defines: vec![],
             undefines: vec![],
             standard: None,
             verbose: false,
             pic: false,
             pie: false,
+            no_pie: false,
+            no_ident: false,
             static_link: false,
             shared: false,
+            nostdlib: false,
+            nostartfiles: false,
+            use_crt: false,
+            no_use_crt: false,
+            no_red_zone: false,
+            omit_frame_pointer: false,
+            no_omit_frame_pointer: false,
+            stack_protector: false,
+            stack_protector_strong: false,
+            stack_protector_all: false,
+            no_stack_protector: false,
+            sse2: false,
+            stack_size: None,
+            stack_usage: false,
+            function_sections: false,
+            data_sections: false,
+            verbose_asm: false,
+            save_asm: false,
+            missing_return: MissingReturn::Unspecified,
+            macro_depth: 256,
+            analyze: false,
+            summary: false,
+            symbol_prefix: None,
             thread_model: "posix".to_string(),
             lto: false,
             sysroot: None,
+            toolchain: None,
             extra_flags: vec![],
+            dry_run: false,
+            stream_diagnostics: false,
+            daemon: None,
+            remote: None,
+            emit: None,
+            emit_ast: None,
+            emit_callgraph: None,
+            whole_program: false,
+            backend: Backend::Native,
+            as_dialect: AsDialect::Gnu,
+            asm_syntax: AsmSyntax::Intel,
         };
 
         let result = Compiler::new(args);
@@ -150,4 +864,5339 @@ mod tests {
         assert!(format!("{}", lex_error).contains("line 1"));
         assert!(format!("{}", lex_error).contains("column 5"));
     }
+
+    #[tokio::test]
+    async fn test_compile_output_reports_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.c");
+        let asm = dir.path().join("main.s");
+        std::fs::write(&source, "int main() { return 0; }").unwrap();
+
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+
+        assert_eq!(output.assembly, vec![asm.clone()]);
+        assert!(output.objects.is_empty());
+        assert!(output.executable.is_none());
+        assert!(output.timings.iter().any(|t| t.phase == Phase::Codegen));
+        assert!(asm.exists());
+    }
+
+    #[test]
+    fn test_output_to_stdout() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.c"), "int main() { return 3; }").unwrap();
+
+        // `-o -` writes the unit's text output to stdout instead of a file named `-`
+        for flag in ["-S", "-E", "--emit=llvm-ir"] {
+            let run = std::process::Command::new(env!("CARGO_BIN_EXE_alecc"))
+                .current_dir(dir.path())
+                .args([flag, "-o", "-", "main.c"])
+                .output()
+                .unwrap();
+            assert!(run.status.success(), "{}: {:?}", flag, run);
+            assert!(
+                String::from_utf8_lossy(&run.stdout).contains("main"),
+                "{}",
+                flag
+            );
+            assert!(!dir.path().join("-").exists(), "{}", flag);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_layout_reuses_sibling_scopes() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("frame.c");
+        let asm = dir.path().join("frame.s");
+        std::fs::write(
+            &source,
+            "int main() { int a = 1; { int b = 2; a = a + b; } { int c = 3; a = a + c; } return a; }",
+        )
+        .unwrap();
+
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "--target=i386",
+            "--fstack-usage",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+
+        // b and c share the slot below a
+        let assembly = std::fs::read_to_string(&asm).unwrap();
+        assert!(assembly.contains("mov DWORD PTR [ebp + -8], eax"));
+        assert!(!assembly.contains("[ebp + -12]"));
+
+        let su = dir.path().join("frame.su");
+        assert_eq!(output.stack_usage, vec![su.clone()]);
+        let report = std::fs::read_to_string(&su).unwrap();
+        assert!(report.ends_with(":main\t24\tstatic\n"));
+    }
+
+    #[tokio::test]
+    async fn test_translation_unit_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("units.c");
+        std::fs::write(
+            &source,
+            "int g;\nint small();\nint small() { return 1; }\nint big(int n) { int s = 0; while (n > 0) { s = s + n; n = n - 1; } return s; }\nint main() { return big(3) + small(); }\n",
+        )
+        .unwrap();
+
+        for emit in [None, Some("--emit=llvm-ir")] {
+            let out = dir.path().join("units.out");
+            let mut argv = vec!["alecc", "-S", "--summary", "-o", out.to_str().unwrap()];
+            argv.extend(emit);
+            argv.push(source.to_str().unwrap());
+            let output = Compiler::new(Args::parse_from(argv))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+
+            let [summary] = output.summaries.as_slice() else {
+                panic!("expected one summary, got {:?}", output.summaries);
+            };
+            assert_eq!(summary.input, source);
+            assert_eq!(summary.lines, 5);
+            assert_eq!(summary.functions, 3);
+            assert_eq!(summary.globals, 1);
+            assert!(summary.tokens > 50);
+            let names: Vec<_> = summary
+                .instructions
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect();
+            assert_eq!(names, ["small", "big", "main"]);
+            assert_eq!(summary.biggest_functions()[0].0, "big");
+            assert!(summary.total_instructions() > summary.biggest_functions()[0].1);
+            let report = summary.to_string();
+            assert!(report.contains("  functions:    3\n"));
+            assert!(report.contains("biggest functions:\n    big "));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.c");
+        let object = dir.path().join("main.o");
+        std::fs::write(&source, "int main() { return 0; }").unwrap();
+
+        let args = Args::parse_from([
+            "alecc",
+            "-###",
+            "-c",
+            "-o",
+            object.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        assert!(args.dry_run);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+
+        assert!(output.objects.is_empty());
+        assert!(!object.exists());
+    }
+
+    #[test]
+    fn test_diagnostics_grouped_in_input_order() {
+        use alecc::diagnostics::{Diagnostic, DiagnosticBuffer};
+
+        let mut buffer = DiagnosticBuffer::new(false);
+        buffer.report(1, Diagnostic::warning("second").with_file("b.c"));
+        buffer.report(0, Diagnostic::warning("first").with_file("a.c"));
+
+        // Unit 1 finishing first must not jump ahead of unit 0
+        buffer.finish_unit(1);
+        assert!(buffer.take_emitted().is_empty());
+
+        buffer.finish_unit(0);
+        let messages: Vec<String> = buffer
+            .take_emitted()
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(messages, vec!["first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_before_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let parse = dir.path().join("parse.c");
+        let sema = dir.path().join("sema.c");
+        std::fs::write(
+            &parse,
+            "int main(void) {\n    int *p = 0;\n    if (p == 3) return 0;\n    int x = ;\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &sema,
+            "int main(void) {\n    int n;\n    int *p = 0;\n    if (p == 3) return n;\n    return missing;\n}\n",
+        )
+        .unwrap();
+
+        // A unit's warnings are reported, with their place, before the error that stops it
+        for (input, expected, error) in [
+            (
+                &parse,
+                vec![(
+                    3,
+                    "comparison between pointer and integer [-Wpointer-integer-compare]",
+                )],
+                "Parse error at line 4",
+            ),
+            (
+                &sema,
+                vec![
+                    (
+                        4,
+                        "'n' is used uninitialized in function 'main' [-Wuninitialized]",
+                    ),
+                    (
+                        4,
+                        "comparison between pointer and integer [-Wpointer-integer-compare]",
+                    ),
+                ],
+                "Semantic error at line 5",
+            ),
+        ] {
+            for streaming in [false, true] {
+                let mut argv = vec!["alecc", "-S", "-o", "-", "-Wall"];
+                if streaming {
+                    argv.push("--stream-diagnostics");
+                }
+                argv.push(input.to_str().unwrap());
+                let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+                let message = compiler.compile().await.unwrap_err().to_string();
+                assert!(
+                    message.starts_with(&format!("{}: {}", input.display(), error)),
+                    "{}",
+                    message
+                );
+
+                let diagnostics: Vec<_> = compiler
+                    .diagnostics()
+                    .iter()
+                    .map(|d| (d.file.as_deref(), d.line, d.message.as_str()))
+                    .collect();
+                let expected: Vec<_> = expected
+                    .iter()
+                    .map(|&(line, message)| (Some(input.as_path()), line, message))
+                    .collect();
+                assert_eq!(diagnostics, expected, "streaming: {}", streaming);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_emit_llvm_ir() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.c");
+        let ir = dir.path().join("main.ll");
+        std::fs::write(
+            &source,
+            "int square(int x) { return x * x; }\nint main() { return square(3) - 9; }",
+        )
+        .unwrap();
+
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "amd64",
+            "--emit=llvm-ir",
+            "-o",
+            ir.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+
+        assert_eq!(output.llvm_ir, vec![ir.clone()]);
+        assert!(output.executable.is_none());
+        let text = std::fs::read_to_string(&ir).unwrap();
+        assert!(text.contains("target triple = \"x86_64-unknown-linux-gnu\""));
+        assert!(text.contains("define i32 @square(i32 %arg0)"));
+        assert!(text.contains("call i32 (i32) @square(i32 3)"));
+
+        // Globals initialized with addresses become constant expressions
+        let ir = dir.path().join("global_addresses.ll");
+        let args = Args::parse_from([
+            "alecc",
+            "--emit=llvm-ir",
+            "-o",
+            ir.to_str().unwrap(),
+            fixture("global_addresses.c").to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&ir).unwrap();
+        for global in [
+            "@pointer = global ptr @value\n",
+            "@function = global ptr @nine\n",
+            "@greeting = global ptr @.str.global_addresses_0\n",
+            "@suffix = global ptr getelementptr (i8, ptr @.str.global_addresses_1, i64 1)\n",
+            "@member = global ptr getelementptr (i8, ptr @origin, i64 4)\n",
+            "@address = global i64 ptrtoint (ptr @value to i64)\n",
+            "@letters = global [8 x i8] c\"abc\\00\\00\\00\\00\\00\"\n",
+            "@main.indirect = internal global ptr @pointer\n",
+        ] {
+            assert!(text.contains(global), "{}", global);
+        }
+        if which::which("lli").is_ok() {
+            let run = std::process::Command::new("lli")
+                .arg("-opaque-pointers")
+                .arg(&ir)
+                .output()
+                .unwrap();
+            assert_eq!(run.status.code(), Some(49), "{:?}", run);
+        }
+    }
+
+    #[cfg(not(feature = "cranelift"))]
+    #[test]
+    fn test_cranelift_backend_requires_feature() {
+        let args = Args::parse_from(["alecc", "--backend=cranelift", "main.c"]);
+        assert_eq!(args.backend, Backend::Cranelift);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[cfg(feature = "cranelift")]
+    #[tokio::test]
+    async fn test_cranelift_backend_writes_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.c");
+        let object = dir.path().join("main.o");
+        std::fs::write(
+            &source,
+            "int square(int x) { return x * x; }\nint main() { return square(3) - 9; }",
+        )
+        .unwrap();
+
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "amd64",
+            "--backend=cranelift",
+            "-c",
+            "-o",
+            object.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+
+        assert_eq!(output.objects, vec![object.clone()]);
+        assert!(std::fs::read(&object).unwrap().starts_with(b"\x7fELF"));
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[tokio::test]
+    async fn test_freestanding_hello_world() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("hello_write.c");
+
+        // x86 executables run on the host; arm64 is only assembled, with
+        // llvm-mc as there is usually no aarch64 binutils to link with
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("hello_{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.stdout, b"hello, world\n", "target {}", target);
+            assert_eq!(run.status.code(), Some(0), "target {}", target);
+        }
+
+        if which::which("llvm-mc").is_ok() {
+            let object = dir.path().join("hello_arm64.o");
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                "arm64",
+                "--as-dialect=llvm",
+                "-c",
+                "-o",
+                object.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            assert!(object.exists());
+
+            let runtime = dir.path().join("runtime_arm64.s");
+            std::fs::write(
+                &runtime,
+                alecc::runtime::runtime_assembly(Target::Arm64, AsDialect::Llvm),
+            )
+            .unwrap();
+            let status = std::process::Command::new("llvm-mc")
+                .args(["--triple=aarch64-unknown-linux-gnu", "--filetype=obj", "-o"])
+                .arg(dir.path().join("runtime_arm64.o"))
+                .arg(&runtime)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nostartfiles_uses_program_entry_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("entry");
+        let source = fixture("nostartfiles.c");
+
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "amd64",
+            "--nostdlib",
+            "--nostartfiles",
+            "-o",
+            exe.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.stdout, b"no start files\n");
+        assert_eq!(run.status.code(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_increment_any_lvalue() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("increments.c");
+
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("increments-{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(26), "target {}", target);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_symbol_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib-one.c");
+        let main = dir.path().join("main.c");
+        std::fs::write(
+            &lib,
+            "static int helper(int x) { return x * 2; }\nint twice(int x) { char *s = \"x\"; if (x) return helper(x); return s[0]; }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &main,
+            "int twice(int x);\nstatic int helper(int x) { return x + 1; }\nint main() { return twice(helper(4)); }\n",
+        )
+        .unwrap();
+
+        // Without a prefix, local labels still carry the file name
+        let asm = dir.path().join("lib.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            lib.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let assembly = std::fs::read_to_string(&asm).unwrap();
+        assert!(assembly.contains(".Llib_one_C0:"));
+        assert!(!assembly.contains(".LC0"));
+        assert!(assembly.contains("helper:"));
+
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "--symbol-prefix=one_",
+            "-o",
+            asm.to_str().unwrap(),
+            lib.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let assembly = std::fs::read_to_string(&asm).unwrap();
+        assert!(assembly.contains(".Lone_C0:"));
+        assert!(assembly.contains("one_helper:"));
+        assert!(assembly.contains("call one_helper"));
+        assert!(!assembly.contains("one_twice"));
+
+        let exe = dir.path().join("prefixed");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "amd64",
+            "--nostdlib",
+            "--symbol-prefix=tu_",
+            "-o",
+            exe.to_str().unwrap(),
+            lib.to_str().unwrap(),
+            main.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(10));
+
+        let args = Args::parse_from(["alecc", "--symbol-prefix=a-b", "x.c"]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_postfix_chains() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("postfix_chains.c");
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("postfix_chains-{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(94), "target {}", target);
+        }
+
+        let ir = dir.path().join("postfix_chains.ll");
+        let args = Args::parse_from([
+            "alecc",
+            "--emit=llvm-ir",
+            "-o",
+            ir.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let ir = std::fs::read_to_string(&ir).unwrap();
+        // `make_pair(3).hi` reads the returned struct through a temporary
+        assert_eq!(ir.matches("alloca { i16, i16 }").count(), 2);
+    }
+
+    #[test]
+    fn test_host_tool_discovery() {
+        use alecc::host::{find_tool, under_sysroot, Host};
+
+        // Off Linux only cross-prefixed or LLVM tools can produce ELF
+        let candidates = Host::MacOs.tool_candidates("ld", Target::Arm64);
+        assert_eq!(candidates, vec!["aarch64-linux-gnu-ld", "ld.lld"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join(format!(
+            "x86_64-linux-gnu-as{}",
+            std::env::consts::EXE_SUFFIX
+        ));
+        std::fs::write(&tool, "").unwrap();
+        let candidates = Host::Windows.tool_candidates("as", Target::Amd64);
+        assert_eq!(
+            find_tool(&candidates, Some(dir.path())),
+            tool.to_string_lossy()
+        );
+
+        assert_eq!(
+            under_sysroot(Some(dir.path()), "/usr/include"),
+            dir.path().join("usr/include")
+        );
+        assert_eq!(
+            under_sysroot(None, "/usr/include"),
+            PathBuf::from("/usr/include")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stack_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("stack_arguments.c");
+        let mut targets = vec!["amd64", "i386"];
+        // arm64 only runs where there is a cross linker and an emulator
+        let arm64_runs =
+            which::which("aarch64-linux-gnu-ld").is_ok() && which::which("qemu-aarch64").is_ok();
+        if arm64_runs {
+            targets.push("arm64");
+        }
+
+        for target in targets {
+            let exe = dir.path().join(format!("stack_arguments-{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = if target == "arm64" {
+                std::process::Command::new("qemu-aarch64")
+                    .arg(&exe)
+                    .output()
+            } else {
+                std::process::Command::new(&exe).output()
+            };
+            assert_eq!(run.unwrap().status.code(), Some(63), "target {}", target);
+        }
+
+        let asm = dir.path().join("stack_arguments-arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        // Three stack arguments take 8-byte slots, padded to 32 bytes, and
+        // the callee reads the third one 16 bytes above its frame record
+        assert!(asm.contains("    sub sp, sp, #32\n    mov x0, #5\n    str x0, [sp, #0]"));
+        assert!(asm.contains("    str x0, [sp, #16]\n"));
+        assert!(asm.contains("    ldr x9, [x29, #32]\n"));
+    }
+
+    #[tokio::test]
+    async fn test_discarded_values_keep_stack_balanced() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("discarded_values");
+        let source = fixture("discarded_values.c");
+        let args = Args::parse_from([
+            "alecc",
+            "--nostdlib",
+            "-o",
+            exe.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_language_standards() {
+        use alecc::language::{LanguageOptions, Standard};
+
+        let c89 = LanguageOptions::from_std("ansi").unwrap();
+        assert_eq!((c89.standard, c89.gnu), (Standard::C89, false));
+        let gnu11 = LanguageOptions::from_std("gnu11").unwrap();
+        assert_eq!((gnu11.standard, gnu11.gnu), (Standard::C11, true));
+        assert!(LanguageOptions::from_std("c77").is_err());
+
+        // C89 has no `//` comments and `inline` is an ordinary identifier there
+        let lex = |source: &str, language| {
+            Lexer::new(source.to_string())
+                .with_language(language)
+                .tokenize()
+        };
+        assert!(lex("int x; // comment", c89).is_err());
+        let tokens = lex("int inline;", c89).unwrap();
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::Identifier("inline".to_string())
+        );
+        let c23 = LanguageOptions::from_std("c23").unwrap();
+        assert_eq!(
+            lex("alignof", c23).unwrap()[0].token_type,
+            TokenType::Alignof
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("version.c");
+        std::fs::write(&source, "int main() { return __STDC_VERSION__ % 100; }").unwrap();
+        for (standard, status) in [("c11", 12), ("gnu99", 1)] {
+            let exe = dir.path().join(standard);
+            let args = Args::parse_from([
+                "alecc",
+                "--nostdlib",
+                "--std",
+                standard,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(status), "--std={}", standard);
+        }
+
+        std::fs::write(&source, "int f(int n) { int a[n]; return 0; }").unwrap();
+        let args = Args::parse_from([
+            "alecc",
+            "--std=c89",
+            "-S",
+            "-o",
+            dir.path().join("vla.s").to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(error.to_string().contains("variable length arrays"));
+    }
+
+    #[tokio::test]
+    async fn test_sema_scopes_and_enumerators() {
+        use alecc::sema::Sema;
+
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("enum_scopes");
+        let source = fixture("enum_scopes.c");
+        let args = Args::parse_from([
+            "alecc",
+            "--nostdlib",
+            "-o",
+            exe.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(19));
+
+        let check = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let mut program = Parser::new(tokens).parse().unwrap();
+            Sema::new()
+                .analyze(&mut program)
+                .map_err(|error| error.to_string())
+        };
+        assert!(check("int main() { int a; { int a; } return 0; }").is_ok());
+        assert!(check("int main() { int a; int a; return 0; }")
+            .unwrap_err()
+            .contains("redefinition of 'a'"));
+        assert!(check("int f(int a) { int a; return 0; }")
+            .unwrap_err()
+            .contains("'a' redeclared as different kind of symbol"));
+        assert!(check("int g; int g = 1; int g = 2;")
+            .unwrap_err()
+            .contains("redefinition of 'g'"));
+    }
+
+    #[tokio::test]
+    async fn test_pie_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("pie.c");
+
+        for (flag, elf_type) in [(None, 3u8), (Some("--no-pie"), 2u8)] {
+            let exe = dir
+                .path()
+                .join(if flag.is_some() { "pie-off" } else { "pie-on" });
+            let mut argv = vec![
+                "alecc",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ];
+            argv.extend(flag);
+            Compiler::new(Args::parse_from(argv))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+
+            // e_type: ET_DYN for a PIE, ET_EXEC otherwise
+            let elf = std::fs::read(&exe).unwrap();
+            assert_eq!(elf[16], elf_type, "{:?}", flag);
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(7), "{:?}", flag);
+        }
+
+        let asm = dir.path().join("pie.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        assert!(asm.contains("    call puts@PLT\n"));
+        assert!(asm.contains("    call bump\n"));
+        assert!(asm.contains("DWORD PTR [rip + counter]"));
+
+        // Nothing would relocate a PIE without the dynamic loader
+        for flag in ["--nostdlib", "--static"] {
+            let args = Args::parse_from(["alecc", "--pie", flag, source.to_str().unwrap()]);
+            assert!(Compiler::new(args).is_err(), "{}", flag);
+        }
+        let args = Args::parse_from(["alecc", "--pie", "-t", "i386", source.to_str().unwrap()]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ident_in_comment_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("ident.c");
+        std::fs::write(&source, "int main() { return 0; }").unwrap();
+        let contains = |haystack: &[u8], needle: &str| {
+            haystack
+                .windows(needle.len())
+                .any(|window| window == needle.as_bytes())
+        };
+
+        let object = dir.path().join("ident.o");
+        let args = Args::parse_from([
+            "alecc",
+            "-c",
+            "-O1",
+            "--std=c99",
+            "-t",
+            "amd64",
+            "-o",
+            object.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let bytes = std::fs::read(&object).unwrap();
+        let ident = format!(
+            "alecc {} (--target=amd64 -O1 --std=c99 --pie)\0",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert!(contains(&bytes, &ident));
+        assert!(contains(&bytes, ".comment"));
+
+        let args = Args::parse_from([
+            "alecc",
+            "-c",
+            "--no-ident",
+            "-o",
+            object.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let bytes = std::fs::read(&object).unwrap();
+        assert!(!contains(&bytes, "alecc "));
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn test_emit_ast_requires_feature() {
+        let args = Args::parse_from(["alecc", "--emit-ast=json", "main.c"]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_emit_ast_json() {
+        use alecc::parser::{ExpressionKind, Program, StatementKind};
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.c");
+        let ast = dir.path().join("main.ast.json");
+        std::fs::write(
+            &source,
+            "enum answers { ANSWER = 42 } last;\nint main() {\n    return ANSWER;\n}\n",
+        )
+        .unwrap();
+
+        let args = Args::parse_from([
+            "alecc",
+            "--emit-ast=json",
+            "-o",
+            ast.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+        assert_eq!(output.ast, vec![ast.clone()]);
+        assert!(output.executable.is_none());
+
+        // The JSON reads back into the same tree, with sema's folding applied
+        let json = std::fs::read_to_string(&ast).unwrap();
+        let program: Program = serde_json::from_str(&json).unwrap();
+        let main = &program.functions[0];
+        assert_eq!(main.name, "main");
+        let StatementKind::Block(body) = &main.body.kind else {
+            panic!("main has no block body");
+        };
+        let StatementKind::Return(Some(value)) = &body[0].kind else {
+            panic!("expected a return statement");
+        };
+        assert!(matches!(value.kind, ExpressionKind::IntegerLiteral(42)));
+        assert_eq!(body[0].span.line, 3);
+    }
+
+    #[test]
+    fn test_undeclared_identifier() {
+        use alecc::error::AleccError;
+        use alecc::lexer::Span;
+        use alecc::sema::Sema;
+
+        let analyze = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let mut program = Parser::new(tokens).parse().unwrap();
+            Sema::new().analyze(&mut program)
+        };
+
+        let error = analyze("int main() {\n    int total = 1;\n    return total + missing;\n}")
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            AleccError::SemanticError {
+                span: Some(Span {
+                    line: 3,
+                    column: 20
+                }),
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Semantic error at line 3, column 20: use of undeclared identifier 'missing'"
+        );
+
+        // Out of scope once its block ends
+        assert!(analyze("int main() { { int inner = 2; } return inner; }").is_err());
+        // Calls to undeclared functions are implicit declarations, not errors
+        assert!(analyze("int main() { return helper(1); }").is_ok());
+        assert!(analyze("int main() { return helper(unknown); }").is_err());
+    }
+
+    #[test]
+    fn test_call_argument_checks() {
+        use alecc::language::LanguageOptions;
+        use alecc::sema::Sema;
+
+        let prelude = "typedef struct point { int x; int y; } point;\n\
+                       int add(int a, int b);\n\
+                       int sum(int n, ...);\n\
+                       int legacy();\n\
+                       int none(void);\n\
+                       void take_point(point p);\n\
+                       void take_ptr(int *p);\n\
+                       void take_void(void *p);\n";
+        let analyze = |body: &str, std: &str| {
+            let language = LanguageOptions::from_std(std).unwrap();
+            let source = format!(
+                "{}int main() {{ point pt; long l; char c; (void)pt; (void)l; (void)c; {} return 0; }}",
+                prelude, body
+            );
+            let tokens = Lexer::new(source)
+                .with_language(language)
+                .tokenize()
+                .unwrap();
+            let mut program = Parser::new(tokens).with_language(language).parse().unwrap();
+            let mut sema = Sema::new();
+            sema.analyze(&mut program)
+                .map(|()| sema.warnings().iter().map(|w| w.option).collect::<Vec<_>>())
+        };
+        let error = |body: &str| analyze(body, "gnu17").unwrap_err().to_string();
+
+        let ok = "add(1, 2); sum(1, 2, 3); take_ptr(0); take_void(&pt); take_point(pt); add(l, c); none();";
+        assert_eq!(analyze(ok, "gnu17").unwrap(), Vec::<&str>::new());
+
+        assert!(error("add(1, 2, 3);")
+            .ends_with("too many arguments to function 'add'; expected 2, have 3"));
+        assert!(
+            error("add(1);").ends_with("too few arguments to function 'add'; expected 2, have 1")
+        );
+        assert!(error("none(1);").contains("too many arguments to function 'none'"));
+        assert!(error("sum();").contains("too few arguments to function 'sum'"));
+        assert!(
+            error("take_point(1);").ends_with("incompatible type for argument 1 of 'take_point'")
+        );
+        assert!(error("add(&pt, 1);").ends_with("makes integer from pointer without a cast"));
+        assert!(error("take_ptr(5);").ends_with("makes pointer from integer without a cast"));
+
+        // Mismatched pointers and calls without a prototype are only warned about
+        assert_eq!(
+            analyze("take_ptr(&l);", "gnu17").unwrap(),
+            ["incompatible-pointer-types"]
+        );
+        assert_eq!(
+            analyze("legacy(1, 2);", "gnu17").unwrap(),
+            ["deprecated-non-prototype"]
+        );
+        // C23 made `()` mean `(void)`
+        assert!(analyze("legacy(1, 2);", "c23").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_compile_cache_reuses_headers_and_tokens() {
+        use alecc::cache::CompileCache;
+
+        let dir = tempfile::tempdir().unwrap();
+        let header = dir.path().join("answer.h");
+        let source = dir.path().join("main.c");
+        std::fs::write(&header, "int answer(void);\n").unwrap();
+        std::fs::write(
+            &source,
+            "#include \"answer.h\"\nint answer(void) { return 42; }\nint main(void) { return answer(); }\n",
+        )
+        .unwrap();
+
+        let cache = CompileCache::new();
+        let compile = |cache: &CompileCache| {
+            let args = Args::parse_from([
+                "alecc",
+                "-S",
+                "-I",
+                dir.path().to_str().unwrap(),
+                "-o",
+                dir.path().join("main.s").to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            let compiler = Compiler::new(args).unwrap().with_cache(cache.clone());
+            async move {
+                let mut compiler = compiler;
+                compiler.compile().await.unwrap()
+            }
+        };
+
+        compile(&cache).await;
+        assert_eq!(cache.hits(), (0, 0));
+        compile(&cache).await;
+        assert_eq!(cache.hits(), (1, 1));
+
+        // A changed header is read again, and its new tokens lexed
+        std::fs::write(&header, "int answer(void);\nint unused(void);\n").unwrap();
+        compile(&cache).await;
+        assert_eq!(cache.hits(), (1, 1));
+    }
+
+    #[cfg(not(all(feature = "daemon", unix)))]
+    #[test]
+    fn test_daemon_requires_feature() {
+        // The flag parses either way; main refuses to serve without the feature
+        let args = Args::parse_from(["alecc", "--daemon", "/tmp/alecc.sock"]);
+        assert_eq!(args.daemon, Some(PathBuf::from("/tmp/alecc.sock")));
+    }
+
+    #[cfg(all(feature = "daemon", unix))]
+    #[tokio::test]
+    async fn test_daemon_compile_jobs() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::UnixStream;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("alecc.sock");
+        let source = dir.path().join("main.c");
+        let object = dir.path().join("main.o");
+        std::fs::write(
+            &source,
+            "int later();\nint main(void) {\n    return later(1);\n}\n",
+        )
+        .unwrap();
+
+        let server = tokio::spawn({
+            let socket = socket.clone();
+            async move { alecc::daemon::serve(&socket).await }
+        });
+        let mut stream = loop {
+            match UnixStream::connect(&socket).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        let job = serde_json::json!({
+            "args": ["-c", "-o", object.to_str().unwrap(), source.to_str().unwrap()]
+        });
+        // The same job twice, the second from the warm caches, then a bad command line
+        let jobs = format!(
+            "{}\n{}\n{}\n",
+            job,
+            job,
+            serde_json::json!({ "args": ["--no-such-flag"] })
+        );
+        stream.write_all(jobs.as_bytes()).await.unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let mut replies = Vec::new();
+        for _ in 0..3 {
+            let reply = lines.next_line().await.unwrap().unwrap();
+            replies.push(serde_json::from_str::<serde_json::Value>(&reply).unwrap());
+        }
+
+        assert_eq!(replies[0]["success"], true);
+        assert_eq!(replies[0]["objects"][0], object.to_str().unwrap());
+        assert!(object.exists());
+        let diagnostic = &replies[0]["diagnostics"][0];
+        assert_eq!(diagnostic["severity"], "warning");
+        assert_eq!(diagnostic["line"], 3);
+        assert_eq!(replies[1], replies[0]);
+
+        assert_eq!(replies[2]["success"], false);
+        assert!(replies[2]["error"]
+            .as_str()
+            .unwrap()
+            .contains("--no-such-flag"));
+
+        server.abort();
+    }
+
+    #[cfg(not(all(feature = "daemon", unix)))]
+    #[test]
+    fn test_remote_requires_feature() {
+        let args = Args::parse_from(["alecc", "--remote", "localhost:7000", "main.c"]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[cfg(all(feature = "daemon", unix))]
+    #[tokio::test]
+    async fn test_remote_compilation() {
+        use alecc::compiler::Phase;
+
+        let dir = tempfile::tempdir().unwrap();
+        let include = dir.path().join("include");
+        std::fs::create_dir(&include).unwrap();
+        // Only the local driver can see the header; the daemon gets preprocessed text
+        std::fs::write(
+            include.join("base.h"),
+            "#define BASE 40\nint bump(int x);\n",
+        )
+        .unwrap();
+        let source = dir.path().join("main.c");
+        std::fs::write(
+            &source,
+            "#include \"base.h\"\nint bump(int x) { return x + 2; }\nint main(void) { return bump(BASE); }\n",
+        )
+        .unwrap();
+        let warns = dir.path().join("warns.c");
+        std::fs::write(
+            &warns,
+            "int later();\nint main(void) {\n    return later(1);\n}\n",
+        )
+        .unwrap();
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let address = format!("127.0.0.1:{}", port);
+        let server = tokio::spawn({
+            let address = PathBuf::from(&address);
+            async move { alecc::daemon::serve(&address).await }
+        });
+        while tokio::net::TcpStream::connect(&address).await.is_err() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        // Compiled remotely, linked here
+        let executable = dir.path().join("main");
+        let args = Args::parse_from([
+            "alecc",
+            "--remote",
+            &address,
+            "-I",
+            include.to_str().unwrap(),
+            "-o",
+            executable.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+        assert!(output.timings.iter().any(|t| t.phase == Phase::Remote));
+        assert!(!output.timings.iter().any(|t| t.phase == Phase::Codegen));
+        let status = std::process::Command::new(&executable).status().unwrap();
+        assert_eq!(status.code(), Some(42));
+
+        // Remote warnings point into the local file
+        let object = dir.path().join("warns.o");
+        let args = Args::parse_from([
+            "alecc",
+            "--remote",
+            &address,
+            "-c",
+            "-o",
+            object.to_str().unwrap(),
+            warns.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+        assert_eq!(output.objects, vec![object.clone()]);
+        assert_eq!(&std::fs::read(&object).unwrap()[..4], b"\x7fELF");
+        assert_eq!(output.diagnostics.len(), 1);
+        assert_eq!(output.diagnostics[0].file.as_deref(), Some(warns.as_path()));
+        assert_eq!(output.diagnostics[0].line, 3);
+
+        server.abort();
+
+        let args = Args::parse_from(["alecc", "--remote", &address, "-S", "main.c"]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        use alecc::cli::{Command, DocFormat};
+        use alecc::doc::{document, render_html, render_markdown, HeaderDocs, ItemKind};
+
+        let args = Args::parse_from(["alecc", "doc", "--format", "html", "api.h"]);
+        let Some(Command::Doc(doc)) = args.command else {
+            panic!("`alecc doc` is not the doc subcommand");
+        };
+        assert_eq!(doc.format, DocFormat::Html);
+        assert_eq!(doc.headers, vec![PathBuf::from("api.h")]);
+
+        let source = std::fs::read_to_string("tests/fixtures/doc_api.h").unwrap();
+        let items = document(&source).unwrap();
+        let names: Vec<(&str, ItemKind)> = items
+            .iter()
+            .map(|item| (item.name.as_str(), item.kind))
+            .collect();
+        // Undocumented declarations, the include guard and comments inside
+        // bodies don't produce entries
+        assert_eq!(
+            names,
+            [
+                ("API_MAX_BUFFER", ItemKind::Macro),
+                ("point_t", ItemKind::Type),
+                ("enum shape", ItemKind::Type),
+                ("api_add", ItemKind::Function),
+                ("api_calls", ItemKind::Variable),
+                ("api_handler", ItemKind::Variable),
+                ("api_max", ItemKind::Function),
+            ]
+        );
+
+        let add = &items[3];
+        assert_eq!(add.declaration, "int api_add(int a, int b)");
+        assert_eq!(add.docs.paragraphs, ["Adds two numbers."]);
+        assert_eq!(
+            add.docs.params,
+            [
+                ("a".to_string(), "the first number".to_string()),
+                (
+                    "b".to_string(),
+                    "the second number, which may be negative".to_string()
+                ),
+            ]
+        );
+        assert_eq!(add.docs.returns.as_deref(), Some("a + b"));
+        assert_eq!(items[1].docs.paragraphs.len(), 2);
+        assert_eq!(
+            items[6].declaration,
+            "static inline int api_max(int a, int b)"
+        );
+
+        let headers = [HeaderDocs {
+            path: PathBuf::from("doc_api.h"),
+            items,
+        }];
+        let markdown = render_markdown("API", &headers);
+        assert!(markdown.starts_with("# API\n\n## `doc_api.h`\n"));
+        assert!(markdown
+            .contains("### `api_add` (function)\n\n```c\nint api_add(int a, int b);\n```\n"));
+        assert!(markdown.contains("- `b`: the second number, which may be negative\n"));
+        let html = render_html("API <draft>", &headers);
+        assert!(html.contains("<title>API &lt;draft&gt;</title>"));
+        assert!(html.contains("<p><strong>Returns:</strong> a + b</p>"));
+    }
+
+    #[test]
+    fn test_return_type_problems() {
+        use alecc::analysis::{return_problems, ReturnProblem};
+        use std::collections::HashSet;
+
+        let source = "_Noreturn void fail(int code);\n\
+                      void log_it(int x) { }\n\
+                      int sign(int x) { if (x > 0) return 1; else if (x < 0) return -1; }\n\
+                      int checked(int x) { if (x) return x; fail(2); }\n\
+                      int quits(int x) { if (x) return x; exit(1); }\n\
+                      int loops(void) { while (1) { } }\n\
+                      void bad(int x) {\n    return x + 1;\n}\n\
+                      void forward(int x) { return log_it(x); }\n\
+                      int empty(int x) {\n    if (x)\n        return;\n    return 2;\n}\n\
+                      int main(void) { sign(1); }";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        assert!(program.functions[0].attributes.noreturn);
+
+        let noreturn: HashSet<String> = ["fail".to_string()].into();
+        let void_functions: HashSet<String> = ["fail", "log_it", "bad", "forward"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let problems = |name: &str| {
+            let function = program.functions.iter().find(|f| f.name == name).unwrap();
+            return_problems(function, &noreturn, &void_functions)
+                .into_iter()
+                .map(|problem| match problem {
+                    ReturnProblem::FallsOff => "falls off".to_string(),
+                    ReturnProblem::ValueInVoid(span) => format!("value at {}", span.line),
+                    ReturnProblem::MissingValue(span) => format!("missing at {}", span.line),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(problems("sign"), ["falls off"]);
+        assert_eq!(problems("bad"), ["value at 8"]);
+        assert_eq!(problems("empty"), ["missing at 13"]);
+        for fine in ["log_it", "checked", "quits", "loops", "forward", "main"] {
+            assert!(problems(fine).is_empty(), "{} should be fine", fine);
+        }
+
+        // The other spellings of _Noreturn
+        for spelling in ["__noreturn", "[[noreturn]]"] {
+            let source = format!("{} void stop(void);", spelling);
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            assert!(program.functions[0].attributes.noreturn, "{}", spelling);
+        }
+    }
+
+    #[test]
+    fn test_fmt_source() {
+        use alecc::cli::BraceStyle;
+        use alecc::fmt::{format_source, Style};
+
+        let source = "#include <stdio.h>\n\
+                      typedef struct node { int value; struct node* next; } node_t;\n\
+                      static int table[]={1,2,3};\n\
+                      int sum(node_t*n)\n\
+                      {\n\
+                      \x20 int total=0;   // running total\n\
+                      \x20 while(n){total+=n->value*-1;n=n->next;}\n\
+                      \x20 switch(total){\n\
+                      \x20 case 0: return (int)sizeof(node_t);\n\
+                      \x20 default: break;\n\
+                      \x20 }\n\n\n\n\
+                      \x20 if(total>table[0])\n\
+                      \x20   return total;\n\
+                      \x20 else\n\
+                      \x20 {\n\
+                      \x20   return -total;\n\
+                      \x20 }\n\
+                      }\n";
+
+        // Trivia keeps every character of the source
+        let mut lexer = Lexer::new(source.to_string()).with_trivia();
+        lexer.tokenize().unwrap();
+        let trivia = lexer.take_trivia();
+        let text: String = trivia
+            .iter()
+            .map(|trivia| format!("{}{}", trivia.leading_text(), trivia.text))
+            .collect();
+        assert_eq!(text, source);
+
+        let attach = format_source(source, &Style::default()).unwrap();
+        assert_eq!(
+            attach,
+            "#include <stdio.h>\n\
+             typedef struct node { int value; struct node *next; } node_t;\n\
+             static int table[] = {1, 2, 3};\n\
+             int sum(node_t *n) {\n\
+             \x20   int total = 0; // running total\n\
+             \x20   while (n) { total += n->value * -1; n = n->next; }\n\
+             \x20   switch (total) {\n\
+             \x20   case 0: return (int)sizeof(node_t);\n\
+             \x20   default: break;\n\
+             \x20   }\n\n\
+             \x20   if (total > table[0])\n\
+             \x20       return total;\n\
+             \x20   else {\n\
+             \x20       return -total;\n\
+             \x20   }\n\
+             }\n"
+        );
+        assert_eq!(format_source(&attach, &Style::default()).unwrap(), attach);
+
+        let allman = Style {
+            indent_width: 2,
+            brace_style: BraceStyle::Allman,
+            ..Style::default()
+        };
+        let formatted = format_source(source, &allman).unwrap();
+        assert!(formatted.contains("int sum(node_t *n)\n{\n  int total = 0;"));
+        assert!(
+            formatted.contains("  while (n)\n  {\n    total += n->value * -1; n = n->next;\n  }\n")
+        );
+        assert!(formatted.contains("typedef struct node\n{\n  int value;"));
+        assert_eq!(format_source(&formatted, &allman).unwrap(), formatted);
+
+        let linux = Style {
+            brace_style: BraceStyle::Linux,
+            use_tabs: true,
+            ..Style::default()
+        };
+        let formatted = format_source(&attach, &linux).unwrap();
+        assert!(formatted.contains("int sum(node_t *n)\n{\n\tint total = 0;"));
+        assert!(formatted.contains("\tswitch (total) {\n\tcase 0:"));
+    }
+
+    #[test]
+    fn test_unused_warnings() {
+        use alecc::sema::Sema;
+
+        let source = "static int helper(int a) { return a; }\n\
+                      static int orphan(int a) { return a; }\n\
+                      static inline int inlined(void) { return 1; }\n\
+                      __attribute__((unused)) static int kept(void) { return 2; }\n\
+                      int f(int used, int idle, int quiet __attribute__((unused)), [[maybe_unused]] int also) {\n\
+                      \x20   int x = 1;\n\
+                      \x20   int y;\n\
+                      \x20   int z;\n\
+                      \x20   [[maybe_unused]] int w;\n\
+                      \x20   int v __attribute__((unused));\n\
+                      \x20   (void)z;\n\
+                      \x20   { int y = 2; x += y; }\n\
+                      \x20   return helper(used) + x;\n\
+                      }\n\
+                      int prototype(int unnamed);\n";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let mut program = parser.parse().unwrap();
+        // `unused` applies to all of these, so none is an ignored attribute
+        assert!(parser.warnings().is_empty());
+
+        let mut sema = Sema::new();
+        sema.analyze(&mut program).unwrap();
+        let warnings: Vec<_> = sema
+            .warnings()
+            .iter()
+            .map(|w| (w.line, w.option, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                // Reported only under -Wshadow
+                (
+                    12,
+                    "shadow",
+                    "declaration of 'y' shadows a previous local [-Wshadow]"
+                ),
+                (
+                    5,
+                    "unused-parameter",
+                    "unused parameter 'idle' [-Wunused-parameter]"
+                ),
+                (
+                    7,
+                    "unused-variable",
+                    "unused variable 'y' [-Wunused-variable]"
+                ),
+                (
+                    2,
+                    "unused-function",
+                    "'orphan' defined but not used [-Wunused-function]"
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trivia_and_macro_expansion() {
+        use alecc::lexer::{TriviaKind, TriviaPiece};
+
+        let mut lexer =
+            Lexer::new("int x; /* one\n two */ \\\n  // end\nx".to_string()).with_trivia();
+        let tokens = lexer.tokenize().unwrap();
+        let trivia = lexer.take_trivia();
+        assert_eq!(tokens.len(), trivia.len());
+        let newline = tokens
+            .iter()
+            .position(|token| token.token_type == TokenType::Newline)
+            .unwrap();
+        let kinds: Vec<_> = trivia[newline]
+            .leading
+            .iter()
+            .map(|piece| (piece.kind, piece.span.line, piece.span.column))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (TriviaKind::Whitespace, 1, 7),
+                (TriviaKind::BlockComment, 1, 8),
+                (TriviaKind::Whitespace, 2, 8),
+                (TriviaKind::LineSplice, 2, 9),
+                (TriviaKind::Whitespace, 3, 1),
+                (TriviaKind::LineComment, 3, 3),
+            ]
+        );
+        let comments: Vec<&str> = trivia[newline]
+            .comments()
+            .map(|piece: &TriviaPiece| piece.text.as_str())
+            .collect();
+        assert_eq!(comments, ["/* one\n two */", "// end"]);
+
+        // -E replaces whole names only, never inside comments or literals
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("macros.c");
+        let output = dir.path().join("macros.i");
+        std::fs::write(
+            &source,
+            "#define N 4\n#define M N\n#define SELF SELF + 1\n\
+             /* N here */ int NN = M; // N\n\
+             char *s = \"N\";\n\
+             int SELF;\n",
+        )
+        .unwrap();
+        let args = Args::parse_from([
+            "alecc",
+            "-E",
+            "-o",
+            output.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let preprocessed = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(
+            preprocessed.trim_start(),
+            "/* N here */ int NN = 4; // N\nchar *s = \"N\";\nint SELF + 1;\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_implicit_function_declaration() {
+        use alecc::diagnostics::Severity;
+        use alecc::language::LanguageOptions;
+        use alecc::sema::Sema;
+
+        let source = "int main() { printf(\"a\"); printf(\"b\"); frob(); va_end(0); return 0; }";
+        let analyze = |std: &str| {
+            let language = LanguageOptions::from_std(std).unwrap();
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let mut program = Parser::new(tokens).with_language(language).parse().unwrap();
+            let mut sema = Sema::new().with_language(language);
+            sema.analyze(&mut program).map(|()| {
+                sema.warnings()
+                    .iter()
+                    .map(|w| (w.message.clone(), w.note.clone().map(|(note, _)| note)))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        // Warned about once per function, with the header to include when known
+        let expected = vec![
+            (
+                "implicit declaration of function 'printf' [-Wimplicit-function-declaration]"
+                    .to_string(),
+                Some("include '<stdio.h>' or provide a declaration of 'printf'".to_string()),
+            ),
+            (
+                "implicit declaration of function 'frob' [-Wimplicit-function-declaration]"
+                    .to_string(),
+                Some("provide a declaration of 'frob'".to_string()),
+            ),
+        ];
+        assert_eq!(analyze("gnu17").unwrap(), expected);
+        assert_eq!(analyze("c89").unwrap(), expected);
+        let error = analyze("c99").unwrap_err().to_string();
+        assert!(error.contains("implicit declaration of function 'printf' is invalid in c99"));
+        assert!(error.contains("include '<stdio.h>'"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("implicit.c");
+        std::fs::write(&input, "int main() { puts(\"hi\"); return 0; }").unwrap();
+        let mut outcomes = Vec::new();
+        for extra in [
+            None,
+            Some("-Wno-implicit-function-declaration"),
+            Some("--std=c11"),
+        ] {
+            let output = dir.path().join("implicit.s");
+            let mut argv = vec!["alecc", "-S", "-o", output.to_str().unwrap()];
+            argv.extend(extra);
+            argv.push(input.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            outcomes.push(compiler.compile().await.map(|output| output.diagnostics));
+        }
+        let diagnostics = outcomes[0].as_ref().unwrap();
+        let severities: Vec<_> = diagnostics.iter().map(|d| (d.severity, d.line)).collect();
+        assert_eq!(severities, [(Severity::Warning, 1), (Severity::Note, 1)]);
+        assert!(diagnostics[1].message.contains("<stdio.h>"));
+        assert!(outcomes[1].as_ref().unwrap().is_empty());
+        assert!(outcomes[2].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_emit_callgraph() {
+        use alecc::callgraph::CallGraph;
+        use alecc::optimizer::{OptimizationLevel, Optimizer};
+        use alecc::sema::Sema;
+
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.c");
+        let util = dir.path().join("util.c");
+        std::fs::write(
+            &main,
+            "static int square(int x) { return x * x; }\n\
+             static int idle(void) { return 0; }\n\
+             int helper(int x);\n\
+             int main() { return helper(square(2)) + square(1); }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &util,
+            "static int square(int x) { return x; }\n\
+             int helper(int x) { return square(x); }\n",
+        )
+        .unwrap();
+
+        let exe = dir.path().join("prog");
+        let args = Args::parse_from([
+            "alecc",
+            "--nostdlib",
+            "-Wno-unused",
+            "--emit-callgraph=dot",
+            "-o",
+            exe.to_str().unwrap(),
+            main.to_str().unwrap(),
+            util.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+        let path = dir.path().join("prog.callgraph.dot");
+        assert_eq!(output.callgraph.as_ref(), Some(&path));
+
+        // Static functions are told apart by their unit
+        let (main, util) = (main.display(), util.display());
+        let expected = format!(
+            "digraph callgraph {{\n    node [shape=box];\n    \"{main}:idle\";\n    \
+             \"{main}:square\";\n    \"{util}:square\";\n    \"helper\";\n    \"main\";\n    \
+             \"helper\" -> \"{util}:square\";\n    \
+             \"main\" -> \"{main}:square\" [label=\"2\"];\n    \"main\" -> \"helper\";\n}}\n"
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+
+        // -O3 drops static functions no longer reachable once calls are inlined
+        let source = "static int square(int x) { return x * x; }\n\
+                      static int idle(void) { return 0; }\n\
+                      static int kept(void) { return 1; }\n\
+                      int main() { return square(3) + (*kept)(); }\n";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut program = Parser::new(tokens).parse().unwrap();
+        Sema::new().analyze(&mut program).unwrap();
+        let graph = CallGraph::build(&program);
+        assert_eq!(graph.reachable(["main"]).len(), 3);
+        Optimizer::new(OptimizationLevel::Aggressive)
+            .optimize(&mut program)
+            .unwrap();
+        let names: Vec<_> = program.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["kept", "main"]);
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn test_callgraph_json_requires_feature() {
+        let args = Args::parse_from(["alecc", "--emit-callgraph=json", "main.c"]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_callgraph_json() {
+        use alecc::callgraph::CallGraph;
+
+        let source = "int puts(const char *s);\nint main() { puts(\"a\"); puts(\"b\"); return 0; }";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&CallGraph::build(&program).to_json().unwrap()).unwrap();
+        assert_eq!(json["functions"]["puts"]["defined"], false);
+        assert_eq!(json["functions"]["main"]["defined"], true);
+        assert_eq!(
+            json["calls"],
+            serde_json::json!([{"caller": "main", "callee": "puts", "kind": "direct", "count": 2}])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_whole_program() {
+        use alecc::merge::merge_programs;
+        use alecc::parser::Program;
+
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.c");
+        let util = dir.path().join("util.c");
+        std::fs::write(
+            &main,
+            "static int square(int x) { return x * x; }\n\
+             int helper(int x);\n\
+             int twice(int x) { return x + x; }\n\
+             int main() { return helper(square(2)) + twice(1); }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &util,
+            "static int square(int x) { return x + 10; }\n\
+             int helper(int x) { int y = square(x); return y; }\n",
+        )
+        .unwrap();
+        let compile = |extra: &[&str], output: &std::path::Path| {
+            let mut argv = vec!["alecc", "--nostdlib", "--fwhole-program"];
+            argv.extend_from_slice(extra);
+            argv.extend(["-o", output.to_str().unwrap()]);
+            argv.extend([main.to_str().unwrap(), util.to_str().unwrap()]);
+            Args::parse_from(argv)
+        };
+
+        // Each unit keeps calling its own `square`
+        let exe = dir.path().join("prog");
+        Compiler::new(compile(&[], &exe))
+            .unwrap()
+            .compile()
+            .await
+            .unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(16));
+
+        // Only main stays exported, and -O3 drops what inlining made unused
+        let asm = dir.path().join("prog.s");
+        Compiler::new(compile(&["-O3", "-S"], &asm))
+            .unwrap()
+            .compile()
+            .await
+            .unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        assert!(asm.contains(".globl main"));
+        assert!(asm.contains("helper:") && !asm.contains(".globl helper"));
+        assert!(!asm.contains("twice:") && !asm.contains("square.0:"));
+
+        let parse = |source: &str| -> Program {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap()
+        };
+        let merge = |a: &str, b: &str| {
+            merge_programs(vec![
+                ("a.c".to_string(), parse(a)),
+                ("b.c".to_string(), parse(b)),
+            ])
+            .map(|merged| merged.program)
+        };
+        let error = |a: &str, b: &str| merge(a, b).unwrap_err().to_string();
+        assert!(error("int f() { return 1; }", "int f() { return 2; }")
+            .ends_with("multiple definition of 'f': in a.c and b.c"));
+        assert!(error("int f(int x);", "int f(int x, int y) { return x; }")
+            .ends_with("conflicting types for 'f': 1 parameters in a.c, 2 parameters in b.c"));
+        assert!(error("int f;", "int f(void) { return 0; }")
+            .ends_with("'f' is a variable in a.c but a function in b.c"));
+        assert!(error("int n = 1;", "int n = 2;").contains("multiple definition of 'n'"));
+
+        // An inline definition gives way to the external one; tentative
+        // definitions to the initialized one
+        let program = merge(
+            "inline int f(void) { return 1; } int n;",
+            "int f(void) { return 2; } int n = 3;",
+        )
+        .unwrap();
+        assert_eq!(program.functions.len(), 1);
+        assert!(!program.functions[0].is_inline);
+        assert_eq!(program.global_variables.len(), 1);
+        assert!(program.global_variables[0].initializer.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_noreturn_calls() {
+        use alecc::optimizer::{OptimizationLevel, Optimizer};
+        use alecc::parser::StatementKind;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("noreturn.c");
+        std::fs::write(
+            &source,
+            "_Noreturn void spin(void) { for (;;) {} }\n\
+             int pick(int x) { if (x) return x; spin(); x = 9; }\n\
+             static int count;\n\
+             void bump(void) { if (count) return; count = 5; }\n\
+             int main(void) { bump(); return pick(3) + count; }\n",
+        )
+        .unwrap();
+        let compile = |extra: &[&str], output: &std::path::Path| {
+            let mut argv = vec!["alecc", "--nostdlib"];
+            argv.extend_from_slice(extra);
+            argv.extend(["-o", output.to_str().unwrap(), source.to_str().unwrap()]);
+            Args::parse_from(argv)
+        };
+
+        // No "missing return" warning for `pick`, and `bump` still returns
+        // after falling off its end
+        let exe = dir.path().join("prog");
+        let output = Compiler::new(compile(&[], &exe))
+            .unwrap()
+            .compile()
+            .await
+            .unwrap();
+        assert!(output.diagnostics.is_empty());
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(8));
+
+        // At -O1 the code after `spin()` is gone, and so is the epilogue
+        let asm = dir.path().join("prog.s");
+        Compiler::new(compile(&["-O1", "-S"], &asm))
+            .unwrap()
+            .compile()
+            .await
+            .unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        let start = asm.find("\npick:").unwrap();
+        let end = start + asm[start..].find("\n\n").unwrap();
+        let pick = &asm[start..end];
+        assert!(pick
+            .trim_end()
+            .ends_with("call spin\n    .cfi_endproc\n.size pick, .-pick"));
+        assert_eq!(pick.lines().filter(|line| line.trim() == "ret").count(), 1);
+        assert!(!pick.contains(", 9"));
+
+        // Also in nested statements
+        let tokens = Lexer::new(
+            "int f(int x) { while (x) { abort(); x--; } exit(1); return x; }".to_string(),
+        )
+        .tokenize()
+        .unwrap();
+        let mut program = Parser::new(tokens).parse().unwrap();
+        Optimizer::new(OptimizationLevel::Basic)
+            .optimize(&mut program)
+            .unwrap();
+        let StatementKind::Block(statements) = &program.functions[0].body.kind else {
+            panic!("expected a block");
+        };
+        assert_eq!(statements.len(), 2);
+        let StatementKind::While { body, .. } = &statements[0].kind else {
+            panic!("expected a loop");
+        };
+        assert!(matches!(&body.kind, StatementKind::Block(statements) if statements.len() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_failing_assert() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("assert.c");
+        std::fs::write(
+            &source,
+            "#include <assert.h>\n\
+             #define LIMIT 3\n\
+             int check(int x) {\n\
+             \x20   assert(x < LIMIT &&\n\
+             \x20          x >= 0);\n\
+             \x20   return __LINE__;\n\
+             }\n\
+             int main(void) {\n\
+             \x20   if (check(1) != 6) return 1;\n\
+             \x20   return check(5);\n\
+             }\n",
+        )
+        .unwrap();
+        let source = source.to_str().unwrap();
+        let exe = dir.path().join("prog");
+        let compile = |extra: &[&str]| {
+            let mut argv = vec!["alecc", "-o", exe.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            argv.push(source);
+            Args::parse_from(argv)
+        };
+
+        Compiler::new(compile(&[]))
+            .unwrap()
+            .compile()
+            .await
+            .unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.signal(), Some(6));
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert!(stderr.contains(&format!(
+            "{}:4: check: Assertion `x < LIMIT && x >= 0' failed.",
+            source
+        )));
+
+        Compiler::new(compile(&["-DNDEBUG"]))
+            .unwrap()
+            .compile()
+            .await
+            .unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_code() {
+        use alecc::analysis::{noreturn_functions, unreachable_code};
+        use alecc::diagnostics::Severity;
+
+        let source = "_Noreturn void die(void) { for (;;) {} }\n\
+                      int f(int x) {\n\
+                      \x20   while (x) {\n\
+                      \x20       continue;\n\
+                      \x20       x--;\n\
+                      \x20   }\n\
+                      \x20   if (x) {\n\
+                      \x20       goto out;\n\
+                      \x20       x = 1;\n\
+                      \x20   out:\n\
+                      \x20       x = 2;\n\
+                      \x20   }\n\
+                      \x20   if (x == 5) {\n\
+                      \x20       die();\n\
+                      \x20       int y;\n\
+                      \x20       break;\n\
+                      \x20       x = 2;\n\
+                      \x20       x = 3;\n\
+                      \x20   }\n\
+                      \x20   return x;\n\
+                      }\n";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let noreturn = noreturn_functions(&program);
+        let found: Vec<_> = unreachable_code(&program.functions[1], &noreturn)
+            .into_iter()
+            .map(|found| (found.span.line, found.after.line, found.terminator))
+            .collect();
+        // Only the first statement of each unreachable run; the label ends one
+        assert_eq!(
+            found,
+            [
+                (5, 4, "'continue'".to_string()),
+                (9, 8, "'goto'".to_string()),
+                (17, 14, "call to 'die'".to_string()),
+            ]
+        );
+
+        // Opt-in, with a note at the statement control stops at
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("unreachable.c");
+        std::fs::write(&input, "int main(void) {\n    return 0;\n    main();\n}\n").unwrap();
+        let mut outcomes = Vec::new();
+        for extra in [None, Some("-Wunreachable-code")] {
+            let output = dir.path().join("unreachable.s");
+            let mut argv = vec!["alecc", "-S", "-o", output.to_str().unwrap()];
+            argv.extend(extra);
+            argv.push(input.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            outcomes.push(compiler.compile().await.unwrap().diagnostics);
+        }
+        assert!(outcomes[0].is_empty());
+        let diagnostics: Vec<_> = outcomes[1]
+            .iter()
+            .map(|d| (d.severity, d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    Severity::Warning,
+                    3,
+                    "code will never be executed [-Wunreachable-code]"
+                ),
+                (
+                    Severity::Note,
+                    2,
+                    "control does not continue past this 'return'"
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_constant_expressions() {
+        use alecc::parser::{StatementKind, Type};
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("constants.c");
+        std::fs::write(
+            &source,
+            "typedef struct pair { int a; long b; } pair;\n\
+             enum limits { BASE = 4, SHIFTED = BASE << 2, MASK = (1 << BASE) - 1, NEG = -BASE * 2 } last;\n\
+             _Static_assert(sizeof(pair) == 2 * sizeof(long), \"pair is padded\");\n\
+             int table[SHIFTED / 2 + 1];\n\
+             int limit = MASK + 2 * 3;\n\
+             int main(void) {\n\
+             \x20   int local[sizeof(pair) / 4];\n\
+             \x20   _Static_assert((char)300 == 44, \"casts truncate\");\n\
+             \x20   local[0] = 1;\n\
+             \x20   return sizeof table + limit + NEG + sizeof local + local[0];\n\
+             }\n",
+        )
+        .unwrap();
+        let exe = dir.path().join("constants");
+        let args = Args::parse_from([
+            "alecc",
+            "--nostdlib",
+            "-o",
+            exe.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        // 36 + 21 - 8 + 16 + 1
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(66));
+
+        let parse = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Parser::new(tokens).parse()
+        };
+        let program = parse(
+            "enum e { A = 2, B = A * 3 } v;\n\
+             int f(int x) { switch (x) { case B + 1: return 1; case sizeof(int): return 2; default: return 0; } }\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            &program.global_variables[0].var_type,
+            Type::Enum { variants, .. } if variants[1] == ("B".to_string(), 6)
+        ));
+        let StatementKind::Block(body) = &program.functions[0].body.kind else {
+            panic!("function body is not a block");
+        };
+        assert!(matches!(&body[0].kind, StatementKind::Switch { cases, .. } if cases.len() == 3));
+
+        let error = |source: &str| parse(source).unwrap_err().to_string();
+        assert!(error("_Static_assert(1 - 1, \"never\");\n")
+            .contains("static assertion failed: \"never\""));
+        assert!(error("int a[2 - 3];\n").contains("size of array is negative"));
+        assert!(error("int a[1 / 0];\n").contains("division by zero in a constant expression"));
+        assert!(
+            error("int f(int x) { switch (x) { case 1: case 2 - 1: return 0; } }\n")
+                .contains("duplicate case value 1")
+        );
+        assert!(error("int f(int n) { static int a[n]; return 0; }\n")
+            .contains("only supported as locals declared in a block"));
+    }
+
+    #[tokio::test]
+    async fn test_setjmp_longjmp() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("jump.c");
+        std::fs::write(
+            &source,
+            "#include <setjmp.h>\n\
+             static jmp_buf env;\n\
+             static int twice(int x) { return x + x; }\n\
+             static void bail(int code) { longjmp(env, code); }\n\
+             int main(void) {\n\
+             \x20   volatile int count = 0;\n\
+             \x20   int plain = 2;\n\
+             \x20   if (setjmp(env) != 0)\n\
+             \x20       return twice(count) + twice(plain);\n\
+             \x20   count = 7;\n\
+             \x20   bail(1);\n\
+             \x20   return 0;\n\
+             }\n",
+        )
+        .unwrap();
+        let exe = dir.path().join("jump");
+        for level in ["-O0", "-O3"] {
+            let args = Args::parse_from([
+                "alecc",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(18), "{}", level);
+        }
+
+        let ir = dir.path().join("jump.ll");
+        let args = Args::parse_from([
+            "alecc",
+            "-O3",
+            "--emit=llvm-ir",
+            "-o",
+            ir.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let ir = std::fs::read_to_string(&ir).unwrap();
+        assert!(ir.contains("declare i32 @setjmp(ptr) returns_twice"));
+        // Every local of a function calling setjmp lives in memory
+        assert!(ir.contains("store volatile i32 7"));
+        assert!(ir.contains("load volatile i32, ptr %plain"));
+        // Reading `count` twice would be a second volatile access
+        assert_eq!(ir.matches("call i32 (i32) @twice").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_compound_assignment_any_lvalue() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("compound_assignment.c");
+
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("compound_assignment-{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = std::process::Command::new(&exe).output().unwrap();
+            // 12 - 3 + 14 + 7 + 25 + 127 + 4 + 2
+            assert_eq!(run.status.code(), Some(188), "target {}", target);
+        }
+
+        let asm = dir.path().join("compound_assignment.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let assembly = std::fs::read_to_string(&asm).unwrap();
+        assert!(!assembly.contains("rax"));
+        assert!(assembly.contains("sdiv x0, x0, x1"));
+    }
+
+    #[tokio::test]
+    async fn test_warning_options() {
+        use alecc::diagnostics::Severity;
+        use alecc::warnings::WarningOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("warnings.c");
+        std::fs::write(
+            &input,
+            "int helper(int idle) {\n\
+             \x20   int spare;\n\
+             \x20   return 1;\n\
+             }\n\
+             int main(void) { int *p = 0; if (p == 3) return 0; return helper(0); }\n",
+        )
+        .unwrap();
+        let compile = |extra: &[&str]| {
+            let output = dir.path().join("warnings.s");
+            let mut argv = vec!["alecc", "-S", "-o", output.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            argv.push(input.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let result = compiler.compile().await;
+                let diagnostics = match &result {
+                    Ok(output) => &output.diagnostics,
+                    Err(_) => compiler.diagnostics(),
+                };
+                let diagnostics: Vec<_> = diagnostics
+                    .iter()
+                    .map(|d| (d.severity, d.message.clone()))
+                    .collect();
+                (result.is_ok(), diagnostics)
+            }
+        };
+
+        let (ok, diagnostics) = compile(&[]).await;
+        assert!(ok);
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    Severity::Warning,
+                    "comparison between pointer and integer [-Wpointer-integer-compare]"
+                        .to_string()
+                ),
+                (
+                    Severity::Warning,
+                    "unused variable 'spare' [-Wunused-variable]".to_string()
+                ),
+            ]
+        );
+
+        // -Wextra adds unused parameters; -Werror=<name> makes one an error
+        let (ok, diagnostics) = compile(&[
+            "-Wextra",
+            "-Werror=unused-variable",
+            "-Wno-pointer-integer-compare",
+        ])
+        .await;
+        assert!(!ok);
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    Severity::Warning,
+                    "unused parameter 'idle' [-Wunused-parameter]".to_string()
+                ),
+                (
+                    Severity::Error,
+                    "unused variable 'spare' [-Werror=unused-variable]".to_string()
+                ),
+            ]
+        );
+
+        // -Wno-error=<name> wins over -Werror; -Wno-unused covers the unused-* warnings
+        let (ok, diagnostics) = compile(&["-Werror", "-Wno-error=pointer-integer-compare"]).await;
+        assert!(!ok);
+        assert_eq!(diagnostics[0].0, Severity::Warning);
+        assert_eq!(diagnostics[1].0, Severity::Error);
+        let (ok, diagnostics) = compile(&["-Werror", "-Wno-unused", "-Wfoo"]).await;
+        assert!(!ok);
+        assert_eq!(
+            diagnostics[0].1,
+            "unknown warning option '-Wfoo' [-Werror=unknown-warning-option]"
+        );
+        assert_eq!(diagnostics.len(), 2);
+
+        let options = WarningOptions::new(&["all".to_string(), "no-format".to_string()]);
+        assert!(options.enabled("uninitialized"));
+        assert!(!options.enabled("format-extra-args"));
+        assert!(!options.enabled("unused-parameter"));
+        assert!(!options.is_error("uninitialized"));
+    }
+
+    #[tokio::test]
+    async fn test_compound_assignment_operators() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("operators.c");
+        std::fs::write(
+            &source,
+            "int flags = 12;\n\
+             int main() {\n\
+             \x20   int a[2];\n\
+             \x20   long wide = 1;\n\
+             \x20   a[1] = 47;\n\
+             \x20   a[1] %= 10;\n\
+             \x20   flags &= 10;\n\
+             \x20   flags |= 1;\n\
+             \x20   flags ^= 3;\n\
+             \x20   wide <<= 40;\n\
+             \x20   wide >>= 38;\n\
+             \x20   a[1] <<= 2;\n\
+             \x20   return a[1] + flags + wide;\n\
+             }\n",
+        )
+        .unwrap();
+        let exe = dir.path().join("operators");
+        let args = Args::parse_from([
+            "alecc",
+            "--nostdlib",
+            "-o",
+            exe.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        // 28 + 10 + 4
+        assert_eq!(run.status.code(), Some(42));
+
+        // The same instructions as the binary operators, for every target
+        let asm = dir.path().join("operators.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "i386",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            fixture("compound_assignment.c").to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let assembly = std::fs::read_to_string(&asm).unwrap();
+        assert!(assembly.contains("idiv ebx"));
+        assert!(!assembly.contains("rax"));
+    }
+
+    #[tokio::test]
+    async fn test_signedness_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let ir = dir.path().join("signedness.ll");
+        let compile = |extra: &[&str]| {
+            let mut argv = vec!["alecc", "--emit=llvm-ir", "-o", ir.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            let source = fixture("signedness.c");
+            argv.push(source.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let output = compiler.compile().await.unwrap();
+                output
+                    .diagnostics
+                    .iter()
+                    .map(|d| d.message.clone())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            compile(&[]).await,
+            ["unsigned conversion from 'int' to 'unsigned char' changes value from '300' to '44' [-Woverflow]"]
+        );
+        assert_eq!(
+            compile(&["-Wextra", "-Wconversion"]).await,
+            [
+                "unsigned conversion from 'int' to 'unsigned char' changes value from '300' to '44' [-Woverflow]",
+                "conversion to 'int' from 'unsigned int' may change the sign of the result [-Wsign-conversion]",
+                "comparison of integer expressions of different signedness: 'int' and 'unsigned int' [-Wsign-compare]",
+                "conversion to 'unsigned int' from 'int' may change the sign of the result [-Wsign-conversion]",
+            ]
+        );
+
+        // Unsigned operands pick the unsigned instructions
+        let text = std::fs::read_to_string(&ir).unwrap();
+        assert!(text.contains("udiv i32"));
+        assert!(text.contains("icmp ult i32"));
+        assert!(text.contains("icmp ugt i32"));
+        assert!(text.contains("zext i8"));
+    }
+
+    #[tokio::test]
+    async fn test_shadow_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("shadow.c");
+        let exe = dir.path().join("shadow");
+        let compile = |extra: &[&str]| {
+            let mut argv = vec!["alecc", "-t", "amd64", "-o", exe.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            argv.push(source.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let output = compiler.compile().await.unwrap();
+                output
+                    .diagnostics
+                    .iter()
+                    .map(|d| format!("{}:{}: {}", d.line, d.column, d.message))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert!(compile(&[]).await.is_empty());
+        assert_eq!(
+            compile(&["-Wshadow"]).await,
+            [
+                "7:9: declaration of 'n' shadows a parameter [-Wshadow]",
+                "4:16: shadowed declaration is here",
+                "8:9: declaration of 'count' shadows a global declaration [-Wshadow]",
+                "2:1: shadowed declaration is here",
+                "10:13: declaration of 'total' shadows a previous local [-Wshadow]",
+                "5:5: shadowed declaration is here",
+            ]
+        );
+
+        // Each name reads the innermost declaration
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_literal_range_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let asm = dir.path().join("literal_range.s");
+        let compile = |target: &str, extra: &[&str]| {
+            let mut argv = vec!["alecc", "-t", target, "-S", "-o", asm.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            let source = fixture("literal_range.c");
+            argv.push(source.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let output = compiler.compile().await.unwrap();
+                output
+                    .diagnostics
+                    .iter()
+                    .map(|d| format!("{}: {}", d.line, d.message))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            compile("amd64", &[]).await,
+            [
+                "3: overflow in conversion from 'int' to 'char' changes value from '200' to '-56' [-Woverflow]",
+                "6: overflow in conversion from 'int' to 'short int' changes value from '100000' to '-31072' [-Woverflow]",
+                "14: overflow in conversion from 'long int' to 'int' changes value from '5000000000' to '705032704' [-Woverflow]",
+                "15: overflow in conversion from 'int' to 'char' changes value from '1000' to '-24' [-Woverflow]",
+            ]
+        );
+        // A 32-bit long can't hold the literal, which is a long long there
+        assert_eq!(
+            compile("i386", &[]).await,
+            [
+                "2: overflow in conversion from 'long long int' to 'long int' changes value from '5000000000' to '705032704' [-Woverflow]",
+                "3: overflow in conversion from 'int' to 'char' changes value from '200' to '-56' [-Woverflow]",
+                "6: overflow in conversion from 'int' to 'short int' changes value from '100000' to '-31072' [-Woverflow]",
+                "14: overflow in conversion from 'long long int' to 'int' changes value from '5000000000' to '705032704' [-Woverflow]",
+                "15: overflow in conversion from 'int' to 'char' changes value from '1000' to '-24' [-Woverflow]",
+            ]
+        );
+        assert!(compile("i386", &["-Wno-overflow"]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trap_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let asm = dir.path().join("traps.s");
+        let compile = |extra: &[&str]| {
+            let mut argv = vec!["alecc", "-S", "-o", asm.to_str().unwrap()];
+            argv.extend_from_slice(extra);
+            let source = fixture("traps.c");
+            argv.push(source.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let result = compiler.compile().await;
+                let diagnostics = match &result {
+                    Ok(output) => &output.diagnostics,
+                    Err(_) => compiler.diagnostics(),
+                };
+                let diagnostics: Vec<_> = diagnostics
+                    .iter()
+                    .map(|d| format!("{}:{}: {}", d.line, d.column, d.message))
+                    .collect();
+                (result.is_ok(), diagnostics)
+            }
+        };
+
+        // `&((point_t *)0)->y` only computes an offset
+        let (ok, diagnostics) = compile(&[]).await;
+        assert!(ok);
+        assert_eq!(
+            diagnostics,
+            [
+                "5:15: division by zero [-Wdiv-by-zero]",
+                "6:15: division by zero [-Wdiv-by-zero]",
+                "7:7: division by zero [-Wdiv-by-zero]",
+                "14:13: null pointer dereference [-Wnull-dereference]",
+                "15:14: null pointer dereference [-Wnull-dereference]",
+                "16:14: null pointer dereference [-Wnull-dereference]",
+            ]
+        );
+
+        let (ok, diagnostics) = compile(&["-Werror=div-by-zero", "-Wno-null-dereference"]).await;
+        assert!(!ok);
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.ends_with("division by zero [-Werror=div-by-zero]")));
+    }
+
+    #[tokio::test]
+    async fn test_main_signatures() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("main_arguments.c");
+
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("main_arguments-{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let run = std::process::Command::new(&exe)
+                .args(["b", "c"])
+                .env_clear()
+                .env("A", "1")
+                .env("B", "2")
+                .output()
+                .unwrap();
+            // argc 3, two environment strings, 'b' - 'a'
+            assert_eq!(run.status.code(), Some(33), "target {}", target);
+        }
+
+        let compile = |name: &str, code: &str| {
+            let input = dir.path().join(name);
+            std::fs::write(&input, code).unwrap();
+            let exe = dir.path().join(name.trim_end_matches(".c"));
+            let args = Args::parse_from([
+                "alecc",
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                input.to_str().unwrap(),
+            ]);
+            let mut compiler = Compiler::new(args).unwrap();
+            async move {
+                let result = compiler.compile().await;
+                result.map(|output| (exe, output.diagnostics))
+            }
+        };
+
+        // void main warns, and exits with 0 whatever is left in the result register
+        let (exe, diagnostics) = compile(
+            "void_main.c",
+            "int n = 7;\nvoid main(void) { n = n * 6; }\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "return type of 'main' is not 'int' [-Wmain]"
+        );
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(0));
+
+        for (name, code, message) in [
+            (
+                "one.c",
+                "int main(int argc) { return argc; }\n",
+                "'main' takes only zero, two or three arguments",
+            ),
+            (
+                "long.c",
+                "int main(long argc, char **argv) { return 0; }\n",
+                "first argument of 'main' should be 'int'",
+            ),
+            (
+                "argv.c",
+                "int main(int argc, char *argv) { return 0; }\n",
+                "second argument of 'main' should be 'char **'",
+            ),
+        ] {
+            let error = compile(name, code).await.unwrap_err();
+            assert!(error.to_string().contains(message), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_return() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("missing_return.c");
+        let compile = |target: &str, mode: &str, extra: &[&str]| {
+            let exe = dir
+                .path()
+                .join(format!("missing_return-{}-{}", target, mode));
+            let mut argv = vec![
+                "alecc".to_string(),
+                "-t".to_string(),
+                target.to_string(),
+                format!("--fmissing-return={}", mode),
+                "--nostdlib".to_string(),
+                "-o".to_string(),
+                exe.to_str().unwrap().to_string(),
+            ];
+            argv.extend(extra.iter().map(|arg| arg.to_string()));
+            argv.push(source.to_str().unwrap().to_string());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let result = compiler.compile().await;
+                result.map(|output| (exe, output.diagnostics))
+            }
+        };
+
+        for target in ["amd64", "i386"] {
+            let (exe, diagnostics) = compile(target, "zero", &[]).await.unwrap();
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(
+                diagnostics[0].message,
+                "non-void function 'pick' does not return a value in all control paths [-Wreturn-type]"
+            );
+            // At the closing brace control reaches
+            assert_eq!((diagnostics[0].line, diagnostics[0].column), (6, 1));
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(7), "target {}", target);
+
+            let (exe, _) = compile(target, "trap", &[]).await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.signal(), Some(4), "target {}", target);
+        }
+
+        let (assembly, _) = compile("arm64", "trap", &["-S"]).await.unwrap();
+        let assembly = std::fs::read_to_string(assembly).unwrap();
+        assert!(assembly.contains("    brk #0x3e8\n"));
+        // main still returns 0 when it reaches its end, whatever the mode
+        assert!(!assembly.contains("    brk #0x3e8\n    mov sp, x29"));
+
+        let error = compile("amd64", "zero", &["-Werror=return-type"])
+            .await
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("warnings being treated as errors"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_switch_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("switch_enum.c");
+        let output = dir.path().join("switch_enum.ll");
+        let warnings = |options: &[&str]| {
+            let mut argv = vec!["alecc", "--emit=llvm-ir", "-o", output.to_str().unwrap()];
+            argv.extend(options);
+            argv.push(source.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let output = compiler.compile().await.unwrap();
+                output
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| (diagnostic.line, diagnostic.message.clone()))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let switch = vec![
+            (
+                6,
+                "enumeration value 'BLUE' not handled in switch [-Wswitch]".to_string(),
+            ),
+            (
+                12,
+                "case value '7' not in enumerated type 'enum color' [-Wswitch]".to_string(),
+            ),
+        ];
+        // `named` has a default; `[[fallthrough]];` marks the second fall-through as intended
+        assert_eq!(warnings(&[]).await, switch);
+        let extra = warnings(&["-Wextra"]).await;
+        assert_eq!(extra.len(), 3);
+        assert!(extra.contains(&(
+            8,
+            "this statement may fall through [-Wimplicit-fallthrough]".to_string()
+        )));
+        assert!(warnings(&["-Wno-switch"]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_const_correctness() {
+        let dir = tempfile::tempdir().unwrap();
+        let compile = |input: std::path::PathBuf, options: &[&str]| {
+            let output = dir
+                .path()
+                .join(input.file_name().unwrap())
+                .with_extension("s");
+            let mut argv = vec![
+                "alecc".to_string(),
+                "-S".to_string(),
+                "-o".to_string(),
+                output.to_str().unwrap().to_string(),
+            ];
+            argv.extend(options.iter().map(|option| option.to_string()));
+            argv.push(input.to_str().unwrap().to_string());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move { compiler.compile().await }
+        };
+
+        let output = compile(fixture("const_correctness.c"), &[]).await.unwrap();
+        let warnings: Vec<_> = output
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.message.as_str()))
+            .collect();
+        let discards =
+            "discards 'const' qualifier from pointer target type [-Wdiscarded-qualifiers]";
+        assert_eq!(
+            warnings,
+            [
+                (5, format!("return {}", discards)),
+                (12, format!("initialization {}", discards)),
+                (14, format!("assignment {}", discards)),
+                (15, format!("passing argument 1 of 'first' {}", discards)),
+            ]
+            .iter()
+            .map(|(line, message)| (*line, message.as_str()))
+            .collect::<Vec<_>>()
+        );
+        let quiet = compile(
+            fixture("const_correctness.c"),
+            &["-Wno-discarded-qualifiers"],
+        )
+        .await
+        .unwrap();
+        assert!(quiet.diagnostics.is_empty());
+
+        for (name, code, message) in [
+            (
+                "variable.c",
+                "const int limit = 3;\nint main(void) { limit = 4; return 0; }\n",
+                "assignment of read-only variable 'limit'",
+            ),
+            (
+                "increment.c",
+                "int main(void) { const int n = 1; int m = n; m++; n++; return m; }\n",
+                "increment of read-only variable 'n'",
+            ),
+            (
+                "pointee.c",
+                "int clear(const int *p) { *p = 0; return 0; }\n",
+                "assignment of read-only location",
+            ),
+            (
+                "member.c",
+                "typedef struct point { int x; } point;\nint move(const point *p) { p->x += 1; return 0; }\n",
+                "assignment of member 'x' in read-only object",
+            ),
+        ] {
+            let input = dir.path().join(name);
+            std::fs::write(&input, code).unwrap();
+            let error = compile(input, &[]).await.unwrap_err();
+            assert!(error.to_string().contains(message), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_switch_enum_and_default_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("switch_enum.c");
+        let output = dir.path().join("switch_enum.ll");
+        let warnings = |options: &[&str]| {
+            let mut argv = vec!["alecc", "--emit=llvm-ir", "-o", output.to_str().unwrap()];
+            argv.extend(options);
+            argv.push(source.to_str().unwrap());
+            let mut compiler = Compiler::new(Args::parse_from(argv)).unwrap();
+            async move {
+                let output = compiler.compile().await.unwrap();
+                output
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| (diagnostic.line, diagnostic.message.clone()))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // The switch in `named` has a default, so only -Wswitch-enum lists what it misses
+        assert_eq!(
+            warnings(&["-Wno-switch", "-Wswitch-enum"]).await,
+            [
+                (
+                    20,
+                    "enumeration value 'RED' not handled in switch [-Wswitch-enum]".to_string()
+                ),
+                (
+                    20,
+                    "enumeration value 'GREEN' not handled in switch [-Wswitch-enum]".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            warnings(&["-Wno-switch", "-Wswitch-default"]).await,
+            [(
+                6,
+                "switch missing default case [-Wswitch-default]".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ir_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("ir_lowering.c");
+
+        // The native generator has no switch or goto; going through the IR it does
+        for target in ["amd64", "i386"] {
+            for level in ["-O0", "-O2"] {
+                let exe = dir.path().join(format!("ir_{}{}", target, level));
+                let args = Args::parse_from([
+                    "alecc",
+                    "-t",
+                    target,
+                    "--nostdlib",
+                    "--backend=ir",
+                    level,
+                    "-o",
+                    exe.to_str().unwrap(),
+                    source.to_str().unwrap(),
+                ]);
+                Compiler::new(args).unwrap().compile().await.unwrap();
+                let run = std::process::Command::new(&exe).output().unwrap();
+                assert_eq!(run.status.code(), Some(173), "{} {}", target, level);
+            }
+        }
+
+        let assembly = dir.path().join("ir_arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "--backend=ir",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        assert!(text.contains("    bl fact\n"));
+        assert!(text.contains("_classify_bb1:\n"));
+
+        let ir = dir.path().join("ir_lowering.ir");
+        let args = Args::parse_from([
+            "alecc",
+            "--emit=ir",
+            "-O1",
+            "-o",
+            ir.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let output = Compiler::new(args).unwrap().compile().await.unwrap();
+        assert_eq!(output.ir, vec![ir.clone()]);
+        let text = std::fs::read_to_string(&ir).unwrap();
+        assert!(text.starts_with("function i64 @add(i32 %0, i32 %2) {\n"));
+        assert!(text.contains("  %5 = cmp eq i32 %3, 1\n"));
+        assert!(text.contains("call i32 @classify(i64 9)"));
+        assert!(!text.contains("is not in IR"));
+    }
+
+    #[tokio::test]
+    async fn test_macro_recursion_and_depth_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("macro_recursion.c");
+        let preprocessed = dir.path().join("macro_recursion.i");
+        let args = Args::parse_from([
+            "alecc",
+            "-E",
+            "-o",
+            preprocessed.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&preprocessed).unwrap();
+        assert!(text.contains("int main(void) { int COUNT + 1 = 1; int PING = 2; return 4; }"));
+
+        let args = Args::parse_from([
+            "alecc",
+            "-E",
+            "--fmacro-depth=3",
+            "-o",
+            preprocessed.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Preprocessor error at line 9: macro expansion nested more than 3 deep \
+             (--fmacro-depth): LEVEL0 -> LEVEL1 -> LEVEL2 -> LEVEL3"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ir_register_allocation() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("register_pressure.c");
+
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("pressure_{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "--backend=ir",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(221), "target {}", target);
+        }
+
+        let assembly = dir.path().join("pressure.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "--backend=ir",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        let (id, main) = text.split_once("main:").unwrap();
+        // `id` needs no callee-saved register; `main` uses all five and saves them
+        assert!(id.contains("    mov r10, rdi\n"));
+        assert!(!id.contains("rbx"));
+        for register in ["rbx", "r12", "r13", "r14", "r15"] {
+            assert!(main.contains(&format!("], {}\n", register)), "{}", register);
+        }
+        assert!(!main.contains("push rax"));
+    }
+
+    #[tokio::test]
+    async fn test_explain_asm() {
+        use alecc::cli::Command;
+        use alecc::explain::{detect_target, explain};
+
+        let args = Args::parse_from(["alecc", "explain-asm", "-t", "arm64", "prog.s"]);
+        let Some(Command::ExplainAsm(options)) = args.command else {
+            panic!("`alecc explain-asm` is not the explain-asm subcommand");
+        };
+        assert_eq!(options.target.as_deref(), Some("arm64"));
+        assert_eq!(options.file, PathBuf::from("prog.s"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("explain_calls.c");
+        let mut listings = Vec::new();
+        for (target, backend) in [("amd64", "native"), ("i386", "ir"), ("arm64", "ir")] {
+            let assembly = dir.path().join(format!("calls_{}.s", target));
+            let backend = format!("--backend={}", backend);
+            let args = Args::parse_from([
+                "alecc",
+                "-S",
+                "-t",
+                target,
+                "--nostdlib",
+                &backend,
+                "-o",
+                assembly.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let text = std::fs::read_to_string(&assembly).unwrap();
+            let detected = detect_target(&text);
+            assert_eq!(detected, Target::from_string(target).unwrap());
+            listings.push((assembly, explain(&text, detected)));
+        }
+
+        let (amd64_file, amd64) = &listings[0];
+        assert!(amd64.contains("counter:"));
+        assert!(amd64.contains("# the data of counter"));
+        assert!(amd64.contains("entry of add: arguments as the System V AMD64 convention"));
+        assert!(amd64.contains("rdi (argument 1)"));
+        assert!(amd64.contains("rsi (argument 2)"));
+        assert!(amd64.contains("reserve 16 bytes of stack for the frame; rsp now 32 bytes below"));
+        // `mov rsp, rbp` goes back to the depth the frame pointer was set at
+        assert!(amd64.contains("rsp now 8 bytes below the caller's"));
+        assert!(amd64.contains("call add: arguments in rdi, rsi"));
+        assert!(amd64.contains("jump to .Lexplain_calls_else_0 if zero"));
+
+        let (_, i386) = &listings[1];
+        assert!(i386.contains("entry of add: arguments as the cdecl convention"));
+        assert!(i386.contains("(argument 2, passed on the stack)"));
+
+        let (_, arm64) = &listings[2];
+        assert!(arm64.contains("// entry of main: arguments as the AAPCS64 convention"));
+        assert!(arm64.contains("x30 (link register: the return address)"));
+        assert!(arm64.contains("sp back where the caller had it"));
+        assert!(arm64.contains("the 4 KiB page holding counter"));
+
+        // The comments don't change what the file assembles to
+        let explained = dir.path().join("explained.s");
+        std::fs::write(&explained, amd64).unwrap();
+        for file in [amd64_file, &explained] {
+            let status = std::process::Command::new("as")
+                .arg(file)
+                .arg("-o")
+                .arg(dir.path().join("explained.o"))
+                .status()
+                .unwrap();
+            assert!(status.success(), "{}", file.display());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_asm_with_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("explain_calls.c");
+
+        for backend in ["native", "ir"] {
+            let object = dir.path().join(format!("calls_{}.o", backend));
+            let args = Args::parse_from([
+                "alecc",
+                "-c",
+                "--save-asm-with-object",
+                &format!("--backend={}", backend),
+                "-o",
+                object.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            let output = Compiler::new(args).unwrap().compile().await.unwrap();
+
+            // One compilation leaves both, the assembly named after the object
+            let assembly = object.with_extension("s");
+            assert_eq!(output.objects, vec![object.clone()]);
+            assert_eq!(output.assembly, vec![assembly.clone()]);
+            let text = std::fs::read_to_string(&assembly).unwrap();
+            assert!(text.contains("add:"));
+            assert!(std::fs::metadata(&object).unwrap().len() > 0);
+
+            // It is the assembly the object was built from
+            let rebuilt = dir.path().join("rebuilt.o");
+            let status = std::process::Command::new("as")
+                .arg(&assembly)
+                .arg("-o")
+                .arg(&rebuilt)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            assert_eq!(
+                std::fs::read(&rebuilt).unwrap(),
+                std::fs::read(&object).unwrap()
+            );
+        }
+
+        let args = Args::parse_from([
+            "alecc",
+            "--save-asm-with-object",
+            "--remote",
+            "localhost:1",
+            "-c",
+            source.to_str().unwrap(),
+        ]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_floating_point_codegen() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("floating_point.c");
+
+        for level in ["-O0", "-O2"] {
+            let exe = dir.path().join(format!("fp{}", level));
+            let args = Args::parse_from([
+                "alecc",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(107), "{}", level);
+        }
+
+        let amd64 = dir.path().join("fp_amd64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            amd64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&amd64).unwrap();
+        // double and float parameters arrive in xmm registers numbered apart
+        // from the integer ones
+        let (mix, _) = text.split_once("half:").unwrap();
+        assert!(mix.contains("mov QWORD PTR [rbp + -8], rdi"));
+        assert!(mix.contains("movsd QWORD PTR [rbp + -16], xmm0"));
+        assert!(mix.contains("movss DWORD PTR [rbp + -24], xmm1"));
+        assert!(mix.contains("mov QWORD PTR [rbp + -32], rsi"));
+        assert!(mix.contains("cvtsi2sd xmm0, rax"));
+        assert!(mix.contains("cvtss2sd xmm0, xmm0"));
+        assert!(text.contains("cvttsd2si rax, xmm0"));
+        assert!(text.contains("ucomisd xmm1, xmm0"));
+        // The ninth and tenth double go on the stack
+        assert!(text.contains("movsd xmm0, QWORD PTR [rbp + -72]"));
+        assert!(text.contains("mov rax, QWORD PTR [rbp + 16]"));
+        assert!(text.contains("scale:\n    .quad 4612811918334230528\n"));
+        assert!(text.contains("third:\n    .long -1098907648\n"));
+
+        let arm64 = dir.path().join("fp_arm64.s");
+        let source = dir.path().join("fp_arm64.c");
+        std::fs::write(
+            &source,
+            "float half(float x) { return x / 2; }\n\
+             int main() { double d = half(3) + 0.25; if (d >= 1.75) return (int)(d * 4); return -d; }\n",
+        )
+        .unwrap();
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        assert!(text.contains("    str s0, [x29, #-8]\n"));
+        assert!(text.contains("    scvtf s0, x0\n"));
+        assert!(text.contains("    fdiv s0, s0, s1\n"));
+        assert!(text.contains("    fcvt d0, s0\n"));
+        assert!(text.contains("    movz x0, #0x3fd0, lsl #48\n"));
+        assert!(text.contains("    fcmp d0, d1\n    cset x0, ge\n"));
+        assert!(text.contains("    fcvtzs x0, d0\n"));
+        assert!(text.contains("    fneg d0, d0\n"));
+
+        let args = Args::parse_from(["alecc", "-t", "i386", "-S", source.to_str().unwrap()]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(error.to_string().contains("only gets with --msse2"));
+    }
+
+    #[tokio::test]
+    async fn test_wrap_and_defsym() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("wrap_calls.c");
+
+        let flag_sets: [&[&str]; 4] = [
+            &["--wrap", "add", "--defsym", "unused=add"],
+            &["-Wl,--wrap=add,--defsym=unused=add"],
+            &[
+                "-Wl,--wrap,add",
+                "--defsym",
+                "unused=add",
+                "--backend=ir",
+                "-O2",
+            ],
+            &["--wrap=add", "-Wl,--defsym,unused=add", "--no-pie"],
+        ];
+        for (index, flags) in flag_sets.iter().enumerate() {
+            let exe = dir.path().join(format!("wrap{}", index));
+            let mut argv = vec!["alecc", "-o", exe.to_str().unwrap()];
+            argv.extend_from_slice(flags);
+            argv.push(source.to_str().unwrap());
+            let output = Compiler::new(Args::parse_from(argv))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            // -Wl, is for the linker, not an unknown warning
+            assert!(output.diagnostics.is_empty(), "{:?}", flags);
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(50), "{:?}", flags);
+        }
+
+        // ld leaves calls inside the defining object alone, so the compiler
+        // names the wrapper itself; references to the original stay as is
+        let assembly = dir.path().join("wrap.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "--wrap",
+            "add",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        assert!(text.contains("    call __wrap_add\n"));
+        assert!(text.contains("    call __real_add@PLT\n"));
+        assert!(!text.contains("    call add"));
+
+        // Without the definition the reference to unused can't be resolved
+        let args = Args::parse_from([
+            "alecc",
+            "--wrap",
+            "add",
+            "-o",
+            dir.path().join("undefined").to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(error.to_string().contains("unused"));
+
+        let args = Args::parse_from(["alecc", "--defsym", "unused", source.to_str().unwrap()]);
+        let error = Compiler::new(args).err().unwrap();
+        assert!(error
+            .to_string()
+            .contains("--defsym expects SYMBOL=EXPRESSION"));
+    }
+
+    #[tokio::test]
+    async fn test_i386_floating_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("floating_cdecl.c");
+
+        for (target, flags) in [("i386", &["--msse2"][..]), ("amd64", &[][..])] {
+            for level in ["-O0", "-O2"] {
+                let exe = dir.path().join(format!("cdecl-{}{}", target, level));
+                let mut argv = vec!["alecc", "-t", target, "--nostdlib", level];
+                argv.extend_from_slice(flags);
+                argv.extend(["-o", exe.to_str().unwrap(), source.to_str().unwrap()]);
+                Compiler::new(Args::parse_from(argv))
+                    .unwrap()
+                    .compile()
+                    .await
+                    .unwrap();
+                let run = std::process::Command::new(&exe).output().unwrap();
+                assert_eq!(run.status.code(), Some(42), "{} {}", target, level);
+            }
+        }
+
+        let assembly = dir.path().join("cdecl.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "i386",
+            "--msse2",
+            "-S",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        assert!(text.starts_with(".arch i686\n.arch .sse2\n"));
+        // Returned in st(0), and taken from there after every call
+        let (twice, _) = text.split_once("third:").unwrap();
+        assert!(twice.contains("    movsd QWORD PTR [esp], xmm0\n    fld QWORD PTR [esp]\n"));
+        assert!(text.contains("    fld DWORD PTR [esp]\n"));
+        assert_eq!(
+            text.matches("    call twice\n    sub esp, 8\n    fstp QWORD PTR [esp]\n")
+                .count(),
+            2
+        );
+        // The double parameter takes two words between the int ones
+        let (pick, _) = text.split_once("main:").unwrap();
+        let (_, pick) = pick.split_once("pick:").unwrap();
+        assert!(pick.contains("mov eax, DWORD PTR [ebp + 20]\n    mov DWORD PTR [ebp + -16], eax"));
+        assert!(pick.contains("movsd xmm0, QWORD PTR [ebp + -12]"));
+        assert!(pick.contains("movss xmm0, DWORD PTR [ebp + -20]"));
+        assert!(text.contains("    add esp, 20\n"));
+
+        // Without SSE2 there is no floating point on i386
+        let args = Args::parse_from(["alecc", "-t", "i386", "-S", source.to_str().unwrap()]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'twice' uses floating point, which i386 code only gets with --msse2"));
+    }
+
+    #[tokio::test]
+    async fn test_builtin_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("builtin_headers.c");
+        // An empty sysroot leaves only the headers alecc ships
+        let sysroot = dir.path().join("sysroot");
+        std::fs::create_dir(&sysroot).unwrap();
+
+        for (target, level) in [("amd64", "-O0"), ("amd64", "-O2"), ("i386", "-O0")] {
+            let exe = dir.path().join(format!("headers-{}{}", target, level));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--nostdlib",
+                "--sysroot",
+                sysroot.to_str().unwrap(),
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(42), "{} {}", target, level);
+        }
+
+        // The limits follow the target's long
+        let limits = dir.path().join("limits.c");
+        std::fs::write(
+            &limits,
+            "#include <limits.h>\n#include <stdint.h>\nlong big = LONG_MAX;\nintptr_t word = INTPTR_MAX;\n",
+        )
+        .unwrap();
+        for (target, value) in [("i386", "2147483647"), ("arm64", "9223372036854775807")] {
+            let assembly = dir.path().join(format!("limits-{}.s", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "-S",
+                "-o",
+                assembly.to_str().unwrap(),
+                limits.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let text = std::fs::read_to_string(&assembly).unwrap();
+            assert_eq!(text.matches(value).count(), 2, "{}", target);
+        }
+
+        // A header of the same name in a -I directory comes first
+        let include = dir.path().join("include");
+        std::fs::create_dir(&include).unwrap();
+        std::fs::write(include.join("limits.h"), "int from_include_dir;\n").unwrap();
+        let assembly = dir.path().join("override.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-I",
+            include.to_str().unwrap(),
+            "-S",
+            "-o",
+            assembly.to_str().unwrap(),
+            limits.to_str().unwrap(),
+        ]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(error.to_string().contains("LONG_MAX"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_struct_abi() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("struct_abi.c");
+
+        for level in ["-O0", "-O2"] {
+            let exe = dir.path().join(format!("struct_abi{}", level));
+            let args = Args::parse_from([
+                "alecc",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(99), "{}", level);
+        }
+
+        let amd64 = dir.path().join("struct_abi_amd64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            amd64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&amd64).unwrap();
+        // struct mixed comes back as an SSE and an INTEGER eightbyte
+        let (_, mixed) = text.split_once("make_mixed:").unwrap();
+        let (mixed, _) = mixed.split_once("ret").unwrap();
+        assert!(mixed.contains("movsd xmm0, QWORD PTR [r11 + 0]"));
+        assert!(mixed.contains("mov rax, QWORD PTR [r11 + 8]"));
+        // struct floats arrives in two xmm registers
+        let (_, floats) = text.split_once("sum_floats:").unwrap();
+        assert!(floats.contains("movsd QWORD PTR [rbp + -16], xmm0"));
+        assert!(floats.contains("movss DWORD PTR [rbp + -8], xmm1"));
+        // struct big is returned through the address the caller passes in rdi
+        assert!(text.contains("lea rdi, [rsp + 0]\n    call make_big"));
+
+        let arm64 = dir.path().join("struct_abi_arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        // A homogeneous float aggregate takes one s register per member
+        let (_, floats) = text.split_once("sum_floats:").unwrap();
+        assert!(
+            floats.contains("str s0, [x29, #-16]\n    str s1, [x29, #-12]\n    str s2, [x29, #-8]")
+        );
+        // Bigger structs are passed by reference and returned through x8
+        let (_, big) = text.split_once("sum_big:").unwrap();
+        assert!(big.contains("mov x10, x0"));
+        assert!(text.contains("add x8, sp, #0\n    bl make_big"));
+
+        // i386 cdecl passes every struct on the stack and returns it through
+        // a hidden pointer the callee pops with ret 4
+        let source = fixture("struct_cdecl.c");
+        for level in ["-O0", "-O2"] {
+            let exe = dir.path().join(format!("struct_cdecl{}", level));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                "i386",
+                "--nostdlib",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(33), "{}", level);
+        }
+        let i386 = dir.path().join("struct_cdecl_i386.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "i386",
+            "-S",
+            "-o",
+            i386.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&i386).unwrap();
+        let (_, make) = text.split_once("make:").unwrap();
+        let (make, _) = make.split_once("sum:").unwrap();
+        assert!(make.contains("ret 4"));
+    }
+
+    #[tokio::test]
+    async fn test_integer_conversions() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("integer_conversions.c");
+
+        for level in ["-O0", "-O2"] {
+            let exe = dir.path().join(format!("conversions{}", level));
+            let args = Args::parse_from([
+                "alecc",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(63), "{}", level);
+        }
+
+        // The same conversions on i386 and arm64, added up
+        let source = dir.path().join("narrow.c");
+        std::fs::write(
+            &source,
+            "char narrow(int x) { return x; }\n\
+             unsigned short wrap(int x) { return x; }\n\
+             int main() { unsigned char u = 255; u = u + 1; return u + wrap(-1) - 65535 + narrow(511) + (_Bool)2 + (char)300; }\n",
+        )
+        .unwrap();
+        let i386 = dir.path().join("narrow_i386");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "i386",
+            "--nostdlib",
+            "-o",
+            i386.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&i386).output().unwrap();
+        assert_eq!(run.status.code(), Some(44));
+
+        let arm64 = dir.path().join("narrow_arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        // Returns truncate to the result type, and callers extend again
+        assert!(text.contains("ldrsw x0, [x29, #-8]\n    sxtb x0, w0\n"));
+        assert!(text.contains("bl narrow\n    sxtb x0, w0\n"));
+        assert!(text.contains("bl wrap\n    uxth w0, w0\n"));
+        assert!(text.contains("add x0, x0, x1\n    uxtb w0, w0\n"));
+        assert!(text.contains("ldrb w0, [x29, #-8]"));
+        assert!(text.contains("cmp x0, #0\n    cset x0, ne\n"));
+    }
+
+    #[tokio::test]
+    async fn test_stdint_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("stdint_types.c");
+
+        for level in ["-O0", "-O2"] {
+            let exe = dir.path().join(format!("stdint{}", level));
+            let args = Args::parse_from([
+                "alecc",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(160), "{}", level);
+        }
+
+        // Globals take the size and alignment of their type
+        let arm64 = dir.path().join("stdint_arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        assert!(text.contains("check:\n    .zero 32\n"));
+        assert!(text.contains(
+            "    .balign 4\nmask:\n    .long -1\n.type small, %object\n.size small, 1\n.globl small\nsmall:\n    .byte -128\n"
+        ));
+        assert!(text.contains("    .balign 2\nhalf:\n    .short -1\n"));
+
+        // On i386 long and the pointer types are 32 bits wide, and there is
+        // no 64-bit type
+        let source = dir.path().join("stdint_i386.c");
+        std::fs::write(
+            &source,
+            "#include <stdint.h>\n\
+             _Static_assert(sizeof(intptr_t) == 4 && SIZE_MAX == 4294967295U && INTPTR_MAX == INT32_MAX, \"pointer\");\n\
+             _Static_assert(UINTPTR_MAX + 1 == 0 && INTPTR_MIN < 0, \"wrap\");\n\
+             typedef struct record { uint8_t tag; uint32_t value; int16_t delta; uintptr_t link; } record;\n\
+             int main() { return sizeof(record) + INT8_MAX; }\n",
+        )
+        .unwrap();
+        let i386 = dir.path().join("stdint_i386");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "i386",
+            "--nostdlib",
+            "-o",
+            i386.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&i386).output().unwrap();
+        assert_eq!(run.status.code(), Some(143));
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn test_replay_requires_feature() {
+        assert!(alecc::replay::parse_database("[]").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_replay_compilation_database() {
+        use alecc::replay::{parse_database, replay_arguments, select};
+        use std::path::Path;
+
+        let entries = parse_database(
+            r#"[
+                {"directory": "/src", "file": "lib/a.c",
+                 "command": "cc -std=c99 -O2 -MD -MF a.d -isystem inc -fPIC -DNAME=\"a b\" -c lib/a.c -o a.o"},
+                {"directory": "/src", "file": "b.c",
+                 "arguments": ["alecc", "-O1", "-t", "arm64", "b.c", "-o", "b"]}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let a = select(&entries, Some(Path::new("a.c")), None).unwrap();
+        assert_eq!(
+            replay_arguments(a, None, None),
+            [
+                "--std=c99",
+                "-O2",
+                "-I",
+                "inc",
+                "--pic",
+                "-DNAME=a b",
+                "-c",
+                "lib/a.c",
+                "-o",
+                "a.o"
+            ]
+        );
+
+        let b = select(&entries, None, Some(1)).unwrap();
+        assert_eq!(
+            replay_arguments(b, Some("3"), Some("i386")),
+            ["b.c", "-o", "b", "-O3", "-t", "i386"]
+        );
+
+        assert!(select(&entries, None, None).is_err());
+        assert!(select(&entries, None, Some(2)).is_err());
+        assert!(select(&entries, Some(Path::new("c.c")), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_variadic_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("variadic_calls.c");
+
+        for level in ["-O0", "-O2"] {
+            let exe = dir.path().join(format!("variadic_calls{}", level));
+            let args = Args::parse_from([
+                "alecc",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(15), "{}", level);
+        }
+
+        let assembly = dir.path().join("variadic_calls.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        // al counts the vector registers, and rsp is realigned around the call
+        assert!(text.contains("mov eax, 2\n    call snprintf@PLT"));
+        assert!(text.contains("mov eax, 8\n    call snprintf@PLT"));
+        assert!(text.contains("and rsp, -16"));
+        assert!(text.contains("add rsp, 8\n    pop rsp"));
+        // strcmp has a prototype, so it gets neither
+        assert!(!text.contains("mov eax, 0\n    call strcmp@PLT"));
+    }
+
+    #[tokio::test]
+    async fn test_variadic_doubles() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("variadic_doubles.c");
+
+        for (target, level) in [
+            ("amd64", "-O0"),
+            ("amd64", "-O2"),
+            ("i386", "-O0"),
+            ("i386", "-O2"),
+        ] {
+            let exe = dir
+                .path()
+                .join(format!("variadic_doubles_{}{}", target, level));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                level,
+                "--nostdlib",
+                "--msse2",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(42), "{} {}", target, level);
+        }
+
+        let arm64 = dir.path().join("variadic_doubles_arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        // __vr_offs walks the q registers' save area 16 bytes at a time
+        assert!(text.contains("ldrsw x11, [x10, #28]"));
+        assert!(text.contains("add w12, w11, #16\n    str w12, [x10, #28]"));
+        assert!(text.contains("ldr d0, [x13, x11]"));
+
+        let source = dir.path().join("variadic_float.c");
+        std::fs::write(
+            &source,
+            "#include <stdarg.h>\n\
+             float first(int n, ...) { va_list ap; va_start(ap, n); return va_arg(ap, float); }\n",
+        )
+        .unwrap();
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            dir.path().join("variadic_float.s").to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(
+            error.to_string().contains("promotes to 'double'"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_operators_on_every_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("operators.c");
+
+        for (target, level) in [("amd64", "-O0"), ("amd64", "-O2"), ("i386", "-O0")] {
+            let exe = dir.path().join(format!("operators_{}{}", target, level));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                level,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(255), "{} {}", target, level);
+        }
+
+        let arm64 = dir.path().join("operators_arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        assert!(text.contains("cmp x0, x1\n    cset x0, gt\n"));
+        assert!(text.contains("cmp x0, x1\n    cset x0, hi\n"));
+        assert!(text.contains("eor x0, x0, x1"));
+        assert!(text.contains("orr x0, x0, x1"));
+        assert!(text.contains("lsl x0, x0, x1"));
+        assert!(text.contains("asr x0, x0, x1"));
+        assert!(text.contains("lsr x0, x0, x1"));
+        assert!(text.contains("udiv x0, x0, x1"));
+        // 4000000000 takes more than one 16-bit move
+        assert!(text.contains("movz x0, #0x2800, lsl #0\n    movk x0, #0xee6b, lsl #16\n"));
+
+        if which::which("llvm-mc").is_ok() {
+            let status = std::process::Command::new("llvm-mc")
+                .args(["--triple=aarch64-unknown-linux-gnu", "--filetype=obj", "-o"])
+                .arg(dir.path().join("operators_arm64.o"))
+                .arg(&arm64)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nested_call_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("nested_calls.c");
+
+        for (target, level) in [("amd64", "-O0"), ("amd64", "-O2"), ("i386", "-O0")] {
+            let exe = dir.path().join(format!("nested_{}{}", target, level));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                level,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(134), "{} {}", target, level);
+        }
+
+        // Computed arguments wait on the stack until right before the call
+        let arm64 = dir.path().join("nested_arm64.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        assert!(text.contains(
+            "ldr x0, [sp], #16\n    ldr x1, [sp], #16\n    ldr x2, [sp], #16\n    ldr x3, [sp], #16\n    bl mix\n"
+        ));
+
+        // Loading a global for x0 leaves x1, set just before, alone
+        let source = dir.path().join("global_argument.c");
+        std::fs::write(
+            &source,
+            "int g = 5;\nint f(int a, int b) { return a * 10 + b; }\nint main(void) { int l = 3; return f(g, l); }\n",
+        )
+        .unwrap();
+        let args = Args::parse_from([
+            "alecc",
+            "-t",
+            "arm64",
+            "-S",
+            "-o",
+            arm64.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&arm64).unwrap();
+        assert!(text.contains("    mov x1, x0\n    adrp x17, g\n    add x17, x17, :lo12:g\n"));
+    }
+
+    #[tokio::test]
+    async fn test_function_pointers() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("function_pointers.c");
+
+        for level in ["-O0", "-O2"] {
+            let exe = dir.path().join(format!("function_pointers{}", level));
+            let args = Args::parse_from([
+                "alecc",
+                level,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(75), "{}", level);
+        }
+
+        // The callee is evaluated into a scratch register and called through it
+        for (target, call) in [("i386", "call eax"), ("arm64", "blr x16")] {
+            let assembly = dir.path().join(format!("function_pointers_{}.s", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "-S",
+                "-o",
+                assembly.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let text = std::fs::read_to_string(&assembly).unwrap();
+            assert!(text.contains(call), "{}", target);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_global_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("global_storage.c");
+        // The other unit has its own `hidden`, which must not clash with main's
+        let other = dir.path().join("counter.c");
+        std::fs::write(
+            &other,
+            "int counter = 4;\nstatic int hidden = 1;\nint bump(void) { return hidden++; }\n",
+        )
+        .unwrap();
+
+        for backend in ["--backend=native", "--backend=ir"] {
+            let exe = dir.path().join(backend.trim_start_matches("--backend="));
+            let args = Args::parse_from([
+                "alecc",
+                backend,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+                other.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(54), "{}", backend);
+        }
+
+        let assembly = dir.path().join("global_storage.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        let (data, bss) = text.split_once(".section .bss").unwrap();
+        assert!(data.contains(".globl five\n    .balign 4\nfive:\n    .long 5\n"));
+        assert!(data.contains("hidden:\n    .long 2\n") && !text.contains(".globl hidden"));
+        assert!(bss.contains(".globl buffer\nbuffer:\n    .zero 4096\n"));
+        assert!(bss.contains("zero:\n    .zero 4\n"));
+        // An address is only known once linked, so it isn't a zero
+        assert!(data.contains("where:\n    .quad five\n"));
+        assert!(bss.contains("nowhere:\n    .zero 8\n"));
+        // Only declared here
+        assert!(!text.contains("counter:"));
+    }
+
+    #[tokio::test]
+    async fn test_static_locals() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("static_locals.c");
+
+        for (target, flag) in [("amd64", "-O0"), ("amd64", "--backend=ir"), ("i386", "-O2")] {
+            let exe = dir.path().join(format!("static_locals_{}{}", target, flag));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                flag,
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(119), "{} {}", target, flag);
+        }
+
+        // Each becomes a local global named after its function
+        let assembly = dir.path().join("static_locals.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        assert!(text.contains("next.counter:\n    .long 10\n"));
+        assert!(text.contains("twice.counter:\n    .zero 4\n"));
+        assert!(text.contains("twice.counter.1:\n    .long 100\n"));
+        assert!(!text.contains(".globl next.counter"));
+    }
+
+    #[tokio::test]
+    async fn test_att_syntax() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("operators.c");
+
+        for target in ["amd64", "i386"] {
+            let exe = dir.path().join(format!("operators_att_{}", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--masm=att",
+                "--nostdlib",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(255), "{}", target);
+        }
+
+        let assembly = dir.path().join("operators_att.s");
+        let args = Args::parse_from([
+            "alecc",
+            "--masm=att",
+            "-S",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        assert!(text.starts_with(".att_syntax\n"));
+        assert!(!text.contains("PTR"));
+        // Source first, sizes in the mnemonic where no register gives them
+        assert!(text.contains("    push %rbp\n    .cfi_def_cfa_offset 16\n    .cfi_offset 6, -16\n    mov %rsp, %rbp\n"));
+        assert!(text.contains("    movzbq %al, %rax\n"));
+        assert!(text.contains("(%rbp)"));
+    }
+
+    #[tokio::test]
+    async fn test_shared_library_globals() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = fixture("shared_library.c");
+        let main = dir.path().join("main.c");
+        std::fs::write(
+            &main,
+            "extern int counter;\nint bump(int n);\nint main(void) { counter = counter * 2; return bump(3); }\n",
+        )
+        .unwrap();
+
+        for backend in ["--backend=native", "--backend=ir"] {
+            let name = backend.trim_start_matches("--backend=");
+            let object = dir.path().join(format!("lib{}.so", name));
+            let args = Args::parse_from([
+                "alecc",
+                backend,
+                "--shared",
+                "-o",
+                object.to_str().unwrap(),
+                library.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+
+            let exe = dir.path().join(format!("main_{}", name));
+            let library_name = format!("-l{}", name);
+            let args = Args::parse_from([
+                "alecc",
+                backend,
+                "-L",
+                dir.path().to_str().unwrap(),
+                &library_name,
+                "-o",
+                exe.to_str().unwrap(),
+                main.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            // The executable's copy of counter is the one the library uses
+            let run = std::process::Command::new(&exe)
+                .env("LD_LIBRARY_PATH", dir.path())
+                .output()
+                .unwrap();
+            assert_eq!(run.status.code(), Some(16), "{}", backend);
+        }
+
+        // Only the static variable is addressed directly
+        for (target, direct, through_got) in [
+            ("amd64", "[rip + hidden]", "[rip + counter@GOTPCREL]"),
+            ("arm64", "adrp x17, hidden", "adrp x17, :got:counter"),
+        ] {
+            let assembly = dir.path().join(format!("shared_{}.s", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-t",
+                target,
+                "--shared",
+                "-S",
+                "-o",
+                assembly.to_str().unwrap(),
+                library.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let text = std::fs::read_to_string(&assembly).unwrap();
+            assert!(text.contains(direct), "{}", target);
+            assert!(text.contains(through_got), "{}", target);
+            assert!(!text.contains("rip + counter]") && !text.contains("adrp x17, counter"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thread_locals() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("thread_locals.c");
+
+        // Dynamically linked, so the loader sets up the thread pointer
+        for flag in ["-O0", "-O2", "--backend=ir"] {
+            let exe = dir.path().join(format!("thread_locals{}", flag));
+            let args = Args::parse_from([
+                "alecc",
+                flag,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(80), "{}", flag);
+        }
+
+        // An executable reaches its own at a fixed offset from the thread
+        // pointer; a shared object reads the offset from the GOT
+        for (target, shared, expected) in [
+            ("amd64", false, "    mov rax, QWORD PTR fs:0\n    lea rax, [rax + counter@tpoff]\n"),
+            ("amd64", true, "    mov rax, QWORD PTR fs:0\n    add rax, QWORD PTR [rip + counter@gottpoff]\n"),
+            ("i386", false, "    mov eax, DWORD PTR gs:0\n    lea eax, [eax + counter@ntpoff]\n"),
+            ("arm64", false, "    mrs x0, tpidr_el0\n    add x0, x0, #:tprel_hi12:counter, lsl #12\n"),
+            ("arm64", true, "    adrp x17, :gottprel:counter\n    ldr x17, [x17, :gottprel_lo12:counter]\n    mrs x0, tpidr_el0\n    add x0, x0, x17\n"),
+        ] {
+            let assembly = dir.path().join(format!("thread_locals_{}{}.s", target, shared));
+            let mut args = vec!["alecc", "-S", "-t", target];
+            if shared {
+                args.push("--shared");
+            }
+            args.extend(["-o", assembly.to_str().unwrap(), source.to_str().unwrap()]);
+            Compiler::new(Args::parse_from(args)).unwrap().compile().await.unwrap();
+            let text = std::fs::read_to_string(&assembly).unwrap();
+            assert!(text.contains(expected), "{} {}:\n{}", target, shared, text);
+            assert!(text.contains(".section .tdata,\"awT\","), "{}", target);
+            assert!(text.contains(".section .tbss,\"awT\","), "{}", target);
+        }
+
+        // alecc's own startup code doesn't set up thread storage
+        let args = Args::parse_from([
+            "alecc",
+            "--static",
+            "-o",
+            dir.path().join("thread_locals_static").to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(error.to_string().contains("--static"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_unwind_information() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("unwind.c");
+        std::fs::write(
+            &source,
+            "int backtrace(void **buffer, int size);\n\
+             int depth(int n) {\n\
+                 void *frames[16];\n\
+                 if (n > 0) {\n\
+                     int inner = depth(n - 1);\n\
+                     return inner;\n\
+                 }\n\
+                 return backtrace(frames, 16);\n\
+             }\n\
+             int main(void) { return depth(3); }\n",
+        )
+        .unwrap();
+
+        // The unwinder walks from backtrace's caller through every frame to
+        // alecc's _start: four of depth, main and _start
+        for flag in ["-O0", "--backend=ir"] {
+            let exe = dir.path().join(format!("unwind{}", flag));
+            let args = Args::parse_from([
+                "alecc",
+                flag,
+                "--fno-use-crt",
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(6), "{}", flag);
+        }
+
+        for (target, entry, exit) in [
+            ("i386", "    push ebp\n    .cfi_def_cfa_offset 8\n    .cfi_offset 5, -8\n    mov ebp, esp\n    .cfi_def_cfa_register 5\n", "    pop ebp\n    .cfi_restore 5\n    .cfi_def_cfa 4, 4\n    ret\n    .cfi_restore_state\n"),
+            ("amd64", "    push rbp\n    .cfi_def_cfa_offset 16\n    .cfi_offset 6, -16\n    mov rbp, rsp\n    .cfi_def_cfa_register 6\n", "    pop rbp\n    .cfi_restore 6\n    .cfi_def_cfa 7, 8\n    ret\n    .cfi_restore_state\n"),
+            ("arm64", "    stp x29, x30, [sp, #-16]!\n    .cfi_def_cfa_offset 16\n    .cfi_offset 29, -16\n    .cfi_offset 30, -8\n    mov x29, sp\n    .cfi_def_cfa_register 29\n", "    ldp x29, x30, [sp], #16\n    .cfi_restore 30\n    .cfi_restore 29\n    .cfi_def_cfa_offset 0\n    ret\n"),
+        ] {
+            let assembly = dir.path().join(format!("unwind_{}.s", target));
+            let args = Args::parse_from([
+                "alecc",
+                "-S",
+                "--fno-use-crt",
+                "-t",
+                target,
+                "-o",
+                assembly.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let text = std::fs::read_to_string(&assembly).unwrap();
+            assert!(text.contains("depth:\n    .cfi_startproc\n"), "{}", target);
+            assert!(text.contains(entry), "{}:\n{}", target, text);
+            assert!(text.contains(exit), "{}:\n{}", target, text);
+            // One state kept for each return, of which depth has two
+            let start = text.find("depth:").unwrap();
+            let depth = &text[start..start + text[start..].find(".cfi_endproc").unwrap()];
+            assert_eq!(depth.matches(".cfi_remember_state").count(), 2, "{}", target);
+            assert!(text.contains("_start:\n    .cfi_startproc\n    .cfi_undefined "));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_symbol_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("static_locals.c");
+
+        for (target, prefix) in [("amd64", '@'), ("arm64", '%')] {
+            for backend in ["--backend=native", "--backend=ir"] {
+                let assembly = dir.path().join(format!("symbols_{}{}.s", target, backend));
+                let args = Args::parse_from([
+                    "alecc",
+                    "-S",
+                    "--fno-use-crt",
+                    "-t",
+                    target,
+                    backend,
+                    "-o",
+                    assembly.to_str().unwrap(),
+                    source.to_str().unwrap(),
+                ]);
+                Compiler::new(args).unwrap().compile().await.unwrap();
+                let text = std::fs::read_to_string(&assembly).unwrap();
+                // Functions are typed before their label and sized after their code
+                for function in ["next", "twice", "main", "_start"] {
+                    assert!(
+                        text.contains(&format!(".type {0}, {1}function\n{0}:\n", function, prefix)),
+                        "{} {} {}:\n{}",
+                        target,
+                        backend,
+                        function,
+                        text
+                    );
+                    assert!(
+                        text.contains(&format!("    .cfi_endproc\n.size {0}, .-{0}\n", function))
+                    );
+                }
+                assert!(text.contains(&format!(
+                    ".type next.counter, {}object\n.size next.counter, 4\n    .balign 4\nnext.counter:\n",
+                    prefix
+                )));
+                assert!(text.contains("    .ident \"alecc "));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_pointer_omission() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("frames.c");
+        std::fs::write(
+            &source,
+            "int backtrace(void **buffer, int size);\n\
+             int scale(int x) { int y = x * 3; return y + 1; }\n\
+             int depth(int n) {\n\
+                 void *frames[16];\n\
+                 if (n > 0) {\n\
+                     int inner = depth(n - 1);\n\
+                     return inner;\n\
+                 }\n\
+                 return backtrace(frames, 16) + scale(0) - 1;\n\
+             }\n\
+             int main(void) { return depth(3); }\n",
+        )
+        .unwrap();
+
+        // The unwinder still walks every frame, following the stack pointer
+        for backend in ["--backend=native", "--backend=ir"] {
+            let exe = dir.path().join(format!("frames{}", backend));
+            let args = Args::parse_from([
+                "alecc",
+                "-O2",
+                "--fno-use-crt",
+                backend,
+                "-o",
+                exe.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(6), "{}", backend);
+        }
+
+        let compile = |flags: &[&str], name: &str| {
+            let assembly = dir.path().join(name);
+            let mut args = vec!["alecc", "-S", "-o", assembly.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            let args = Args::parse_from(args);
+            async move {
+                Compiler::new(args).unwrap().compile().await.unwrap();
+                std::fs::read_to_string(&assembly).unwrap()
+            }
+        };
+        let function = |text: &str, name: &str| {
+            let start = text.find(&format!("\n{}:", name)).unwrap();
+            text[start..start + text[start..].find(".size").unwrap()].to_string()
+        };
+
+        // On from -O1, frames are addressed from rsp, counting what was pushed
+        let text = compile(&["-O1"], "frames_o1.s").await;
+        let scale = function(&text, "scale");
+        assert!(!scale.contains("rbp"), "{}", scale);
+        assert!(scale.contains("    sub rsp, 24\n    .cfi_def_cfa_offset 32\n"));
+        assert!(scale.contains("    push rax\n    .cfi_adjust_cfa_offset 8\n"));
+        assert!(scale.contains("    add rsp, 24\n    .cfi_def_cfa_offset 8\n    ret\n"));
+        let text = compile(&["-O1", "--fno-omit-frame-pointer"], "frames_kept.s").await;
+        assert!(function(&text, "scale").contains("    push rbp\n"));
+        let text = compile(&["--fomit-frame-pointer"], "frames_o0.s").await;
+        assert!(!function(&text, "depth").contains("rbp"));
+
+        // IR code drops it everywhere on x86, and in leaf functions on arm64
+        let text = compile(&["-O1", "--backend=ir", "-t", "i386"], "frames_i386.s").await;
+        for name in ["scale", "depth", "main"] {
+            assert!(!function(&text, name).contains("ebp"), "{}", name);
+        }
+        let text = compile(&["-O1", "--backend=ir", "-t", "arm64"], "frames_arm64.s").await;
+        let scale = function(&text, "scale");
+        assert!(!scale.contains("x29"), "{}", scale);
+        assert!(scale.contains("    add sp, sp, #16\n    .cfi_def_cfa_offset 0\n    ret\n"));
+        assert!(function(&text, "depth").contains("    mov x29, sp\n"));
+    }
+
+    #[tokio::test]
+    async fn test_stack_protector() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("smash.c");
+        std::fs::write(
+            &source,
+            "int fill(int n) {\n\
+                 char buffer[16];\n\
+                 int i;\n\
+                 for (i = 0; i < n; i = i + 1) {\n\
+                     buffer[i] = 65;\n\
+                 }\n\
+                 return buffer[0] + n;\n\
+             }\n\
+             int sum(int a, int b) { int c = a + b; return c; }\n\
+             int pointed(void) { int x = 1; int *p = &x; return *p; }\n\
+             int main(void) {\n\
+                 if (fill(8) != 73 || sum(2, 3) != 5 || pointed() != 1) {\n\
+                     return 1;\n\
+                 }\n\
+                 return fill(40);\n\
+             }\n",
+        )
+        .unwrap();
+
+        // Overflowing the buffer reaches the canary, and the check aborts
+        // before the function returns through a smashed frame
+        for flags in [
+            &["--fstack-protector"][..],
+            &["--fstack-protector-strong", "-O2"][..],
+            &["--fstack-protector", "--nostdlib"][..],
+        ] {
+            let exe = dir.path().join(format!("smash{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.signal(), Some(6), "{:?}", flags);
+            assert!(String::from_utf8_lossy(&run.stderr).contains("stack smashing detected"));
+        }
+
+        let compile = |flags: &[&str], name: &str| {
+            let assembly = dir.path().join(name);
+            let mut args = vec!["alecc", "-S", "-o", assembly.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            let args = Args::parse_from(args);
+            async move {
+                Compiler::new(args).unwrap().compile().await.unwrap();
+                std::fs::read_to_string(&assembly).unwrap()
+            }
+        };
+        let function = |text: &str, name: &str| {
+            let start = text.find(&format!("\n{}:", name)).unwrap();
+            text[start..start + text[start..].find(".size").unwrap()].to_string()
+        };
+
+        // The basic mode guards character buffers; the strong one also
+        // locals whose address is taken; all of them every function
+        let text = compile(&["--fstack-protector"], "basic.s").await;
+        assert!(function(&text, "fill").contains("    mov rcx, QWORD PTR fs:40\n"));
+        assert!(function(&text, "fill").contains("    call __stack_chk_fail@PLT\n"));
+        assert!(!function(&text, "pointed").contains("__stack_chk_fail"));
+        let text = compile(&["--fstack-protector-strong"], "strong.s").await;
+        assert!(function(&text, "pointed").contains("__stack_chk_fail"));
+        assert!(!function(&text, "sum").contains("__stack_chk_fail"));
+        let text = compile(&["--fstack-protector-all"], "all.s").await;
+        assert!(function(&text, "sum").contains("__stack_chk_fail"));
+        let text = compile(
+            &["--fstack-protector-all", "--fno-stack-protector"],
+            "none.s",
+        )
+        .await;
+        assert!(!text.contains("__stack_chk_fail"));
+
+        // i386 reads glibc's guard from gs, arm64 from __stack_chk_guard
+        let text = compile(&["--fstack-protector", "-t", "i386"], "i386.s").await;
+        assert!(function(&text, "fill").contains("    sub ecx, DWORD PTR gs:20\n"));
+        let text = compile(&["--fstack-protector", "-t", "arm64"], "arm64.s").await;
+        let fill = function(&text, "fill");
+        assert!(fill.contains(":got:__stack_chk_guard"), "{}", fill);
+        assert!(fill.contains("    bl __stack_chk_fail\n"));
+
+        let args = Args::parse_from(["alecc", "--fstack-protector", "--static", "smash.c"]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_break_and_continue() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("loops.c");
+        std::fs::write(
+            &source,
+            "int main(void) {\n\
+                 int total = 0;\n\
+                 int i;\n\
+                 int j;\n\
+                 for (i = 0; i < 10; i = i + 1) {\n\
+                     if (i == 2) continue;\n\
+                     if (i == 7) break;\n\
+                     for (j = 0; j < 10; j = j + 1) {\n\
+                         if (j == 1) break;\n\
+                         total = total + 1;\n\
+                     }\n\
+                     total = total + i;\n\
+                 }\n\
+                 i = 0;\n\
+                 while (1) {\n\
+                     i = i + 1;\n\
+                     if (i < 3) continue;\n\
+                     if (i > 5) break;\n\
+                     total = total + 10;\n\
+                 }\n\
+                 return total;\n\
+             }\n",
+        )
+        .unwrap();
+
+        // Each jump goes to the innermost loop's labels; `continue` in a
+        // for loop still runs the increment
+        for flags in [&[][..], &["-O1"][..], &["-t", "i386", "--nostdlib"][..]] {
+            let exe = dir.path().join(format!("loops{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(6 + 19 + 30), "{:?}", flags);
+        }
+
+        let stray = dir.path().join("stray.c");
+        std::fs::write(&stray, "int main(void) { continue; return 0; }\n").unwrap();
+        let args = Args::parse_from(["alecc", "-S", stray.to_str().unwrap()]);
+        let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+        assert!(error.to_string().contains("not within a loop"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_switch_jump_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("switch.c");
+        std::fs::write(
+            &source,
+            "int dense(int v) {\n\
+                 switch (v) {\n\
+                 case -1: return 5;\n\
+                 case 0: return 10;\n\
+                 case 1:\n\
+                 case 2: return 20;\n\
+                 default: break;\n\
+                 case 4: return 40;\n\
+                 }\n\
+                 return 30;\n\
+             }\n\
+             int sparse(int v) {\n\
+                 switch (v) {\n\
+                 case 1: return 1;\n\
+                 case 1000: return 2;\n\
+                 case 70000: return 3;\n\
+                 case -7: return 4;\n\
+                 }\n\
+                 return 0;\n\
+             }\n\
+             int main(void) {\n\
+                 int i;\n\
+                 int total = 0;\n\
+                 for (i = -3; i < 7; i = i + 1) {\n\
+                     switch (i) {\n\
+                     case 0: continue;\n\
+                     case 5: break;\n\
+                     }\n\
+                     total = total + dense(i);\n\
+                 }\n\
+                 return total + sparse(1000) + sparse(70000) + sparse(-7) + sparse(2);\n\
+             }\n",
+        )
+        .unwrap();
+
+        // -3, -2, 3, 5 and 6 fall to the default and 0 is skipped
+        let expected = 5 * 30 + 5 + 20 + 20 + 40 + 2 + 3 + 4;
+        for flags in [&[][..], &["-O1"][..], &["-t", "i386", "--nostdlib"][..]] {
+            let exe = dir.path().join(format!("switch{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(expected), "{:?}", flags);
+        }
+
+        // Only the dense switch gets a table
+        let asm = dir.path().join("switch.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        let (dense, rest) = asm.split_once("\nsparse:").unwrap();
+        let (sparse, _) = rest.split_once("\nmain:").unwrap();
+        assert!(dense.contains("switch_table"), "{}", asm);
+        assert!(!sparse.contains("switch_table"), "{}", asm);
+        assert!(sparse.contains("cmp rax, 70000"), "{}", asm);
+    }
+
+    #[tokio::test]
+    async fn test_builtin_functions() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("builtins.c");
+
+        // Expanded inline, they need no library, not even on i386
+        for flags in [&[][..], &["-O1"][..], &["-t", "i386", "--nostdlib"][..]] {
+            let exe = dir.path().join(format!("builtins{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(6), "{:?}", flags);
+        }
+
+        // The others call the library function of the same name
+        let library = dir.path().join("library.c");
+        std::fs::write(
+            &library,
+            "int main(void) { return __builtin_strlen(\"four\"); }\n",
+        )
+        .unwrap();
+        let exe = dir.path().join("library");
+        let args = Args::parse_from([
+            "alecc",
+            "-o",
+            exe.to_str().unwrap(),
+            library.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(4));
+
+        // The LLVM backend lowers them to its intrinsics
+        for (source, status) in [(source, 6), (library, 4)] {
+            let ir = dir
+                .path()
+                .join(source.file_name().unwrap())
+                .with_extension("ll");
+            let args = Args::parse_from([
+                "alecc",
+                "--emit=llvm-ir",
+                "-o",
+                ir.to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            Compiler::new(args).unwrap().compile().await.unwrap();
+            let text = std::fs::read_to_string(&ir).unwrap();
+            assert!(!text.contains("@__builtin_"), "{}", text);
+            if which::which("lli").is_ok() {
+                let run = std::process::Command::new("lli")
+                    .arg("-opaque-pointers")
+                    .arg(&ir)
+                    .output()
+                    .unwrap();
+                assert_eq!(run.status.code(), Some(status), "{:?}", run);
+            }
+        }
+        let text = std::fs::read_to_string(dir.path().join("builtins.ll")).unwrap();
+        for intrinsic in [
+            "call void @llvm.memcpy.p0.p0.i64(",
+            "call void @llvm.memset.p0.i64(",
+            "call i32 @llvm.ctpop.i32(",
+            "call i64 @llvm.ctpop.i64(",
+            "call i32 @llvm.bswap.i32(",
+            "call i16 @llvm.bswap.i16(",
+            "call i32 @llvm.ctlz.i32(",
+            "call i64 @llvm.cttz.i64(",
+            "  unreachable\n",
+        ] {
+            assert!(text.contains(intrinsic), "{}", intrinsic);
+        }
+    }
+
+    #[cfg(feature = "cranelift")]
+    #[tokio::test]
+    async fn test_cranelift_builtin_functions() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("builtins");
+        let args = Args::parse_from([
+            "alecc",
+            "--backend=cranelift",
+            "-o",
+            exe.to_str().unwrap(),
+            fixture("builtins.c").to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_atomic_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("atomics.c");
+
+        for flags in [&[][..], &["-O1"][..], &["-t", "i386", "--nostdlib"][..]] {
+            let exe = dir.path().join(format!("atomic{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(13), "{:?}", flags);
+        }
+
+        // arm64 follows the memory order: a relaxed operation has no
+        // acquire or release
+        let asm = dir.path().join("atomic.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-t",
+            "arm64",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        for instruction in ["stlr w0", "ldar w0", "ldaxr w12", "stlxr w14", "ldxr w12"] {
+            assert!(asm.contains(instruction), "{}: {}", instruction, asm);
+        }
+
+        // LLVM gets the same orders on its atomic instructions
+        let ir = dir.path().join("atomics.ll");
+        let args = Args::parse_from([
+            "alecc",
+            "--emit=llvm-ir",
+            "-o",
+            ir.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&ir).unwrap();
+        for instruction in [
+            "store atomic i32 10, ptr @counter release, align 4",
+            "load atomic i32, ptr @counter acquire, align 4",
+            "atomicrmw sub ptr @counter, i32 8 monotonic",
+            "cmpxchg ptr @counter, i32 ",
+            "atomicrmw nand ptr @flags, i8 ",
+            "fence seq_cst",
+        ] {
+            assert!(text.contains(instruction), "{}: {}", instruction, text);
+        }
+        if which::which("lli").is_ok() {
+            let run = std::process::Command::new("lli")
+                .arg("-opaque-pointers")
+                .arg(&ir)
+                .output()
+                .unwrap();
+            assert_eq!(run.status.code(), Some(13), "{:?}", run);
+        }
+    }
+
+    #[cfg(feature = "cranelift")]
+    #[tokio::test]
+    async fn test_cranelift_atomic_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("atomics");
+        let args = Args::parse_from([
+            "alecc",
+            "--backend=cranelift",
+            "-o",
+            exe.to_str().unwrap(),
+            fixture("atomics.c").to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(13));
+    }
+
+    #[tokio::test]
+    async fn test_long_long_i386() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("wide.c");
+        std::fs::write(
+            &source,
+            "long long big = 5000000000LL;\n\
+             unsigned long long huge = 18000000000000000000ULL;\n\
+             long long add(long long a, long long b) { return a + b; }\n\
+             long long scale(int x) { return x * 1000000000LL; }\n\
+             int main(void) {\n\
+                 long long a = 3000000000LL;\n\
+                 long long b = -7;\n\
+                 unsigned int narrow = 4000000000u;\n\
+                 unsigned long long w = narrow;\n\
+                 if (add(a, big) != 8000000000LL) return 1;\n\
+                 if (a * b != -21000000000LL) return 2;\n\
+                 if (a / b != -428571428LL || a % b != 4) return 3;\n\
+                 if (huge / 3 != 6000000000000000000ULL || huge % 1000 != 0) return 4;\n\
+                 if ((a << 4) != 48000000000LL || (big >> 33) != 0 || (b >> 40) != -1) return 5;\n\
+                 if (!(b < a) || a <= b || huge < w) return 6;\n\
+                 a += 5; a -= 10; a++; --a;\n\
+                 if (a != 2999999995LL || -a != -2999999995LL) return 7;\n\
+                 if (scale(-3) != -3000000000LL) return 8;\n\
+                 w *= narrow;\n\
+                 if (w != 16000000000000000000ULL) return 9;\n\
+                 if ((int)(big - 4294967296LL) != 705032704) return 10;\n\
+                 return 42;\n\
+             }\n",
+        )
+        .unwrap();
+
+        // The same source on both x86 targets; i386 divides with the
+        // __divdi3 family, which --nostdlib leaves to the compiler to supply
+        for flags in [
+            &[][..],
+            &["-t", "i386", "--nostdlib"][..],
+            &["-t", "i386", "--nostdlib", "-O1"][..],
+        ] {
+            let exe = dir.path().join(format!("wide{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(42), "{:?}", flags);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verbose_asm() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("verbose.c");
+        std::fs::write(
+            &source,
+            "int square(int x) {\n\
+             \x20   int result = x * x;\n\
+             \x20   return result;\n\
+             }\n\
+             int main(void) {\n\
+             \x20   return square(3);\n\
+             }\n",
+        )
+        .unwrap();
+        let file = source.to_str().unwrap();
+
+        // Each statement is preceded by its line, as GCC quotes it, and the
+        // slots are where the code addresses the variables: from the stack
+        // pointer once the frame pointer is omitted
+        for (flags, x, result) in [
+            (&[][..], "[rbp - 8]", "[rbp - 16]"),
+            (&["--fomit-frame-pointer"][..], "[rsp + 8]", "[rsp + 0]"),
+            (&["--masm=att"][..], "-8(%rbp)", "-16(%rbp)"),
+        ] {
+            let asm = dir.path().join("verbose.s");
+            let mut args = vec!["alecc", "-S", "--fverbose-asm", "-o", asm.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(file);
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let asm = std::fs::read_to_string(&asm).unwrap();
+            assert!(
+                asm.contains(&format!("\n# {}:2:     int result = x * x;\n", file)),
+                "{}",
+                asm
+            );
+            assert!(asm.contains(&format!("\n# {}:6:     return square(3);\n", file)));
+            assert!(
+                asm.contains(&format!("\n#   x: {}, 4 bytes\n", x)),
+                "{}",
+                asm
+            );
+            assert!(asm.contains(&format!("\n#   result: {}, 4 bytes\n", result)));
+            assert_eq!(asm.matches("stack slots").count(), 1, "{:?}", flags);
+        }
+
+        // The comments assemble away
+        let exe = dir.path().join("verbose");
+        let args = Args::parse_from(["alecc", "--fverbose-asm", "-o", exe.to_str().unwrap(), file]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let run = std::process::Command::new(&exe).output().unwrap();
+        assert_eq!(run.status.code(), Some(9));
+    }
+
+    #[tokio::test]
+    async fn test_function_and_data_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("sections.c");
+        std::fs::write(
+            &source,
+            "int counter = 3;\n\
+             int unused_value = 5;\n\
+             int zeroed;\n\
+             int unused_zeroed[32];\n\
+             __attribute__((section(\".keep\"))) int tagged = 7;\n\
+             __attribute__((section(\".text.hot\"))) int hot(int x) { return x + tagged; }\n\
+             int unused_function(int x) { return x * unused_value + unused_zeroed[1]; }\n\
+             int main(void) { return hot(counter) + zeroed; }\n",
+        )
+        .unwrap();
+        let contains = |haystack: &[u8], needle: &str| {
+            haystack
+                .windows(needle.len())
+                .any(|window| window == needle.as_bytes())
+        };
+
+        let asm = dir.path().join("sections.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "--ffunction-sections",
+            "--fdata-sections",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        for directive in [
+            ".pushsection .data.counter,\"aw\",@progbits",
+            ".pushsection .bss.zeroed,\"aw\",@nobits",
+            ".pushsection .keep,\"aw\",@progbits",
+            ".pushsection .text.main,\"ax\",@progbits",
+            // A section attribute wins
+            ".pushsection .text.hot,\"ax\",@progbits",
+        ] {
+            assert!(asm.contains(directive), "{}: {}", directive, asm);
+        }
+        assert!(!asm.contains(".text.hot.hot"));
+
+        // With a section each, the linker drops what nothing refers to
+        for (flags, kept) in [
+            (&["--ffunction-sections", "--fdata-sections"][..], false),
+            (&[][..], true),
+        ] {
+            let exe = dir.path().join(format!("sections{}", flags.len()));
+            let mut args = vec!["alecc", "-Wl,--gc-sections", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(10), "{:?}", flags);
+            let bytes = std::fs::read(&exe).unwrap();
+            for symbol in ["unused_function", "unused_value", "unused_zeroed"] {
+                assert_eq!(contains(&bytes, symbol), kept, "{} {:?}", symbol, flags);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_constructor_priorities() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("constructor_priorities.c");
+        let asm = dir.path().join("priorities.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            asm.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let asm = std::fs::read_to_string(&asm).unwrap();
+        for directive in [
+            ".pushsection .init_array,\"aw\",@init_array",
+            ".pushsection .init_array.00101,\"aw\",@init_array",
+            ".pushsection .fini_array.00300,\"aw\",@fini_array",
+        ] {
+            assert!(asm.contains(directive), "{}: {}", directive, asm);
+        }
+
+        // Lower priorities construct first and destruct last, the rest after them
+        for (target, nostdlib) in [("amd64", false), ("amd64", true), ("i386", true)] {
+            let exe = dir
+                .path()
+                .join(format!("priorities-{}-{}", target, nostdlib));
+            let mut args = vec!["alecc", "-t", target, "-o", exe.to_str().unwrap()];
+            if nostdlib {
+                args.push("--nostdlib");
+            }
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.stdout, b"abc main xyz", "{} {}", target, nostdlib);
+        }
+
+        let mut parser = Parser::new(
+            Lexer::new("__attribute__((constructor(50))) void f() {}".to_string())
+                .tokenize()
+                .unwrap(),
+        );
+        parser.parse().unwrap();
+        assert_eq!(
+            parser.warnings()[0].message,
+            "constructor priorities from 0 to 100 are reserved for the implementation [-Wprio-ctor-dtor]"
+        );
+        let mut parser = Parser::new(
+            Lexer::new("__attribute__((destructor(70000))) void f() {}".to_string())
+                .tokenize()
+                .unwrap(),
+        );
+        assert!(parser.parse().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_c_runtime_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("crt.c");
+        std::fs::write(
+            &source,
+            "int printf(const char *format, ...);\n\
+             int atexit(void (*function)(void));\n\
+             char *getenv(const char *name);\n\
+             void bye(void) { printf(\"atexit\\n\"); }\n\
+             __attribute__((constructor)) void hello(void) { printf(\"constructor\\n\"); }\n\
+             int main(int argc, char **argv) {\n\
+                 atexit(bye);\n\
+                 printf(\"%s %s\\n\", argv[1], getenv(\"ALECC_CRT\"));\n\
+                 return argc;\n\
+             }\n",
+        )
+        .unwrap();
+
+        // libc's startup files run main, flush stdio and call the atexit handlers
+        for flags in [&[][..], &["--no-pie"][..], &["--fuse-crt"][..]] {
+            let exe = dir.path().join(format!("crt{}", flags.len()));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe)
+                .arg("argument")
+                .env("ALECC_CRT", "environment")
+                .output()
+                .unwrap();
+            assert_eq!(
+                String::from_utf8_lossy(&run.stdout),
+                "constructor\nargument environment\natexit\n",
+                "{:?}",
+                flags
+            );
+            assert_eq!(run.status.code(), Some(2), "{:?}", flags);
+        }
+
+        // alecc's _start stays for --nostdlib and when asked for
+        let asm = |flags: &[&str]| {
+            let assembly = dir.path().join("crt.s");
+            let mut args = vec!["alecc", "-S", "-o", assembly.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            let args = Args::parse_from(args);
+            async move {
+                Compiler::new(args).unwrap().compile().await.unwrap();
+                std::fs::read_to_string(&assembly).unwrap()
+            }
+        };
+        assert!(!asm(&[]).await.contains("_start:"));
+        assert!(asm(&["--nostdlib"]).await.contains("_start:"));
+        assert!(asm(&["--fno-use-crt"]).await.contains("_start:"));
+        // The last of the pair wins
+        assert!(!asm(&["--fno-use-crt", "--fuse-crt"])
+            .await
+            .contains("_start:"));
+
+        let args = Args::parse_from(["alecc", "--fuse-crt", "--nostdlib", "x.c"]);
+        assert!(Compiler::new(args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_computed_goto() {
+        use alecc::error::AleccError;
+        use alecc::sema::Sema;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("computed_goto.c");
+        for flags in [
+            &["-t", "amd64"][..],
+            &["-t", "amd64", "-O2"][..],
+            &["-t", "amd64", "--backend=ir"][..],
+            &["-t", "amd64", "--masm=att"][..],
+            &["-t", "i386", "--nostdlib"][..],
+            &["-t", "i386", "--nostdlib", "-O1"][..],
+        ] {
+            let exe = dir.path().join(format!("computed_goto{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(77), "{:?}", flags);
+        }
+
+        // LLVM gets the addresses of the blocks and every one an indirectbr may reach
+        let ir = dir.path().join("computed_goto.ll");
+        let args = Args::parse_from([
+            "alecc",
+            "--emit=llvm-ir",
+            "-o",
+            ir.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let ir = std::fs::read_to_string(&ir).unwrap();
+        assert!(ir.contains("store ptr blockaddress(@run, %label.halt), ptr"));
+        assert!(ir.contains(
+            ", [label %label.halt, label %label.inc, label %label.dbl, label %label.dec]"
+        ));
+        assert!(ir.contains(", [label %label.done]"));
+
+        let analyze = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let mut program = Parser::new(tokens).parse().unwrap();
+            Sema::new().analyze(&mut program)
+        };
+        for (source, message) in [
+            (
+                "int main(void) { void *p = &&missing; goto *p; }",
+                "label 'missing' used but not defined",
+            ),
+            (
+                "int main(void) { again: again: return 0; }",
+                "duplicate label 'again'",
+            ),
+        ] {
+            match analyze(source) {
+                Err(AleccError::SemanticError { message: m, .. }) => assert_eq!(m, message),
+                other => panic!("{}: {:?}", source, other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alloca_and_variable_length_arrays() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("alloca.c");
+        for flags in [
+            &["-t", "amd64"][..],
+            &["-t", "amd64", "-O2"][..],
+            &["-t", "amd64", "--masm=att"][..],
+            &["-t", "amd64", "--fomit-frame-pointer"][..],
+            &["-t", "amd64", "--fstack-protector-strong"][..],
+            &["-t", "i386", "--nostdlib"][..],
+            &["-t", "i386", "--nostdlib", "-O1"][..],
+        ] {
+            let exe = dir.path().join(format!("alloca{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(28), "{:?}", flags);
+        }
+
+        // LLVM allocates the blocks itself and frees each block's arrays on the way out
+        let ir = dir.path().join("alloca.ll");
+        let args = Args::parse_from([
+            "alecc",
+            "--emit=llvm-ir",
+            "-o",
+            ir.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let ir = std::fs::read_to_string(&ir).unwrap();
+        assert!(ir.contains("alloca i8, i64 "));
+        assert!(ir.contains("call ptr @llvm.stacksave()"));
+        assert!(ir.contains("call void @llvm.stackrestore(ptr "));
+
+        let error = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap_err().to_string()
+        };
+        assert!(error("int f(int n) { int a[n] = {0}; return 0; }\n")
+            .contains("variable length array 'a' can't be initialized or aligned"));
+        assert!(error("int f(int n) { int a[2][n]; return 0; }\n")
+            .contains("only the first size of a variable length array can vary"));
+        assert!(error("int n = 2;\nint a[n];\n")
+            .contains("only supported as locals declared in a block"));
+    }
+
+    #[tokio::test]
+    async fn test_global_address_initializers() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture("global_addresses.c");
+        for flags in [
+            &["-t", "amd64"][..],
+            &["-t", "amd64", "-O2"][..],
+            &["-t", "amd64", "--static"][..],
+            &["-t", "i386", "--nostdlib", "--msse2"][..],
+        ] {
+            let exe = dir
+                .path()
+                .join(format!("global_addresses{}", flags.join("")));
+            let mut args = vec!["alecc", "-o", exe.to_str().unwrap()];
+            args.extend_from_slice(flags);
+            args.push(source.to_str().unwrap());
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            assert_eq!(run.status.code(), Some(49), "{:?}", flags);
+        }
+
+        let assembly = dir.path().join("global_addresses.s");
+        let args = Args::parse_from([
+            "alecc",
+            "-S",
+            "-o",
+            assembly.to_str().unwrap(),
+            source.to_str().unwrap(),
+        ]);
+        Compiler::new(args).unwrap().compile().await.unwrap();
+        let text = std::fs::read_to_string(&assembly).unwrap();
+        assert!(text.contains("pointer:\n    .quad value\n"));
+        assert!(text.contains("function:\n    .quad nine\n"));
+        assert!(text.contains("element:\n    .quad table+4\n"));
+        assert!(text.contains("member:\n    .quad origin+4\n"));
+        assert!(text.contains("letters:\n    .ascii \"abc\"\n    .zero 5\n"));
+
+        // What doesn't fold to a constant or an address is an error, not a zero
+        for (name, global) in [
+            ("sum", "int value = 1;\nint sum = value + 1;\n"),
+            ("call", "int nine(void);\nint call = nine();\n"),
+            ("scaled", "int value;\nint *scaled = &value + value;\n"),
+            ("narrow", "int value;\nchar narrow = (char)&value;\n"),
+        ] {
+            let source = dir.path().join(format!("{}.c", name));
+            std::fs::write(
+                &source,
+                format!("{}int main(void) {{ return 0; }}\n", global),
+            )
+            .unwrap();
+            let args = Args::parse_from([
+                "alecc",
+                "-S",
+                "-o",
+                dir.path().join(format!("{}.s", name)).to_str().unwrap(),
+                source.to_str().unwrap(),
+            ]);
+            let error = Compiler::new(args).unwrap().compile().await.unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .contains("initializer element is not constant"),
+                "{}: {}",
+                name,
+                error
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_whole_program_static_variables() {
+        use alecc::merge::merge_programs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.c");
+        let second = dir.path().join("second.c");
+        std::fs::write(
+            &first,
+            "static int counter = 10;\n\
+             static int shared;\n\
+             static int bump(void) { return ++shared; }\n\
+             int count(void) { bump(); return counter + shared; }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &second,
+            "static int counter = 3;\n\
+             static int shared;\n\
+             int count(void);\n\
+             int main(void) { shared = 100; return count() + counter + shared; }\n",
+        )
+        .unwrap();
+
+        // Each unit keeps its own `counter` and `shared`, as separate objects would
+        for flags in [&[][..], &["-O2"][..], &["--backend=ir"][..]] {
+            let exe = dir.path().join(format!("statics{}", flags.join("")));
+            let mut args = vec!["alecc", "--nostdlib", "--fwhole-program"];
+            args.extend_from_slice(flags);
+            args.extend(["-o", exe.to_str().unwrap()]);
+            args.extend([first.to_str().unwrap(), second.to_str().unwrap()]);
+            Compiler::new(Args::parse_from(args))
+                .unwrap()
+                .compile()
+                .await
+                .unwrap();
+            let run = std::process::Command::new(&exe).output().unwrap();
+            // 10 + 1 + 3 + 100
+            assert_eq!(run.status.code(), Some(114), "{:?}", flags);
+        }
+
+        let parse = |path: &std::path::Path| {
+            let source = std::fs::read_to_string(path).unwrap();
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap()
+        };
+        let program = merge_programs(vec![
+            ("first.c".to_string(), parse(&first)),
+            ("second.c".to_string(), parse(&second)),
+        ])
+        .unwrap()
+        .program;
+        let mut names: Vec<&str> = program
+            .global_variables
+            .iter()
+            .map(|global| global.name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["counter.0", "counter.1", "shared.0", "shared.1"]);
+    }
+
+    #[cfg(all(feature = "daemon", unix))]
+    #[tokio::test]
+    async fn test_daemon_socket_path() {
+        use tokio::net::UnixStream;
+
+        let dir = tempfile::tempdir().unwrap();
+        // A file that isn't a socket is never replaced by one
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "keep me").unwrap();
+        let error = alecc::daemon::serve(&file).await.unwrap_err();
+        assert!(error.to_string().contains("it is not a socket"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+
+        // A stale socket is replaced, but not one a daemon still answers on
+        let socket = dir.path().join("alecc.sock");
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        let server = tokio::spawn({
+            let socket = socket.clone();
+            async move { alecc::daemon::serve(&socket).await }
+        });
+        while UnixStream::connect(&socket).await.is_err() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let error = alecc::daemon::serve(&socket).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("another daemon is serving on it"));
+        server.abort();
+    }
+
+    #[cfg(all(feature = "daemon", unix))]
+    #[tokio::test]
+    async fn test_daemon_tcp_jobs() {
+        use alecc::daemon::{compile_remote, Endpoint, Request, Source};
+
+        assert_eq!(
+            Endpoint::parse(":7000"),
+            Endpoint::Tcp("127.0.0.1:7000".to_string())
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("main.c");
+        std::fs::write(&source, "int main(void) { return 0; }\n").unwrap();
+        let object = dir.path().join("main.o");
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let address = format!(":{}", port);
+        let server = tokio::spawn({
+            let address = PathBuf::from(&address);
+            async move { alecc::daemon::serve(&address).await }
+        });
+        while tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_err()
+        {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let job = |args: &[&str], text: Option<&str>| Request {
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            cwd: None,
+            source: text.map(|text| Source {
+                name: "main.c".to_string(),
+                text: text.to_string(),
+            }),
+        };
+        let main = "int main(void) { return 0; }\n";
+        let response = compile_remote(
+            &address,
+            &job(&["--target=amd64", "-O2", "-Wall"], Some(main)),
+        )
+        .await
+        .unwrap();
+        assert!(response.success, "{:?}", response.error);
+
+        // Anyone can connect, so nothing may reach the daemon's files or tools
+        let refusals = [
+            (
+                job(
+                    &["-c", "-o", object.to_str().unwrap(), source.to_str().unwrap()],
+                    None,
+                ),
+                "a job over TCP must carry its source",
+            ),
+            (
+                job(&["--toolchain", "/tmp"], Some(main)),
+                "'--toolchain' is not accepted in a job over TCP",
+            ),
+            (
+                job(&["-I/etc"], Some(main)),
+                "'-I/etc' is not accepted in a job over TCP",
+            ),
+            (
+                job(&["-Wl,-T,/etc/passwd"], Some(main)),
+                "'-Wl,-T,/etc/passwd' is not accepted in a job over TCP",
+            ),
+            (
+                job(&["--symbol-prefix=x\n.incbin \"/etc/passwd\""], Some(main)),
+                "is not accepted in a job over TCP",
+            ),
+            (
+                job(
+                    &[],
+                    Some("__attribute__((section(\"x\\n.incbin \\\"/etc/passwd\\\"\"))) int x = 1;\n"),
+                ),
+                "invalid section name",
+            ),
+        ];
+        for (request, refusal) in refusals {
+            let response = compile_remote(&address, &request).await.unwrap();
+            assert!(!response.success);
+            let error = response.error.unwrap();
+            assert!(error.contains(refusal), "{}", error);
+        }
+        assert!(!object.exists());
+
+        let request = Request {
+            cwd: Some(PathBuf::from("/")),
+            ..job(&[], Some(main))
+        };
+        let response = compile_remote(&address, &request).await.unwrap();
+        assert_eq!(
+            response.error.as_deref(),
+            Some("a job over TCP can't name a working directory")
+        );
+
+        server.abort();
+    }
 }